@@ -1,40 +1,42 @@
 use eframe::egui;
 use crate::style::ColorPalette;
 use super::style::{self, ThemeMode};
-use super::modules::{EditorModule, text_edit::TextEditor, image_converter::ImageConverter, image_edit::ImageEditor, json_edit::JsonEditor, data_converter::DataConverter, archive_converter::ArchiveConverter};
+use super::modules::{EditorModule, EditorSettings, SaveState, MenuContribution, ViewState, text_edit::{TextEditor, TextEditorSettings, PasteCleanup}, image_converter::ImageConverter, image_edit::{ImageEditor, ImageEditorSettings, ImageClip, CanvasSurround, CursorColor, CursorOutline, RepeatableFilter}, json_edit::JsonEditor, data_converter::DataConverter, archive_converter::ArchiveConverter};
 use crate::modules::image_editor::ie_cache;
+use crate::modules::helpers::{config_file, file_info, share};
 use crate::modules::doc_edit::DocumentEditor;
-use std::path::PathBuf;
+use crate::modules::load_failed::LoadFailed;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
 use crate::registry::{self, CreateModule};
-use std::fs;
+use crate::error::EditorResult;
+use crate::updater::{self, UpdateCheck, UpdateStatus};
+use crate::taskbar::TaskbarProgress;
+use crate::tour::{Tour, TourOutcome, APP_TOUR};
+use crate::templates::{TemplateGallery, GalleryOutcome, SaveTemplateDialog, TemplateContent};
+use crate::export_all::{ExportAllDialog, ExportDocument, ExportSource};
+use crate::clipboard::{ClipboardHistory, ClipOutcome};
+use crate::profile::{self, ProfileImport};
+use crate::tasks;
+use crate::diagnostics;
+use crate::i18n::{self, tr};
+use crate::modules::{Shortcut, KeyBinding, shortcut, gesture, key, ctrl, shift, ctrl_shift, pressed_shortcuts};
 
 #[derive(Serialize, Deserialize, Clone)]
 struct RecentFile { path: PathBuf, timestamp: i64 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Default)]
 struct RecentFiles { files: Vec<RecentFile> }
 
 impl RecentFiles {
-    fn new() -> Self { Self { files: Vec::new() } }
-
-    fn load() -> Self {
-        let p = Self::get_config_path();
-        if let Ok(s) = fs::read_to_string(&p) { if let Ok(r) = serde_json::from_str(&s) { return r; } }
-        Self::new()
-    }
+    fn load() -> Self { config_file::load_json(&Self::get_config_path(), "Recent files") }
 
-    fn save(&self) {
-        let p = Self::get_config_path();
-        if let Some(parent) = p.parent() { let _ = fs::create_dir_all(parent); }
-        if let Ok(json) = serde_json::to_string_pretty(self) { let _ = fs::write(p, json); }
-    }
+    fn save(&self) { let _ = config_file::save_json(&Self::get_config_path(), self); }
 
-    fn get_config_path() -> PathBuf {
-        let mut p = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
-        p.push("universal_editor"); p.push("recent_files.json"); p
-    }
+    fn get_config_path() -> PathBuf { config_file::config_path("recent_files.json") }
 
     fn add_file(&mut self, path: PathBuf) {
         self.files.retain(|f| f.path != path);
@@ -51,11 +53,33 @@ impl RecentFiles {
     }
 }
 
+const CLOSED_HISTORY: usize = 10;
+
+/// What the app's own keys do, before any editor sees them.
+#[derive(Clone, Copy, PartialEq)]
+enum AppKey { Sidebar, FocusMode, Reopen, Shortcuts, FocusRegion }
+
+const APP_SHORTCUTS: &[Shortcut<AppKey>] = &[
+    shortcut(&[ctrl(egui::Key::Backslash)], AppKey::Sidebar, "Toggle sidebar"),
+    shortcut(&[key(egui::Key::F11)], AppKey::FocusMode, "Toggle focus mode"),
+    shortcut(&[ctrl_shift(egui::Key::T)], AppKey::Reopen, "Reopen the last closed document"),
+    shortcut(&[key(egui::Key::Questionmark)], AppKey::Shortcuts, "Show or hide this list"),
+    gesture("Tab / Shift+Tab", "Move keyboard focus between controls"),
+    shortcut(&[key(egui::Key::F6), shift(egui::Key::F6)], AppKey::FocusRegion, "Move focus between the canvas, options bar, panels and menu"),
+];
+
+#[derive(Clone)]
+struct ClosedDocument { path: PathBuf, create: CreateModule, title: String, view: Option<ViewState> }
+
 #[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
 pub enum ThemePreference { System, Light, Dark }
 
 fn default_font_name() -> String { "Ubuntu".to_string() }
 fn default_font_size() -> f32 { 14.0 }
+fn default_true() -> bool { true }
+fn default_large_file_mb() -> u32 { 200 }
+fn default_max_canvas_mp() -> u32 { 256 }
+fn default_language() -> String { "en".to_string() }
 
 #[derive(Serialize, Deserialize)]
 struct AppSettings {
@@ -65,6 +89,30 @@ struct AppSettings {
     #[serde(default = "default_font_name")] default_font: String,
     #[serde(default = "default_font_size")] default_font_size: f32,
     show_file_info_je: bool,
+    #[serde(default)] check_for_updates: bool,
+    #[serde(default)] update_check_asked: bool,
+    #[serde(default)] skipped_update_version: Option<String>,
+    #[serde(default)] high_visibility_focus: bool,
+    #[serde(default)] tours_seen: Vec<String>,
+    #[serde(default)] paste_cleanup: PasteCleanup,
+    #[serde(default)] use_gpu: bool,
+    #[serde(default)] cursor_outline: CursorOutline,
+    #[serde(default = "default_large_file_mb")] large_file_mb: u32,
+    #[serde(default = "default_true")] word_completion: bool,
+    #[serde(default = "default_true")] color_swatches: bool,
+    #[serde(default)] skip_comment_colors: bool,
+    #[serde(default = "default_true")] highlight_current_line: bool,
+    #[serde(default)] typewriter_scrolling: bool,
+    #[serde(default)] persistent_undo: bool,
+    #[serde(default)] persist_clipboard: bool,
+    #[serde(default)] keep_backup: bool,
+    #[serde(default = "default_language")] language: String,
+    #[serde(default = "default_max_canvas_mp")] max_canvas_mp: u32,
+}
+
+impl AppSettings {
+    /// GitHub is only contacted at startup once the user has said yes to the first-run question.
+    fn checks_for_updates_at_startup(&self) -> bool { self.update_check_asked && self.check_for_updates }
 }
 
 impl Default for AppSettings {
@@ -74,27 +122,33 @@ impl Default for AppSettings {
             show_toolbar_te: true, show_file_info_te: true,
             default_font: default_font_name(), default_font_size: default_font_size(),
             show_file_info_je: true,
+            check_for_updates: false, update_check_asked: false, skipped_update_version: None,
+            high_visibility_focus: false,
+            tours_seen: Vec::new(),
+            paste_cleanup: PasteCleanup::default(),
+            use_gpu: false,
+            cursor_outline: CursorOutline::default(),
+            large_file_mb: default_large_file_mb(),
+            max_canvas_mp: default_max_canvas_mp(),
+            word_completion: true,
+            color_swatches: true,
+            skip_comment_colors: false,
+            highlight_current_line: true,
+            typewriter_scrolling: false,
+            persistent_undo: false,
+            persist_clipboard: false,
+            keep_backup: false,
+            language: default_language(),
         }
     }
 }
 
 impl AppSettings {
-    fn load() -> Self {
-        let p = Self::get_config_path();
-        if let Ok(s) = fs::read_to_string(&p) { if let Ok(settings) = serde_json::from_str(&s) { return settings; } }
-        Self::default()
-    }
+    fn load() -> Self { config_file::load_json(&Self::get_config_path(), "Settings") }
 
-    fn save(&self) {
-        let p = Self::get_config_path();
-        if let Some(parent) = p.parent() { let _ = fs::create_dir_all(parent); }
-        if let Ok(json) = serde_json::to_string_pretty(self) { let _ = fs::write(p, json); }
-    }
+    fn save(&self) { let _ = config_file::save_json(&Self::get_config_path(), self); }
 
-    fn get_config_path() -> PathBuf {
-        let mut p = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
-        p.push("universal_editor"); p.push("app_settings.json"); p
-    }
+    fn get_config_path() -> PathBuf { config_file::config_path("app_settings.json") }
 }
 
 enum PendingAction { OpenFile(PathBuf), NewFile, SwitchModule(Box<dyn EditorModule>), GoHome, Exit }
@@ -118,6 +172,8 @@ pub struct UniversalEditor {
     screens_expanded: bool,
     converters_expanded: bool,
     recent_files_expanded: bool,
+    window_title: String,
+    taskbar: TaskbarProgress,
     show_toolbar_te: bool,
     show_file_info_te: bool,
     show_file_info_je: bool,
@@ -138,7 +194,54 @@ pub struct UniversalEditor {
     rename_target: Option<PathBuf>,
     rename_buffer: String,
     cache_entries: Option<Vec<ie_cache::CacheEntry>>,
+    remember_tools: bool,
     open_cache_path: Option<PathBuf>,
+    notification: Option<(String, f64)>,
+    check_for_updates: bool,
+    update_check_asked: bool,
+    skipped_update_version: Option<String>,
+    update_check: Option<UpdateCheck>,
+    update_dialog: Option<UpdateStatus>,
+    high_visibility_focus: bool,
+    paste_cleanup: PasteCleanup,
+    use_gpu: bool,
+    cursor_outline: CursorOutline,
+    large_file_mb: u32,
+    max_canvas_mp: u32,
+    word_completion: bool,
+    color_swatches: bool,
+    skip_comment_colors: bool,
+    highlight_current_line: bool,
+    typewriter_scrolling: bool,
+    persistent_undo: bool,
+    persist_clipboard: bool,
+    keep_backup: bool,
+    /// Session-only, picked from the image editor's View menu.
+    canvas_surround: CanvasSurround,
+    last_filter: Option<RepeatableFilter>,
+    image_clip: Option<Arc<ImageClip>>,
+    /// The unsaved-changes dialog saved into a file that needs replacing; its pending action waits for the answer.
+    awaiting_save: bool,
+    open_files: file_info::OpenFiles,
+    language: String,
+    clipboard: ClipboardHistory,
+    show_clipboard: bool,
+    paste_target: Option<egui::Id>,
+    pending_paste: Option<String>,
+    /// Menu contributions of the active module, refetched only while the menu bar can be interacted with.
+    menu_cache: Option<Rc<MenuContribution>>,
+    show_shortcuts: bool,
+    shortcut_query: String,
+    focus_mode: bool,
+    tour: Option<Tour>,
+    tours_seen: Vec<String>,
+    closed_documents: Vec<ClosedDocument>,
+    template_gallery: Option<TemplateGallery>,
+    save_template: Option<SaveTemplateDialog>,
+    profile_import: Option<ProfileImport>,
+    export_all: Option<ExportAllDialog>,
+    renderer: Option<String>,
+    problem_report: Option<String>,
 }
 
 fn open_file_location(path: &PathBuf) {
@@ -155,12 +258,15 @@ fn open_file_location(path: &PathBuf) {
 impl UniversalEditor {
     pub fn new(cc: &eframe::CreationContext<'_>, startup_file: Option<PathBuf>) -> Self {
         let settings = AppSettings::load();
+        i18n::set_locale(&settings.language);
         let system_theme = match cc.egui_ctx.theme() { egui::Theme::Dark => ThemeMode::Dark, egui::Theme::Light => ThemeMode::Light };
         let initial_theme = match settings.theme_preference {
             ThemePreference::System => system_theme, ThemePreference::Light => ThemeMode::Light, ThemePreference::Dark => ThemeMode::Dark,
         };
         style::apply_theme(&cc.egui_ctx, initial_theme);
+        style::apply_focus_outline(&cc.egui_ctx, settings.high_visibility_focus);
         style::register_fonts(&cc.egui_ctx);
+        tasks::init(&cc.egui_ctx);
 
         let (tx, rx) = sync_channel(20);
         let (replace_tx, replace_rx) = sync_channel::<(PathBuf, PathBuf)>(20);
@@ -211,34 +317,12 @@ impl UniversalEditor {
             v.tag = if i == 0 { "Current" } else if i == total - 1 { "Initial Release" } else { "Update" }.to_string();
         }
 
-        let mut recent_files = RecentFiles::load();
-        let active_module = startup_file.map(|path| {
-            recent_files.add_file(path.clone());
-            let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
-            let create = registry::screen_for_extension(ext).map(|s| s.create).unwrap_or(CreateModule::TextEditor);
-            let m: Box<dyn EditorModule> = match create {
-                CreateModule::TextEditor => {
-                    let mut e = TextEditor::load(path);
-                    e.set_default_font(egui::FontFamily::Name(settings.default_font.clone().into()), settings.default_font_size);
-                    e.set_path_replace_tx(replace_tx.clone());
-                    Box::new(e)
-                }
-                CreateModule::ImageEditor => {
-                    let mut e = ImageEditor::load(path);
-                    let tx = tx.clone();
-                    e.set_file_callback(Box::new(move |p: PathBuf| { let _ = tx.send(p); }));
-                    Box::new(e)
-                }
-                CreateModule::JsonEditor => Box::new(JsonEditor::load(path)),
-                _ => Box::new(TextEditor::load(path)),
-            };
-            m
-        });
-
-        Self {
-            active_module, sidebar_open: true, theme_mode: initial_theme,
+        let update_check = settings.checks_for_updates_at_startup().then(|| UpdateCheck::spawn(&cc.egui_ctx, false));
+        let recent_files = RecentFiles::load();
+        let mut app = Self {
+            active_module: None, sidebar_open: true, theme_mode: initial_theme,
             theme_preference: settings.theme_preference, recent_files,
-            screens_expanded: false, converters_expanded: false, recent_files_expanded: false,
+            screens_expanded: false, converters_expanded: false, recent_files_expanded: false, window_title: String::new(), taskbar: TaskbarProgress::default(),
             show_toolbar_te: settings.show_toolbar_te, show_file_info_te: settings.show_file_info_te,
             show_file_info_je: settings.show_file_info_je,
             default_font: settings.default_font, default_font_size: settings.default_font_size,
@@ -247,8 +331,26 @@ impl UniversalEditor {
             recent_file_tx: tx, recent_file_rx: rx,
             path_replace_tx: replace_tx, path_replace_rx: replace_rx,
             patch_notes, patch_notes_page: 0, rename_target: None, rename_buffer: String::new(),
-            cache_entries: None, open_cache_path: None,
+            cache_entries: None, remember_tools: ImageEditor::remember_tool_settings(), open_cache_path: None, notification: None,
+            check_for_updates: settings.check_for_updates, update_check_asked: settings.update_check_asked, skipped_update_version: settings.skipped_update_version,
+            update_check, update_dialog: None,
+            high_visibility_focus: settings.high_visibility_focus, show_shortcuts: false, shortcut_query: String::new(), focus_mode: false,
+            tour: if settings.tours_seen.iter().any(|t| t == "app") { None } else { Tour::new(vec!["app"], APP_TOUR.to_vec()) },
+            tours_seen: settings.tours_seen.clone(), paste_cleanup: settings.paste_cleanup, use_gpu: settings.use_gpu, cursor_outline: settings.cursor_outline, large_file_mb: settings.large_file_mb, max_canvas_mp: settings.max_canvas_mp, word_completion: settings.word_completion,
+            color_swatches: settings.color_swatches, skip_comment_colors: settings.skip_comment_colors,
+            highlight_current_line: settings.highlight_current_line, typewriter_scrolling: settings.typewriter_scrolling, persistent_undo: settings.persistent_undo,
+            persist_clipboard: settings.persist_clipboard, keep_backup: settings.keep_backup, clipboard: ClipboardHistory::load(settings.persist_clipboard),
+            show_clipboard: false, paste_target: None, pending_paste: None, menu_cache: None, language: settings.language,
+            closed_documents: Vec::new(), template_gallery: None, save_template: None, profile_import: None, export_all: None,
+            renderer: None, problem_report: None, canvas_surround: CanvasSurround::default(), last_filter: None, image_clip: None,
+            awaiting_save: false, open_files: file_info::OpenFiles::new(),
+        };
+        if let Some(path) = startup_file {
+            app.recent_files.add_file(path.clone());
+            let m = app.module_from_path(path);
+            app.set_active(Some(m));
         }
+        app
     }
 
     fn is_in_text_editor(&self) -> bool {
@@ -269,6 +371,37 @@ impl UniversalEditor {
         false
     }
 
+    fn editor_settings(&self) -> EditorSettings {
+        EditorSettings {
+            text: TextEditorSettings {
+                large_file_mb: self.large_file_mb, word_completion: self.word_completion, highlight_current_line: self.highlight_current_line,
+                typewriter_scrolling: self.typewriter_scrolling, persistent_undo: self.persistent_undo,
+                color_swatches: self.color_swatches, skip_comment_colors: self.skip_comment_colors, paste_cleanup: self.paste_cleanup, keep_backup: self.keep_backup,
+            },
+            image: ImageEditorSettings {
+                max_canvas_mp: self.max_canvas_mp, cursor_outline: self.cursor_outline, canvas_surround: self.canvas_surround, keep_backup: self.keep_backup, use_gpu: self.use_gpu, last_filter: self.last_filter,
+                image_clip: self.image_clip.clone(), remember_tools: self.remember_tools, reset_tools: false,
+            },
+        }
+    }
+
+    /// Hands the current preferences to the active editor; called whenever either of them changes.
+    fn apply_editor_settings(&mut self) {
+        let settings = self.editor_settings();
+        if let Some(m) = &mut self.active_module { m.apply_settings(&settings); }
+    }
+
+    fn keep_editor_settings(&mut self) {
+        let mut settings = self.editor_settings();
+        if !self.active_module.as_mut().is_some_and(|m| m.take_settings_change(&mut settings)) { return; }
+        let EditorSettings { text, image } = settings;
+        (self.large_file_mb, self.word_completion, self.highlight_current_line) = (text.large_file_mb, text.word_completion, text.highlight_current_line);
+        (self.typewriter_scrolling, self.persistent_undo) = (text.typewriter_scrolling, text.persistent_undo);
+        (self.color_swatches, self.skip_comment_colors, self.paste_cleanup) = (text.color_swatches, text.skip_comment_colors, text.paste_cleanup);
+        (self.max_canvas_mp, self.cursor_outline, self.canvas_surround, self.last_filter) = (image.max_canvas_mp, image.cursor_outline, image.canvas_surround, image.last_filter);
+        self.image_clip = image.image_clip;
+    }
+
     fn apply_default_font(&self, editor: &mut TextEditor) {
         editor.set_default_font(egui::FontFamily::Name(self.default_font.clone().into()), self.default_font_size);
     }
@@ -276,18 +409,26 @@ impl UniversalEditor {
     fn instantiate(&self, create: CreateModule, path: Option<PathBuf>) -> Box<dyn EditorModule> {
         match create {
             CreateModule::TextEditor => {
-                let mut e = if let Some(p) = path { TextEditor::load(p) } else { TextEditor::new_empty() };
+                let mut e = match path {
+                    Some(p) => match TextEditor::load(p.clone(), self.editor_settings().text) { Ok(e) => e, Err(err) => return Box::new(LoadFailed::new(p, create, err)) },
+                    None => TextEditor::new_empty(),
+                };
                 self.apply_default_font(&mut e);
                 e.set_path_replace_tx(self.path_replace_tx.clone());
                 Box::new(e)
             }
             CreateModule::ImageEditor => {
-                let mut e = if let Some(ref p) = path { ImageEditor::load(p.clone()) } else { ImageEditor::new() };
+                let mut e = match &path {
+                    Some(p) => match ImageEditor::load(p.clone()) { Ok(e) => e, Err(err) => return Box::new(LoadFailed::new(p.clone(), create, err)) },
+                    None => ImageEditor::new(),
+                };
                 if let Some(ref p) = path {
                     if let Some(cache) = ie_cache::load_cache(p) { ie_cache::apply_cache(&mut e, cache); }
+                    ie_cache::offer_text_journal(&mut e, p);
                 }
                 let tx = self.recent_file_tx.clone();
                 e.set_file_callback(Box::new(move |p: PathBuf| { let _ = tx.send(p); }));
+                e.set_path_replace_tx(self.path_replace_tx.clone());
                 Box::new(e)
             }
             CreateModule::JsonEditor => Box::new(if let Some(p) = path { JsonEditor::load(p) } else { JsonEditor::new_empty() }),
@@ -308,7 +449,7 @@ impl UniversalEditor {
         if self.has_unsaved_changes() {
             self.pending_action = Some(PendingAction::OpenFile(path)); self.show_unsaved_dialog = true;
         } else {
-            self.recent_files.add_file(path.clone()); self.active_module = Some(self.module_from_path(path));
+            self.recent_files.add_file(path.clone()); let m = self.module_from_path(path); self.set_active(Some(m));
         }
     }
 
@@ -316,7 +457,52 @@ impl UniversalEditor {
         if self.has_unsaved_changes() {
             self.pending_action = Some(PendingAction::NewFile); self.show_unsaved_dialog = true;
         } else {
-            let mut editor = TextEditor::new_empty(); self.apply_default_font(&mut editor); self.active_module = Some(Box::new(editor));
+            let mut editor = TextEditor::new_empty(); self.apply_default_font(&mut editor); self.set_active(Some(Box::new(editor)));
+        }
+    }
+
+    fn module_from_template(&self, content: TemplateContent) -> Box<dyn EditorModule> {
+        let mut e = match content {
+            TemplateContent::Text { text, markdown } => {
+                let mut e = TextEditor::from_template(text, markdown);
+                self.apply_default_font(&mut e);
+                e.set_path_replace_tx(self.path_replace_tx.clone());
+                return Box::new(e);
+            }
+            TemplateContent::Canvas(spec) => ImageEditor::from_canvas(&spec),
+            TemplateContent::Image(img) => ImageEditor::from_template_image(img),
+        };
+        let tx = self.recent_file_tx.clone();
+        e.set_file_callback(Box::new(move |p: PathBuf| { let _ = tx.send(p); }));
+        e.set_path_replace_tx(self.path_replace_tx.clone());
+        Box::new(e)
+    }
+
+    fn render_template_dialogs(&mut self, ctx: &egui::Context) {
+        if let Some(gallery) = &mut self.template_gallery {
+            match gallery.render(ctx, self.theme_mode) {
+                GalleryOutcome::Open => {}
+                GalleryOutcome::Closed => self.template_gallery = None,
+                GalleryOutcome::Failed(msg) => self.notify(ctx, msg),
+                GalleryOutcome::Create(content) => { self.template_gallery = None; let m = self.module_from_template(content); self.switch_to_module(m); }
+            }
+        }
+        let Some(dialog) = &mut self.save_template else { return };
+        match dialog.render(ctx, self.theme_mode) {
+            None => {}
+            Some(false) => self.save_template = None,
+            Some(true) => {
+                let (name, description) = (dialog.name.clone(), dialog.description.clone());
+                self.save_template = None;
+                let msg = match self.active_module.as_ref().and_then(|m| m.template_content()) {
+                    Some(content) => match crate::templates::save_template(&name, &description, &content) {
+                        Ok(_) => format!("Saved template \"{}\"", name.trim()),
+                        Err(e) => format!("Could not save template: {}", e),
+                    },
+                    None => "This document cannot be saved as a template".to_string(),
+                };
+                self.notify(ctx, msg);
+            }
         }
     }
 
@@ -324,7 +510,7 @@ impl UniversalEditor {
         if self.has_unsaved_changes() {
             self.pending_action = Some(PendingAction::SwitchModule(module)); self.show_unsaved_dialog = true;
         } else {
-            self.active_module = Some(module);
+            self.set_active(Some(module));
         }
     }
 
@@ -332,30 +518,393 @@ impl UniversalEditor {
         if self.has_unsaved_changes() {
             self.pending_action = Some(PendingAction::GoHome); self.show_unsaved_dialog = true;
         } else {
-            self.active_module = None;
+            self.set_active(None);
         }
     }
 
     fn execute_pending_action(&mut self) {
         if let Some(action) = self.pending_action.take() {
             match action {
-                PendingAction::OpenFile(path) => { self.recent_files.add_file(path.clone()); self.active_module = Some(self.module_from_path(path)); }
-                PendingAction::NewFile => { let mut e = TextEditor::new_empty(); self.apply_default_font(&mut e); self.active_module = Some(Box::new(e)); }
-                PendingAction::SwitchModule(module) => { self.active_module = Some(module); }
-                PendingAction::GoHome => { self.active_module = None; }
+                PendingAction::OpenFile(path) => { self.recent_files.add_file(path.clone()); let m = self.module_from_path(path); self.set_active(Some(m)); }
+                PendingAction::NewFile => { let mut e = TextEditor::new_empty(); self.apply_default_font(&mut e); self.set_active(Some(Box::new(e))); }
+                PendingAction::SwitchModule(module) => { self.set_active(Some(module)); }
+                PendingAction::GoHome => { self.set_active(None); }
                 PendingAction::Exit => {}
             }
         }
     }
 
+    fn set_active(&mut self, next: Option<Box<dyn EditorModule>>) {
+        if let Some(mut old) = self.active_module.take() { old.unload(); self.remember_closed(old.as_ref()); }
+        if let Some(path) = next.as_ref().and_then(|m| m.file_path()) { self.closed_documents.retain(|d| d.path != path); }
+        self.active_module = next;
+        self.menu_cache = None;
+        self.apply_editor_settings();
+    }
+
+    fn remember_closed(&mut self, module: &dyn EditorModule) {
+        let Some(path) = module.file_path().filter(|p| p.exists()).map(Path::to_path_buf) else { return };
+        let any = module.as_any();
+        let create = if any.is::<TextEditor>() { CreateModule::TextEditor } else if any.is::<ImageEditor>() { CreateModule::ImageEditor }
+            else if any.is::<JsonEditor>() { CreateModule::JsonEditor } else if any.is::<DocumentEditor>() { CreateModule::DocEditor } else { return };
+        let title = module.get_title().trim_end_matches(" *").to_string();
+        self.closed_documents.retain(|d| d.path != path);
+        self.closed_documents.insert(0, ClosedDocument { path, create, title, view: module.view_state() });
+        self.closed_documents.truncate(CLOSED_HISTORY);
+    }
+
+    fn reopen_closed(&mut self, ctx: &egui::Context, index: usize) {
+        let Some(doc) = self.closed_documents.get(index).cloned() else { return };
+        if !doc.path.exists() {
+            self.closed_documents.remove(index);
+            self.notify(ctx, format!("{} no longer exists", doc.path.display()));
+            return;
+        }
+        let mut module = self.instantiate(doc.create, Some(doc.path.clone()));
+        if let Some(view) = doc.view { module.restore_view_state(view); }
+        self.recent_files.add_file(doc.path);
+        self.switch_to_module(module);
+    }
+
+    fn update_window_title(&mut self, ctx: &egui::Context) {
+        let title = match &self.active_module {
+            Some(m) => format!("{} — Universal Editor", m.get_title()),
+            None => "Universal Editor".to_string(),
+        };
+        if title != self.window_title {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Title(title.clone()));
+            self.window_title = title;
+        }
+    }
+
     fn save_settings(&self) {
         AppSettings {
             theme_preference: self.theme_preference, show_toolbar_te: self.show_toolbar_te,
             show_file_info_te: self.show_file_info_te, default_font: self.default_font.clone(),
             default_font_size: self.default_font_size, show_file_info_je: self.show_file_info_je,
+            check_for_updates: self.check_for_updates, update_check_asked: self.update_check_asked, skipped_update_version: self.skipped_update_version.clone(),
+            high_visibility_focus: self.high_visibility_focus,
+            tours_seen: self.tours_seen.clone(),
+            paste_cleanup: self.paste_cleanup,
+            use_gpu: self.use_gpu,
+            cursor_outline: self.cursor_outline,
+            large_file_mb: self.large_file_mb,
+            max_canvas_mp: self.max_canvas_mp,
+            word_completion: self.word_completion,
+            color_swatches: self.color_swatches,
+            skip_comment_colors: self.skip_comment_colors,
+            highlight_current_line: self.highlight_current_line,
+            typewriter_scrolling: self.typewriter_scrolling,
+            persistent_undo: self.persistent_undo,
+            persist_clipboard: self.persist_clipboard,
+            keep_backup: self.keep_backup,
+            language: self.language.clone(),
         }.save();
     }
 
+    fn reload_settings(&mut self, ctx: &egui::Context) {
+        let s = AppSettings::load();
+        self.theme_preference = s.theme_preference;
+        self.theme_mode = match s.theme_preference {
+            ThemePreference::System => match ctx.theme() { egui::Theme::Dark => ThemeMode::Dark, egui::Theme::Light => ThemeMode::Light },
+            ThemePreference::Light => ThemeMode::Light, ThemePreference::Dark => ThemeMode::Dark,
+        };
+        self.show_toolbar_te = s.show_toolbar_te; self.show_file_info_te = s.show_file_info_te; self.show_file_info_je = s.show_file_info_je;
+        self.default_font = s.default_font; self.default_font_size = s.default_font_size;
+        self.check_for_updates = s.check_for_updates; self.update_check_asked = s.update_check_asked; self.skipped_update_version = s.skipped_update_version;
+        self.high_visibility_focus = s.high_visibility_focus; self.tours_seen = s.tours_seen;
+        self.paste_cleanup = s.paste_cleanup;
+        self.use_gpu = s.use_gpu;
+        self.max_canvas_mp = s.max_canvas_mp; self.cursor_outline = s.cursor_outline;
+        self.large_file_mb = s.large_file_mb; self.word_completion = s.word_completion;
+        self.color_swatches = s.color_swatches; self.skip_comment_colors = s.skip_comment_colors;
+        if self.persistent_undo && !s.persistent_undo { TextEditor::forget_saved_undo(); }
+        self.persistent_undo = s.persistent_undo;
+        self.keep_backup = s.keep_backup;
+        self.highlight_current_line = s.highlight_current_line; self.typewriter_scrolling = s.typewriter_scrolling;
+        self.remember_tools = ImageEditor::remember_tool_settings();
+        self.apply_editor_settings();
+        self.language = s.language; i18n::set_locale(&self.language);
+        if self.persist_clipboard != s.persist_clipboard { self.persist_clipboard = s.persist_clipboard; self.clipboard.set_persist(s.persist_clipboard); }
+        self.apply_style(ctx);
+    }
+
+    fn export_profile(&mut self, ctx: &egui::Context) {
+        let Some(path) = rfd::FileDialog::new().add_filter("Universal Editor Profile", &["json"]).set_file_name("universal-editor-profile.json").save_file() else { return };
+        self.save_settings();
+        let msg = match profile::export_profile(&profile::config_dir(), &path) {
+            Ok(n) => format!("Exported {} setting groups to {}", n, path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default()),
+            Err(e) => format!("Could not export profile: {}", e),
+        };
+        self.notify(ctx, msg);
+    }
+
+    fn import_profile(&mut self, ctx: &egui::Context) {
+        let Some(path) = rfd::FileDialog::new().add_filter("Universal Editor Profile", &["json"]).pick_file() else { return };
+        match profile::read_profile(&path) {
+            Ok(import) => { self.profile_import = Some(import); self.show_settings = false; }
+            Err(msg) => self.notify(ctx, msg),
+        }
+    }
+
+    /// The active image plus every recent image file that still exists; text documents never take part.
+    fn open_export_all(&mut self) {
+        let mut docs = Vec::new();
+        let active = self.active_module.as_ref().filter(|m| m.as_any().is::<ImageEditor>());
+        let active_path = active.and_then(|m| m.file_path()).map(Path::to_path_buf);
+        if let Some(m) = active {
+            let source = match m.template_content() { Some(TemplateContent::Image(img)) => ExportSource::Open(img), _ => ExportSource::Blank };
+            docs.push(ExportDocument { title: ExportDocument::title_for(active_path.as_deref()), source });
+        }
+        for rf in self.recent_files.get_files() {
+            let ext = rf.path.extension().and_then(|e| e.to_str()).unwrap_or("");
+            let is_image = registry::screen_for_extension(ext).is_some_and(|s| s.create == CreateModule::ImageEditor);
+            if !is_image || active_path.as_ref() == Some(&rf.path) || !rf.path.is_file() { continue; }
+            docs.push(ExportDocument { title: ExportDocument::title_for(Some(&rf.path)), source: ExportSource::File(rf.path.clone()) });
+        }
+        let tx = self.recent_file_tx.clone();
+        self.export_all = Some(ExportAllDialog::new(docs, Arc::new(move |p: PathBuf| { let _ = tx.send(p); })));
+    }
+
+    fn render_export_all(&mut self, ctx: &egui::Context) {
+        let Some(dialog) = &mut self.export_all else { return };
+        if !dialog.show(ctx, self.theme_mode) { self.export_all = None; }
+    }
+
+    fn render_profile_import(&mut self, ctx: &egui::Context) {
+        let Some(import) = &mut self.profile_import else { return };
+        let dir = profile::config_dir();
+        let outcome = if ctx.input(|i| i.key_pressed(egui::Key::Escape)) { Some(false) } else { import.show(ctx, self.theme_mode, &dir) };
+        let Some(apply) = outcome else { return };
+        let Some(import) = self.profile_import.take() else { return };
+        if !apply { return; }
+        match import.apply(&dir) {
+            Ok(applied) => {
+                self.reload_settings(ctx);
+                if let Some(m) = &mut self.active_module { m.reload_config(); }
+                self.notify(ctx, format!("Imported {}", applied.join(", ").to_lowercase()));
+            }
+            Err(e) => self.notify(ctx, format!("Could not import profile: {}", e)),
+        }
+    }
+
+    fn apply_style(&self, ctx: &egui::Context) {
+        style::apply_theme(ctx, self.theme_mode);
+        style::apply_focus_outline(ctx, self.high_visibility_focus);
+    }
+
+    fn check_for_updates_now(&mut self, ctx: &egui::Context) {
+        if self.check_for_updates && self.update_check.is_none() { self.update_check = Some(UpdateCheck::spawn(ctx, true)); }
+    }
+
+    fn poll_update_check(&mut self, ctx: &egui::Context) {
+        let Some(result) = self.update_check.as_ref().and_then(|c| c.poll()) else { return };
+        let manual = self.update_check.take().is_some_and(|c| c.manual);
+        match result {
+            Ok(UpdateStatus::Available(r)) => {
+                if manual || self.skipped_update_version.as_deref() != Some(r.version.as_str()) { self.update_dialog = Some(UpdateStatus::Available(r)); }
+            }
+            Ok(UpdateStatus::UpToDate) => { if manual { self.update_dialog = Some(UpdateStatus::UpToDate); } }
+            Err(e) => { if manual { self.notify(ctx, e); } }
+        }
+    }
+
+    /// The open document as its kind, extension and size on disk; titles and paths are left out.
+    fn document_summary(&self) -> Vec<String> {
+        let Some(module) = &self.active_module else { return Vec::new() };
+        let Some(path) = module.file_path() else { return vec![format!("{} (unsaved)", module.get_title().trim_start_matches('*').trim())] };
+        let ext = path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+        let kind = registry::screen_for_extension(&ext).map_or("Unknown editor", |s| s.name);
+        let size = std::fs::metadata(path).map_or_else(|_| "not on disk".to_string(), |m| file_info::format_size(m.len()));
+        vec![format!("{} (.{}, {})", kind, ext, size)]
+    }
+
+    fn open_problem_report(&mut self) {
+        let os = format!("{} {}", std::env::consts::OS, std::env::consts::ARCH);
+        let settings = [
+            ("Build", if cfg!(debug_assertions) { "debug" } else { "release" }.to_string()),
+            ("GPU filters", if self.use_gpu { "on" } else { "off" }.to_string()),
+            ("Language", self.language.clone()),
+            ("Config", match config_file::store() { s if s.in_memory => "in memory (read-only)", s if s.portable => "portable", _ => "user profile" }.to_string()),
+        ];
+        let report = diagnostics::Report {
+            version: updater::CURRENT_VERSION, os: &os, renderer: self.renderer.as_deref().unwrap_or("unknown"), settings: &settings,
+            documents: &self.document_summary(), errors: &diagnostics::recent_errors(), log: &diagnostics::recent_log(diagnostics::REPORT_LOG_LINES),
+        };
+        let home = dirs::home_dir().map(|h| h.to_string_lossy().into_owned());
+        self.problem_report = Some(diagnostics::build_report(&report, home.as_deref()));
+    }
+
+    fn render_problem_report(&mut self, ctx: &egui::Context) {
+        let Some(text) = &mut self.problem_report else { return };
+        let is_dark = matches!(self.theme_mode, ThemeMode::Dark);
+        let (bg, border, muted) = if is_dark { (ColorPalette::ZINC_800, ColorPalette::ZINC_700, ColorPalette::ZINC_400) } else { (egui::Color32::WHITE, ColorPalette::STONE_200, ColorPalette::STONE_500) };
+        let mut open = true;
+        let mut close = false;
+        egui::Window::new("Report a Problem")
+            .open(&mut open).collapsible(false).resizable(true)
+            .default_width(620.0).default_pos(ctx.content_rect().center() - egui::vec2(310.0, 260.0))
+            .frame(egui::Frame::new().fill(bg).stroke(egui::Stroke::new(1.0, border)).corner_radius(8.0).inner_margin(16.0))
+            .show(ctx, |ui| {
+                ui.label(egui::RichText::new("This is everything that will be shared. No document content is included; edit or remove anything you'd rather keep private.").size(12.0).color(muted));
+                ui.add_space(8.0);
+                egui::ScrollArea::vertical().max_height(380.0).auto_shrink([false, true]).show(ui, |ui| {
+                    ui.add(egui::TextEdit::multiline(text).code_editor().desired_width(f32::INFINITY).desired_rows(18));
+                });
+                ui.add_space(10.0);
+                ui.horizontal(|ui| {
+                    if ui.button("Copy & Open GitHub Issue").on_hover_cursor(egui::CursorIcon::PointingHand).clicked() {
+                        ctx.copy_text(text.clone());
+                        ctx.open_url(egui::OpenUrl::new_tab(diagnostics::issue_url(updater::CURRENT_VERSION, std::env::consts::OS)));
+                        close = true;
+                    }
+                    if ui.button("Copy Only").on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { ctx.copy_text(text.clone()); close = true; }
+                    if ui.button("Cancel").on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { close = true; }
+                });
+            });
+        if !open || close { self.problem_report = None; }
+    }
+
+    fn render_update_prompt(&mut self, ctx: &egui::Context) {
+        if self.update_check_asked { return; }
+        let is_dark = matches!(self.theme_mode, ThemeMode::Dark);
+        let (bg, border, text, muted) = if is_dark { (ColorPalette::ZINC_800, ColorPalette::ZINC_700, ColorPalette::ZINC_100, ColorPalette::ZINC_400) } else { (egui::Color32::WHITE, ColorPalette::STONE_200, ColorPalette::STONE_900, ColorPalette::STONE_500) };
+        let mut answer = None;
+        egui::Window::new(tr("Check for Updates"))
+            .collapsible(false).resizable(false)
+            .default_width(420.0).default_pos(ctx.content_rect().center() - egui::vec2(210.0, 80.0))
+            .frame(egui::Frame::new().fill(bg).stroke(egui::Stroke::new(1.0, border)).corner_radius(8.0).inner_margin(16.0))
+            .show(ctx, |ui| {
+                ui.label(egui::RichText::new(tr("Check for new versions when Universal Editor starts?")).size(14.0).color(text));
+                ui.add_space(4.0);
+                ui.label(egui::RichText::new(tr("This asks GitHub for the latest release. You can change it later in Settings.")).size(12.0).color(muted));
+                ui.add_space(10.0);
+                ui.horizontal(|ui| {
+                    if ui.button(tr("Check Automatically")).on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { answer = Some(true); }
+                    if ui.button(tr("Don't Check")).on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { answer = Some(false); }
+                });
+            });
+        let Some(yes) = answer else { return };
+        self.check_for_updates = yes;
+        self.update_check_asked = true;
+        self.save_settings();
+        if yes && self.update_check.is_none() { self.update_check = Some(UpdateCheck::spawn(ctx, false)); }
+    }
+
+    fn render_update_dialog(&mut self, ctx: &egui::Context) {
+        let Some(status) = &self.update_dialog else { return };
+        let is_dark = matches!(self.theme_mode, ThemeMode::Dark);
+        let (bg, border, text, muted) = if is_dark { (ColorPalette::ZINC_800, ColorPalette::ZINC_700, ColorPalette::ZINC_100, ColorPalette::ZINC_400) } else { (egui::Color32::WHITE, ColorPalette::STONE_200, ColorPalette::STONE_900, ColorPalette::STONE_500) };
+        let mut open = true;
+        let mut skip = false;
+        let mut close = false;
+        let title = if matches!(status, UpdateStatus::Available(_)) { "Update Available" } else { "Check for Updates" };
+        egui::Window::new(title)
+            .open(&mut open).collapsible(false).resizable(true)
+            .default_width(460.0).default_pos(ctx.content_rect().center() - egui::vec2(230.0, 200.0))
+            .frame(egui::Frame::new().fill(bg).stroke(egui::Stroke::new(1.0, border)).corner_radius(8.0).inner_margin(16.0))
+            .show(ctx, |ui| {
+                match status {
+                    UpdateStatus::UpToDate => {
+                        ui.label(egui::RichText::new(format!("Universal Editor {} is the latest version.", updater::CURRENT_VERSION)).size(14.0).color(text));
+                        ui.add_space(10.0);
+                        if ui.button("OK").on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { close = true; }
+                    }
+                    UpdateStatus::Available(r) => {
+                        ui.label(egui::RichText::new(&r.title).size(16.0).strong().color(text));
+                        ui.label(egui::RichText::new(format!("Version {} is available. You have {}.", r.version, updater::CURRENT_VERSION)).size(12.0).color(muted));
+                        ui.add_space(8.0);
+                        egui::ScrollArea::vertical().max_height(320.0).auto_shrink([false, true]).show(ui, |ui| {
+                            if r.notes.trim().is_empty() { ui.label(egui::RichText::new("No release notes were published.").size(13.0).color(muted).italics()); }
+                            let wrap = ui.available_width();
+                            for line in r.notes.lines() { ui.label(TextEditor::markdown_preview_job(line.trim_end(), 13.0, wrap, is_dark)); }
+                        });
+                        ui.add_space(10.0);
+                        ui.horizontal(|ui| {
+                            if ui.button("Download").on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { ctx.open_url(egui::OpenUrl::new_tab(&r.url)); close = true; }
+                            if ui.button("Skip This Version").on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { skip = true; }
+                            if ui.button("Later").on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { close = true; }
+                        });
+                    }
+                }
+            });
+        if skip {
+            if let Some(UpdateStatus::Available(r)) = &self.update_dialog { self.skipped_update_version = Some(r.version.clone()); }
+            self.save_settings();
+        }
+        if !open || close || skip { self.update_dialog = None; }
+    }
+
+    fn report<T>(&mut self, ctx: &egui::Context, res: EditorResult<T>) {
+        let Err(e) = res else { return };
+        log::error!("{:?}", e);
+        if let Some(msg) = e.user_message() { self.notify(ctx, msg); }
+    }
+
+    fn notify(&mut self, ctx: &egui::Context, msg: String) {
+        diagnostics::record_error(&msg);
+        self.notification = Some((msg, ctx.input(|i| i.time) + 6.0));
+    }
+
+    fn render_notification(&mut self, ctx: &egui::Context) {
+        let Some((msg, until)) = self.notification.clone() else { return };
+        let now = ctx.input(|i| i.time);
+        if now >= until { self.notification = None; return; }
+        ctx.request_repaint_after(std::time::Duration::from_secs_f64(until - now));
+        let is_dark = matches!(self.theme_mode, ThemeMode::Dark);
+        let (bg, border, text) = if is_dark { (ColorPalette::ZINC_800, ColorPalette::RED_500, ColorPalette::ZINC_100) } else { (egui::Color32::WHITE, ColorPalette::RED_500, ColorPalette::STONE_900) };
+        let mut dismiss = false;
+        egui::Area::new(egui::Id::new("app_notification"))
+            .order(egui::Order::Foreground)
+            .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-16.0, -16.0))
+            .show(ctx, |ui| {
+                egui::Frame::new().fill(bg).stroke(egui::Stroke::new(1.0, border)).corner_radius(8.0).inner_margin(12.0).show(ui, |ui| {
+                    ui.set_max_width(360.0);
+                    ui.horizontal(|ui| {
+                        ui.add(egui::Label::new(egui::RichText::new(&msg).size(13.0).color(text)).wrap());
+                        if ui.small_button("x").on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { dismiss = true; }
+                    });
+                });
+            });
+        if dismiss { self.notification = None; }
+    }
+
+    fn claims_drop(&self, path: &Path) -> bool { self.active_module.as_ref().is_some_and(|m| m.accepts_dropped_file(path)) }
+
+    /// Opens files dropped on the window that the active module doesn't take itself. There is one document at a
+    /// time, so the first supported file opens and the others go to Recent Files.
+    fn handle_dropped_files(&mut self, ctx: &egui::Context) {
+        let dropped: Vec<PathBuf> = ctx.input(|i| i.raw.dropped_files.iter().filter_map(|f| f.path.clone()).collect());
+        let (mut open, mut unsupported) = (Vec::new(), Vec::new());
+        for path in dropped.into_iter().filter(|p| !self.claims_drop(p)) {
+            let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+            if path.is_file() && registry::screen_for_extension(ext).is_some() { open.push(path); } else { unsupported.push(path); }
+        }
+        let name = |p: &PathBuf| p.file_name().map_or_else(|| p.display().to_string(), |n| n.to_string_lossy().into_owned());
+        let mut msgs = Vec::new();
+        if !unsupported.is_empty() { msgs.push(format!("Cannot open {}: unsupported file type", unsupported.iter().map(name).collect::<Vec<_>>().join(", "))); }
+        let mut open = open.into_iter();
+        if let Some(first) = open.next() {
+            let rest: Vec<PathBuf> = open.collect();
+            if !rest.is_empty() {
+                for path in rest.iter().rev() { self.recent_files.add_file(path.clone()); }
+                msgs.push(format!("Opened {}; {} more added to Recent Files", name(&first), rest.len()));
+            }
+            self.open_file(first);
+        }
+        if !msgs.is_empty() { self.notify(ctx, msgs.join("\n")); }
+    }
+
+    fn render_drop_overlay(&self, ctx: &egui::Context) {
+        let hovering = ctx.input(|i| i.raw.hovered_files.iter().any(|f| f.path.as_deref().map_or(self.active_module.is_none(), |p| !self.claims_drop(p))));
+        if !hovering { return; }
+        let rect = ctx.content_rect();
+        let painter = ctx.layer_painter(egui::LayerId::new(egui::Order::Foreground, egui::Id::new("file_drop_overlay")));
+        painter.rect_filled(rect, 0.0, egui::Color32::from_black_alpha(120));
+        painter.rect_stroke(rect.shrink(6.0), 8.0, egui::Stroke::new(3.0, ColorPalette::BLUE_400), egui::StrokeKind::Inside);
+        painter.text(rect.center(), egui::Align2::CENTER_CENTER, tr("Drop to open"), egui::FontId::proportional(20.0), egui::Color32::WHITE);
+    }
+
     fn render_unsaved_dialog(&mut self, ctx: &egui::Context) {
         if !self.show_unsaved_dialog { return; }
         let is_dark = matches!(self.theme_mode, ThemeMode::Dark);
@@ -376,7 +925,15 @@ impl UniversalEditor {
                         let save = style::primary_button(ui, "Save").on_hover_cursor(egui::CursorIcon::PointingHand).clicked();
                         let dont = style::secondary_button(ui, "Don't Save", self.theme_mode).on_hover_cursor(egui::CursorIcon::PointingHand).clicked();
                         let cancel = style::secondary_button(ui, "Cancel", self.theme_mode).on_hover_cursor(egui::CursorIcon::PointingHand).clicked();
-                        if save { if let Some(m) = &mut self.active_module { let _ = m.save(); } self.show_unsaved_dialog = false; self.execute_pending_action(); }
+                        if save {
+                            let res = self.active_module.as_mut().map_or(Ok(SaveState::Saved), |m| m.save());
+                            self.show_unsaved_dialog = false;
+                            match res {
+                                Ok(SaveState::Saved) => self.execute_pending_action(),
+                                Ok(SaveState::AwaitingConfirm) => self.awaiting_save = true,
+                                Err(e) => { self.report(ctx, Err::<(), _>(e)); self.pending_action = None; }
+                            }
+                        }
                         if dont { self.show_unsaved_dialog = false; self.execute_pending_action(); }
                         if cancel { self.show_unsaved_dialog = false; self.pending_action = None; }
                     });
@@ -385,11 +942,14 @@ impl UniversalEditor {
             });
     }
 
-    fn menu_items_ui(&mut self, ui: &mut egui::Ui, items: &[(crate::modules::MenuItem, crate::modules::MenuAction)]) {
-        for (item, action) in items {
-            if item.label == "Separator" { ui.separator(); continue; }
-            let label = item.shortcut.as_ref().map(|s| format!("{} ({})", item.label, s)).unwrap_or_else(|| item.label.clone());
-            if ui.add_enabled(item.enabled, egui::Button::new(label)).on_hover_cursor(egui::CursorIcon::PointingHand).clicked() {
+    fn menu_items_ui(&mut self, ui: &mut egui::Ui, items: &[crate::modules::MenuEntry]) {
+        for entry in items {
+            let crate::modules::MenuEntry::Item(item, action) = entry else { ui.separator(); continue };
+            let text = format!("{}{}", if item.checked == Some(true) { "✔ " } else { "" }, tr(&item.label));
+            let label = item.shortcut.as_ref().map(|s| format!("{} ({})", text, s)).unwrap_or(text);
+            let resp = ui.add_enabled(item.enabled, egui::Button::new(label)).on_hover_cursor(egui::CursorIcon::PointingHand);
+            let resp = match item.tooltip { Some(tip) => resp.on_hover_text(tr(tip)).on_disabled_hover_text(tr(tip)), None => resp };
+            if resp.clicked() {
                 if let Some(m) = &mut self.active_module { m.handle_menu_action(action.clone()); }
                 ui.close();
             }
@@ -397,81 +957,156 @@ impl UniversalEditor {
     }
 
     fn top_bar(&mut self, ctx: &egui::Context) {
-        let contributions = self.active_module.as_ref().map(|m| m.get_menu_contributions()).unwrap_or_default();
-        egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
+        let live = egui::Popup::is_any_open(ctx) || ctx.input(|i| i.pointer.any_pressed() || i.pointer.any_released());
+        let contributions = match &self.menu_cache {
+            Some(cached) if !live => cached.clone(),
+            _ => self.menu_cache.insert(Rc::new(self.active_module.as_ref().map(|m| m.get_menu_contributions()).unwrap_or_default())).clone(),
+        };
+        let panel = egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
             ui.add_space(4.0);
             egui::MenuBar::new().ui(ui, |ui| {
                 let has_module = self.active_module.is_some();
                 let mut go_home = false;
-                if has_module { if ui.button("Home").on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { go_home = true; } ui.separator(); }
+                if has_module { if ui.button(tr("Home")).on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { go_home = true; } ui.separator(); }
                 if go_home { self.go_home(); return; }
 
-                ui.menu_button("File", |ui| {
-                    if ui.button("Open...").on_hover_cursor(egui::CursorIcon::PointingHand).clicked() {
+                ui.menu_button(tr("File"), |ui| {
+                    if ui.button(tr("Open...")).on_hover_cursor(egui::CursorIcon::PointingHand).clicked() {
                         let exts = registry::all_accepted_extensions();
                         if let Some(path) = rfd::FileDialog::new().add_filter("All Files", &exts).pick_file() { self.open_file(path); }
                         ui.close();
                     }
+                    if ui.button(tr("New from Template...")).on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { self.template_gallery = Some(TemplateGallery::new()); ui.close(); }
+                    ui.add_enabled_ui(!self.closed_documents.is_empty(), |ui| {
+                        ui.menu_button(tr("Recently Closed"), |ui| {
+                            let mut reopen = None;
+                            if ui.button(tr("Reopen Closed Document (Ctrl+Shift+T)")).on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { reopen = Some(0); }
+                            ui.separator();
+                            for (i, doc) in self.closed_documents.iter().enumerate() {
+                                if ui.button(&doc.title).on_hover_text(doc.path.display().to_string()).on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { reopen = Some(i); }
+                            }
+                            if let Some(i) = reopen { self.reopen_closed(ctx, i); ui.close(); }
+                        });
+                    });
                     ui.separator();
-                    if ui.add_enabled(has_module, egui::Button::new("Save (Ctrl+S)")).on_hover_cursor(egui::CursorIcon::PointingHand).clicked() {
-                        if let Some(m) = &mut self.active_module { let _ = m.save(); } ui.close();
+                    let can_save = has_module && !self.active_module.as_ref().is_some_and(|m| m.as_any().is::<LoadFailed>());
+                    if ui.add_enabled(can_save, egui::Button::new(tr("Save (Ctrl+S)"))).on_hover_cursor(egui::CursorIcon::PointingHand).clicked() {
+                        let res = self.active_module.as_mut().map_or(Ok(SaveState::Saved), |m| m.save()); self.report(ctx, res); ui.close();
                     }
-                    if ui.add_enabled(has_module, egui::Button::new("Save As...")).on_hover_cursor(egui::CursorIcon::PointingHand).clicked() {
-                        if let Some(m) = &mut self.active_module { let _ = m.save_as(); } ui.close();
+                    if ui.add_enabled(can_save, egui::Button::new(tr("Save As..."))).on_hover_cursor(egui::CursorIcon::PointingHand).clicked() {
+                        let res = self.active_module.as_mut().map_or(Ok(SaveState::Saved), |m| m.save_as()); self.report(ctx, res); ui.close();
                     }
+                    let can_template = self.active_module.as_ref().is_some_and(|m| m.as_any().is::<TextEditor>() || m.as_any().is::<ImageEditor>());
+                    if ui.add_enabled(can_template, egui::Button::new(tr("Save as Template..."))).on_hover_cursor(egui::CursorIcon::PointingHand).clicked() {
+                        let title = self.active_module.as_ref().map(|m| m.get_title()).unwrap_or_default();
+                        let stem = Path::new(title.trim_end_matches(" *")).file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+                        self.save_template = Some(SaveTemplateDialog::new(stem)); ui.close();
+                    }
+                    if ui.button(tr("Export All Images...")).on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { self.open_export_all(); ui.close(); }
                     if !contributions.file_items.is_empty() { ui.separator(); self.menu_items_ui(ui, &contributions.file_items.clone()); }
                     ui.separator();
-                    if ui.button("Exit").on_hover_cursor(egui::CursorIcon::PointingHand).clicked() {
+                    if ui.button(tr("Exit")).on_hover_cursor(egui::CursorIcon::PointingHand).clicked() {
                         if self.has_unsaved_changes() { self.pending_action = Some(PendingAction::Exit); self.show_unsaved_dialog = true; }
                         else { ctx.send_viewport_cmd(egui::ViewportCommand::Close); }
                         ui.close();
                     }
                 });
 
-                if !contributions.edit_items.is_empty() {
-                    let items = contributions.edit_items.clone();
-                    ui.menu_button("Edit", |ui| { self.menu_items_ui(ui, &items); });
-                }
+                ui.menu_button(tr("Edit"), |ui| {
+                    if !contributions.edit_items.is_empty() { self.menu_items_ui(ui, &contributions.edit_items.clone()); ui.separator(); }
+                    if ui.button(tr("Clipboard History...")).on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { self.show_clipboard = true; ui.close(); }
+                });
 
-                ui.menu_button("View", |ui| {
-                    if ui.button("Toggle Sidebar (Ctrl+\\)").on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { self.sidebar_open = !self.sidebar_open; ui.close(); }
+                ui.menu_button(tr("View"), |ui| {
+                    if ui.button(tr("Toggle Sidebar (Ctrl+\\)")).on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { self.sidebar_open = !self.sidebar_open; ui.close(); }
+                    if ui.button(tr("Focus Mode (F11)")).on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { self.focus_mode = true; ui.close(); }
                     if self.is_in_text_editor() || self.is_in_json_editor() {
                         ui.separator();
                         if self.is_in_text_editor() {
-                            let a = ui.checkbox(&mut self.show_toolbar_te, "Show Toolbar").changed();
-                            let b = ui.checkbox(&mut self.show_file_info_te, "Show File Info").changed();
-                            if a || b { self.save_settings(); }
+                            let a = ui.checkbox(&mut self.show_toolbar_te, tr("Show Toolbar")).changed();
+                            let b = ui.checkbox(&mut self.show_file_info_te, tr("Show File Info")).changed();
+                            let c = ui.checkbox(&mut self.highlight_current_line, tr("Highlight Current Line")).changed();
+                            let d = ui.checkbox(&mut self.typewriter_scrolling, tr("Typewriter Scrolling")).changed();
+                            if c || d { self.apply_editor_settings(); }
+                            if a || b || c || d { self.save_settings(); }
                         }
                         if self.is_in_json_editor() {
-                            if ui.checkbox(&mut self.show_file_info_je, "Show File Info").changed() { 
+                            if ui.checkbox(&mut self.show_file_info_je, tr("Show File Info")).changed() { 
                                 self.save_settings(); 
                             }
                         }
                     }
                     if !contributions.view_items.is_empty() { ui.separator(); self.menu_items_ui(ui, &contributions.view_items.clone()); }
 
-                    ui.separator(); ui.label("Theme:");
-                    let sys = ui.selectable_label(matches!(self.theme_preference, ThemePreference::System), "System").on_hover_cursor(egui::CursorIcon::PointingHand).clicked();
-                    let light = ui.selectable_label(matches!(self.theme_preference, ThemePreference::Light), "Light").on_hover_cursor(egui::CursorIcon::PointingHand).clicked();
-                    let dark = ui.selectable_label(matches!(self.theme_preference, ThemePreference::Dark), "Dark").on_hover_cursor(egui::CursorIcon::PointingHand).clicked();
-                    if sys { self.theme_preference = ThemePreference::System; self.theme_mode = match ctx.theme() { egui::Theme::Dark => ThemeMode::Dark, egui::Theme::Light => ThemeMode::Light }; style::apply_theme(ctx, self.theme_mode); self.save_settings(); ui.close(); }
-                    if light { self.theme_preference = ThemePreference::Light; self.theme_mode = ThemeMode::Light; style::apply_theme(ctx, self.theme_mode); self.save_settings(); ui.close(); }
-                    if dark { self.theme_preference = ThemePreference::Dark; self.theme_mode = ThemeMode::Dark; style::apply_theme(ctx, self.theme_mode); self.save_settings(); ui.close(); }
+                    ui.separator(); ui.label(tr("Theme:"));
+                    let sys = ui.selectable_label(matches!(self.theme_preference, ThemePreference::System), tr("System")).on_hover_cursor(egui::CursorIcon::PointingHand).clicked();
+                    let light = ui.selectable_label(matches!(self.theme_preference, ThemePreference::Light), tr("Light")).on_hover_cursor(egui::CursorIcon::PointingHand).clicked();
+                    let dark = ui.selectable_label(matches!(self.theme_preference, ThemePreference::Dark), tr("Dark")).on_hover_cursor(egui::CursorIcon::PointingHand).clicked();
+                    if sys { self.theme_preference = ThemePreference::System; self.theme_mode = match ctx.theme() { egui::Theme::Dark => ThemeMode::Dark, egui::Theme::Light => ThemeMode::Light }; self.apply_style(ctx); self.save_settings(); ui.close(); }
+                    if light { self.theme_preference = ThemePreference::Light; self.theme_mode = ThemeMode::Light; self.apply_style(ctx); self.save_settings(); ui.close(); }
+                    if dark { self.theme_preference = ThemePreference::Dark; self.theme_mode = ThemeMode::Dark; self.apply_style(ctx); self.save_settings(); ui.close(); }
                 });
 
-                if !contributions.image_items.is_empty() { let items = contributions.image_items.clone(); ui.menu_button("Image", |ui| { self.menu_items_ui(ui, &items); }); }
-                if !contributions.filter_items.is_empty() { let items = contributions.filter_items.clone(); ui.menu_button("Filter", |ui| { self.menu_items_ui(ui, &items); }); }
-                if !contributions.layer_items.is_empty() { let items = contributions.layer_items.clone(); ui.menu_button("Layer", |ui| { self.menu_items_ui(ui, &items); }); }
-                if !contributions.insert_items.is_empty() { let items = contributions.insert_items.clone(); ui.menu_button("Insert", |ui| { self.menu_items_ui(ui, &items); }); }
-                if !contributions.format_items.is_empty() { let items = contributions.format_items.clone(); ui.menu_button("Format", |ui| { self.menu_items_ui(ui, &items); }); }
+                if !contributions.image_items.is_empty() { let items = contributions.image_items.clone(); ui.menu_button(tr("Image"), |ui| { self.menu_items_ui(ui, &items); }); }
+                if !contributions.filter_items.is_empty() { let items = contributions.filter_items.clone(); ui.menu_button(tr("Filter"), |ui| { self.menu_items_ui(ui, &items); }); }
+                if !contributions.layer_items.is_empty() { let items = contributions.layer_items.clone(); ui.menu_button(tr("Layer"), |ui| { self.menu_items_ui(ui, &items); }); }
+                if !contributions.insert_items.is_empty() { let items = contributions.insert_items.clone(); ui.menu_button(tr("Insert"), |ui| { self.menu_items_ui(ui, &items); }); }
+                if !contributions.format_items.is_empty() { let items = contributions.format_items.clone(); ui.menu_button(tr("Format"), |ui| { self.menu_items_ui(ui, &items); }); }
+
+                ui.menu_button(tr("Help"), |ui| {
+                    if ui.button(tr("Take the Tour")).on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { self.start_tour(); ui.close(); }
+                    if ui.button(tr("Keyboard Shortcuts (?)")).on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { self.show_shortcuts = true; ui.close(); }
+                    if ui.button(tr("About Universal Editor")).on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { self.show_about = true; ui.close(); }
+                    ui.separator();
+                    let label = if self.update_check.is_some() { "Checking for Updates..." } else { "Check for Updates" };
+                    let resp = ui.add_enabled(self.check_for_updates && self.update_check.is_none(), egui::Button::new(tr(label))).on_hover_cursor(egui::CursorIcon::PointingHand)
+                        .on_disabled_hover_text(tr("Update checks are turned off in Settings"));
+                    if resp.clicked() { self.check_for_updates_now(ctx); ui.close(); }
+                    if ui.button(tr("Report a Problem...")).on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { self.open_problem_report(); ui.close(); }
+                });
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| tasks::indicator(ui, self.theme_mode));
             });
             ui.add_space(4.0);
         });
+        crate::tour::anchor(ctx, "menu_bar", panel.response.rect);
+        crate::focus::register(ctx, crate::focus::Region::Menu, panel.response.layer_id, panel.response.rect);
+    }
+
+    fn start_tour(&mut self) {
+        let mut steps = APP_TOUR.to_vec();
+        let mut ids = vec!["app"];
+        if let Some((id, module_steps)) = self.active_module.as_ref().and_then(|m| m.tour()) { ids.push(id); steps.extend_from_slice(module_steps); }
+        self.tour = Tour::new(ids, steps);
+    }
+
+    fn render_tour(&mut self, ctx: &egui::Context) {
+        if self.tour.is_none() {
+            let pending = self.active_module.as_ref().and_then(|m| m.tour()).filter(|(id, _)| !self.tours_seen.iter().any(|t| t == id));
+            if let Some((id, steps)) = pending { self.tour = Tour::new(vec![id], steps.to_vec()); }
+        }
+        let Some(tour) = &mut self.tour else { return };
+        if let TourOutcome::Finished = tour.render(ctx, self.theme_mode) {
+            for id in &tour.ids { if !self.tours_seen.iter().any(|t| t == id) { self.tours_seen.push(id.to_string()); } }
+            self.tour = None;
+            self.save_settings();
+        }
+    }
+
+    fn render_focus_pill(&mut self, ctx: &egui::Context) {
+        egui::Area::new(egui::Id::new("focus_mode_pill")).anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 6.0)).order(egui::Order::Foreground).show(ctx, |ui| {
+            let hovered = ui.rect_contains_pointer(ui.max_rect().expand(24.0));
+            egui::Frame::new().fill(ui.visuals().window_fill.gamma_multiply(if hovered { 1.0 } else { 0.6 })).corner_radius(12.0)
+                .stroke(egui::Stroke::new(1.0, ui.visuals().widgets.noninteractive.bg_stroke.color)).inner_margin(egui::Margin::symmetric(10, 2))
+                .show(ui, |ui| {
+                    let text = egui::RichText::new("Exit Focus Mode (F11)").size(11.0).color(ui.visuals().weak_text_color());
+                    if ui.add(egui::Label::new(text).sense(egui::Sense::click())).on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { self.focus_mode = false; }
+                });
+        });
     }
 
     fn sidebar(&mut self, ctx: &egui::Context) {
         if !self.sidebar_open { return; }
-        egui::SidePanel::left("sidebar").resizable(true).default_width(240.0).min_width(200.0).show(ctx, |ui| {
+        let panel = egui::SidePanel::left("sidebar").resizable(true).default_width(240.0).min_width(200.0).show(ctx, |ui| {
             egui::ScrollArea::vertical().auto_shrink([false, false]).show(ui, |ui| {
                 ui.add_space(8.0);
                 let mut open_screen: Option<&'static str> = None;
@@ -567,6 +1202,7 @@ impl UniversalEditor {
                 ui.add_space(8.0);
             });
         });
+        crate::tour::anchor(ctx, "sidebar", panel.response.rect);
     }
 
     fn rename_modal(&mut self, ctx: &egui::Context) {
@@ -749,21 +1385,25 @@ impl UniversalEditor {
             entries.retain(|e| std::path::Path::new(&e.src_path).exists());
             self.cache_entries = Some(entries);
         }
+        let mut remember_tools = self.remember_tools;
+        let mut reset_tools = false;
 
         let mut hdr_close = false;
         let mut sys_c = false; let mut light_c = false; let mut dark_c = false;
         let mut prefs_changed = false;
+        let mut profile_io: Option<bool> = None;
+        let mut focus_c = false;
         let mut to_delete: Option<usize> = None;
 
         let outside = style::main_menu_modal(ctx, "settings_mw", theme, 440.0, |ui| {
-            if style::main_menu_modal_header(ui, "Settings", "", theme) { hdr_close = true; }
+            if style::main_menu_modal_header(ui, &tr("Settings"), "", theme) { hdr_close = true; }
             egui::Frame::new().inner_margin(egui::Margin { left: 24, right: 24, top: 10, bottom: 4 }).show(ui, |ui| {
                 ui.horizontal(|ui| {
                     ui.spacing_mut().item_spacing.x = 0.0;
                     for (tab, label) in &[(SettingsTab::General, "General"), (SettingsTab::TextEditor, "Text Editor"), (SettingsTab::Cache, "Image Editor"), (SettingsTab::JsonEditor, "JSON Editor")] {
                         let sel = self.settings_tab == *tab;
                         let (fill, tc) = if sel { (if is_dark { egui::Color32::from_rgb(40, 40, 50) } else { ColorPalette::STONE_150 }, text) } else { (egui::Color32::TRANSPARENT, muted) };
-                        if ui.add(egui::Button::new(egui::RichText::new(*label).size(12.0).color(tc)).fill(fill).corner_radius(6.0)).on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { self.settings_tab = *tab; }
+                        if ui.add(egui::Button::new(egui::RichText::new(tr(label)).size(12.0).color(tc)).fill(fill).corner_radius(6.0)).on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { self.settings_tab = *tab; }
                         ui.add_space(4.0);
                     }
                 });
@@ -774,34 +1414,113 @@ impl UniversalEditor {
                 egui::Frame::new().inner_margin(egui::Margin { left: 28, right: 28, top: 16, bottom: 20 }).show(ui, |ui| {
                     match self.settings_tab {
                         SettingsTab::General => {
-                            ui.label(egui::RichText::new("APPEARANCE").size(11.0).color(muted));
+                            ui.label(egui::RichText::new(tr("APPEARANCE")).size(11.0).color(muted));
+                            ui.add_space(10.0);
+                            ui.horizontal(|ui| {
+                                ui.label(egui::RichText::new(tr("Theme")).size(14.0).color(text));
+                                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                                    dark_c = ui.selectable_label(matches!(self.theme_preference, ThemePreference::Dark), tr("Dark")).on_hover_cursor(egui::CursorIcon::PointingHand).clicked();
+                                    light_c = ui.selectable_label(matches!(self.theme_preference, ThemePreference::Light), tr("Light")).on_hover_cursor(egui::CursorIcon::PointingHand).clicked();
+                                    sys_c = ui.selectable_label(matches!(self.theme_preference, ThemePreference::System), tr("System")).on_hover_cursor(egui::CursorIcon::PointingHand).clicked();
+                                });
+                            });
+                            ui.add_space(8.0);
+                            ui.horizontal(|ui| {
+                                ui.label(egui::RichText::new(tr("Language")).size(14.0).color(text));
+                                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                                    let current = i18n::LOCALES.iter().find(|l| l.code == self.language).map_or("English", |l| l.name);
+                                    egui::ComboBox::from_id_salt("settings_language").selected_text(current).width(120.0).show_ui(ui, |ui| {
+                                        for locale in &i18n::LOCALES {
+                                            if ui.selectable_label(self.language == locale.code, locale.name).clicked() && self.language != locale.code {
+                                                self.language = locale.code.to_string(); i18n::set_locale(locale.code); prefs_changed = true;
+                                            }
+                                        }
+                                    });
+                                });
+                            });
+                            ui.add_space(16.0);
+                            ui.label(egui::RichText::new(tr("ACCESSIBILITY")).size(11.0).color(muted));
+                            ui.add_space(10.0);
+                            ui.horizontal(|ui| {
+                                ui.label(egui::RichText::new(tr("High-Visibility Focus Outline")).size(14.0).color(text));
+                                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                                    if ui.checkbox(&mut self.high_visibility_focus, "").changed() { focus_c = true; }
+                                });
+                            });
+                            ui.add_space(16.0);
+                            ui.label(egui::RichText::new(tr("PRIVACY")).size(11.0).color(muted));
+                            ui.add_space(10.0);
+                            ui.horizontal(|ui| {
+                                ui.label(egui::RichText::new(tr("Keep Clipboard History Between Sessions")).size(14.0).color(text));
+                                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                                    if ui.checkbox(&mut self.persist_clipboard, "").changed() { self.clipboard.set_persist(self.persist_clipboard); prefs_changed = true; }
+                                });
+                            });
+                            ui.add_space(6.0);
+                            ui.label(egui::RichText::new(tr("Entries that look like passwords, keys or card numbers are never written to disk.")).size(11.0).color(muted).italics());
+                            ui.add_space(10.0);
+                            ui.horizontal(|ui| {
+                                ui.label(egui::RichText::new(tr("Keep a Backup of Replaced Files")).size(14.0).color(text));
+                                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                                    if ui.checkbox(&mut self.keep_backup, "").changed() { prefs_changed = true; }
+                                });
+                            });
+                            ui.add_space(6.0);
+                            ui.label(egui::RichText::new(tr("When Save As replaces an existing file, the old file goes to the system trash instead of being destroyed.")).size(11.0).color(muted).italics());
+                            ui.add_space(16.0);
+                            ui.label(egui::RichText::new(tr("UPDATES")).size(11.0).color(muted));
+                            ui.add_space(10.0);
+                            ui.horizontal(|ui| {
+                                ui.label(egui::RichText::new(tr("Check for Updates")).size(14.0).color(text));
+                                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                                    if ui.checkbox(&mut self.check_for_updates, "").changed() {
+                                        if !self.check_for_updates { self.update_check = None; }
+                                        self.update_check_asked = true;
+                                        prefs_changed = true;
+                                    }
+                                });
+                            });
+                            if let Some(v) = self.skipped_update_version.clone() {
+                                ui.add_space(6.0);
+                                ui.horizontal(|ui| {
+                                    ui.label(egui::RichText::new(i18n::tr_args("Skipping version {version}", &[("version", &v)])).size(12.0).color(muted));
+                                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                                        if ui.small_button(tr("Reset")).on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { self.skipped_update_version = None; prefs_changed = true; }
+                                    });
+                                });
+                            }
+                            ui.add_space(6.0);
+                            ui.label(egui::RichText::new(tr("When off, Universal Editor never contacts the network.")).size(11.0).color(muted).italics());
+                            ui.add_space(16.0);
+                            ui.label(egui::RichText::new(tr("PROFILE")).size(11.0).color(muted));
                             ui.add_space(10.0);
                             ui.horizontal(|ui| {
-                                ui.label(egui::RichText::new("Theme").size(14.0).color(text));
+                                ui.label(egui::RichText::new(tr("Settings, Brushes and Presets")).size(14.0).color(text));
                                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                                    dark_c = ui.selectable_label(matches!(self.theme_preference, ThemePreference::Dark), "Dark").on_hover_cursor(egui::CursorIcon::PointingHand).clicked();
-                                    light_c = ui.selectable_label(matches!(self.theme_preference, ThemePreference::Light), "Light").on_hover_cursor(egui::CursorIcon::PointingHand).clicked();
-                                    sys_c = ui.selectable_label(matches!(self.theme_preference, ThemePreference::System), "System").on_hover_cursor(egui::CursorIcon::PointingHand).clicked();
+                                    if ui.small_button(tr("Import Profile...")).on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { profile_io = Some(false); }
+                                    if ui.small_button(tr("Export Profile...")).on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { profile_io = Some(true); }
                                 });
                             });
+                            ui.add_space(6.0);
+                            ui.label(egui::RichText::new(tr("Copy your setup to another machine as a single file.")).size(11.0).color(muted).italics());
                         }
                         SettingsTab::TextEditor => {
-                            ui.label(egui::RichText::new("DISPLAY").size(11.0).color(muted));
+                            ui.label(egui::RichText::new(tr("DISPLAY")).size(11.0).color(muted));
                             ui.add_space(10.0);
                             ui.horizontal(|ui| {
-                                ui.label(egui::RichText::new("Show Toolbar").size(14.0).color(text));
+                                ui.label(egui::RichText::new(tr("Show Toolbar")).size(14.0).color(text));
                                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| { if ui.checkbox(&mut self.show_toolbar_te, "").changed() { prefs_changed = true; } });
                             });
                             ui.add_space(6.0);
                             ui.horizontal(|ui| {
-                                ui.label(egui::RichText::new("Show File Info").size(14.0).color(text));
+                                ui.label(egui::RichText::new(tr("Show File Info")).size(14.0).color(text));
                                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| { if ui.checkbox(&mut self.show_file_info_te, "").changed() { prefs_changed = true; } });
                             });
                             ui.add_space(16.0);
-                            ui.label(egui::RichText::new("TYPOGRAPHY").size(11.0).color(muted));
+                            ui.label(egui::RichText::new(tr("TYPOGRAPHY")).size(11.0).color(muted));
                             ui.add_space(10.0);
                             ui.horizontal(|ui| {
-                                ui.label(egui::RichText::new("Default Font:").size(14.0).color(text));
+                                ui.label(egui::RichText::new(tr("Default Font:")).size(14.0).color(text));
                             });
                             ui.add_space(4.0);
                             ui.horizontal_wrapped(|ui| {
@@ -827,27 +1546,155 @@ impl UniversalEditor {
                             });
                             ui.add_space(6.0);
                             ui.horizontal(|ui| {
-                                ui.label(egui::RichText::new("Default Font Size:").size(14.0).color(text));
+                                ui.label(egui::RichText::new(tr("Default Font Size:")).size(14.0).color(text));
                                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                                     if ui.add(egui::DragValue::new(&mut self.default_font_size).range(8.0..=72.0).speed(0.5).suffix(" pt")).changed() { prefs_changed = true; }
                                 });
                             });
+                            ui.add_space(16.0);
+                            ui.label(egui::RichText::new(tr("PASTE AS CLEAN TEXT (CTRL+SHIFT+V)")).size(11.0).color(muted));
+                            ui.add_space(10.0);
+                            let mut paste_changed = false;
+                            for (label, flag) in [
+                                ("Smart quotes and dashes to ASCII", &mut self.paste_cleanup.ascii_punctuation),
+                                ("Strip zero-width and BOM characters", &mut self.paste_cleanup.strip_invisible),
+                                ("Non-breaking spaces to spaces", &mut self.paste_cleanup.nbsp_to_space),
+                                ("Normalize line endings", &mut self.paste_cleanup.normalize_line_endings),
+                            ] {
+                                ui.horizontal(|ui| {
+                                    ui.label(egui::RichText::new(tr(label)).size(14.0).color(text));
+                                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| { if ui.checkbox(flag, "").changed() { paste_changed = true; } });
+                                });
+                                ui.add_space(6.0);
+                            }
+                            if paste_changed { prefs_changed = true; }
+                            ui.add_space(16.0);
+                            ui.label(egui::RichText::new(tr("LARGE FILES")).size(11.0).color(muted));
+                            ui.add_space(10.0);
+                            ui.horizontal(|ui| {
+                                ui.label(egui::RichText::new(tr("Ask before opening files larger than")).size(14.0).color(text));
+                                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                                    if ui.add(egui::DragValue::new(&mut self.large_file_mb).range(1..=8192).speed(5.0).suffix(" MB")).changed() {
+                                        prefs_changed = true;
+                                    }
+                                });
+                            });
+                            ui.add_space(16.0);
+                            ui.label(egui::RichText::new(tr("EDITING")).size(11.0).color(muted));
+                            ui.add_space(10.0);
+                            ui.horizontal(|ui| {
+                                ui.label(egui::RichText::new(tr("Suggest words from the document")).size(14.0).color(text));
+                                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                                    if ui.checkbox(&mut self.word_completion, "").changed() { prefs_changed = true; }
+                                });
+                            });
+                            ui.add_space(6.0);
+                            ui.horizontal(|ui| {
+                                ui.label(egui::RichText::new(tr("Show swatches under color codes")).size(14.0).color(text));
+                                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                                    if ui.checkbox(&mut self.color_swatches, "").changed() { prefs_changed = true; }
+                                });
+                            });
+                            ui.add_space(6.0);
+                            ui.horizontal(|ui| {
+                                ui.label(egui::RichText::new(tr("Skip color codes inside comments")).size(14.0).color(text));
+                                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                                    let resp = ui.add_enabled(self.color_swatches, egui::Checkbox::without_text(&mut self.skip_comment_colors));
+                                    if resp.changed() { prefs_changed = true; }
+                                });
+                            });
+                            ui.add_space(6.0);
+                            ui.horizontal(|ui| {
+                                ui.label(egui::RichText::new(tr("Keep undo history after closing a file")).size(14.0).color(text));
+                                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                                    if ui.checkbox(&mut self.persistent_undo, "").changed() {
+                                        if !self.persistent_undo { TextEditor::forget_saved_undo(); }
+                                        prefs_changed = true;
+                                    }
+                                });
+                            });
+                            ui.add_space(6.0);
+                            ui.label(egui::RichText::new(tr("Edits are stored in the settings folder when you save, up to 256 KB per file. Turning this off deletes them.")).size(11.0).color(muted).italics());
+                            ui.add_space(16.0);
+                            ui.label(egui::RichText::new(tr("FOCUS")).size(11.0).color(muted));
+                            ui.add_space(10.0);
+                            ui.horizontal(|ui| {
+                                ui.label(egui::RichText::new(tr("Highlight the current line")).size(14.0).color(text));
+                                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                                    if ui.checkbox(&mut self.highlight_current_line, "").changed() { prefs_changed = true; }
+                                });
+                            });
+                            ui.add_space(6.0);
+                            ui.horizontal(|ui| {
+                                ui.label(egui::RichText::new(tr("Typewriter scrolling")).size(14.0).color(text));
+                                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                                    if ui.checkbox(&mut self.typewriter_scrolling, "").changed() { prefs_changed = true; }
+                                });
+                            });
+                            ui.add_space(6.0);
+                            ui.label(egui::RichText::new(tr("Keeps the line you are typing on centered in the window.")).size(11.0).color(muted).italics());
                         }
                         SettingsTab::JsonEditor => {
-                            ui.label(egui::RichText::new("DISPLAY").size(11.0).color(muted));
+                            ui.label(egui::RichText::new(tr("DISPLAY")).size(11.0).color(muted));
                             ui.add_space(10.0);
                             ui.horizontal(|ui| {
-                                ui.label(egui::RichText::new("Show File Info").size(14.0).color(text));
+                                ui.label(egui::RichText::new(tr("Show File Info")).size(14.0).color(text));
                                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| { if ui.checkbox(&mut self.show_file_info_je, "").changed() { prefs_changed = true; } });
                             });
                         }
                         SettingsTab::Cache => {
+                            ui.label(egui::RichText::new(tr("TOOLS")).size(11.0).color(muted));
+                            ui.add_space(4.0);
+                            ui.horizontal(|ui| {
+                                ui.label(egui::RichText::new(tr("Remember tool settings between sessions")).size(14.0).color(text));
+                                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                                    ui.checkbox(&mut remember_tools, "");
+                                });
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label(egui::RichText::new(tr("New image tabs start with your last tool and its options.")).size(11.0).color(muted).italics());
+                                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                                    if ui.small_button(tr("Reset tool defaults")).on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { reset_tools = true; }
+                                });
+                            });
+                            ui.add_space(6.0);
+                            ui.horizontal(|ui| {
+                                ui.label(egui::RichText::new(tr("Use GPU acceleration")).size(14.0).color(text));
+                                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                                    if ui.checkbox(&mut self.use_gpu, "").changed() { prefs_changed = true; }
+                                });
+                            });
+                            ui.label(egui::RichText::new(tr("Blur and resize run on the graphics card when one is available, falling back to the CPU otherwise.")).size(11.0).color(muted).italics());
+                            ui.add_space(6.0);
+                            ui.horizontal(|ui| {
+                                ui.label(egui::RichText::new(tr("Largest new or resized canvas")).size(14.0).color(text));
+                                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                                    if ui.add(egui::DragValue::new(&mut self.max_canvas_mp).range(1..=4096).speed(4.0).suffix(" MP")).changed() {
+                                        prefs_changed = true;
+                                    }
+                                });
+                            });
+                            ui.label(egui::RichText::new(tr("Each megapixel takes about 4 MB of memory, more with layers and undo.")).size(11.0).color(muted).italics());
+                            ui.add_space(6.0);
+                            let before = self.cursor_outline;
+                            ui.horizontal(|ui| {
+                                ui.label(egui::RichText::new(tr("Cursor outline")).size(14.0).color(text));
+                                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                                    ui.add(egui::DragValue::new(&mut self.cursor_outline.width).range(0.5..=6.0).speed(0.1).suffix(" px"));
+                                    egui::ComboBox::from_id_salt("cursor_outline_color").selected_text(self.cursor_outline.color.label()).show_ui(ui, |ui| {
+                                        for c in CursorColor::ALL { ui.selectable_value(&mut self.cursor_outline.color, c, c.label()); }
+                                    });
+                                });
+                            });
+                            ui.label(egui::RichText::new(tr("Brush, fill, eyedropper and crop cursors are drawn with this outline.")).size(11.0).color(muted).italics());
+                            if self.cursor_outline != before { prefs_changed = true; }
+                            ui.add_space(12.0);
                             let count = self.cache_entries.as_ref().map(|v| v.len()).unwrap_or(0);
                             let total_kb: u64 = self.cache_entries.as_ref().map(|v| v.iter().map(|e| e.size_kb).sum()).unwrap_or(0);
                             ui.horizontal(|ui| {
-                                ui.label(egui::RichText::new("LAYER CACHES").size(11.0).color(muted));
+                                ui.label(egui::RichText::new(tr("LAYER CACHES")).size(11.0).color(muted));
                                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                                    if ui.add_enabled(count > 0, egui::Button::new(egui::RichText::new("Clear All").size(12.0).color(if is_dark { ColorPalette::RED_400 } else { ColorPalette::RED_600 }))).on_hover_cursor(egui::CursorIcon::PointingHand).clicked() {
+                                    if ui.add_enabled(count > 0, egui::Button::new(egui::RichText::new(tr("Clear All")).size(12.0).color(if is_dark { ColorPalette::RED_400 } else { ColorPalette::RED_600 }))).on_hover_cursor(egui::CursorIcon::PointingHand).clicked() {
                                         ie_cache::delete_all_caches(); self.cache_entries = Some(Vec::new());
                                     }
                                 });
@@ -855,7 +1702,7 @@ impl UniversalEditor {
                             ui.label(egui::RichText::new(format!("{} cached files  ·  {} KB total", count, total_kb)).size(12.0).color(muted));
                             ui.add_space(8.0);
                             if count == 0 {
-                                ui.label(egui::RichText::new("No layer caches stored.").size(13.0).color(muted).italics());
+                                ui.label(egui::RichText::new(tr("No layer caches stored.")).size(13.0).color(muted).italics());
                             } else {
                                 egui::ScrollArea::vertical().max_height(220.0).id_salt("cache_scroll").show(ui, |ui| {
                                     if let Some(ref entries_vec) = self.cache_entries {
@@ -869,8 +1716,8 @@ impl UniversalEditor {
                                                     });
                                                     ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                                                         ui.label(egui::RichText::new(format!("{} KB", entry.size_kb)).size(11.0).color(muted));
-                                                        if ui.button(egui::RichText::new("Delete").size(11.0)).on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { to_delete = Some(i); }
-                                                        if ui.button(egui::RichText::new("Open").size(11.0)).on_hover_cursor(egui::CursorIcon::PointingHand).on_hover_text("Open cache metadata in JSON Editor").clicked() {
+                                                        if ui.button(egui::RichText::new(tr("Delete")).size(11.0)).on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { to_delete = Some(i); }
+                                                        if ui.button(egui::RichText::new(tr("Open")).size(11.0)).on_hover_cursor(egui::CursorIcon::PointingHand).on_hover_text(tr("Open cache metadata in JSON Editor")).clicked() {
                                                             self.open_cache_path = Some(entry.cache_dir.join("meta.json"));
                                                         }
                                                     });
@@ -882,18 +1729,31 @@ impl UniversalEditor {
                                 });
                             }
                             ui.add_space(8.0);
-                            ui.label(egui::RichText::new("Layer caches are automatically cleared if the source image is modified outside this application.").size(11.0).color(muted).italics());
+                            ui.label(egui::RichText::new(tr("Layer caches are automatically cleared if the source image is modified outside this application.")).size(11.0).color(muted).italics());
                         }
                     }
                 });
             });
         });
 
+        if remember_tools != self.remember_tools {
+            self.remember_tools = remember_tools;
+            ImageEditor::set_remember_tool_settings(remember_tools);
+            self.apply_editor_settings();
+        }
+        if reset_tools {
+            ImageEditor::reset_tool_defaults();
+            let mut settings = self.editor_settings();
+            settings.image.reset_tools = true;
+            if let Some(m) = &mut self.active_module { m.apply_settings(&settings); }
+        }
         if outside || hdr_close { self.show_settings = false; self.cache_entries = None; }
-        if sys_c { self.theme_preference = ThemePreference::System; self.theme_mode = match ctx.theme() { egui::Theme::Dark => ThemeMode::Dark, egui::Theme::Light => ThemeMode::Light }; style::apply_theme(ctx, self.theme_mode); self.save_settings(); }
-        if light_c { self.theme_preference = ThemePreference::Light; self.theme_mode = ThemeMode::Light; style::apply_theme(ctx, self.theme_mode); self.save_settings(); }
-        if dark_c { self.theme_preference = ThemePreference::Dark; self.theme_mode = ThemeMode::Dark; style::apply_theme(ctx, self.theme_mode); self.save_settings(); }
-        if prefs_changed { self.save_settings(); }
+        if sys_c { self.theme_preference = ThemePreference::System; self.theme_mode = match ctx.theme() { egui::Theme::Dark => ThemeMode::Dark, egui::Theme::Light => ThemeMode::Light }; self.apply_style(ctx); self.save_settings(); }
+        if light_c { self.theme_preference = ThemePreference::Light; self.theme_mode = ThemeMode::Light; self.apply_style(ctx); self.save_settings(); }
+        if dark_c { self.theme_preference = ThemePreference::Dark; self.theme_mode = ThemeMode::Dark; self.apply_style(ctx); self.save_settings(); }
+        if focus_c { self.apply_style(ctx); self.save_settings(); }
+        if prefs_changed { self.save_settings(); self.apply_editor_settings(); }
+        match profile_io { Some(true) => self.export_profile(ctx), Some(false) => self.import_profile(ctx), None => {} }
         if let Some(idx) = to_delete {
             if let Some(ref v) = self.cache_entries {
                 if let Some(e) = v.get(idx) { let _ = std::fs::remove_dir_all(&e.cache_dir); }
@@ -902,6 +1762,70 @@ impl UniversalEditor {
         }
     }
 
+    fn track_paste_target(&mut self, ctx: &egui::Context) {
+        let focused = ctx.memory(|m| m.focused());
+        if let Some(id) = focused.filter(|id| egui::TextEdit::load_state(ctx, *id).is_some()) { self.paste_target = Some(id); }
+        else if focused.is_none() && !self.show_clipboard && !egui::Popup::is_any_open(ctx) && ctx.input(|i| i.pointer.any_pressed()) { self.paste_target = None; }
+    }
+
+    fn render_clipboard_history(&mut self, ctx: &egui::Context) {
+        if !self.show_clipboard { return; }
+        let can_paste = self.paste_target.is_some() || self.active_module.as_ref().is_some_and(|m| m.accepts_text_paste());
+        let mut open = true;
+        let outcome = self.clipboard.show(ctx, self.theme_mode, can_paste, &mut open);
+        if !open || ctx.input(|i| i.key_pressed(egui::Key::Escape)) { self.show_clipboard = false; }
+        if let Some(ClipOutcome::Paste(text)) = outcome && can_paste {
+            if let Some(id) = self.paste_target { ctx.memory_mut(|m| m.request_focus(id)); }
+            self.pending_paste = Some(text);
+            self.show_clipboard = false;
+            ctx.request_repaint();
+        }
+    }
+
+    fn render_shortcuts_modal(&mut self, ctx: &egui::Context) {
+        if !self.show_shortcuts { return; }
+        let theme = self.theme_mode;
+        let is_dark = matches!(theme, ThemeMode::Dark);
+        let (muted, text) = if is_dark { (ColorPalette::ZINC_500, ColorPalette::SLATE_200) } else { (ColorPalette::STONE_400, ColorPalette::STONE_800) };
+        let key_bg = if is_dark { ColorPalette::ZINC_800 } else { ColorPalette::GRAY_100 };
+        let module_title = self.active_module.as_ref().map(|m| m.get_title());
+        let module_keys = self.active_module.as_ref().map_or_else(Vec::new, |m| m.key_bindings());
+        let query = self.shortcut_query.trim().to_lowercase();
+        let sections = shortcut_sections(module_title.as_deref().map(|t| (t, &module_keys[..])), &query);
+        let search = &mut self.shortcut_query;
+        let mut hdr_close = false;
+        let outside = style::main_menu_modal(ctx, "shortcuts_mw", theme, 520.0, |ui| {
+            if style::main_menu_modal_header(ui, &tr("Keyboard Shortcuts"), "", theme) { hdr_close = true; }
+            egui::Frame::new().inner_margin(egui::Margin { left: 28, right: 28, top: 12, bottom: 0 }).show(ui, |ui| {
+                ui.add(egui::TextEdit::singleline(search).hint_text(tr("Search all shortcuts…")).desired_width(f32::INFINITY));
+            });
+            egui::ScrollArea::vertical().max_height(440.0).auto_shrink([false, true]).show(ui, |ui| {
+                egui::Frame::new().inner_margin(egui::Margin { left: 28, right: 28, top: 16, bottom: 16 }).show(ui, |ui| {
+                    let section = |ui: &mut egui::Ui, title: &str, keys: &[KeyBinding]| {
+                        ui.label(egui::RichText::new(tr(title)).size(11.0).color(muted));
+                        ui.add_space(8.0);
+                        egui::Grid::new(title).num_columns(2).spacing(egui::vec2(16.0, 6.0)).show(ui, |ui| {
+                            for b in keys {
+                                egui::Frame::new().fill(key_bg).corner_radius(4.0).inner_margin(egui::Margin { left: 6, right: 6, top: 2, bottom: 2 })
+                                    .show(ui, |ui| { ui.label(egui::RichText::new(&b.keys).size(12.0).monospace().color(text)); });
+                                ui.label(egui::RichText::new(tr(b.action)).size(13.0).color(text));
+                                ui.end_row();
+                            }
+                        });
+                        ui.add_space(14.0);
+                    };
+                    for (title, keys) in &sections { section(ui, title, keys); }
+                    let hint = if !query.is_empty() { (sections.is_empty()).then_some("No shortcuts match your search.") }
+                        else if module_title.is_none() { Some("Open a file to see its editor's shortcuts, or search to browse every editor.") }
+                        else if module_keys.is_empty() { Some("This editor has no extra shortcuts.") }
+                        else { None };
+                    if let Some(hint) = hint { ui.label(egui::RichText::new(tr(hint)).size(12.0).color(muted).italics()); }
+                });
+            });
+        });
+        if outside || hdr_close { self.show_shortcuts = false; }
+    }
+
     fn render_patch_notes_modal(&mut self, ctx: &egui::Context) {
         if !self.show_patch_notes { return; }
         let theme = self.theme_mode;
@@ -994,7 +1918,8 @@ impl UniversalEditor {
 
         let outside = style::main_menu_modal(ctx, "about_mw", theme, 640.0, |ui| {
             ui.set_min_height(600.0);
-            if style::main_menu_modal_header(ui, "Universal Editor", &format!("v{}  ·  Built with Rust + egui", env!("CARGO_PKG_VERSION")), theme) { hdr_close = true; }
+            let build = format!("v{}  ·  {} {} {} build  ·  Built with Rust + egui", env!("CARGO_PKG_VERSION"), std::env::consts::OS, std::env::consts::ARCH, if cfg!(debug_assertions) { "debug" } else { "release" });
+            if style::main_menu_modal_header(ui, "Universal Editor", &build, theme) { hdr_close = true; }
 
             egui::ScrollArea::vertical().max_height(880.0).auto_shrink([false, true]).show(ui, |ui| {
                 egui::Frame::new().inner_margin(egui::Margin { left: 28, right: 28, top: 16, bottom: 4 }).show(ui, |ui| {
@@ -1146,44 +2071,106 @@ impl UniversalEditor {
 }
 
 impl eframe::App for UniversalEditor {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        if self.renderer.is_none() { self.renderer = Some(diagnostics::renderer(frame)); }
+        self.taskbar.update(frame, self.active_module.as_ref().and_then(|m| m.progress()));
         if matches!(self.theme_preference, ThemePreference::System) {
             let system_theme = match ctx.theme() { egui::Theme::Dark => ThemeMode::Dark, egui::Theme::Light => ThemeMode::Light };
-            if self.theme_mode != system_theme { self.theme_mode = system_theme; style::apply_theme(ctx, self.theme_mode); }
+            if self.theme_mode != system_theme { self.theme_mode = system_theme; self.apply_style(ctx); }
         }
 
+        let cycle = ctx.input_mut(|i| pressed_shortcuts(i, APP_SHORTCUTS, |a| a == AppKey::FocusRegion)).first().map(|&(_, chord)| chord == 1);
+        crate::focus::begin_frame(ctx, cycle);
+        self.track_paste_target(ctx);
         while let Ok(path) = self.recent_file_rx.try_recv() { self.recent_files.add_file(path); }
         while let Ok((old, new)) = self.path_replace_rx.try_recv() { self.recent_files.remove_file(&old); self.recent_files.add_file(new); }
 
         if let Some(path) = self.open_cache_path.take() {
             self.show_settings = false;
             self.cache_entries = None;
-            self.active_module = Some(Box::new(JsonEditor::load(path)));
+            self.set_active(Some(Box::new(JsonEditor::load(path))));
         }
 
+        if self.awaiting_save && !self.active_module.as_ref().is_some_and(|m| m.save_pending()) {
+            self.awaiting_save = false;
+            if self.has_unsaved_changes() { self.pending_action = None; }
+            else if matches!(self.pending_action, Some(PendingAction::Exit)) { ctx.send_viewport_cmd(egui::ViewportCommand::Close); }
+            else { self.execute_pending_action(); }
+        }
         if let Some(PendingAction::Exit) = &self.pending_action {
-            if !self.show_unsaved_dialog { ctx.send_viewport_cmd(egui::ViewportCommand::Close); }
+            if !self.show_unsaved_dialog && !self.awaiting_save { ctx.send_viewport_cmd(egui::ViewportCommand::Close); }
         }
-
-        if !self.show_unsaved_dialog && !self.show_settings && !self.show_patch_notes && !self.show_about {
-            ctx.input_mut(|i| { if i.consume_key(egui::Modifiers::CTRL, egui::Key::Backslash) { self.sidebar_open = !self.sidebar_open; } });
+        self.open_files.update(self.active_module.as_ref().and_then(|m| m.file_path()));
+
+        let modal = self.show_unsaved_dialog || self.show_settings || self.show_patch_notes || self.show_about;
+        let unfocused = ctx.memory(|m| m.focused().is_none());
+        let live = |a| match a {
+            AppKey::FocusMode => true,
+            AppKey::Sidebar | AppKey::Reopen => !modal,
+            AppKey::Shortcuts => !modal && unfocused,
+            AppKey::FocusRegion => false,
+        };
+        for (action, _) in ctx.input_mut(|i| pressed_shortcuts(i, APP_SHORTCUTS, live)) {
+            match action {
+                AppKey::FocusMode => self.focus_mode = !self.focus_mode,
+                AppKey::Sidebar => self.sidebar_open = !self.sidebar_open,
+                AppKey::Reopen => self.reopen_closed(ctx, 0),
+                AppKey::Shortcuts => self.show_shortcuts = !self.show_shortcuts,
+                AppKey::FocusRegion => {}
+            }
         }
 
         self.render_unsaved_dialog(ctx);
         self.render_settings_modal(ctx);
         self.render_patch_notes_modal(ctx);
         self.render_about_modal(ctx);
+        self.render_shortcuts_modal(ctx);
         self.rename_modal(ctx);
-        self.top_bar(ctx);
-        self.sidebar(ctx);
+        self.render_template_dialogs(ctx);
+        if self.focus_mode {
+            self.render_focus_pill(ctx);
+        } else {
+            self.top_bar(ctx);
+            self.sidebar(ctx);
+        }
 
-        let show_fi = if self.is_in_json_editor() { self.show_file_info_je } else { self.show_file_info_te };
-        let show_toolbar = self.show_toolbar_te;
+        let show_fi = !self.focus_mode && if self.is_in_json_editor() { self.show_file_info_je } else { self.show_file_info_te };
+        let show_toolbar = self.show_toolbar_te && !self.focus_mode;
+        if let Some(module) = &mut self.active_module { module.set_focus_mode(self.focus_mode); }
         egui::CentralPanel::default().show(ctx, |ui| {
             if let Some(module) = &mut self.active_module { module.ui(ui, ctx, show_toolbar, show_fi); }
             else { self.landing_page(ui); }
         });
-
+        self.keep_editor_settings();
+
+        let config_warnings = config_file::take_warnings();
+        if !config_warnings.is_empty() { self.notify(ctx, config_warnings.join("\n")); }
+        let toasts = tasks::take_toasts();
+        if !toasts.is_empty() { self.notify(ctx, toasts.join("\n")); }
+        let module_error = self.active_module.as_mut().and_then(|m| m.take_error());
+        if let Some(e) = &module_error { log::error!("{:?}", e); }
+        if let Some(msg) = module_error.and_then(|e| e.user_message()) { self.notify(ctx, msg); }
+        if let Some(msg) = self.active_module.as_mut().and_then(|m| m.take_notice()) { self.notify(ctx, msg); }
+        self.render_clipboard_history(ctx);
+        self.render_profile_import(ctx);
+        self.render_export_all(ctx);
+        self.update_window_title(ctx);
+        self.render_notification(ctx);
+        if !self.focus_mode { self.render_tour(ctx); }
+        if self.tour.is_none() { self.render_update_prompt(ctx); }
+        self.poll_update_check(ctx);
+        self.render_update_dialog(ctx);
+        self.render_problem_report(ctx);
+
+        self.handle_dropped_files(ctx);
+        self.render_drop_overlay(ctx);
+
+        if let Some(path) = self.active_module.as_mut().and_then(|m| m.take_share()) {
+            match share::share_file(frame, &path) {
+                Ok(how) => if let Some(msg) = share::notice(&how, &path) { self.notify(ctx, msg); },
+                Err(e) => self.report::<()>(ctx, Err(e)),
+            }
+        }
         let converter_path = self.active_module.as_mut().and_then(|m| m.take_converter_path());
         if let Some(path) = converter_path {
             let mut converter = crate::modules::data_converter::DataConverter::new();
@@ -1197,10 +2184,80 @@ impl eframe::App for UniversalEditor {
                 let mut editor = ImageEditor::from_image(img);
                 let tx = self.recent_file_tx.clone();
                 editor.set_file_callback(Box::new(move |p: PathBuf| { let _ = tx.send(p); }));
+                editor.set_path_replace_tx(self.path_replace_tx.clone());
                 self.switch_to_module(Box::new(editor));
             }
         }
 
+        if let Some(module) = self.active_module.as_mut().and_then(|m| m.take_new_document()) { self.switch_to_module(module); }
+        if self.active_module.as_mut().is_some_and(|m| m.take_close_request()) {
+            if let Some(mut old) = self.active_module.take() { old.unload(); }
+            self.menu_cache = None;
+        }
+        if let Some((path, create)) = self.active_module.as_mut().and_then(|m| m.take_reopen_request()) {
+            if let Some(mut old) = self.active_module.take() { old.unload(); }
+            let m = self.instantiate(create, Some(path));
+            self.active_module = Some(m);
+            self.menu_cache = None;
+            self.apply_editor_settings();
+        }
+
         if self.show_unsaved_dialog { ctx.set_cursor_icon(egui::CursorIcon::Default); }
+        self.clipboard.capture(ctx);
+    }
+
+    fn raw_input_hook(&mut self, _ctx: &egui::Context, raw_input: &mut egui::RawInput) {
+        if let Some(text) = self.pending_paste.take() { raw_input.events.push(egui::Event::Paste(text)); }
+    }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        if let Some(module) = self.active_module.as_mut() { module.unload(); }
+    }
+}
+
+/// The Keyboard Shortcuts list, read from the same registries the app and editors dispatch from: the app's keys and the
+/// open editor's, and with a search, matches from every editor.
+fn shortcut_sections(module: Option<(&str, &[KeyBinding])>, query: &str) -> Vec<(String, Vec<KeyBinding>)> {
+    let filter = |keys: Vec<KeyBinding>| -> Vec<KeyBinding> {
+        keys.into_iter().filter(|b| query.is_empty() || b.keys.to_lowercase().contains(query) || b.action.to_lowercase().contains(query)).collect()
+    };
+    let module_keys = module.map_or(&[][..], |(_, keys)| keys);
+    let mut sections = vec![("GENERAL".to_string(), filter(crate::modules::key_bindings(APP_SHORTCUTS)))];
+    if let Some((title, keys)) = module && !keys.is_empty() { sections.push((title.trim_end_matches(" *").to_uppercase(), filter(keys.to_vec()))); }
+    if !query.is_empty() {
+        let mut others: Vec<_> = registry::SCREENS.iter().map(|s| (s.name, (s.key_bindings)())).filter(|(_, keys)| !keys.is_empty() && keys != module_keys).collect();
+        others.sort_by_key(|(name, _)| *name);
+        for (name, keys) in others { sections.push((name.to_uppercase(), filter(keys))); }
+    }
+    sections.retain(|(_, keys)| !keys.is_empty());
+    sections
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_shortcut_list_is_read_from_the_dispatch_registries() {
+        let open = crate::modules::json_editor::key_bindings();
+        let titles = |sections: &[(String, Vec<KeyBinding>)]| sections.iter().map(|(t, _)| t.clone()).collect::<Vec<_>>();
+        let sections = shortcut_sections(Some(("data.json *", &open)), "");
+        assert_eq!(titles(&sections), ["GENERAL", "DATA.JSON"]);
+        assert!(sections[0].1.contains(&KeyBinding { keys: "Ctrl+\\".into(), action: "Toggle sidebar" }));
+        assert_eq!(sections[1].1, open);
+
+        let found = shortcut_sections(Some(("data.json", &open)), "save as");
+        assert_eq!(titles(&found), ["DATA.JSON", "IMAGE EDITOR", "TEXT EDITOR"], "the open editor's own entry is not listed twice");
+        assert_eq!(found[0].1, [KeyBinding { keys: "Ctrl+Shift+S".into(), action: "Save As" }]);
+        assert_eq!(found[2].1, [KeyBinding { keys: "Ctrl+Shift+A".into(), action: "Save As" }]);
+    }
+
+    #[test]
+    fn updates_are_only_fetched_at_startup_after_the_user_opts_in() {
+        assert!(!AppSettings::default().checks_for_updates_at_startup());
+        let upgraded: AppSettings = serde_json::from_str(r#"{"theme_preference":"System","show_toolbar_te":true,"show_file_info_te":true,"show_file_info_je":true,"check_for_updates":true}"#).unwrap();
+        assert!(!upgraded.update_check_asked && !upgraded.checks_for_updates_at_startup(), "settings saved before the prompt existed must still ask");
+        let answered = AppSettings { check_for_updates: true, update_check_asked: true, ..AppSettings::default() };
+        assert!(answered.checks_for_updates_at_startup());
     }
 }
@@ -0,0 +1,164 @@
+use eframe::egui;
+use std::path::PathBuf;
+use crate::style::{ColorPalette, ThemeMode};
+use crate::modules::helpers::config_file;
+
+const MAX_ENTRIES: usize = 25;
+const PREVIEW_CHARS: usize = 60;
+
+pub enum ClipOutcome { Copy(String), Paste(String) }
+
+pub struct ClipboardHistory { entries: Vec<String>, persist: bool, own_copy: Option<String> }
+
+impl ClipboardHistory {
+    pub fn load(persist: bool) -> Self {
+        let entries = if persist { config_file::load_json(&Self::get_config_path(), "Clipboard history") } else { Vec::new() };
+        Self { entries, persist, own_copy: None }
+    }
+
+    fn get_config_path() -> PathBuf { config_file::config_path("clipboard_history.json") }
+
+    fn save(&self) {
+        if !self.persist { return; }
+        let kept: Vec<&String> = self.entries.iter().filter(|e| !looks_sensitive(e)).collect();
+        let _ = config_file::save_json(&Self::get_config_path(), &kept);
+    }
+
+    pub fn set_persist(&mut self, persist: bool) {
+        self.persist = persist;
+        if persist { self.save(); } else { let _ = config_file::remove(&Self::get_config_path()); }
+    }
+
+    pub fn capture(&mut self, ctx: &egui::Context) {
+        let copied: Vec<String> = ctx.output(|o| o.commands.iter().filter_map(|c| match c { egui::OutputCommand::CopyText(t) => Some(t.clone()), _ => None }).collect());
+        if copied.is_empty() { return; }
+        for text in copied {
+            if self.own_copy.as_deref() == Some(text.as_str()) { self.own_copy = None; continue; }
+            self.record(text);
+        }
+        self.save();
+    }
+
+    fn record(&mut self, text: String) {
+        if text.trim().is_empty() { return; }
+        self.entries.retain(|e| *e != text);
+        self.entries.insert(0, text);
+        self.entries.truncate(MAX_ENTRIES);
+    }
+
+    pub fn clear(&mut self) { self.entries.clear(); self.save(); }
+
+    pub fn show(&mut self, ctx: &egui::Context, theme: ThemeMode, can_paste: bool, open: &mut bool) -> Option<ClipOutcome> {
+        let is_dark = matches!(theme, ThemeMode::Dark);
+        let (muted, text_col, icon_bg) = if is_dark { (ColorPalette::ZINC_500, ColorPalette::ZINC_100, ColorPalette::ZINC_700) } else { (ColorPalette::STONE_500, ColorPalette::STONE_900, ColorPalette::GRAY_200) };
+        let mut outcome = None;
+        let mut clear = false;
+        egui::Window::new("Clipboard History")
+            .open(open).collapsible(false).resizable(false)
+            .anchor(egui::Align2::RIGHT_TOP, egui::vec2(-16.0, 48.0))
+            .fixed_size(egui::vec2(320.0, 0.0))
+            .show(ctx, |ui| {
+                if self.entries.is_empty() {
+                    ui.label(egui::RichText::new("Nothing copied yet. Text and colors you copy in the app show up here.").size(12.0).color(muted));
+                    return;
+                }
+                egui::ScrollArea::vertical().max_height(360.0).auto_shrink([false, true]).show(ui, |ui| {
+                    for entry in &self.entries {
+                        let color = parse_color(entry);
+                        let resp = ui.horizontal(|ui| {
+                            let (icon, _) = ui.allocate_exact_size(egui::vec2(18.0, 18.0), egui::Sense::hover());
+                            match color {
+                                Some(c) => {
+                                    ui.painter().rect_filled(icon, 3.0, c);
+                                    ui.painter().rect_stroke(icon, 3.0, egui::Stroke::new(1.0, muted), egui::StrokeKind::Inside);
+                                }
+                                None => {
+                                    ui.painter().rect_filled(icon, 3.0, icon_bg);
+                                    ui.painter().text(icon.center(), egui::Align2::CENTER_CENTER, "T", egui::FontId::proportional(11.0), text_col);
+                                }
+                            }
+                            ui.add(egui::Label::new(egui::RichText::new(preview(entry)).size(12.5).color(text_col)).truncate().selectable(false).sense(egui::Sense::click()))
+                        }).inner;
+                        let hint = if can_paste { "Click to copy, double-click to paste" } else { "Click to copy" };
+                        let full: String = entry.chars().take(500).collect();
+                        let resp = resp.on_hover_cursor(egui::CursorIcon::PointingHand).on_hover_text(format!("{}\n\n{}", full, hint));
+                        if resp.double_clicked() { outcome = Some(ClipOutcome::Paste(entry.clone())); }
+                        else if resp.clicked() { outcome = Some(ClipOutcome::Copy(entry.clone())); }
+                    }
+                });
+                ui.separator();
+                ui.horizontal(|ui| {
+                    let note = if self.persist { "Kept between sessions" } else { "This session only" };
+                    ui.label(egui::RichText::new(note).size(11.0).color(muted).italics());
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        if ui.small_button("Clear").on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { clear = true; }
+                    });
+                });
+            });
+        if clear { self.clear(); }
+        if let Some(ClipOutcome::Copy(t) | ClipOutcome::Paste(t)) = &outcome {
+            self.own_copy = Some(t.clone());
+            ctx.copy_text(t.clone());
+        }
+        outcome
+    }
+}
+
+fn preview(text: &str) -> String {
+    let line = text.trim().lines().next().unwrap_or("");
+    let mut out: String = line.chars().take(PREVIEW_CHARS).collect();
+    if out.len() < line.len() || text.trim().lines().nth(1).is_some() { out.push('…'); }
+    out
+}
+
+pub fn parse_color(text: &str) -> Option<egui::Color32> {
+    let t = text.trim();
+    if let Some(hex) = t.strip_prefix('#') {
+        let byte = |i: usize| hex.get(i..i + 2).and_then(|s| u8::from_str_radix(s, 16).ok());
+        return match hex.len() {
+            6 => Some(egui::Color32::from_rgb(byte(0)?, byte(2)?, byte(4)?)),
+            8 => Some(egui::Color32::from_rgba_unmultiplied(byte(0)?, byte(2)?, byte(4)?, byte(6)?)),
+            _ => None,
+        };
+    }
+    let lower = t.to_ascii_lowercase();
+    let inner = lower.strip_prefix("rgba(").or_else(|| lower.strip_prefix("rgb("))?.strip_suffix(')')?;
+    let parts: Vec<&str> = inner.split(',').map(str::trim).collect();
+    let channel = |i: usize| parts.get(i).and_then(|p| p.parse::<u8>().ok());
+    match parts.len() {
+        3 => Some(egui::Color32::from_rgb(channel(0)?, channel(1)?, channel(2)?)),
+        4 => Some(egui::Color32::from_rgba_unmultiplied(channel(0)?, channel(1)?, channel(2)?, (parts[3].parse::<f32>().ok()?.clamp(0.0, 1.0) * 255.0).round() as u8)),
+        _ => None,
+    }
+}
+
+pub fn looks_sensitive(text: &str) -> bool {
+    let t = text.trim();
+    let lower = t.to_lowercase();
+    if ["password", "passwd", "secret", "token", "api_key", "apikey", "private key", "bearer "].iter().any(|k| lower.contains(k)) { return true; }
+    if t.len() >= 16 && ["sk-", "ghp_", "gho_", "github_pat_", "xox", "akia", "eyj"].iter().any(|p| lower.starts_with(p)) { return true; }
+    let digits: String = t.chars().filter(|c| !matches!(c, ' ' | '-')).collect();
+    if (13..=19).contains(&digits.len()) && digits.chars().all(|c| c.is_ascii_digit()) { return true; }
+    (20..=200).contains(&t.len()) && !t.contains(char::is_whitespace)
+        && t.chars().any(|c| c.is_ascii_lowercase()) && t.chars().any(|c| c.is_ascii_uppercase()) && t.chars().any(|c| c.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colors_and_secrets_are_recognised() {
+        assert_eq!(parse_color("#FF8000"), Some(egui::Color32::from_rgb(255, 128, 0)));
+        assert_eq!(parse_color("rgb(1, 2, 3)"), Some(egui::Color32::from_rgb(1, 2, 3)));
+        assert_eq!(parse_color("rgba(10, 20, 30, 0.50)").map(|c| c.a()), Some(128));
+        assert_eq!(parse_color("#GG0000"), None);
+        assert_eq!(parse_color("hello"), None);
+        assert!(looks_sensitive("password: hunter2"));
+        assert!(looks_sensitive("ghp_abcdefghijklmnop1234"));
+        assert!(looks_sensitive("4111 1111 1111 1111"));
+        assert!(looks_sensitive("aB3dE5gH7jK9mN1pQ3sT5"));
+        assert!(!looks_sensitive("The quick brown fox"));
+        assert!(!looks_sensitive("#FF8000"));
+    }
+}
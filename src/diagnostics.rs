@@ -0,0 +1,164 @@
+//! Diagnostics for bug reports: a `log` backend that mirrors records to stderr and keeps the latest lines in
+//! memory, a short history of the error toasts the user saw, and the report text and GitHub issue link built
+//! from them.
+
+use std::collections::VecDeque;
+use std::io::Write;
+use std::sync::Mutex;
+
+const LOG_CAPACITY: usize = 200;
+const ERROR_CAPACITY: usize = 20;
+pub const REPORT_LOG_LINES: usize = 80;
+const NEW_ISSUE_URL: &str = "https://github.com/Evan-Pochtar/UniversalEditor/issues/new";
+
+static LINES: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+static ERRORS: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+fn push(ring: &Mutex<VecDeque<String>>, line: String, cap: usize) {
+    let mut ring = ring.lock().unwrap_or_else(|e| e.into_inner());
+    if ring.len() == cap { ring.pop_front(); }
+    ring.push_back(line);
+}
+
+fn tail(ring: &Mutex<VecDeque<String>>, n: usize) -> Vec<String> {
+    let ring = ring.lock().unwrap_or_else(|e| e.into_inner());
+    ring.iter().skip(ring.len().saturating_sub(n)).cloned().collect()
+}
+
+struct RingLogger;
+
+impl log::Log for RingLogger {
+    /// Our own records from info up, dependencies (winit, wgpu, ...) only when they warn.
+    fn enabled(&self, meta: &log::Metadata) -> bool {
+        let own = meta.target().starts_with(env!("CARGO_CRATE_NAME"));
+        meta.level() <= if own { log::Level::Info } else { log::Level::Warn }
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) { return; }
+        let line = format!("{} {:<5} {}: {}", chrono::Local::now().format("%H:%M:%S%.3f"), record.level(), record.target(), record.args());
+        let _ = writeln!(std::io::stderr().lock(), "{}", line);
+        push(&LINES, line, LOG_CAPACITY);
+    }
+
+    fn flush(&self) { let _ = std::io::stderr().flush(); }
+}
+
+static LOGGER: RingLogger = RingLogger;
+
+/// Installs the logger and routes panics (including ones on filter and export threads) into the ring as well.
+pub fn init() {
+    if log::set_logger(&LOGGER).is_err() { return; }
+    log::set_max_level(log::LevelFilter::Info);
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let thread = std::thread::current().name().unwrap_or("unnamed").to_string();
+        push(&LINES, format!("{} PANIC thread '{}': {}", chrono::Local::now().format("%H:%M:%S%.3f"), thread, info), LOG_CAPACITY);
+        default_hook(info);
+    }));
+}
+
+pub fn recent_log(n: usize) -> Vec<String> { tail(&LINES, n) }
+
+/// Remembers a message that was shown as an error toast so it can go into the next report.
+pub fn record_error(msg: &str) {
+    log::warn!("toast: {}", msg.replace('\n', " | "));
+    push(&ERRORS, format!("{} {}", chrono::Local::now().format("%H:%M:%S"), msg.replace('\n', " | ")), ERROR_CAPACITY);
+}
+
+pub fn recent_errors() -> Vec<String> { tail(&ERRORS, ERROR_CAPACITY) }
+
+/// Describes the GPU eframe is drawing with. Only the glow backend exposes its context to the app.
+pub fn renderer(frame: &eframe::Frame) -> String {
+    let Some(gl) = frame.gl() else { return "unknown backend".to_string() };
+    use eframe::glow::HasContext;
+    // SAFETY: string queries on the context eframe is rendering the current frame with.
+    let (vendor, renderer, version) = unsafe {
+        (gl.get_parameter_string(eframe::glow::VENDOR), gl.get_parameter_string(eframe::glow::RENDERER), gl.get_parameter_string(eframe::glow::VERSION))
+    };
+    format!("glow (OpenGL {}), {} {}", version, vendor, renderer)
+}
+
+pub struct Report<'a> {
+    pub version: &'a str,
+    pub os: &'a str,
+    pub renderer: &'a str,
+    pub settings: &'a [(&'a str, String)],
+    pub documents: &'a [String],
+    pub errors: &'a [String],
+    pub log: &'a [String],
+}
+
+fn section(out: &mut String, title: &str, lines: &[String], empty: &str) {
+    out.push_str(&format!("\n### {}\n", title));
+    if lines.is_empty() { out.push_str(empty); out.push('\n'); return; }
+    for l in lines { out.push_str("- "); out.push_str(l); out.push('\n'); }
+}
+
+/// Plain markdown bundle. The user's home directory is replaced with `~` so paths in the log don't name them.
+pub fn build_report(r: &Report, home: Option<&str>) -> String {
+    let mut out = format!("### Environment\n- Version: {}\n- OS: {}\n- Renderer: {}\n", r.version, r.os, r.renderer);
+    for (k, v) in r.settings { out.push_str(&format!("- {}: {}\n", k, v)); }
+    section(&mut out, "Open documents", r.documents, "None");
+    section(&mut out, "Recent errors", r.errors, "None");
+    out.push_str(&format!("\n### Log (last {} lines)\n```\n", r.log.len()));
+    for l in r.log { out.push_str(l); out.push('\n'); }
+    out.push_str("```\n");
+    match home.filter(|h| h.len() > 1) {
+        Some(h) => out.replace(h, "~"),
+        None => out,
+    }
+}
+
+fn encode_component(s: &str) -> String {
+    s.bytes().map(|b| match b {
+        b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+        _ => format!("%{:02X}", b),
+    }).collect()
+}
+
+/// New-issue page with a short template; the full bundle travels through the clipboard since it can outgrow a URL.
+pub fn issue_url(version: &str, os: &str) -> String {
+    let body = format!(
+        "**What happened?**\n\n\n**Steps to reproduce**\n1. \n\n**Diagnostics**\n<!-- Universal Editor copied the diagnostics bundle to your clipboard. Paste it here. -->\n\nVersion {} on {}\n",
+        version, os,
+    );
+    format!("{}?labels=bug&title={}&body={}", NEW_ISSUE_URL, encode_component("[Bug] "), encode_component(&body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_lists_everything_and_hides_the_home_directory() {
+        let settings = [("GPU filters", "off".to_string())];
+        let documents = ["Image Editor (.png, 12 KB)".to_string()];
+        let errors = ["12:00:00 Could not read /home/ada/a.txt: File not found".to_string()];
+        let log = ["12:00:01 WARN  universal_editor: /home/ada/.config is corrupted".to_string()];
+        let report = build_report(&Report { version: "1.2.3", os: "linux x86_64", renderer: "glow", settings: &settings, documents: &documents, errors: &errors, log: &log }, Some("/home/ada"));
+        for needle in ["- Version: 1.2.3", "- GPU filters: off", "- Image Editor (.png, 12 KB)", "~/a.txt", "### Log (last 1 lines)", "~/.config is corrupted"] {
+            assert!(report.contains(needle), "{needle:?} missing from\n{report}");
+        }
+        assert!(!report.contains("/home/ada"));
+        let empty = build_report(&Report { version: "1", os: "x", renderer: "y", settings: &[], documents: &[], errors: &[], log: &[] }, None);
+        assert!(empty.contains("### Recent errors\nNone\n"));
+    }
+
+    #[test]
+    fn issue_url_percent_encodes_its_template() {
+        assert_eq!(encode_component("a b&c=d/é~"), "a%20b%26c%3Dd%2F%C3%A9~");
+        let url = issue_url("1.2.3", "linux");
+        assert!(url.starts_with("https://github.com/Evan-Pochtar/UniversalEditor/issues/new?labels=bug&title=%5BBug%5D%20&body="));
+        assert!(url.contains("Version%201.2.3%20on%20linux"));
+        assert!(!url[url.find('?').unwrap_or(0)..].contains(' '));
+    }
+
+    #[test]
+    fn rings_keep_only_the_newest_lines() {
+        let ring = Mutex::new(VecDeque::new());
+        for i in 0..5 { push(&ring, i.to_string(), 3); }
+        assert_eq!(tail(&ring, 10), ["2", "3", "4"]);
+        assert_eq!(tail(&ring, 2), ["3", "4"]);
+    }
+}
@@ -0,0 +1,118 @@
+use std::{io, path::{Path, PathBuf}};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum EditorError {
+    #[error("Cancelled")]
+    Cancelled,
+    #[error("{} writing {}", io_reason(.source), .path.display())]
+    Io { path: PathBuf, #[source] source: io::Error },
+    #[error("Could not read {}: {}", .path.display(), read_reason(.source))]
+    Read { path: PathBuf, #[source] source: io::Error },
+    #[error("Could not decode {}: {source}", .path.display())]
+    ImageDecode { path: PathBuf, #[source] source: image::ImageError },
+    #[error("{format} encoding failed: {source}")]
+    ImageEncode { format: String, #[source] source: image::ImageError },
+    #[error("{0}")]
+    UnsupportedFormat(String),
+    #[error("Nothing to save: {0}")]
+    Empty(&'static str),
+    #[error("{0}")]
+    Other(String),
+}
+
+pub type EditorResult<T = ()> = Result<T, EditorError>;
+
+fn io_reason(e: &io::Error) -> String {
+    match e.kind() {
+        io::ErrorKind::PermissionDenied => "Permission denied".to_string(),
+        io::ErrorKind::NotFound => "Folder not found".to_string(),
+        io::ErrorKind::StorageFull => "Disk full".to_string(),
+        io::ErrorKind::ReadOnlyFilesystem => "Read-only file system".to_string(),
+        _ => format!("{}", e),
+    }
+}
+
+fn read_reason(e: &io::Error) -> String {
+    match e.kind() {
+        io::ErrorKind::NotFound => "File not found".to_string(),
+        io::ErrorKind::InvalidData => "Not valid UTF-8 text".to_string(),
+        _ => io_reason(e),
+    }
+}
+
+impl EditorError {
+    pub fn io(path: &Path, source: io::Error) -> Self { EditorError::Io { path: path.to_path_buf(), source } }
+
+    pub fn read(path: &Path, source: io::Error) -> Self { EditorError::Read { path: path.to_path_buf(), source } }
+
+    pub fn decode(path: &Path, source: image::ImageError) -> Self { EditorError::ImageDecode { path: path.to_path_buf(), source } }
+
+    pub fn encode(format: &str, path: &Path, source: image::ImageError) -> Self {
+        match source {
+            image::ImageError::IoError(e) => EditorError::io(path, e),
+            e => EditorError::encode_in_memory(format, e),
+        }
+    }
+
+    /// For encoders writing to a buffer, where there is no file to blame an I/O error on.
+    pub fn encode_in_memory(format: &str, source: image::ImageError) -> Self {
+        match source {
+            image::ImageError::Unsupported(u) => EditorError::UnsupportedFormat(format!("{} encoding not supported: {}", format, u)),
+            e => EditorError::ImageEncode { format: format.to_string(), source: e },
+        }
+    }
+
+    pub fn open_elsewhere(path: &Path) -> Self {
+        let name = path.file_name().map_or_else(|| path.display().to_string(), |n| n.to_string_lossy().into_owned());
+        EditorError::Other(format!("{} is open in another window. Close it there before replacing it.", name))
+    }
+
+    pub fn is_cancelled(&self) -> bool { matches!(self, EditorError::Cancelled) }
+
+    pub fn user_message(&self) -> Option<String> { if self.is_cancelled() { None } else { Some(self.to_string()) } }
+}
+
+impl From<String> for EditorError {
+    fn from(s: String) -> Self { EditorError::Other(s) }
+}
+
+impl From<&str> for EditorError {
+    fn from(s: &str) -> Self { EditorError::Other(s.to_string()) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn in_memory_encode_errors_name_the_format_not_a_path() {
+        let broken = || image::ImageError::IoError(io::Error::new(io::ErrorKind::WriteZero, "buffer closed"));
+        let err = EditorError::encode_in_memory("PNG", broken());
+        assert!(matches!(&err, EditorError::ImageEncode { format, .. } if format == "PNG"));
+        assert_eq!(err.to_string(), "PNG encoding failed: buffer closed");
+        assert!(matches!(EditorError::encode("PNG", Path::new("out.png"), broken()), EditorError::Io { path, .. } if path == Path::new("out.png")));
+    }
+
+    #[test]
+    fn every_variant_reads_as_a_sentence_for_the_notification() {
+        let path = Path::new("/tmp/out.png");
+        let io_err = |kind| EditorError::io(path, io::Error::from(kind)).to_string();
+        assert_eq!(io_err(io::ErrorKind::PermissionDenied), "Permission denied writing /tmp/out.png");
+        assert_eq!(io_err(io::ErrorKind::NotFound), "Folder not found writing /tmp/out.png");
+        assert_eq!(io_err(io::ErrorKind::StorageFull), "Disk full writing /tmp/out.png");
+        assert_eq!(io_err(io::ErrorKind::ReadOnlyFilesystem), "Read-only file system writing /tmp/out.png");
+        assert_eq!(EditorError::read(path, io::Error::from(io::ErrorKind::NotFound)).to_string(), "Could not read /tmp/out.png: File not found");
+        assert_eq!(EditorError::read(path, io::Error::from(io::ErrorKind::InvalidData)).to_string(), "Could not read /tmp/out.png: Not valid UTF-8 text");
+        let decode = image::ImageError::Limits(image::error::LimitError::from_kind(image::error::LimitErrorKind::DimensionError));
+        assert!(EditorError::decode(path, decode).to_string().starts_with("Could not decode /tmp/out.png: "));
+        let unsupported = image::ImageError::Unsupported(image::error::UnsupportedError::from_format_and_kind(
+            image::error::ImageFormatHint::Name("XYZ".into()), image::error::UnsupportedErrorKind::Format(image::error::ImageFormatHint::Name("XYZ".into())),
+        ));
+        assert!(matches!(EditorError::encode("XYZ", path, unsupported), EditorError::UnsupportedFormat(m) if m.starts_with("XYZ encoding not supported: ")));
+        assert_eq!(EditorError::Empty("no image is loaded").to_string(), "Nothing to save: no image is loaded");
+        assert_eq!(EditorError::from("plain").to_string(), "plain");
+        assert_eq!(EditorError::open_elsewhere(path).to_string(), "out.png is open in another window. Close it there before replacing it.");
+        assert_eq!((EditorError::Cancelled.user_message(), EditorError::from("shown".to_string()).user_message()), (None, Some("shown".to_string())));
+    }
+}
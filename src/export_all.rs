@@ -0,0 +1,261 @@
+use eframe::egui;
+use image::DynamicImage;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use crate::i18n::{tr, tr_args};
+use crate::modules::image_export::{ExportFormat, export_image};
+use crate::style::{ColorPalette, ThemeMode};
+use crate::tasks;
+
+/// Where a document's pixels come from: the open editor's flattened layers, a file on disk, or nothing yet.
+pub enum ExportSource { Open(DynamicImage), File(PathBuf), Blank }
+
+pub struct ExportDocument { pub title: String, pub source: ExportSource }
+
+impl ExportDocument {
+    pub fn title_for(path: Option<&Path>) -> String {
+        path.and_then(|p| p.file_stem()).map(|s| s.to_string_lossy().into_owned()).unwrap_or_else(|| "Untitled".to_string())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum ItemStatus { Waiting, Exporting, Exported(PathBuf), Skipped(String), Failed(String) }
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResizeRule { Original, LongestSide(u32), Percent(u32) }
+
+impl ResizeRule {
+    fn apply(self, img: DynamicImage) -> DynamicImage {
+        let (w, h) = (img.width(), img.height());
+        let scale = match self {
+            ResizeRule::Original => return img,
+            ResizeRule::LongestSide(max) => (max as f64 / w.max(h) as f64).min(1.0),
+            ResizeRule::Percent(p) => p as f64 / 100.0,
+        };
+        let (nw, nh) = (((w as f64 * scale).round() as u32).max(1), ((h as f64 * scale).round() as u32).max(1));
+        if (nw, nh) == (w, h) { img } else { img.resize_exact(nw, nh, image::imageops::FilterType::Lanczos3) }
+    }
+}
+
+#[derive(Clone)]
+pub struct ExportAllSettings { pub format: ExportFormat, pub quality: u8, pub resize: ResizeRule, pub folder: PathBuf, pub pattern: String }
+
+/// Fills `{title}` and the 1-based `{index}` into the pattern, swapping characters no file system accepts.
+pub fn file_name(pattern: &str, title: &str, index: usize, format: ExportFormat) -> String {
+    let stem: String = pattern.replace("{title}", title).replace("{index}", &index.to_string()).chars()
+        .map(|c| if matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') { '_' } else { c }).collect();
+    let stem = stem.trim();
+    format!("{}.{}", if stem.is_empty() { "export" } else { stem }, format.extension())
+}
+
+/// Exports each document in turn. A failure is recorded against its row and never stops the rest.
+fn run(docs: Vec<ExportDocument>, settings: &ExportAllSettings, rows: &Mutex<Vec<(String, ItemStatus)>>, exported: &(dyn Fn(PathBuf) + Send + Sync), task: &tasks::Task) -> (usize, usize) {
+    let (mut done, mut failed) = (0, 0);
+    let mut used = HashSet::new();
+    let total = docs.len();
+    for (i, doc) in docs.into_iter().enumerate() {
+        if task.cancelled() { break; }
+        task.progress(i as f32 / total as f32);
+        rows.lock().unwrap()[i].1 = ItemStatus::Exporting;
+        let img = match doc.source {
+            ExportSource::Open(img) => Ok(img),
+            ExportSource::File(path) => image::open(&path).map_err(|e| tr_args("Could not read the file: {error}", &[("error", &e.to_string())])),
+            ExportSource::Blank => { rows.lock().unwrap()[i].1 = ItemStatus::Skipped(tr("No image to export")); continue; }
+        };
+        let mut name = file_name(&settings.pattern, &doc.title, i + 1, settings.format);
+        if !used.insert(name.clone()) { name = file_name(&format!("{}_{{index}}", settings.pattern), &doc.title, i + 1, settings.format); }
+        let path = settings.folder.join(name);
+        let status = match img.and_then(|img| {
+            export_image(&settings.resize.apply(img), &path, settings.format, settings.quality, 6, 100.0, true, settings.quality, 4).map_err(|e| e.to_string())
+        }) {
+            Ok(()) => { exported(path.clone()); done += 1; ItemStatus::Exported(path) }
+            Err(e) => { failed += 1; ItemStatus::Failed(e) }
+        };
+        rows.lock().unwrap()[i].1 = status;
+    }
+    (done, failed)
+}
+
+struct ExportRun { task: tasks::Task, rows: Arc<Mutex<Vec<(String, ItemStatus)>>> }
+
+pub struct ExportAllDialog {
+    docs: Vec<(ExportDocument, bool)>,
+    format: ExportFormat,
+    quality: u8,
+    resize: ResizeRule,
+    folder: Option<PathBuf>,
+    pattern: String,
+    exported: Arc<dyn Fn(PathBuf) + Send + Sync>,
+    run: Option<ExportRun>,
+}
+
+impl ExportAllDialog {
+    /// `exported` sees every written path, the same way an editor's own export reports it.
+    pub fn new(docs: Vec<ExportDocument>, exported: Arc<dyn Fn(PathBuf) + Send + Sync>) -> Self {
+        Self {
+            docs: docs.into_iter().map(|d| (d, true)).collect(), format: ExportFormat::Png, quality: 90, resize: ResizeRule::Original,
+            folder: None, pattern: "{title}".to_string(), exported, run: None,
+        }
+    }
+
+    fn start(&mut self) {
+        let Some(folder) = self.folder.clone() else { return };
+        let settings = ExportAllSettings { format: self.format, quality: self.quality, resize: self.resize, folder, pattern: self.pattern.clone() };
+        let docs: Vec<ExportDocument> = std::mem::take(&mut self.docs).into_iter().filter(|(_, on)| *on).map(|(d, _)| d).collect();
+        let rows = Arc::new(Mutex::new(docs.iter().map(|d| (d.title.clone(), ItemStatus::Waiting)).collect::<Vec<_>>()));
+        let (job_rows, exported) = (Arc::clone(&rows), Arc::clone(&self.exported));
+        let name = if docs.len() == 1 { "Export 1 image".to_string() } else { format!("Export {} images", docs.len()) };
+        let task = tasks::spawn(name, true, move |task| {
+            let (done, failed) = run(docs, &settings, &job_rows, exported.as_ref(), task);
+            let msg = if task.cancelled() { tr_args("Export All cancelled after {done} images", &[("done", &done.to_string())]) }
+                else { tr_args("Export All: {done} exported, {failed} failed", &[("done", &done.to_string()), ("failed", &failed.to_string())]) };
+            task.finish(Some(msg));
+        });
+        self.run = Some(ExportRun { task, rows });
+    }
+
+    /// False once the user closes the dialog. A running export keeps going and reports through the task list.
+    pub fn show(&mut self, ctx: &egui::Context, theme: ThemeMode) -> bool {
+        let (bg, border, text, muted) = if matches!(theme, ThemeMode::Dark) {
+            (ColorPalette::ZINC_900, ColorPalette::ZINC_700, ColorPalette::SLATE_200, ColorPalette::ZINC_500)
+        } else {
+            (egui::Color32::WHITE, ColorPalette::GRAY_200, ColorPalette::GRAY_800, ColorPalette::STONE_500)
+        };
+        let mut open = true;
+        egui::Window::new(tr("Export All Images"))
+            .collapsible(false).resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+            .fixed_size(egui::vec2(420.0, 0.0))
+            .frame(egui::Frame::new().fill(bg).stroke(egui::Stroke::new(1.0, border)).corner_radius(10.0).inner_margin(20.0))
+            .show(ctx, |ui| match &self.run {
+                Some(run) => open = Self::progress_ui(ui, run, text, muted),
+                None => open = self.settings_ui(ui, text, muted),
+            });
+        if ctx.input(|i| i.key_pressed(egui::Key::Escape)) { open = false; }
+        open
+    }
+
+    fn settings_ui(&mut self, ui: &mut egui::Ui, text: egui::Color32, muted: egui::Color32) -> bool {
+        let mut open = true;
+        if self.docs.is_empty() {
+            ui.label(egui::RichText::new(tr("No open or recent images to export.")).size(12.5).color(muted));
+        }
+        egui::ScrollArea::vertical().max_height(160.0).show(ui, |ui| {
+            for (doc, on) in &mut self.docs {
+                ui.horizontal(|ui| {
+                    ui.checkbox(on, egui::RichText::new(&doc.title).size(13.0).color(text));
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        let note = match &doc.source { ExportSource::Open(_) => tr("open"), ExportSource::File(_) => tr("recent"), ExportSource::Blank => tr("no image") };
+                        ui.label(egui::RichText::new(note).size(11.0).color(muted));
+                    });
+                });
+            }
+        });
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.label(egui::RichText::new(tr("Format")).size(12.5).color(text));
+            egui::ComboBox::from_id_salt("export_all_format").selected_text(self.format.as_str()).show_ui(ui, |ui| {
+                for f in ExportFormat::all() { ui.selectable_value(&mut self.format, f, f.as_str()); }
+            });
+            if matches!(self.format, ExportFormat::Jpeg | ExportFormat::Avif) {
+                ui.label(egui::RichText::new(tr("Quality")).size(12.5).color(text));
+                ui.add(egui::Slider::new(&mut self.quality, 1..=100).suffix("%"));
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label(egui::RichText::new(tr("Resize")).size(12.5).color(text));
+            if ui.radio(self.resize == ResizeRule::Original, tr("Original")).clicked() { self.resize = ResizeRule::Original; }
+            if ui.radio(matches!(self.resize, ResizeRule::LongestSide(_)), tr("Longest side")).clicked() && !matches!(self.resize, ResizeRule::LongestSide(_)) { self.resize = ResizeRule::LongestSide(1600); }
+            if ui.radio(matches!(self.resize, ResizeRule::Percent(_)), tr("Scale")).clicked() && !matches!(self.resize, ResizeRule::Percent(_)) { self.resize = ResizeRule::Percent(50); }
+            match &mut self.resize {
+                ResizeRule::Original => {}
+                ResizeRule::LongestSide(m) => { ui.add(egui::DragValue::new(m).range(1..=16384).suffix("px")); }
+                ResizeRule::Percent(p) => { ui.add(egui::DragValue::new(p).range(1..=400).suffix("%")); }
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label(egui::RichText::new(tr("Folder")).size(12.5).color(text));
+            let shown = self.folder.as_ref().map_or(tr("None chosen"), |f| f.display().to_string());
+            ui.label(egui::RichText::new(shown).size(12.0).color(muted));
+            if ui.button(tr("Browse...")).on_hover_cursor(egui::CursorIcon::PointingHand).clicked() && let Some(dir) = rfd::FileDialog::new().pick_folder() { self.folder = Some(dir); }
+        });
+        ui.horizontal(|ui| {
+            ui.label(egui::RichText::new(tr("File names")).size(12.5).color(text));
+            ui.add(egui::TextEdit::singleline(&mut self.pattern).desired_width(160.0));
+            if let Some((doc, _)) = self.docs.iter().find(|(_, on)| *on) {
+                ui.label(egui::RichText::new(format!("→ {}", file_name(&self.pattern, &doc.title, 1, self.format))).size(11.0).color(muted));
+            }
+        });
+        ui.label(egui::RichText::new(tr("{title} is the document name, {index} its place in the list.")).size(11.0).color(muted).italics());
+        ui.add_space(10.0);
+        ui.horizontal(|ui| {
+            let ready = self.folder.is_some() && self.docs.iter().any(|(_, on)| *on);
+            if ui.add_enabled(ready, egui::Button::new(tr("Export"))).on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { self.start(); }
+            if ui.button(tr("Cancel")).on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { open = false; }
+        });
+        open
+    }
+
+    fn progress_ui(ui: &mut egui::Ui, run: &ExportRun, text: egui::Color32, muted: egui::Color32) -> bool {
+        let mut open = true;
+        let rows = run.rows.lock().unwrap().clone();
+        egui::ScrollArea::vertical().max_height(240.0).show(ui, |ui| {
+            for (title, status) in &rows {
+                ui.horizontal(|ui| {
+                    ui.label(egui::RichText::new(title).size(13.0).color(text));
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        let (note, col) = match status {
+                            ItemStatus::Waiting => (tr("Waiting"), muted),
+                            ItemStatus::Exporting => (tr("Exporting..."), ColorPalette::BLUE_500),
+                            ItemStatus::Exported(_) => (tr("Exported"), ColorPalette::GREEN_500),
+                            ItemStatus::Skipped(why) => (why.clone(), ColorPalette::AMBER_500),
+                            ItemStatus::Failed(why) => (why.clone(), ColorPalette::RED_500),
+                        };
+                        let resp = ui.label(egui::RichText::new(note).size(11.0).color(col));
+                        if let ItemStatus::Exported(path) = status { resp.on_hover_text(path.display().to_string()); }
+                    });
+                });
+            }
+        });
+        ui.add_space(10.0);
+        let running = !run.task.cancelled() && rows.iter().any(|(_, s)| matches!(s, ItemStatus::Waiting | ItemStatus::Exporting));
+        ui.horizontal(|ui| {
+            if running && ui.button(tr("Cancel")).on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { run.task.cancel(); }
+            if ui.button(tr("Close")).on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { open = false; }
+        });
+        open
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn export_all_writes_each_document_and_keeps_going_past_failures() {
+        let folder = std::env::temp_dir().join(format!("ue_export_all_{}", std::process::id()));
+        std::fs::create_dir_all(&folder).unwrap();
+        let docs = vec![
+            ExportDocument { title: "shot".into(), source: ExportSource::Open(DynamicImage::new_rgba8(40, 20)) },
+            ExportDocument { title: "gone".into(), source: ExportSource::File(folder.join("missing.png")) },
+            ExportDocument { title: "empty".into(), source: ExportSource::Blank },
+            ExportDocument { title: "shot".into(), source: ExportSource::Open(DynamicImage::new_rgba8(8, 8)) },
+        ];
+        let settings = ExportAllSettings { format: ExportFormat::Png, quality: 90, resize: ResizeRule::LongestSide(10), folder: folder.clone(), pattern: "{title}".into() };
+        let rows = Mutex::new(docs.iter().map(|d| (d.title.clone(), ItemStatus::Waiting)).collect::<Vec<_>>());
+        let seen = Mutex::new(Vec::new());
+        let task = tasks::submit("Export all test", true);
+        assert_eq!(run(docs, &settings, &rows, &|p| seen.lock().unwrap().push(p), &task), (2, 1));
+        task.finish(None);
+        let rows = rows.into_inner().unwrap();
+        assert_eq!(rows[0].1, ItemStatus::Exported(folder.join("shot.png")));
+        assert!(matches!(rows[1].1, ItemStatus::Failed(_)));
+        assert!(matches!(rows[2].1, ItemStatus::Skipped(_)));
+        assert_eq!(rows[3].1, ItemStatus::Exported(folder.join("shot_4.png")), "a repeated name must not overwrite the first export");
+        assert_eq!(image::image_dimensions(folder.join("shot.png")).unwrap(), (10, 5));
+        assert_eq!(seen.into_inner().unwrap(), vec![folder.join("shot.png"), folder.join("shot_4.png")]);
+        assert_eq!(file_name("{index} - {title}", "a/b", 3, ExportFormat::Jpeg), "3 - a_b.jpg");
+        let _ = std::fs::remove_dir_all(&folder);
+    }
+}
@@ -0,0 +1,103 @@
+//! Keyboard focus regions. Editors register the areas they draw each frame (canvas, options bar, floating
+//! panels) and the app registers the menu bar. F6 / Shift+F6 moves focus between them, Tab stays inside a
+//! focused panel, and canvas shortcuts only fire while the canvas region holds focus.
+
+use eframe::egui;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region { Canvas, Toolbar, Panel, Menu }
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Area { region: Region, layer: egui::LayerId, rect: egui::Rect }
+
+fn areas_id(next: bool) -> egui::Id { egui::Id::new(("focus_areas", next)) }
+
+pub fn register(ctx: &egui::Context, region: Region, layer: egui::LayerId, rect: egui::Rect) {
+    ctx.data_mut(|d| d.get_temp_mut_or_default::<Vec<Area>>(areas_id(true)).push(Area { region, layer, rect }));
+}
+
+fn areas(ctx: &egui::Context) -> Vec<Area> { ctx.data(|d| d.get_temp(areas_id(false))).unwrap_or_default() }
+
+/// Index of the area holding keyboard focus. With nothing focused the canvas has it.
+fn focused_area(ctx: &egui::Context, areas: &[Area]) -> Option<usize> {
+    let Some(id) = ctx.memory(|m| m.focused()) else { return areas.iter().position(|a| a.region == Region::Canvas) };
+    let widget = ctx.viewport(|vp| vp.prev_pass.widgets.get(id).copied())?;
+    areas.iter().position(|a| a.layer == widget.layer_id && a.rect.contains(widget.rect.center()))
+}
+
+pub fn current(ctx: &egui::Context) -> Option<Region> {
+    let areas = areas(ctx);
+    focused_area(ctx, &areas).map(|i| areas[i].region)
+}
+
+pub fn canvas_focused(ctx: &egui::Context) -> bool { current(ctx) == Some(Region::Canvas) }
+
+/// True while a text field has keyboard focus, so editor shortcuts that share its keys should stand back.
+pub fn typing(ctx: &egui::Context) -> bool { ctx.memory(|m| m.focused()).is_some_and(|id| egui::TextEdit::load_state(ctx, id).is_some()) }
+
+fn focusable(ctx: &egui::Context, area: &Area) -> Vec<egui::Id> {
+    ctx.viewport(|vp| vp.prev_pass.widgets.get_layer(area.layer)
+        .filter(|w| w.enabled && w.sense.is_focusable() && area.rect.contains(w.rect.center()))
+        .map(|w| w.id).collect())
+}
+
+fn cycle(len: usize, from: Option<usize>, back: bool) -> Option<usize> {
+    if len == 0 { return None; }
+    Some(match (from, back) {
+        (None, false) => 0,
+        (None, true) => len - 1,
+        (Some(i), false) => (i + 1) % len,
+        (Some(i), true) => (i + len - 1) % len,
+    })
+}
+
+/// Where Tab should wrap to when it would leave the first or last widget of a trapped panel.
+fn wrap_tab(ids: &[egui::Id], focused: Option<egui::Id>, back: bool) -> Option<egui::Id> {
+    let (first, last) = (*ids.first()?, *ids.last()?);
+    match focused {
+        Some(f) if f == last && !back => Some(first),
+        Some(f) if f == first && back => Some(last),
+        Some(f) if ids.contains(&f) => None,
+        _ => Some(if back { last } else { first }),
+    }
+}
+
+/// Runs at the start of each frame, before anything registers or reads keys. `cycle` is an F6 press this frame,
+/// `Some(true)` going backwards.
+pub fn begin_frame(ctx: &egui::Context, cycle: Option<bool>) {
+    let registered = ctx.data_mut(|d| d.remove_temp::<Vec<Area>>(areas_id(true))).unwrap_or_default();
+    ctx.data_mut(|d| d.insert_temp(areas_id(false), registered.clone()));
+    let current = focused_area(ctx, &registered);
+    if let Some(back) = cycle && let Some(next) = self::cycle(registered.len(), current, back) {
+        let area = registered[next];
+        match (area.region, focusable(ctx, &area).first()) {
+            (Region::Canvas, _) | (_, None) => ctx.memory_mut(|m| m.stop_text_input()),
+            (_, Some(id)) => ctx.memory_mut(|m| m.request_focus(*id)),
+        }
+        return;
+    }
+    let Some(area) = current.map(|i| registered[i]).filter(|a| a.region == Region::Panel) else { return };
+    let Some(back) = ctx.input(|i| i.events.iter().find_map(|e| match e {
+        egui::Event::Key { key: egui::Key::Tab, pressed: true, modifiers, .. } => Some(modifiers.shift),
+        _ => None,
+    })) else { return };
+    if let Some(id) = wrap_tab(&focusable(ctx, &area), ctx.memory(|m| m.focused()), back) {
+        ctx.memory_mut(|m| { m.request_focus(id); m.move_focus(egui::FocusDirection::None); });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn f6_cycles_regions_and_tab_wraps_inside_a_panel() {
+        assert_eq!([cycle(3, None, false), cycle(3, None, true), cycle(3, Some(2), false), cycle(3, Some(0), true), cycle(0, None, false)], [Some(0), Some(2), Some(0), Some(2), None]);
+        let ids: Vec<egui::Id> = (0..3).map(|i| egui::Id::new(("w", i))).collect();
+        assert_eq!(wrap_tab(&ids, Some(ids[2]), false), Some(ids[0]));
+        assert_eq!(wrap_tab(&ids, Some(ids[0]), true), Some(ids[2]));
+        assert_eq!(wrap_tab(&ids, Some(ids[1]), false), None);
+        assert_eq!(wrap_tab(&ids, Some(egui::Id::new("elsewhere")), true), Some(ids[2]));
+        assert_eq!(wrap_tab(&[], None, false), None);
+    }
+}
@@ -0,0 +1,114 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::{Mutex, RwLock};
+
+pub struct Locale { pub code: &'static str, pub name: &'static str, decimal: char, group: char, percent: &'static str, times: &'static str, bundle: Option<&'static str> }
+
+pub const LOCALES: [Locale; 2] = [
+    Locale { code: "en", name: "English", decimal: '.', group: ',', percent: "%", times: "x", bundle: None },
+    Locale { code: "de", name: "Deutsch", decimal: ',', group: '.', percent: "\u{a0}%", times: " × ", bundle: Some(include_str!("../assets/locales/de.json")) },
+];
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Entry { Text(String), Plural { one: String, other: String } }
+
+pub struct Catalog { locale: &'static Locale, entries: HashMap<String, Entry> }
+
+static ACTIVE: RwLock<Option<Catalog>> = RwLock::new(None);
+static MISSING: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+impl Catalog {
+    pub fn load(code: &str) -> Self {
+        let locale = LOCALES.iter().find(|l| l.code == code).unwrap_or(&LOCALES[0]);
+        let entries = locale.bundle.map_or_else(HashMap::new, |src| serde_json::from_str(src).unwrap_or_else(|e| {
+            log::error!("{} translations are damaged ({}), falling back to English", locale.name, e);
+            HashMap::new()
+        }));
+        Self { locale, entries }
+    }
+
+    fn lookup(&self, key: &str, n: Option<u64>) -> Option<&str> {
+        let found = self.entries.get(key).map(|e| match (e, n) {
+            (Entry::Text(s), _) => s.as_str(),
+            (Entry::Plural { one, .. }, Some(1)) => one.as_str(),
+            (Entry::Plural { other, .. }, _) => other.as_str(),
+        });
+        if found.is_none() && self.locale.bundle.is_some() && cfg!(debug_assertions) {
+            let mut missing = MISSING.lock().unwrap_or_else(|e| e.into_inner());
+            if !missing.iter().any(|k| k == key) { log::info!("no {} translation for {:?}", self.locale.code, key); missing.push(key.to_string()); }
+        }
+        found
+    }
+
+    pub fn tr(&self, key: &str) -> String { self.lookup(key, None).unwrap_or(key).to_string() }
+
+    pub fn tr_args(&self, key: &str, args: &[(&str, &str)]) -> String {
+        args.iter().fold(self.tr(key), |s, (name, value)| s.replace(&format!("{{{}}}", name), value))
+    }
+
+    pub fn tr_n(&self, one: &str, other: &str, n: u64) -> String {
+        let template = self.lookup(other, Some(n)).unwrap_or(if n == 1 { one } else { other });
+        template.replace("{n}", &self.format_int(n))
+    }
+
+    pub fn format_int(&self, n: u64) -> String {
+        let digits = n.to_string();
+        if digits.len() <= 4 { return digits; }
+        let mut out = String::new();
+        for (i, c) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i).is_multiple_of(3) { out.push(self.locale.group); }
+            out.push(c);
+        }
+        out
+    }
+
+    pub fn format_decimal(&self, v: f64, decimals: usize) -> String { format!("{:.*}", decimals, v).replace('.', &self.locale.decimal.to_string()) }
+
+    pub fn format_percent(&self, fraction: f64) -> String { format!("{}{}", self.format_decimal(fraction * 100.0, 0), self.locale.percent) }
+
+    pub fn format_dims(&self, w: u32, h: u32) -> String { format!("{}{}{}", w, self.locale.times, h) }
+}
+
+fn with<R>(f: impl FnOnce(&Catalog) -> R) -> R {
+    let active = ACTIVE.read().unwrap_or_else(|e| e.into_inner());
+    match active.as_ref() { Some(c) => f(c), None => f(&Catalog { locale: &LOCALES[0], entries: HashMap::new() }) }
+}
+
+pub fn set_locale(code: &str) { *ACTIVE.write().unwrap_or_else(|e| e.into_inner()) = Some(Catalog::load(code)); }
+
+pub fn tr(key: &str) -> String { with(|c| c.tr(key)) }
+
+pub fn tr_args(key: &str, args: &[(&str, &str)]) -> String { with(|c| c.tr_args(key, args)) }
+
+pub fn tr_n(one: &str, other: &str, n: usize) -> String { with(|c| c.tr_n(one, other, n as u64)) }
+
+pub fn format_percent(fraction: f64) -> String { with(|c| c.format_percent(fraction)) }
+
+pub fn format_dims(w: u32, h: u32) -> String { with(|c| c.format_dims(w, h)) }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn german_catalog_translates_pluralises_and_falls_back_to_english() {
+        let (en, de) = (Catalog::load("en"), Catalog::load("de"));
+        assert!(!de.entries.is_empty());
+        assert_eq!(en.tr("File"), "File");
+        assert_eq!(de.tr("File"), "Datei");
+        assert_eq!(de.tr("No such string anywhere"), "No such string anywhere");
+        assert_eq!(en.tr_n("{n} slice", "{n} slices", 1), "1 slice");
+        assert_eq!(en.tr_n("{n} slice", "{n} slices", 12000), "12,000 slices");
+        assert_eq!(de.tr_n("{n} slice", "{n} slices", 1), "1 Slice");
+        assert_eq!(de.tr_n("{n} slice", "{n} slices", 3), "3 Slices");
+        assert_eq!(de.tr_n("Exported {n} slice", "Exported {n} slices", 4), "4 Slices exportiert");
+        assert_eq!(de.tr_args("Skipping version {version}", &[("version", "2.1")]), "Version 2.1 wird übersprungen");
+        assert_eq!((en.format_percent(0.5), de.format_percent(0.5)), ("50%".to_string(), "50\u{a0}%".to_string()));
+        assert_eq!((en.format_decimal(1.26, 1), de.format_decimal(1.26, 1)), ("1.3".to_string(), "1,3".to_string()));
+        assert_eq!(de.format_dims(800, 600), "800 × 600");
+        assert_eq!(de.tr_args("{elapsed} · about {eta} left", &[("elapsed", "1:05"), ("eta", "12s")]), "1:05 · noch etwa 12s");
+        assert_eq!(de.tr_args("{task} finished in {elapsed}", &[("task", "Blur"), ("elapsed", "3s")]), "Blur fertig nach 3s");
+        assert_eq!(Catalog::load("xx").locale.code, "en");
+    }
+}
@@ -1,16 +1,32 @@
 #![windows_subsystem = "windows"]
 
 mod app;
+mod clipboard;
+mod diagnostics;
+mod error;
+mod export_all;
+mod focus;
+mod i18n;
 mod modules;
+mod profile;
 mod registry;
 mod style;
+mod tasks;
+mod taskbar;
+mod templates;
+mod tour;
+mod updater;
 
 use app::UniversalEditor;
+use modules::helpers::config_file;
 use eframe::egui;
 use std::path::PathBuf;
 
 fn main() -> eframe::Result<()> {
-    let startup_file: Option<PathBuf> = std::env::args().nth(1).map(PathBuf::from);
+    diagnostics::init();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    config_file::init(args.iter().any(|a| a == config_file::PORTABLE_ARG));
+    let startup_file: Option<PathBuf> = args.into_iter().find(|a| a != config_file::PORTABLE_ARG).map(PathBuf::from);
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
             .with_inner_size([1280.0, 720.0])
@@ -19,12 +35,14 @@ fn main() -> eframe::Result<()> {
         ..Default::default()
     };
     
-    eframe::run_native(
+    let result = eframe::run_native(
         "Universal Editor",
         options,
         Box::new(move |cc| {
             cc.egui_ctx.style_mut(|s| s.visuals.text_cursor.blink = false);
             Ok(Box::new(UniversalEditor::new(cc, startup_file)))
         }),
-    )
+    );
+    modules::helpers::share::cleanup();
+    result
 }
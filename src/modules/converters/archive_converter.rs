@@ -407,9 +407,10 @@ impl ArchiveConverter {
 
 impl EditorModule for ArchiveConverter {
     fn as_any(&self) -> &dyn std::any::Any { self }
-    fn save(&mut self) -> Result<(), String> { Ok(()) }
-    fn save_as(&mut self) -> Result<(), String> { Ok(()) }
+    fn save(&mut self) -> crate::error::EditorResult<crate::modules::SaveState> { Ok(crate::modules::SaveState::Saved) }
+    fn save_as(&mut self) -> crate::error::EditorResult<crate::modules::SaveState> { Ok(crate::modules::SaveState::Saved) }
     fn get_title(&self) -> String { "Archive Converter".to_string() }
+    fn accepts_dropped_file(&self, _path: &std::path::Path) -> bool { true }
     
     fn ui(&mut self, ui: &mut egui::Ui, ctx: &egui::Context, _show_toolbar: bool, _show_file_info: bool) {
         let theme = if ui.visuals().dark_mode { ThemeMode::Dark } else { ThemeMode::Light };
@@ -522,9 +522,15 @@ fn write_xml_node<W: Write>(writer: &mut quick_xml::Writer<W>, tag: &str, value:
 
 impl EditorModule for DataConverter {
     fn as_any(&self) -> &dyn std::any::Any { self }
-    fn save(&mut self) -> Result<(), String> { Ok(()) }
-    fn save_as(&mut self) -> Result<(), String> { Ok(()) }
+    fn save(&mut self) -> crate::error::EditorResult<crate::modules::SaveState> { Ok(crate::modules::SaveState::Saved) }
+    fn save_as(&mut self) -> crate::error::EditorResult<crate::modules::SaveState> { Ok(crate::modules::SaveState::Saved) }
     fn get_title(&self) -> String { "Data Format Converter".to_string() }
+    fn accepts_dropped_file(&self, _path: &std::path::Path) -> bool { true }
+
+    fn progress(&self) -> Option<f32> {
+        let p = self.progress.lock().unwrap();
+        (p.state == ConversionState::Converting && p.total > 0).then(|| p.current as f32 / p.total as f32)
+    }
     
     fn ui(&mut self, ui: &mut egui::Ui, ctx: &egui::Context, _show_toolbar: bool, _show_file_info: bool) {
         let theme = if ui.visuals().dark_mode { ThemeMode::Dark } else { ThemeMode::Light };
@@ -1,7 +1,7 @@
 use eframe::egui;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
-use std::thread;
+use crate::tasks;
 use crate::style::{ColorPalette, ThemeMode};
 use crate::modules::image_export::{ExportFormat, export_image};
 use crate::modules::EditorModule;
@@ -19,7 +19,7 @@ impl ImageFile {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
-enum ConversionState { Idle, Converting, Completed, Failed }
+enum ConversionState { Idle, Converting, Completed, Failed, Cancelled }
 #[derive(Debug, Clone)]
 struct ConversionProgress {state: ConversionState, current: usize, total: usize, message: String }
 impl Default for ConversionProgress { fn default() -> Self { Self { state: ConversionState::Idle, current: 0, total: 0, message: String::new() } } }
@@ -84,8 +84,10 @@ impl ImageConverter {
     fn remove_image(&mut self, index: usize) { if index < self.images.len() { self.images.remove(index); } }
     fn clear_images(&mut self) { self.images.clear(); }
 
-    fn start_conversion(&mut self) {
-        if self.images.is_empty() { return; }
+    fn is_converting(&self) -> bool { self.progress.lock().unwrap().state == ConversionState::Converting }
+
+    fn start_conversion(&mut self) -> Option<tasks::Task> {
+        if self.images.is_empty() { return None; }
         self.conversion_errors.lock().unwrap().clear();
         let output_dir = self.output_directory.clone().unwrap_or_else(|| self.images[0].path.parent().unwrap_or(std::path::Path::new(".")).to_path_buf());
         let images = self.images.clone();
@@ -101,16 +103,21 @@ impl ImageConverter {
         let progress = Arc::clone(&self.progress);
         let errors = Arc::clone(&self.conversion_errors);
         let auto_scale_ico = self.auto_scale_ico;
-        thread::spawn(move || {{
-                let mut p = progress.lock().unwrap();
-                p.state = ConversionState::Converting; p.current = 0; p.total = images.len(); p.message = "Starting conversion...".to_string();
-            }
+        {
+            let mut p = progress.lock().unwrap();
+            p.state = ConversionState::Converting; p.current = 0; p.total = images.len(); p.message = "Waiting for other tasks...".to_string();
+        }
+        let name = if images.len() == 1 { "Convert 1 image".to_string() } else { format!("Convert {} images", images.len()) };
+        Some(tasks::spawn(name, true, move |task| {
             let mut success_count = 0;
             let mut fail_count = 0;
-            for (idx, image) in images.iter().enumerate() {{
+            for (idx, image) in images.iter().enumerate() {
+                if task.cancelled() { break; }
+                {
                     let mut p = progress.lock().unwrap();
                     p.current = idx + 1; p.message = format!("Converting {} ({}/{})", image.file_name(), idx + 1, images.len());
                 }
+                task.progress(idx as f32 / images.len() as f32);
                 match Self::convert_image(&image.path, &output_dir, target_format, jpeg_quality, png_compression, webp_quality, overwrite, add_suffix, &suffix, auto_scale_ico, avif_quality, avif_speed) {
                     Ok(_) => success_count += 1,
                     Err(e) => {
@@ -120,9 +127,10 @@ impl ImageConverter {
                 }
             }
             let mut p = progress.lock().unwrap();
-            p.state = if fail_count == 0 { ConversionState::Completed } else { ConversionState::Failed };
-            p.message = format!("Completed: {} succeeded, {} failed", success_count, fail_count);
-        });
+            p.state = if task.cancelled() { ConversionState::Cancelled } else if fail_count == 0 { ConversionState::Completed } else { ConversionState::Failed };
+            p.message = if task.cancelled() { format!("Cancelled: {} converted before stopping", success_count) } else { format!("Completed: {} succeeded, {} failed", success_count, fail_count) };
+            task.finish(Some(format!("Image conversion: {}", p.message.to_lowercase())));
+        }))
     }
 
     fn convert_image(input_path: &PathBuf, output_dir: &PathBuf, target_format: ExportFormat, jpeg_quality: u8, png_compression: u8, webp_quality: f32,
@@ -133,7 +141,7 @@ impl ImageConverter {
         let new_stem = if add_suffix { format!("{}{}", stem, suffix) } else { stem.to_string() };
         let output_path = output_dir.join(format!("{}.{}", new_stem, target_format.extension()));
         if output_path.exists() && !overwrite { return Err("File exists and overwrite is disabled".to_string()); }
-        export_image(&img, &output_path, target_format, jpeg_quality, png_compression, webp_quality, auto_scale_ico, avif_quality, avif_speed)
+        export_image(&img, &output_path, target_format, jpeg_quality, png_compression, webp_quality, auto_scale_ico, avif_quality, avif_speed).map_err(|e| e.to_string())
     }
 
     fn render_header(&self, ui: &mut egui::Ui, theme: ThemeMode) {
@@ -343,7 +351,7 @@ impl ImageConverter {
             ConversionState::Converting => ColorPalette::BLUE_500,
             ConversionState::Completed => ColorPalette::GREEN_500,
             ConversionState::Failed => ColorPalette::RED_500,
-            ConversionState::Idle => ColorPalette::ZINC_500,
+            ConversionState::Idle | ConversionState::Cancelled => ColorPalette::ZINC_500,
         };
         let fraction = if progress.total > 0 { progress.current as f32 / progress.total as f32 } else { 0.0 };
         egui::Frame::new().fill(panel_bg).stroke(egui::Stroke::new(1.0, border_color)).corner_radius(8.0).inner_margin(16.0).show(ui, |ui| {
@@ -359,7 +367,7 @@ impl ImageConverter {
     }
 
     fn render_action_buttons(&mut self, ui: &mut egui::Ui, theme: ThemeMode) {
-        let is_converting = self.progress.lock().unwrap().state == ConversionState::Converting;
+        let is_converting = self.is_converting();
         let can_convert = !self.images.is_empty() && !is_converting;
         let (button_bg, button_hover, button_text) = if can_convert {
             (ColorPalette::BLUE_600, ColorPalette::BLUE_500, egui::Color32::WHITE)
@@ -388,9 +396,15 @@ impl ImageConverter {
 
 impl EditorModule for ImageConverter {
     fn as_any(&self) -> &dyn std::any::Any { self }
-    fn save(&mut self) -> Result<(), String> { Ok(()) }
-    fn save_as(&mut self) -> Result<(), String> { Ok(()) }
+    fn save(&mut self) -> crate::error::EditorResult<crate::modules::SaveState> { Ok(crate::modules::SaveState::Saved) }
+    fn save_as(&mut self) -> crate::error::EditorResult<crate::modules::SaveState> { Ok(crate::modules::SaveState::Saved) }
     fn get_title(&self) -> String { "Image Converter".to_string() }
+    fn accepts_dropped_file(&self, _path: &std::path::Path) -> bool { true }
+
+    fn progress(&self) -> Option<f32> {
+        let p = self.progress.lock().unwrap();
+        (p.state == ConversionState::Converting && p.total > 0).then(|| p.current as f32 / p.total as f32)
+    }
 
     fn ui(&mut self, ui: &mut egui::Ui, ctx: &egui::Context, _show_toolbar: bool, _show_file_info: bool) {
         let theme = if ui.visuals().dark_mode { ThemeMode::Dark } else { ThemeMode::Light };
@@ -423,3 +437,29 @@ impl EditorModule for ImageConverter {
         ctx.request_repaint();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+
+    #[test]
+    fn cancelling_a_queued_conversion_frees_the_converter() {
+        let gate = Arc::new(AtomicBool::new(false));
+        let blockers: Vec<tasks::Task> = (0..tasks::MAX_RUNNING).map(|i| {
+            let gate = gate.clone();
+            tasks::spawn(format!("converter blocker {}", i), false, move |_| while !gate.load(Ordering::Relaxed) { std::thread::sleep(Duration::from_millis(5)); })
+        }).collect();
+        while blockers.iter().any(|t| t.is_queued()) { std::thread::sleep(Duration::from_millis(5)); }
+        let mut converter = ImageConverter::new();
+        converter.images.push(ImageFile::new(PathBuf::from("queued.png")));
+        let task = converter.start_conversion().expect("a conversion starts with an image listed");
+        assert!(task.is_queued() && converter.is_converting());
+        task.cancel();
+        while converter.is_converting() { std::thread::sleep(Duration::from_millis(5)); }
+        gate.store(true, Ordering::Relaxed);
+        assert_eq!(converter.progress.lock().unwrap().state, ConversionState::Cancelled);
+        assert!(converter.conversion_errors.lock().unwrap().is_empty());
+    }
+}
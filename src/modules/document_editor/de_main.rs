@@ -1,9 +1,17 @@
 use eframe::egui;
 use std::collections::VecDeque;
 use std::path::PathBuf;
-use crate::modules::{EditorModule, MenuAction, MenuItem, MenuContribution};
+use crate::modules::{EditorModule, SaveState, MenuAction, MenuEntry, MenuContribution, KeyBinding, ViewState, Shortcut, shortcut, key, ctrl, shift, ctrl_shift, ctrl_alt, menu_keys};
+use crate::error::{EditorError, EditorResult};
 use super::de_tools::*;
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DocumentMenuAction {
+    Find, Stats, PageSettings, ToggleOutline, ZoomIn, ZoomOut, ZoomReset, InsertImage,
+    InsertBulletList, InsertNumberedList, InsertChecklist, InsertHorizontalRule,
+    ToggleStrike, ToggleSuperscript, ToggleSubscript, IncreaseIndent, DecreaseIndent,
+}
+
 #[derive(Clone, Copy, PartialEq, Debug, Default)]
 pub(super) struct DocPos { pub para: usize, pub byte: usize }
 
@@ -17,6 +25,7 @@ pub struct DocumentEditor {
     pub(super) last_selection: Option<(usize, usize, usize)>,
     pub(super) pending_focus: Option<usize>,
     pub(super) show_outline: bool,
+    pub(super) focus_mode: bool,
     pub(super) show_stats: bool,
     pub(super) show_page_settings: bool,
     pub(super) find_text: String,
@@ -56,6 +65,7 @@ pub struct DocumentEditor {
     pub(super) ctx_sel: Option<(usize, usize, usize)>,
     pub(super) doc_scroll_y: f32,
     pub(super) ctx_link_show: bool,
+    pub(super) pending_error: Option<EditorError>,
 }
 
 impl DocumentEditor {
@@ -83,7 +93,7 @@ impl DocumentEditor {
         Self {
             file_path: path, dirty: false, paras, layout, cur_fmt: SpanFmt::default(),
             focused_para: 0, last_selection: None, pending_focus: None,
-            show_outline: false, show_stats: false, show_page_settings: false,
+            show_outline: false, focus_mode: false, show_stats: false, show_page_settings: false,
             find_text: String::new(), replace_text: String::new(), show_find: false, focus_find: false,
             find_results: Vec::new(), find_cursor: 0, find_stale: false,
             zoom: 1.0, auto_zoom_done: false, scroll_to_para: None,
@@ -95,7 +105,7 @@ impl DocumentEditor {
             doc_sel: None, page_settings_draft: None, last_edit_action: 0,
             table_picker_hover: (0, 0), active_table: None, table_sel: None, table_multi_sel: None, table_text_sel: None, cell_edit_buf: String::new(),
             image_textures: std::collections::HashMap::new(), selected_image_para: None, image_drag: None, next_image_uid: 0,
-            toolbar_has_focus: false, pending_open_in_image_editor: None, ctx_sel: None, doc_scroll_y: 0.0, ctx_link_show: false,
+            toolbar_has_focus: false, pending_open_in_image_editor: None, ctx_sel: None, doc_scroll_y: 0.0, ctx_link_show: false, pending_error: None,
         }
     }
 
@@ -716,7 +726,7 @@ impl DocumentEditor {
         false
     }
 
-    fn save_impl(&mut self, path: PathBuf) -> Result<(), String> {
+    fn save_impl(&mut self, path: PathBuf) -> EditorResult {
         let _ = self.commit_active_table_cell();
         let mut save_paras = self.paras.clone();
         let mut j = 0;
@@ -732,7 +742,7 @@ impl DocumentEditor {
         match ext.as_str() {
             "docx" | "doc" => save_docx(&path, &save_paras, &self.layout)?,
             "odt" => save_odt(&path, &save_paras, &self.layout)?,
-            _ => { let t: String = save_paras.iter().map(|p| p.text.as_str()).collect::<Vec<_>>().join("\n"); std::fs::write(&path, t).map_err(|e| e.to_string())?; }
+            _ => { let t: String = save_paras.iter().map(|p| p.text.as_str()).collect::<Vec<_>>().join("\n"); std::fs::write(&path, t).map_err(|e| EditorError::io(&path, e))?; }
         }
         self.file_path = Some(path); self.dirty = false; Ok(())
     }
@@ -793,22 +803,53 @@ impl DocumentEditor {
     }
 }
 
+/// What the document editor's keys do; `DE_SHORTCUTS` binds them and the editor's frame runs them.
+#[derive(Clone, Copy, PartialEq)]
+pub(super) enum DocumentKey { Save, Undo, Redo, Find, Bold, Italic, Underline, Heading, Indent, Outdent, SelectAll, ZoomIn, ZoomOut, ZoomReset }
+
+pub(super) const DE_SHORTCUTS: &[Shortcut<DocumentKey>] = &[
+    shortcut(&[ctrl(egui::Key::S)], DocumentKey::Save, "Save"),
+    shortcut(&[ctrl(egui::Key::Z)], DocumentKey::Undo, "Undo"),
+    shortcut(&[ctrl(egui::Key::Y), ctrl_shift(egui::Key::Z)], DocumentKey::Redo, "Redo"),
+    shortcut(&[ctrl(egui::Key::F)], DocumentKey::Find, "Find"),
+    shortcut(&[ctrl(egui::Key::B)], DocumentKey::Bold, "Bold"),
+    shortcut(&[ctrl(egui::Key::I)], DocumentKey::Italic, "Italic"),
+    shortcut(&[ctrl(egui::Key::U)], DocumentKey::Underline, "Underline"),
+    shortcut(&[ctrl_alt(egui::Key::Num1), ctrl_alt(egui::Key::Num2), ctrl_alt(egui::Key::Num3), ctrl_alt(egui::Key::Num4), ctrl_alt(egui::Key::Num5)], DocumentKey::Heading, "Heading level"),
+    shortcut(&[key(egui::Key::Tab), ctrl(egui::Key::M)], DocumentKey::Indent, "Indent"),
+    shortcut(&[shift(egui::Key::Tab), ctrl_shift(egui::Key::M)], DocumentKey::Outdent, "Outdent"),
+    shortcut(&[ctrl(egui::Key::A)], DocumentKey::SelectAll, "Select all"),
+    shortcut(&[ctrl(egui::Key::Plus), ctrl(egui::Key::Equals)], DocumentKey::ZoomIn, "Zoom in"),
+    shortcut(&[ctrl(egui::Key::Minus)], DocumentKey::ZoomOut, "Zoom out"),
+    shortcut(&[ctrl(egui::Key::Num0)], DocumentKey::ZoomReset, "Reset zoom"),
+];
+
+pub fn key_bindings() -> Vec<KeyBinding> { crate::modules::key_bindings(DE_SHORTCUTS) }
+
 impl EditorModule for DocumentEditor {
     fn as_any(&self) -> &dyn std::any::Any { self }
     fn get_title(&self) -> String {
         let name = self.file_path.as_ref().and_then(|p| p.file_name()).and_then(|n| n.to_str()).unwrap_or("Untitled").to_string();
         if self.dirty { format!("{} *", name) } else { name }
     }
-    fn save(&mut self) -> Result<(), String> {
-        if let Some(p) = self.file_path.clone() { self.save_impl(p) } else { self.save_as() }
+    fn save(&mut self) -> EditorResult<SaveState> {
+        if let Some(p) = self.file_path.clone() { self.save_impl(p).map(|_| SaveState::Saved) } else { self.save_as() }
     }
-    fn save_as(&mut self) -> Result<(), String> {
+    fn save_as(&mut self) -> EditorResult<SaveState> {
         if let Some(path) = rfd::FileDialog::new()
             .add_filter("Word Document", &["docx"])
             .add_filter("OpenDocument Text", &["odt"])
             .add_filter("Text", &["txt"])
-            .save_file() { self.save_impl(path) }
-        else { Err("Cancelled".to_string()) }
+            .save_file() { self.save_impl(path).map(|_| SaveState::Saved) }
+        else { Err(EditorError::Cancelled) }
+    }
+    fn take_error(&mut self) -> Option<EditorError> { self.pending_error.take() }
+    fn key_bindings(&self) -> Vec<KeyBinding> { key_bindings() }
+    fn file_path(&self) -> Option<&std::path::Path> { self.file_path.as_deref() }
+    fn view_state(&self) -> Option<ViewState> { Some(ViewState { cursor: Some(self.focused_para), zoom: Some(self.zoom), ..Default::default() }) }
+    fn restore_view_state(&mut self, state: ViewState) {
+        if let Some(z) = state.zoom { self.zoom = z; self.auto_zoom_done = true; }
+        if let Some(i) = state.cursor.filter(|&i| i < self.paras.len()) { self.focused_para = i; self.scroll_to_para = Some(i); }
     }
     fn take_open_in_image_editor(&mut self) -> Option<Vec<u8>> {
         self.pending_open_in_image_editor.take()
@@ -816,36 +857,36 @@ impl EditorModule for DocumentEditor {
     fn get_menu_contributions(&self) -> MenuContribution {
         MenuContribution {
             file_items: vec![
-                (MenuItem { label: "Find & Replace...".into(), shortcut: Some("Ctrl+F".into()), enabled: true }, MenuAction::Custom("Find".into())),
-                (MenuItem { label: "Document Statistics".into(), shortcut: None, enabled: true }, MenuAction::Custom("Stats".into())),
-                (MenuItem { label: "Page Settings...".into(), shortcut: None, enabled: true }, MenuAction::Custom("PageSettings".into())),
+                MenuEntry::item("Find & Replace...", DocumentMenuAction::Find).shortcut(&menu_keys(DE_SHORTCUTS, DocumentKey::Find)),
+                MenuEntry::item("Document Statistics", DocumentMenuAction::Stats),
+                MenuEntry::item("Page Settings...", DocumentMenuAction::PageSettings),
             ],
             edit_items: vec![
-                (MenuItem { label: "Undo".into(), shortcut: Some("Ctrl+Z".into()), enabled: !self.undo_stack.is_empty() }, MenuAction::Undo),
-                (MenuItem { label: "Redo".into(), shortcut: Some("Ctrl+Y".into()), enabled: !self.redo_stack.is_empty() }, MenuAction::Redo),
+                MenuEntry::item("Undo", MenuAction::Undo).shortcut(&menu_keys(DE_SHORTCUTS, DocumentKey::Undo)).enabled(!self.undo_stack.is_empty()),
+                MenuEntry::item("Redo", MenuAction::Redo).shortcut(&menu_keys(DE_SHORTCUTS, DocumentKey::Redo)).enabled(!self.redo_stack.is_empty()),
             ],
             view_items: vec![
-                (MenuItem { label: if self.show_outline { "Hide Outline".into() } else { "Show Outline".into() }, shortcut: None, enabled: true }, MenuAction::Custom("ToggleOutline".into())),
-                (MenuItem { label: "Zoom In".into(), shortcut: Some("Ctrl++".into()), enabled: true }, MenuAction::Custom("ZoomIn".into())),
-                (MenuItem { label: "Zoom Out".into(), shortcut: Some("Ctrl+-".into()), enabled: true }, MenuAction::Custom("ZoomOut".into())),
-                (MenuItem { label: "Reset Zoom".into(), shortcut: Some("Ctrl+0".into()), enabled: true }, MenuAction::Custom("ZoomReset".into())),
+                MenuEntry::item(if self.show_outline { "Hide Outline" } else { "Show Outline" }, DocumentMenuAction::ToggleOutline),
+                MenuEntry::item("Zoom In", DocumentMenuAction::ZoomIn).shortcut(&menu_keys(DE_SHORTCUTS, DocumentKey::ZoomIn)),
+                MenuEntry::item("Zoom Out", DocumentMenuAction::ZoomOut).shortcut(&menu_keys(DE_SHORTCUTS, DocumentKey::ZoomOut)),
+                MenuEntry::item("Reset Zoom", DocumentMenuAction::ZoomReset).shortcut(&menu_keys(DE_SHORTCUTS, DocumentKey::ZoomReset)),
             ],
             insert_items: vec![
-                (MenuItem { label: "Insert Image...".into(), shortcut: None, enabled: true }, MenuAction::Custom("InsertImage".into())),
-                (MenuItem { label: "Separator".into(), shortcut: None, enabled: false }, MenuAction::None),
-                (MenuItem { label: "Bullet List".into(), shortcut: None, enabled: true }, MenuAction::Custom("InsertBulletList".into())),
-                (MenuItem { label: "Numbered List".into(), shortcut: None, enabled: true }, MenuAction::Custom("InsertNumberedList".into())),
-                (MenuItem { label: "Checklist".into(), shortcut: None, enabled: true }, MenuAction::Custom("InsertChecklist".into())),
-                (MenuItem { label: "Separator".into(), shortcut: None, enabled: false }, MenuAction::None),
-                (MenuItem { label: "Horizontal Line".into(), shortcut: None, enabled: true }, MenuAction::Custom("InsertHorizontalRule".into())),
+                MenuEntry::item("Insert Image...", DocumentMenuAction::InsertImage),
+                MenuEntry::Separator,
+                MenuEntry::item("Bullet List", DocumentMenuAction::InsertBulletList),
+                MenuEntry::item("Numbered List", DocumentMenuAction::InsertNumberedList),
+                MenuEntry::item("Checklist", DocumentMenuAction::InsertChecklist),
+                MenuEntry::Separator,
+                MenuEntry::item("Horizontal Line", DocumentMenuAction::InsertHorizontalRule),
             ],
             format_items: vec![
-                (MenuItem { label: "Strikethrough".into(), shortcut: None, enabled: true }, MenuAction::Custom("ToggleStrike".into())),
-                (MenuItem { label: "Superscript".into(), shortcut: None, enabled: true }, MenuAction::Custom("ToggleSuperscript".into())),
-                (MenuItem { label: "Subscript".into(), shortcut: None, enabled: true }, MenuAction::Custom("ToggleSubscript".into())),
-                (MenuItem { label: "Separator".into(), shortcut: None, enabled: false }, MenuAction::None),
-                (MenuItem { label: "Increase Indent".into(), shortcut: None, enabled: true }, MenuAction::Custom("IncreaseIndent".into())),
-                (MenuItem { label: "Decrease Indent".into(), shortcut: None, enabled: true }, MenuAction::Custom("DecreaseIndent".into())),
+                MenuEntry::item("Strikethrough", DocumentMenuAction::ToggleStrike),
+                MenuEntry::item("Superscript", DocumentMenuAction::ToggleSuperscript),
+                MenuEntry::item("Subscript", DocumentMenuAction::ToggleSubscript),
+                MenuEntry::Separator,
+                MenuEntry::item("Increase Indent", DocumentMenuAction::IncreaseIndent),
+                MenuEntry::item("Decrease Indent", DocumentMenuAction::DecreaseIndent),
             ],
             image_items: vec![], filter_items: vec![], layer_items: vec![],
         }
@@ -854,24 +895,24 @@ impl EditorModule for DocumentEditor {
         match action {
             MenuAction::Undo => { self.undo(); true }
             MenuAction::Redo => { self.redo(); true }
-            MenuAction::Custom(ref v) => match v.as_str() {
-                "Find" => { self.show_find = true; self.focus_find = true; true }
-                "Stats" => { self.show_stats = true; true }
-                "PageSettings" => { self.page_settings_draft = None; self.show_page_settings = true; true }
-                "ToggleOutline" => { self.show_outline = !self.show_outline; true }
-                "ZoomIn" => { self.zoom = (self.zoom + 0.1).min(3.0); true }
-                "ZoomOut" => { self.zoom = (self.zoom - 0.1).max(0.3); true }
-                "ZoomReset" => { self.auto_zoom_done = false; true }
-                "InsertBulletList" => { self.apply_style_toggle(ParaStyle::ListBullet); true }
-                "InsertNumberedList" => { self.apply_style_toggle(ParaStyle::ListOrdered); true }
-                "InsertChecklist" => { self.apply_style_toggle(ParaStyle::ListCheck); true }
-                "InsertHorizontalRule" => { self.insert_horizontal_rule_after_focus(); true }
-                "ToggleStrike" => { self.apply_fmt_toggle_strike(); true }
-                "ToggleSuperscript" => { self.apply_fmt_toggle_sup(); true }
-                "ToggleSubscript" => { self.apply_fmt_toggle_sub(); true }
-                "IncreaseIndent" => { self.adjust_indent_selection(36.0); true }
-                "DecreaseIndent" => { self.adjust_indent_selection(-36.0); true }
-                "InsertImage" => {
+            MenuAction::Document(action) => match action {
+                DocumentMenuAction::Find => { self.show_find = true; self.focus_find = true; true }
+                DocumentMenuAction::Stats => { self.show_stats = true; true }
+                DocumentMenuAction::PageSettings => { self.page_settings_draft = None; self.show_page_settings = true; true }
+                DocumentMenuAction::ToggleOutline => { self.show_outline = !self.show_outline; true }
+                DocumentMenuAction::ZoomIn => { self.zoom = (self.zoom + 0.1).min(3.0); true }
+                DocumentMenuAction::ZoomOut => { self.zoom = (self.zoom - 0.1).max(0.3); true }
+                DocumentMenuAction::ZoomReset => { self.auto_zoom_done = false; true }
+                DocumentMenuAction::InsertBulletList => { self.apply_style_toggle(ParaStyle::ListBullet); true }
+                DocumentMenuAction::InsertNumberedList => { self.apply_style_toggle(ParaStyle::ListOrdered); true }
+                DocumentMenuAction::InsertChecklist => { self.apply_style_toggle(ParaStyle::ListCheck); true }
+                DocumentMenuAction::InsertHorizontalRule => { self.insert_horizontal_rule_after_focus(); true }
+                DocumentMenuAction::ToggleStrike => { self.apply_fmt_toggle_strike(); true }
+                DocumentMenuAction::ToggleSuperscript => { self.apply_fmt_toggle_sup(); true }
+                DocumentMenuAction::ToggleSubscript => { self.apply_fmt_toggle_sub(); true }
+                DocumentMenuAction::IncreaseIndent => { self.adjust_indent_selection(36.0); true }
+                DocumentMenuAction::DecreaseIndent => { self.adjust_indent_selection(-36.0); true }
+                DocumentMenuAction::InsertImage => {
                     if let Some(path) = rfd::FileDialog::new().add_filter("Images", &["jpg", "jpeg", "png", "webp", "bmp", "tiff", "ico"]).pick_file() {
                         if let Ok(img) = image::open(&path) {
                             let iw = img.width() as f32;
@@ -888,7 +929,6 @@ impl EditorModule for DocumentEditor {
                     }
                     true
                 }
-                _ => false,
             },
             _ => false,
         }
@@ -896,4 +936,5 @@ impl EditorModule for DocumentEditor {
     fn ui(&mut self, ui: &mut egui::Ui, ctx: &egui::Context, _show_toolbar: bool, _show_file_info: bool) {
         super::de_ui::render(self, ui, ctx);
     }
+    fn set_focus_mode(&mut self, focus: bool) { self.focus_mode = focus; }
 }
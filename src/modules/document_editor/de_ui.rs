@@ -1,7 +1,7 @@
 use eframe::egui;
 use crate::style::{ColorPalette, ThemeMode, toolbar_action_btn, toolbar_toggle_btn};
-use super::de_main::{DocumentEditor, DocPos};
-use crate::modules::EditorModule;
+use super::de_main::{DocumentEditor, DocPos, DocumentKey, DE_SHORTCUTS};
+use crate::modules::{EditorModule, pressed_shortcuts};
 use super::de_tools::*;
 use std::cell::RefCell;
 
@@ -549,12 +549,14 @@ pub fn render(ed: &mut DocumentEditor, ui: &mut egui::Ui, ctx: &egui::Context) {
     let theme = if is_dark { ThemeMode::Dark } else { ThemeMode::Light };
     handle_keyboard(ed, ctx);
     ed.run_find();
-    render_toolbar(ed, ui, theme, is_dark);
-    ui.separator();
+    if !ed.focus_mode {
+        render_toolbar(ed, ui, theme, is_dark);
+        ui.separator();
+    }
     egui::SidePanel::left("de_outline_panel").resizable(true).default_width(200.0).min_width(140.0).max_width(320.0)
         .frame(egui::Frame::new().fill(if is_dark { egui::Color32::from_rgb(20,20,26) } else { ColorPalette::GRAY_50 })
             .stroke(egui::Stroke::new(1.0, if is_dark { ColorPalette::ZINC_700 } else { ColorPalette::GRAY_300 })))
-        .show_animated_inside(ui, ed.show_outline, |ui| render_outline(ed, ui, is_dark));
+        .show_animated_inside(ui, ed.show_outline && !ed.focus_mode, |ui| render_outline(ed, ui, is_dark));
     egui::CentralPanel::default()
         .frame(egui::Frame::new().fill(if is_dark { egui::Color32::from_rgb(14,14,18) } else { egui::Color32::from_rgb(188,188,196) }))
         .show_inside(ui, |ui| render_canvas(ed, ui, ctx, is_dark));
@@ -608,31 +610,35 @@ fn handle_keyboard(ed: &mut DocumentEditor, ctx: &egui::Context) {
         }
     }
 
-    ctx.input_mut(|i| {
-        if !ed.has_cross_sel() && i.events.iter().any(|e| matches!(e, egui::Event::Paste(_))) { ed.push_undo(); }
-        if i.consume_key(egui::Modifiers::CTRL, egui::Key::Z) { ed.undo(); }
-        if i.consume_key(egui::Modifiers::CTRL | egui::Modifiers::SHIFT, egui::Key::Z) || i.consume_key(egui::Modifiers::CTRL, egui::Key::Y) { ed.redo(); }
-        if i.consume_key(egui::Modifiers::CTRL, egui::Key::S) { let _ = ed.save(); }
-        if i.consume_key(egui::Modifiers::CTRL, egui::Key::F) { ed.show_find = true; ed.focus_find = true; }
-        if i.consume_key(egui::Modifiers::CTRL, egui::Key::Plus) || i.consume_key(egui::Modifiers::CTRL, egui::Key::Equals) { ed.zoom = (ed.zoom + 0.1).min(3.0); }
-        if i.consume_key(egui::Modifiers::CTRL, egui::Key::Minus) { ed.zoom = (ed.zoom - 0.1).max(0.3); }
-        if i.consume_key(egui::Modifiers::CTRL, egui::Key::Num0) { ed.auto_zoom_done = false; }
-        if i.consume_key(egui::Modifiers::CTRL, egui::Key::A) {
-            if let Some((pi, _, _)) = ed.active_table {
-                let new_sel = ed.paras.get(pi).and_then(|p| p.table.as_ref()).map(|tbl| {
-                    let lr = tbl.rows.len().saturating_sub(1);
-                    let lc = tbl.rows.iter().map(|r| r.len()).max().unwrap_or(1).saturating_sub(1);
-                    (pi, (0usize, 0usize), (lr, lc))
-                });
-                if let Some(s) = new_sel { ed.table_sel = Some(s); ed.table_multi_sel = None; }
-            } else {
-                let last = ed.paras.len().saturating_sub(1);
-                let end = ed.paras.last().map(|p| p.text.len()).unwrap_or(0);
-                ed.doc_sel = Some([DocPos { para: 0, byte: 0 }, DocPos { para: last, byte: end }]);
-                ed.table_multi_sel = None;
+    if !ed.has_cross_sel() && ctx.input(|i| i.events.iter().any(|e| matches!(e, egui::Event::Paste(_)))) { ed.push_undo(); }
+    let general = |a| !matches!(a, DocumentKey::Bold | DocumentKey::Italic | DocumentKey::Underline | DocumentKey::Heading | DocumentKey::Indent | DocumentKey::Outdent);
+    for (action, _) in ctx.input_mut(|i| pressed_shortcuts(i, DE_SHORTCUTS, general)) {
+        match action {
+            DocumentKey::Undo => ed.undo(),
+            DocumentKey::Redo => ed.redo(),
+            DocumentKey::Save => ed.pending_error = ed.save().err(),
+            DocumentKey::Find => { ed.show_find = true; ed.focus_find = true; }
+            DocumentKey::ZoomIn => ed.zoom = (ed.zoom + 0.1).min(3.0),
+            DocumentKey::ZoomOut => ed.zoom = (ed.zoom - 0.1).max(0.3),
+            DocumentKey::ZoomReset => ed.auto_zoom_done = false,
+            DocumentKey::SelectAll => {
+                if let Some((pi, _, _)) = ed.active_table {
+                    let new_sel = ed.paras.get(pi).and_then(|p| p.table.as_ref()).map(|tbl| {
+                        let lr = tbl.rows.len().saturating_sub(1);
+                        let lc = tbl.rows.iter().map(|r| r.len()).max().unwrap_or(1).saturating_sub(1);
+                        (pi, (0usize, 0usize), (lr, lc))
+                    });
+                    if let Some(s) = new_sel { ed.table_sel = Some(s); ed.table_multi_sel = None; }
+                } else {
+                    let last = ed.paras.len().saturating_sub(1);
+                    let end = ed.paras.last().map(|p| p.text.len()).unwrap_or(0);
+                    ed.doc_sel = Some([DocPos { para: 0, byte: 0 }, DocPos { para: last, byte: end }]);
+                    ed.table_multi_sel = None;
+                }
             }
+            _ => {}
         }
-    });
+    }
 }
 
 fn fmt_btn(ui: &mut egui::Ui, label: impl Into<egui::WidgetText>, active: bool, theme: ThemeMode, tip: &str) -> bool {
@@ -1740,15 +1746,9 @@ fn render_canvas(ed: &mut DocumentEditor, ui: &mut egui::Ui, ctx: &egui::Context
                 }
             }
             if ed.pending_focus == Some(i) && ed.active_table.is_none() && !ed.toolbar_has_focus { ctx.memory_mut(|m| m.request_focus(id)); }
-            let tab_keys = if i == focused {
-                ctx.input_mut(|inp| {
-                    let shift_tab = inp.consume_key(egui::Modifiers::SHIFT, egui::Key::Tab);
-                    let ctrl_shft_m = inp.consume_key(egui::Modifiers::CTRL | egui::Modifiers::SHIFT, egui::Key::M);
-                    let plain_tab = inp.consume_key(egui::Modifiers::NONE, egui::Key::Tab);
-                    let ctrl_m = inp.consume_key(egui::Modifiers::CTRL, egui::Key::M);
-                    (plain_tab, shift_tab, ctrl_m, ctrl_shft_m)
-                })
-            } else { (false, false, false, false) };
+            let indent = if i == focused {
+                ctx.input_mut(|inp| pressed_shortcuts(inp, DE_SHORTCUTS, |a| matches!(a, DocumentKey::Indent | DocumentKey::Outdent))).first().map(|&(a, _)| a)
+            } else { None };
             let text_ref = &mut ed.para_texts[i];
             let effective_rect = if i + 1 < n && ed.paras[i + 1].style == ParaStyle::Table {
                 let tpg = pl.para_page[i + 1];
@@ -1772,22 +1772,17 @@ fn render_canvas(ed: &mut DocumentEditor, ui: &mut egui::Ui, ctx: &egui::Context
             let has_focus = output.response.has_focus();
             if has_focus && ed.focused_para != i && ed.active_table.is_none() { ed.focused_para = i; ed.line_spacing_input = ed.paras[i].line_height; ed.last_edit_action = 0; }
             if has_focus {
-                let mut b = false; let mut it = false; let mut u = false; let mut h: u8 = 0;
-                ctx.input_mut(|inp| {
-                    if inp.consume_key(egui::Modifiers::CTRL, egui::Key::B) { b = true; }
-                    if inp.consume_key(egui::Modifiers::CTRL, egui::Key::I) { it = true; }
-                    if inp.consume_key(egui::Modifiers::CTRL, egui::Key::U) { u = true; }
-                    for (key, level) in [(egui::Key::Num1,1u8),(egui::Key::Num2,2),(egui::Key::Num3,3),(egui::Key::Num4,4),(egui::Key::Num5,5)] {
-                        if inp.consume_key(egui::Modifiers::CTRL | egui::Modifiers::ALT, key) { h = level; }
+                let format = |a| matches!(a, DocumentKey::Bold | DocumentKey::Italic | DocumentKey::Underline | DocumentKey::Heading);
+                for (action, chord) in ctx.input_mut(|inp| pressed_shortcuts(inp, DE_SHORTCUTS, format)) {
+                    match action {
+                        DocumentKey::Bold => ed.apply_fmt_toggle_bold(),
+                        DocumentKey::Italic => ed.apply_fmt_toggle_italic(),
+                        DocumentKey::Underline => ed.apply_fmt_toggle_underline(),
+                        _ => ed.apply_style_toggle([ParaStyle::H1, ParaStyle::H2, ParaStyle::H3, ParaStyle::H4, ParaStyle::H5][chord]),
                     }
-                });
-
-                if b { ed.apply_fmt_toggle_bold(); }
-                if it { ed.apply_fmt_toggle_italic(); }
-                if u { ed.apply_fmt_toggle_underline(); }
-                if h > 0 { let style = match h { 1 => ParaStyle::H1, 2 => ParaStyle::H2, 3 => ParaStyle::H3, 4 => ParaStyle::H4, _ => ParaStyle::H5 }; ed.apply_style_toggle(style); }
-                if tab_keys.0 || tab_keys.1 || tab_keys.2 || tab_keys.3 {
-                    let shift_tab = tab_keys.1 || tab_keys.3;
+                }
+                if let Some(indent) = indent {
+                    let shift_tab = indent == DocumentKey::Outdent;
                     let delta = if shift_tab { -36.0f32 } else { 36.0f32 };
                     let max_indent = (ed.layout.content_width() - 36.0).max(0.0);
                     let state = egui::TextEdit::load_state(ctx, id);
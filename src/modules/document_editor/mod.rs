@@ -2,4 +2,4 @@ pub mod de_main;
 mod de_tools;
 mod de_ui;
 
-pub use de_main::DocumentEditor;
+pub use de_main::{DocumentEditor, DocumentMenuAction, key_bindings};
@@ -0,0 +1,227 @@
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+const APP_DIR: &str = "universal_editor";
+pub const PORTABLE_FLAG: &str = "portable.flag";
+pub const PORTABLE_ARG: &str = "--portable";
+
+static WARNINGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+static STORE: OnceLock<ConfigStore> = OnceLock::new();
+static MEMORY: Mutex<Option<HashMap<PathBuf, Vec<u8>>>> = Mutex::new(None);
+
+pub fn take_warnings() -> Vec<String> { std::mem::take(&mut *WARNINGS.lock().unwrap_or_else(|e| e.into_inner())) }
+
+/// Where every piece of config lives. Portable installs keep it in `config/` beside the executable; when that
+/// folder can't be written (read-only media) writes are held in memory for the session instead.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigStore { pub root: PathBuf, pub portable: bool, pub in_memory: bool }
+
+impl ConfigStore {
+    pub fn resolve(exe_dir: Option<&Path>, portable_arg: bool, user_config: Option<PathBuf>) -> Self {
+        let (root, portable) = match exe_dir.filter(|d| portable_arg || d.join(PORTABLE_FLAG).is_file()) {
+            Some(dir) => (dir.join("config"), true),
+            None => (user_config.unwrap_or_else(|| PathBuf::from(".")).join(APP_DIR), false),
+        };
+        let in_memory = !writable(&root);
+        Self { root, portable, in_memory }
+    }
+
+    pub fn path(&self, name: &str) -> PathBuf { self.root.join(name) }
+}
+
+fn writable(dir: &Path) -> bool {
+    if std::fs::create_dir_all(dir).is_err() { return false; }
+    let probe = dir.join(format!(".write_probe_{}", std::process::id()));
+    let ok = std::fs::write(&probe, b"").is_ok();
+    let _ = std::fs::remove_file(&probe);
+    ok
+}
+
+/// Resolves the store once at startup; `portable_arg` is whether `--portable` was passed.
+pub fn init(portable_arg: bool) {
+    let exe_dir = std::env::current_exe().ok().and_then(|p| p.parent().map(Path::to_path_buf));
+    let store = ConfigStore::resolve(exe_dir.as_deref(), portable_arg, user_config());
+    if store.portable { log::info!("portable mode, config in {}", store.root.display()); }
+    if store.in_memory {
+        log::warn!("{} is not writable, keeping config in memory", store.root.display());
+        WARNINGS.lock().unwrap_or_else(|e| e.into_inner()).push(format!("{} can't be written to, so changes to settings will only last until you close the editor", store.root.display()));
+    }
+    let _ = STORE.set(store);
+}
+
+pub fn store() -> &'static ConfigStore { STORE.get_or_init(|| ConfigStore::resolve(None, false, user_config())) }
+
+#[cfg(not(test))]
+fn user_config() -> Option<PathBuf> { dirs::config_dir() }
+
+/// Tests write settings, view state and caches like the app does, so they get a scratch root instead of the user's.
+#[cfg(test)]
+fn user_config() -> Option<PathBuf> { Some(std::env::temp_dir().join(format!("ue_test_config_{}", std::process::id()))) }
+
+pub fn config_dir() -> PathBuf { store().root.clone() }
+
+pub fn config_path(name: &str) -> PathBuf { store().path(name) }
+
+fn held_in_memory(path: &Path) -> bool { let s = store(); s.in_memory && path.starts_with(&s.root) }
+
+/// Reads a config file, preferring what this session wrote if the store is in memory.
+pub fn read(path: &Path) -> io::Result<Vec<u8>> {
+    if let Some(bytes) = MEMORY.lock().unwrap_or_else(|e| e.into_inner()).as_ref().and_then(|m| m.get(path)) { return Ok(bytes.clone()); }
+    std::fs::read(path)
+}
+
+pub fn remove(path: &Path) -> io::Result<()> {
+    if held_in_memory(path) {
+        MEMORY.lock().unwrap_or_else(|e| e.into_inner()).get_or_insert_default().remove(path);
+        return Ok(());
+    }
+    std::fs::remove_file(path)
+}
+
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    if held_in_memory(path) {
+        MEMORY.lock().unwrap_or_else(|e| e.into_inner()).get_or_insert_default().insert(path.to_path_buf(), contents.to_vec());
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() { std::fs::create_dir_all(parent)?; }
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    let written = std::fs::File::create(&tmp).and_then(|mut f| { f.write_all(contents)?; f.sync_all() });
+    written.and_then(|_| std::fs::rename(&tmp, path)).inspect_err(|_| { let _ = std::fs::remove_file(&tmp); })
+}
+
+pub fn save_json<T: Serialize>(path: &Path, val: &T) -> io::Result<()> {
+    let json = serde_json::to_string_pretty(val).map_err(io::Error::other)?;
+    write_atomic(path, json.as_bytes())
+}
+
+pub fn load_json<T: DeserializeOwned + Default>(path: &Path, what: &str) -> T {
+    let text = match read(path) {
+        Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return T::default(),
+        Err(e) => { log::warn!("could not read {}: {}", path.display(), e); return T::default(); }
+    };
+    let err = match serde_json::from_str(&text) { Ok(v) => return v, Err(e) => e };
+    let backup = backup_corrupt(path);
+    let salvaged = salvage::<T>(&text);
+    log::error!("{} is corrupted ({}), {}", path.display(), err, if salvaged.is_some() { "salvaged readable entries" } else { "starting fresh" });
+    let recovered = if salvaged.is_some() { "readable entries were recovered and " } else { "" };
+    let msg = match backup {
+        Ok(b) => format!("{} was corrupted; {}a backup was kept as {}", what, recovered, b.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default()),
+        Err(e) => format!("{} was corrupted; {}the damaged file could not be backed up ({})", what, recovered, e),
+    };
+    WARNINGS.lock().unwrap_or_else(|e| e.into_inner()).push(msg);
+    salvaged.unwrap_or_default()
+}
+
+fn backup_corrupt(path: &Path) -> io::Result<PathBuf> {
+    let stem = format!("{}.corrupt-{}", path.file_name().unwrap_or_default().to_string_lossy(), chrono::Local::now().format("%Y%m%d-%H%M%S"));
+    let mut backup = path.with_file_name(&stem);
+    let mut n = 2;
+    while backup.exists() { backup = path.with_file_name(format!("{}-{}", stem, n)); n += 1; }
+    std::fs::rename(path, &backup)?;
+    Ok(backup)
+}
+
+fn salvage<T: DeserializeOwned>(text: &str) -> Option<T> {
+    let mut stack: Vec<u8> = Vec::new();
+    let mut cuts: Vec<(usize, String)> = Vec::new();
+    let (mut in_str, mut escaped) = (false, false);
+    let closers = |stack: &[u8]| stack.iter().rev().map(|&c| if c == b'[' { ']' } else { '}' }).collect::<String>();
+    for (i, &b) in text.as_bytes().iter().enumerate() {
+        if in_str {
+            match b { _ if escaped => escaped = false, b'\\' => escaped = true, b'"' => in_str = false, _ => {} }
+            continue;
+        }
+        match b {
+            b'"' => in_str = true,
+            b'{' | b'[' => stack.push(b),
+            b'}' | b']' => {
+                stack.pop();
+                if stack.last() == Some(&b'[') { cuts.push((i + 1, closers(&stack))); }
+            }
+            b',' if stack.last() == Some(&b'[') => cuts.push((i, closers(&stack))),
+            _ => {}
+        }
+    }
+    cuts.iter().rev().find_map(|(end, close)| serde_json::from_str(&format!("{}{}", &text[..*end], close)).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(serde::Deserialize, Default, Debug, PartialEq)]
+    struct Colors { colors: Vec<[u8; 3]> }
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ue_config_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn portable_flag_or_argument_moves_config_beside_the_executable() {
+        let exe = scratch("portable_exe");
+        let user = scratch("portable_user");
+        assert_eq!(ConfigStore::resolve(Some(&exe), false, Some(user.clone())), ConfigStore { root: user.join(APP_DIR), portable: false, in_memory: false });
+        assert_eq!(ConfigStore::resolve(Some(&exe), true, Some(user.clone())), ConfigStore { root: exe.join("config"), portable: true, in_memory: false });
+        assert_eq!(ConfigStore::resolve(None, true, Some(user.clone())).root, user.join(APP_DIR));
+        std::fs::remove_dir_all(exe.join("config")).unwrap();
+        std::fs::write(exe.join(PORTABLE_FLAG), "").unwrap();
+        assert_eq!(ConfigStore::resolve(Some(&exe), false, Some(user.clone())).root, exe.join("config"));
+        std::fs::remove_dir_all(exe.join("config")).unwrap();
+        std::fs::write(exe.join("config"), "not a folder").unwrap();
+        let stuck = ConfigStore::resolve(Some(&exe), false, Some(user.clone()));
+        assert!(stuck.portable && stuck.in_memory, "an unwritable portable folder falls back to memory rather than the user profile");
+        let _ = std::fs::remove_dir_all(&exe);
+        let _ = std::fs::remove_dir_all(&user);
+    }
+
+    fn backups(dir: &Path) -> usize {
+        std::fs::read_dir(dir).unwrap().filter(|e| e.as_ref().unwrap().file_name().to_string_lossy().contains(".corrupt-")).count()
+    }
+
+    #[test]
+    fn truncated_file_keeps_complete_entries_and_a_backup() {
+        let dir = scratch("truncated");
+        let path = dir.join("colors.json");
+        std::fs::write(&path, r#"{"colors":[[1,2,3],[4,5,6],[7,8"#).unwrap();
+        let loaded: Colors = load_json(&path, "Colors");
+        assert_eq!(loaded.colors, vec![[1, 2, 3], [4, 5, 6]]);
+        assert!(!path.exists());
+        assert_eq!(backups(&dir), 1);
+        assert!(take_warnings().iter().any(|w| w.starts_with("Colors was corrupted")));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn broken_syntax_salvages_entries_before_the_damage() {
+        let dir = scratch("broken");
+        let path = dir.join("colors.json");
+        std::fs::write(&path, r#"{"colors":[[1,2,3],[4,5,6],[7,8,oops],[9,9,9]]}"#).unwrap();
+        let loaded: Colors = load_json(&path, "Colors");
+        assert_eq!(loaded.colors, vec![[1, 2, 3], [4, 5, 6]]);
+        assert_eq!(backups(&dir), 1);
+        std::fs::write(&path, "\u{0}\u{0}garbage").unwrap();
+        assert_eq!(load_json::<Colors>(&path, "Colors"), Colors::default());
+        assert_eq!(backups(&dir), 2);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn atomic_write_replaces_without_leaving_temp_files() {
+        let dir = scratch("atomic");
+        let path = dir.join("settings.json");
+        write_atomic(&path, b"{\"a\":1}").unwrap();
+        write_atomic(&path, b"{\"a\":2}").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "{\"a\":2}");
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,228 @@
+use eframe::egui;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use crate::i18n::{tr, tr_args};
+use crate::style::ColorPalette;
+use super::config_file;
+
+const OPEN_FILES_DIR: &str = "open_files";
+const OPEN_FILES_HEARTBEAT: Duration = Duration::from_secs(30);
+const OPEN_FILES_STALE: Duration = Duration::from_secs(90);
+
+pub enum FileInfoAction { Rename, SaveAs, Reveal }
+
+pub struct FileDetails { pub size: u64, pub modified: Option<chrono::DateTime<chrono::Local>>, pub mode: Option<u32> }
+
+impl FileDetails {
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let meta = std::fs::metadata(path)?;
+        #[cfg(unix)]
+        let mode = { use std::os::unix::fs::PermissionsExt; Some(meta.permissions().mode() & 0o7777) };
+        #[cfg(not(unix))]
+        let mode = None;
+        Ok(Self { size: meta.len(), modified: meta.modified().ok().map(chrono::DateTime::from), mode })
+    }
+}
+
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 { return format!("{} bytes", bytes); }
+    let mut v = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while v >= 1024.0 && unit + 1 < UNITS.len() { v /= 1024.0; unit += 1; }
+    format!("{:.1} {} ({} bytes)", v, UNITS[unit], bytes)
+}
+
+pub fn format_mode(mode: u32) -> String {
+    let bits: String = (0..9).rev().map(|i| if mode & (1 << i) == 0 { '-' } else { ['x', 'w', 'r'][i % 3] }).collect();
+    format!("{} ({:03o})", bits, mode & 0o777)
+}
+
+pub fn rename_file(from: &Path, to: &Path) -> io::Result<()> {
+    if from == to { return Ok(()); }
+    if to.exists() { return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} already exists", to.display()))); }
+    match std::fs::rename(from, to) {
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+            std::fs::copy(from, to)?;
+            std::fs::remove_file(from).inspect_err(|_| { let _ = std::fs::remove_file(to); })
+        }
+        r => r,
+    }
+}
+
+pub fn reveal_in_file_manager(path: &Path) -> io::Result<()> {
+    #[cfg(target_os = "windows")]
+    std::process::Command::new("explorer").arg(format!("/select,{}", path.display())).spawn()?;
+    #[cfg(target_os = "macos")]
+    std::process::Command::new("open").arg("-R").arg(path).spawn()?;
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    std::process::Command::new("xdg-open").arg(path.parent().unwrap_or(path)).spawn()?;
+    Ok(())
+}
+
+pub fn same_file(a: &Path, b: &Path) -> bool {
+    a == b || std::fs::canonicalize(a).ok().zip(std::fs::canonicalize(b).ok()).is_some_and(|(a, b)| a == b)
+}
+
+pub fn needs_overwrite_confirm(target: &Path, current: Option<&Path>) -> bool {
+    target.exists() && current.is_none_or(|c| !same_file(c, target))
+}
+
+/// With `keep_backup` the file about to be replaced goes to the system trash first; otherwise it is simply overwritten.
+pub fn release_overwrite_target(path: &Path, keep_backup: bool) -> io::Result<()> {
+    if keep_backup && path.exists() { trash::delete(path).map_err(io::Error::other) } else { Ok(()) }
+}
+
+/// The file each running window has open, one list per process in a folder they all share, so Save As in one
+/// window won't replace a file another is editing. Lists are rewritten at least every heartbeat; an older one was
+/// left by a window that crashed and is ignored.
+pub struct OpenFiles { dir: PathBuf, own: PathBuf, listed: Option<PathBuf>, written: Option<Instant> }
+
+impl OpenFiles {
+    pub fn new() -> Self { Self::in_dir(config_file::config_path(OPEN_FILES_DIR), std::process::id()) }
+
+    fn in_dir(dir: PathBuf, pid: u32) -> Self {
+        let own = dir.join(format!("{}.json", pid));
+        Self { dir, own, listed: None, written: None }
+    }
+
+    pub fn update(&mut self, open: Option<&Path>) {
+        if open == self.listed.as_deref() && self.written.is_some_and(|t| t.elapsed() < OPEN_FILES_HEARTBEAT) { return; }
+        self.listed = open.map(Path::to_path_buf);
+        self.written = Some(Instant::now());
+        let Some(path) = open else { let _ = std::fs::remove_file(&self.own); return };
+        let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let written = std::fs::create_dir_all(&self.dir).and_then(|_| std::fs::write(&self.own, serde_json::to_vec(&[path]).unwrap_or_default()));
+        if let Err(e) = written { log::warn!("could not record open file in {}: {}", self.dir.display(), e); }
+    }
+}
+
+impl Drop for OpenFiles {
+    fn drop(&mut self) { let _ = std::fs::remove_file(&self.own); }
+}
+
+/// Whether another running window has `path` open.
+pub fn open_in_another_window(path: &Path) -> bool {
+    open_elsewhere(&config_file::config_path(OPEN_FILES_DIR), std::process::id(), path)
+}
+
+fn open_elsewhere(dir: &Path, pid: u32, path: &Path) -> bool {
+    let Ok(entries) = std::fs::read_dir(dir) else { return false };
+    let own = format!("{}.json", pid);
+    entries.flatten().filter(|e| e.file_name().to_str() != Some(own.as_str()))
+        .filter(|e| e.metadata().and_then(|m| m.modified()).ok().and_then(|t| t.elapsed().ok()).is_some_and(|age| age < OPEN_FILES_STALE))
+        .filter_map(|e| serde_json::from_slice::<Vec<PathBuf>>(&std::fs::read(e.path()).ok()?).ok())
+        .any(|open| open.iter().any(|p| same_file(p, path)))
+}
+
+pub fn overwrite_confirm_window(ctx: &egui::Context, path: &Path, keep_backup: bool) -> Option<bool> {
+    let dark = ctx.style().visuals.dark_mode;
+    let (bg, text_col) = if dark { (ColorPalette::ZINC_800, ColorPalette::ZINC_100) } else { (ColorPalette::GRAY_50, ColorPalette::GRAY_900) };
+    let name = path.file_name().map_or_else(|| path.display().to_string(), |n| n.to_string_lossy().into_owned());
+    let mut choice = None;
+    egui::Window::new(tr("Replace File?"))
+        .collapsible(false).resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+        .frame(egui::Frame::new().fill(bg).stroke(egui::Stroke::new(1.5, ColorPalette::AMBER_500)).corner_radius(8.0).inner_margin(16.0))
+        .show(ctx, |ui: &mut egui::Ui| {
+            ui.label(egui::RichText::new(tr_args("{name} already exists.", &[("name", &name)])).size(13.0).strong().color(text_col));
+            let fate = if keep_backup { "The existing file will be moved to the trash." } else { "The existing file will be replaced and cannot be recovered." };
+            ui.label(egui::RichText::new(tr(fate)).size(12.5).color(text_col));
+            ui.add_space(8.0);
+            ui.horizontal(|ui: &mut egui::Ui| {
+                if ui.button(tr("Replace")).clicked() { choice = Some(true); }
+                if ui.button(tr("Cancel")).clicked() || ui.input(|i| i.key_pressed(egui::Key::Escape)) { choice = Some(false); }
+            });
+        });
+    choice
+}
+
+pub fn file_info_popover(ui: &mut egui::Ui, path: Option<&Path>, dirty: bool) -> Option<FileInfoAction> {
+    let weak = if ui.visuals().dark_mode { ColorPalette::ZINC_400 } else { ColorPalette::ZINC_600 };
+    let mut action = None;
+    ui.set_min_width(280.0);
+    let Some(path) = path else {
+        ui.label(egui::RichText::new(tr("Not saved yet")).strong());
+        ui.label(egui::RichText::new(tr("This document has no file on disk.")).size(12.0).color(weak));
+        ui.add_space(4.0);
+        if ui.button(tr("Save As...")).clicked() { action = Some(FileInfoAction::SaveAs); }
+        return action;
+    };
+    let abs = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    ui.horizontal(|ui| {
+        ui.add(egui::Label::new(egui::RichText::new(abs.display().to_string()).monospace().size(11.5)).wrap());
+        if ui.small_button(tr("Copy")).on_hover_text(tr("Copy the full path")).clicked() { ui.ctx().copy_text(abs.display().to_string()); }
+    });
+    ui.separator();
+    match FileDetails::read(path) {
+        Ok(d) => {
+            egui::Grid::new("file_info_grid").num_columns(2).spacing([12.0, 4.0]).show(ui, |ui| {
+                ui.label(egui::RichText::new(tr("Size")).color(weak)); ui.label(format_size(d.size)); ui.end_row();
+                ui.label(egui::RichText::new(tr("Modified")).color(weak));
+                ui.label(d.modified.map_or(tr("Unknown"), |t| t.format("%Y-%m-%d %H:%M:%S").to_string())); ui.end_row();
+                if let Some(mode) = d.mode { ui.label(egui::RichText::new(tr("Permissions")).color(weak)); ui.label(egui::RichText::new(format_mode(mode)).monospace()); ui.end_row(); }
+            });
+            if dirty { ui.label(egui::RichText::new(tr("Unsaved changes are not reflected above.")).size(11.5).color(weak)); }
+        }
+        Err(e) => { ui.label(egui::RichText::new(tr_args("Could not read file details: {error}", &[("error", &e.to_string())])).color(ColorPalette::RED_400)); }
+    }
+    ui.add_space(4.0);
+    ui.horizontal(|ui| {
+        let rename = if dirty { "Save && Rename..." } else { "Rename..." };
+        if ui.button(tr(rename)).on_hover_text(tr(if dirty { "Saves your changes first, then renames the file" } else { "Rename the file in place" })).clicked() { action = Some(FileInfoAction::Rename); }
+        if ui.button(tr("Reveal in File Manager")).clicked() { action = Some(FileInfoAction::Reveal); }
+    });
+    action
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes_and_modes_are_human_readable() {
+        assert_eq!(format_size(512), "512 bytes");
+        assert_eq!(format_size(1536), "1.5 KB (1536 bytes)");
+        assert_eq!(format_mode(0o644), "rw-r--r-- (644)");
+        assert_eq!(format_mode(0o4755), "rwxr-xr-x (755)");
+    }
+
+    #[test]
+    fn rename_refuses_to_overwrite() {
+        let dir = std::env::temp_dir().join(format!("ue_rename_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (a, b, c) = (dir.join("a.txt"), dir.join("b.txt"), dir.join("c.txt"));
+        std::fs::write(&a, "a").unwrap();
+        std::fs::write(&b, "b").unwrap();
+        assert_eq!(rename_file(&a, &b).unwrap_err().kind(), io::ErrorKind::AlreadyExists);
+        rename_file(&a, &c).unwrap();
+        assert!(!a.exists() && std::fs::read_to_string(&c).unwrap() == "a");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn files_open_in_a_live_window_are_guarded_until_it_closes_or_goes_stale() {
+        let dir = std::env::temp_dir().join(format!("ue_open_files_{}", std::process::id()));
+        let file = dir.join("notes.txt");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&file, "x").unwrap();
+        let registry = dir.join("open");
+        let mut other = OpenFiles::in_dir(registry.clone(), 1);
+        other.update(Some(&file));
+        assert!(open_elsewhere(&registry, 2, &dir.join(".").join("notes.txt")));
+        assert!(!open_elsewhere(&registry, 1, &file));
+        assert!(needs_overwrite_confirm(&file, None) && !needs_overwrite_confirm(&file, Some(&file)));
+
+        let old = std::time::SystemTime::now() - OPEN_FILES_STALE * 2;
+        std::fs::File::options().write(true).open(registry.join("1.json")).unwrap().set_modified(old).unwrap();
+        assert!(!open_elsewhere(&registry, 2, &file));
+        other.update(None);
+        other.update(Some(&file));
+        drop(other);
+        assert!(!open_elsewhere(&registry, 2, &file));
+        release_overwrite_target(&file, false).unwrap();
+        assert!(file.exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,87 @@
+use eframe::egui;
+
+pub fn rgb_to_hsv_f32(r: f32, g: f32, b: f32) -> (f32, f32, f32) {
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+    let s = if max == 0.0 { 0.0 } else { delta / max };
+    let h = if delta == 0.0 { 0.0 }
+        else if max == r { 60.0 * (((g - b) / delta) % 6.0) }
+        else if max == g { 60.0 * ((b - r) / delta + 2.0) }
+        else { 60.0 * ((r - g) / delta + 4.0) };
+    (if h < 0.0 { h + 360.0 } else { h }, s, max)
+}
+
+pub fn hsv_to_rgb_f32(h: f32, s: f32, v: f32) -> (f32, f32, f32) {
+    let c = v * s;
+    let x = c * (1.0 - ((h / 60.0) % 2.0 - 1.0).abs());
+    let m = v - c;
+    let (r, g, b) = match h as u32 {
+        0..=59 => (c, x, 0.0),
+        60..=119 => (x, c, 0.0),
+        120..=179 => (0.0, c, x),
+        180..=239 => (0.0, x, c),
+        240..=299 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    (r + m, g + m, b + m)
+}
+
+pub fn hsv_color(h: f32, s: f32, v: f32) -> egui::Color32 {
+    let (r, g, b) = hsv_to_rgb_f32(h, s, v);
+    egui::Color32::from_rgb((r * 255.0) as u8, (g * 255.0) as u8, (b * 255.0) as u8)
+}
+
+/// Saturation (x) / value (y) square for hue `h`. Returns true while the user is picking in it.
+pub fn sv_square(ui: &mut egui::Ui, rect: egui::Rect, id: egui::Id, h: f32, s: &mut f32, v: &mut f32) -> bool {
+    let response = ui.interact(rect, id, egui::Sense::click_and_drag());
+    response.widget_info(|| egui::WidgetInfo::labeled(egui::WidgetType::Other, true, "Saturation and brightness"));
+    if ui.is_rect_visible(rect) {
+        let painter = ui.painter_at(rect);
+        let steps = 40i32;
+        let (cw, ch) = (rect.width() / steps as f32, rect.height() / steps as f32);
+        for cy in 0..steps {
+            for cx in 0..steps {
+                let (sc, vc) = (cx as f32 / (steps - 1) as f32, 1.0 - cy as f32 / (steps - 1) as f32);
+                painter.rect_filled(egui::Rect::from_min_size(
+                    egui::pos2(rect.min.x + cx as f32 * cw, rect.min.y + cy as f32 * ch),
+                    egui::vec2(cw.ceil(), ch.ceil()),
+                ), 0.0, hsv_color(h, sc, vc));
+            }
+        }
+        let cur = egui::pos2(rect.min.x + *s * rect.width(), rect.min.y + (1.0 - *v) * rect.height());
+        painter.circle_stroke(cur, 6.0, egui::Stroke::new(2.0, egui::Color32::WHITE));
+        painter.circle_stroke(cur, 6.0, egui::Stroke::new(1.0, egui::Color32::BLACK));
+    }
+    if !(response.dragged() || response.clicked()) { return false; }
+    let Some(pos) = response.interact_pointer_pos() else { return false };
+    *s = ((pos.x - rect.min.x) / rect.width()).clamp(0.0, 1.0);
+    *v = 1.0 - ((pos.y - rect.min.y) / rect.height()).clamp(0.0, 1.0);
+    true
+}
+
+/// Horizontal hue strip, 0..360 left to right. Returns true while the user is dragging it.
+pub fn hue_strip(ui: &mut egui::Ui, rect: egui::Rect, id: egui::Id, h: &mut f32, outline: egui::Color32) -> bool {
+    let response = ui.interact(rect, id, egui::Sense::click_and_drag());
+    response.widget_info(|| egui::WidgetInfo::labeled(egui::WidgetType::Slider, true, "Hue"));
+    if ui.is_rect_visible(rect) {
+        let painter = ui.painter_at(rect);
+        let steps = 60i32;
+        let sw = rect.width() / steps as f32;
+        for i in 0..steps {
+            painter.rect_filled(egui::Rect::from_min_size(
+                egui::pos2(rect.min.x + i as f32 * sw, rect.min.y),
+                egui::vec2(sw.ceil(), rect.height()),
+            ), 0.0, hsv_color((i as f32 / steps as f32) * 360.0, 1.0, 1.0));
+        }
+        painter.rect_stroke(rect, 2.0, egui::Stroke::new(1.0, outline), egui::StrokeKind::Outside);
+        let hx = rect.min.x + (*h / 360.0) * rect.width();
+        let hcr = egui::Rect::from_center_size(egui::pos2(hx, rect.center().y), egui::vec2(4.0, rect.height() + 2.0));
+        painter.rect_filled(hcr, 2.0, egui::Color32::WHITE);
+        painter.rect_stroke(hcr, 2.0, egui::Stroke::new(1.0, egui::Color32::BLACK), egui::StrokeKind::Outside);
+    }
+    if !(response.dragged() || response.clicked()) { return false; }
+    let Some(pos) = response.interact_pointer_pos() else { return false };
+    *h = ((pos.x - rect.min.x) / rect.width()).clamp(0.0, 1.0) * 360.0;
+    true
+}
@@ -1,8 +1,9 @@
 use image::{DynamicImage, ImageEncoder};
 use std::path::Path;
+use crate::error::{EditorError, EditorResult};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
-pub enum ExportFormat { Jpeg, Png, Webp, Bmp, Tiff, Ico, Avif, }
+pub enum ExportFormat { Jpeg, Png, Webp, Bmp, Tiff, Ico, Avif, Svg, }
 
 impl ExportFormat {
     pub fn as_str(&self) -> &str {
@@ -14,6 +15,7 @@ impl ExportFormat {
             ExportFormat::Tiff => "TIFF",
             ExportFormat::Ico => "ICO",
             ExportFormat::Avif => "AVIF",
+            ExportFormat::Svg => "SVG",
         }
     }
 
@@ -26,9 +28,27 @@ impl ExportFormat {
             ExportFormat::Tiff => "tiff",
             ExportFormat::Ico => "ico",
             ExportFormat::Avif => "avif",
+            ExportFormat::Svg => "svg",
         }
     }
 
+    pub fn mime_type(&self) -> &str {
+        match self {
+            ExportFormat::Jpeg => "image/jpeg",
+            ExportFormat::Png => "image/png",
+            ExportFormat::Webp => "image/webp",
+            ExportFormat::Bmp => "image/bmp",
+            ExportFormat::Tiff => "image/tiff",
+            ExportFormat::Ico => "image/x-icon",
+            ExportFormat::Avif => "image/avif",
+            ExportFormat::Svg => "image/svg+xml",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<ExportFormat> {
+        Self::with_svg().into_iter().find(|f| f.as_str().eq_ignore_ascii_case(name.trim()) || f.extension().eq_ignore_ascii_case(name.trim()))
+    }
+
     pub fn all() -> Vec<ExportFormat> {
         vec![
             ExportFormat::Jpeg,
@@ -40,11 +60,26 @@ impl ExportFormat {
             ExportFormat::Avif,
         ]
     }
+
+    /// `all` plus SVG, which only the image editor offers: it needs the layers to keep text as text.
+    pub fn with_svg() -> Vec<ExportFormat> {
+        let mut formats = Self::all();
+        formats.push(ExportFormat::Svg);
+        formats
+    }
 }
 
 pub fn export_image(img: &DynamicImage, path: &Path, format: ExportFormat, jpeg_quality: u8, png_compression: u8,
     _webp_quality: f32, auto_scale_ico: bool, avif_quality: u8, avif_speed: u8,
-) -> Result<(), String> {
+) -> EditorResult {
+    let bytes = encode_image(img, format, jpeg_quality, png_compression, auto_scale_ico, avif_quality, avif_speed)?;
+    std::fs::write(path, bytes).map_err(|e| EditorError::io(path, e))
+}
+
+/// The bytes `export_image` writes, for callers that keep them in memory.
+pub fn encode_image(img: &DynamicImage, format: ExportFormat, jpeg_quality: u8, png_compression: u8,
+    auto_scale_ico: bool, avif_quality: u8, avif_speed: u8,
+) -> EditorResult<Vec<u8>> {
     let mut export_img: DynamicImage = img.clone();
     if format == ExportFormat::Ico && auto_scale_ico {
         if export_img.width() > 256 || export_img.height() > 256 {
@@ -55,52 +90,124 @@ pub fn export_image(img: &DynamicImage, path: &Path, format: ExportFormat, jpeg_
         }
     }
 
+    let mut out: std::io::Cursor<Vec<u8>> = std::io::Cursor::new(Vec::new());
     match format {
         ExportFormat::Jpeg => {
-            let mut encoder: image::codecs::jpeg::JpegEncoder<std::fs::File> = image::codecs::jpeg::JpegEncoder::new_with_quality(
-                std::fs::File::create(path).map_err(|e| format!("Failed to create file: {}", e))?, jpeg_quality,
-            );
-            encoder.encode_image(&export_img) .map_err(|e| format!("Failed to encode JPEG: {}", e))?;
+            let mut encoder: image::codecs::jpeg::JpegEncoder<&mut std::io::Cursor<Vec<u8>>> = image::codecs::jpeg::JpegEncoder::new_with_quality(&mut out, jpeg_quality);
+            encoder.encode_image(&export_img).map_err(|e| EditorError::encode_in_memory("JPEG", e))?;
         }
         ExportFormat::Png => {
-            let file: std::fs::File = std::fs::File::create(path).map_err(|e| format!("Failed to create file: {}", e))?;
             let compression: image::codecs::png::CompressionType = match png_compression {
                 0..=3 => image::codecs::png::CompressionType::Fast,
                 4..=6 => image::codecs::png::CompressionType::Default,
                 _ => image::codecs::png::CompressionType::Best,
             };
-            let encoder: image::codecs::png::PngEncoder<std::fs::File> = image::codecs::png::PngEncoder::new_with_quality(
-                file, compression, image::codecs::png::FilterType::Adaptive,
+            let encoder: image::codecs::png::PngEncoder<&mut std::io::Cursor<Vec<u8>>> = image::codecs::png::PngEncoder::new_with_quality(
+                &mut out, compression, image::codecs::png::FilterType::Adaptive,
             );
             encoder.write_image(
                 export_img.as_bytes(), export_img.width(), export_img.height(), export_img.color().into(),
-            ).map_err(|e: image::ImageError| format!("Failed to encode PNG: {}", e))?;
+            ).map_err(|e| EditorError::encode_in_memory("PNG", e))?;
         }
         ExportFormat::Webp => {
-            export_img.save_with_format(path, image::ImageFormat::WebP).map_err(|e: image::ImageError| format!("Failed to save WebP: {}", e))?;
+            export_img.write_to(&mut out, image::ImageFormat::WebP).map_err(|e| EditorError::encode_in_memory("WebP", e))?;
         }
         ExportFormat::Bmp => {
-            export_img.save_with_format(path, image::ImageFormat::Bmp).map_err(|e: image::ImageError| format!("Failed to save BMP: {}", e))?;
+            export_img.write_to(&mut out, image::ImageFormat::Bmp).map_err(|e| EditorError::encode_in_memory("BMP", e))?;
         }
         ExportFormat::Tiff => {
-            export_img.save_with_format(path, image::ImageFormat::Tiff).map_err(|e: image::ImageError| format!("Failed to save TIFF: {}", e))?;
+            export_img.write_to(&mut out, image::ImageFormat::Tiff).map_err(|e| EditorError::encode_in_memory("TIFF", e))?;
         }
         ExportFormat::Ico => {
             if export_img.width() > 256 || export_img.height() > 256 {
-                return Err(format!(
-                    "ICO format requires dimensions 256px. Image is {}x{}. Enable auto-scaling.",
+                return Err(EditorError::UnsupportedFormat(format!(
+                    "ICO format requires dimensions of at most 256px. Image is {}x{}. Enable auto-scaling.",
                     export_img.width(), export_img.height()
-                ));
+                )));
             }
-            export_img.save_with_format(path, image::ImageFormat::Ico).map_err(|e: image::ImageError| format!("Failed to save ICO: {}", e))?;
+            export_img.write_to(&mut out, image::ImageFormat::Ico).map_err(|e| EditorError::encode_in_memory("ICO", e))?;
         }
         ExportFormat::Avif => {
-            let file = std::fs::File::create(path).map_err(|e| format!("Failed to create file: {}", e))?;
-            let encoder = image::codecs::avif::AvifEncoder::new_with_speed_quality(file, avif_speed, avif_quality);
+            let encoder = image::codecs::avif::AvifEncoder::new_with_speed_quality(&mut out, avif_speed, avif_quality);
             encoder.write_image(
                 export_img.as_bytes(), export_img.width(), export_img.height(), export_img.color().into(),
-            ).map_err(|e| format!("Failed to encode AVIF: {}", e))?;
+            ).map_err(|e| EditorError::encode_in_memory("AVIF", e))?;
         }
+        ExportFormat::Svg => {
+            let doc = SvgDocument { width: export_img.width(), height: export_img.height(), layers: vec![SvgLayer::Image(export_img)] };
+            return Ok(doc.render().map_err(|e| EditorError::encode_in_memory("SVG", e))?.into_bytes());
+        }
+    }
+    Ok(out.into_inner())
+}
+
+pub struct SvgText {
+    pub lines: Vec<String>,
+    pub x: f32, pub y: f32, pub width: f32, pub height: f32,
+    pub line_height: f32, pub ascent: f32, pub font_size: f32,
+    pub font_family: String, pub bold: bool, pub italic: bool, pub underline: bool,
+    pub color: [u8; 4], pub opacity: f32, pub rotation: f32, pub rtl: bool, pub vertical: bool,
+}
+
+/// One piece of an SVG export, listed bottom to top.
+pub enum SvgLayer { Image(DynamicImage), Text(SvgText) }
+
+pub struct SvgDocument { pub width: u32, pub height: u32, pub layers: Vec<SvgLayer> }
+
+pub fn export_svg(doc: &SvgDocument, path: &Path) -> EditorResult {
+    let svg = doc.render().map_err(|e| EditorError::encode("SVG", path, e))?;
+    std::fs::write(path, svg).map_err(|e| EditorError::io(path, e))
+}
+
+impl SvgDocument {
+    /// The layers in order, pixels as embedded PNGs and text as real SVG text.
+    pub fn render(&self) -> image::ImageResult<String> {
+        use base64::Engine;
+        use quick_xml::escape::escape;
+        let (w, h) = (self.width, self.height);
+        let mut svg = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<svg xmlns=\"http://www.w3.org/2000/svg\" xmlns:xlink=\"http://www.w3.org/1999/xlink\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\n",
+        );
+        let mut families: Vec<&str> = self.layers.iter().filter_map(|l| match l { SvgLayer::Text(t) => Some(t.font_family.as_str()), SvgLayer::Image(_) => None }).collect();
+        if !families.is_empty() {
+            families.sort_unstable(); families.dedup();
+            svg.push_str(&format!("<!-- Text is referenced by font name ({}). Viewers without these fonts installed fall back to sans-serif. -->\n", escape(families.join(", ").as_str())));
+        }
+        for layer in &self.layers {
+            match layer {
+                SvgLayer::Image(img) => {
+                    let mut png: Vec<u8> = Vec::new();
+                    img.write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)?;
+                    svg.push_str(&format!(
+                        "<image x=\"0\" y=\"0\" width=\"{}\" height=\"{}\" xlink:href=\"data:image/png;base64,{}\"/>\n",
+                        img.width(), img.height(), base64::engine::general_purpose::STANDARD.encode(&png),
+                    ));
+                }
+                SvgLayer::Text(t) => push_svg_text(&mut svg, t),
+            }
+        }
+        svg.push_str("</svg>\n");
+        Ok(svg)
+    }
+}
+
+fn push_svg_text(svg: &mut String, t: &SvgText) {
+    use quick_xml::escape::escape;
+    let (cx, cy) = (t.x + t.width / 2.0, t.y + t.height / 2.0);
+    let transform = if t.rotation != 0.0 { format!(" transform=\"rotate({:.3} {:.3} {:.3})\"", t.rotation, cx, cy) } else { String::new() };
+    svg.push_str(&format!(
+        "<text xml:space=\"preserve\" font-family=\"{}, sans-serif\" font-size=\"{:.3}\" font-weight=\"{}\" font-style=\"{}\"{} fill=\"#{:02x}{:02x}{:02x}\" fill-opacity=\"{:.3}\"{}{}>",
+        escape(t.font_family.as_str()), t.font_size,
+        if t.bold { "bold" } else { "normal" }, if t.italic { "italic" } else { "normal" },
+        if t.underline { " text-decoration=\"underline\"" } else { "" },
+        t.color[0], t.color[1], t.color[2], t.color[3] as f32 / 255.0 * t.opacity, transform,
+        if t.vertical { " writing-mode=\"vertical-rl\" text-orientation=\"upright\"" } else if t.rtl { " direction=\"rtl\" text-anchor=\"end\"" } else { "" },
+    ));
+    for (i, line) in t.lines.iter().enumerate() {
+        let (x, y) = if t.vertical { (t.x + t.width - (i as f32 + 0.5) * t.line_height, t.y) }
+            else if t.rtl { (t.x + t.width, t.y + i as f32 * t.line_height + t.ascent) }
+            else { (t.x, t.y + i as f32 * t.line_height + t.ascent) };
+        svg.push_str(&format!("<tspan x=\"{:.3}\" y=\"{:.3}\">{}</tspan>", x, y, escape(line.as_str())));
     }
-    Ok(())
+    svg.push_str("</text>\n");
 }
@@ -1 +1,7 @@
+pub mod config_file;
+pub mod file_info;
+pub mod hsv_picker;
 pub mod image_export;
+pub mod share;
+pub mod text_bounds;
+pub mod undo;
@@ -0,0 +1,157 @@
+use image::DynamicImage;
+use std::path::{Path, PathBuf};
+use crate::error::{EditorError, EditorResult};
+use super::file_info::reveal_in_file_manager;
+
+pub enum Shared { Native, Email, FileManager }
+
+pub fn unsupported_reason() -> Option<&'static str> {
+    if cfg!(any(target_os = "windows", target_os = "macos", target_os = "linux", target_os = "freebsd", target_os = "openbsd", target_os = "netbsd")) { None }
+    else { Some("Sharing is not available on this system") }
+}
+
+pub fn share_dir() -> PathBuf { std::env::temp_dir().join("universal_editor_share") }
+
+pub fn stage(img: &DynamicImage, name: &str, dir: &Path) -> EditorResult<PathBuf> {
+    let path = dir.join(format!("{}.png", name));
+    std::fs::create_dir_all(dir).map_err(|source| EditorError::Io { path: dir.to_path_buf(), source })?;
+    img.save_with_format(&path, image::ImageFormat::Png).map_err(|source| EditorError::ImageEncode { format: "PNG".into(), source })?;
+    Ok(path)
+}
+
+/// Opens the system share sheet for a staged file: DataTransferManager on Windows, NSSharingServicePicker on macOS.
+/// Where there is none, or it fails to open, the file goes to an email draft (`xdg-email`) or is revealed in the file manager.
+pub fn share_file(frame: &eframe::Frame, path: &Path) -> EditorResult<Shared> {
+    if platform::share(frame, path) { return Ok(Shared::Native); }
+    fallback(path, email_draft, reveal_in_file_manager)
+}
+
+fn email_draft(path: &Path) -> bool {
+    cfg!(not(any(target_os = "windows", target_os = "macos"))) && std::process::Command::new("xdg-email").arg("--attach").arg(path).spawn().is_ok()
+}
+
+fn fallback(path: &Path, email: impl FnOnce(&Path) -> bool, reveal: impl FnOnce(&Path) -> std::io::Result<()>) -> EditorResult<Shared> {
+    if email(path) { return Ok(Shared::Email); }
+    reveal(path).map_err(|e| EditorError::Other(format!("Could not open a share target: {}", e)))?;
+    Ok(Shared::FileManager)
+}
+
+/// What to tell the user once a file was handed off; the share sheet speaks for itself.
+pub fn notice(how: &Shared, path: &Path) -> Option<String> {
+    let file = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    match how {
+        Shared::Native => None,
+        Shared::Email => Some(format!("Opened a new email with {} attached", file)),
+        Shared::FileManager => Some(format!("{} is ready to share from the file manager", file)),
+    }
+}
+
+pub fn cleanup() { let _ = std::fs::remove_dir_all(share_dir()); }
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use std::cell::RefCell;
+    use std::path::Path;
+    use raw_window_handle::{HasWindowHandle, RawWindowHandle};
+    use windows::ApplicationModel::DataTransfer::{DataRequestedEventArgs, DataTransferManager};
+    use windows::Foundation::TypedEventHandler;
+    use windows::Storage::{IStorageItem, StorageFile};
+    use windows::Win32::Foundation::HWND;
+    use windows::Win32::UI::Shell::IDataTransferManagerInterop;
+    use windows::core::{factory, AgileReference, Interface, Ref, HSTRING};
+    use windows_collections::IIterable;
+
+    thread_local! {
+        // The handler feeding the open share sheet; replaced, not stacked, when the user shares again.
+        static REQUEST: RefCell<Option<(DataTransferManager, i64)>> = const { RefCell::new(None) };
+    }
+
+    pub fn share(frame: &eframe::Frame, path: &Path) -> bool { show(frame, path).is_ok() }
+
+    fn show(frame: &eframe::Frame, path: &Path) -> windows::core::Result<()> {
+        let hwnd = match frame.window_handle().map(|h| h.as_raw()) {
+            Ok(RawWindowHandle::Win32(h)) => HWND(h.hwnd.get() as *mut _),
+            _ => return Err(windows::core::Error::empty()),
+        };
+        let file = AgileReference::new(&StorageFile::GetFileFromPathAsync(&HSTRING::from(path.as_os_str()))?.join()?)?;
+        let title = HSTRING::from(path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default());
+        let interop = factory::<DataTransferManager, IDataTransferManagerInterop>()?;
+        // SAFETY: `hwnd` is the live main window for the duration of this frame.
+        let manager: DataTransferManager = unsafe { interop.GetForWindow(hwnd)? };
+        let token = manager.DataRequested(&TypedEventHandler::new(move |_, args: Ref<DataRequestedEventArgs>| {
+            let data = args.ok()?.Request()?.Data()?;
+            data.Properties()?.SetTitle(&title)?;
+            data.SetStorageItemsReadOnly(&IIterable::from(vec![Some(file.resolve()?.cast::<IStorageItem>()?)]))
+        }))?;
+        REQUEST.with(|r| if let Some((old, old_token)) = r.replace(Some((manager, token))) { let _ = old.RemoveDataRequested(old_token); });
+        // SAFETY: as above.
+        unsafe { interop.ShowShareUIForWindow(hwnd) }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::cell::RefCell;
+    use std::path::Path;
+    use objc2::{rc::Retained, runtime::AnyObject, MainThreadMarker};
+    use objc2_app_kit::{NSSharingServicePicker, NSView};
+    use objc2_foundation::{NSArray, NSPoint, NSRect, NSRectEdge, NSSize, NSString, NSURL};
+    use raw_window_handle::{HasWindowHandle, RawWindowHandle};
+
+    thread_local! {
+        // Keeps the picker alive while its menu is open.
+        static PICKER: RefCell<Option<Retained<NSSharingServicePicker>>> = const { RefCell::new(None) };
+    }
+
+    pub fn share(frame: &eframe::Frame, path: &Path) -> bool {
+        let Some(mtm) = MainThreadMarker::new() else { return false };
+        let Ok(RawWindowHandle::AppKit(handle)) = frame.window_handle().map(|h| h.as_raw()) else { return false };
+        // SAFETY: winit's content view for the main window, alive for the duration of this frame.
+        let view: &NSView = unsafe { handle.ns_view.cast().as_ref() };
+        let url = NSURL::fileURLWithPath(&NSString::from_str(&path.to_string_lossy()));
+        let items: Retained<NSArray<AnyObject>> = NSArray::from_retained_slice(&[Retained::into_super(Retained::into_super(url))]);
+        // SAFETY: the items are file URLs, which the picker accepts.
+        let picker = unsafe { NSSharingServicePicker::initWithItems(mtm.alloc(), &items) };
+        let b = view.bounds();
+        let anchor = NSRect::new(NSPoint::new(b.origin.x + b.size.width / 2.0, b.origin.y + b.size.height - 1.0), NSSize::new(1.0, 1.0));
+        picker.showRelativeToRect_ofView_preferredEdge(anchor, view, NSRectEdge::MinY);
+        PICKER.with(|p| *p.borrow_mut() = Some(picker));
+        true
+    }
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+mod platform {
+    pub fn share(_frame: &eframe::Frame, _path: &std::path::Path) -> bool { false }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn staging_writes_a_png_and_replaces_an_earlier_copy() {
+        let dir = std::env::temp_dir().join(format!("ue_share_{}", std::process::id()));
+        let first = stage(&DynamicImage::new_rgba8(3, 2), "Poster", &dir).unwrap();
+        let again = stage(&DynamicImage::new_rgba8(5, 4), "Poster", &dir).unwrap();
+        assert_eq!(first, again);
+        assert_eq!(first.file_name().unwrap(), "Poster.png");
+        assert_eq!(image::image_dimensions(&again).unwrap(), (5, 4));
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn without_a_share_sheet_the_file_goes_to_email_or_the_file_manager() {
+        let path = Path::new("/tmp/share/Poster.png");
+        let revealed = std::cell::Cell::new(None);
+        let reveal = |p: &Path| { revealed.set(Some(p.to_path_buf())); Ok(()) };
+        assert!(matches!(fallback(path, |_| true, |_| panic!("email worked, nothing to reveal")), Ok(Shared::Email)));
+        assert!(matches!(fallback(path, |_| false, reveal), Ok(Shared::FileManager)));
+        assert_eq!(revealed.take().as_deref(), Some(path));
+        let failed = fallback(path, |_| false, |_| Err(std::io::Error::new(std::io::ErrorKind::NotFound, "no file manager"))).err().unwrap();
+        assert_eq!(failed.to_string(), "Could not open a share target: no file manager");
+        assert_eq!(notice(&Shared::FileManager, path).as_deref(), Some("Poster.png is ready to share from the file manager"));
+        assert_eq!(notice(&Shared::Native, path), None);
+    }
+}
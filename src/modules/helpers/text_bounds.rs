@@ -0,0 +1,19 @@
+//! Grapheme and word boundaries for caret motion and deletion, shared by the text editor and image text layers.
+
+use unicode_segmentation::UnicodeSegmentation;
+
+pub fn prev_grapheme_boundary(s: &str, i: usize) -> usize {
+    s[..i].grapheme_indices(true).next_back().map(|(j, _)| j).unwrap_or(0)
+}
+
+pub fn next_grapheme_boundary(s: &str, i: usize) -> usize {
+    s[i..].grapheme_indices(true).nth(1).map(|(j, _)| i + j).unwrap_or(s.len())
+}
+
+pub fn prev_word_boundary(s: &str, i: usize) -> usize {
+    s[..i].split_word_bound_indices().rev().find(|(_, w)| !w.trim().is_empty()).map(|(j, _)| j).unwrap_or(0)
+}
+
+pub fn next_word_boundary(s: &str, i: usize) -> usize {
+    s[i..].split_word_bound_indices().find(|(_, w)| !w.trim().is_empty()).map(|(j, w)| i + j + w.len()).unwrap_or(s.len())
+}
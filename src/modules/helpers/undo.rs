@@ -0,0 +1,212 @@
+//! Undo/redo history shared by the editors. An entry is whatever a module needs to get back to an earlier
+//! state (a full snapshot, a diff, ...); the history takes care of depth, coalescing, memory and the save point.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+pub trait UndoableEdit {
+    /// Approximate memory held by the entry, counted against the byte budget.
+    fn size_bytes(&self) -> usize { 0 }
+
+    /// Whether this entry may be folded into `older`, the entry on top of the stack, so both undo as one step.
+    /// Only asked while the coalescing window of the previous push is still open.
+    fn coalesces_with(&self, _older: &Self) -> bool { true }
+}
+
+struct Entry<T> { edit: T, label: String, state: u64 }
+
+/// Every document state gets an id; an entry remembers the id of the state it restores, which is how the
+/// history knows when undo or redo lands back on the last saved state.
+pub struct UndoHistory<T> {
+    undo: VecDeque<Entry<T>>,
+    redo: VecDeque<Entry<T>>,
+    depth: usize,
+    coalesce_window: Option<Duration>,
+    last_push: Option<Instant>,
+    byte_budget: Option<usize>,
+    on_trim: Option<Box<dyn FnMut(usize)>>,
+    state: u64,
+    next_state: u64,
+    saved: Option<u64>,
+}
+
+impl<T: UndoableEdit> UndoHistory<T> {
+    /// Keeps at most `depth` undo steps. The initial state counts as saved.
+    pub fn new(depth: usize) -> Self {
+        Self {
+            undo: VecDeque::new(), redo: VecDeque::new(), depth, coalesce_window: None, last_push: None,
+            byte_budget: None, on_trim: None, state: 0, next_state: 1, saved: Some(0),
+        }
+    }
+
+    /// Pushes arriving within `window` of the previous one merge into it when the entry agrees.
+    pub fn with_coalesce_window(mut self, window: Duration) -> Self { self.coalesce_window = Some(window); self }
+
+    /// Drops the oldest undo steps once all entries together hold more than `bytes`; `on_trim` gets the bytes freed.
+    pub fn with_byte_budget(mut self, bytes: usize, on_trim: impl FnMut(usize) + 'static) -> Self {
+        self.byte_budget = Some(bytes);
+        self.on_trim = Some(Box::new(on_trim));
+        self
+    }
+
+    /// Records `edit`, the state from before a change, and clears redo. Returns false when it was coalesced.
+    pub fn push(&mut self, edit: T, label: impl Into<String>) -> bool { self.push_at(edit, label, Instant::now()) }
+
+    pub fn push_at(&mut self, edit: T, label: impl Into<String>, now: Instant) -> bool {
+        self.redo.clear();
+        let open = self.coalesce_window.zip(self.last_push).is_some_and(|(w, t)| now.saturating_duration_since(t) <= w);
+        let coalesce = open && self.undo.back().is_some_and(|top| edit.coalesces_with(&top.edit));
+        self.last_push = Some(now);
+        let before = std::mem::replace(&mut self.state, self.next_state);
+        self.next_state += 1;
+        if coalesce { return false; }
+        self.undo.push_back(Entry { edit, label: label.into(), state: before });
+        while self.undo.len() > self.depth { self.undo.pop_front(); }
+        self.enforce_budget();
+        true
+    }
+
+    fn enforce_budget(&mut self) {
+        let Some(budget) = self.byte_budget else { return };
+        let mut total = self.bytes();
+        let mut freed = 0;
+        while total > budget && let Some(oldest) = self.undo.pop_front() {
+            let size = oldest.edit.size_bytes();
+            total -= size; freed += size;
+        }
+        if freed > 0 && let Some(on_trim) = &mut self.on_trim { on_trim(freed); }
+    }
+
+    /// Steps back: `current` is kept for redo and the entry to restore is returned.
+    pub fn undo(&mut self, current: T) -> Option<T> {
+        let entry = self.undo.pop_back()?;
+        self.redo.push_back(Entry { edit: current, label: entry.label.clone(), state: self.state });
+        self.state = entry.state;
+        self.last_push = None;
+        Some(entry.edit)
+    }
+
+    pub fn redo(&mut self, current: T) -> Option<T> {
+        let entry = self.redo.pop_back()?;
+        self.undo.push_back(Entry { edit: current, label: entry.label.clone(), state: self.state });
+        self.state = entry.state;
+        self.last_push = None;
+        Some(entry.edit)
+    }
+
+    /// Takes back the latest push for an operation that was cancelled or turned out to change nothing.
+    pub fn discard_last(&mut self) -> Option<T> {
+        let entry = self.undo.pop_back()?;
+        self.state = entry.state;
+        self.last_push = None;
+        Some(entry.edit)
+    }
+
+    pub fn discard_redo(&mut self) -> Option<T> { self.redo.pop_back().map(|e| e.edit) }
+
+    pub fn last(&self) -> Option<&T> { self.undo.back().map(|e| &e.edit) }
+
+    /// Undo entries from newest to oldest.
+    pub fn undo_entries(&self) -> impl Iterator<Item = &T> { self.undo.iter().rev().map(|e| &e.edit) }
+
+    pub fn can_undo(&self) -> bool { !self.undo.is_empty() }
+    pub fn can_redo(&self) -> bool { !self.redo.is_empty() }
+    pub fn undo_len(&self) -> usize { self.undo.len() }
+    pub fn redo_len(&self) -> usize { self.redo.len() }
+    pub fn undo_label(&self) -> Option<&str> { self.undo.back().map(|e| e.label.as_str()).filter(|l| !l.is_empty()) }
+    pub fn redo_label(&self) -> Option<&str> { self.redo.back().map(|e| e.label.as_str()).filter(|l| !l.is_empty()) }
+
+    pub fn bytes(&self) -> usize { self.undo.iter().chain(&self.redo).map(|e| e.edit.size_bytes()).sum() }
+
+    pub fn clear(&mut self) {
+        self.undo.clear(); self.redo.clear();
+        self.last_push = None;
+    }
+
+    /// The current state is what's on disk now. Also ends coalescing so the saved state stays reachable.
+    pub fn mark_saved(&mut self) { self.saved = Some(self.state); self.last_push = None; }
+
+    /// For documents that have never been written, so no state in the history reads as saved.
+    pub fn forget_save_point(&mut self) { self.saved = None; }
+
+    pub fn is_saved(&self) -> bool { self.saved == Some(self.state) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Snap { text: &'static str, typing: bool }
+
+    impl UndoableEdit for Snap {
+        fn size_bytes(&self) -> usize { self.text.len() }
+        fn coalesces_with(&self, older: &Self) -> bool { self.typing && older.typing }
+    }
+
+    fn snap(text: &'static str, typing: bool) -> Snap { Snap { text, typing } }
+
+    #[test]
+    fn pushes_inside_the_window_coalesce_only_when_the_entries_agree() {
+        let t0 = Instant::now();
+        let at = |ms: u64| t0 + Duration::from_millis(ms);
+        let mut h = UndoHistory::new(10).with_coalesce_window(Duration::from_millis(500));
+        assert!(h.push_at(snap("", true), "Typing", at(0)));
+        assert!(!h.push_at(snap("a", true), "Typing", at(300)));
+        assert!(!h.push_at(snap("ab", true), "Typing", at(700)));
+        assert!(h.push_at(snap("abc", true), "Typing", at(1300)));
+        assert!(h.push_at(snap("abcd", false), "Paste", at(1400)));
+        assert!(h.push_at(snap("abcd!", true), "Typing", at(1500)));
+        assert_eq!(h.undo_len(), 4);
+        assert_eq!(h.undo(snap("abcd!?", false)), Some(snap("abcd!", true)));
+        assert_eq!((h.undo_label(), h.redo_label()), (Some("Paste"), Some("Typing")));
+        assert_eq!(h.undo(snap("abcd!", false)), Some(snap("abcd", false)));
+        assert_eq!(h.undo(snap("abcd", false)), Some(snap("abc", true)));
+        assert_eq!(h.undo(snap("abc", false)), Some(snap("", true)));
+        assert!(!h.can_undo() && h.redo_len() == 4);
+        assert!(h.push_at(snap("", true), "Typing", at(1600)));
+        assert!(!h.can_redo());
+
+        let mut never = UndoHistory::new(3);
+        for i in 0..5u64 { assert!(never.push_at(snap("x", true), "", at(i))); }
+        assert_eq!((never.undo_len(), never.undo_label()), (3, None));
+    }
+
+    #[test]
+    fn undoing_back_to_the_save_point_reads_as_saved() {
+        let mut h = UndoHistory::new(10);
+        assert!(h.is_saved());
+        h.push(snap("", false), "Edit");
+        h.push(snap("a", false), "Edit");
+        h.mark_saved();
+        h.push(snap("ab", false), "Edit");
+        assert!(!h.is_saved());
+        h.undo(snap("abc", false));
+        assert!(h.is_saved());
+        h.undo(snap("ab", false));
+        assert!(!h.is_saved());
+        h.redo(snap("a", false));
+        assert!(h.is_saved());
+        h.redo(snap("ab", false));
+        h.discard_last();
+        assert!(h.is_saved());
+        h.push(snap("ab", false), "Edit");
+        h.undo(snap("abx", false));
+        h.push(snap("ab", false), "Edit");
+        assert!(!h.is_saved() && !h.can_redo());
+    }
+
+    #[test]
+    fn byte_budget_drops_the_oldest_steps_and_reports_them() {
+        let freed = std::rc::Rc::new(std::cell::Cell::new(0));
+        let seen = freed.clone();
+        let mut h = UndoHistory::new(10).with_byte_budget(10, move |b| seen.set(seen.get() + b));
+        h.push(snap("aaaa", false), "");
+        h.push(snap("bbbb", false), "");
+        assert_eq!((h.bytes(), freed.get()), (8, 0));
+        h.push(snap("cccc", false), "");
+        assert_eq!((h.undo_len(), h.bytes(), freed.get()), (2, 8, 4));
+        assert_eq!(h.undo_entries().map(|s| s.text).collect::<Vec<_>>(), ["cccc", "bbbb"]);
+        assert_eq!(h.last().map(|s| s.text), Some("cccc"));
+    }
+}
@@ -0,0 +1,98 @@
+use image::{DynamicImage, Rgba};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use super::ie_helpers::{rgb_to_hsv, hsv_to_rgb};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub(super) enum AdjustKind {
+    BrightnessContrast { brightness: f32, contrast: f32 },
+    HueSaturation { hue: f32, saturation: f32 },
+    Levels { black: u8, white: u8, gamma: f32 },
+    Blur { radius: f32 },
+}
+
+impl AdjustKind {
+    pub(super) const DEFAULTS: [AdjustKind; 4] = [
+        AdjustKind::BrightnessContrast { brightness: 0.0, contrast: 0.0 },
+        AdjustKind::HueSaturation { hue: 0.0, saturation: 0.0 },
+        AdjustKind::Levels { black: 0, white: 255, gamma: 1.0 },
+        AdjustKind::Blur { radius: 2.0 },
+    ];
+
+    pub(super) fn label(&self) -> &'static str {
+        match self {
+            AdjustKind::BrightnessContrast { .. } => "Brightness / Contrast",
+            AdjustKind::HueSaturation { .. } => "Hue / Saturation",
+            AdjustKind::Levels { .. } => "Levels",
+            AdjustKind::Blur { .. } => "Blur",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub(super) struct Adjustment { pub kind: AdjustKind, pub enabled: bool }
+
+impl Adjustment {
+    pub fn new(kind: AdjustKind) -> Self { Self { kind, enabled: true } }
+
+    fn apply(&self, img: DynamicImage) -> DynamicImage {
+        match self.kind {
+            AdjustKind::BrightnessContrast { brightness, contrast } => {
+                let c = 1.0 + contrast / 100.0;
+                map_rgb(img, |v| ((v as f32 - 128.0) * c + 128.0 + brightness).clamp(0.0, 255.0) as u8)
+            }
+            AdjustKind::HueSaturation { hue, saturation } => {
+                let mut buf = img.into_rgba8();
+                let sat = 1.0 + saturation / 100.0;
+                for p in buf.pixels_mut() {
+                    let (h, s, v) = rgb_to_hsv(p[0], p[1], p[2]);
+                    let (r, g, b) = hsv_to_rgb((h + hue).rem_euclid(360.0), (s * sat).clamp(0.0, 1.0), v);
+                    *p = Rgba([r, g, b, p[3]]);
+                }
+                DynamicImage::ImageRgba8(buf)
+            }
+            AdjustKind::Levels { black, white, gamma } => {
+                let (lo, span, inv) = (black as f32, (white as f32 - black as f32).max(1.0), 1.0 / gamma.max(0.01));
+                let lut: Vec<u8> = (0..=255).map(|v| (((v as f32 - lo) / span).clamp(0.0, 1.0).powf(inv) * 255.0).round() as u8).collect();
+                map_rgb(img, |v| lut[v as usize])
+            }
+            AdjustKind::Blur { radius } => if radius > 0.0 { img.blur(radius) } else { img },
+        }
+    }
+}
+
+fn map_rgb(img: DynamicImage, f: impl Fn(u8) -> u8) -> DynamicImage {
+    let mut buf = img.into_rgba8();
+    for p in buf.pixels_mut() { for i in 0..3 { p[i] = f(p[i]); } }
+    DynamicImage::ImageRgba8(buf)
+}
+
+pub(super) fn apply_stack(base: &DynamicImage, stack: &[Adjustment], cancel: &AtomicBool) -> Option<DynamicImage> {
+    let mut out = base.clone();
+    for adj in stack.iter().filter(|a| a.enabled) {
+        if cancel.load(Ordering::Relaxed) { return None; }
+        out = adj.apply(out);
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stack_applies_enabled_adjustments_in_order() {
+        let base = DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(2, 2, Rgba([100, 150, 200, 255])));
+        let cancel = AtomicBool::new(false);
+        let levels = Adjustment::new(AdjustKind::Levels { black: 100, white: 200, gamma: 1.0 });
+        let bright = Adjustment::new(AdjustKind::BrightnessContrast { brightness: 20.0, contrast: 0.0 });
+        let out = apply_stack(&base, &[levels, bright], &cancel).unwrap().to_rgba8();
+        assert_eq!(out.get_pixel(0, 0).0, [20, 148, 255, 255]);
+        let out = apply_stack(&base, &[bright, levels], &cancel).unwrap().to_rgba8();
+        assert_eq!(out.get_pixel(0, 0).0[0], 51);
+        let off = Adjustment { enabled: false, ..levels };
+        assert_eq!(apply_stack(&base, &[off], &cancel).unwrap().to_rgba8(), base.to_rgba8());
+        cancel.store(true, Ordering::Relaxed);
+        assert!(apply_stack(&base, &[levels], &cancel).is_none());
+    }
+}
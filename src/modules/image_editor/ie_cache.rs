@@ -2,19 +2,54 @@ use serde::{Serialize, Deserialize};
 use std::{collections::{HashMap, hash_map::DefaultHasher}, fs, hash::{Hash, Hasher}, path::{Path, PathBuf}};
 use image::DynamicImage;
 use eframe::egui;
-use super::ie_main::{ImageEditor, ImageLayer, LayerKind, BlendMode, TextLayer, ImageLayerData};
+use super::ie_adjust::Adjustment;
+use super::ie_shape::ShapeLayer;
+use super::ie_main::{ImageEditor, ImageLayer, LayerKind, BlendMode, TextLayer, TextDirection, ImageLayerData, ImageSlice};
+use crate::modules::helpers::config_file;
+use crate::modules::ViewState;
+
+const JOURNAL_LIMIT: usize = 20;
+const VIEW_LIMIT: usize = 200;
 
 #[derive(Serialize, Deserialize)]
-struct LMeta { id: u64, name: String, opacity: f32, visible: bool, locked: bool, blend: BlendMode, kind: LayerKind, ltid: Option<u64>, liid: Option<u64> }
+struct LMeta { id: u64, name: String, opacity: f32, visible: bool, locked: bool, blend: BlendMode, kind: LayerKind, ltid: Option<u64>, liid: Option<u64>, #[serde(default)] lsid: Option<u64> }
 
 #[derive(Serialize, Deserialize)]
-struct TLMeta { id: u64, content: String, x: f32, y: f32, fs: f32, bw: Option<f32>, bh: Option<f32>, rot: f32, c: [u8; 4], bold: bool, ital: bool, ul: bool, font: String }
+struct TLMeta { id: u64, content: String, x: f32, y: f32, fs: f32, bw: Option<f32>, bh: Option<f32>, rot: f32, c: [u8; 4], bold: bool, ital: bool, ul: bool, font: String, #[serde(default)] dir: TextDirection, #[serde(default = "default_tab_width")] tab: u8, #[serde(default)] hid: bool }
+
+fn default_tab_width() -> u8 { 4 }
+
+impl From<&TextLayer> for TLMeta {
+    fn from(t: &TextLayer) -> Self {
+        TLMeta {
+            id: t.id, content: t.content.clone(), x: t.img_x, y: t.img_y, fs: t.font_size,
+            bw: t.box_width, bh: t.box_height, rot: t.rotation,
+            c: [t.color.r(), t.color.g(), t.color.b(), t.color.a()],
+            bold: t.bold, ital: t.italic, ul: t.underline, font: t.font_name.clone(), dir: t.direction, tab: t.tab_width, hid: t.canvas_hidden,
+        }
+    }
+}
+
+impl From<TLMeta> for TextLayer {
+    fn from(t: TLMeta) -> Self {
+        TextLayer {
+            id: t.id, content: t.content, img_x: t.x, img_y: t.y, font_size: t.fs,
+            box_width: t.bw, box_height: t.bh, rotation: t.rot,
+            color: egui::Color32::from_rgba_unmultiplied(t.c[0], t.c[1], t.c[2], t.c[3]),
+            bold: t.bold, italic: t.ital, underline: t.ul, font_name: t.font,
+            rendered_height: 0.0, cached_lines: Vec::new(), direction: t.dir, tab_width: t.tab, font_substitute: None, canvas_hidden: t.hid,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct TextJournal { path: String, tls: Vec<TLMeta> }
 
 #[derive(Serialize, Deserialize)]
 struct ILMeta { id: u64, cx: f32, cy: f32, dw: f32, dh: f32, rot: f32, fh: bool, fv: bool }
 
 #[derive(Serialize, Deserialize)]
-struct Meta { path: String, mod_ms: u64, layers: Vec<LMeta>, tls: Vec<TLMeta>, ils: Vec<ILMeta>, active: u64, nlid: u64, ntid: u64, niid: u64 }
+struct Meta { path: String, mod_ms: u64, layers: Vec<LMeta>, tls: Vec<TLMeta>, ils: Vec<ILMeta>, active: u64, nlid: u64, ntid: u64, niid: u64, #[serde(default)] slices: Vec<ImageSlice>, #[serde(default)] fonts: HashMap<String, String>, #[serde(default)] guides: Vec<ImageSlice>, #[serde(default)] pixels_locked: bool, #[serde(default)] adjustments: Vec<Adjustment>, #[serde(default)] shapes: Vec<ShapeLayer>, #[serde(default)] nsid: u64, #[serde(default)] hide_text: bool }
 
 pub struct CacheEntry { pub src_path: String, pub cache_dir: PathBuf, pub size_kb: u64 }
 
@@ -28,19 +63,31 @@ pub struct LoadedCache {
     pub next_layer_id: u64,
     pub next_text_id: u64,
     pub next_image_layer_id: u64,
+    pub(super) slices: Vec<ImageSlice>,
+    pub font_relinks: HashMap<String, String>,
+    pub(super) guides: Vec<ImageSlice>,
+    pub pixels_locked: bool,
+    pub(super) adjustments: Vec<Adjustment>,
+    pub(super) shape_layers: Vec<ShapeLayer>,
+    pub next_shape_id: u64,
+    pub hide_all_text: bool,
 }
 
-fn cache_base() -> PathBuf {
-    let mut p = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
-    p.push("universal_editor"); p.push("layer_cache"); p
-}
+fn cache_base() -> PathBuf { config_file::config_path("layer_cache") }
 
-pub fn cache_dir_for(path: &Path) -> PathBuf {
+fn path_key(path: &Path) -> String {
     let abs = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
     let mut h = DefaultHasher::new(); abs.hash(&mut h);
-    cache_base().join(format!("{:016x}", h.finish()))
+    format!("{:016x}", h.finish())
 }
 
+pub fn cache_dir_for(path: &Path) -> PathBuf { cache_base().join(path_key(path)) }
+
+/// Journals live with the settings rather than the layer cache, so clearing caches can't take unsaved text with it.
+fn journal_dir() -> PathBuf { config_file::config_path("text_journal") }
+
+fn journal_path(path: &Path) -> PathBuf { journal_dir().join(format!("{}.json", path_key(path))) }
+
 fn mod_ms(path: &Path) -> u64 {
     fs::metadata(path).ok()
         .and_then(|m| m.modified().ok())
@@ -68,20 +115,22 @@ pub fn save_cache(editor: &ImageEditor) -> Result<(), String> {
         path: path.to_string_lossy().into_owned(), mod_ms: mod_ms(path),
         layers: editor.layers.iter().map(|l| LMeta {
             id: l.id, name: l.name.clone(), opacity: l.opacity, visible: l.visible,
-            locked: l.locked, blend: l.blend_mode, kind: l.kind, ltid: l.linked_text_id, liid: l.linked_image_id,
-        }).collect(),
-        tls: editor.text_layers.iter().map(|t| TLMeta {
-            id: t.id, content: t.content.clone(), x: t.img_x, y: t.img_y, fs: t.font_size,
-            bw: t.box_width, bh: t.box_height, rot: t.rotation,
-            c: [t.color.r(), t.color.g(), t.color.b(), t.color.a()],
-            bold: t.bold, ital: t.italic, ul: t.underline, font: t.font_name.clone(),
+            locked: l.locked, blend: l.blend_mode, kind: l.kind, ltid: l.linked_text_id, liid: l.linked_image_id, lsid: l.linked_shape_id,
         }).collect(),
+        tls: editor.text_layers.iter().map(TLMeta::from).collect(),
         ils: editor.image_layer_data.iter().map(|(&id, ild)| ILMeta {
             id, cx: ild.canvas_x, cy: ild.canvas_y, dw: ild.display_w, dh: ild.display_h,
             rot: ild.rotation, fh: ild.flip_h, fv: ild.flip_v,
         }).collect(),
         active: editor.active_layer_id, nlid: editor.next_layer_id,
         ntid: editor.next_text_id, niid: editor.next_image_layer_id,
+        slices: editor.slices.clone(),
+        fonts: editor.font_relinks.clone(),
+        guides: editor.guides.clone(),
+        pixels_locked: editor.pixels_locked,
+        adjustments: editor.adjustments.clone(),
+        shapes: editor.shape_layers.clone(), nsid: editor.next_shape_id,
+        hide_text: editor.hide_all_text,
     };
     fs::write(dir.join("meta.json"), serde_json::to_string(&m).map_err(|e| e.to_string())?).map_err(|e| e.to_string())
 }
@@ -99,16 +148,10 @@ pub fn load_cache(path: &Path) -> Option<LoadedCache> {
     }).collect();
     let layers = m.layers.into_iter().map(|l| ImageLayer {
         id: l.id, name: l.name, opacity: l.opacity, visible: l.visible, locked: l.locked,
-        blend_mode: l.blend, kind: l.kind, linked_text_id: l.ltid, linked_image_id: l.liid,
+        blend_mode: l.blend, kind: l.kind, linked_text_id: l.ltid, linked_image_id: l.liid, linked_shape_id: l.lsid,
     }).collect();
-    let text_layers = m.tls.into_iter().map(|t| TextLayer {
-        id: t.id, content: t.content, img_x: t.x, img_y: t.y, font_size: t.fs,
-        box_width: t.bw, box_height: t.bh, rotation: t.rot,
-        color: egui::Color32::from_rgba_unmultiplied(t.c[0], t.c[1], t.c[2], t.c[3]),
-        bold: t.bold, italic: t.ital, underline: t.ul, font_name: t.font,
-        rendered_height: 0.0, cached_lines: Vec::new(),
-    }).collect();
-    Some(LoadedCache { background, layers, layer_images, text_layers, image_layer_data, active_layer_id: m.active, next_layer_id: m.nlid, next_text_id: m.ntid, next_image_layer_id: m.niid })
+    let text_layers = m.tls.into_iter().map(TextLayer::from).collect();
+    Some(LoadedCache { background, layers, layer_images, text_layers, image_layer_data, active_layer_id: m.active, next_layer_id: m.nlid, next_text_id: m.ntid, next_image_layer_id: m.niid, slices: m.slices, font_relinks: m.fonts, guides: m.guides, pixels_locked: m.pixels_locked, adjustments: m.adjustments, shape_layers: m.shapes, next_shape_id: m.nsid, hide_all_text: m.hide_text })
 }
 
 pub fn apply_cache(editor: &mut ImageEditor, c: LoadedCache) {
@@ -121,6 +164,16 @@ pub fn apply_cache(editor: &mut ImageEditor, c: LoadedCache) {
     editor.next_layer_id = c.next_layer_id;
     editor.next_text_id = c.next_text_id;
     editor.next_image_layer_id = c.next_image_layer_id;
+    editor.slices = c.slices;
+    editor.guides = c.guides;
+    editor.pixels_locked = c.pixels_locked;
+    editor.adjustments = c.adjustments;
+    editor.shape_layers = c.shape_layers;
+    editor.next_shape_id = c.next_shape_id;
+    editor.hide_all_text = c.hide_all_text;
+    editor.refresh_adjustments();
+    editor.font_relinks = c.font_relinks;
+    editor.resolve_text_fonts();
     for l in &editor.layers {
         match l.kind {
             LayerKind::Raster => { editor.raster_layer_texture_dirty.insert(l.id); }
@@ -132,6 +185,59 @@ pub fn apply_cache(editor: &mut ImageEditor, c: LoadedCache) {
     editor.texture_dirty = true;
 }
 
+pub(super) fn write_text_journal(editor: &ImageEditor) -> Result<(), String> {
+    let path = editor.file_path.as_ref().ok_or("no path")?;
+    let j = TextJournal { path: path.to_string_lossy().into_owned(), tls: editor.text_layers.iter().map(TLMeta::from).collect() };
+    let json = serde_json::to_vec(&j).map_err(|e| e.to_string())?;
+    config_file::write_atomic(&journal_path(path), &json).map_err(|e| e.to_string())?;
+    let Ok(rd) = fs::read_dir(journal_dir()) else { return Ok(()) };
+    let mut journals: Vec<(u64, PathBuf)> = rd.flatten().map(|e| (mod_ms(&e.path()), e.path())).collect();
+    if journals.len() > JOURNAL_LIMIT {
+        journals.sort_unstable_by_key(|j| std::cmp::Reverse(j.0));
+        for (_, p) in journals.drain(JOURNAL_LIMIT..) { let _ = fs::remove_file(p); }
+    }
+    Ok(())
+}
+
+pub fn delete_text_journal(path: &Path) { let _ = fs::remove_file(journal_path(path)); }
+
+pub fn offer_text_journal(editor: &mut ImageEditor, path: &Path) {
+    let jp = journal_path(path);
+    if mod_ms(&jp) <= mod_ms(path) { let _ = fs::remove_file(&jp); return; }
+    let Some(j) = fs::read_to_string(&jp).ok().and_then(|s| serde_json::from_str::<TextJournal>(&s).ok()) else { let _ = fs::remove_file(&jp); return };
+    let layers: Vec<TextLayer> = j.tls.into_iter().map(TextLayer::from).collect();
+    if ImageEditor::text_layers_hash(&layers) == ImageEditor::text_layers_hash(&editor.text_layers) { let _ = fs::remove_file(&jp); return; }
+    editor.journal_offer = Some(layers);
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedView { fit: bool, zoom: f32, pan: [f32; 2], at: u64 }
+
+fn view_store() -> PathBuf { config_file::config_path("image_view_state.json") }
+
+fn load_views() -> HashMap<String, SavedView> {
+    config_file::read(&view_store()).ok().and_then(|b| serde_json::from_slice(&b).ok()).unwrap_or_default()
+}
+
+/// The zoom mode, and for a manual zoom the zoom and pan, a file was last left at.
+pub(super) fn load_view_state(path: &Path) -> Option<ViewState> {
+    let v = load_views().remove(&path_key(path))?;
+    Some(ViewState { zoom: Some(v.zoom), pan: Some(egui::vec2(v.pan[0], v.pan[1])), zoom_fit: v.fit, ..Default::default() })
+}
+
+pub(super) fn save_view_state(path: &Path, view: &ViewState) {
+    let mut all = load_views();
+    let at = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let pan = view.pan.unwrap_or_default();
+    all.insert(path_key(path), SavedView { fit: view.zoom_fit, zoom: view.zoom.unwrap_or(1.0), pan: [pan.x, pan.y], at });
+    if all.len() > VIEW_LIMIT {
+        let mut by_age: Vec<(u64, String)> = all.iter().map(|(k, v)| (v.at, k.clone())).collect();
+        by_age.sort_unstable_by_key(|(at, _)| std::cmp::Reverse(*at));
+        for (_, key) in by_age.drain(VIEW_LIMIT..) { all.remove(&key); }
+    }
+    if let Ok(json) = serde_json::to_string(&all) { let _ = config_file::write_atomic(&view_store(), json.as_bytes()); }
+}
+
 pub fn list_caches() -> Vec<CacheEntry> {
     fs::read_dir(cache_base()).ok().map(|rd| {
         rd.flatten().filter_map(|e| {
@@ -144,3 +250,15 @@ pub fn list_caches() -> Vec<CacheEntry> {
 }
 
 pub fn delete_all_caches() { let _ = fs::remove_dir_all(cache_base()); }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_journals_are_kept_out_of_the_clearable_layer_cache() {
+        let journal = journal_path(Path::new("notes.png"));
+        assert_eq!(journal.parent(), Some(config_file::config_path("text_journal").as_path()));
+        assert!(!journal.starts_with(cache_base()));
+    }
+}
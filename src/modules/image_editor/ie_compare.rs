@@ -0,0 +1,89 @@
+use eframe::egui;
+use image::RgbaImage;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+const DIFF_GAIN: u32 = 8;
+const OVERLAY_TINT: egui::Color32 = egui::Color32::from_rgba_premultiplied(170, 0, 170, 170);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompareMode { Difference, Overlay }
+
+impl CompareMode {
+    pub(super) fn label(self) -> &'static str {
+        match self { CompareMode::Difference => "Difference", CompareMode::Overlay => "Highlight" }
+    }
+}
+
+pub(super) struct ImageDiff {
+    pub w: u32, pub h: u32, pub changed: usize, pub bounds: Option<[u32; 4]>, pub size_changed: bool,
+    pub difference: egui::ColorImage, pub overlay: egui::ColorImage,
+}
+
+impl ImageDiff {
+    pub(super) fn summary(&self) -> String {
+        let mut s = match self.bounds {
+            Some([x0, y0, x1, y1]) => format!("{} px changed in {}x{} at ({}, {})", self.changed, x1 - x0, y1 - y0, x0, y0),
+            None => "No pixel changes".to_string(),
+        };
+        if self.size_changed { s.push_str(" · canvas size differs"); }
+        s
+    }
+
+    pub(super) fn image(&self, mode: CompareMode) -> &egui::ColorImage {
+        match mode { CompareMode::Difference => &self.difference, CompareMode::Overlay => &self.overlay }
+    }
+}
+
+pub(super) fn diff(saved: &RgbaImage, current: &RgbaImage, progress: &dyn Fn(f32), cancel: &AtomicBool) -> Option<ImageDiff> {
+    let (w, h) = (saved.width().max(current.width()), saved.height().max(current.height()));
+    let pixel = |img: &RgbaImage, x: u32, y: u32| if x < img.width() && y < img.height() { img.get_pixel(x, y).0 } else { [0; 4] };
+    let mut difference = Vec::with_capacity((w * h) as usize);
+    let mut overlay = Vec::with_capacity((w * h) as usize);
+    let (mut changed, mut x0, mut y0, mut x1, mut y1) = (0, u32::MAX, u32::MAX, 0, 0);
+    for y in 0..h {
+        if y.is_multiple_of(64) {
+            if cancel.load(Ordering::Relaxed) { return None; }
+            progress(y as f32 / h.max(1) as f32);
+        }
+        for x in 0..w {
+            let (a, b) = (pixel(saved, x, y), pixel(current, x, y));
+            let d: [u32; 4] = std::array::from_fn(|i| a[i].abs_diff(b[i]) as u32);
+            let amp = |c: u32| ((c + d[3]) * DIFF_GAIN).min(255) as u8;
+            difference.push(egui::Color32::from_rgb(amp(d[0]), amp(d[1]), amp(d[2])));
+            let hit = d.iter().any(|c| *c > 0);
+            overlay.push(if hit { OVERLAY_TINT } else { egui::Color32::TRANSPARENT });
+            if hit {
+                changed += 1;
+                x0 = x0.min(x); y0 = y0.min(y); x1 = x1.max(x + 1); y1 = y1.max(y + 1);
+            }
+        }
+    }
+    progress(1.0);
+    let size = [w as usize, h as usize];
+    Some(ImageDiff {
+        w, h, changed, bounds: (changed > 0).then_some([x0, y0, x1, y1]), size_changed: saved.dimensions() != current.dimensions(),
+        difference: egui::ColorImage::new(size, difference), overlay: egui::ColorImage::new(size, overlay),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_counts_changed_pixels_and_bounds_them() {
+        let none = AtomicBool::new(false);
+        let saved = RgbaImage::from_pixel(8, 6, image::Rgba([10, 20, 30, 255]));
+        let same = diff(&saved, &saved, &|_| {}, &none).unwrap();
+        assert_eq!((same.changed, same.bounds, same.size_changed), (0, None, false));
+        let mut dabbed = saved.clone();
+        dabbed.put_pixel(2, 1, image::Rgba([12, 20, 30, 255]));
+        dabbed.put_pixel(5, 4, image::Rgba([10, 20, 30, 0]));
+        let d = diff(&saved, &dabbed, &|_| {}, &none).unwrap();
+        assert_eq!((d.changed, d.bounds), (2, Some([2, 1, 6, 5])));
+        assert_eq!(d.difference.pixels[8 + 2], egui::Color32::from_rgb(16, 0, 0));
+        assert_eq!((d.overlay.pixels[0], d.overlay.pixels[4 * 8 + 5]), (egui::Color32::TRANSPARENT, OVERLAY_TINT));
+        let grown = diff(&saved, &RgbaImage::from_pixel(9, 6, image::Rgba([10, 20, 30, 255])), &|_| {}, &none).unwrap();
+        assert_eq!((grown.w, grown.changed, grown.bounds, grown.size_changed), (9, 6, Some([8, 0, 9, 6]), true));
+    }
+}
@@ -0,0 +1,101 @@
+use eframe::egui;
+use egui::emath::GuiRounding;
+use serde::{Deserialize, Serialize};
+
+const DEFAULT_WIDTH: f32 = 1.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum CursorColor { #[default] Auto, White, Black, Magenta, Yellow }
+
+impl CursorColor {
+    pub const ALL: [CursorColor; 5] = [CursorColor::Auto, CursorColor::White, CursorColor::Black, CursorColor::Magenta, CursorColor::Yellow];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            CursorColor::Auto => "Contrast with image", CursorColor::White => "White", CursorColor::Black => "Black",
+            CursorColor::Magenta => "Magenta", CursorColor::Yellow => "Yellow",
+        }
+    }
+
+    fn fixed(self) -> Option<egui::Color32> {
+        match self {
+            CursorColor::Auto => None, CursorColor::White => Some(egui::Color32::WHITE), CursorColor::Black => Some(egui::Color32::BLACK),
+            CursorColor::Magenta => Some(egui::Color32::from_rgb(255, 0, 255)), CursorColor::Yellow => Some(egui::Color32::from_rgb(255, 230, 0)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CursorOutline { pub width: f32, pub color: CursorColor }
+
+impl Default for CursorOutline {
+    fn default() -> Self { Self { width: DEFAULT_WIDTH, color: CursorColor::Auto } }
+}
+
+impl CursorOutline {
+    /// `auto` is the colour picked from the pixels under the pointer, used unless a fixed colour is set.
+    pub(super) fn stroke(self, auto: egui::Color32) -> egui::Stroke { egui::Stroke::new(self.width.clamp(0.5, 6.0), self.color.fixed().unwrap_or(auto)) }
+}
+
+/// Snaps `pos` to the centre of a physical pixel so thin outlines stay crisp at fractional `pixels_per_point`.
+pub(super) fn snap(painter: &egui::Painter, pos: egui::Pos2) -> egui::Pos2 { pos.round_to_pixel_center(painter.pixels_per_point()) }
+
+fn halo(stroke: egui::Stroke) -> egui::Stroke {
+    let [r, g, b, _] = stroke.color.to_array();
+    let light = 0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32 > 128.0;
+    egui::Stroke::new(stroke.width + 2.0, if light { egui::Color32::from_black_alpha(150) } else { egui::Color32::from_white_alpha(150) })
+}
+
+/// Paths for a tilted paint bucket whose pour ends exactly on `hot`: the body (closed) and the pour (open).
+pub(super) fn bucket_paths(hot: egui::Pos2) -> [Vec<egui::Pos2>; 2] {
+    let (u, v) = (egui::vec2(1.0, -1.0).normalized(), egui::vec2(1.0, 1.0).normalized());
+    let mouth = hot + u * 7.0;
+    let body = vec![mouth - v * 6.0, mouth + v * 6.0, mouth + u * 11.0 + v * 4.5, mouth + u * 11.0 - v * 4.5];
+    [body, vec![mouth, hot]]
+}
+
+/// Paths for an eyedropper whose tip sits on `hot`: the barrel with its bulb (closed) and the glass tip (open).
+pub(super) fn eyedropper_paths(hot: egui::Pos2) -> [Vec<egui::Pos2>; 2] {
+    let (u, v) = (egui::vec2(1.0, -1.0).normalized(), egui::vec2(1.0, 1.0).normalized());
+    let side = |along: f32, half: f32| [hot + u * along + v * half, hot + u * along - v * half];
+    let [a, a2] = side(5.0, 1.2);
+    let [b, b2] = side(12.0, 2.6);
+    let [c, c2] = side(17.0, 3.2);
+    let [d, d2] = side(20.0, 1.8);
+    let barrel = vec![a2, a, b, c, d, d2, c2, b2];
+    [barrel, vec![hot + u * 5.0, hot]]
+}
+
+pub(super) fn draw_glyph(painter: &egui::Painter, [body, tip]: [Vec<egui::Pos2>; 2], stroke: egui::Stroke, fill: egui::Color32) {
+    painter.add(egui::Shape::closed_line(body.clone(), halo(stroke)));
+    painter.add(egui::Shape::line(tip.clone(), halo(stroke)));
+    painter.add(egui::Shape::convex_polygon(body, fill, stroke));
+    painter.add(egui::Shape::line(tip, stroke));
+}
+
+pub(super) fn draw_crosshair(painter: &egui::Painter, center: egui::Pos2, stroke: egui::Stroke) {
+    let (gap, arm) = (3.0, 9.0);
+    let dirs = [egui::vec2(1.0, 0.0), egui::vec2(-1.0, 0.0), egui::vec2(0.0, 1.0), egui::vec2(0.0, -1.0)];
+    for s in [halo(stroke), stroke] {
+        for dir in dirs { painter.line_segment([center + dir * gap, center + dir * arm], s); }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glyphs_end_on_the_hotspot_and_stay_clear_of_it() {
+        let hot = egui::pos2(40.5, 30.5);
+        for [body, tip] in [bucket_paths(hot), eyedropper_paths(hot)] {
+            assert_eq!(*tip.last().unwrap(), hot);
+            assert!(body.iter().chain(&tip).all(|p| (p.x - hot.x) - (p.y - hot.y) >= -1e-3), "glyphs extend up and to the right of the hotspot");
+            assert!(body.iter().all(|p| p.distance(hot) > 3.0), "the glyph body must not cover the hotspot");
+        }
+        let outline = CursorOutline { width: 9.0, color: CursorColor::Magenta };
+        assert_eq!(outline.stroke(egui::Color32::WHITE), egui::Stroke::new(6.0, egui::Color32::from_rgb(255, 0, 255)));
+        assert_eq!(CursorOutline::default().stroke(egui::Color32::WHITE), egui::Stroke::new(1.5, egui::Color32::WHITE));
+    }
+}
@@ -0,0 +1,121 @@
+use image::{DynamicImage, ImageBuffer, Rgba};
+use super::ie_helpers::{rgb_to_hsv, hsv_to_rgb};
+
+pub(super) fn brightness_contrast(img: &DynamicImage, brightness: f32, contrast: f32, progress: &dyn Fn(f32)) -> DynamicImage {
+    let c = 1.0 + contrast / 100.0;
+    let mut buf = img.to_rgba8();
+    let total = (buf.width() * buf.height()) as usize;
+    for (n, pixel) in buf.pixels_mut().enumerate() {
+        for i in 0..3 { pixel[i] = ((pixel[i] as f32 - 128.0) * c + 128.0 + brightness).clamp(0.0, 255.0) as u8; }
+        if (n + 1).is_multiple_of(5000) { progress((n + 1) as f32 / total as f32); }
+    }
+    DynamicImage::ImageRgba8(buf)
+}
+
+pub(super) fn hue_saturation(img: &DynamicImage, hue: f32, saturation: f32, progress: &dyn Fn(f32)) -> DynamicImage {
+    let sat_factor = 1.0 + saturation / 100.0;
+    let mut buf = img.to_rgba8();
+    for y in 0..buf.height() {
+        for x in 0..buf.width() {
+            let p = buf.get_pixel(x, y).0;
+            let (h, s, v) = rgb_to_hsv(p[0], p[1], p[2]);
+            let (nr, ng, nb) = hsv_to_rgb((h + hue).rem_euclid(360.0), (s * sat_factor).clamp(0.0, 1.0), v);
+            buf.put_pixel(x, y, Rgba([nr, ng, nb, p[3]]));
+        }
+        if y.is_multiple_of(10) { progress(y as f32 / buf.height() as f32); }
+    }
+    DynamicImage::ImageRgba8(buf)
+}
+
+pub(super) fn blur(img: &DynamicImage, radius: f32) -> DynamicImage { img.blur(radius) }
+
+pub(super) fn sharpen(img: &DynamicImage, amount: f32) -> DynamicImage { img.unsharpen(amount, 0) }
+
+pub(super) fn grayscale(img: &DynamicImage) -> DynamicImage { DynamicImage::ImageRgba8(img.grayscale().to_rgba8()) }
+
+pub(super) fn invert_pixel(px: &mut [u8]) { px[0] = 255 - px[0]; px[1] = 255 - px[1]; px[2] = 255 - px[2]; }
+
+pub(super) fn sepia_pixel(px: &mut [u8]) {
+    let (rf, gf, bf) = (px[0] as f32, px[1] as f32, px[2] as f32);
+    px[0] = (rf*0.393 + gf*0.769 + bf*0.189).min(255.0) as u8;
+    px[1] = (rf*0.349 + gf*0.686 + bf*0.168).min(255.0) as u8;
+    px[2] = (rf*0.272 + gf*0.534 + bf*0.131).min(255.0) as u8;
+}
+
+fn luma(p: &[u8]) -> u8 { (0.299 * p[0] as f32 + 0.587 * p[1] as f32 + 0.114 * p[2] as f32).round().clamp(0.0, 255.0) as u8 }
+
+pub(super) fn luma_histogram(img: &DynamicImage) -> [u32; 256] {
+    let mut hist = [0u32; 256];
+    for p in img.to_rgba8().pixels().filter(|p| p[3] > 0) { hist[luma(&p.0) as usize] += 1; }
+    hist
+}
+
+/// Otsu's threshold: the level splitting the histogram into dark (below) and light (at or above) classes with the
+/// largest between-class variance. Ties, as in the empty gap of a clean bimodal image, resolve to the middle of the gap.
+pub(super) fn otsu(hist: &[u32; 256]) -> u8 {
+    let total: f64 = hist.iter().map(|&c| c as f64).sum();
+    let sum_all: f64 = hist.iter().enumerate().map(|(i, &c)| i as f64 * c as f64).sum();
+    let (mut w0, mut sum0, mut best, mut first, mut last) = (0.0, 0.0, -1.0, 128usize, 128usize);
+    for t in 1..256 {
+        w0 += hist[t - 1] as f64;
+        sum0 += (t - 1) as f64 * hist[t - 1] as f64;
+        let w1 = total - w0;
+        if w0 == 0.0 || w1 == 0.0 { continue; }
+        let var = w0 * w1 * (sum0 / w0 - (sum_all - sum0) / w1).powi(2);
+        if var > best { best = var; first = t; last = t; } else if var == best { last = t; }
+    }
+    ((first + last) / 2) as u8
+}
+
+/// Pixels at or above `level` turn white and below it black; `soft` widens that edge into a grey ramp of that many levels.
+pub(super) fn threshold(img: &DynamicImage, level: u8, soft: u8, progress: &dyn Fn(f32)) -> DynamicImage {
+    let (lo, hi) = (level as f32 - soft as f32 / 2.0, level as f32 + soft as f32 / 2.0);
+    let map: [u8; 256] = std::array::from_fn(|l| {
+        let l = l as f32;
+        if soft == 0 { return if l >= level as f32 { 255 } else { 0 }; }
+        (((l - lo) / (hi - lo)).clamp(0.0, 1.0) * 255.0).round() as u8
+    });
+    let mut buf = img.to_rgba8();
+    let total = (buf.width() * buf.height()) as usize;
+    for (n, pixel) in buf.pixels_mut().enumerate() {
+        let v = map[luma(&pixel.0) as usize];
+        pixel[0] = v; pixel[1] = v; pixel[2] = v;
+        if (n + 1).is_multiple_of(5000) { progress((n + 1) as f32 / total as f32); }
+    }
+    DynamicImage::ImageRgba8(buf)
+}
+
+pub(super) fn map_pixels(img: &DynamicImage, op: impl Fn(&mut [u8])) -> DynamicImage {
+    let mut buf = img.to_rgba8();
+    for chunk in buf.as_flat_samples_mut().as_mut_slice().chunks_exact_mut(4) { op(chunk); }
+    DynamicImage::ImageRgba8(buf)
+}
+
+pub(super) fn resize(img: &DynamicImage, w: u32, h: u32, stretch: bool) -> DynamicImage {
+    if stretch { return img.resize_exact(w, h, image::imageops::FilterType::Lanczos3); }
+    let mut new_buf: ImageBuffer<Rgba<u8>, Vec<u8>> = ImageBuffer::from_pixel(w, h, Rgba([255, 255, 255, 255]));
+    image::imageops::overlay(&mut new_buf, img, 0, 0);
+    DynamicImage::ImageRgba8(new_buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn otsu_splits_a_bimodal_image_in_the_middle_of_its_gap() {
+        let img = DynamicImage::ImageRgba8(ImageBuffer::from_fn(82, 10, |x, _| {
+            let v = if x < 41 { 40 + x as u8 } else { 170 + (x - 41) as u8 };
+            Rgba([v, v, v, 255])
+        }));
+        let hist = luma_histogram(&img);
+        assert_eq!((hist[40], hist[80], hist[81], hist[170], hist[210]), (10, 10, 0, 10, 10));
+        let level = otsu(&hist);
+        assert_eq!(level, 125);
+        let hard = threshold(&img, level, 0, &|_| {}).to_rgba8();
+        assert_eq!((hard.get_pixel(40, 3).0, hard.get_pixel(41, 3).0), ([0, 0, 0, 255], [255, 255, 255, 255]));
+        let soft = threshold(&img, 60, 20, &|_| {}).to_rgba8();
+        assert_eq!((soft.get_pixel(5, 0)[0], soft.get_pixel(20, 0)[0], soft.get_pixel(35, 0)[0]), (0, 128, 255));
+        assert_eq!(otsu(&[0; 256]), 128);
+    }
+}
@@ -0,0 +1,94 @@
+use image::{DynamicImage, Rgba, RgbaImage};
+use std::path::PathBuf;
+use super::ie_filters as filters;
+use super::ie_main::{ImageEditor, TextDirection, TextLayer};
+
+const TOLERANCE: u8 = 2;
+
+fn fixtures_dir() -> PathBuf { PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures") }
+
+fn diff_dir() -> PathBuf { PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target").join("golden-diff") }
+
+pub(super) fn fixture(name: &str) -> DynamicImage {
+    let path = fixtures_dir().join(format!("{}.png", name));
+    image::open(&path).unwrap_or_else(|e| panic!("could not load fixture {}: {}", path.display(), e))
+}
+
+pub(super) fn compare(actual: &RgbaImage, golden: &RgbaImage, tolerance: u8) -> Option<(usize, RgbaImage)> {
+    let mut bad = 0;
+    let diff = RgbaImage::from_fn(actual.width(), actual.height(), |x, y| {
+        let (a, g) = (actual.get_pixel(x, y).0, golden.get_pixel(x, y).0);
+        if (0..4).any(|c| a[c].abs_diff(g[c]) > tolerance) { bad += 1; return Rgba([255, 0, 0, 255]); }
+        let luma = ((a[0] as u32 * 3 + a[1] as u32 * 6 + a[2] as u32) / 40) as u8;
+        Rgba([luma, luma, luma, 255])
+    });
+    (bad > 0).then_some((bad, diff))
+}
+
+pub(super) fn check_golden(name: &str, actual: &DynamicImage) -> Result<(), String> {
+    let path = fixtures_dir().join("golden").join(format!("{}.png", name));
+    let actual = actual.to_rgba8();
+    if std::env::var_os("UPDATE_GOLDEN").is_some() { return actual.save(&path).map_err(|e| format!("{}: could not write golden: {}", name, e)); }
+    let golden = image::open(&path).map_err(|e| format!("{}: no golden at {} ({}); rerun with UPDATE_GOLDEN=1 to create it", name, path.display(), e))?.to_rgba8();
+    if golden.dimensions() != actual.dimensions() { return Err(format!("{}: expected {:?}, got {:?}", name, golden.dimensions(), actual.dimensions())); }
+    let Some((bad, diff)) = compare(&actual, &golden, TOLERANCE) else { return Ok(()) };
+    let dir = diff_dir();
+    let _ = std::fs::create_dir_all(&dir);
+    let _ = actual.save(dir.join(format!("{}.actual.png", name)));
+    let _ = diff.save(dir.join(format!("{}.diff.png", name)));
+    Err(format!("{}: {} pixel(s) differ by more than {}; see {}", name, bad, TOLERANCE, dir.join(format!("{}.diff.png", name)).display()))
+}
+
+fn edited(f: impl FnOnce(&mut ImageEditor)) -> DynamicImage {
+    let mut ed = ImageEditor::new();
+    ed.image = Some(fixture("swatches"));
+    f(&mut ed);
+    ed.image.take().unwrap()
+}
+
+fn caption() -> TextLayer {
+    TextLayer {
+        id: 1, content: "Ag".into(), img_x: 3.0, img_y: 2.0, font_size: 14.0,
+        box_width: None, box_height: None, rotation: 12.0, color: egui::Color32::from_rgb(20, 30, 160),
+        bold: true, italic: false, underline: true, font_name: "Ubuntu".into(),
+        rendered_height: 0.0, cached_lines: Vec::new(), direction: TextDirection::Ltr, tab_width: 4, font_substitute: None, canvas_hidden: false,
+    }
+}
+
+#[test]
+fn golden_comparison_tolerates_small_drift_and_marks_real_changes() {
+    let base = fixture("swatches").to_rgba8();
+    let mut drifted = base.clone();
+    drifted.pixels_mut().for_each(|p| p[0] = p[0].saturating_add(TOLERANCE));
+    assert!(compare(&drifted, &base, TOLERANCE).is_none());
+    drifted.put_pixel(5, 7, Rgba([0, 0, 0, 0]));
+    let (bad, diff) = compare(&drifted, &base, TOLERANCE).unwrap();
+    assert_eq!(bad, 1);
+    assert_eq!(diff.get_pixel(5, 7).0, [255, 0, 0, 255]);
+    assert_ne!(diff.get_pixel(6, 7).0, [255, 0, 0, 255]);
+}
+
+#[test]
+fn filters_match_golden_images() {
+    let src = fixture("swatches");
+    let none = |_| {};
+    let cases: Vec<(&str, DynamicImage)> = vec![
+        ("brightness_contrast", filters::brightness_contrast(&src, 20.0, 35.0, &none)),
+        ("hue_saturation", filters::hue_saturation(&src, 90.0, -40.0, &none)),
+        ("blur", filters::blur(&src, 1.5)),
+        ("sharpen", filters::sharpen(&src, 2.0)),
+        ("resize_stretch", filters::resize(&src, 20, 15, true)),
+        ("resize_canvas", filters::resize(&src, 40, 30, false)),
+        ("grayscale", edited(|ed| ed.apply_grayscale())),
+        ("invert", edited(|ed| ed.apply_invert())),
+        ("sepia", edited(|ed| ed.apply_sepia())),
+        ("crop", edited(|ed| { ed.crop_state.start = Some((4.0, 3.0)); ed.crop_state.end = Some((25.0, 19.0)); ed.apply_crop(); })),
+        ("flip_h", edited(|ed| ed.apply_flip_h())),
+        ("flip_v", edited(|ed| ed.apply_flip_v())),
+        ("rotate_cw", edited(|ed| ed.apply_rotate_cw())),
+        ("rotate_ccw", edited(|ed| ed.apply_rotate_ccw())),
+        ("text", ImageEditor::new().stamp_single_text_layer(&src, &caption(), 0.9)),
+    ];
+    let failures: Vec<String> = cases.iter().filter_map(|(name, img)| check_golden(name, img).err()).collect();
+    assert!(failures.is_empty(), "{} golden image(s) differ:\n{}", failures.len(), failures.join("\n"));
+}
@@ -0,0 +1,479 @@
+use image::RgbaImage;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, OnceLock};
+use std::time::Duration;
+
+static CONTEXT: OnceLock<Option<GpuContext>> = OnceLock::new();
+
+const WORKGROUP: u32 = 16;
+const MIN_TILE: u32 = 64;
+
+const SHADER: &str = r#"
+// One tile: `src_*` is the uploaded region and `dst_*` the output region, both placed in the full image by their
+// offsets. Resizing works in full-image coordinates so tiles line up exactly.
+struct Params {
+    src_w: u32, src_h: u32, dst_w: u32, dst_h: u32,
+    radius: u32, src_x: u32, src_y: u32, dst_x: u32,
+    dst_y: u32, full_src_w: u32, full_src_h: u32, full_dst_w: u32,
+    full_dst_h: u32, pad0: u32, pad1: u32, pad2: u32,
+}
+
+@group(0) @binding(0) var src: texture_2d<f32>;
+@group(0) @binding(1) var<storage, read_write> mid: array<vec4<f32>>;
+@group(0) @binding(2) var<storage, read_write> dst: array<u32>;
+@group(0) @binding(3) var<uniform> p: Params;
+@group(0) @binding(4) var<storage, read> weights: array<f32>;
+
+const PI: f32 = 3.14159265358979;
+
+fn sinc(t: f32) -> f32 { if (t == 0.0) { return 1.0; } let a = t * PI; return sin(a) / a; }
+fn lanczos3(x: f32) -> f32 { if (abs(x) < 3.0) { return sinc(x) * sinc(x / 3.0); } return 0.0; }
+
+@compute @workgroup_size(16, 16)
+fn blur_h(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x >= p.src_w || id.y >= p.src_h) { return; }
+    let r = i32(p.radius);
+    var acc = vec4<f32>(0.0);
+    for (var k = -r; k <= r; k++) {
+        let x = clamp(i32(id.x) + k, 0, i32(p.src_w) - 1);
+        acc += textureLoad(src, vec2<i32>(x, i32(id.y)), 0) * weights[u32(k + r)];
+    }
+    mid[id.y * p.src_w + id.x] = acc;
+}
+
+@compute @workgroup_size(16, 16)
+fn blur_v(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x >= p.src_w || id.y >= p.src_h) { return; }
+    let r = i32(p.radius);
+    var acc = vec4<f32>(0.0);
+    for (var k = -r; k <= r; k++) {
+        let y = u32(clamp(i32(id.y) + k, 0, i32(p.src_h) - 1));
+        acc += mid[y * p.src_w + id.x] * weights[u32(k + r)];
+    }
+    dst[id.y * p.src_w + id.x] = pack4x8unorm(acc);
+}
+
+@compute @workgroup_size(16, 16)
+fn resize_v(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x >= p.src_w || id.y >= p.dst_h) { return; }
+    let ratio = f32(p.full_src_h) / f32(p.full_dst_h);
+    let sratio = max(ratio, 1.0);
+    let center = (f32(p.dst_y + id.y) + 0.5) * ratio;
+    let left = clamp(i32(floor(center - 3.0 * sratio)), 0, i32(p.full_src_h) - 1);
+    let right = clamp(i32(ceil(center + 3.0 * sratio)), left + 1, i32(p.full_src_h));
+    var acc = vec4<f32>(0.0);
+    var sum = 0.0;
+    for (var i = left; i < right; i++) {
+        let w = lanczos3((f32(i) - (center - 0.5)) / sratio);
+        acc += textureLoad(src, vec2<i32>(i32(id.x), i - i32(p.src_y)), 0) * w;
+        sum += w;
+    }
+    mid[id.y * p.src_w + id.x] = acc / sum;
+}
+
+@compute @workgroup_size(16, 16)
+fn resize_h(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x >= p.dst_w || id.y >= p.dst_h) { return; }
+    let ratio = f32(p.full_src_w) / f32(p.full_dst_w);
+    let sratio = max(ratio, 1.0);
+    let center = (f32(p.dst_x + id.x) + 0.5) * ratio;
+    let left = clamp(i32(floor(center - 3.0 * sratio)), 0, i32(p.full_src_w) - 1);
+    let right = clamp(i32(ceil(center + 3.0 * sratio)), left + 1, i32(p.full_src_w));
+    var acc = vec4<f32>(0.0);
+    var sum = 0.0;
+    for (var i = left; i < right; i++) {
+        let w = lanczos3((f32(i) - (center - 0.5)) / sratio);
+        acc += mid[id.y * p.src_w + u32(i - i32(p.src_x))] * w;
+        sum += w;
+    }
+    dst[id.y * p.dst_w + id.x] = pack4x8unorm(acc / sum);
+}
+"#;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum GpuJob { Blur(f32), Resize(u32, u32) }
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum GpuStage { Upload, Dispatch, Readback }
+
+impl GpuStage {
+    pub(super) fn label(&self) -> &'static str {
+        match self { GpuStage::Upload => "Uploading to GPU...", GpuStage::Dispatch => "Running on GPU...", GpuStage::Readback => "Reading back from GPU..." }
+    }
+    pub(super) fn progress(&self) -> f32 {
+        match self { GpuStage::Upload => 0.1, GpuStage::Dispatch => 0.4, GpuStage::Readback => 0.8 }
+    }
+}
+
+/// A region of an image as `[x, y, width, height]`.
+type Region = [u32; 4];
+
+/// One dispatch over part of the image: `src` is uploaded and `dst` is the part of the output it writes. Blur tiles
+/// carry a halo of the kernel radius; resize tiles carry the source span the Lanczos window reads.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Tile { src: Region, dst: Region }
+
+/// Source rows (or columns) `[start, end)` the resize window reads for output rows `[a, b)`, in the shader's f32 math plus a pixel of slack.
+fn resize_span(a: u32, b: u32, src: u32, dst: u32) -> (u32, u32) {
+    let ratio = src as f32 / dst as f32;
+    let sratio = ratio.max(1.0);
+    let left = (((a as f32 + 0.5) * ratio - 3.0 * sratio).floor() as i64 - 1).clamp(0, src as i64 - 1);
+    let right = (((b as f32 - 0.5) * ratio + 3.0 * sratio).ceil() as i64 + 1).clamp(left + 1, src as i64);
+    (left as u32, right as u32)
+}
+
+fn plan_tiles(src: (u32, u32), job: GpuJob, radius: u32, tile: u32) -> Vec<Tile> {
+    let dst = match job { GpuJob::Blur(_) => src, GpuJob::Resize(w, h) => (w, h) };
+    let mut tiles = Vec::new();
+    for y in (0..dst.1).step_by(tile as usize) {
+        for x in (0..dst.0).step_by(tile as usize) {
+            let d = [x, y, tile.min(dst.0 - x), tile.min(dst.1 - y)];
+            let (sx, sx1, sy, sy1) = match job {
+                GpuJob::Blur(_) => (x.saturating_sub(radius), (x + d[2] + radius).min(src.0), y.saturating_sub(radius), (y + d[3] + radius).min(src.1)),
+                GpuJob::Resize(..) => {
+                    let ((sx, sx1), (sy, sy1)) = (resize_span(x, x + d[2], src.0, dst.0), resize_span(y, y + d[3], src.1, dst.1));
+                    (sx, sx1, sy, sy1)
+                }
+            };
+            tiles.push(Tile { src: [sx, sy, sx1 - sx, sy1 - sy], dst: d });
+        }
+    }
+    tiles
+}
+
+/// Bytes of the intermediate and output buffers a tile needs.
+fn tile_buffers(t: &Tile, job: GpuJob) -> (u64, u64) {
+    let [_, _, sw, sh] = t.src;
+    let [_, _, dw, dh] = t.dst;
+    match job {
+        GpuJob::Blur(_) => (sw as u64 * sh as u64 * 16, sw as u64 * sh as u64 * 4),
+        GpuJob::Resize(..) => (sw as u64 * dh as u64 * 16, dw as u64 * dh as u64 * 4),
+    }
+}
+
+struct GpuContext {
+    device: wgpu::Device, queue: wgpu::Queue, layout: wgpu::BindGroupLayout,
+    blur_h: wgpu::ComputePipeline, blur_v: wgpu::ComputePipeline,
+    resize_v: wgpu::ComputePipeline, resize_h: wgpu::ComputePipeline,
+}
+
+fn context() -> Option<&'static GpuContext> { CONTEXT.get_or_init(GpuContext::new).as_ref() }
+
+fn gaussian_kernel(sigma: f32) -> Vec<f32> {
+    let sigma = if sigma == 0.0 { 0.8 } else { sigma };
+    let size = (((sigma - 0.8) / 0.3 + 1.0) * 2.0 + 1.0).max(3.0) as usize;
+    let size = if size.is_multiple_of(2) { size + 1 } else { size };
+    let mean = (size / 2) as f32;
+    let kernel: Vec<f32> = (0..size).map(|x| (-0.5 * ((x as f32 - mean) / sigma).powi(2)).exp()).collect();
+    let sum: f32 = kernel.iter().sum();
+    kernel.into_iter().map(|w| w / sum).collect()
+}
+
+fn storage_entry(binding: u32, read_only: bool) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding, visibility: wgpu::ShaderStages::COMPUTE, count: None,
+        ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Storage { read_only }, has_dynamic_offset: false, min_binding_size: None },
+    }
+}
+
+impl GpuContext {
+    fn new() -> Option<Self> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::from_env_or_default());
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions { power_preference: wgpu::PowerPreference::HighPerformance, ..Default::default() })).ok()?;
+        let (device, queue) = pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
+            label: Some("ie_gpu"), required_limits: adapter.limits(), ..Default::default()
+        })).ok()?;
+        device.on_uncaptured_error(Arc::new(|e: wgpu::Error| log::error!("GPU filter error: {}", e)));
+        Some(Self::with_device(device, queue))
+    }
+
+    fn with_device(device: wgpu::Device, queue: wgpu::Queue) -> Self {
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor { label: Some("ie_gpu"), source: wgpu::ShaderSource::Wgsl(SHADER.into()) });
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("ie_gpu"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0, visibility: wgpu::ShaderStages::COMPUTE, count: None,
+                    ty: wgpu::BindingType::Texture { sample_type: wgpu::TextureSampleType::Float { filterable: false }, view_dimension: wgpu::TextureViewDimension::D2, multisampled: false },
+                },
+                storage_entry(1, false),
+                storage_entry(2, false),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3, visibility: wgpu::ShaderStages::COMPUTE, count: None,
+                    ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None },
+                },
+                storage_entry(4, true),
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor { label: Some("ie_gpu"), bind_group_layouts: &[&layout], push_constant_ranges: &[] });
+        let pipeline = |entry: &str| device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(entry), layout: Some(&pipeline_layout), module: &module, entry_point: Some(entry),
+            compilation_options: Default::default(), cache: None,
+        });
+        let (blur_h, blur_v, resize_v, resize_h) = (pipeline("blur_h"), pipeline("blur_v"), pipeline("resize_v"), pipeline("resize_h"));
+        Self { device, queue, layout, blur_h, blur_v, resize_v, resize_h }
+    }
+
+    fn fits(&self, t: &Tile, job: GpuJob) -> bool {
+        let limits = self.device.limits();
+        let max_binding = (limits.max_storage_buffer_binding_size as u64).min(limits.max_buffer_size);
+        let (mid, out) = tile_buffers(t, job);
+        t.src[2] <= limits.max_texture_dimension_2d && t.src[3] <= limits.max_texture_dimension_2d && mid <= max_binding && out <= max_binding
+    }
+
+    /// Splits the job into the largest square tiles the device takes, so images past its limits still run.
+    fn tiles(&self, src: (u32, u32), job: GpuJob, radius: u32) -> Option<Vec<Tile>> {
+        let dst = match job { GpuJob::Blur(_) => src, GpuJob::Resize(w, h) => (w, h) };
+        let mut size = dst.0.max(dst.1);
+        loop {
+            let tiles = plan_tiles(src, job, radius, size);
+            if tiles.iter().all(|t| self.fits(t, job)) { return Some(tiles); }
+            if size <= MIN_TILE { return None; }
+            size = (size / 2).max(MIN_TILE);
+        }
+    }
+
+    fn run(&self, img: &RgbaImage, job: GpuJob, stage: &dyn Fn(GpuStage, f32), cancel: &AtomicBool) -> Option<RgbaImage> {
+        let (sw, sh) = img.dimensions();
+        let (dw, dh) = match job { GpuJob::Blur(_) => (sw, sh), GpuJob::Resize(w, h) => (w, h) };
+        if sw == 0 || sh == 0 || dw == 0 || dh == 0 { return None; }
+        let radius = match job { GpuJob::Blur(sigma) => (gaussian_kernel(sigma).len() / 2) as u32, GpuJob::Resize(..) => 0 };
+        let tiles = self.tiles((sw, sh), job, radius)?;
+        self.run_tiles(img, job, &tiles, stage, cancel)
+    }
+
+    fn run_tiles(&self, img: &RgbaImage, job: GpuJob, tiles: &[Tile], stage: &dyn Fn(GpuStage, f32), cancel: &AtomicBool) -> Option<RgbaImage> {
+        let (sw, sh) = img.dimensions();
+        let (dw, dh) = match job { GpuJob::Blur(_) => (sw, sh), GpuJob::Resize(w, h) => (w, h) };
+        let weights = match job { GpuJob::Blur(sigma) => gaussian_kernel(sigma), GpuJob::Resize(..) => vec![0.0] };
+        if let [t] = tiles { return self.run_tile(img, *t, (sw, sh), (dw, dh), job, &weights, &|s| stage(s, s.progress()), cancel); }
+        let mut out = RgbaImage::new(dw, dh);
+        let n = tiles.len() as f32;
+        for (i, t) in tiles.iter().enumerate() {
+            let [x, y, w, h] = t.src;
+            let part = image::imageops::crop_imm(img, x, y, w, h).to_image();
+            let done = self.run_tile(&part, *t, (sw, sh), (dw, dh), job, &weights, &|s| stage(s, (i as f32 + s.progress()) / n), cancel)?;
+            image::imageops::replace(&mut out, &done, t.dst[0] as i64, t.dst[1] as i64);
+        }
+        Some(out)
+    }
+
+    /// Runs one tile and returns its `dst` region.
+    #[allow(clippy::too_many_arguments)]
+    fn run_tile(&self, img: &RgbaImage, t: Tile, full_src: (u32, u32), full_dst: (u32, u32), job: GpuJob, weights: &[f32], stage: &dyn Fn(GpuStage), cancel: &AtomicBool) -> Option<RgbaImage> {
+        let [sx, sy, sw, sh] = t.src;
+        let [dx, dy, dw, dh] = t.dst;
+        let (mid_size, out_size) = tile_buffers(&t, job);
+        let (out_w, out_h) = match job { GpuJob::Blur(_) => (sw, sh), GpuJob::Resize(..) => (dw, dh) };
+        let mid_h = match job { GpuJob::Blur(_) => sh, GpuJob::Resize(..) => dh };
+        let radius = (weights.len() / 2) as u32;
+
+        stage(GpuStage::Upload);
+        self.device.push_error_scope(wgpu::ErrorFilter::OutOfMemory);
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let size = wgpu::Extent3d { width: sw, height: sh, depth_or_array_layers: 1 };
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("ie_gpu_src"), size, mip_level_count: 1, sample_count: 1, dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm, usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST, view_formats: &[],
+        });
+        self.queue.write_texture(
+            wgpu::TexelCopyTextureInfo { texture: &texture, mip_level: 0, origin: wgpu::Origin3d::ZERO, aspect: wgpu::TextureAspect::All },
+            img.as_raw(), wgpu::TexelCopyBufferLayout { offset: 0, bytes_per_row: Some(sw * 4), rows_per_image: Some(sh) }, size,
+        );
+        let buffer = |label: &str, size: u64, usage: wgpu::BufferUsages| self.device.create_buffer(&wgpu::BufferDescriptor { label: Some(label), size, usage, mapped_at_creation: false });
+        let mid = buffer("ie_gpu_mid", mid_size, wgpu::BufferUsages::STORAGE);
+        let out = buffer("ie_gpu_out", out_size, wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC);
+        let staging = buffer("ie_gpu_staging", out_size, wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST);
+        let params = buffer("ie_gpu_params", 64, wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST);
+        let kernel = buffer("ie_gpu_kernel", weights.len() as u64 * 4, wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST);
+        let param_bytes: Vec<u8> = [sw, sh, dw, dh, radius, sx, sy, dx, dy, full_src.0, full_src.1, full_dst.0, full_dst.1, 0, 0, 0].iter().flat_map(|v| v.to_le_bytes()).collect();
+        self.queue.write_buffer(&params, 0, &param_bytes);
+        self.queue.write_buffer(&kernel, 0, &weights.iter().flat_map(|w| w.to_le_bytes()).collect::<Vec<u8>>());
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("ie_gpu"), layout: &self.layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(&view) },
+                wgpu::BindGroupEntry { binding: 1, resource: mid.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 2, resource: out.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 3, resource: params.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 4, resource: kernel.as_entire_binding() },
+            ],
+        });
+        let release = || { texture.destroy(); mid.destroy(); out.destroy(); staging.destroy(); params.destroy(); kernel.destroy(); };
+        if cancel.load(Ordering::Relaxed) { release(); return None; }
+
+        stage(GpuStage::Dispatch);
+        let (first, second) = match job { GpuJob::Blur(_) => (&self.blur_h, &self.blur_v), GpuJob::Resize(..) => (&self.resize_v, &self.resize_h) };
+        let groups = |w: u32, h: u32| (w.div_ceil(WORKGROUP), h.div_ceil(WORKGROUP));
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("ie_gpu") });
+        for (pipeline, (gx, gy)) in [(first, groups(sw, mid_h)), (second, groups(out_w, out_h))] {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some("ie_gpu"), timestamp_writes: None });
+            pass.set_pipeline(pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(gx, gy, 1);
+        }
+        encoder.copy_buffer_to_buffer(&out, 0, &staging, 0, out_size);
+        self.queue.submit(Some(encoder.finish()));
+        let validation = pollster::block_on(self.device.pop_error_scope());
+        let oom = pollster::block_on(self.device.pop_error_scope());
+        if validation.is_some() || oom.is_some() { release(); return None; }
+
+        stage(GpuStage::Readback);
+        let (tx, rx) = mpsc::channel();
+        staging.slice(..).map_async(wgpu::MapMode::Read, move |r| { let _ = tx.send(r.is_ok()); });
+        let mapped = loop {
+            if cancel.load(Ordering::Relaxed) { release(); let _ = self.device.poll(wgpu::PollType::Poll); return None; }
+            let _ = self.device.poll(wgpu::PollType::Wait { submission_index: None, timeout: Some(Duration::from_millis(50)) });
+            match rx.try_recv() { Ok(ok) => break ok, Err(mpsc::TryRecvError::Empty) => continue, Err(mpsc::TryRecvError::Disconnected) => break false }
+        };
+        let result = mapped.then(|| RgbaImage::from_raw(out_w, out_h, staging.slice(..).get_mapped_range().to_vec())).flatten();
+        if mapped { staging.unmap(); }
+        release();
+        match job {
+            GpuJob::Blur(_) => result.map(|r| image::imageops::crop_imm(&r, dx - sx, dy - sy, dw, dh).to_image()),
+            GpuJob::Resize(..) => result,
+        }
+    }
+}
+
+/// Runs `job` on the GPU; `stage` hears each step with the overall fraction done.
+pub(super) fn run(img: &RgbaImage, job: GpuJob, stage: &dyn Fn(GpuStage, f32), cancel: &AtomicBool) -> Option<RgbaImage> {
+    context()?.run(img, job, stage, cancel)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{DynamicImage, Rgba};
+
+    fn test_image() -> RgbaImage {
+        RgbaImage::from_fn(97, 61, |x, y| Rgba([(x * 7 % 256) as u8, (y * 13 % 256) as u8, ((x ^ y) * 5 % 256) as u8, if (x / 8 + y / 8) % 3 == 0 { 128 } else { 255 }]))
+    }
+
+    fn max_diff(a: &RgbaImage, b: &RgbaImage) -> u8 {
+        assert_eq!(a.dimensions(), b.dimensions());
+        a.as_raw().iter().zip(b.as_raw()).map(|(x, y)| x.abs_diff(*y)).max().unwrap_or(0)
+    }
+
+    /// The shader passes in plain Rust, run over each tile the same way `run` stitches them.
+    fn emulate(img: &RgbaImage, job: GpuJob, tile: u32) -> RgbaImage {
+        let (sw, sh) = img.dimensions();
+        let (dw, dh) = match job { GpuJob::Blur(_) => (sw, sh), GpuJob::Resize(w, h) => (w, h) };
+        let weights = match job { GpuJob::Blur(sigma) => gaussian_kernel(sigma), GpuJob::Resize(..) => vec![0.0] };
+        let r = (weights.len() / 2) as i64;
+        let lanczos3 = |x: f32| {
+            let sinc = |t: f32| if t == 0.0 { 1.0 } else { (t * std::f32::consts::PI).sin() / (t * std::f32::consts::PI) };
+            if x.abs() < 3.0 { sinc(x) * sinc(x / 3.0) } else { 0.0 }
+        };
+        let pack = |v: [f32; 4]| Rgba(v.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8));
+        let window = |i: u32, full_src: u32, full_dst: u32| {
+            let ratio = full_src as f32 / full_dst as f32;
+            let sratio = ratio.max(1.0);
+            let center = (i as f32 + 0.5) * ratio;
+            let left = ((center - 3.0 * sratio).floor() as i64).clamp(0, full_src as i64 - 1);
+            let right = ((center + 3.0 * sratio).ceil() as i64).clamp(left + 1, full_src as i64);
+            (left..right).map(move |i| (i, lanczos3((i as f32 - (center - 0.5)) / sratio)))
+        };
+        let mut out = RgbaImage::new(dw, dh);
+        for t in plan_tiles((sw, sh), job, r as u32, tile) {
+            let [sx, sy, tw, th] = t.src;
+            let [dx, dy, ow, oh] = t.dst;
+            let px = |x: i64, y: i64| img.get_pixel(sx + x as u32, sy + y as u32).0.map(|c| c as f32 / 255.0);
+            let acc = |sum: &mut [f32; 4], v: [f32; 4], w: f32| sum.iter_mut().zip(v).for_each(|(s, c)| *s += c * w);
+            match job {
+                GpuJob::Blur(_) => {
+                    let mid: Vec<[f32; 4]> = (0..th as i64).flat_map(|y| (0..tw as i64).map(move |x| (x, y))).map(|(x, y)| {
+                        let mut sum = [0.0; 4];
+                        for k in -r..=r { acc(&mut sum, px((x + k).clamp(0, tw as i64 - 1), y), weights[(k + r) as usize]); }
+                        sum
+                    }).collect();
+                    for y in dy..dy + oh {
+                        for x in dx..dx + ow {
+                            let (lx, ly) = ((x - sx) as i64, (y - sy) as i64);
+                            let mut sum = [0.0; 4];
+                            for k in -r..=r { acc(&mut sum, mid[((ly + k).clamp(0, th as i64 - 1) * tw as i64 + lx) as usize], weights[(k + r) as usize]); }
+                            out.put_pixel(x, y, pack(sum));
+                        }
+                    }
+                }
+                GpuJob::Resize(..) => {
+                    let mid: Vec<[f32; 4]> = (dy..dy + oh).flat_map(|y| (0..tw as i64).map(move |x| (x, y))).map(|(x, y)| {
+                        let (mut sum, mut total) = ([0.0; 4], 0.0);
+                        for (i, w) in window(y, sh, dh) { acc(&mut sum, px(x, i - sy as i64), w); total += w; }
+                        sum.map(|c| c / total)
+                    }).collect();
+                    for y in 0..oh {
+                        for x in dx..dx + ow {
+                            let (mut sum, mut total) = ([0.0; 4], 0.0);
+                            for (i, w) in window(x, sw, dw) { acc(&mut sum, mid[(y * tw) as usize + (i - sx as i64) as usize], w); total += w; }
+                            out.put_pixel(x, dy + y, pack(sum.map(|c| c / total)));
+                        }
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn tiled_shader_passes_match_cpu_filters() {
+        let img = test_image();
+        let cpu_blur = DynamicImage::ImageRgba8(img.clone()).blur(3.0).to_rgba8();
+        for tile in [u32::MAX, 32, 7] {
+            let blur = emulate(&img, GpuJob::Blur(3.0), tile);
+            assert!(max_diff(&cpu_blur, &blur) <= 2, "blur in {} tiles differs by {}", tile, max_diff(&cpu_blur, &blur));
+            for (w, h) in [(40, 25), (180, 130)] {
+                let cpu = DynamicImage::ImageRgba8(img.clone()).resize_exact(w, h, image::imageops::FilterType::Lanczos3).to_rgba8();
+                let resized = emulate(&img, GpuJob::Resize(w, h), tile);
+                assert!(max_diff(&cpu, &resized) <= 2, "resize {}x{} in {} tiles differs by {}", w, h, tile, max_diff(&cpu, &resized));
+            }
+        }
+    }
+
+    #[test]
+    fn tiles_cover_the_output_once() {
+        for job in [GpuJob::Blur(2.0), GpuJob::Resize(150, 40)] {
+            let tiles = plan_tiles((97, 61), job, 3, 32);
+            let (dw, dh) = match job { GpuJob::Blur(_) => (97, 61), GpuJob::Resize(w, h) => (w, h) };
+            assert_eq!(tiles.iter().map(|t| t.dst[2] * t.dst[3]).sum::<u32>(), dw * dh);
+            assert!(tiles.iter().all(|t| t.src[0] + t.src[2] <= 97 && t.src[1] + t.src[3] <= 61));
+        }
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter"]
+    fn gpu_filters_match_cpu() {
+        let ctx = context().expect("a GPU adapter");
+        let img = test_image();
+        let never = AtomicBool::new(false);
+        let cpu_blur = DynamicImage::ImageRgba8(img.clone()).blur(3.0).to_rgba8();
+        let radius = (gaussian_kernel(3.0).len() / 2) as u32;
+        for tile in [u32::MAX, 32] {
+            let gpu_blur = ctx.run_tiles(&img, GpuJob::Blur(3.0), &plan_tiles(img.dimensions(), GpuJob::Blur(3.0), radius, tile), &|_, _| {}, &never).expect("gpu blur");
+            assert!(max_diff(&cpu_blur, &gpu_blur) <= 2, "blur in {} tiles differs by {}", tile, max_diff(&cpu_blur, &gpu_blur));
+            for (w, h) in [(40, 25), (180, 130)] {
+                let cpu = DynamicImage::ImageRgba8(img.clone()).resize_exact(w, h, image::imageops::FilterType::Lanczos3).to_rgba8();
+                let gpu = ctx.run_tiles(&img, GpuJob::Resize(w, h), &plan_tiles(img.dimensions(), GpuJob::Resize(w, h), 0, tile), &|_, _| {}, &never).expect("gpu resize");
+                assert!(max_diff(&cpu, &gpu) <= 2, "resize {}x{} in {} tiles differs by {}", w, h, tile, max_diff(&cpu, &gpu));
+            }
+        }
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter"]
+    fn cancelled_job_returns_nothing() {
+        let ctx = context().expect("a GPU adapter");
+        let cancel = AtomicBool::new(true);
+        assert!(ctx.run(&test_image(), GpuJob::Blur(2.0), &|_, _| {}, &cancel).is_none());
+    }
+
+    #[test]
+    fn kernel_is_normalized_and_odd() {
+        for sigma in [0.5, 1.0, 3.0, 20.0] {
+            let k = gaussian_kernel(sigma);
+            assert_eq!(k.len() % 2, 1);
+            assert!((k.iter().sum::<f32>() - 1.0).abs() < 1e-4);
+        }
+    }
+}
@@ -1,27 +1,132 @@
 use eframe::egui;
-use std::fs;
 use std::path::PathBuf;
 use serde::{Serialize, Deserialize};
-use super::ie_main::{THandle, BlendMode, HANDLE_HIT, HANDLE_VIS};
+use crate::modules::helpers::config_file;
+pub(super) use crate::modules::helpers::hsv_picker::{rgb_to_hsv_f32, hsv_to_rgb_f32};
+use super::ie_main::{THandle, BlendMode, BrushShape, FillMode, HANDLE_HIT, HANDLE_VIS};
 
-pub(super) fn config_path(filename: &str) -> PathBuf {
-    let mut p = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
-    p.push("universal_editor");
-    p.push(filename);
-    p
+pub(super) struct FillMask { pub x0: u32, pub y0: u32, pub w: u32, pub h: u32, bits: Vec<bool> }
+
+impl FillMask {
+    /// Sets every masked pixel to `fill(x, y)`, in image coordinates.
+    pub(super) fn paint(&self, buf: &mut image::RgbaImage, fill: impl Fn(u32, u32) -> [u8; 4]) {
+        for (i, _) in self.bits.iter().enumerate().filter(|(_, b)| **b) {
+            let (x, y) = (self.x0 + i as u32 % self.w, self.y0 + i as u32 / self.w);
+            buf.put_pixel(x, y, image::Rgba(fill(x, y)));
+        }
+    }
+
+    pub(super) fn contains(&self, x: u32, y: u32) -> bool { x < self.w && y < self.h && self.bits[(y * self.w + x) as usize] }
+
+    /// Blends the unfilled pixels touching the mask toward `fill`: a neighbor within `tolerance * 2` of `target` takes
+    /// more of the fill the closer it is, so anti-aliased edges lose their halo of the old color. Straight lerp on
+    /// unpremultiplied RGBA; fully transparent pixels are left alone unless the target was transparent too.
+    pub(super) fn antialias_edges(&self, buf: &mut image::RgbaImage, target: [u8; 4], fill: impl Fn(u32, u32) -> [u8; 4], tolerance: u32) {
+        if tolerance == 0 { return; }
+        let (width, height) = buf.dimensions();
+        let inside = |x: i64, y: i64| x >= 0 && y >= 0 && self.contains(x as u32, y as u32);
+        let (x0, y0) = (self.x0.saturating_sub(1), self.y0.saturating_sub(1));
+        let (x1, y1) = ((self.x0 + self.w).min(width - 1), (self.y0 + self.h).min(height - 1));
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                let (lx, ly) = (x as i64 - self.x0 as i64, y as i64 - self.y0 as i64);
+                if inside(lx, ly) || !(-1..=1).any(|dy| (-1..=1).any(|dx| inside(lx + dx, ly + dy))) { continue; }
+                let cur = buf.get_pixel(x, y).0;
+                if cur[3] == 0 && target[3] != 0 { continue; }
+                let diff = (0..4).map(|i| cur[i].abs_diff(target[i]) as u32).max().unwrap_or(0);
+                if diff > tolerance * 2 { continue; }
+                let t = ((tolerance * 2 - diff) as f32 / tolerance as f32).min(1.0);
+                let fill = fill(x, y);
+                let from = if cur[3] == 0 { [fill[0], fill[1], fill[2], 0] } else { cur };
+                let mixed = std::array::from_fn(|i| (from[i] as f32 + (fill[i] as f32 - from[i] as f32) * t).round() as u8);
+                buf.put_pixel(x, y, image::Rgba(mixed));
+            }
+        }
+    }
+
+    pub(super) fn to_color_image(&self, tint: egui::Color32) -> egui::ColorImage {
+        egui::ColorImage::new([self.w as usize, self.h as usize], self.bits.iter().map(|&b| if b { tint } else { egui::Color32::TRANSPARENT }).collect())
+    }
+}
+
+/// Where `p` falls between `start` (0) and `end` (1): projected onto the axis for a linear gradient, by distance
+/// from `start` for a radial one. Solid fills and a zero-length axis sit at 0.
+pub(super) fn gradient_t(mode: FillMode, start: (f32, f32), end: (f32, f32), p: (f32, f32)) -> f32 {
+    let (ax, ay) = (end.0 - start.0, end.1 - start.1);
+    let len_sq = ax * ax + ay * ay;
+    if len_sq < 1e-6 { return 0.0; }
+    let (dx, dy) = (p.0 - start.0, p.1 - start.1);
+    let t = match mode {
+        FillMode::Solid => 0.0,
+        FillMode::LinearGradient => (dx * ax + dy * ay) / len_sq,
+        FillMode::RadialGradient => ((dx * dx + dy * dy) / len_sq).sqrt(),
+    };
+    t.clamp(0.0, 1.0)
+}
+
+/// `from` blended toward `to` at `p`. Straight lerp on unpremultiplied RGBA, like the fill's edge antialiasing.
+pub(super) fn gradient_color(mode: FillMode, start: (f32, f32), end: (f32, f32), from: [u8; 4], to: [u8; 4], p: (f32, f32)) -> [u8; 4] {
+    let t = gradient_t(mode, start, end, p);
+    std::array::from_fn(|i| (from[i] as f32 + (to[i] as f32 - from[i] as f32) * t).round() as u8)
 }
 
-pub(super) fn load_persisted<T: for<'de> Deserialize<'de> + Default>(filename: &str) -> T {
-    fs::read_to_string(config_path(filename))
-        .ok()
-        .and_then(|s| serde_json::from_str(&s).ok())
-        .unwrap_or_default()
+/// Every channel, alpha included, may differ by at most `tolerance`; a summed difference would let a large
+/// shift in one channel through.
+pub(super) fn within_tolerance(cur: [u8; 4], target: [u8; 4], tolerance: u32) -> bool {
+    (0..4).all(|i| cur[i].abs_diff(target[i]) as u32 <= tolerance)
+}
+
+/// Pixels a fill starting at `(sx, sy)` would cover: the 4-connected region, or with `contiguous` off every
+/// matching pixel in the image.
+pub(super) fn fill_mask(buf: &image::RgbaImage, sx: u32, sy: u32, tolerance: u32, contiguous: bool, deadline: Option<std::time::Instant>, cancel: &std::sync::atomic::AtomicBool) -> Option<FillMask> {
+    let (width, height) = buf.dimensions();
+    if sx >= width || sy >= height { return None; }
+    let target = buf.get_pixel(sx, sy).0;
+    let stop = || cancel.load(std::sync::atomic::Ordering::Relaxed) || deadline.is_some_and(|d| std::time::Instant::now() >= d);
+    if !contiguous {
+        let (mut x0, mut y0, mut x1, mut y1) = (sx, sy, sx, sy);
+        let mut all = vec![false; (width * height) as usize];
+        for y in 0..height {
+            if y.is_multiple_of(64) && stop() { return None; }
+            for x in 0..width {
+                if !within_tolerance(buf.get_pixel(x, y).0, target, tolerance) { continue; }
+                all[(y * width + x) as usize] = true;
+                x0 = x0.min(x); y0 = y0.min(y); x1 = x1.max(x); y1 = y1.max(y);
+            }
+        }
+        let bits = (y0..=y1).flat_map(|y| (x0..=x1).map(move |x| (y * width + x) as usize)).map(|i| all[i]).collect();
+        return Some(FillMask { x0, y0, w: x1 - x0 + 1, h: y1 - y0 + 1, bits });
+    }
+    let mut state = vec![0u8; (width * height) as usize];
+    let mut stack = vec![(sx, sy)];
+    let (mut x0, mut y0, mut x1, mut y1) = (sx, sy, sx, sy);
+    let mut steps = 0u32;
+    while let Some((x, y)) = stack.pop() {
+        steps = steps.wrapping_add(1);
+        if steps & 0xFFF == 0 && stop() { return None; }
+        let idx = (y * width + x) as usize;
+        if state[idx] != 0 { continue; }
+        let cur = buf.get_pixel(x, y).0;
+        if !within_tolerance(cur, target, tolerance) { state[idx] = 1; continue; }
+        state[idx] = 2;
+        x0 = x0.min(x); y0 = y0.min(y); x1 = x1.max(x); y1 = y1.max(y);
+        if x > 0 { stack.push((x-1, y)); }
+        if x+1 < width { stack.push((x+1, y)); }
+        if y > 0 { stack.push((x, y-1)); }
+        if y+1 < height { stack.push((x, y+1)); }
+    }
+    let bits = (y0..=y1).flat_map(|y| (x0..=x1).map(move |x| (y * width + x) as usize)).map(|i| state[i] == 2).collect();
+    Some(FillMask { x0, y0, w: x1 - x0 + 1, h: y1 - y0 + 1, bits })
+}
+
+pub(super) fn config_path(filename: &str) -> PathBuf { config_file::config_path(filename) }
+
+pub(super) fn load_persisted<T: for<'de> Deserialize<'de> + Default>(filename: &str, what: &str) -> T {
+    config_file::load_json(&config_path(filename), what)
 }
 
 pub(super) fn save_persisted<T: Serialize>(filename: &str, val: &T) {
-    let path = config_path(filename);
-    if let Some(p) = path.parent() { let _ = fs::create_dir_all(p); }
-    if let Ok(j) = serde_json::to_string(val) { let _ = fs::write(path, j); }
+    if let Ok(j) = serde_json::to_string(val) { let _ = config_file::write_atomic(&config_path(filename), j.as_bytes()); }
 }
 
 #[inline]
@@ -35,13 +140,28 @@ pub(super) fn blend_pixels_u8(dst: [u8; 4], src: [u8; 4], opacity: f32, mode: Bl
     let dr = [dst[0] as f32/255.0, dst[1] as f32/255.0, dst[2] as f32/255.0];
     let out = std::array::from_fn::<f32, 3, _>(|i| (mode.blend_channel(dr[i], sr[i]) * sa + dr[i] * da * (1.0 - sa)) / out_a);
     [
-        (out[0]*255.0).clamp(0.0,255.0) as u8,
-        (out[1]*255.0).clamp(0.0,255.0) as u8,
-        (out[2]*255.0).clamp(0.0,255.0) as u8,
-        (out_a*255.0).clamp(0.0,255.0) as u8,
+        (out[0]*255.0).round().clamp(0.0,255.0) as u8,
+        (out[1]*255.0).round().clamp(0.0,255.0) as u8,
+        (out[2]*255.0).round().clamp(0.0,255.0) as u8,
+        (out_a*255.0).round().clamp(0.0,255.0) as u8,
     ]
 }
 
+#[inline]
+pub(super) fn composite_over_u8(dst: [u8; 4], src: [u8; 3], alpha: u8) -> [u8; 4] {
+    if alpha == 0 { return dst; }
+    let sa = alpha as u32 * 255;
+    let da = dst[3] as u32 * (255 - alpha as u32);
+    let out = sa + da;
+    let ch = |s: u8, d: u8| ((s as u32 * sa + d as u32 * da + out / 2) / out) as u8;
+    [ch(src[0], dst[0]), ch(src[1], dst[1]), ch(src[2], dst[2]), ((out + 127) / 255) as u8]
+}
+
+#[inline]
+pub(super) fn stamp_u8(dst: [u8; 4], src: [u8; 3], alpha: u8, mode: BlendMode) -> [u8; 4] {
+    if mode == BlendMode::Normal { composite_over_u8(dst, src, alpha) } else { blend_pixels_u8(dst, [src[0], src[1], src[2], alpha], 1.0, mode) }
+}
+
 #[inline]
 pub(super) fn srgb_to_linear(c: u8) -> f32 { let c = c as f32 / 255.0; if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) } }
 
@@ -70,31 +190,30 @@ pub(super) fn blend_pixels_linear(dst: [u8; 4], src: [u8; 4], opacity: f32, mode
     ]
 }
 
-pub(super) fn rgb_to_hsv_f32(r: f32, g: f32, b: f32) -> (f32, f32, f32) {
-    let max = r.max(g).max(b);
-    let min = r.min(g).min(b);
-    let delta = max - min;
-    let s = if max == 0.0 { 0.0 } else { delta / max };
-    let h = if delta == 0.0 { 0.0 }
-        else if max == r { 60.0 * (((g - b) / delta) % 6.0) }
-        else if max == g { 60.0 * ((b - r) / delta + 2.0) }
-        else { 60.0 * ((r - g) / delta + 4.0) };
-    (if h < 0.0 { h + 360.0 } else { h }, s, max)
-}
-
-pub(super) fn hsv_to_rgb_f32(h: f32, s: f32, v: f32) -> (f32, f32, f32) {
-    let c = v * s;
-    let x = c * (1.0 - ((h / 60.0) % 2.0 - 1.0).abs());
-    let m = v - c;
-    let (r, g, b) = match h as u32 {
-        0..=59 => (c, x, 0.0),
-        60..=119 => (x, c, 0.0),
-        120..=179 => (0.0, c, x),
-        180..=239 => (0.0, x, c),
-        240..=299 => (x, 0.0, c),
-        _ => (c, 0.0, x),
+pub(super) fn hsv_to_hsl(s: f32, v: f32) -> (f32, f32) {
+    let l = v * (1.0 - s / 2.0);
+    let m = l.min(1.0 - l);
+    (if m <= 0.0 { 0.0 } else { (v - l) / m }, l)
+}
+
+pub(super) fn hsl_to_hsv(s: f32, l: f32) -> (f32, f32) {
+    let v = l + s * l.min(1.0 - l);
+    (if v <= 0.0 { 0.0 } else { 2.0 * (1.0 - l / v) }, v)
+}
+
+pub(super) fn color_harmonies(c: egui::Color32) -> [(&'static str, Vec<egui::Color32>); 4] {
+    let (h, s, v) = rgb_to_hsv_f32(c.r() as f32 / 255.0, c.g() as f32 / 255.0, c.b() as f32 / 255.0);
+    let at = |d: f32| {
+        let (r, g, b) = hsv_to_rgb_f32((h + d).rem_euclid(360.0), s, v);
+        egui::Color32::from_rgb((r * 255.0).round() as u8, (g * 255.0).round() as u8, (b * 255.0).round() as u8)
     };
-    (r + m, g + m, b + m)
+    [("Complementary", vec![at(180.0)]), ("Analogous", vec![at(-30.0), at(30.0)]), ("Triadic", vec![at(120.0), at(240.0)]), ("Split Comp.", vec![at(150.0), at(210.0)])]
+}
+
+pub(super) fn contrast_ratio(a: egui::Color32, b: egui::Color32) -> f32 {
+    let lum = |c: egui::Color32| 0.2126 * srgb_to_linear(c.r()) + 0.7152 * srgb_to_linear(c.g()) + 0.0722 * srgb_to_linear(c.b());
+    let (x, y) = (lum(a), lum(b));
+    (x.max(y) + 0.05) / (x.min(y) + 0.05)
 }
 
 pub(super) fn rgb_to_hsv(r: u8, g: u8, b: u8) -> (f32, f32, f32) {
@@ -166,3 +285,273 @@ pub(super) fn brush_rand(seed: u64) -> f32 {
 
 #[inline(always)]
 pub(super) fn retouch_lerp_u8(a: u8, b: u8, t: f32) -> u8 { (a as f32 + (b as f32 - a as f32) * t).clamp(0.0, 255.0) as u8 }
+
+pub(super) fn snap_grapheme_boundary(s: &str, i: usize) -> usize {
+    use unicode_segmentation::UnicodeSegmentation;
+    if i >= s.len() { return s.len(); }
+    s.grapheme_indices(true).map(|(j, _)| j).take_while(|&j| j <= i).last().unwrap_or(0)
+}
+
+pub(super) fn word_range_at(s: &str, i: usize) -> (usize, usize) {
+    use unicode_segmentation::UnicodeSegmentation;
+    s.split_word_bound_indices().find(|(j, w)| i >= *j && i < j + w.len()).or_else(|| s.split_word_bound_indices().next_back())
+        .map(|(j, w)| (j, j + w.len())).unwrap_or((0, 0))
+}
+
+/// How far a tab at `x` advances to reach the next stop, stops being `tab_width` spaces apart.
+pub(super) fn tab_advance(x: f32, space_w: f32, tab_width: u8) -> f32 {
+    let tab_w = space_w * tab_width.max(1) as f32;
+    if tab_w <= 0.0 { 0.0 } else { ((x / tab_w).floor() + 1.0) * tab_w - x }
+}
+
+/// Lays text out in one pass with the stamp's tab stops, each tab drawn as a space pushed out to its stop.
+/// With tabs the rows are broken here rather than by egui, since where a row starts decides every stop on it.
+pub(super) fn layout_with_tabs(painter: &egui::Painter, text: &str, format: &egui::TextFormat, wrap_w: f32, halign: egui::Align, tab_width: u8) -> std::sync::Arc<egui::Galley> {
+    let mut job = egui::text::LayoutJob { halign, ..Default::default() };
+    if !text.contains('\t') {
+        job.wrap.max_width = wrap_w;
+        job.append(text, 0.0, format.clone());
+        return painter.layout_job(job);
+    }
+    let font_id = &format.font_id;
+    let (space_w, widths) = painter.ctx().fonts_mut(|f| (f.glyph_width(font_id, ' '), text.chars().map(|c| f.glyph_width(font_id, c)).collect::<Vec<f32>>()));
+    let (mut section, mut lead, mut x) = (String::new(), 0.0f32, 0.0f32);
+    let mut widths = widths.into_iter();
+    for (pi, paragraph) in text.split('\n').enumerate() {
+        if pi > 0 { section.push('\n'); widths.next(); x = 0.0; }
+        for word in paragraph.split_inclusive([' ', '\t']) {
+            let ws: Vec<f32> = widths.by_ref().take(word.chars().count()).collect();
+            let word_w: f32 = word.chars().zip(&ws).filter(|(c, _)| !c.is_whitespace()).map(|(_, w)| w).sum();
+            if x > 0.0 && x + word_w > wrap_w { section.push('\n'); x = 0.0; }
+            for (c, w) in word.chars().zip(ws) {
+                if c == '\t' {
+                    job.append(&std::mem::take(&mut section), lead, format.clone());
+                    lead = tab_advance(x, space_w, tab_width) - space_w;
+                    section.push(' ');
+                    x += lead + space_w;
+                    continue;
+                }
+                if x > 0.0 && x + w > wrap_w && c != ' ' { section.push('\n'); x = 0.0; }
+                section.push(c);
+                x += w;
+            }
+        }
+    }
+    job.append(&section, lead, format.clone());
+    painter.layout_job(job)
+}
+
+/// Maps a char index in a `layout_with_tabs` galley back to the text it laid out, skipping the row breaks it added.
+pub(super) fn layout_char_to_text(galley_text: &str, text: &str, index: usize) -> usize {
+    let mut chars = text.chars().peekable();
+    let mut out = 0;
+    for c in galley_text.chars().take(index) {
+        if c == '\n' && chars.peek() != Some(&'\n') { continue; }
+        chars.next();
+        out += 1;
+    }
+    out
+}
+
+pub(super) const CARET_BLINK_SECS: f64 = 0.5;
+
+pub(super) fn caret_blink(elapsed: f64) -> (bool, f64) {
+    let elapsed = elapsed.max(0.0);
+    let phase = (elapsed / CARET_BLINK_SECS) as u64;
+    (phase.is_multiple_of(2), (phase + 1) as f64 * CARET_BLINK_SECS - elapsed)
+}
+
+pub(super) const CANVAS_RESIZE_SLACK: f32 = 4.0;
+
+/// Space pans only when it goes down with the primary button up; pressed mid-drag it waits for the release,
+/// so a stroke in progress carries on instead of being cut short.
+pub(super) fn space_pan_next(panning: bool, space_held: bool, primary_down: bool) -> bool {
+    space_held && (panning || !primary_down)
+}
+
+/// egui-winit swallows the press of a paste chord and passes on only the release, so a V release with no press seen
+/// before it is a paste, however soon the modifier came up. `v_down` carries the press across frames.
+pub(super) fn paste_chord_released(v_down: &mut bool, events: &[egui::Event]) -> bool {
+    let mut paste = false;
+    for e in events {
+        match e {
+            egui::Event::Key { key: egui::Key::V, pressed: true, .. } => *v_down = true,
+            egui::Event::Key { key: egui::Key::V, pressed: false, .. } => { paste |= !*v_down; *v_down = false; }
+            _ => {}
+        }
+    }
+    paste
+}
+
+/// Whether the canvas changed size by more than layout jitter.
+pub(super) fn canvas_resized(old: egui::Vec2, new: egui::Vec2) -> bool {
+    old.min_elem() > 0.0 && (new - old).abs().max_elem() >= CANVAS_RESIZE_SLACK
+}
+
+pub(super) fn char_is_rtl(c: char) -> Option<bool> {
+    use unicode_bidi::BidiClass;
+    match unicode_bidi::bidi_class(c) { BidiClass::R | BidiClass::AL => Some(true), BidiClass::L => Some(false), _ => None }
+}
+
+pub(super) fn has_rtl(s: &str) -> bool { s.chars().any(|c| char_is_rtl(c) == Some(true)) }
+
+pub(super) fn rtl_at(s: &str, i: usize, rtl_base: bool) -> bool {
+    let i = i.min(s.len());
+    s[..i].chars().next_back().and_then(char_is_rtl).or_else(|| s[i..].chars().next().and_then(char_is_rtl)).unwrap_or(rtl_base)
+}
+
+pub(super) fn bidi_visual_runs(line: &str, rtl_base: bool) -> Vec<(std::ops::Range<usize>, bool)> {
+    use unicode_bidi::{BidiInfo, Level};
+    let info = BidiInfo::new(line, Some(if rtl_base { Level::rtl() } else { Level::ltr() }));
+    let Some(para) = info.paragraphs.first() else { return Vec::new() };
+    let (levels, runs) = info.visual_runs(para, para.range.clone());
+    runs.into_iter().map(|r| { let rtl = levels[r.start].is_rtl(); (r, rtl) }).collect()
+}
+
+pub(super) fn textured_disc(tex: egui::TextureId, center: egui::Pos2, radius: f32, uv: egui::Rect) -> egui::Mesh {
+    let mut mesh = egui::Mesh::with_texture(tex);
+    let to_uv = |p: egui::Pos2| uv.lerp_inside(egui::vec2((p.x - center.x) / (2.0 * radius) + 0.5, (p.y - center.y) / (2.0 * radius) + 0.5));
+    mesh.colored_vertex(center, egui::Color32::WHITE);
+    mesh.vertices[0].uv = to_uv(center);
+    for i in 0..=64 {
+        let t = i as f32 / 64.0 * std::f32::consts::TAU;
+        let p = center + egui::vec2(t.cos(), t.sin()) * radius;
+        mesh.vertices.push(egui::epaint::Vertex { pos: p, uv: to_uv(p), color: egui::Color32::WHITE });
+        if i > 0 { mesh.add_triangle(0, i, i + 1); }
+    }
+    mesh
+}
+
+pub(super) fn brush_footprint(shape: BrushShape, radius: f32, aspect: f32, angle: f32) -> Vec<egui::Vec2> {
+    let (ca, sa) = (angle.cos(), angle.sin());
+    let rot = |lx: f32, ly: f32| egui::vec2(lx * ca - ly * sa, lx * sa + ly * ca);
+    let ellipse = |ry: f32| (0..48).map(|i| { let t = i as f32 / 48.0 * std::f32::consts::TAU; rot(t.cos() * radius, t.sin() * ry) }).collect();
+    match shape {
+        BrushShape::Circle => ellipse(radius),
+        BrushShape::CalligraphyFlat => ellipse(radius * aspect),
+        BrushShape::Square => [(1.0, 1.0), (-1.0, 1.0), (-1.0, -1.0), (1.0, -1.0)].iter().map(|&(x, y)| rot(x * radius, y * radius)).collect(),
+        BrushShape::Diamond => [(1.0, 0.0), (0.0, 1.0), (-1.0, 0.0), (0.0, -1.0)].iter().map(|&(x, y)| rot(x * radius, y * radius)).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tabs_land_on_the_stamp_stops_measured_from_the_start_of_their_row() {
+        let ctx = egui::Context::default();
+        let _ = ctx.run(egui::RawInput::default(), |ctx| {
+            let painter = ctx.layer_painter(egui::LayerId::background());
+            let format = egui::TextFormat { font_id: egui::FontId::monospace(14.0), ..Default::default() };
+            let w = ctx.fonts_mut(|f| f.glyph_width(&format.font_id, ' '));
+            let near = |a: f32, b: f32| (a - b).abs() < 1.0;
+            let galley = layout_with_tabs(&painter, "a\tbb\tc", &format, f32::INFINITY, egui::Align::LEFT, 4);
+            let xs: Vec<f32> = galley.rows[0].glyphs.iter().map(|g| g.pos.x).collect();
+            assert_eq!(xs.len(), 6, "a tab is one glyph, so carets still map to chars");
+            assert!(near(xs[2], 4.0 * w) && near(xs[5], 8.0 * w), "{xs:?}");
+            let galley = layout_with_tabs(&painter, "aaaaaa bbbb\tc", &format, 10.0 * w, egui::Align::LEFT, 4);
+            let rows: Vec<usize> = galley.rows.iter().map(|r| r.glyphs.len()).collect();
+            assert_eq!(rows, [7, 6]);
+            assert!(near(galley.rows[1].glyphs[5].pos.x, 8.0 * w), "{:?}", galley.rows[1].glyphs[5].pos);
+            assert_eq!(layout_char_to_text(&galley.job.text, "aaaaaa bbbb\tc", 9), 8, "the added break is not a char of the text");
+            assert!(near(tab_advance(5.0 * w, w, 4), 3.0 * w));
+        });
+    }
+
+    fn assert_close(got: [u8; 4], want: [u8; 4]) {
+        assert!(got.iter().zip(want).all(|(g, w)| (*g as i16 - w as i16).abs() <= 1), "got {:?}, want {:?}", got, want);
+    }
+
+    #[test]
+    fn multiply_matches_hand_computed_values() {
+        assert_close(stamp_u8([200, 100, 50, 255], [100, 200, 250], 255, BlendMode::Multiply), [78, 78, 49, 255]);
+        assert_close(stamp_u8([200, 100, 50, 128], [100, 200, 250], 128, BlendMode::Multiply), [119, 86, 49, 192]);
+        assert_close(stamp_u8([180, 60, 90, 64], [40, 220, 160], 192, BlendMode::Multiply), [40, 52, 59, 208]);
+        assert_close(stamp_u8([0, 0, 0, 0], [100, 200, 250], 128, BlendMode::Multiply), [0, 0, 0, 128]);
+    }
+
+    #[test]
+    fn screen_matches_hand_computed_values() {
+        assert_close(stamp_u8([200, 100, 50, 255], [100, 200, 250], 255, BlendMode::Screen), [222, 222, 251, 255]);
+        assert_close(stamp_u8([200, 100, 50, 128], [100, 200, 250], 128, BlendMode::Screen), [214, 181, 184, 192]);
+        assert_close(stamp_u8([180, 60, 90, 64], [40, 220, 160], 192, BlendMode::Screen), [191, 215, 186, 208]);
+        assert_close(stamp_u8([0, 0, 0, 0], [100, 200, 250], 128, BlendMode::Screen), [100, 200, 250, 128]);
+    }
+
+    #[test]
+    fn blended_stamps_use_the_layer_compositing_formula() {
+        for mode in [BlendMode::Multiply, BlendMode::Screen, BlendMode::Overlay, BlendMode::Difference] {
+            assert_eq!(stamp_u8([180, 60, 90, 64], [40, 220, 160], 192, mode), blend_pixels_u8([180, 60, 90, 64], [40, 220, 160, 192], 1.0, mode));
+        }
+    }
+
+    #[test]
+    fn hsl_round_trips_through_hsv() {
+        for &(s, v) in &[(0.0, 0.0), (0.0, 1.0), (1.0, 1.0), (0.5, 0.8), (0.25, 0.3), (1.0, 0.5)] {
+            let (hs, l) = hsv_to_hsl(s, v);
+            let (s2, v2) = hsl_to_hsv(hs, l);
+            assert!((v - v2).abs() < 1e-5 && (v == 0.0 || (s - s2).abs() < 1e-5), "{} {} -> {} {} -> {} {}", s, v, hs, l, s2, v2);
+        }
+        assert_eq!(hsv_to_hsl(1.0, 1.0), (1.0, 0.5));
+        assert_eq!(hsl_to_hsv(1.0, 0.5), (1.0, 1.0));
+    }
+
+    #[test]
+    fn caret_blink_toggles_on_wall_clock_and_reports_next_transition() {
+        assert_eq!(caret_blink(0.0), (true, 0.5));
+        assert_eq!(caret_blink(0.25), (true, 0.25));
+        assert_eq!(caret_blink(0.75), (false, 0.25));
+        assert_eq!(caret_blink(1.0), (true, 0.5));
+        assert_eq!(caret_blink(-3.0), (true, 0.5));
+    }
+
+    #[test]
+    fn space_pressed_mid_stroke_waits_for_the_pointer_to_come_up() {
+        let frames = [(true, false), (true, true), (false, true), (true, true), (true, true), (true, false), (true, true), (false, true)];
+        let mut panning = false;
+        let seen: Vec<bool> = frames.iter().map(|&(space, primary)| { panning = space_pan_next(panning, space, primary); panning }).collect();
+        assert_eq!(seen, [true, true, false, false, false, true, true, false]);
+    }
+
+    #[test]
+    fn a_paste_is_the_release_of_a_swallowed_v_press_whenever_ctrl_came_up() {
+        let key = |pressed, modifiers| egui::Event::Key { key: egui::Key::V, physical_key: None, pressed, repeat: false, modifiers };
+        let mut v_down = false;
+        assert!(paste_chord_released(&mut v_down, &[key(false, egui::Modifiers::COMMAND)]));
+        assert!(paste_chord_released(&mut v_down, &[key(false, egui::Modifiers::NONE)]), "Ctrl let go before V");
+        assert!(!paste_chord_released(&mut v_down, &[key(true, egui::Modifiers::NONE)]));
+        assert!(!paste_chord_released(&mut v_down, &[key(false, egui::Modifiers::NONE)]), "a plain V press is seen, so its release is no paste");
+        assert!(!paste_chord_released(&mut v_down, &[egui::Event::Paste("text".into())]));
+    }
+
+    #[test]
+    fn canvas_resizes_ignore_layout_jitter() {
+        assert!(!canvas_resized(egui::vec2(800.0, 600.0), egui::vec2(802.0, 599.0)));
+        assert!(canvas_resized(egui::vec2(800.0, 600.0), egui::vec2(400.0, 900.0)));
+        assert!(!canvas_resized(egui::Vec2::ZERO, egui::vec2(400.0, 900.0)));
+    }
+
+    #[test]
+    fn normal_stamp_matches_alpha_over() {
+        assert_eq!(stamp_u8([10, 20, 30, 255], [200, 100, 0], 0, BlendMode::Normal), [10, 20, 30, 255]);
+        assert_close(stamp_u8([200, 100, 50, 128], [100, 200, 250], 128, BlendMode::Normal), blend_pixels_u8([200, 100, 50, 128], [100, 200, 250, 128], 1.0, BlendMode::Normal));
+    }
+
+    #[test]
+    fn gradients_run_from_the_primary_at_the_start_to_the_secondary_at_the_end() {
+        let (from, to) = ([255, 0, 0, 255], [0, 0, 255, 0]);
+        let (start, end) = ((10.0, 20.0), (110.0, 20.0));
+        let linear = |p| gradient_color(FillMode::LinearGradient, start, end, from, to, p);
+        assert_eq!(linear((10.0, 20.0)), from);
+        assert_eq!(linear((60.0, 95.0)), [128, 0, 128, 128]);
+        assert_eq!(linear((110.0, -5.0)), to);
+        assert_eq!((linear((-40.0, 20.0)), linear((300.0, 20.0))), (from, to));
+        let radial = |p| gradient_color(FillMode::RadialGradient, start, end, from, to, p);
+        assert_eq!(radial((10.0, 20.0)), from);
+        assert_eq!((radial((60.0, 20.0)), radial((10.0, 70.0))), ([128, 0, 128, 128], [128, 0, 128, 128]));
+        assert_eq!((radial((10.0, 120.0)), radial((200.0, 200.0))), (to, to));
+        assert_eq!(gradient_t(FillMode::LinearGradient, start, start, (90.0, 20.0)), 0.0);
+        assert_eq!(gradient_t(FillMode::Solid, start, end, (90.0, 20.0)), 0.0);
+    }
+}
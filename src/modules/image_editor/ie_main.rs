@@ -1,21 +1,65 @@
 use eframe::egui;
-use image::{DynamicImage, GenericImage, GenericImageView, ImageBuffer, ImageReader, Rgba};
+use image::{DynamicImage, GenericImage, GenericImageView, ImageBuffer, Rgba};
 use crate::modules::helpers::image_export::ExportFormat;
+use crate::modules::helpers::undo::{UndoHistory, UndoableEdit};
 use std::collections::VecDeque;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
 use crate::style::ThemeMode;
-use crate::modules::{EditorModule, MenuAction, MenuItem, MenuContribution};
+use crate::focus::Region;
+use crate::modules::{EditorModule, EditorSettings, SaveState, MenuAction, MenuEntry, MenuContribution, KeyBinding, ViewState, Chord, Shortcut, shortcut, gesture, key, ctrl, shift, ctrl_shift, menu_keys, pressed_shortcuts};
+use crate::error::{EditorError, EditorResult};
 use serde::{Deserialize, Serialize};
-use super::ie_helpers::{load_persisted, save_persisted, blend_pixels_u8, blend_pixels_linear};
+use super::ie_helpers::{load_persisted, save_persisted, blend_pixels_u8, blend_pixels_linear, canvas_resized, paste_chord_released, FillMask};
+use super::ie_session::SessionRecorder;
+use super::ie_gpu::GpuStage;
+use super::ie_adjust::{Adjustment, apply_stack};
+use super::ie_shape::{ShapeKind, ShapeLayer};
+use crate::tasks::{self, Task};
+use crate::modules::helpers::file_info::{FileInfoAction, rename_file, reveal_in_file_manager, needs_overwrite_confirm, open_in_another_window, release_overwrite_target, overwrite_confirm_window};
+use crate::modules::helpers::share;
+use super::ie_source::{read_with_source, MutedConversions, SourceInfo};
+use super::ie_select::{Selection, SelectOp};
+use super::ie_compare::{CompareMode, ImageDiff};
+use super::ie_proxy::CanvasProxy;
+use super::ie_cursor::CursorOutline;
 
 pub(super) const MAX_UNDO: usize = 20;
-pub(super) const MAX_COLOR_HISTORY: usize = 20;
+pub(super) const COLOR_HISTORY_LIMITS: std::ops::RangeInclusive<usize> = 10..=100;
 pub(super) const MAX_COLOR_FAVORITES: usize = 30;
 pub(super) const COLOR_FAV_HOTKEYS: usize = 10;
 pub(super) const HANDLE_HIT: f32 = 22.0;
 pub(super) const HANDLE_VIS: f32 = 8.0;
 pub(super) const ROTATE_DIST: f32 = 28.0;
+pub(super) const MIN_ZOOM: f32 = 0.01;
+pub(super) const MAX_ZOOM: f32 = 50.0;
+/// Fitting never shrinks the short side of a sliver below this many screen pixels; the long side scrolls instead.
+const MIN_FIT_SIDE: f32 = 16.0;
+pub(super) const MIN_CROP_SIDE: u32 = 4;
+pub(super) const DEFAULT_MAX_CANVAS_MEGAPIXELS: u32 = 256;
+
+/// Whether a new or resized canvas of `w`×`h` may be allocated under a limit of `max_mp` megapixels.
+pub(super) fn check_canvas_size(w: u32, h: u32, max_mp: u32) -> EditorResult {
+    if w == 0 || h == 0 { return Err(EditorError::Other(format!("A {}×{} canvas has no pixels. Width and height must be at least 1.", w, h))); }
+    let limit = max_mp.max(1) as u64 * 1_000_000;
+    if w as u64 * h as u64 > limit {
+        return Err(EditorError::Other(format!(
+            "A {}×{} canvas has {:.0} megapixels, more than the {} allowed. Raise the limit in Settings if you have the memory for it.",
+            w, h, (w as u64 * h as u64) as f64 / 1e6, limit / 1_000_000,
+        )));
+    }
+    Ok(())
+}
+
+/// Zoom that fits an `img_w`×`img_h` image into `canvas`, never enlarging and keeping slivers visible.
+pub(super) fn fit_zoom(img_w: u32, img_h: u32, canvas: egui::Vec2) -> f32 {
+    if img_w == 0 || img_h == 0 || !(canvas.x.is_finite() && canvas.y.is_finite() && canvas.x > 0.0 && canvas.y > 0.0) { return 1.0; }
+    let fit = (canvas.x / img_w as f32).min(canvas.y / img_h as f32).min(1.0);
+    let sliver = (MIN_FIT_SIDE / img_w.min(img_h) as f32).min(1.0);
+    fit.max(sliver).clamp(MIN_ZOOM, MAX_ZOOM)
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub(super) struct RgbaColor { pub r: u8, pub g: u8, pub b: u8, pub a: u8 }
@@ -51,26 +95,74 @@ impl RgbaColor {
     }
 }
 
-#[derive(Serialize, Deserialize, Default)]
-pub(super) struct ColorHistory { pub colors: VecDeque<RgbaColor> }
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(from = "StoredHistoryColor")]
+pub(super) struct HistoryColor { pub color: RgbaColor, pub uses: u32 }
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredHistoryColor { Entry { color: RgbaColor, #[serde(default)] uses: u32 }, Bare(RgbaColor) }
+
+impl From<StoredHistoryColor> for HistoryColor {
+    fn from(s: StoredHistoryColor) -> Self {
+        match s { StoredHistoryColor::Entry { color, uses } => Self { color, uses }, StoredHistoryColor::Bare(color) => Self { color, uses: 1 } }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub(super) struct ColorHistory { pub colors: VecDeque<HistoryColor>, pub pinned: Vec<HistoryColor>, pub limit: usize }
+
+impl Default for ColorHistory {
+    fn default() -> Self { Self { colors: VecDeque::new(), pinned: Vec::new(), limit: 40 } }
+}
 
 impl ColorHistory {
-    pub(super) fn load() -> Self { load_persisted("color_history.json") }
+    /// Loads the history, writing it back if it held more colors than its limit allows.
+    pub(super) fn load() -> Self {
+        let mut history: Self = load_persisted("color_history.json", "Color history");
+        if history.trim() { history.save(); }
+        history
+    }
     pub(super) fn save(&self) { save_persisted("color_history.json", self); }
-    pub(super) fn add_color(&mut self, color: RgbaColor) {
-        if let Some(pos) = self.colors.iter().position(|c| *c == color) { self.colors.remove(pos); }
-        self.colors.push_front(color);
-        if self.colors.len() > MAX_COLOR_HISTORY { self.colors.pop_back(); }
+    pub(super) fn add_color(&mut self, color: RgbaColor) { self.push(color); self.save(); }
+    pub(super) fn push(&mut self, color: RgbaColor) {
+        if let Some(p) = self.pinned.iter_mut().find(|p| p.color == color) { p.uses += 1; return; }
+        let uses = self.colors.iter().position(|c| c.color == color).and_then(|pos| self.colors.remove(pos)).map_or(0, |c| c.uses);
+        self.colors.push_front(HistoryColor { color, uses: uses + 1 });
+        self.trim();
+    }
+    /// Drops the oldest unpinned colors past the limit; true when any went.
+    pub(super) fn trim(&mut self) -> bool {
+        self.limit = self.limit.clamp(*COLOR_HISTORY_LIMITS.start(), *COLOR_HISTORY_LIMITS.end());
+        let before = self.colors.len();
+        self.colors.truncate(self.limit);
+        self.colors.len() != before
+    }
+    pub(super) fn toggle_pin(&mut self, color: RgbaColor) {
+        if let Some(pos) = self.pinned.iter().position(|p| p.color == color) { let entry = self.pinned.remove(pos); self.colors.push_front(entry); self.trim(); }
+        else if let Some(pos) = self.colors.iter().position(|c| c.color == color) && let Some(entry) = self.colors.remove(pos) { self.pinned.push(entry); }
+        self.save();
+    }
+    pub(super) fn move_pinned(&mut self, from: usize, to: usize) {
+        if from == to || from >= self.pinned.len() || to >= self.pinned.len() { return; }
+        let item = self.pinned.remove(from);
+        self.pinned.insert(to, item);
+        self.save();
+    }
+    pub(super) fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+        self.trim();
         self.save();
     }
-    pub(super) fn get_colors(&self) -> &VecDeque<RgbaColor> { &self.colors }
+    pub(super) fn clear_recent(&mut self) { self.colors.clear(); self.save(); }
 }
 
 #[derive(Serialize, Deserialize, Default)]
 pub(super) struct ColorFavorites { pub colors: Vec<RgbaColor> }
 
 impl ColorFavorites {
-    pub(super) fn load() -> Self { load_persisted("color_favorites.json") }
+    pub(super) fn load() -> Self { load_persisted("color_favorites.json", "Color favorites") }
     pub(super) fn save(&self) { save_persisted("color_favorites.json", self); }
     pub(super) fn toggle(&mut self, color: RgbaColor) -> bool {
         if let Some(pos) = self.colors.iter().position(|c| *c == color) {
@@ -88,8 +180,8 @@ impl ColorFavorites {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub enum Tool { Brush, Eraser, Fill, Text, Eyedropper, Crop, Pan, Retouch }
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Tool { Brush, Eraser, Fill, Text, Eyedropper, Crop, Pan, Retouch, Slice, Measure, Wand, Line, Rectangle, Ellipse, Select }
 
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub(super) enum RetouchMode { Blur, Sharpen, Smudge, Vibrance, Saturation, Temperature, Brightness, Pixelate }
@@ -146,6 +238,7 @@ pub(super) struct BrushSettings {
     pub angle: f32, pub angle_jitter: f32, pub scatter: f32, pub aspect_ratio: f32,
     pub texture_mode: BrushTextureMode, pub texture_strength: f32, pub shape: BrushShape,
     pub spray_mode: bool, pub spray_particles: u32, pub wetness: f32,
+    #[serde(default)] pub blend_mode: BlendMode,
 }
 
 impl Default for BrushSettings {
@@ -155,6 +248,7 @@ impl Default for BrushSettings {
             angle: 0.0, angle_jitter: 0.0, scatter: 0.0, aspect_ratio: 0.3,
             texture_mode: BrushTextureMode::None, texture_strength: 0.0,
             shape: BrushShape::Circle, spray_mode: false, spray_particles: 40, wetness: 0.0,
+            blend_mode: BlendMode::Normal,
         }
     }
 }
@@ -201,12 +295,276 @@ pub(super) struct SavedBrush { pub name: String, pub settings: BrushSettings }
 pub(super) struct BrushFavorites { pub brushes: Vec<SavedBrush> }
 
 impl BrushFavorites {
-    pub(super) fn load() -> Self { load_persisted("brush_favorites.json") }
+    pub(super) fn load() -> Self { load_persisted("brush_favorites.json", "Saved brushes") }
     pub(super) fn save(&self) { save_persisted("brush_favorites.json", self); }
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub(super) struct ExportPreset {
+    pub name: String, pub format: String, pub jpeg_quality: u8, pub avif_quality: u8, pub avif_speed: u8,
+    pub max_dim: Option<u32>, pub preserve_metadata: bool, pub auto_scale_ico: bool,
+}
+
+impl Default for ExportPreset {
+    fn default() -> Self {
+        Self { name: String::new(), format: "PNG".into(), jpeg_quality: 90, avif_quality: 80, avif_speed: 4, max_dim: None, preserve_metadata: true, auto_scale_ico: true }
+    }
+}
+
+/// The image-space region `[x0, y0, x1, y1]` that was on screen when File > Export Viewport was chosen.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) struct ViewportExport { pub rect: [u32; 4], pub zoom: f32, pub scaled: bool }
+
+impl ViewportExport {
+    pub(super) fn native_size(&self) -> (u32, u32) { (self.rect[2] - self.rect[0], self.rect[3] - self.rect[1]) }
+
+    pub(super) fn screen_size(&self) -> (u32, u32) {
+        let (w, h) = self.native_size();
+        (((w as f32 * self.zoom).round() as u32).max(1), ((h as f32 * self.zoom).round() as u32).max(1))
+    }
+
+    pub(super) fn output_size(&self) -> (u32, u32) { if self.scaled { self.screen_size() } else { self.native_size() } }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub(super) struct ExportPresets { pub presets: Vec<ExportPreset> }
+
+impl ExportPresets {
+    pub(super) fn load() -> (Self, Option<String>) {
+        let mut loaded: Self = load_persisted("export_presets.json", "Export presets");
+        let invalid: Vec<String> = loaded.presets.iter_mut().filter(|p| ExportFormat::from_name(&p.format).is_none()).map(|p| {
+            let desc = format!("\"{}\" ({})", p.name, p.format);
+            p.format = ExportFormat::Png.as_str().into();
+            desc
+        }).collect();
+        if invalid.is_empty() { return (loaded, None); }
+        loaded.save();
+        (loaded, Some(format!("Unknown export format in preset {}, using PNG instead", invalid.join(", "))))
+    }
+    pub(super) fn save(&self) { save_persisted("export_presets.json", self); }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(super) struct RememberedExport { pub format: String, pub jpeg_quality: u8, pub avif_quality: u8, pub avif_speed: u8 }
+
+/// The format and quality last exported from documents of each type, keyed by lowercase source extension.
+#[derive(Serialize, Deserialize, Default)]
+pub(super) struct ExportMemory { pub by_extension: std::collections::BTreeMap<String, RememberedExport> }
+
+impl ExportMemory {
+    pub(super) fn load() -> Self { load_persisted("export_memory.json", "Remembered export settings") }
+    pub(super) fn save(&self) { save_persisted("export_memory.json", self); }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub(super) struct PanelPositions {
+    pub positions: std::collections::HashMap<String, (f32, f32)>,
+    #[serde(skip)] pub sizes: std::collections::HashMap<String, egui::Vec2>,
+    #[serde(skip)] pub unsaved: bool,
+}
+
+impl PanelPositions {
+    pub(super) fn load() -> Self { load_persisted("panel_positions.json", "Panel positions") }
+    pub(super) fn save(&mut self) { save_persisted("panel_positions.json", self); self.unsaved = false; }
+
+    pub(super) fn resolve(&self, key: &str, default: egui::Pos2, bounds: egui::Rect) -> egui::Pos2 {
+        let pos = self.positions.get(key).map_or(default, |&(x, y)| egui::pos2(x, y));
+        let size = self.sizes.get(key).copied().unwrap_or(egui::vec2(120.0, 40.0));
+        let max = egui::pos2((bounds.max.x - size.x).max(bounds.min.x), (bounds.max.y - size.y).max(bounds.min.y));
+        egui::pos2(pos.x.clamp(bounds.min.x, max.x), pos.y.clamp(bounds.min.y, max.y))
+    }
+
+    pub(super) fn track(&mut self, ctx: &egui::Context, key: &str, requested: egui::Pos2, rect: egui::Rect) {
+        self.sizes.insert(key.to_string(), rect.size());
+        let title_bar = egui::Rect::from_min_size(rect.min, egui::vec2(rect.width(), 36.0));
+        let reset = ctx.input(|i| i.pointer.button_double_clicked(egui::PointerButton::Primary) && i.pointer.interact_pos().is_some_and(|p| title_bar.contains(p)));
+        if reset {
+            self.unsaved |= self.positions.remove(key).is_some();
+        } else if (rect.min - requested).length() > 0.5 {
+            self.positions.insert(key.to_string(), (rect.min.x, rect.min.y));
+            self.unsaved = true;
+        }
+        if self.unsaved && !ctx.input(|i| i.pointer.any_down()) { self.save(); }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub(super) struct ToolMemory {
+    pub remember: bool, pub tool: Tool, pub brush: BrushSettings,
+    pub eraser_size: f32, pub eraser_transparent: bool,
+    pub text_font_size: f32, pub text_bold: bool, pub text_italic: bool, pub text_underline: bool, pub text_font_name: String, pub text_direction: TextDirection, pub text_tab_width: u8,
+    pub retouch_mode: RetouchMode, pub retouch_size: f32, pub retouch_strength: f32, pub retouch_softness: f32, pub retouch_pixelate_block: u32,
+    pub measure_dpi: f32, pub dimension_snap: DimensionSnap,
+    pub loupe_zoom: u32, pub loupe_round: bool, pub loupe_grid: bool,
+    pub adaptive_strokes: bool,
+}
+
+impl Default for ToolMemory {
+    fn default() -> Self {
+        Self {
+            remember: true, tool: Tool::Brush, brush: BrushSettings::default(),
+            eraser_size: 20.0, eraser_transparent: false,
+            text_font_size: 24.0, text_bold: false, text_italic: false, text_underline: false, text_font_name: "Ubuntu".to_string(), text_direction: TextDirection::Ltr, text_tab_width: 4,
+            retouch_mode: RetouchMode::Blur, retouch_size: 40.0, retouch_strength: 0.5, retouch_softness: 0.7, retouch_pixelate_block: 12,
+            measure_dpi: 0.0, dimension_snap: DimensionSnap::None,
+            loupe_zoom: 8, loupe_round: true, loupe_grid: true,
+            adaptive_strokes: false,
+        }
+    }
+}
+
+impl ToolMemory {
+    pub(super) fn load() -> Self { load_persisted("tool_settings.json", "Tool settings") }
+    pub(super) fn save(&self) { save_persisted("tool_settings.json", self); }
+    pub(super) fn seed() -> Self {
+        let mem = Self::load();
+        if mem.remember { mem } else { Self { remember: false, ..Self::default() } }
+    }
+}
+
+/// The last filter applied, kept by the app for the session so Repeat Last Filter works in the next image too.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RepeatableFilter {
+    BrightnessContrast { brightness: f32, contrast: f32 },
+    HueSaturation { hue: f32, saturation: f32 },
+    Blur { radius: f32 },
+    Sharpen { amount: f32 },
+    Resize { w: u32, h: u32, stretch: bool },
+    Grayscale, Invert, Sepia,
+}
+
+impl RepeatableFilter {
+    pub(super) fn label(&self) -> &'static str {
+        match self {
+            Self::BrightnessContrast { .. } => "Brightness/Contrast", Self::HueSaturation { .. } => "Hue/Saturation",
+            Self::Blur { .. } => "Blur", Self::Sharpen { .. } => "Sharpen", Self::Resize { .. } => "Resize",
+            Self::Grayscale => "Grayscale", Self::Invert => "Invert", Self::Sepia => "Sepia",
+        }
+    }
+    pub(super) fn panel(&self) -> Option<FilterPanel> {
+        match self {
+            Self::BrightnessContrast { .. } => Some(FilterPanel::BrightnessContrast), Self::HueSaturation { .. } => Some(FilterPanel::HueSaturation),
+            Self::Blur { .. } => Some(FilterPanel::Blur), Self::Sharpen { .. } => Some(FilterPanel::Sharpen), Self::Resize { .. } => Some(FilterPanel::Resize),
+            Self::Grayscale | Self::Invert | Self::Sepia => None,
+        }
+    }
+}
+
+pub(super) const PASTE_OFFSET: f32 = 12.0;
+
+/// The last region copied in any image tab, kept by the app and handed to every editor with its settings. It goes to
+/// the OS clipboard too, but only this copy knows its DPI and, by identity, which editor copied it.
+#[derive(Debug, PartialEq)]
+pub struct ImageClip { pub(super) image: image::RgbaImage, pub(super) dpi: f32 }
+
+impl ImageClip {
+    /// `ours` while the OS clipboard still holds its pixels (or can't be read), otherwise whatever image was copied
+    /// after it elsewhere.
+    pub(super) fn newest(ours: Option<Arc<Self>>, os: Option<arboard::ImageData>) -> Option<Arc<Self>> {
+        let Some(os) = os else { return ours };
+        if let Some(c) = ours.filter(|c| (c.image.width() as usize, c.image.height() as usize) == (os.width, os.height) && c.image.as_raw()[..] == os.bytes[..]) {
+            return Some(c);
+        }
+        image::RgbaImage::from_raw(os.width as u32, os.height as u32, os.bytes.into_owned()).map(|image| Arc::new(Self { image, dpi: 0.0 }))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum FilterPanel { None, BrightnessContrast, HueSaturation, Blur, Sharpen, Equalize, Threshold, ChannelMixer, Stroke, Resize, Export, Brush, TimeLapse, Adjustments }
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum ZoomMode { Fit, Manual }
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum EqualizeMode { Global, Clahe }
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum FillMode { Solid, LinearGradient, RadialGradient }
+
+impl FillMode {
+    pub(super) const ALL: [Self; 3] = [Self::Solid, Self::LinearGradient, Self::RadialGradient];
+
+    pub(super) fn label(self) -> &'static str {
+        match self { Self::Solid => "Solid", Self::LinearGradient => "Linear gradient", Self::RadialGradient => "Radial gradient" }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum ChannelView { Red, Green, Blue, Alpha }
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum StrokePosition { Inside, Center, Outside }
+
 #[derive(Debug, Clone, Copy, PartialEq)]
-pub(super) enum FilterPanel { None, BrightnessContrast, HueSaturation, Blur, Sharpen, Resize, Export, Brush }
+pub enum ImageMenuAction {
+    ZoomIn, ZoomOut, Fit, CycleChannelView, Loupe, LoupeZoom(u32), LoupeRound, LoupeGrid, Diagnostics, AdaptiveStrokes, ToggleLayers, Surround(CanvasSurround),
+    FlipHorizontal, FlipVertical, RotateCcw, RotateCw, ResizeCanvas, RecordSession, ExportTimeLapse, Share, CopySelection, CopyImage, PasteImage, CopyDataUri, CopyMarkdownImage, PlaceImage, PlaceFloating, Stroke,
+    BrightnessContrast, HueSaturation, Blur, Sharpen, Equalize, Threshold, ChannelMixer, Grayscale, Invert, Sepia, RepeatFilter, RepeatFilterAdjust,
+    DuplicateFlattened, FlattenText, InsertShape(ShapeKind), ExportWithPreset(usize), ExportViewport, ToggleGuides, ClearGuides, HideAllText, RestoreText(usize), CompareSaved, CompareMode(CompareMode), InvertSelection, Deselect, ExportSelection, LayerNew, LayerDuplicate, LayerDelete, LayerMergeDown, LayerFlatten, LockPixels, Adjustments,
+}
+
+impl ImageMenuAction {
+    fn mutates_pixels(&self) -> bool {
+        matches!(self, ImageMenuAction::FlipHorizontal | ImageMenuAction::FlipVertical | ImageMenuAction::RotateCcw | ImageMenuAction::RotateCw | ImageMenuAction::ResizeCanvas | ImageMenuAction::Stroke
+            | ImageMenuAction::BrightnessContrast | ImageMenuAction::HueSaturation | ImageMenuAction::Blur | ImageMenuAction::Sharpen | ImageMenuAction::Equalize | ImageMenuAction::Threshold | ImageMenuAction::ChannelMixer
+            | ImageMenuAction::Grayscale | ImageMenuAction::Invert | ImageMenuAction::Sepia)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum CanvasSurround { #[default] Checker, Light, MidGray, Dark }
+
+impl CanvasSurround {
+    pub(super) const ALL: [CanvasSurround; 4] = [CanvasSurround::Checker, CanvasSurround::Light, CanvasSurround::MidGray, CanvasSurround::Dark];
+    pub(super) fn label(&self) -> &'static str {
+        match self { CanvasSurround::Checker => "Checkerboard", CanvasSurround::Light => "Light Gray", CanvasSurround::MidGray => "Mid Gray", CanvasSurround::Dark => "Dark Gray" }
+    }
+    pub(super) fn color(&self) -> Option<egui::Color32> {
+        match self {
+            CanvasSurround::Checker => None, CanvasSurround::Light => Some(egui::Color32::from_gray(200)),
+            CanvasSurround::MidGray => Some(egui::Color32::from_gray(128)), CanvasSurround::Dark => Some(egui::Color32::from_gray(40)),
+        }
+    }
+}
+
+/// The canvas surround and last filter are picked in the editor and kept by the app for the rest of the session.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageEditorSettings {
+    pub max_canvas_mp: u32, pub cursor_outline: CursorOutline, pub canvas_surround: CanvasSurround, pub keep_backup: bool, pub use_gpu: bool,
+    pub last_filter: Option<RepeatableFilter>, pub image_clip: Option<Arc<ImageClip>>,
+    /// Whether tool choices are saved for new tabs, and a one-shot request to put the open editor's tools back to their defaults.
+    pub remember_tools: bool, pub reset_tools: bool,
+}
+
+impl Default for ImageEditorSettings {
+    fn default() -> Self { Self { max_canvas_mp: DEFAULT_MAX_CANVAS_MEGAPIXELS, cursor_outline: CursorOutline::default(), canvas_surround: CanvasSurround::Checker, keep_backup: false, use_gpu: false, last_filter: None, image_clip: None, remember_tools: true, reset_tools: false } }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub(super) enum TextDirection { #[default] Ltr, Rtl, Vertical }
+
+impl TextDirection {
+    pub(super) fn label(&self) -> &'static str {
+        match self { TextDirection::Ltr => "Left to Right", TextDirection::Rtl => "Right to Left", TextDirection::Vertical => "Vertical" }
+    }
+}
+
+impl ChannelView {
+    pub(super) fn label(&self) -> &'static str {
+        match self { ChannelView::Red => "Red", ChannelView::Green => "Green", ChannelView::Blue => "Blue", ChannelView::Alpha => "Alpha" }
+    }
+    pub(super) fn cycle(view: Option<Self>) -> Option<Self> {
+        match view {
+            None => Some(ChannelView::Red), Some(ChannelView::Red) => Some(ChannelView::Green),
+            Some(ChannelView::Green) => Some(ChannelView::Blue), Some(ChannelView::Blue) => Some(ChannelView::Alpha),
+            Some(ChannelView::Alpha) => None,
+        }
+    }
+}
+
+pub(super) const MIXER_IDENTITY: [[f32; 4]; 3] = [[1.0, 0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0]];
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub(super) enum THandle { Move, N, S, E, W, NE, NW, SE, SW, Rotate }
@@ -246,12 +604,13 @@ impl TransformHandleSet {
                 return Some(h);
             }
         }
+        self.contains(pos).then_some(THandle::Move)
+    }
+    pub(super) fn contains(&self, pos: egui::Pos2) -> bool {
         let c = self.rect.center();
         let d = pos - c;
         let (ca, sa) = (self.angle_rad.cos(), self.angle_rad.sin());
-        let local = c + egui::vec2(d.x * ca + d.y * sa, -d.x * sa + d.y * ca);
-        if self.rect.contains(local) { return Some(THandle::Move); }
-        None
+        self.rect.contains(c + egui::vec2(d.x * ca + d.y * sa, -d.x * sa + d.y * ca))
     }
     pub(super) fn draw(&self, painter: &egui::Painter, accent: egui::Color32) {
         let corners = [self.rect.left_top(), self.rect.right_top(), self.rect.right_bottom(), self.rect.left_bottom()];
@@ -277,6 +636,11 @@ impl TransformHandleSet {
     }
 }
 
+pub(super) const RECENTLY_DELETED_LIMIT: usize = 10;
+pub(super) const TEXT_FONTS: &[(&str, &str)] = &[("Ubuntu", "Ubuntu"), ("Roboto", "Roboto"), ("GoogleSans", "Google Sans"), ("OpenSans", "Open Sans")];
+
+pub(super) fn is_known_font(name: &str) -> bool { TEXT_FONTS.iter().any(|(n, _)| *n == name) }
+
 #[derive(Debug, Clone)]
 pub(super) struct TextLayer {
     pub id: u64, pub content: String,
@@ -284,17 +648,61 @@ pub(super) struct TextLayer {
     pub box_width: Option<f32>, pub box_height: Option<f32>, pub rotation: f32,
     pub color: egui::Color32, pub bold: bool, pub italic: bool, pub underline: bool,
     pub font_name: String, pub rendered_height: f32, pub cached_lines: Vec<String>,
+    pub direction: TextDirection, pub tab_width: u8, pub font_substitute: Option<String>, pub canvas_hidden: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct TextStyle {
+    pub font_name: String, pub font_size: f32, pub bold: bool, pub italic: bool, pub underline: bool,
+    pub direction: TextDirection, pub tab_width: u8, pub color: egui::Color32,
 }
 
 impl TextLayer {
-    pub(super) fn line_count(&self) -> usize { self.content.lines().count().max(1) }
+    pub(super) fn style(&self) -> TextStyle {
+        TextStyle {
+            font_name: self.effective_font().to_string(), font_size: self.font_size, bold: self.bold, italic: self.italic, underline: self.underline,
+            direction: self.direction, tab_width: self.tab_width, color: self.color,
+        }
+    }
+    pub(super) fn apply_style(&mut self, style: &TextStyle) {
+        if style.font_name != self.effective_font() { self.font_name = style.font_name.clone(); self.font_substitute = None; }
+        self.font_size = style.font_size; self.bold = style.bold; self.italic = style.italic; self.underline = style.underline;
+        self.direction = style.direction; self.tab_width = style.tab_width; self.color = style.color;
+    }
+    pub(super) fn line_count(&self) -> usize { self.content.split('\n').count() }
+    pub(super) fn preview(&self) -> String {
+        let line = self.content.lines().map(str::trim).find(|l| !l.is_empty()).unwrap_or("(empty)");
+        if line.chars().count() > 28 { format!("{}…", line.chars().take(27).collect::<String>()) } else { line.to_string() }
+    }
     pub(super) fn max_line_chars(&self) -> usize {
-        self.content.lines().map(|l| l.chars().count()).max().unwrap_or(1).max(1)
+        let tw = self.tab_width.max(1) as usize;
+        self.content.split('\n').map(|l| unicode_segmentation::UnicodeSegmentation::graphemes(l, true).fold(0, |col, g| if g == "\t" { (col / tw + 1) * tw } else { col + 1 })).max().unwrap_or(1).max(1)
+    }
+    pub(super) fn vertical_step(&self) -> f32 { self.font_size * 1.1 }
+    pub(super) fn column_width(&self) -> f32 { self.font_size * 1.3 }
+    pub(super) fn vertical_columns(&self) -> Vec<(usize, Vec<(usize, char)>)> {
+        let per_col = self.box_height.map(|h| ((h / self.vertical_step()).floor() as usize).max(1)).unwrap_or(usize::MAX);
+        let mut cols: Vec<(usize, Vec<(usize, char)>)> = vec![(0, Vec::new())];
+        for (b, ch) in self.content.char_indices() {
+            if ch == '\n' { cols.push((b + 1, Vec::new())); continue; }
+            if cols.last().is_some_and(|c| c.1.len() >= per_col) { cols.push((b, Vec::new())); }
+            if let Some(c) = cols.last_mut() { c.1.push((b, ch)); }
+        }
+        cols
+    }
+    pub(super) fn vertical_caret_cell(&self, byte: usize) -> (usize, usize) {
+        let cols = self.vertical_columns();
+        let ci = cols.iter().rposition(|(start, _)| *start <= byte).unwrap_or(0);
+        (ci, cols[ci].1.iter().take_while(|(b, _)| *b < byte).count())
     }
     pub(super) fn auto_width(&self, zoom: f32) -> f32 {
+        if self.direction == TextDirection::Vertical { return self.vertical_columns().len() as f32 * self.column_width() * zoom; }
         (self.max_line_chars() as f32 * self.font_size * 0.58 * zoom).max(self.font_size * zoom)
     }
     pub(super) fn auto_height(&self, zoom: f32) -> f32 {
+        if self.direction == TextDirection::Vertical {
+            return (self.vertical_columns().iter().map(|c| c.1.len()).max().unwrap_or(1).max(1) as f32 * self.vertical_step() * zoom).max(self.font_size * zoom);
+        }
         if self.rendered_height > 0.0 { self.rendered_height * zoom }
         else { self.line_count() as f32 * self.font_size * 1.35 * zoom }
     }
@@ -303,8 +711,15 @@ impl TextLayer {
         let h = self.box_height.map(|bh| bh * zoom).unwrap_or_else(|| self.auto_height(zoom));
         egui::Rect::from_min_size(anchor, egui::vec2(w, h))
     }
+    pub(super) fn text_origin(&self, anchor: egui::Pos2, zoom: f32) -> egui::Pos2 {
+        let rect = self.screen_rect(anchor, zoom);
+        let (sin_a, cos_a) = self.rotation.to_radians().sin_cos();
+        let d = if self.direction == TextDirection::Rtl { rect.right_top() } else { anchor } - rect.center();
+        rect.center() + egui::vec2(d.x * cos_a - d.y * sin_a, d.x * sin_a + d.y * cos_a)
+    }
+    pub(super) fn effective_font(&self) -> &str { self.font_substitute.as_deref().unwrap_or(&self.font_name) }
     pub(super) fn font_family_name(&self) -> &'static str {
-        match (self.font_name.as_str(), self.bold, self.italic) {
+        match (self.effective_font(), self.bold, self.italic) {
             ("Roboto", true, _) => "Roboto-Bold",
             ("Roboto", _, true) => "Roboto-Italic",
             ("Roboto", ..) => "Roboto",
@@ -384,19 +799,159 @@ impl ImageLayerData {
     }
 }
 
-pub(super) struct ImageDrag {
+pub(super) struct BoxDrag {
     pub handle: THandle, pub start: egui::Pos2,
     pub orig_x: f32, pub orig_y: f32, pub orig_w: f32, pub orig_h: f32,
     pub orig_rotation: f32, pub orig_rot_start_angle: f32,
 }
 
+impl BoxDrag {
+    pub(super) fn apply(&self, pos: egui::Pos2, origin: egui::Pos2, zoom: f32, aspect: Option<f32>) -> (f32, f32, f32, f32, f32) {
+        let (ox, oy) = (origin.x, origin.y);
+        let (mut x, mut y, mut w, mut h, mut rotation) = (self.orig_x, self.orig_y, self.orig_w, self.orig_h, self.orig_rotation);
+        let anchor = egui::pos2(ox + self.orig_x * zoom, oy + self.orig_y * zoom);
+        let (right, bottom) = (anchor.x + self.orig_w * zoom, anchor.y + self.orig_h * zoom);
+        let min_sz = 8.0_f32;
+        let span = |d: f32| (d.max(min_sz) / zoom).max(1.0);
+        let locked_h = |w: f32, free: f32| aspect.map_or(free, |a| (w / a).max(1.0));
+        match self.handle {
+            THandle::Move => { let delta = pos - self.start; x = self.orig_x + delta.x / zoom; y = self.orig_y + delta.y / zoom; }
+            THandle::E => { w = span(pos.x - anchor.x); h = locked_h(w, h); }
+            THandle::W => { w = span(right - pos.x); x = (pos.x - ox) / zoom; h = locked_h(w, h); }
+            THandle::S => { h = span(pos.y - anchor.y); if let Some(a) = aspect { w = (h * a).max(1.0); } }
+            THandle::N => { h = span(bottom - pos.y); y = (pos.y - oy) / zoom; if let Some(a) = aspect { w = (h * a).max(1.0); } }
+            THandle::SE => { w = span(pos.x - anchor.x); h = locked_h(w, span(pos.y - anchor.y)); }
+            THandle::NE => { w = span(pos.x - anchor.x); h = locked_h(w, span(bottom - pos.y)); y = if aspect.is_some() { self.orig_y + self.orig_h - h } else { (pos.y - oy) / zoom }; }
+            THandle::NW => {
+                w = span(right - pos.x); h = locked_h(w, span(bottom - pos.y)); x = self.orig_x + self.orig_w - w;
+                y = if aspect.is_some() { self.orig_y + self.orig_h - h } else { (bottom - (bottom - pos.y).max(min_sz) - oy) / zoom };
+            }
+            THandle::SW => { w = span(right - pos.x); x = self.orig_x + self.orig_w - w; h = locked_h(w, span(pos.y - anchor.y)); }
+            THandle::Rotate => { let c = egui::pos2((anchor.x + right) / 2.0, (anchor.y + bottom) / 2.0); rotation = self.orig_rotation + ((pos - c).angle() - self.orig_rot_start_angle).to_degrees(); }
+        }
+        (x, y, w, h, rotation)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(super) struct ImageSlice { pub name: String, pub x: f32, pub y: f32, pub w: f32, pub h: f32 }
+
+impl ImageSlice {
+    pub fn from_corners(name: String, a: (f32, f32), b: (f32, f32)) -> Self {
+        Self { name, x: a.0.min(b.0).round(), y: a.1.min(b.1).round(), w: (a.0 - b.0).abs().round(), h: (a.1 - b.1).abs().round() }
+    }
+
+    pub fn pixel_rect(&self, img_w: u32, img_h: u32) -> Option<(u32, u32, u32, u32)> {
+        let x0 = self.x.round().clamp(0.0, img_w as f32) as u32;
+        let y0 = self.y.round().clamp(0.0, img_h as f32) as u32;
+        let x1 = (self.x + self.w).round().clamp(0.0, img_w as f32) as u32;
+        let y1 = (self.y + self.h).round().clamp(0.0, img_h as f32) as u32;
+        if x1 <= x0 || y1 <= y0 { None } else { Some((x0, y0, x1 - x0, y1 - y0)) }
+    }
+}
+
+pub(super) const MAX_MEASUREMENTS: usize = 5;
+pub(super) const LOUPE_ZOOMS: [u32; 4] = [2, 4, 8, 16];
+pub(super) const ADAPTIVE_FRAME_MS: f32 = 33.0;
+pub(super) const COARSE_STEP_SCALE: f32 = 3.0;
+
+#[derive(Default)]
+pub(super) struct PerfStats { pub overlay: bool, pub frame_ms: f32, pub stroke_ms: f32, pub upload_ms: f32, pub coarse: bool, pub stroke_path: Vec<(f32, f32)> }
+
+impl PerfStats {
+    pub fn sample(avg: &mut f32, ms: f32) { *avg = if *avg == 0.0 { ms } else { *avg * 0.9 + ms * 0.1 }; }
+    pub fn record_stroke(&mut self, started: std::time::Instant) { Self::sample(&mut self.stroke_ms, started.elapsed().as_secs_f32() * 1000.0); }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) struct Measurement { pub a: (f32, f32), pub b: (f32, f32), pub c: Option<(f32, f32)> }
+
+impl Measurement {
+    pub fn new(p: (f32, f32)) -> Self { Self { a: p, b: p, c: None } }
+
+    pub fn delta(&self) -> (f32, f32) { (self.b.0 - self.a.0, self.b.1 - self.a.1) }
+
+    pub fn length(&self) -> f32 { let (dx, dy) = self.delta(); dx.hypot(dy) }
+
+    pub fn angle(&self) -> f32 { let (dx, dy) = self.delta(); (-dy).atan2(dx).to_degrees() }
+
+    pub fn vertex_angle(&self) -> Option<f32> {
+        let c = self.c?;
+        let (u, v) = ((self.a.0 - self.b.0, self.a.1 - self.b.1), (c.0 - self.b.0, c.1 - self.b.1));
+        if u.0.hypot(u.1) < f32::EPSILON || v.0.hypot(v.1) < f32::EPSILON { return None; }
+        let diff = (v.1.atan2(v.0) - u.1.atan2(u.0)).to_degrees().abs() % 360.0;
+        Some(if diff > 180.0 { 360.0 - diff } else { diff })
+    }
+
+    pub fn constrain(from: (f32, f32), to: (f32, f32)) -> (f32, f32) {
+        let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+        let step = std::f32::consts::FRAC_PI_4;
+        let ang = (dy.atan2(dx) / step).round() * step;
+        let len = dx.hypot(dy);
+        (from.0 + ang.cos() * len, from.1 + ang.sin() * len)
+    }
+
+    pub fn format_length(px: f32, dpi: f32) -> String {
+        if dpi > 0.0 { format!("{:.1} px ({:.2} in / {:.1} mm)", px, px / dpi, px / dpi * 25.4) } else { format!("{:.1} px", px) }
+    }
+
+    pub fn summary(&self, dpi: f32) -> String {
+        let (dx, dy) = self.delta();
+        let base = format!("{}  dx {:.0}  dy {:.0}  {:.1}°", Self::format_length(self.length(), dpi), dx, dy, self.angle());
+        match self.vertex_angle() { Some(a) => format!("{}  ∠ {:.1}°", base, a), None => base }
+    }
+}
+
 #[derive(Default)]
 pub(super) struct CropState { pub start: Option<(f32, f32)>, pub end: Option<(f32, f32)> }
 
-#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+/// Select tool: the marquee being dragged, and pixels lifted off a layer (or pasted) that float until committed.
+/// `move_from` is the pointer and floating offset a move drag started at.
+#[derive(Default)]
+pub(super) struct SelectionState {
+    pub marquee: Option<((f32, f32), (f32, f32))>,
+    pub floating: Option<FloatingPixels>,
+    pub move_from: Option<((f32, f32), (i32, i32))>,
+}
+
+/// `offset` puts the buffer's top-left pixel on the canvas. `mask` is the selection the pixels were lifted with,
+/// when the buffer sat at `origin`; it follows them when they land.
+pub(super) struct FloatingPixels {
+    pub pixels: image::RgbaImage, pub offset: (i32, i32), pub origin: (i32, i32), pub mask: Option<Selection>,
+    pub layer: u64, pub undo_mark: u64, pub texture: Option<egui::TextureHandle>,
+}
+
+/// Size constraint shared by crop, resize and resize-on-export, for video encoders that want even or mod-N dimensions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub(super) enum DimensionSnap { #[default] None, Even, Multiple(u32) }
+
+impl DimensionSnap {
+    pub(super) fn step(self) -> u32 { match self { Self::None => 1, Self::Even => 2, Self::Multiple(n) => n.max(1) } }
+    pub(super) fn fits(self, len: u32) -> bool { len.is_multiple_of(self.step()) }
+
+    fn bounded(self, steps: u32, max: u32) -> u32 {
+        let step = self.step();
+        let len = steps.max(1) * step;
+        if len <= max { len } else if max >= step { max / step * step } else { max }
+    }
+
+    /// Nearest allowed length, at least one step and rounded down instead when rounding up would pass `max`.
+    pub(super) fn snap(self, len: u32, max: u32) -> u32 { self.bounded((len + self.step() / 2) / self.step(), max) }
+
+    /// Rounds away from `prev`, so nudging a value field by one always reaches the next allowed size.
+    pub(super) fn snap_from(self, len: u32, prev: u32, max: u32) -> u32 {
+        self.bounded(if len > prev { len.div_ceil(self.step()) } else { len / self.step() }, max)
+    }
+
+    pub(super) fn label(self) -> String {
+        match self { Self::None => "None".into(), Self::Even => "Even".into(), Self::Multiple(n) => format!("Multiple of {}", n) }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
 pub enum BlendMode {
-    Normal, Multiply, Screen, Overlay, SoftLight,
-    HardLight, Darken, Lighten, Difference, Exclusion,
+    #[default] Normal, Multiply, Screen, Overlay, SoftLight,
+    HardLight, Darken, Lighten, Difference, Exclusion, Add,
 }
 
 impl BlendMode {
@@ -405,12 +960,12 @@ impl BlendMode {
             Self::Normal => "Normal", Self::Multiply => "Multiply", Self::Screen => "Screen",
             Self::Overlay => "Overlay", Self::SoftLight => "Soft Light", Self::HardLight => "Hard Light",
             Self::Darken => "Darken", Self::Lighten => "Lighten",
-            Self::Difference => "Difference", Self::Exclusion => "Exclusion",
+            Self::Difference => "Difference", Self::Exclusion => "Exclusion", Self::Add => "Add",
         }
     }
     pub fn all() -> &'static [BlendMode] {
         &[Self::Normal, Self::Multiply, Self::Screen, Self::Overlay, Self::SoftLight,
-          Self::HardLight, Self::Darken, Self::Lighten, Self::Difference, Self::Exclusion]
+          Self::HardLight, Self::Darken, Self::Lighten, Self::Difference, Self::Exclusion, Self::Add]
     }
     pub fn blend_channel(self, bot: f32, top: f32) -> f32 {
         match self {
@@ -432,18 +987,78 @@ impl BlendMode {
             Self::Lighten => bot.max(top),
             Self::Difference => (bot - top).abs(),
             Self::Exclusion => bot + top - 2.0 * bot * top,
+            Self::Add => (bot + top).min(1.0),
         }
     }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
-pub enum LayerKind { Background, Raster, Text, Image }
+pub enum LayerKind { Background, Raster, Text, Image, Shape }
 
 #[derive(Debug, Clone)]
 pub struct ImageLayer {
     pub id: u64, pub name: String, pub opacity: f32,
     pub visible: bool, pub locked: bool, pub blend_mode: BlendMode,
-    pub kind: LayerKind, pub linked_text_id: Option<u64>, pub linked_image_id: Option<u64>,
+    pub kind: LayerKind, pub linked_text_id: Option<u64>, pub linked_image_id: Option<u64>, pub linked_shape_id: Option<u64>,
+}
+
+pub(super) type FilterMessage = (u64, Option<DynamicImage>);
+
+pub(super) struct FilterJob { pub(super) id: u64, pub(super) task: Task, pub(super) cancellable: bool, pub(super) dims: Option<(u32, u32)> }
+
+pub(super) struct JobHandle { id: u64, pub(super) task: Task, tx: Option<Sender<FilterMessage>> }
+
+impl JobHandle {
+    pub(super) fn progress(&self, p: f32) { self.task.progress(p); }
+    pub(super) fn stage(&self, s: Option<GpuStage>) { self.task.set_detail(s.map(|s| s.label())); }
+    pub(super) fn cancelled(&self) -> bool { self.task.cancelled() }
+    pub(super) fn finish(mut self, img: DynamicImage) {
+        self.progress(1.0);
+        if let Some(tx) = self.tx.take() { let _ = tx.send((self.id, Some(img))); }
+    }
+}
+
+impl Drop for JobHandle {
+    fn drop(&mut self) {
+        if let Some(tx) = self.tx.take() { let _ = tx.send((self.id, None)); }
+        self.task.finish(None);
+    }
+}
+
+pub(super) const VARIANT_SCALES: [f32; 3] = [1.0, 2.0, 3.0];
+pub(super) const VARIANT_UPSCALE_WARN: f32 = 2.0;
+const JOURNAL_DELAY: std::time::Duration = std::time::Duration::from_secs(3);
+
+pub(super) struct VariantExport { pub(super) done: Arc<AtomicUsize>, pub(super) total: usize, pub(super) task: Task, pub(super) rx: Receiver<EditorResult<Vec<PathBuf>>> }
+
+impl VariantExport {
+    pub(super) fn done(&self) -> usize { self.done.load(Ordering::Relaxed) }
+}
+
+pub(super) const FILL_PREVIEW_DELAY: f64 = 0.15;
+pub(super) const FILL_PREVIEW_BUDGET: std::time::Duration = std::time::Duration::from_millis(250);
+pub(super) const FILL_HOVER_SLOP: f32 = 3.0;
+
+pub(super) struct FillPreview {
+    pub(super) key: (u64, u64, u32, u32, u32, bool), pub(super) cancel: Arc<AtomicBool>,
+    pub(super) job: Option<Receiver<Option<FillMask>>>, pub(super) mask: Option<FillMask>, pub(super) texture: Option<egui::TextureHandle>,
+}
+
+/// Data URIs past this start to bog down the editors they get pasted into.
+pub(super) const DATA_URI_WARN_BYTES: usize = 1024 * 1024;
+
+pub(super) struct DataUriJob { pub(super) rx: Receiver<EditorResult<String>>, pub(super) format: ExportFormat, pub(super) markdown: bool }
+
+pub(super) struct AdjustJob { key: AdjustKey, task: Task, result: Arc<std::sync::OnceLock<Option<DynamicImage>>> }
+
+/// The adjustment stack and the `pixel_revision` of the base it was applied to.
+type AdjustKey = (Vec<Adjustment>, u64);
+
+pub(super) struct WandJob { pub(super) rx: Receiver<Option<Selection>>, pub(super) op: SelectOp, pub(super) task: Task }
+
+pub(super) struct SavedComparison {
+    pub(super) job: Option<(Receiver<image::ImageResult<Option<ImageDiff>>>, Task)>, pub(super) diff: Option<ImageDiff>,
+    pub(super) mode: CompareMode, pub(super) texture: Option<(CompareMode, egui::TextureHandle)>,
 }
 
 pub(super) struct LayerUndoEntry {
@@ -454,20 +1069,32 @@ pub(super) struct LayerUndoEntry {
     pub active_layer_id: u64, pub next_layer_id: u64, pub next_text_id: u64,
     pub image_layer_data: std::collections::HashMap<u64, ImageLayerData>,
     pub next_image_layer_id: u64,
+    pub adjustments: Vec<Adjustment>,
+    pub(super) shape_layers: Vec<ShapeLayer>, pub next_shape_id: u64,
+}
+
+impl UndoableEdit for LayerUndoEntry {
+    fn size_bytes(&self) -> usize {
+        self.image.as_ref().map_or(0, |i| i.as_bytes().len()) + self.layer_images.values().map(|i| i.as_bytes().len()).sum::<usize>()
+    }
 }
 
 pub struct ImageEditor {
+    pub(super) settings: ImageEditorSettings,
+    pub(super) settings_changed: bool,
     pub(super) image: Option<DynamicImage>,
     pub(super) texture: Option<egui::TextureId>,
     pub(super) texture_dirty: bool,
     pub(super) texture_dirty_rect: Option<[u32; 4]>,
+    pub(super) canvas_proxy: CanvasProxy,
     pub(crate) file_path: Option<PathBuf>,
     pub(super) dirty: bool,
-    pub(super) undo_stack: VecDeque<LayerUndoEntry>,
-    pub(super) redo_stack: VecDeque<LayerUndoEntry>,
+    pub(super) history: UndoHistory<LayerUndoEntry>,
     pub(super) zoom: f32,
     pub(super) pan: egui::Vec2,
     pub(super) fit_on_next_frame: bool,
+    pub(super) zoom_mode: ZoomMode,
+    pub(super) view_size: egui::Vec2,
     pub(super) tool: Tool,
     pub(super) brush: BrushSettings,
     pub(super) brush_favorites: BrushFavorites,
@@ -479,42 +1106,78 @@ pub struct ImageEditor {
     pub(super) color: egui::Color32,
     pub(super) stroke_points: Vec<(f32, f32)>,
     pub(super) is_dragging: bool,
+    pub(super) space_pan: bool,
     pub(super) text_layers: Vec<TextLayer>,
+    pub(super) deleted_text: Vec<TextLayer>,
     pub(super) selected_text: Option<u64>,
     pub(super) editing_text: bool,
     pub(super) next_text_id: u64,
     pub(super) text_font_size: f32,
     pub(super) text_bold: bool, pub(super) text_italic: bool, pub(super) text_underline: bool,
-    pub(super) text_font_name: String,
+    pub(super) text_font_name: String, pub(super) text_direction: TextDirection, pub(super) text_tab_width: u8,
     pub(super) text_drag: Option<TextDrag>,
     pub(super) text_cursor: usize,
     pub(super) text_sel_anchor: Option<usize>,
+    pub(super) caret_epoch: Option<((u64, usize), f64)>,
     pub(super) crop_state: CropState,
+    pub(super) selection_state: SelectionState,
     pub(super) crop_drag: Option<THandle>,
     pub(super) crop_drag_orig: Option<(f32, f32, f32, f32)>,
     pub(super) filter_panel: FilterPanel,
     pub(super) brightness: f32, pub(super) contrast: f32,
     pub(super) hue: f32, pub(super) saturation: f32,
     pub(super) blur_radius: f32, pub(super) sharpen_amount: f32,
+    pub(super) equalize_mode: EqualizeMode, pub(super) clahe_tile: u32, pub(super) clahe_clip: f32,
+    pub(super) threshold_level: u8, pub(super) threshold_soft: u8, pub(super) threshold_hist: Option<[u32; 256]>,
+    pub(super) mixer: [[f32; 4]; 3],
+    pub(super) stroke_width: f32, pub(super) stroke_color: egui::Color32, pub(super) stroke_position: StrokePosition,
+    pub(super) stroke_corner_radius: f32, pub(super) stroke_new_layer: bool,
+    pub(super) channel_view: Option<ChannelView>,
+    pub(super) channel_view_texture: Option<egui::TextureId>, pub(super) channel_view_built: Option<(ChannelView, u64)>,
     pub(super) resize_w: u32, pub(super) resize_h: u32,
     pub(super) resize_locked: bool, pub(super) resize_stretch: bool,
     pub(super) export_format: ExportFormat,
     pub(super) export_jpeg_quality: u8, pub(super) export_avif_quality: u8,
     pub(super) export_avif_speed: u8, pub(super) export_preserve_metadata: bool,
-    pub(super) export_auto_scale_ico: bool,
-    pub(super) export_callback: Option<Box<dyn Fn(PathBuf) + Send + Sync>>,
+    pub(super) export_auto_scale_ico: bool, pub(super) export_max_dim: Option<u32>,
+    pub(super) export_presets: ExportPresets, pub(super) export_preset_name: String,
+    pub(super) export_viewport: Option<ViewportExport>,
+    pub(super) export_memory: ExportMemory, pub(super) export_preset_chosen: bool,
+    pub(super) export_callback: Option<std::sync::Arc<dyn Fn(PathBuf) + Send + Sync>>,
+    pub(super) variant_scales: [bool; 3], pub(super) variant_custom: Option<f32>, pub(super) variant_base: f32,
+    pub(super) variant_pattern: String, pub(super) variant_export: Option<VariantExport>, pub(super) variant_confirm: bool, pub(super) data_uri_job: Option<DataUriJob>,
+    pub(super) journal_offer: Option<Vec<TextLayer>>, journal_hash: Option<u64>, journal_due: Option<std::time::Instant>,
+    pub(super) adjustments: Vec<Adjustment>, adjusted: Option<(AdjustKey, DynamicImage)>, adjust_job: Option<AdjustJob>,
+    adjust_key: AdjustKey, pub(super) adjust_gesture: bool, pub(super) bake_confirm: bool, bake_pending: bool,
+    pub(super) fill_tolerance: u32, pub(super) fill_global: bool, pub(super) fill_antialias: bool,
+    pub(super) fill_mode: FillMode, pub(super) secondary_color: egui::Color32, pub(super) fill_gradient: Option<((f32, f32), (f32, f32))>,
+    pub(super) shape_tool_width: f32, pub(super) shape_tool_filled: bool, pub(super) shape_tool_drag: Option<((f32, f32), (f32, f32))>, pub(super) fill_hover: Option<(egui::Pos2, f64)>, pub(super) fill_preview: Option<FillPreview>,
+    pub(super) selection: Option<Selection>, pub(super) wand_job: Option<WandJob>, pub(super) wand_tolerance: u32, pub(super) wand_contiguous: bool,
+    pub(super) comparison: Option<SavedComparison>,
+    pub(super) pending_new_document: Option<Box<ImageEditor>>,
+    pub(super) rename_buffer: Option<String>,
+    pub(super) overwrite_confirm: Option<PathBuf>,
+    pub(super) undo_pushes: u64,
+    pub(super) blank_canvas: Option<u64>,
+    pub(super) paste_key_down: bool,
+    pub(super) click_undo_mark: Option<u64>,
+    pub(super) path_replace_tx: Option<std::sync::mpsc::SyncSender<(PathBuf, PathBuf)>>,
     pub(super) show_color_picker: bool,
     pub(super) color_history: ColorHistory,
     pub(super) color_favorites: ColorFavorites,
     pub(super) color_fav_drag_src: Option<usize>,
+    pub(super) pinned_drag_src: Option<usize>,
     pub(super) hex_input: String,
+    pub(super) color_picker_hsl: bool,
+    pub(super) contrast_bg: egui::Color32,
     pub(super) canvas_rect: Option<egui::Rect>,
     pub(super) color_picker_rect: Option<egui::Rect>,
     pub(super) filter_panel_rect: Option<egui::Rect>,
-    pub(super) filter_progress: Arc<Mutex<f32>>,
-    pub(super) is_processing: bool,
+    pub(super) filter_job: Option<FilterJob>,
+    pub(super) next_filter_job: u64,
+    pub(super) filter_tx: Sender<FilterMessage>,
+    pub(super) filter_rx: Receiver<FilterMessage>,
     pub(super) processing_is_preview: bool,
-    pub(super) pending_filter_result: Arc<Mutex<Option<DynamicImage>>>,
     pub(super) retouch_mode: RetouchMode,
     pub(super) retouch_size: f32, pub(super) retouch_strength: f32, pub(super) retouch_softness: f32,
     pub(super) retouch_smudge_sample: [f32; 4],
@@ -527,10 +1190,13 @@ pub struct ImageEditor {
     pub(super) layer_images: std::collections::HashMap<u64, DynamicImage>,
     pub(super) composite_dirty: bool,
     pub(super) composite_dirty_rect: Option<[u32; 4]>,
+    /// Bumped each frame the canvas pixels changed; caches of anything computed from them key on it.
+    pub(super) pixel_revision: u64,
     pub(super) stroke_backdrop: Option<ImageBuffer<Rgba<u8>, Vec<u8>>>,
+    pub(super) stroke_blend: BlendMode,
     pub(super) backdrop_cache: Arc<Mutex<Option<ImageBuffer<Rgba<u8>, Vec<u8>>>>>,
     pub(super) backdrop_cache_for: u64,
-    pub(super) show_layers_panel: bool,
+    pub(super) show_layers_panel: bool, pub(super) focus_mode: bool,
     pub(super) layer_panel_width: f32,
     pub(super) layer_drag_src: Option<usize>,
     pub(super) layer_rename_id: Option<u64>,
@@ -543,64 +1209,121 @@ pub struct ImageEditor {
     pub(super) image_layer_texture_dirty: std::collections::HashSet<u64>,
     pub(super) image_layer_stroke_rects: std::collections::HashMap<u64, [u32; 4]>,
     pub(super) selected_image_layer: Option<u64>,
-    pub(super) image_drag: Option<ImageDrag>,
+    pub(super) image_drag: Option<BoxDrag>,
+    pub(super) shape_layers: Vec<ShapeLayer>, pub(super) selected_shape: Option<u64>,
+    pub(super) shape_drag: Option<BoxDrag>, pub(super) next_shape_id: u64,
     pub(super) next_image_layer_id: u64,
     pub(super) image_aspect_lock: bool,
+    pub(super) placing_image: Option<(u64, u64)>,
     pub(super) raster_layer_textures: std::collections::HashMap<u64, egui::TextureId>,
     pub(super) raster_layer_texture_dirty: std::collections::HashSet<u64>,
     pub(super) raster_layer_dirty_rects: std::collections::HashMap<u64, [u32; 4]>,
+    pub(super) pending_error: Option<EditorError>,
+    pub(super) session: SessionRecorder,
+    pub(super) session_capture_pending: bool,
+    pub(super) slices: Vec<ImageSlice>,
+    pub(super) guides: Vec<ImageSlice>, pub(super) show_guides: bool, pub(super) pixels_locked: bool, pub(super) hide_all_text: bool,
+    pub(super) selected_slice: Option<usize>,
+    pub(super) slice_drag: Option<(THandle, egui::Pos2)>,
+    pub(super) slice_drag_orig: Option<(f32, f32, f32, f32)>,
+    pub(super) slice_draft: Option<((f32, f32), (f32, f32))>,
+    pub(super) slice_pattern: String,
+    pub(super) slice_message: Option<String>,
+    pub(super) measure_active: Option<Measurement>,
+    pub(super) measurements: Vec<Measurement>,
+    pub(super) measure_dpi: f32,
+    pub(super) dimension_snap: DimensionSnap,
+    pub(super) loupe_pinned: bool,
+    pub(super) loupe_zoom: u32,
+    pub(super) loupe_round: bool,
+    pub(super) loupe_grid: bool,
+    /// The pixels under the loupe, keyed on their origin, side and the pixel revision they were read at.
+    pub(super) loupe_texture: Option<((i64, i64, u32, u64), egui::TextureHandle)>,
+    pub(super) adaptive_strokes: bool,
+    pub(super) perf: PerfStats,
+    pub(super) kb_cursor: Option<(f32, f32)>,
+    pub(super) kb_click_pending: bool,
+    pub(super) panel_positions: PanelPositions,
+    pub(super) tool_memory: ToolMemory, pub(super) tool_memory_changed_at: Option<f64>,
+    pub(super) saved_hash: Option<u64>, pub(super) pending_notice: Option<String>, pub(super) pending_share: Option<PathBuf>,
+    pub(super) font_relinks: std::collections::HashMap<String, String>, pub(super) font_banner_dismissed: bool, pub(super) font_relink_dialog: Option<Vec<(String, String)>>,
+    pub(super) source_info: Option<SourceInfo>, pub(super) conversion_dismissed: bool, pub(super) muted_conversions: MutedConversions,
+    pub(super) text_galley_cache: std::collections::HashMap<u64, std::sync::Arc<egui::Galley>>,
+    pub(super) text_caret_maps: std::collections::HashMap<u64, Vec<(usize, bool)>>,
+    /// The clip this editor copied last, so a paste back into it is nudged off the original.
+    pub(super) own_clip: std::sync::Weak<ImageClip>,
 }
 
 impl ImageEditor {
     pub fn new() -> Self {
+        let mem = ToolMemory::seed();
+        let (export_presets, preset_warning) = ExportPresets::load();
+        let (filter_tx, filter_rx) = channel();
         Self {
-            image: None, texture: None, texture_dirty: false, texture_dirty_rect: None,
+            settings: ImageEditorSettings::default(), settings_changed: false,
+            image: None, texture: None, texture_dirty: false, texture_dirty_rect: None, canvas_proxy: CanvasProxy::default(),
             file_path: None, dirty: false,
-            undo_stack: VecDeque::new(), redo_stack: VecDeque::new(),
-            zoom: 1.0, pan: egui::Vec2::ZERO, fit_on_next_frame: true,
-            tool: Tool::Brush,
-            brush: BrushSettings::default(), brush_favorites: BrushFavorites::load(),
+            history: UndoHistory::new(MAX_UNDO),
+            zoom: 1.0, pan: egui::Vec2::ZERO, fit_on_next_frame: true, zoom_mode: ZoomMode::Fit, view_size: egui::Vec2::ZERO,
+            tool: mem.tool,
+            brush: mem.brush.clone(), brush_favorites: BrushFavorites::load(),
             brush_fav_name: String::new(), brush_preview_texture: None,
             brush_preview_cache_key: None,
-            eraser_size: 20.0, eraser_transparent: false,
+            eraser_size: mem.eraser_size, eraser_transparent: mem.eraser_transparent,
             color: egui::Color32::BLACK,
-            stroke_points: Vec::new(), is_dragging: false,
-            text_layers: Vec::new(), selected_text: None, editing_text: false,
-            next_text_id: 0, text_font_size: 24.0,
-            text_bold: false, text_italic: false, text_underline: false,
-            text_font_name: "Ubuntu".to_string(),
-            text_drag: None, text_cursor: 0, text_sel_anchor: None,
-            crop_state: CropState::default(), crop_drag: None, crop_drag_orig: None,
+            stroke_points: Vec::new(), is_dragging: false, space_pan: false,
+            text_layers: Vec::new(), deleted_text: Vec::new(), selected_text: None, editing_text: false,
+            next_text_id: 0, text_font_size: mem.text_font_size,
+            text_bold: mem.text_bold, text_italic: mem.text_italic, text_underline: mem.text_underline,
+            text_font_name: mem.text_font_name.clone(), text_direction: mem.text_direction, text_tab_width: mem.text_tab_width,
+            text_drag: None, text_cursor: 0, text_sel_anchor: None, caret_epoch: None,
+            crop_state: CropState::default(), selection_state: SelectionState::default(), crop_drag: None, crop_drag_orig: None,
             filter_panel: FilterPanel::None,
             brightness: 0.0, contrast: 0.0, hue: 0.0, saturation: 0.0,
             blur_radius: 3.0, sharpen_amount: 1.0,
+            equalize_mode: EqualizeMode::Global, clahe_tile: 8, clahe_clip: 2.0,
+            threshold_level: 128, threshold_soft: 0, threshold_hist: None,
+            mixer: MIXER_IDENTITY,
+            stroke_width: 8.0, stroke_color: egui::Color32::BLACK, stroke_position: StrokePosition::Inside,
+            stroke_corner_radius: 0.0, stroke_new_layer: false,
+            channel_view: None,
+            channel_view_texture: None, channel_view_built: None,
             resize_w: 0, resize_h: 0, resize_locked: true, resize_stretch: false,
             export_format: ExportFormat::Png,
             export_jpeg_quality: 90, export_avif_quality: 80, export_avif_speed: 4,
-            export_preserve_metadata: true, export_auto_scale_ico: true,
+            export_preserve_metadata: true, export_auto_scale_ico: true, export_max_dim: None,
+            export_presets, export_preset_name: String::new(), export_viewport: None,
+            export_memory: ExportMemory::load(), export_preset_chosen: false,
             export_callback: None,
+            variant_scales: [true, true, true], variant_custom: None, variant_base: 1.0,
+            variant_pattern: "{name}@{scale}x".to_string(), variant_export: None, variant_confirm: false, data_uri_job: None,
+            journal_offer: None, journal_hash: None, journal_due: None,
+            adjustments: Vec::new(), adjusted: None, adjust_job: None, adjust_key: (Vec::new(), 0), adjust_gesture: false, bake_confirm: false, bake_pending: false,
+            fill_tolerance: 30, fill_global: false, fill_antialias: true,
+            fill_mode: FillMode::Solid, secondary_color: egui::Color32::BLACK, fill_gradient: None,
+            shape_tool_width: 4.0, shape_tool_filled: false, shape_tool_drag: None, fill_hover: None, fill_preview: None,
+            selection: None, wand_job: None, wand_tolerance: 30, wand_contiguous: true, comparison: None,
+            pending_new_document: None, rename_buffer: None, overwrite_confirm: None, path_replace_tx: None, undo_pushes: 0, blank_canvas: None, paste_key_down: false, click_undo_mark: None,
             show_color_picker: false, color_history: ColorHistory::load(),
-            color_favorites: ColorFavorites::load(), color_fav_drag_src: None,
-            hex_input: String::from("#000000FF"), canvas_rect: None,
+            color_favorites: ColorFavorites::load(), color_fav_drag_src: None, pinned_drag_src: None,
+            hex_input: String::from("#000000FF"), color_picker_hsl: false, contrast_bg: egui::Color32::WHITE, canvas_rect: None,
             color_picker_rect: None, filter_panel_rect: None,
-            filter_progress: Arc::new(Mutex::new(0.0)),
-            is_processing: false, processing_is_preview: false,
-            pending_filter_result: Arc::new(Mutex::new(None)),
-            retouch_mode: RetouchMode::Blur,
-            retouch_size: 40.0, retouch_strength: 0.5, retouch_softness: 0.7,
-            retouch_smudge_sample: [0.0; 4], retouch_pixelate_block: 12,
+            filter_job: None, next_filter_job: 0, filter_tx, filter_rx, processing_is_preview: false,
+            retouch_mode: mem.retouch_mode,
+            retouch_size: mem.retouch_size, retouch_strength: mem.retouch_strength, retouch_softness: mem.retouch_softness,
+            retouch_smudge_sample: [0.0; 4], retouch_pixelate_block: mem.retouch_pixelate_block,
             filter_preview_active: false, filter_preview_snapshot: None,
             layers: vec![ImageLayer {
                 id: 0, name: "Background".to_string(), opacity: 1.0,
                 visible: true, locked: false, blend_mode: BlendMode::Normal,
-                kind: LayerKind::Background, linked_text_id: None, linked_image_id: None,
+                kind: LayerKind::Background, linked_text_id: None, linked_image_id: None, linked_shape_id: None,
             }],
             active_layer_id: 0, next_layer_id: 1,
             layer_images: std::collections::HashMap::new(),
-            composite_dirty: false, composite_dirty_rect: None,
-            stroke_backdrop: None,
+            composite_dirty: false, composite_dirty_rect: None, pixel_revision: 0,
+            stroke_backdrop: None, stroke_blend: BlendMode::Normal,
             backdrop_cache: Arc::new(Mutex::new(None)), backdrop_cache_for: u64::MAX,
-            show_layers_panel: true, layer_panel_width: 240.0,
+            show_layers_panel: true, focus_mode: false, layer_panel_width: 240.0,
             layer_drag_src: None, layer_rename_id: None, layer_rename_buf: String::new(),
             filter_target_layer_id: 0, checker_texture: None, checker_texture_dark: false,
             image_layer_data: std::collections::HashMap::new(),
@@ -608,28 +1331,43 @@ impl ImageEditor {
             image_layer_texture_dirty: std::collections::HashSet::new(),
             image_layer_stroke_rects: std::collections::HashMap::new(),
             selected_image_layer: None, image_drag: None, next_image_layer_id: 0,
+            shape_layers: Vec::new(), selected_shape: None, shape_drag: None, next_shape_id: 0,
             image_aspect_lock: true,
+            placing_image: None,
             raster_layer_textures: std::collections::HashMap::new(),
             raster_layer_texture_dirty: std::collections::HashSet::new(),
             raster_layer_dirty_rects: std::collections::HashMap::new(),
+            pending_error: None,
+            session: SessionRecorder::default(), session_capture_pending: false,
+            slices: Vec::new(), guides: Vec::new(), show_guides: true, pixels_locked: false, hide_all_text: false, selected_slice: None, slice_drag: None, slice_drag_orig: None, kb_cursor: None, kb_click_pending: false, panel_positions: PanelPositions::load(), slice_draft: None,
+            measure_active: None, measurements: Vec::new(), measure_dpi: mem.measure_dpi, dimension_snap: mem.dimension_snap,
+            loupe_pinned: false, loupe_zoom: mem.loupe_zoom, loupe_round: mem.loupe_round, loupe_grid: mem.loupe_grid, loupe_texture: None,
+            adaptive_strokes: mem.adaptive_strokes, perf: PerfStats::default(),
+            tool_memory: mem, tool_memory_changed_at: None,
+            saved_hash: None, pending_share: None, pending_notice: preset_warning,
+            font_relinks: std::collections::HashMap::new(), font_banner_dismissed: false, font_relink_dialog: None,
+            source_info: None, conversion_dismissed: false, muted_conversions: MutedConversions::load(),
+            text_galley_cache: std::collections::HashMap::new(),
+            text_caret_maps: std::collections::HashMap::new(),
+            slice_pattern: "{name}".to_string(), slice_message: None,
+            own_clip: std::sync::Weak::new(),
         }
     }
 
-    pub fn load(path: PathBuf) -> Self {
+    pub fn load(path: PathBuf) -> EditorResult<Self> {
         let mut editor = Self::new();
-        let img = ImageReader::open(&path).ok()
-            .and_then(|r| r.with_guessed_format().ok())
-            .and_then(|r| r.decode().ok())
-            .or_else(|| image::open(&path).ok());
-        if let Some(img) = img {
-            editor.resize_w = img.width();
-            editor.resize_h = img.height();
-            editor.image = Some(DynamicImage::ImageRgba8(img.into_rgba8()));
-            editor.texture_dirty = true;
-            editor.composite_dirty = true;
-            editor.file_path = Some(path);
-        }
-        editor
+        let img = match read_with_source(&path) {
+            Ok((img, source)) => { editor.source_info = Some(source); img }
+            Err(_) => image::open(&path).map_err(|e| EditorError::decode(&path, e))?,
+        };
+        editor.resize_w = img.width();
+        editor.resize_h = img.height();
+        editor.image = Some(DynamicImage::ImageRgba8(img.into_rgba8()));
+        editor.texture_dirty = true;
+        editor.composite_dirty = true;
+        if let Some(view) = super::ie_cache::load_view_state(&path) { editor.restore_view_state(view); }
+        editor.file_path = Some(path);
+        Ok(editor)
     }
 
     pub fn from_image(img: DynamicImage) -> Self {
@@ -643,9 +1381,24 @@ impl ImageEditor {
         editor
     }
 
+    pub fn from_canvas(spec: &crate::templates::CanvasSpec) -> Self {
+        let fill = if spec.transparent { Rgba([0, 0, 0, 0]) } else { Rgba([255, 255, 255, 255]) };
+        let mut editor = Self::from_image(DynamicImage::ImageRgba8(ImageBuffer::from_pixel(spec.width, spec.height, fill)));
+        editor.guides = spec.guides.iter().map(|g| ImageSlice { name: g.name.clone(), x: g.x, y: g.y, w: g.w, h: g.h }).collect();
+        editor.dirty = true;
+        editor
+    }
+
+    pub fn from_template_image(img: DynamicImage) -> Self {
+        let mut editor = Self::from_image(img);
+        editor.dirty = true;
+        editor
+    }
+
     pub fn is_dirty(&self) -> bool { self.dirty }
+    pub fn set_path_replace_tx(&mut self, tx: std::sync::mpsc::SyncSender<(PathBuf, PathBuf)>) { self.path_replace_tx = Some(tx); }
     pub fn set_file_callback(&mut self, callback: Box<dyn Fn(PathBuf) + Send + Sync>) {
-        self.export_callback = Some(callback);
+        self.export_callback = Some(callback.into());
     }
     pub(super) fn add_color_to_history(&mut self) {
         self.color_history.add_color(RgbaColor::from_egui(self.color));
@@ -662,10 +1415,13 @@ impl ImageEditor {
             next_text_id: self.next_text_id,
             image_layer_data: self.image_layer_data.clone(),
             next_image_layer_id: self.next_image_layer_id,
+            adjustments: self.adjustments.clone(),
+            shape_layers: self.shape_layers.clone(), next_shape_id: self.next_shape_id,
         }
     }
 
     pub(super) fn restore_undo_snapshot(&mut self, entry: LayerUndoEntry) {
+        self.clear_fill_preview();
         self.image = entry.image;
         self.layer_images = entry.layer_images;
         self.layers = entry.layers;
@@ -679,6 +1435,13 @@ impl ImageEditor {
         for id in &new_keys { self.image_layer_texture_dirty.insert(*id); }
         self.image_layer_data = entry.image_layer_data;
         self.next_image_layer_id = entry.next_image_layer_id;
+        self.shape_layers = entry.shape_layers;
+        self.next_shape_id = entry.next_shape_id;
+        self.shape_drag = None;
+        if self.selected_shape.is_some_and(|id| !self.shape_layers.iter().any(|s| s.id == id)) { self.selected_shape = None; }
+        let adjustments_changed = self.adjustments != entry.adjustments;
+        self.adjustments = entry.adjustments;
+        if adjustments_changed || self.adjustments_active() { self.refresh_adjustments(); }
         self.raster_layer_texture_dirty.clear();
         self.raster_layer_dirty_rects.clear();
         for l in &self.layers {
@@ -691,10 +1454,196 @@ impl ImageEditor {
         self.backdrop_cache_for = u64::MAX;
     }
 
-    pub(super) fn push_undo(&mut self) {
-        self.redo_stack.clear();
-        self.undo_stack.push_back(self.take_undo_snapshot());
-        if self.undo_stack.len() > MAX_UNDO { self.undo_stack.pop_front(); }
+    pub(super) fn document_hash(&self) -> u64 {
+        use std::hash::{Hash, Hasher};
+        let mut h = std::collections::hash_map::DefaultHasher::new();
+        let hash_img = |h: &mut std::collections::hash_map::DefaultHasher, img: &DynamicImage| { (img.width(), img.height(), img.color() as u8).hash(h); img.as_bytes().hash(h); };
+        if let Some(img) = &self.image { hash_img(&mut h, img); }
+        for l in &self.layers {
+            (l.id, &l.name, l.opacity.to_bits(), l.visible, l.locked, l.blend_mode as u8, l.kind as u8, l.linked_text_id, l.linked_image_id, l.linked_shape_id).hash(&mut h);
+            if let Some(img) = self.layer_images.get(&l.id) { hash_img(&mut h, img); }
+        }
+        Self::hash_text_layers(&self.text_layers, &mut h);
+        let mut ids: Vec<&u64> = self.image_layer_data.keys().collect();
+        ids.sort_unstable();
+        for id in ids {
+            let d = &self.image_layer_data[id];
+            (id, d.canvas_x.to_bits(), d.canvas_y.to_bits(), d.display_w.to_bits(), d.display_h.to_bits(), d.rotation.to_bits(), d.flip_h, d.flip_v).hash(&mut h);
+            hash_img(&mut h, &d.image);
+        }
+        self.pixels_locked.hash(&mut h);
+        self.hide_all_text.hash(&mut h);
+        for t in &self.text_layers { t.canvas_hidden.hash(&mut h); }
+        for a in &self.adjustments { format!("{:?}", a).hash(&mut h); }
+        for s in &self.shape_layers { format!("{:?}", s).hash(&mut h); }
+        h.finish()
+    }
+
+    fn hash_text_layers(layers: &[TextLayer], h: &mut std::collections::hash_map::DefaultHasher) {
+        use std::hash::Hash;
+        for t in layers {
+            (t.id, &t.content, t.img_x.to_bits(), t.img_y.to_bits(), t.font_size.to_bits(), t.rotation.to_bits(), t.box_width.map(f32::to_bits), t.box_height.map(f32::to_bits)).hash(h);
+            (t.color.to_array(), t.bold, t.italic, t.underline, &t.font_name, &t.font_substitute, t.direction).hash(h);
+        }
+    }
+
+    pub(super) fn text_layers_hash(layers: &[TextLayer]) -> u64 {
+        use std::hash::Hasher;
+        let mut h = std::collections::hash_map::DefaultHasher::new();
+        Self::hash_text_layers(layers, &mut h);
+        h.finish()
+    }
+
+    pub(super) fn tick_text_journal(&mut self, ctx: &egui::Context) {
+        if self.file_path.is_none() || self.journal_offer.is_some() { return; }
+        let hash = Self::text_layers_hash(&self.text_layers);
+        if self.journal_hash.replace(hash).is_some_and(|h| h != hash) { self.journal_due = Some(std::time::Instant::now() + JOURNAL_DELAY); }
+        let Some(due) = self.journal_due else { return };
+        if !self.dirty { self.journal_due = None; return; }
+        if self.editing_text || ctx.input(|i| i.pointer.any_down()) { return; }
+        let now = std::time::Instant::now();
+        if now < due { ctx.request_repaint_after(due - now); return; }
+        self.journal_due = None;
+        if let Err(e) = super::ie_cache::write_text_journal(self) { log::warn!("text layer journal was not written: {}", e); }
+    }
+
+    pub(super) fn restore_text_journal(&mut self) {
+        let Some(layers) = self.journal_offer.take() else { return };
+        self.commit_or_discard_active_text();
+        self.push_undo();
+        let ids: Vec<u64> = layers.iter().map(|t| t.id).collect();
+        self.layers.retain(|l| l.kind != LayerKind::Text || l.linked_text_id.is_some_and(|id| ids.contains(&id)));
+        if !self.layers.iter().any(|l| l.id == self.active_layer_id) { self.active_layer_id = self.layers.last().map_or(0, |l| l.id); }
+        self.text_layers = layers;
+        for &id in &ids { self.ensure_layer_entry_for_text(id); }
+        self.next_text_id = self.next_text_id.max(ids.iter().max().map_or(0, |m| m + 1));
+        self.selected_text = None;
+        self.resolve_text_fonts();
+        self.composite_dirty = true; self.texture_dirty = true; self.dirty = true;
+    }
+
+    pub(super) fn discard_text_journal(&mut self) {
+        self.journal_offer = None;
+        if let Some(p) = &self.file_path { super::ie_cache::delete_text_journal(p); }
+    }
+
+    pub(super) fn pixels_blocked(&mut self) -> bool {
+        if self.pixels_locked { self.pending_notice = Some("Pixels are locked. Unlock them to paint, crop or filter.".to_string()); return true; }
+        if self.adjustments_active() { self.bake_confirm = true; return true; }
+        false
+    }
+
+    pub(super) fn base_frozen(&self) -> bool { self.pixels_locked || self.adjustments_active() }
+
+    pub(super) fn adjustments_active(&self) -> bool { self.adjustments.iter().any(|a| a.enabled) }
+
+    pub(super) fn refresh_adjustments(&mut self) {
+        self.composite_dirty = true;
+        if !self.adjustments_active() {
+            if let Some(job) = self.adjust_job.take() { job.task.cancel(); }
+            (self.adjusted, self.bake_pending) = (None, false);
+            return;
+        }
+        let key = (self.adjustments.clone(), self.pixel_revision);
+        if key == self.adjust_key && (self.adjust_job.is_some() || self.adjusted.is_some()) { return; }
+        if let Some(job) = self.adjust_job.take() { job.task.cancel(); }
+        self.adjust_key = key.clone();
+        let Some(img) = self.image.clone() else { return };
+        let result = Arc::new(std::sync::OnceLock::new());
+        let (out, stack) = (result.clone(), key.0.clone());
+        // Not user-cancellable: output waits on `result`, which a task cancelled before it starts would never fill.
+        let task = tasks::spawn("Apply adjustments", false, move |task| { let _ = out.set(apply_stack(&img, &stack, task.cancel_flag())); });
+        self.adjust_job = Some(AdjustJob { key, task, result });
+    }
+
+    pub(super) fn check_adjustments(&mut self, ctx: &egui::Context) {
+        let Some(job) = self.adjust_job.take() else { return };
+        if job.result.get().is_none() { self.adjust_job = Some(job); ctx.request_repaint_after(std::time::Duration::from_millis(30)); return; }
+        match Arc::try_unwrap(job.result) {
+            Ok(cell) => if let Some(img) = cell.into_inner().flatten() {
+                self.adjusted = Some((job.key, img));
+                self.composite_dirty = true;
+                if self.bake_pending { self.bake_adjustments(); }
+            },
+            Err(result) => { self.adjust_job = Some(AdjustJob { result, ..job }); ctx.request_repaint(); }
+        }
+    }
+
+    fn adjusted_current(&self) -> Option<&DynamicImage> {
+        self.adjusted.as_ref().filter(|(key, _)| *key == self.adjust_key).map(|(_, img)| img)
+    }
+
+    /// The background with the adjustment stack applied. Output that needs it while the stack is still being
+    /// applied waits for that task rather than running the stack again on this thread.
+    pub(super) fn adjusted_base(&self) -> Option<&DynamicImage> {
+        let img = self.image.as_ref()?;
+        if !self.adjustments_active() { return Some(img); }
+        if let Some(a) = self.adjusted_current() { return Some(a); }
+        match &self.adjust_job {
+            Some(job) => job.result.wait().as_ref().or(Some(img)),
+            None => Some(img),
+        }
+    }
+
+    /// Bakes once the stack's result is ready, as a single undo step.
+    pub(super) fn bake_adjustments(&mut self) {
+        self.bake_confirm = false;
+        if self.adjusted_current().is_none() { self.bake_pending = self.adjust_job.is_some(); return; }
+        let Some((_, baked)) = self.adjusted.take() else { return };
+        self.bake_pending = false;
+        self.push_undo();
+        self.image = Some(baked);
+        self.adjustments.clear();
+        self.refresh_adjustments();
+        self.texture_dirty = true; self.dirty = true;
+    }
+
+    pub(super) fn toggle_pixel_lock(&mut self) {
+        self.pixels_locked = !self.pixels_locked;
+        if self.pixels_locked {
+            if self.filter_preview_active { self.cancel_filter_preview(); }
+            if !matches!(self.filter_panel, FilterPanel::None | FilterPanel::Export | FilterPanel::Brush | FilterPanel::TimeLapse | FilterPanel::Adjustments) { self.filter_panel = FilterPanel::None; }
+            self.crop_state = CropState::default();
+        }
+        self.refresh_dirty_from_hash();
+    }
+
+    pub(super) fn apply_crop_selection(&mut self) {
+        if self.pixels_blocked() { return; }
+        if self.image_layer_for_active().is_some() { self.apply_crop_to_image_layer(); return; }
+        let (Some([x0, y0, x1, y1]), Some((w, h))) = (self.crop_rect(), self.image.as_ref().map(|i| i.dimensions())) else { return };
+        if self.refuse_crop_size(x1 - x0, y1 - y0, w, h) { return; }
+        self.push_undo(); self.apply_crop();
+    }
+
+    fn needs_layer_cache(&self) -> bool { self.layers.len() > 1 || !self.slices.is_empty() || !self.guides.is_empty() || self.pixels_locked || !self.adjustments.is_empty() }
+
+    pub(super) fn refresh_dirty_from_hash(&mut self) {
+        if let Some(saved) = self.saved_hash { self.dirty = self.document_hash() != saved; }
+    }
+
+    pub(super) fn push_undo(&mut self) { self.push_undo_entry(self.take_undo_snapshot()); }
+
+    pub(super) fn push_undo_entry(&mut self, entry: LayerUndoEntry) {
+        self.undo_pushes += 1;
+        self.clear_fill_preview();
+        self.history.push(entry, "");
+        if self.session.recording { self.session_capture_pending = true; }
+    }
+
+    pub(super) fn capture_session_frame(&mut self, ctx: &egui::Context) {
+        if !self.session_capture_pending || self.is_processing() || ctx.input(|i| i.pointer.any_down()) { return; }
+        self.session_capture_pending = false;
+        if !self.session.wants_frame() { return; }
+        if let Some(img) = self.composite_all_layers() { self.session.push(&img); }
+    }
+
+    pub(super) fn toggle_session_recording(&mut self) {
+        if !self.session.recording {
+            self.session.start();
+            if let Some(img) = self.composite_all_layers() { self.session.push(&img); }
+        } else if self.session.frames.is_empty() { self.session.stop(); }
+        else { self.session.confirm_discard = true; }
     }
 
     pub(super) fn cancel_filter_preview(&mut self) {
@@ -705,24 +1654,25 @@ impl ImageEditor {
 
     pub(super) fn accept_filter_preview(&mut self) {
         if let Some(snapshot) = self.filter_preview_snapshot.take() {
-            self.undo_stack.push_back(snapshot);
-            if self.undo_stack.len() > MAX_UNDO { self.undo_stack.pop_front(); }
-            self.redo_stack.clear();
+            self.history.push(snapshot, "");
         }
         self.filter_preview_active = false;
     }
 
     pub(super) fn undo(&mut self) {
-        if let Some(entry) = self.undo_stack.pop_back() {
-            self.redo_stack.push_back(self.take_undo_snapshot());
+        if self.selection_state.floating.is_some() { self.cancel_floating_selection(); return; }
+        if self.history.can_undo() && let Some(entry) = self.history.undo(self.take_undo_snapshot()) {
             self.restore_undo_snapshot(entry);
+            self.prune_deleted_text();
+            self.refresh_dirty_from_hash();
         }
     }
 
     pub(super) fn redo(&mut self) {
-        if let Some(entry) = self.redo_stack.pop_back() {
-            self.undo_stack.push_back(self.take_undo_snapshot());
+        if self.history.can_redo() && let Some(entry) = self.history.redo(self.take_undo_snapshot()) {
             self.restore_undo_snapshot(entry);
+            self.prune_deleted_text();
+            self.refresh_dirty_from_hash();
         }
     }
 
@@ -735,28 +1685,43 @@ impl ImageEditor {
                 let iid = layer.linked_image_id?;
                 self.image_layer_data.get(&iid).map(|ild| ild.image.clone())
             }
-            LayerKind::Text => None,
+            LayerKind::Text | LayerKind::Shape => None,
+        }
+    }
+
+    pub(super) fn composite_for_display(&self) -> Option<DynamicImage> {
+        if self.adjustments_active() && let Some((_, a)) = &self.adjusted { return Some(a.clone()); }
+        self.image.clone()
+    }
+
+    pub(super) fn composite_all_layers(&self) -> Option<DynamicImage> { self.composite_layers(true) }
+
+    pub(super) fn composite_layers(&self, include_text: bool) -> Option<DynamicImage> {
+        let mut result = self.composite_stack(&self.layers, include_text)?;
+        for tl in self.text_layers.iter().filter(|t| include_text && self.unlinked_text(t.id)) {
+            let base = DynamicImage::ImageRgba8(result.clone());
+            result = self.stamp_single_text_layer(&base, tl, 1.0).to_rgba8();
         }
+        Some(DynamicImage::ImageRgba8(result))
     }
 
-    pub(super) fn composite_for_display(&self) -> Option<DynamicImage> { self.image.clone() }
+    /// Text layers with no entry in the layer stack are drawn on top of it.
+    pub(super) fn unlinked_text(&self, tid: u64) -> bool { !self.layers.iter().any(|l| l.linked_text_id == Some(tid)) }
 
-    pub(super) fn composite_all_layers(&self) -> Option<DynamicImage> {
-        let bg = self.image.as_ref()?;
+    /// `layers`, a run of the layer stack, flattened onto a transparent canvas the size of the image.
+    pub(super) fn composite_stack(&self, layers: &[ImageLayer], include_text: bool) -> Option<ImageBuffer<Rgba<u8>, Vec<u8>>> {
+        let bg = self.adjusted_base()?;
         let (w, h) = (bg.width(), bg.height());
         let mut result: ImageBuffer<Rgba<u8>, Vec<u8>> = ImageBuffer::from_pixel(w, h, Rgba([0u8, 0, 0, 0]));
-        let mut linked: std::collections::HashSet<u64> = std::collections::HashSet::new();
 
-        for layer in &self.layers {
+        for layer in layers {
             if !layer.visible { continue; }
             match layer.kind {
                 LayerKind::Text => {
-                    if let Some(tid) = layer.linked_text_id {
-                        linked.insert(tid);
-                        if let Some(tl) = self.text_layers.iter().find(|t| t.id == tid).cloned() {
-                            let base = DynamicImage::ImageRgba8(result.clone());
-                            result = self.stamp_single_text_layer(&base, &tl, layer.opacity).to_rgba8();
-                        }
+                    if !include_text { continue; }
+                    if let Some(tid) = layer.linked_text_id && let Some(tl) = self.text_layers.iter().find(|t| t.id == tid) {
+                        let base = DynamicImage::ImageRgba8(result.clone());
+                        result = self.stamp_single_text_layer(&base, tl, layer.opacity).to_rgba8();
                     }
                 }
                 LayerKind::Image => {
@@ -766,6 +1731,11 @@ impl ImageEditor {
                         }
                     }
                 }
+                LayerKind::Shape => {
+                    if let Some(shape) = layer.linked_shape_id.and_then(|sid| self.shape_layers.iter().find(|s| s.id == sid)) {
+                        shape.stamp(&mut result, layer.opacity, layer.blend_mode);
+                    }
+                }
                 LayerKind::Background | LayerKind::Raster => {
                     let src = match layer.kind {
                         LayerKind::Background => Some(bg),
@@ -785,11 +1755,7 @@ impl ImageEditor {
                 }
             }
         }
-        for tl in self.text_layers.iter().filter(|t| !linked.contains(&t.id)) {
-            let base = DynamicImage::ImageRgba8(result.clone());
-            result = self.stamp_single_text_layer(&base, tl, 1.0).to_rgba8();
-        }
-        Some(DynamicImage::ImageRgba8(result))
+        Some(result)
     }
 
     pub(super) fn stamp_image_layer(composite: &mut ImageBuffer<Rgba<u8>, Vec<u8>>, ild: &ImageLayerData, layer_opacity: f32, blend_mode: BlendMode) {
@@ -850,7 +1816,8 @@ impl ImageEditor {
         let wy = [wt(1.0+fy), wt(fy), wt(1.0-fy), wt(2.0-fy)];
         let get = |xi: i32, yi: i32| -> [f32; 4] {
             let p = src.get_pixel(xi.clamp(0, w as i32-1) as u32, yi.clamp(0, h as i32-1) as u32).0;
-            [p[0] as f32/255.0, p[1] as f32/255.0, p[2] as f32/255.0, p[3] as f32/255.0]
+            let a = p[3] as f32/255.0;
+            [p[0] as f32/255.0*a, p[1] as f32/255.0*a, p[2] as f32/255.0*a, a]
         };
         let mut out = [0.0f32; 4];
         for dy in 0..4i32 {
@@ -860,13 +1827,15 @@ impl ImageEditor {
                 for c in 0..4 { out[c] += p[c] * w; }
             }
         }
+        let a = out[3].clamp(0.0, 1.0);
+        if a < 1e-6 { return [0, 0, 0, 0]; }
         [
-            (out[0].clamp(0.0,1.0)*255.0).round() as u8, (out[1].clamp(0.0,1.0)*255.0).round() as u8,
-            (out[2].clamp(0.0,1.0)*255.0).round() as u8, (out[3].clamp(0.0,1.0)*255.0).round() as u8,
+            ((out[0]/a).clamp(0.0,1.0)*255.0).round() as u8, ((out[1]/a).clamp(0.0,1.0)*255.0).round() as u8,
+            ((out[2]/a).clamp(0.0,1.0)*255.0).round() as u8, (a*255.0).round() as u8,
         ]
     }
 
-    fn insert_above_active(&self) -> usize {
+    pub(super) fn insert_above_active(&self) -> usize {
         self.layers.iter().rposition(|l| l.id == self.active_layer_id)
             .map(|pos| pos + 1).unwrap_or(self.layers.len())
     }
@@ -878,7 +1847,7 @@ impl ImageEditor {
         let layer = ImageLayer {
             id, name: format!("Layer {}", id), opacity: 1.0, visible: true, locked: false,
             blend_mode: BlendMode::Normal, kind: LayerKind::Raster,
-            linked_text_id: None, linked_image_id: None,
+            linked_text_id: None, linked_image_id: None, linked_shape_id: None,
         };
         let pos = self.insert_above_active();
         self.layers.insert(pos, layer);
@@ -891,9 +1860,9 @@ impl ImageEditor {
 
     pub(super) fn duplicate_active_layer(&mut self) {
         let Some(src_layer) = self.layers.iter().find(|l| l.id == self.active_layer_id) else { return };
-        let (src_kind, src_opacity, src_blend, src_name, src_text_id, src_image_id, src_locked) =
+        let (src_kind, src_opacity, src_blend, src_name, src_text_id, src_image_id, src_shape_id, src_locked) =
             (src_layer.kind, src_layer.opacity, src_layer.blend_mode, src_layer.name.clone(),
-             src_layer.linked_text_id, src_layer.linked_image_id, src_layer.locked);
+             src_layer.linked_text_id, src_layer.linked_image_id, src_layer.linked_shape_id, src_layer.locked);
         self.push_undo();
         let new_id = self.next_layer_id; self.next_layer_id += 1;
         let src_img = match src_kind {
@@ -922,6 +1891,12 @@ impl ImageEditor {
                 }
             }
         }
+        let new_shape_id = src_shape_id.and_then(|sid| self.shape_layers.iter().find(|s| s.id == sid).cloned()).map(|shape| {
+            let nsid = self.next_shape_id; self.next_shape_id += 1;
+            self.shape_layers.push(ShapeLayer { id: nsid, ..shape });
+            nsid
+        });
+        if new_shape_id.is_some() { self.selected_shape = new_shape_id; }
         let new_kind = if src_kind == LayerKind::Background { LayerKind::Raster } else { src_kind };
         let new_name = if src_kind == LayerKind::Background {
             format!("{} (copy)", src_name)
@@ -929,7 +1904,7 @@ impl ImageEditor {
         let new_layer = ImageLayer {
             id: new_id, name: new_name, opacity: src_opacity, visible: true, locked: src_locked,
             blend_mode: src_blend, kind: new_kind,
-            linked_text_id: new_text_id, linked_image_id: new_image_id,
+            linked_text_id: new_text_id, linked_image_id: new_image_id, linked_shape_id: new_shape_id,
         };
         let pos = self.insert_above_active();
         self.layers.insert(pos, new_layer);
@@ -954,7 +1929,7 @@ impl ImageEditor {
             self.raster_layer_dirty_rects.remove(&removed.id);
         }
         if removed.kind == LayerKind::Text {
-            if let Some(tid) = removed.linked_text_id { self.text_layers.retain(|t| t.id != tid); }
+            if let Some(tid) = removed.linked_text_id { self.bin_text_layer(tid); }
         }
         if removed.kind == LayerKind::Image {
             if let Some(iid) = removed.linked_image_id {
@@ -964,6 +1939,10 @@ impl ImageEditor {
                 if self.selected_image_layer == Some(iid) { self.selected_image_layer = None; }
             }
         }
+        if let Some(sid) = removed.linked_shape_id {
+            self.shape_layers.retain(|s| s.id != sid);
+            if self.selected_shape == Some(sid) { self.selected_shape = None; self.shape_drag = None; }
+        }
         let new_idx = if idx > 0 { idx - 1 } else { 0 };
         self.active_layer_id = self.layers.get(new_idx).map(|l| l.id).unwrap_or(0);
         self.composite_dirty = true;
@@ -971,27 +1950,36 @@ impl ImageEditor {
     }
 
     pub(super) fn merge_down(&mut self) {
+        if self.pixels_blocked() { return; }
         let Some(idx) = self.layers.iter().position(|l| l.id == self.active_layer_id) else { return };
         if idx == 0 { return; }
         let below_kind = self.layers[idx - 1].kind;
-        if matches!(below_kind, LayerKind::Text | LayerKind::Image) { return; }
+        if matches!(below_kind, LayerKind::Text | LayerKind::Image | LayerKind::Shape) { return; }
         self.push_undo();
-        let idx = if self.layers[idx].kind == LayerKind::Text {
-            let tid = match self.layers[idx].linked_text_id { Some(id) => id, None => return };
-            let tl = match self.text_layers.iter().find(|t| t.id == tid).cloned() { Some(t) => t, None => return };
-            let (cw, ch) = match &self.image { Some(i) => (i.width(), i.height()), None => return };
-            let base = DynamicImage::ImageRgba8(ImageBuffer::from_pixel(cw, ch, Rgba([0u8, 0, 0, 0])));
-            let rasterized = self.stamp_single_text_layer(&base, &tl, self.layers[idx].opacity);
-            let new_lid = self.next_layer_id; self.next_layer_id += 1;
-            let (name, blend, vis, locked) = (self.layers[idx].name.clone(), self.layers[idx].blend_mode, self.layers[idx].visible, self.layers[idx].locked);
-            self.layer_images.insert(new_lid, rasterized);
-            self.raster_layer_texture_dirty.insert(new_lid);
-            self.text_layers.retain(|t| t.id != tid);
-            if self.selected_text == Some(tid) { self.selected_text = None; self.editing_text = false; self.text_drag = None; }
-            self.layers[idx] = ImageLayer { id: new_lid, name, opacity: 1.0, visible: vis, locked, blend_mode: blend, kind: LayerKind::Raster, linked_text_id: None, linked_image_id: None };
-            self.active_layer_id = new_lid;
-            idx
-        } else { idx };
+        self.merge_layer_down(idx);
+    }
+
+    fn text_layer_to_raster(&mut self, idx: usize) -> bool {
+        let Some(tid) = self.layers[idx].linked_text_id else { return false };
+        let Some(tl) = self.text_layers.iter().find(|t| t.id == tid).cloned() else { return false };
+        let Some((cw, ch)) = self.image.as_ref().map(|i| (i.width(), i.height())) else { return false };
+        let base = DynamicImage::ImageRgba8(ImageBuffer::from_pixel(cw, ch, Rgba([0u8, 0, 0, 0])));
+        let rasterized = self.stamp_single_text_layer(&base, &tl, self.layers[idx].opacity);
+        let new_lid = self.next_layer_id; self.next_layer_id += 1;
+        let (name, blend, vis, locked) = (self.layers[idx].name.clone(), self.layers[idx].blend_mode, self.layers[idx].visible, self.layers[idx].locked);
+        self.layer_images.insert(new_lid, rasterized);
+        self.raster_layer_texture_dirty.insert(new_lid);
+        self.text_layers.retain(|t| t.id != tid);
+        if self.selected_text == Some(tid) { self.selected_text = None; self.editing_text = false; self.text_drag = None; }
+        self.layers[idx] = ImageLayer { id: new_lid, name, opacity: 1.0, visible: vis, locked, blend_mode: blend, kind: LayerKind::Raster, linked_text_id: None, linked_image_id: None, linked_shape_id: None };
+        self.active_layer_id = new_lid;
+        true
+    }
+
+    pub(super) fn merge_layer_down(&mut self, idx: usize) {
+        let below_kind = self.layers[idx - 1].kind;
+        if self.layers[idx].kind == LayerKind::Text && !self.text_layer_to_raster(idx) { return; }
+        if self.layers[idx].kind == LayerKind::Shape && !self.shape_layer_to_raster(idx) { return; }
         let idx = if self.layers[idx].kind == LayerKind::Image {
             let (cw, ch) = match &self.image { Some(i) => (i.width(), i.height()), None => return };
             let iid = match self.layers[idx].linked_image_id { Some(id) => id, None => return };
@@ -999,13 +1987,13 @@ impl ImageEditor {
             let (opacity, blend, name) = (self.layers[idx].opacity, self.layers[idx].blend_mode, self.layers[idx].name.clone());
             let new_lid = self.next_layer_id; self.next_layer_id += 1;
             let mut raster = ImageBuffer::from_pixel(cw, ch, Rgba([0u8,0,0,0]));
-            Self::stamp_image_layer(&mut raster, &ild_clone, opacity, blend);
+            Self::stamp_image_layer(&mut raster, &ild_clone, 1.0, BlendMode::Normal);
             self.image_layer_data.remove(&iid);
             self.image_layer_texture_dirty.remove(&iid);
             if self.selected_image_layer == Some(iid) { self.selected_image_layer = None; }
             self.layer_images.insert(new_lid, DynamicImage::ImageRgba8(raster));
             self.raster_layer_texture_dirty.insert(new_lid);
-            self.layers[idx] = ImageLayer { id: new_lid, name, opacity: 1.0, visible: true, locked: false, blend_mode: BlendMode::Normal, kind: LayerKind::Raster, linked_text_id: None, linked_image_id: None };
+            self.layers[idx] = ImageLayer { id: new_lid, name, opacity, visible: true, locked: false, blend_mode: blend, kind: LayerKind::Raster, linked_text_id: None, linked_image_id: None, linked_shape_id: None };
             self.active_layer_id = new_lid;
             idx
         } else { idx };
@@ -1054,6 +2042,7 @@ impl ImageEditor {
     }
 
     pub(super) fn flatten_all_layers(&mut self) {
+        if self.pixels_blocked() { return; }
         if let Some(composite) = self.composite_all_layers() {
             self.push_undo();
             self.image = Some(composite);
@@ -1061,6 +2050,8 @@ impl ImageEditor {
             self.text_layers.clear();
             self.image_layer_data.clear();
             self.image_layer_texture_dirty.clear();
+            self.shape_layers.clear();
+            self.selected_shape = None;
             self.raster_layer_textures.clear();
             self.raster_layer_texture_dirty.clear();
             self.raster_layer_dirty_rects.clear();
@@ -1068,7 +2059,7 @@ impl ImageEditor {
             self.layers = vec![ImageLayer {
                 id: 0, name: "Background".to_string(), opacity: 1.0,
                 visible: true, locked: false, blend_mode: BlendMode::Normal,
-                kind: LayerKind::Background, linked_text_id: None, linked_image_id: None,
+                kind: LayerKind::Background, linked_text_id: None, linked_image_id: None, linked_shape_id: None,
             }];
             self.active_layer_id = 0;
             self.texture_dirty = true;
@@ -1077,13 +2068,45 @@ impl ImageEditor {
         }
     }
 
+    pub(super) fn flatten_text_layers(&mut self) {
+        if self.text_layers.is_empty() || self.image.is_none() || self.pixels_blocked() { return; }
+        self.commit_or_discard_active_text();
+        self.push_undo();
+        let active = self.active_layer_id;
+        for tid in self.text_layers.iter().map(|t| t.id).collect::<Vec<_>>() { self.ensure_layer_entry_for_text(tid); }
+        let text_ids: Vec<u64> = self.layers.iter().filter(|l| l.kind == LayerKind::Text).map(|l| l.id).collect();
+        for id in text_ids {
+            let Some(idx) = self.layers.iter().position(|l| l.id == id) else { continue };
+            let mergeable = idx > 0 && self.layers[idx].visible && {
+                let below = &self.layers[idx - 1];
+                matches!(below.kind, LayerKind::Background | LayerKind::Raster) && below.visible && below.opacity >= 1.0 && below.blend_mode == BlendMode::Normal
+            };
+            if mergeable { self.merge_layer_down(idx); }
+            else if !self.text_layer_to_raster(idx) { self.layers.remove(idx); }
+        }
+        self.text_layers.clear();
+        self.selected_text = None; self.editing_text = false; self.text_drag = None;
+        self.active_layer_id = if self.layers.iter().any(|l| l.id == active) { active } else { self.layers.last().map(|l| l.id).unwrap_or(0) };
+        self.texture_dirty = true;
+        self.composite_dirty = true;
+        self.dirty = true;
+    }
+
+    pub(super) fn duplicate_as_flattened(&mut self) {
+        let Some(composite) = self.composite_all_layers() else { return };
+        let mut editor = ImageEditor::from_image(composite);
+        editor.dirty = true;
+        editor.export_callback = self.export_callback.clone();
+        self.pending_new_document = Some(Box::new(editor));
+    }
+
     pub(super) fn ensure_layer_entry_for_text(&mut self, text_id: u64) {
         if self.layers.iter().any(|l| l.linked_text_id == Some(text_id)) { return; }
         let id = self.next_layer_id; self.next_layer_id += 1;
         let layer = ImageLayer {
             id, name: format!("Text {}", text_id + 1), opacity: 1.0, visible: true, locked: false,
             blend_mode: BlendMode::Normal, kind: LayerKind::Text,
-            linked_text_id: Some(text_id), linked_image_id: None,
+            linked_text_id: Some(text_id), linked_image_id: None, linked_shape_id: None,
         };
         let pos = self.insert_above_active();
         self.layers.insert(pos, layer);
@@ -1109,7 +2132,7 @@ impl ImageEditor {
         let layer = ImageLayer {
             id: lid, name: format!("Image {}", iid+1), opacity: 1.0, visible: true, locked: false,
             blend_mode: BlendMode::Normal, kind: LayerKind::Image,
-            linked_text_id: None, linked_image_id: Some(iid),
+            linked_text_id: None, linked_image_id: Some(iid), linked_shape_id: None,
         };
         let pos = self.insert_above_active();
         self.layers.insert(pos, layer);
@@ -1307,7 +2330,7 @@ impl ImageEditor {
         self.raster_layer_texture_dirty.insert(new_lid);
         self.text_layers.retain(|t| t.id != tid);
         if self.selected_text == Some(tid) { self.selected_text = None; self.editing_text = false; self.text_drag = None; }
-        self.layers[idx] = ImageLayer { id: new_lid, name, opacity, visible: vis, locked, blend_mode: blend, kind: LayerKind::Raster, linked_text_id: None, linked_image_id: None };
+        self.layers[idx] = ImageLayer { id: new_lid, name, opacity, visible: vis, locked, blend_mode: blend, kind: LayerKind::Raster, linked_text_id: None, linked_image_id: None, linked_shape_id: None };
         self.active_layer_id = new_lid;
         self.composite_dirty = true;
         self.dirty = true;
@@ -1334,7 +2357,7 @@ impl ImageEditor {
         self.layers[layer_idx] = ImageLayer {
             id: new_lid, name, opacity: 1.0, visible: true, locked: false,
             blend_mode: BlendMode::Normal, kind: LayerKind::Raster,
-            linked_text_id: None, linked_image_id: None,
+            linked_text_id: None, linked_image_id: None, linked_shape_id: None,
         };
         self.active_layer_id = new_lid;
         self.raster_layer_texture_dirty.insert(new_lid);
@@ -1395,6 +2418,25 @@ impl ImageEditor {
         tid
     }
 
+    pub(super) fn screen_to_image_f32(&self, pos: egui::Pos2) -> (f32, f32) {
+        let tl = self.image_to_screen(0.0, 0.0);
+        ((pos.x - tl.x) / self.zoom, (pos.y - tl.y) / self.zoom)
+    }
+
+    /// The part of the image visible in the canvas as `[x0, y0, x1, y1]`, or `None` when none of it is.
+    pub(super) fn visible_image_rect(&self) -> Option<[u32; 4]> {
+        let (canvas, img) = (self.canvas_rect?, self.image.as_ref()?);
+        let (tl, br) = (self.screen_to_image_f32(canvas.min), self.screen_to_image_f32(canvas.max));
+        let clamp = |v: f32, max: u32| v.clamp(0.0, max as f32) as u32;
+        let rect = [clamp(tl.0.floor(), img.width()), clamp(tl.1.floor(), img.height()), clamp(br.0.ceil(), img.width()), clamp(br.1.ceil(), img.height())];
+        (rect[2] > rect[0] && rect[3] > rect[1]).then_some(rect)
+    }
+
+    pub(super) fn viewport_crops_image(&self) -> bool {
+        let Some(img) = &self.image else { return false };
+        self.visible_image_rect().is_some_and(|r| r != [0, 0, img.width(), img.height()])
+    }
+
     pub(super) fn image_to_screen(&self, ix: f32, iy: f32) -> egui::Pos2 {
         let canvas = self.canvas_rect.unwrap_or(egui::Rect::NOTHING);
         let (img_w, img_h) = self.image.as_ref()
@@ -1406,14 +2448,32 @@ impl ImageEditor {
 
     pub(super) fn fit_image(&mut self) {
         if let (Some(img), Some(canvas)) = (&self.image, self.canvas_rect) {
-            let sx = canvas.width() / img.width() as f32;
-            let sy = canvas.height() / img.height() as f32;
-            self.zoom = sx.min(sy).min(1.0).max(0.01);
+            self.zoom = fit_zoom(img.width(), img.height(), canvas.size());
             self.pan = egui::Vec2::ZERO;
+            self.zoom_mode = ZoomMode::Fit;
+            self.view_size = canvas.size();
+        }
+    }
+
+    pub(super) fn set_zoom(&mut self, zoom: f32) {
+        if !zoom.is_finite() { return; }
+        self.zoom = zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+        self.zoom_mode = ZoomMode::Manual;
+    }
+
+    pub(super) fn follow_canvas_resize(&mut self) {
+        let Some(size) = self.canvas_rect.map(|r| r.size()) else { return };
+        if self.view_size == egui::Vec2::ZERO { self.view_size = size; return; }
+        if !canvas_resized(self.view_size, size) { return; }
+        match self.zoom_mode {
+            ZoomMode::Fit => self.fit_image(),
+            // Pan is measured from the canvas centre, so keeping it keeps the same image point centred.
+            ZoomMode::Manual => self.view_size = size,
         }
     }
 
     pub(super) fn new_image(&mut self, w: u32, h: u32) {
+        if self.refuse_canvas_size(w, h) { return; }
         self.push_undo();
         self.image = Some(DynamicImage::ImageRgba8(ImageBuffer::from_pixel(w, h, Rgba([255,255,255,255]))));
         self.resize_w = w; self.resize_h = h;
@@ -1421,7 +2481,32 @@ impl ImageEditor {
         self.file_path = None; self.dirty = true; self.fit_on_next_frame = true;
     }
 
+    /// The blank canvas an editor starts with, not yet saved or drawn on, which a pasted image replaces.
+    pub(super) fn untouched_blank(&self) -> bool { self.file_path.is_none() && self.blank_canvas == Some(self.undo_pushes) }
+
+    /// Reports an unusable canvas size to the user; true means the caller must not go ahead.
+    pub(super) fn refuse_canvas_size(&mut self, w: u32, h: u32) -> bool {
+        match check_canvas_size(w, h, self.settings.max_canvas_mp) {
+            Ok(()) => false,
+            Err(e) => { self.pending_error = Some(e); true }
+        }
+    }
+
+    /// Like `refuse_canvas_size` for crops, which must keep at least `MIN_CROP_SIDE` pixels on each side the image has room for.
+    pub(super) fn refuse_crop_size(&mut self, w: u32, h: u32, img_w: u32, img_h: u32) -> bool {
+        if w >= MIN_CROP_SIDE.min(img_w) && h >= MIN_CROP_SIDE.min(img_h) { return false; }
+        self.pending_notice = Some(format!("The crop area must be at least {0}×{0} pixels", MIN_CROP_SIDE));
+        true
+    }
+
+    /// Whether pixels changed since the textures were last uploaded.
+    pub(super) fn pixels_pending(&self) -> bool {
+        self.composite_dirty || self.texture_dirty || !self.raster_layer_texture_dirty.is_empty() || !self.image_layer_texture_dirty.is_empty()
+    }
+
     pub(super) fn ensure_texture(&mut self, ctx: &egui::Context) {
+        if self.texture_dirty || self.composite_dirty { self.canvas_proxy.invalidate(); }
+        if self.adjustments_active() && (self.texture_dirty || self.composite_dirty) { self.composite_dirty = true; self.composite_dirty_rect = None; }
         if self.composite_dirty {
             let partial = self.composite_dirty_rect.take();
             let tex_opt = self.texture;
@@ -1513,6 +2598,28 @@ impl ImageEditor {
         self.texture_dirty_rect = None;
     }
 
+    /// The grey texture for the channel being viewed, rebuilt only when the channel or the pixels change.
+    pub(super) fn ensure_channel_view_texture(&mut self, ctx: &egui::Context) -> Option<egui::TextureId> {
+        let key = (self.channel_view?, self.pixel_revision);
+        if self.channel_view_built == Some(key) { return self.channel_view_texture; }
+        self.channel_view_built = Some(key);
+        let view = key.0;
+        let rgba = self.composite_all_layers()?.to_rgba8();
+        let (w, h) = (rgba.width() as usize, rgba.height() as usize);
+        let pixels: Vec<egui::Color32> = rgba.pixels().map(|p| {
+            let v = match view { ChannelView::Red => p[0], ChannelView::Green => p[1], ChannelView::Blue => p[2], ChannelView::Alpha => p[3] };
+            egui::Color32::from_gray(v)
+        }).collect();
+        let color_image = egui::ColorImage { size: [w, h], source_size: egui::vec2(w as f32, h as f32), pixels };
+        let opts = egui::TextureOptions { magnification: egui::TextureFilter::Nearest, minification: egui::TextureFilter::Linear, ..Default::default() };
+        if let Some(tid) = self.channel_view_texture {
+            ctx.tex_manager().write().set(tid, egui::epaint::ImageDelta::full(color_image, opts));
+        } else {
+            self.channel_view_texture = Some(ctx.tex_manager().write().alloc("image_editor_channel".into(), color_image.into(), opts));
+        }
+        self.channel_view_texture
+    }
+
     fn has_visible_text_in_rect(&self, x0: u32, y0: u32, x1: u32, y1: u32) -> bool {
         self.text_layers.iter().any(|tl| {
             let bw = tl.box_width.unwrap_or_else(|| tl.auto_width(1.0)).ceil() as u32 + 1;
@@ -1532,7 +2639,7 @@ impl ImageEditor {
         for layer in &self.layers {
             if !layer.visible { continue; }
             match layer.kind {
-                LayerKind::Text | LayerKind::Image => continue,
+                LayerKind::Text | LayerKind::Image | LayerKind::Shape => continue,
                 LayerKind::Background | LayerKind::Raster => {
                     let src_buf: &ImageBuffer<Rgba<u8>, Vec<u8>> = match layer.kind {
                         LayerKind::Background => match bg { DynamicImage::ImageRgba8(b) => b, _ => continue },
@@ -1625,139 +2732,416 @@ impl ImageEditor {
         }
     }
 
+    pub(super) fn capture_tool_memory(&self) -> ToolMemory {
+        ToolMemory {
+            remember: self.settings.remember_tools, tool: self.tool, brush: self.brush.clone(),
+            eraser_size: self.eraser_size, eraser_transparent: self.eraser_transparent,
+            text_font_size: self.text_font_size, text_bold: self.text_bold, text_italic: self.text_italic,
+            text_underline: self.text_underline, text_font_name: self.text_font_name.clone(), text_direction: self.text_direction, text_tab_width: self.text_tab_width,
+            retouch_mode: self.retouch_mode, retouch_size: self.retouch_size, retouch_strength: self.retouch_strength,
+            retouch_softness: self.retouch_softness, retouch_pixelate_block: self.retouch_pixelate_block,
+            measure_dpi: self.measure_dpi, dimension_snap: self.dimension_snap,
+            loupe_zoom: self.loupe_zoom, loupe_round: self.loupe_round, loupe_grid: self.loupe_grid,
+            adaptive_strokes: self.adaptive_strokes,
+        }
+    }
+
+    fn sync_tool_memory(&mut self, ctx: &egui::Context) {
+        let now = ctx.input(|i| i.time);
+        let current = self.capture_tool_memory();
+        if current != self.tool_memory { self.tool_memory = current; self.tool_memory_changed_at = Some(now); }
+        let Some(changed) = self.tool_memory_changed_at else { return };
+        if now - changed < 1.0 || ctx.input(|i| i.pointer.any_down()) { ctx.request_repaint_after(std::time::Duration::from_millis(500)); return; }
+        self.tool_memory_changed_at = None;
+        if self.tool_memory.remember { self.tool_memory.save(); }
+    }
+
+    /// Puts the tools back to their defaults; the memory is updated too so the pending save doesn't undo the reset.
+    pub(super) fn reset_tools(&mut self) {
+        let mem = ToolMemory { remember: self.settings.remember_tools, ..ToolMemory::default() };
+        self.tool = mem.tool; self.brush = mem.brush.clone();
+        (self.eraser_size, self.eraser_transparent) = (mem.eraser_size, mem.eraser_transparent);
+        (self.text_font_size, self.text_bold, self.text_italic, self.text_underline) = (mem.text_font_size, mem.text_bold, mem.text_italic, mem.text_underline);
+        (self.text_font_name, self.text_direction, self.text_tab_width) = (mem.text_font_name.clone(), mem.text_direction, mem.text_tab_width);
+        (self.retouch_mode, self.retouch_size, self.retouch_strength) = (mem.retouch_mode, mem.retouch_size, mem.retouch_strength);
+        (self.retouch_softness, self.retouch_pixelate_block) = (mem.retouch_softness, mem.retouch_pixelate_block);
+        (self.measure_dpi, self.dimension_snap) = (mem.measure_dpi, mem.dimension_snap);
+        (self.loupe_zoom, self.loupe_round, self.loupe_grid) = (mem.loupe_zoom, mem.loupe_round, mem.loupe_grid);
+        self.adaptive_strokes = mem.adaptive_strokes;
+        self.tool_memory = mem;
+        self.tool_memory_changed_at = None;
+    }
+
+    pub fn remember_tool_settings() -> bool { ToolMemory::load().remember }
+
+    pub fn set_remember_tool_settings(remember: bool) { ToolMemory { remember, ..ToolMemory::load() }.save(); }
+
+    pub fn reset_tool_defaults() { ToolMemory { remember: ToolMemory::load().remember, ..ToolMemory::default() }.save(); }
+
+
+    pub(super) fn is_processing(&self) -> bool { self.filter_job.is_some() }
+
+    pub(super) fn start_filter_job(&mut self, name: &str, cancellable: bool) -> JobHandle {
+        if let Some(old) = self.filter_job.take() { old.task.cancel(); }
+        self.next_filter_job += 1;
+        let task = tasks::submit(if self.processing_is_preview { format!("{} preview", name) } else { name.to_string() }, cancellable);
+        let dims = self.image.as_ref().map(|img| (img.width(), img.height()));
+        self.filter_job = Some(FilterJob { id: self.next_filter_job, task: task.clone(), cancellable, dims });
+        JobHandle { id: self.next_filter_job, task, tx: Some(self.filter_tx.clone()) }
+    }
+
+    fn abandon_filter_job(&mut self) {
+        if self.processing_is_preview { self.cancel_filter_preview(); } else { self.history.discard_last(); }
+    }
+
+    pub(super) fn cancel_filter_job(&mut self) {
+        let Some(job) = self.filter_job.take_if(|j| j.cancellable) else { return };
+        job.task.cancel();
+        self.abandon_filter_job();
+    }
+
     pub(super) fn check_filter_completion(&mut self) {
-        if !self.is_processing { return; }
-        if *self.filter_progress.lock().unwrap() >= 1.0 {
-            if let Some(result) = self.pending_filter_result.lock().unwrap().take() {
-                let target_id = self.filter_target_layer_id;
-                let layer = self.layers.iter().find(|l| l.id == target_id);
-                let kind = layer.map(|l| l.kind).unwrap_or(LayerKind::Background);
-                let linked_iid = layer.and_then(|l| l.linked_image_id);
-                match kind {
-                    LayerKind::Background => {
-                        self.resize_w = result.width(); self.resize_h = result.height();
-                        self.image = Some(result);
-                    }
-                    LayerKind::Raster => {
-                        self.layer_images.insert(target_id, result);
-                        self.raster_layer_texture_dirty.insert(target_id);
-                        self.raster_layer_dirty_rects.remove(&target_id);
-                    }
-                    LayerKind::Image => {
-                        if let Some(iid) = linked_iid {
-                            if let Some(ild) = self.image_layer_data.get_mut(&iid) {
-                                ild.image = result;
-                            }
-                            self.image_layer_texture_dirty.insert(iid);
-                            self.image_layer_stroke_rects.remove(&iid);
-                        }
+        let mut outcome = None;
+        while let Ok((id, result)) = self.filter_rx.try_recv() {
+            if self.filter_job.as_ref().is_some_and(|j| j.id == id) { outcome = Some(result); }
+        }
+        let Some(result) = outcome else { return };
+        let cancelled = self.filter_job.take().is_some_and(|j| j.task.cancelled());
+        let Some(result) = result else {
+            self.abandon_filter_job();
+            if cancelled { return; }
+            self.pending_error = Some(EditorError::Other("The filter stopped before producing a result".to_string()));
+            return;
+        };
+        let target_id = self.filter_target_layer_id;
+        let layer = self.layers.iter().find(|l| l.id == target_id);
+        let kind = layer.map(|l| l.kind).unwrap_or(LayerKind::Background);
+        let linked_iid = layer.and_then(|l| l.linked_image_id);
+        let before = match kind {
+            LayerKind::Background => self.filter_preview_snapshot.as_ref().map_or(self.image.as_ref(), |s| s.image.as_ref()),
+            LayerKind::Raster => self.filter_preview_snapshot.as_ref().map_or(self.layer_images.get(&target_id), |s| s.layer_images.get(&target_id)),
+            _ => None,
+        };
+        let result = self.within_selection(before, result);
+        match kind {
+            LayerKind::Background => {
+                self.resize_w = result.width(); self.resize_h = result.height();
+                self.image = Some(result);
+            }
+            LayerKind::Raster => {
+                self.layer_images.insert(target_id, result);
+                self.raster_layer_texture_dirty.insert(target_id);
+                self.raster_layer_dirty_rects.remove(&target_id);
+            }
+            LayerKind::Image => {
+                if let Some(iid) = linked_iid {
+                    if let Some(ild) = self.image_layer_data.get_mut(&iid) {
+                        ild.image = result;
                     }
-                    _ => {}
-                }
-                self.texture_dirty = true; self.composite_dirty = true; self.dirty = true;
-                self.is_processing = false;
-                if self.processing_is_preview {
-                    self.processing_is_preview = false;
-                } else {
-                    self.filter_panel = FilterPanel::None;
-                    if self.resize_w != 0 { self.fit_on_next_frame = true; }
+                    self.image_layer_texture_dirty.insert(iid);
+                    self.image_layer_stroke_rects.remove(&iid);
                 }
             }
+            _ => {}
+        }
+        self.texture_dirty = true; self.composite_dirty = true; self.dirty = true;
+        if self.processing_is_preview {
+            self.processing_is_preview = false;
+        } else {
+            self.filter_panel = FilterPanel::None;
+            if self.resize_w != 0 { self.fit_on_next_frame = true; }
         }
     }
 
     pub(super) fn handle_keyboard(&mut self, ctx: &egui::Context) {
         self.process_text_input(ctx);
-        ctx.input_mut(|i| {
-            if i.consume_key(egui::Modifiers::CTRL, egui::Key::Z) { self.undo(); }
-            if i.consume_key(egui::Modifiers::CTRL | egui::Modifiers::SHIFT, egui::Key::Z) { self.redo(); }
-            if i.consume_key(egui::Modifiers::CTRL, egui::Key::Y) { self.redo(); }
-            if i.consume_key(egui::Modifiers::CTRL, egui::Key::S) {
-                if i.modifiers.shift { let _ = self.save_as_impl(); } else { let _ = self.save_impl(); }
-            }
-            if i.consume_key(egui::Modifiers::NONE, egui::Key::Escape) { self.commit_or_discard_active_text(); }
-            if i.consume_key(egui::Modifiers::CTRL | egui::Modifiers::SHIFT, egui::Key::N) { self.new_raster_layer(); }
-            if i.consume_key(egui::Modifiers::CTRL, egui::Key::E) { self.merge_down(); }
-        });
-        if !self.editing_text && ctx.memory(|m| m.focused().is_none()) {
+        if let Some((iid, _)) = self.placing_image && (self.selected_image_layer != Some(iid) || !self.image_layer_data.contains_key(&iid)) { self.placing_image = None; }
+        if self.tool != Tool::Select { self.commit_floating_selection(); }
+        let typing = crate::focus::typing(ctx);
+        let canvas = !self.editing_text && crate::focus::canvas_focused(ctx);
+        let paste = ctx.input(|i| paste_chord_released(&mut self.paste_key_down, &i.events));
+        let pressed = ctx.input_mut(|i| pressed_shortcuts(i, IE_SHORTCUTS, |a| match a {
+            ImageKey::Save | ImageKey::SaveAs => true,
+            ImageKey::Undo | ImageKey::Redo | ImageKey::Cancel | ImageKey::NewLayer | ImageKey::Diagnostics | ImageKey::Deselect
+                | ImageKey::InvertSelection | ImageKey::RepeatFilter | ImageKey::MergeDown => !typing,
+            _ => canvas,
+        }));
+        for (action, chord) in pressed { self.run_key(action, chord); }
+        if paste && !typing && !self.editing_text { self.paste_image(); }
+        if canvas {
             ctx.input_mut(|i| {
-                if i.consume_key(egui::Modifiers::NONE, egui::Key::B) { self.commit_or_discard_active_text(); self.tool = Tool::Brush; }
-                if i.consume_key(egui::Modifiers::NONE, egui::Key::E) { self.commit_or_discard_active_text(); self.tool = Tool::Eraser; }
-                if i.consume_key(egui::Modifiers::NONE, egui::Key::F) { self.commit_or_discard_active_text(); self.tool = Tool::Fill; }
-                if i.consume_key(egui::Modifiers::NONE, egui::Key::T) { self.tool = Tool::Text; }
-                if i.consume_key(egui::Modifiers::NONE, egui::Key::D) { self.commit_or_discard_active_text(); self.tool = Tool::Eyedropper; }
-                if i.consume_key(egui::Modifiers::NONE, egui::Key::C) { self.commit_or_discard_active_text(); self.tool = Tool::Crop; }
-                if i.consume_key(egui::Modifiers::NONE, egui::Key::P) { self.commit_or_discard_active_text(); self.tool = Tool::Pan; }
-                if i.consume_key(egui::Modifiers::NONE, egui::Key::R) { self.commit_or_discard_active_text(); self.tool = Tool::Retouch; }
-                if i.consume_key(egui::Modifiers::NONE, egui::Key::Enter) {
-                    if self.tool == Tool::Crop && self.crop_state.start.is_some() && self.crop_state.end.is_some() {
-                        if self.image_layer_for_active().is_some() { self.apply_crop_to_image_layer(); }
-                        else { self.push_undo(); self.apply_crop(); }
-                    }
+                if i.events.iter().any(|e| matches!(e, egui::Event::Copy)) {
+                    i.events.retain(|e| !matches!(e, egui::Event::Copy));
+                    self.commit_floating_selection();
+                    self.copy_image(false);
                 }
-                if i.consume_key(egui::Modifiers::NONE, egui::Key::Delete) || i.consume_key(egui::Modifiers::NONE, egui::Key::Backspace) {
-                    if self.selected_image_layer.is_some() && self.image_layer_for_active().is_some() {
-                        self.delete_active_layer();
-                    }
+                if i.events.iter().any(|e| matches!(e, egui::Event::Cut)) {
+                    i.events.retain(|e| !matches!(e, egui::Event::Cut));
+                    self.commit_floating_selection();
+                    self.cut_selection();
                 }
-                for (key, slot) in [
-                    (egui::Key::Num1,0usize),(egui::Key::Num2,1),(egui::Key::Num3,2),
-                    (egui::Key::Num4,3),(egui::Key::Num5,4),(egui::Key::Num6,5),
-                    (egui::Key::Num7,6),(egui::Key::Num8,7),(egui::Key::Num9,8),(egui::Key::Num0,9),
-                ] {
-                    if i.consume_key(egui::Modifiers::NONE, key) {
-                        if let Some(c) = self.color_favorites.colors.get(slot) {
-                            let mut col = *c; col.a = 255; self.color = col.to_egui(); self.hex_input = col.to_hex();
-                        }
+                let point_tool = matches!(self.tool, Tool::Brush | Tool::Eraser | Tool::Retouch | Tool::Fill | Tool::Eyedropper);
+                if point_tool || self.tool == Tool::Crop {
+                    let (ctrl, alt, shift) = (i.modifiers.ctrl, i.modifiers.alt, i.modifiers.shift);
+                    let mut d = (0.0f32, 0.0f32);
+                    for (key, dx, dy) in [(egui::Key::ArrowLeft, -1.0, 0.0), (egui::Key::ArrowRight, 1.0, 0.0), (egui::Key::ArrowUp, 0.0, -1.0), (egui::Key::ArrowDown, 0.0, 1.0)] {
+                        let n = i.count_and_consume_key(if ctrl { egui::Modifiers::CTRL } else { egui::Modifiers::NONE }, key) as f32;
+                        d.0 += dx * n; d.1 += dy * n;
+                    }
+                    let step = if shift { 10.0 } else { 1.0 };
+                    if d != (0.0, 0.0) {
+                        if self.tool == Tool::Crop { self.nudge_crop(d.0 * step, d.1 * step, alt, ctrl); }
+                        else { self.move_keyboard_cursor(d.0 * step, d.1 * step, i.key_down(egui::Key::Space)); }
                     }
                 }
-                if i.consume_key(egui::Modifiers::NONE, egui::Key::Home) { self.fit_image(); }
-                if i.consume_key(egui::Modifiers::NONE, egui::Key::Plus) { self.zoom *= 1.25; }
-                if i.consume_key(egui::Modifiers::NONE, egui::Key::Minus) { self.zoom = (self.zoom / 1.25).max(0.01); }
-                for (key, slot) in [
-                    (egui::Key::Num1,0usize),(egui::Key::Num2,1),(egui::Key::Num3,2),
-                    (egui::Key::Num4,3),(egui::Key::Num5,4),(egui::Key::Num6,5),
-                    (egui::Key::Num7,6),(egui::Key::Num8,7),(egui::Key::Num9,8),(egui::Key::Num0,9),
-                ] {
-                    if i.consume_key(egui::Modifiers::CTRL, key) {
-                        if let Some(b) = self.brush_favorites.brushes.get(slot) {
-                            self.brush = b.settings.clone();
-                            self.brush_preview_cache_key = None;
-                        }
+                if point_tool && self.kb_cursor.is_some() {
+                    let fresh_space = i.events.iter().any(|e| matches!(e, egui::Event::Key { key: egui::Key::Space, pressed: true, repeat: false, .. }));
+                    if i.consume_key(egui::Modifiers::NONE, egui::Key::Space) && fresh_space {
+                        self.kb_click_pending = true;
                     }
                 }
             });
         }
     }
 
-    pub(super) fn save_impl(&mut self) -> Result<(), String> {
+    /// Runs a key from `IE_SHORTCUTS`; `chord` is which of its chords fired, the slot for the digit favorites.
+    pub(super) fn run_key(&mut self, action: ImageKey, chord: usize) {
+        match action {
+            ImageKey::Save => self.pending_error = self.save_impl().err(),
+            ImageKey::SaveAs => self.pending_error = self.save_as_impl().err(),
+            ImageKey::Undo => self.undo(),
+            ImageKey::Redo => self.redo(),
+            ImageKey::Cancel => {
+                if self.placing_image.is_some() { self.cancel_placed_image(); }
+                else if self.selection_state.floating.is_some() { self.cancel_floating_selection(); }
+                else if self.comparison.is_some() { self.toggle_saved_comparison(); }
+                else if matches!(self.tool, Tool::Wand | Tool::Select) && self.selection.is_some() { self.selection = None; }
+                else if self.shape_tool_drag.is_some() { self.shape_tool_drag = None; }
+                else if !self.editing_text && (self.measure_active.is_some() || !self.measurements.is_empty()) { self.clear_measurements(); }
+                else { self.commit_or_discard_active_text(); }
+            }
+            ImageKey::NewLayer => self.new_raster_layer(),
+            ImageKey::Diagnostics => self.perf.overlay = !self.perf.overlay,
+            ImageKey::Deselect => self.selection = None,
+            ImageKey::InvertSelection => self.invert_selection(),
+            ImageKey::RepeatFilter => if !self.pixels_blocked() { self.repeat_last_filter(false); },
+            ImageKey::MergeDown => self.merge_down(),
+            ImageKey::Tool(Tool::Text) => self.tool = Tool::Text,
+            ImageKey::Tool(tool) => { self.commit_or_discard_active_text(); self.tool = tool; }
+            ImageKey::HideAllText => self.toggle_hide_all_text(),
+            ImageKey::ChannelView => self.channel_view = ChannelView::cycle(self.channel_view),
+            ImageKey::Confirm => {
+                if self.placing_image.is_some() { self.commit_placed_image(); }
+                else if self.selection_state.floating.is_some() { self.commit_floating_selection(); }
+                else if self.tool == Tool::Crop && self.crop_state.start.is_some() && self.crop_state.end.is_some() {
+                    self.apply_crop_selection();
+                }
+            }
+            ImageKey::Delete => {
+                if self.selection_state.floating.as_ref().is_some_and(|f| f.mask.is_some()) {
+                    self.selection_state.floating = None;
+                } else if self.selection_state.floating.is_some() {
+                    self.cancel_floating_selection();
+                } else if self.tool == Tool::Slice && self.selected_slice.is_some() {
+                    self.delete_selected_slice();
+                } else if (self.selected_image_layer.is_some() && self.image_layer_for_active().is_some())
+                    || (self.selected_shape.is_some() && self.layers.iter().any(|l| l.id == self.active_layer_id && l.linked_shape_id == self.selected_shape)) {
+                    self.delete_active_layer();
+                } else if let Some(id) = self.selected_text {
+                    self.delete_text_layer(id);
+                } else if self.selection.is_some() {
+                    self.delete_selected_pixels();
+                }
+            }
+            ImageKey::ColorFavorite => if let Some(c) = self.color_favorites.colors.get(chord) {
+                let mut col = *c; col.a = 255; self.color = col.to_egui(); self.hex_input = col.to_hex();
+            },
+            ImageKey::BrushFavorite => if let Some(b) = self.brush_favorites.brushes.get(chord) {
+                self.brush = b.settings.clone();
+                self.brush_preview_cache_key = None;
+            },
+            ImageKey::BrushSmaller => self.nudge_brush_size(-1.0),
+            ImageKey::BrushLarger => self.nudge_brush_size(1.0),
+            ImageKey::Fit => self.fit_image(),
+            ImageKey::ZoomIn => self.set_zoom(self.zoom * 1.25),
+            ImageKey::ZoomOut => self.set_zoom(self.zoom / 1.25),
+        }
+    }
+
+    pub(super) fn save_impl(&mut self) -> EditorResult<SaveState> {
+        self.commit_floating_selection();
         let path = match &self.file_path { Some(p) => p.clone(), None => return self.save_as_impl() };
         if self.image.is_some() {
-            let composite = self.composite_all_layers().ok_or("No image to save")?;
-            composite.save(&path).map_err(|e| e.to_string())?;
-            self.dirty = false;
-            if self.layers.len() > 1 { let _ = super::ie_cache::save_cache(self); }
+            let hash = self.document_hash();
+            if self.saved_hash == Some(hash) && path.exists() { self.dirty = false; self.pending_notice = Some("No changes to save".to_string()); return Ok(SaveState::Saved); }
+            let composite = self.composite_all_layers().ok_or(EditorError::Empty("no image is loaded"))?;
+            composite.save(&path).map_err(|e| EditorError::encode(&save_format_name(&path), &path, e))?;
+            self.dirty = false; self.saved_hash = Some(hash);
+            super::ie_cache::delete_text_journal(&path);
+            if self.needs_layer_cache() { self.pending_error = super::ie_cache::save_cache(self).err().map(|e| EditorError::Other(format!("Layers were not cached: {}", e))); }
         }
-        Ok(())
+        Ok(SaveState::Saved)
     }
 
-    pub(super) fn save_as_impl(&mut self) -> Result<(), String> {
-        if let Some(path) = rfd::FileDialog::new()
+    pub(super) fn save_as_impl(&mut self) -> EditorResult<SaveState> {
+        self.commit_floating_selection();
+        let Some(path) = rfd::FileDialog::new()
             .add_filter("Images", &["png", "jpg", "jpeg", "webp", "bmp", "tiff", "gif"])
-            .save_file()
-        {
-            if self.image.is_some() {
-                let composite = self.composite_all_layers().ok_or("No image to save")?;
-                composite.save(&path).map_err(|e| e.to_string())?;
-                self.file_path = Some(path);
-                self.dirty = false;
-                if self.layers.len() > 1 { let _ = super::ie_cache::save_cache(self); }
+            .save_file() else { return Err(EditorError::Cancelled) };
+        if self.image.is_some() && needs_overwrite_confirm(&path, self.file_path.as_deref()) {
+            if open_in_another_window(&path) { return Err(EditorError::open_elsewhere(&path)); }
+            self.overwrite_confirm = Some(path);
+            return Ok(SaveState::AwaitingConfirm);
+        }
+        self.save_to(path).map(|_| SaveState::Saved)
+    }
+
+    pub(super) fn confirm_overwrite(&mut self) {
+        let Some(path) = self.overwrite_confirm.take() else { return };
+        let res = release_overwrite_target(&path, self.settings.keep_backup).map_err(|e| EditorError::Other(format!("Could not move {} to the trash: {}", path.display(), e)))
+            .and_then(|_| self.save_to(path));
+        if let Err(e) = res { self.pending_error = Some(e); }
+    }
+
+    fn save_to(&mut self, path: PathBuf) -> EditorResult {
+        if self.image.is_some() {
+            let composite = self.composite_all_layers().ok_or(EditorError::Empty("no image is loaded"))?;
+            composite.save(&path).map_err(|e| EditorError::encode(&save_format_name(&path), &path, e))?;
+            if let Some(old) = &self.file_path { super::ie_cache::delete_text_journal(old); }
+            self.file_path = Some(path);
+            self.dirty = false; self.saved_hash = Some(self.document_hash());
+            if self.needs_layer_cache() { self.pending_error = super::ie_cache::save_cache(self).err().map(|e| EditorError::Other(format!("Layers were not cached: {}", e))); }
+        }
+        Ok(())
+    }
+}
+
+impl ImageEditor {
+    pub(super) fn handle_file_info_action(&mut self, action: FileInfoAction) {
+        if self.file_path.is_none() || matches!(action, FileInfoAction::SaveAs) {
+            if let Err(e) = self.save_as_impl() && !e.is_cancelled() { self.pending_error = Some(e); }
+            return;
+        }
+        let Some(path) = self.file_path.clone() else { return };
+        match action {
+            FileInfoAction::Reveal => if let Err(e) = reveal_in_file_manager(&path) {
+                self.pending_error = Some(EditorError::Other(format!("Could not open the file manager: {}", e)));
+            },
+            FileInfoAction::Rename => {
+                if self.dirty && let Err(e) = self.save_impl() { self.pending_error = Some(e); return; }
+                self.rename_buffer = Some(path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default());
             }
-            Ok(())
-        } else { Err("Cancelled".to_string()) }
+            FileInfoAction::SaveAs => {}
+        }
     }
+
+    pub(super) fn apply_rename(&mut self) {
+        let (Some(stem), Some(old)) = (self.rename_buffer.take(), self.file_path.clone()) else { return };
+        let stem = stem.trim();
+        if stem.is_empty() { return; }
+        let new = match old.extension() { Some(ext) => old.with_file_name(format!("{}.{}", stem, ext.to_string_lossy())), None => old.with_file_name(stem) };
+        let old_cache = super::ie_cache::cache_dir_for(&old);
+        if let Err(e) = rename_file(&old, &new) {
+            self.pending_error = Some(EditorError::Other(format!("Could not rename {}: {}", old.display(), e)));
+            return;
+        }
+        self.file_path = Some(new.clone());
+        if let Some(tx) = &self.path_replace_tx { let _ = tx.send((old, new)); }
+        if old_cache.exists() {
+            let _ = std::fs::remove_dir_all(&old_cache);
+            self.pending_error = super::ie_cache::save_cache(self).err().map(|e| EditorError::Other(format!("Layers were not cached: {}", e)));
+        }
+    }
+}
+
+fn pick_image() -> Option<DynamicImage> {
+    let path = rfd::FileDialog::new().add_filter("Images", &["png","jpg","jpeg","webp","bmp","tiff","tif","gif"]).pick_file()?;
+    image::ImageReader::open(&path).ok()
+        .and_then(|r| r.with_guessed_format().ok())
+        .and_then(|r| r.decode().ok())
+        .or_else(|| image::open(&path).ok())
+}
+
+fn save_format_name(path: &std::path::Path) -> String {
+    path.extension().and_then(|e| e.to_str()).map(|e| e.to_uppercase()).unwrap_or_else(|| "Image".to_string())
+}
+
+const IE_TOUR: &[crate::tour::TourStep] = &[
+    crate::tour::step("ie_toolbar", "Tools", "Brush, eraser, fill, text and more. Each tool has a one-key shortcut, listed under Help > Keyboard Shortcuts."),
+    crate::tour::step("ie_options", "Tool options", "Size, opacity and other settings for the current tool show up here."),
+    crate::tour::step("ie_color_swatch", "Color", "Click the swatch to open the color picker with history and favorites."),
+    crate::tour::step("ie_canvas", "Canvas", "Scroll to zoom. Fitting the image and resizing the brush have keys too; Help > Keyboard Shortcuts lists them."),
+];
+
+/// What the image editor's keys do; `IE_SHORTCUTS` binds them and `handle_keyboard` runs them.
+#[derive(Clone, Copy, PartialEq)]
+pub(super) enum ImageKey {
+    Save, SaveAs, Undo, Redo, Cancel, NewLayer, Diagnostics, Deselect, InvertSelection, RepeatFilter, MergeDown,
+    Tool(Tool), HideAllText, ChannelView, Confirm, Delete, ColorFavorite, BrushFavorite, BrushSmaller, BrushLarger, Fit, ZoomIn, ZoomOut,
 }
 
+const DIGITS: &[Chord] = &[
+    key(egui::Key::Num1), key(egui::Key::Num2), key(egui::Key::Num3), key(egui::Key::Num4), key(egui::Key::Num5),
+    key(egui::Key::Num6), key(egui::Key::Num7), key(egui::Key::Num8), key(egui::Key::Num9), key(egui::Key::Num0),
+];
+const CTRL_DIGITS: &[Chord] = &[
+    ctrl(egui::Key::Num1), ctrl(egui::Key::Num2), ctrl(egui::Key::Num3), ctrl(egui::Key::Num4), ctrl(egui::Key::Num5),
+    ctrl(egui::Key::Num6), ctrl(egui::Key::Num7), ctrl(egui::Key::Num8), ctrl(egui::Key::Num9), ctrl(egui::Key::Num0),
+];
+
+pub(super) const IE_SHORTCUTS: &[Shortcut<ImageKey>] = &[
+    shortcut(&[key(egui::Key::B)], ImageKey::Tool(Tool::Brush), "Brush"),
+    shortcut(&[key(egui::Key::E)], ImageKey::Tool(Tool::Eraser), "Eraser"),
+    shortcut(&[key(egui::Key::F)], ImageKey::Tool(Tool::Fill), "Fill"),
+    shortcut(&[key(egui::Key::D)], ImageKey::Tool(Tool::Eyedropper), "Eyedropper"),
+    shortcut(&[key(egui::Key::T)], ImageKey::Tool(Tool::Text), "Text"),
+    shortcut(&[key(egui::Key::C)], ImageKey::Tool(Tool::Crop), "Crop"),
+    shortcut(&[key(egui::Key::P)], ImageKey::Tool(Tool::Pan), "Pan"),
+    shortcut(&[key(egui::Key::R)], ImageKey::Tool(Tool::Retouch), "Retouch"),
+    shortcut(&[key(egui::Key::S)], ImageKey::Tool(Tool::Slice), "Slice"),
+    shortcut(&[key(egui::Key::M)], ImageKey::Tool(Tool::Measure), "Measure"),
+    shortcut(&[key(egui::Key::W)], ImageKey::Tool(Tool::Wand), "Magic Wand"),
+    shortcut(&[key(egui::Key::L)], ImageKey::Tool(Tool::Line), "Line"),
+    shortcut(&[key(egui::Key::U)], ImageKey::Tool(Tool::Rectangle), "Rectangle"),
+    shortcut(&[key(egui::Key::O)], ImageKey::Tool(Tool::Ellipse), "Ellipse"),
+    shortcut(&[key(egui::Key::Q)], ImageKey::Tool(Tool::Select), "Select (rectangular marquee); drag inside the selection to move its pixels"),
+    gesture("Shift+click / Alt+click (Wand)", "Add to / subtract from the selection"),
+    shortcut(&[ctrl(egui::Key::D)], ImageKey::Deselect, "Deselect"),
+    shortcut(&[ctrl_shift(egui::Key::I)], ImageKey::InvertSelection, "Invert selection"),
+    gesture("Shift+drag (Measure)", "Constrain to 45° steps"),
+    gesture("Shift+drag (Line / Rectangle / Ellipse)", "45° lines, squares and circles"),
+    gesture("Hold Z", "Show the loupe magnifier"),
+    shortcut(&[ctrl_shift(egui::Key::D)], ImageKey::Diagnostics, "Toggle diagnostics overlay"),
+    gesture("Ctrl+C / Ctrl+X / Ctrl+V", "Copy / cut the selection (or copy the image) / paste as a floating image, or as the canvas when none is open"),
+    shortcut(&[key(egui::Key::Escape)], ImageKey::Cancel, "Cancel placing or floating pixels, leave Compare with Saved, drop the selection, shape or measurements, or finish text editing"),
+    shortcut(&[key(egui::Key::Enter)], ImageKey::Confirm, "Place the floating pixels or image, or apply the crop"),
+    gesture("Click outside (Select)", "Place the floating pixels"),
+    shortcut(&[ctrl(egui::Key::F)], ImageKey::RepeatFilter, "Repeat last filter"),
+    shortcut(&[ctrl(egui::Key::Z)], ImageKey::Undo, "Undo"),
+    shortcut(&[ctrl(egui::Key::Y), ctrl_shift(egui::Key::Z)], ImageKey::Redo, "Redo"),
+    shortcut(&[ctrl(egui::Key::S)], ImageKey::Save, "Save"),
+    shortcut(&[ctrl_shift(egui::Key::S)], ImageKey::SaveAs, "Save As"),
+    shortcut(&[ctrl_shift(egui::Key::N)], ImageKey::NewLayer, "New raster layer"),
+    shortcut(&[ctrl(egui::Key::E)], ImageKey::MergeDown, "Merge layer down"),
+    shortcut(&[key(egui::Key::OpenBracket)], ImageKey::BrushSmaller, "Decrease brush size"),
+    shortcut(&[key(egui::Key::CloseBracket)], ImageKey::BrushLarger, "Increase brush size"),
+    gesture("Arrows", "Move the keyboard cursor (Shift: 10 px)"),
+    gesture("Space", "Paint, fill or sample at the keyboard cursor; hold and use arrows to paint a line"),
+    gesture("Arrows (Crop)", "Create or move the crop rectangle"),
+    gesture("Alt+Arrows (Crop)", "Move the left / top edge"),
+    gesture("Ctrl+Arrows (Crop)", "Move the right / bottom edge"),
+    shortcut(&[key(egui::Key::Delete), key(egui::Key::Backspace)], ImageKey::Delete, "Delete selected slice, image layer, text or selected pixels"),
+    shortcut(DIGITS, ImageKey::ColorFavorite, "Pick color favorite"),
+    shortcut(CTRL_DIGITS, ImageKey::BrushFavorite, "Load brush favorite"),
+    shortcut(&[key(egui::Key::Home)], ImageKey::Fit, "Fit"),
+    shortcut(&[key(egui::Key::Plus)], ImageKey::ZoomIn, "Zoom in"),
+    shortcut(&[key(egui::Key::Minus)], ImageKey::ZoomOut, "Zoom out"),
+    shortcut(&[shift(egui::Key::C)], ImageKey::ChannelView, "Cycle channel view (R / G / B / Alpha / off)"),
+    shortcut(&[shift(egui::Key::T)], ImageKey::HideAllText, "Hide or show all text layers on the canvas"),
+];
+
+pub fn key_bindings() -> Vec<KeyBinding> { crate::modules::key_bindings(IE_SHORTCUTS) }
+
 impl EditorModule for ImageEditor {
     fn as_any(&self) -> &dyn std::any::Any { self }
 
@@ -1767,100 +3151,235 @@ impl EditorModule for ImageEditor {
         if self.dirty { format!("{} *", name) } else { name.to_string() }
     }
 
-    fn save(&mut self) -> Result<(), String> { self.save_impl() }
-    fn save_as(&mut self) -> Result<(), String> { self.save_as_impl() }
+    fn apply_settings(&mut self, settings: &EditorSettings) {
+        self.settings = settings.image.clone();
+        if std::mem::take(&mut self.settings.reset_tools) { self.reset_tools(); }
+    }
+
+    fn take_settings_change(&mut self, settings: &mut EditorSettings) -> bool {
+        if !std::mem::take(&mut self.settings_changed) { return false; }
+        settings.image = self.settings.clone();
+        true
+    }
+
+    fn reload_config(&mut self) {
+        self.color_favorites = ColorFavorites::load();
+        self.brush_favorites = BrushFavorites::load();
+        self.export_presets = ExportPresets::load().0;
+        self.export_memory = ExportMemory::load();
+    }
+
+    fn progress(&self) -> Option<f32> {
+        if let Some(job) = &self.variant_export { return Some(job.task.fraction()); }
+        self.filter_job.as_ref().filter(|_| !self.processing_is_preview).map(|job| job.task.fraction())
+    }
+
+    fn save(&mut self) -> EditorResult<SaveState> { self.save_impl() }
+    fn save_as(&mut self) -> EditorResult<SaveState> { self.save_as_impl() }
+    fn save_pending(&self) -> bool { self.overwrite_confirm.is_some() }
+    fn take_error(&mut self) -> Option<EditorError> { self.pending_error.take() }
+    fn take_notice(&mut self) -> Option<String> { self.pending_notice.take() }
+    fn take_share(&mut self) -> Option<PathBuf> { self.pending_share.take() }
+    fn take_new_document(&mut self) -> Option<Box<dyn EditorModule>> { self.pending_new_document.take().map(|e| e as Box<dyn EditorModule>) }
+    fn key_bindings(&self) -> Vec<KeyBinding> { key_bindings() }
+    fn file_path(&self) -> Option<&std::path::Path> { self.file_path.as_deref() }
+    fn template_content(&self) -> Option<crate::templates::TemplateContent> { self.composite_all_layers().map(crate::templates::TemplateContent::Image) }
+    fn view_state(&self) -> Option<ViewState> { Some(ViewState { zoom: Some(self.zoom), pan: Some(self.pan), zoom_fit: self.zoom_mode == ZoomMode::Fit, ..Default::default() }) }
+    fn unload(&mut self) {
+        if let (Some(path), Some(view)) = (&self.file_path, self.view_state()) { super::ie_cache::save_view_state(path, &view); }
+    }
+    fn restore_view_state(&mut self, state: ViewState) {
+        if state.zoom_fit { self.fit_on_next_frame = true; return; }
+        if let Some(z) = state.zoom { self.set_zoom(z); self.fit_on_next_frame = false; }
+        if let Some(p) = state.pan { self.pan = p; }
+    }
+    fn tour(&self) -> Option<(&'static str, &'static [crate::tour::TourStep])> { Some(("image_editor", IE_TOUR)) }
 
     fn get_menu_contributions(&self) -> MenuContribution {
         let has_image = self.image.is_some();
         let can_merge = self.layers.iter().position(|l| l.id == self.active_layer_id).map(|i| i > 0).unwrap_or(false);
+        let last_filter = self.settings.last_filter;
+        let mut file_items = vec![
+            MenuEntry::item("Export...", MenuAction::Export).enabled(has_image),
+            MenuEntry::item("Import to Canvas...", ImageMenuAction::PlaceImage).enabled(has_image),
+            MenuEntry::item("Export Selection...", ImageMenuAction::ExportSelection).enabled(self.selection.is_some()),
+            MenuEntry::item("Export Viewport...", ImageMenuAction::ExportViewport).enabled(self.viewport_crops_image()),
+            match share::unsupported_reason() { Some(why) => MenuEntry::item("Share...", ImageMenuAction::Share).enabled(false).tooltip(why), None => MenuEntry::item("Share...", ImageMenuAction::Share).enabled(has_image) },
+            MenuEntry::Separator,
+        ];
+        file_items.extend(self.export_presets.presets.iter().enumerate().map(|(i, p)| MenuEntry::item(format!("Export with {}...", p.name), ImageMenuAction::ExportWithPreset(i)).enabled(has_image)));
+        if !self.export_presets.presets.is_empty() { file_items.push(MenuEntry::Separator); }
+        file_items.extend([
+            MenuEntry::item(if self.session.recording { "Stop Recording Session" } else { "Record Session" }, ImageMenuAction::RecordSession).enabled(has_image),
+            MenuEntry::item("Export Time-lapse...", ImageMenuAction::ExportTimeLapse).enabled(!self.session.frames.is_empty()),
+        ]);
         MenuContribution {
-            file_items: vec![
-                (MenuItem { label: "Export...".into(), shortcut: None, enabled: has_image }, MenuAction::Export),
-                (MenuItem { label: "Import to Canvas...".into(), shortcut: None, enabled: has_image }, MenuAction::Custom("Place Image".into())),
-            ],
+            file_items,
             edit_items: vec![
-                (MenuItem { label: "Undo".into(), shortcut: Some("Ctrl+Z".into()), enabled: !self.undo_stack.is_empty() }, MenuAction::Undo),
-                (MenuItem { label: "Redo".into(), shortcut: Some("Ctrl+Y".into()), enabled: !self.redo_stack.is_empty() }, MenuAction::Redo),
-            ],
+                MenuEntry::item("Undo", MenuAction::Undo).shortcut(&menu_keys(IE_SHORTCUTS, ImageKey::Undo)).enabled(self.history.can_undo()),
+                MenuEntry::item("Redo", MenuAction::Redo).shortcut(&menu_keys(IE_SHORTCUTS, ImageKey::Redo)).enabled(self.history.can_redo()),
+                MenuEntry::Separator,
+                MenuEntry::item("Copy", ImageMenuAction::CopySelection).shortcut("Ctrl+C").enabled(has_image),
+                MenuEntry::item("Copy Image", ImageMenuAction::CopyImage).enabled(has_image),
+                MenuEntry::item("Paste", ImageMenuAction::PasteImage).shortcut("Ctrl+V").enabled(!self.pixels_locked),
+                MenuEntry::item("Copy as Data URI", ImageMenuAction::CopyDataUri).enabled(has_image && self.data_uri_job.is_none()),
+                MenuEntry::item("Copy as Markdown Image", ImageMenuAction::CopyMarkdownImage).enabled(has_image && self.data_uri_job.is_none()),
+                MenuEntry::Separator,
+                MenuEntry::item("Stroke Selection...", ImageMenuAction::Stroke).enabled(has_image),
+                MenuEntry::item("Invert Selection", ImageMenuAction::InvertSelection).shortcut(&menu_keys(IE_SHORTCUTS, ImageKey::InvertSelection)).enabled(self.selection.is_some()),
+                MenuEntry::item("Deselect", ImageMenuAction::Deselect).shortcut(&menu_keys(IE_SHORTCUTS, ImageKey::Deselect)).enabled(self.selection.is_some()),
+            ].into_iter()
+            .chain((!self.deleted_text.is_empty()).then_some(MenuEntry::Separator))
+            .chain(self.deleted_text.iter().enumerate().map(|(i, t)| MenuEntry::item(format!("Restore Text: {}", t.preview()), ImageMenuAction::RestoreText(i))))
+            .collect(),
             view_items: vec![
-                (MenuItem { label: "Zoom In".into(), shortcut: Some("+".into()), enabled: true }, MenuAction::Custom("Zoom In".into())),
-                (MenuItem { label: "Zoom Out".into(), shortcut: Some("-".into()), enabled: true }, MenuAction::Custom("Zoom Out".into())),
-                (MenuItem { label: "Fit".into(), shortcut: Some("0".into()), enabled: true }, MenuAction::Custom("Fit".into())),
-                (MenuItem { label: "Separator".into(), shortcut: None, enabled: false }, MenuAction::None),
-                (MenuItem { label: if self.show_layers_panel { "Hide Layers Panel".into() } else { "Show Layers Panel".into() }, shortcut: None, enabled: true }, MenuAction::Custom("Toggle Layers".into())),
-            ],
+                MenuEntry::item("Zoom In", ImageMenuAction::ZoomIn).shortcut(&menu_keys(IE_SHORTCUTS, ImageKey::ZoomIn)),
+                MenuEntry::item("Zoom Out", ImageMenuAction::ZoomOut).shortcut(&menu_keys(IE_SHORTCUTS, ImageKey::ZoomOut)),
+                MenuEntry::item("Fit", ImageMenuAction::Fit).shortcut(&menu_keys(IE_SHORTCUTS, ImageKey::Fit)),
+                MenuEntry::item("Cycle Channel View", ImageMenuAction::CycleChannelView).shortcut(&menu_keys(IE_SHORTCUTS, ImageKey::ChannelView)).enabled(has_image),
+                MenuEntry::Separator,
+                MenuEntry::item("Loupe", ImageMenuAction::Loupe).shortcut("Hold Z").enabled(has_image).checked(self.loupe_pinned),
+            ].into_iter()
+            .chain(LOUPE_ZOOMS.iter().map(|z| MenuEntry::item(format!("Loupe {}x", z), ImageMenuAction::LoupeZoom(*z)).checked(self.loupe_zoom == *z)))
+            .chain([
+                MenuEntry::item("Round Loupe", ImageMenuAction::LoupeRound).checked(self.loupe_round),
+                MenuEntry::item("Loupe Pixel Grid", ImageMenuAction::LoupeGrid).checked(self.loupe_grid),
+                MenuEntry::Separator,
+                MenuEntry::item("Diagnostics Overlay", ImageMenuAction::Diagnostics).shortcut(&menu_keys(IE_SHORTCUTS, ImageKey::Diagnostics)).checked(self.perf.overlay),
+                MenuEntry::item("Adaptive Stroke Quality", ImageMenuAction::AdaptiveStrokes).checked(self.adaptive_strokes),
+                MenuEntry::Separator,
+                MenuEntry::item(if self.show_layers_panel { "Hide Layers Panel" } else { "Show Layers Panel" }, ImageMenuAction::ToggleLayers),
+                MenuEntry::item("Show Guides", ImageMenuAction::ToggleGuides).enabled(!self.guides.is_empty()).checked(self.show_guides),
+                MenuEntry::item("Clear Guides", ImageMenuAction::ClearGuides).enabled(!self.guides.is_empty()),
+                MenuEntry::item("Hide All Text Layers", ImageMenuAction::HideAllText).shortcut(&menu_keys(IE_SHORTCUTS, ImageKey::HideAllText)).enabled(!self.text_layers.is_empty()).checked(self.hide_all_text),
+                MenuEntry::Separator,
+            ])
+            .chain(CanvasSurround::ALL.iter().map(|s| MenuEntry::item(format!("Surround: {}", s.label()), ImageMenuAction::Surround(*s)).checked(self.settings.canvas_surround == *s)))
+            .collect(),
             image_items: vec![
-                (MenuItem { label: "Resize Canvas...".into(), shortcut: None, enabled: has_image }, MenuAction::Custom("Resize Canvas".into())),
-                (MenuItem { label: "Separator".into(), shortcut: None, enabled: false }, MenuAction::None),
-                (MenuItem { label: "Flip Horizontal".into(), shortcut: None, enabled: true }, MenuAction::Custom("Flip Horizontal".into())),
-                (MenuItem { label: "Flip Vertical".into(), shortcut: None, enabled: true }, MenuAction::Custom("Flip Vertical".into())),
-                (MenuItem { label: "Rotate CCW".into(), shortcut: None, enabled: true }, MenuAction::Custom("Rotate CCW".into())),
-                (MenuItem { label: "Rotate CW".into(), shortcut: None, enabled: true }, MenuAction::Custom("Rotate CW".into())),
+                MenuEntry::item("Resize Canvas...", ImageMenuAction::ResizeCanvas).enabled(has_image),
+                MenuEntry::item("Lock Pixels", ImageMenuAction::LockPixels).enabled(has_image).checked(self.pixels_locked),
+                MenuEntry::item("Place Image...", ImageMenuAction::PlaceFloating).enabled(has_image && !self.pixels_locked),
+                MenuEntry::item("Compare with Saved", ImageMenuAction::CompareSaved).enabled(has_image && self.file_path.as_ref().is_some_and(|p| p.exists())).checked(self.comparison.is_some()),
+                MenuEntry::item("Comparison: Difference", ImageMenuAction::CompareMode(CompareMode::Difference)).enabled(self.comparison.is_some())
+                    .checked(self.comparison.as_ref().is_some_and(|c| c.mode == CompareMode::Difference)),
+                MenuEntry::item("Comparison: Highlight Changes", ImageMenuAction::CompareMode(CompareMode::Overlay)).enabled(self.comparison.is_some())
+                    .checked(self.comparison.as_ref().is_some_and(|c| c.mode == CompareMode::Overlay)),
+                MenuEntry::Separator,
+                MenuEntry::item("Flip Horizontal", ImageMenuAction::FlipHorizontal),
+                MenuEntry::item("Flip Vertical", ImageMenuAction::FlipVertical),
+                MenuEntry::item("Rotate CCW", ImageMenuAction::RotateCcw),
+                MenuEntry::item("Rotate CW", ImageMenuAction::RotateCw),
+                MenuEntry::Separator,
+                MenuEntry::item("Duplicate as Flattened", ImageMenuAction::DuplicateFlattened).enabled(has_image && !self.text_layers.is_empty()),
+                MenuEntry::item("Flatten Text Layers", ImageMenuAction::FlattenText).enabled(has_image && !self.text_layers.is_empty()),
             ],
             filter_items: vec![
-                (MenuItem { label: "Brightness/Contrast...".into(), shortcut: None, enabled: has_image }, MenuAction::Custom("B/C".into())),
-                (MenuItem { label: "Hue/Saturation...".into(), shortcut: None, enabled: has_image }, MenuAction::Custom("H/S".into())),
-                (MenuItem { label: "Blur...".into(), shortcut: None, enabled: has_image }, MenuAction::Custom("Blur".into())),
-                (MenuItem { label: "Sharpen...".into(), shortcut: None, enabled: has_image }, MenuAction::Custom("Sharpen".into())),
-                (MenuItem { label: "Separator".into(), shortcut: None, enabled: false }, MenuAction::None),
-                (MenuItem { label: "Grayscale".into(), shortcut: None, enabled: has_image }, MenuAction::Custom("Gray".into())),
-                (MenuItem { label: "Invert".into(), shortcut: None, enabled: has_image }, MenuAction::Custom("Invert".into())),
-                (MenuItem { label: "Sepia".into(), shortcut: None, enabled: has_image }, MenuAction::Custom("Sepia".into())),
+                MenuEntry::item("Adjustment Stack...", ImageMenuAction::Adjustments).enabled(has_image),
+                MenuEntry::Separator,
+                MenuEntry::item("Brightness/Contrast...", ImageMenuAction::BrightnessContrast).enabled(has_image),
+                MenuEntry::item("Hue/Saturation...", ImageMenuAction::HueSaturation).enabled(has_image),
+                MenuEntry::item("Blur...", ImageMenuAction::Blur).enabled(has_image),
+                MenuEntry::item("Sharpen...", ImageMenuAction::Sharpen).enabled(has_image),
+                MenuEntry::item("Equalize...", ImageMenuAction::Equalize).enabled(has_image),
+                MenuEntry::item("Threshold...", ImageMenuAction::Threshold).enabled(has_image),
+                MenuEntry::item("Channel Mixer...", ImageMenuAction::ChannelMixer).enabled(has_image),
+                MenuEntry::Separator,
+                MenuEntry::item("Grayscale", ImageMenuAction::Grayscale).enabled(has_image),
+                MenuEntry::item("Invert", ImageMenuAction::Invert).enabled(has_image),
+                MenuEntry::item("Sepia", ImageMenuAction::Sepia).enabled(has_image),
+                MenuEntry::Separator,
+                MenuEntry::item(last_filter.map_or("Repeat Last Filter".into(), |f| format!("Repeat {}", f.label())), ImageMenuAction::RepeatFilter)
+                    .shortcut(&menu_keys(IE_SHORTCUTS, ImageKey::RepeatFilter)).enabled(has_image && last_filter.is_some()),
+                MenuEntry::item(last_filter.map_or("Repeat with Adjustments...".into(), |f| format!("Repeat {} with Adjustments...", f.label())), ImageMenuAction::RepeatFilterAdjust)
+                    .enabled(has_image && last_filter.is_some()),
             ],
             layer_items: vec![
-                (MenuItem { label: "New Layer".into(), shortcut: Some("Ctrl+Shift+N".into()), enabled: has_image }, MenuAction::Custom("Layer New".into())),
-                (MenuItem { label: "Duplicate Layer".into(), shortcut: None, enabled: has_image }, MenuAction::Custom("Layer Duplicate".into())),
-                (MenuItem { label: "Delete Layer".into(), shortcut: None, enabled: self.layers.len() > 1 }, MenuAction::Custom("Layer Delete".into())),
-                (MenuItem { label: "Separator".into(), shortcut: None, enabled: false }, MenuAction::None),
-                (MenuItem { label: "Merge Down".into(), shortcut: Some("Ctrl+E".into()), enabled: can_merge }, MenuAction::Custom("Layer Merge Down".into())),
-                (MenuItem { label: "Flatten Image".into(), shortcut: None, enabled: self.layers.len() > 1 }, MenuAction::Custom("Layer Flatten".into())),
+                MenuEntry::item("New Layer", ImageMenuAction::LayerNew).shortcut(&menu_keys(IE_SHORTCUTS, ImageKey::NewLayer)).enabled(has_image),
+                MenuEntry::item("Duplicate Layer", ImageMenuAction::LayerDuplicate).enabled(has_image),
+                MenuEntry::item("Delete Layer", ImageMenuAction::LayerDelete).enabled(self.layers.len() > 1),
+                MenuEntry::Separator,
+                MenuEntry::item("Merge Down", ImageMenuAction::LayerMergeDown).shortcut(&menu_keys(IE_SHORTCUTS, ImageKey::MergeDown)).enabled(can_merge),
+                MenuEntry::item("Flatten Image", ImageMenuAction::LayerFlatten).enabled(self.layers.len() > 1),
             ],
-            insert_items: Vec::new(), format_items: Vec::new()
+            insert_items: ShapeKind::ALL.iter().map(|k| MenuEntry::item(k.label(), ImageMenuAction::InsertShape(*k)).enabled(has_image)).collect(),
+            format_items: Vec::new()
         }
     }
 
     fn handle_menu_action(&mut self, action: MenuAction) -> bool {
+        if !matches!(action, MenuAction::Undo) { self.commit_floating_selection(); }
         match action {
             MenuAction::Undo => { self.undo(); true }
             MenuAction::Redo => { self.redo(); true }
-            MenuAction::Export => { self.filter_panel = FilterPanel::Export; true }
-            MenuAction::Custom(ref v) => match v.as_str() {
-                "Zoom In" => { self.zoom *= 1.25; true }
-                "Zoom Out" => { self.zoom = (self.zoom / 1.25).max(0.01); true }
-                "Fit" => { self.fit_image(); true }
-                "Toggle Layers" => { self.show_layers_panel = !self.show_layers_panel; true }
-                "Flip Horizontal" => { self.push_undo(); self.apply_flip_h(); true }
-                "Flip Vertical" => { self.push_undo(); self.apply_flip_v(); true }
-                "Rotate CCW" => { self.push_undo(); self.apply_rotate_ccw(); true }
-                "Rotate CW" => { self.push_undo(); self.apply_rotate_cw(); true }
-                "Resize Canvas" => { self.filter_panel = FilterPanel::Resize; true }
-                "B/C" => { self.filter_panel = FilterPanel::BrightnessContrast; true }
-                "H/S" => { self.filter_panel = FilterPanel::HueSaturation; true }
-                "Blur" => { self.filter_panel = FilterPanel::Blur; true }
-                "Sharpen" => { self.filter_panel = FilterPanel::Sharpen; true }
-                "Gray" => { self.push_undo(); self.apply_grayscale(); true }
-                "Invert" => { self.push_undo(); self.apply_invert(); true }
-                "Sepia" => { self.push_undo(); self.apply_sepia(); true }
-                "Layer New" => { self.new_raster_layer(); true }
-                "Layer Duplicate" => { self.duplicate_active_layer(); true }
-                "Layer Delete" => { self.delete_active_layer(); true }
-                "Layer Merge Down" => { self.merge_down(); true }
-                "Layer Flatten" => { self.flatten_all_layers(); true }
-                "Place Image" => {
-                    if let Some(path) = rfd::FileDialog::new()
-                        .add_filter("Images", &["png","jpg","jpeg","webp","bmp","tiff","tif","gif"])
-                        .pick_file()
-                    {
-                        let img = image::ImageReader::open(&path).ok()
-                            .and_then(|r| r.with_guessed_format().ok())
-                            .and_then(|r| r.decode().ok())
-                            .or_else(|| image::open(&path).ok());
-                        if let Some(img) = img { self.insert_image_layer(img, true); }
-                    }
-                    true
+            MenuAction::Export => { self.open_export_panel(); true }
+            MenuAction::Image(action) if action.mutates_pixels() && self.pixels_blocked() => true,
+            MenuAction::Image(action) => {
+                match action {
+                    ImageMenuAction::ZoomIn => self.set_zoom(self.zoom * 1.25),
+                    ImageMenuAction::ZoomOut => self.set_zoom(self.zoom / 1.25),
+                    ImageMenuAction::Fit => self.fit_image(),
+                    ImageMenuAction::CycleChannelView => self.channel_view = ChannelView::cycle(self.channel_view),
+                    ImageMenuAction::Loupe => self.loupe_pinned = !self.loupe_pinned,
+                    ImageMenuAction::LoupeZoom(z) => self.loupe_zoom = z,
+                    ImageMenuAction::LoupeRound => self.loupe_round = !self.loupe_round,
+                    ImageMenuAction::LoupeGrid => self.loupe_grid = !self.loupe_grid,
+                    ImageMenuAction::Diagnostics => self.perf.overlay = !self.perf.overlay,
+                    ImageMenuAction::AdaptiveStrokes => self.adaptive_strokes = !self.adaptive_strokes,
+                    ImageMenuAction::ToggleLayers => self.show_layers_panel = !self.show_layers_panel,
+                    ImageMenuAction::ToggleGuides => self.show_guides = !self.show_guides,
+                    ImageMenuAction::ClearGuides => self.guides.clear(),
+                    ImageMenuAction::HideAllText => self.toggle_hide_all_text(),
+                    ImageMenuAction::RestoreText(i) => self.restore_deleted_text(i),
+                    ImageMenuAction::Surround(surround) => { self.settings.canvas_surround = surround; self.settings_changed = true; }
+                    ImageMenuAction::FlipHorizontal => self.apply_flip_h(),
+                    ImageMenuAction::FlipVertical => self.apply_flip_v(),
+                    ImageMenuAction::RotateCcw => self.apply_rotate_ccw(),
+                    ImageMenuAction::RotateCw => self.apply_rotate_cw(),
+                    ImageMenuAction::ResizeCanvas => self.filter_panel = FilterPanel::Resize,
+                    ImageMenuAction::RecordSession => self.toggle_session_recording(),
+                    ImageMenuAction::Share => self.share_image(),
+                    ImageMenuAction::CopySelection => self.copy_image(false),
+                    ImageMenuAction::CopyImage => self.copy_image(true),
+                    ImageMenuAction::PasteImage => self.paste_image(),
+                    ImageMenuAction::CopyDataUri => self.copy_as_data_uri(ExportFormat::Png, false),
+                    ImageMenuAction::CopyMarkdownImage => self.copy_as_data_uri(ExportFormat::Png, true),
+                    ImageMenuAction::ExportTimeLapse => self.filter_panel = FilterPanel::TimeLapse,
+                    ImageMenuAction::ExportWithPreset(i) => self.export_with_preset(i),
+                    ImageMenuAction::BrightnessContrast => self.filter_panel = FilterPanel::BrightnessContrast,
+                    ImageMenuAction::HueSaturation => self.filter_panel = FilterPanel::HueSaturation,
+                    ImageMenuAction::Blur => self.filter_panel = FilterPanel::Blur,
+                    ImageMenuAction::Sharpen => self.filter_panel = FilterPanel::Sharpen,
+                    ImageMenuAction::Equalize => self.filter_panel = FilterPanel::Equalize,
+                    ImageMenuAction::Threshold => self.open_threshold_panel(),
+                    ImageMenuAction::ChannelMixer => self.filter_panel = FilterPanel::ChannelMixer,
+                    ImageMenuAction::Stroke => { self.stroke_color = self.color; self.filter_panel = FilterPanel::Stroke; }
+                    ImageMenuAction::Grayscale => { self.set_last_filter(RepeatableFilter::Grayscale); self.push_undo(); self.apply_grayscale(); }
+                    ImageMenuAction::Invert => { self.set_last_filter(RepeatableFilter::Invert); self.push_undo(); self.apply_invert(); }
+                    ImageMenuAction::Sepia => { self.set_last_filter(RepeatableFilter::Sepia); self.push_undo(); self.apply_sepia(); }
+                    ImageMenuAction::RepeatFilter => { if !self.pixels_blocked() { self.repeat_last_filter(false); } }
+                    ImageMenuAction::RepeatFilterAdjust => { if !self.pixels_blocked() { self.repeat_last_filter(true); } }
+                    ImageMenuAction::LockPixels => self.toggle_pixel_lock(),
+                    ImageMenuAction::Adjustments => self.filter_panel = FilterPanel::Adjustments,
+                    ImageMenuAction::DuplicateFlattened => self.duplicate_as_flattened(),
+                    ImageMenuAction::FlattenText => self.flatten_text_layers(),
+                    ImageMenuAction::InsertShape(kind) => self.insert_shape_layer(kind),
+                    ImageMenuAction::LayerNew => self.new_raster_layer(),
+                    ImageMenuAction::LayerDuplicate => self.duplicate_active_layer(),
+                    ImageMenuAction::LayerDelete => self.delete_active_layer(),
+                    ImageMenuAction::LayerMergeDown => self.merge_down(),
+                    ImageMenuAction::LayerFlatten => self.flatten_all_layers(),
+                    ImageMenuAction::CompareSaved => self.toggle_saved_comparison(),
+                    ImageMenuAction::CompareMode(mode) => if let Some(c) = &mut self.comparison { c.mode = mode; },
+                    ImageMenuAction::InvertSelection => self.invert_selection(),
+                    ImageMenuAction::Deselect => self.selection = None,
+                    ImageMenuAction::ExportSelection => self.export_selection(),
+                    ImageMenuAction::ExportViewport => self.begin_viewport_export(),
+                    ImageMenuAction::PlaceImage => if let Some(img) = pick_image() { self.insert_image_layer(img, true); },
+                    ImageMenuAction::PlaceFloating => if let Some(img) = pick_image() { self.place_floating_image(img); },
                 }
-                _ => false,
-            },
+                true
+            }
             _ => false,
         }
     }
@@ -1869,23 +3388,57 @@ impl EditorModule for ImageEditor {
         let theme = if ui.visuals().dark_mode { ThemeMode::Dark } else { ThemeMode::Light };
         self.handle_keyboard(ctx);
         self.check_filter_completion();
-        if self.is_processing { ctx.request_repaint(); }
-        if self.image.is_none() && self.file_path.is_none() { self.new_image(800, 600); }
-        self.render_toolbar(ui, theme);
-        ui.add_space(4.0);
-        self.render_options_bar(ui, theme);
-        ui.add_space(4.0);
-        if self.show_layers_panel {
-            egui::SidePanel::right("layers_panel")
+        self.check_variant_export();
+        if let Some(Err(e)) = self.session.poll_export() && !e.is_cancelled() { self.pending_error = Some(e); }
+        self.check_data_uri_job(ctx);
+        self.tick_text_journal(ctx);
+        self.check_adjustments(ctx);
+        self.check_fill_preview(ctx);
+        self.check_wand_job(ctx);
+        self.check_saved_comparison(ctx);
+        if self.is_processing() { ctx.request_repaint(); }
+        if self.image.is_none() && self.file_path.is_none() { self.new_image(800, 600); self.blank_canvas = Some(self.undo_pushes); }
+        if self.saved_hash.is_none() && !self.dirty && self.image.is_some() { self.saved_hash = Some(self.document_hash()); }
+        if !self.focus_mode {
+            let toolbar = ui.scope(|ui| self.render_toolbar(ui, theme)).response.rect;
+            crate::tour::anchor(ctx, "ie_toolbar", toolbar);
+            ui.add_space(4.0);
+            let options = ui.scope(|ui| self.render_options_bar(ui, theme)).response.rect;
+            crate::tour::anchor(ctx, "ie_options", options);
+            crate::focus::register(ctx, Region::Toolbar, ui.layer_id(), toolbar.union(options));
+            ui.add_space(4.0);
+        }
+        if self.show_layers_panel && !self.focus_mode {
+            let panel = egui::SidePanel::right("layers_panel")
                 .resizable(true).default_width(self.layer_panel_width)
                 .min_width(180.0).max_width(360.0)
                 .frame(egui::Frame::new()
                     .fill(if matches!(theme, ThemeMode::Dark) { egui::Color32::from_rgb(28,28,32) } else { egui::Color32::from_rgb(245,245,248) })
                     .stroke(egui::Stroke::new(1.0, if matches!(theme, ThemeMode::Dark) { egui::Color32::from_rgb(55,55,65) } else { egui::Color32::from_rgb(210,210,220) })))
                 .show_inside(ui, |ui| { self.render_layers_panel(ui, theme); });
+            crate::focus::register(ctx, Region::Panel, panel.response.layer_id, panel.response.rect);
         }
         if self.filter_panel != FilterPanel::None { self.render_filter_panel(ui, ctx, theme); }
         if self.show_color_picker { self.render_color_picker(ui, ctx, theme); }
+        if self.tool == Tool::Slice { self.render_slices_panel(ctx, theme); }
+        if self.session.confirm_discard { self.render_session_discard_confirm(ctx, theme); }
+        if self.rename_buffer.is_some() { self.render_rename_dialog(ctx, theme); }
+        if self.font_relink_dialog.is_some() { self.render_font_relink_dialog(ctx, theme); }
+        if self.journal_offer.is_some() { self.render_text_journal_offer(ctx, theme); }
+        if self.bake_confirm { self.render_bake_confirm(ctx, theme); }
+        if let Some(confirmed) = self.overwrite_confirm.as_deref().and_then(|p| overwrite_confirm_window(ctx, p, self.settings.keep_backup)) {
+            if confirmed { self.confirm_overwrite(); } else { self.overwrite_confirm = None; }
+        }
+        if !self.font_banner_dismissed && !self.focus_mode { self.render_missing_font_banner(ui, theme); }
+        if !self.conversion_dismissed && !self.focus_mode { self.render_conversion_banner(ui, theme); }
         self.render_canvas(ui, ctx);
+        self.capture_session_frame(ctx);
+        self.sync_tool_memory(ctx);
+    }
+    fn set_focus_mode(&mut self, focus: bool) { self.focus_mode = focus; }
+    fn accepts_text_paste(&self) -> bool { self.editing_text && self.selected_text.is_some() }
+    fn accepts_dropped_file(&self, path: &std::path::Path) -> bool {
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        crate::registry::screen_for_extension(ext).is_some_and(|s| s.create == crate::registry::CreateModule::ImageEditor)
     }
 }
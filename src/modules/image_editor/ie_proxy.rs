@@ -0,0 +1,112 @@
+use eframe::egui;
+use image::{DynamicImage, RgbaImage, imageops::FilterType};
+use std::collections::HashMap;
+use std::sync::mpsc::{Receiver, TryRecvError, sync_channel};
+use std::time::{Duration, Instant};
+use crate::tasks::{self, Task};
+
+const ZOOM_THRESHOLD: f32 = 0.5;
+const MAX_LEVEL: u32 = 3;
+/// How long the image and zoom must hold still before a proxy is built, so strokes and wheel zooming don't queue rebuilds.
+const SETTLE: Duration = Duration::from_millis(200);
+
+/// Proxy level for `zoom`: 0 draws the full texture, `n` draws the copy downscaled by `2^n`, the smallest one still at or above screen size.
+pub(super) fn level_for_zoom(zoom: f32) -> u32 {
+    if zoom >= ZOOM_THRESHOLD { return 0; }
+    ((1.0 / zoom).log2().floor() as u32).min(MAX_LEVEL)
+}
+
+pub(super) fn downscale(img: &RgbaImage, level: u32) -> RgbaImage {
+    let f = 1 << level;
+    image::imageops::resize(img, img.width().div_ceil(f).max(1), img.height().div_ceil(f).max(1), FilterType::Lanczos3)
+}
+
+/// Lanczos-downscaled copies of the canvas texture drawn at low zoom instead of letting the GPU minify the full image.
+pub(super) struct CanvasProxy {
+    textures: HashMap<u32, egui::TextureHandle>,
+    job: Option<(u32, Task, Receiver<Option<egui::ColorImage>>)>,
+    wanted: u32,
+    settle_from: Instant,
+}
+
+impl Default for CanvasProxy {
+    fn default() -> Self { Self { textures: HashMap::new(), job: None, wanted: 0, settle_from: Instant::now() } }
+}
+
+impl CanvasProxy {
+    pub(super) fn invalidate(&mut self) {
+        self.textures.clear();
+        if let Some((_, task, _)) = self.job.take() { task.cancel(); }
+        self.settle_from = Instant::now();
+    }
+
+    pub(super) fn texture(&self, zoom: f32) -> Option<egui::TextureId> {
+        self.textures.get(&level_for_zoom(zoom)).map(|t| t.id())
+    }
+
+    /// Collects a finished build and returns the level that should be built next, once things have settled.
+    pub(super) fn poll(&mut self, ctx: &egui::Context, zoom: f32) -> Option<u32> {
+        if let Some((level, _, rx)) = &self.job {
+            match rx.try_recv() {
+                Ok(img) => {
+                    let level = *level;
+                    self.job = None;
+                    if let Some(img) = img { self.textures.insert(level, ctx.load_texture(format!("image_editor_proxy_{}", level), img, egui::TextureOptions::LINEAR)); }
+                }
+                Err(TryRecvError::Empty) => { ctx.request_repaint_after(Duration::from_millis(30)); return None; }
+                Err(TryRecvError::Disconnected) => self.job = None,
+            }
+        }
+        let level = level_for_zoom(zoom);
+        if level != self.wanted { self.wanted = level; self.settle_from = Instant::now(); }
+        if level == 0 || self.textures.contains_key(&level) { return None; }
+        let waited = self.settle_from.elapsed();
+        if waited < SETTLE { ctx.request_repaint_after(SETTLE - waited); return None; }
+        Some(level)
+    }
+
+    pub(super) fn build(&mut self, level: u32, source: DynamicImage) {
+        let (tx, rx) = sync_channel(1);
+        let task = tasks::spawn("Build zoomed-out preview", true, move |task| {
+            if task.cancelled() { return; }
+            let small = downscale(&source.into_rgba8(), level);
+            let img = (!task.cancelled()).then(|| egui::ColorImage::from_rgba_unmultiplied([small.width() as usize, small.height() as usize], small.as_raw()));
+            let _ = tx.send(img);
+        });
+        self.job = Some((level, task, rx));
+    }
+}
+
+impl Drop for CanvasProxy {
+    fn drop(&mut self) { if let Some((_, task, _)) = self.job.take() { task.cancel(); } }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn low_zoom_picks_the_nearest_larger_proxy_and_averages_fine_detail() {
+        let levels: Vec<u32> = [1.0, 0.5, 0.49, 0.25, 0.2, 0.125, 0.05].iter().map(|z| level_for_zoom(*z)).collect();
+        assert_eq!(levels, [0, 0, 1, 2, 2, 3, 3]);
+        let stripes = RgbaImage::from_fn(101, 40, |x, _| if x % 2 == 0 { image::Rgba([0, 0, 0, 255]) } else { image::Rgba([255, 255, 255, 255]) });
+        let half = downscale(&stripes, 2);
+        assert_eq!(half.dimensions(), (26, 10));
+        assert!(half.pixels().skip(2).take(20).all(|p| (100..=155).contains(&p[0])), "stripes blend to grey instead of aliasing");
+    }
+
+    #[test]
+    fn proxies_build_as_cancellable_tasks() {
+        let ctx = egui::Context::default();
+        let mut proxy = CanvasProxy::default();
+        let source = DynamicImage::ImageRgba8(RgbaImage::new(64, 48));
+        proxy.build(2, source.clone());
+        let started = Instant::now();
+        while proxy.texture(0.25).is_none() && started.elapsed() < Duration::from_secs(10) { proxy.poll(&ctx, 0.25); std::thread::sleep(Duration::from_millis(5)); }
+        assert!(proxy.texture(0.25).is_some());
+        proxy.build(1, source);
+        let task = proxy.job.as_ref().map(|(_, t, _)| t.clone()).unwrap();
+        proxy.invalidate();
+        assert!(task.cancelled() && proxy.job.is_none() && proxy.texture(0.25).is_none());
+    }
+}
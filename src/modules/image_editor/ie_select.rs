@@ -0,0 +1,171 @@
+use eframe::egui;
+use image::{DynamicImage, GenericImageView, RgbaImage};
+use std::sync::atomic::{AtomicBool, Ordering};
+use super::ie_helpers::{fill_mask, within_tolerance};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum SelectOp { Replace, Add, Subtract }
+
+impl SelectOp {
+    pub(super) fn from_modifiers(m: egui::Modifiers) -> Self {
+        if m.shift { Self::Add } else if m.alt { Self::Subtract } else { Self::Replace }
+    }
+}
+
+#[derive(Clone)]
+pub(super) struct Selection { pub w: u32, pub h: u32, bits: Vec<bool>, outline: Vec<[(u32, u32); 2]> }
+
+impl Selection {
+    pub(super) fn new(w: u32, h: u32, bits: Vec<bool>) -> Self {
+        let mut sel = Self { w, h, bits, outline: Vec::new() };
+        sel.outline = sel.trace();
+        sel
+    }
+
+    /// Pixels inside the rectangle spanned by `a` and `b`, `None` when it covers none of the canvas.
+    pub(super) fn rect(w: u32, h: u32, a: (f32, f32), b: (f32, f32)) -> Option<Self> {
+        let clamp = |v: f32, max: u32| v.round().clamp(0.0, max as f32) as u32;
+        let (x0, x1, y0, y1) = (clamp(a.0.min(b.0), w), clamp(a.0.max(b.0), w), clamp(a.1.min(b.1), h), clamp(a.1.max(b.1), h));
+        (x1 > x0 && y1 > y0).then(|| Self::new(w, h, (0..w * h).map(|i| (x0..x1).contains(&(i % w)) && (y0..y1).contains(&(i / w))).collect()))
+    }
+
+    /// The same shape moved by `(dx, dy)` and clipped to the canvas.
+    pub(super) fn translated(&self, dx: i32, dy: i32) -> Option<Self> {
+        let bits: Vec<bool> = (0..self.w * self.h).map(|i| {
+            let (x, y) = ((i % self.w) as i64 - dx as i64, (i / self.w) as i64 - dy as i64);
+            x >= 0 && y >= 0 && self.contains(x as u32, y as u32)
+        }).collect();
+        bits.iter().any(|b| *b).then(|| Self::new(self.w, self.h, bits))
+    }
+
+    pub(super) fn fits(&self, w: u32, h: u32) -> bool { self.w == w && self.h == h }
+    pub(super) fn contains(&self, x: u32, y: u32) -> bool { x < self.w && y < self.h && self.bits[(y * self.w + x) as usize] }
+    pub(super) fn count(&self) -> usize { self.bits.iter().filter(|b| **b).count() }
+    pub(super) fn outline(&self) -> &[[(u32, u32); 2]] { &self.outline }
+
+    pub(super) fn bounds(&self) -> Option<[u32; 4]> {
+        let (mut x0, mut y0, mut x1, mut y1) = (u32::MAX, u32::MAX, 0, 0);
+        for (i, _) in self.bits.iter().enumerate().filter(|(_, b)| **b) {
+            let (x, y) = (i as u32 % self.w, i as u32 / self.w);
+            x0 = x0.min(x); y0 = y0.min(y); x1 = x1.max(x + 1); y1 = y1.max(y + 1);
+        }
+        (x1 > x0).then_some([x0, y0, x1, y1])
+    }
+
+    pub(super) fn inverted(&self) -> Option<Self> {
+        let bits: Vec<bool> = self.bits.iter().map(|b| !b).collect();
+        bits.iter().any(|b| *b).then(|| Self::new(self.w, self.h, bits))
+    }
+
+    pub(super) fn combine(current: Option<Self>, new: Self, op: SelectOp) -> Option<Self> {
+        let merged = match (current.filter(|c| c.fits(new.w, new.h)), op) {
+            (Some(cur), SelectOp::Add) => cur.bits.iter().zip(&new.bits).map(|(a, b)| *a || *b).collect(),
+            (Some(cur), SelectOp::Subtract) => cur.bits.iter().zip(&new.bits).map(|(a, b)| *a && !*b).collect(),
+            (None, SelectOp::Subtract) => return None,
+            _ => new.bits,
+        };
+        merged.iter().any(|b| *b).then(|| Self::new(new.w, new.h, merged))
+    }
+
+    fn trace(&self) -> Vec<[(u32, u32); 2]> {
+        let mut edges = Vec::new();
+        for y in 0..=self.h {
+            let mut run: Option<u32> = None;
+            for x in 0..=self.w {
+                let edge = x < self.w && (y > 0 && self.contains(x, y - 1)) != self.contains(x, y);
+                match (edge, run) {
+                    (true, None) => run = Some(x),
+                    (false, Some(start)) => { edges.push([(start, y), (x, y)]); run = None; }
+                    _ => {}
+                }
+            }
+        }
+        for x in 0..=self.w {
+            let mut run: Option<u32> = None;
+            for y in 0..=self.h {
+                let edge = y < self.h && (x > 0 && self.contains(x - 1, y)) != self.contains(x, y);
+                match (edge, run) {
+                    (true, None) => run = Some(y),
+                    (false, Some(start)) => { edges.push([(x, start), (x, y)]); run = None; }
+                    _ => {}
+                }
+            }
+        }
+        edges
+    }
+
+    pub(super) fn restore_outside(&self, buf: &mut RgbaImage, before: &DynamicImage, rect: [u32; 4]) {
+        if !self.fits(buf.width(), buf.height()) || before.dimensions() != buf.dimensions() { return; }
+        for y in rect[1]..rect[3].min(self.h) {
+            for x in rect[0]..rect[2].min(self.w) {
+                if !self.contains(x, y) { buf.put_pixel(x, y, before.get_pixel(x, y)); }
+            }
+        }
+    }
+
+    pub(super) fn keep_outside(&self, before: &DynamicImage, after: DynamicImage) -> DynamicImage {
+        if !self.fits(after.width(), after.height()) || before.dimensions() != after.dimensions() { return after; }
+        let mut buf = after.into_rgba8();
+        self.restore_outside(&mut buf, before, [0, 0, self.w, self.h]);
+        DynamicImage::ImageRgba8(buf)
+    }
+
+    pub(super) fn clear_inside(&self, img: &DynamicImage) -> DynamicImage {
+        let mut buf = img.to_rgba8();
+        for (i, _) in self.bits.iter().enumerate().filter(|(_, b)| **b) { buf.put_pixel(i as u32 % self.w, i as u32 / self.w, image::Rgba([0, 0, 0, 0])); }
+        DynamicImage::ImageRgba8(buf)
+    }
+
+    pub(super) fn cut(&self, img: &DynamicImage) -> Option<DynamicImage> {
+        let [x0, y0, x1, y1] = self.bounds()?;
+        let src = img.to_rgba8();
+        Some(DynamicImage::ImageRgba8(RgbaImage::from_fn(x1 - x0, y1 - y0, |x, y| {
+            if self.contains(x0 + x, y0 + y) { *src.get_pixel(x0 + x, y0 + y) } else { image::Rgba([0, 0, 0, 0]) }
+        })))
+    }
+}
+
+pub(super) fn wand(buf: &RgbaImage, sx: u32, sy: u32, tolerance: u32, contiguous: bool, progress: &dyn Fn(f32), cancel: &AtomicBool) -> Option<Selection> {
+    let (w, h) = buf.dimensions();
+    if sx >= w || sy >= h { return None; }
+    let mut bits = vec![false; (w * h) as usize];
+    if contiguous {
+        let mask = fill_mask(buf, sx, sy, tolerance, true, None, cancel)?;
+        for y in 0..mask.h { for x in 0..mask.w { bits[((mask.y0 + y) * w + mask.x0 + x) as usize] = mask.contains(x, y); } }
+    } else {
+        let target = buf.get_pixel(sx, sy).0;
+        for y in 0..h {
+            if y.is_multiple_of(64) {
+                if cancel.load(Ordering::Relaxed) { return None; }
+                progress(y as f32 / h as f32);
+            }
+            for x in 0..w { bits[(y * w + x) as usize] = within_tolerance(buf.get_pixel(x, y).0, target, tolerance); }
+        }
+    }
+    progress(1.0);
+    Some(Selection::new(w, h, bits))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wand_selects_connected_or_matching_pixels_and_combines_with_modifiers() {
+        let buf = RgbaImage::from_fn(6, 4, |x, _| if x == 2 { image::Rgba([0, 0, 0, 255]) } else { image::Rgba([250, 250, 250, 255]) });
+        let none = AtomicBool::new(false);
+        let left = wand(&buf, 0, 0, 30, true, &|_| {}, &none).unwrap();
+        assert_eq!((left.count(), left.bounds()), (8, Some([0, 0, 2, 4])));
+        assert_eq!(left.outline().len(), 4);
+        let both = wand(&buf, 0, 0, 30, false, &|_| {}, &none).unwrap();
+        assert_eq!(both.count(), 20);
+        let right = Selection::combine(Some(both.clone()), left.clone(), SelectOp::Subtract).unwrap();
+        assert_eq!(right.bounds(), Some([3, 0, 6, 4]));
+        assert_eq!(Selection::combine(Some(right), left.clone(), SelectOp::Add).unwrap().count(), 20);
+        assert_eq!(both.inverted().unwrap().bounds(), Some([2, 0, 3, 4]));
+        let painted = DynamicImage::ImageRgba8(RgbaImage::from_pixel(6, 4, image::Rgba([255, 0, 0, 255])));
+        let kept = left.keep_outside(&DynamicImage::ImageRgba8(buf.clone()), painted).to_rgba8();
+        assert_eq!((kept.get_pixel(1, 1).0, kept.get_pixel(4, 1).0), ([255, 0, 0, 255], [250, 250, 250, 255]));
+        assert_eq!(left.cut(&DynamicImage::ImageRgba8(buf)).unwrap().dimensions(), (2, 4));
+    }
+}
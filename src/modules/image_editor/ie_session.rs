@@ -0,0 +1,189 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::mpsc::{sync_channel, Receiver, TryRecvError};
+use image::{DynamicImage, RgbaImage, Frame, Delay};
+use image::codecs::gif::{GifEncoder, Repeat};
+use crate::error::{EditorError, EditorResult};
+use crate::tasks::{self, Task};
+
+/// Frames are never captured larger than this, whatever the frame size setting says.
+pub(super) const SESSION_MAX_DIM: u32 = 1024;
+/// Recorded frames are thinned out to every other one whenever they would take more than this.
+pub(super) const SESSION_MAX_BYTES: usize = 256 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum TimeLapseFormat { Gif, PngSequence }
+
+pub(super) struct SessionRecorder {
+    pub recording: bool,
+    pub frames: VecDeque<Arc<RgbaImage>>,
+    pub max_dim: u32,
+    pub frame_delay_ms: u32,
+    pub format: TimeLapseFormat,
+    pub confirm_discard: bool,
+    pub exporting: Option<(Receiver<EditorResult>, Task)>,
+    bytes: usize,
+    budget: usize,
+    stride: u32,
+    skipped: u32,
+}
+
+impl Default for SessionRecorder {
+    fn default() -> Self {
+        Self {
+            recording: false, frames: VecDeque::new(), max_dim: 480, frame_delay_ms: 120, format: TimeLapseFormat::Gif, confirm_discard: false,
+            exporting: None, bytes: 0, budget: SESSION_MAX_BYTES, stride: 1, skipped: 0,
+        }
+    }
+}
+
+impl SessionRecorder {
+    pub fn start(&mut self) { self.recording = true; self.clear(); }
+
+    pub fn stop(&mut self) { self.recording = false; self.confirm_discard = false; self.clear(); }
+
+    fn clear(&mut self) { self.frames.clear(); self.bytes = 0; self.stride = 1; self.skipped = 0; }
+
+    pub fn memory_kb(&self) -> usize { self.bytes / 1024 }
+
+    pub fn wants_frame(&mut self) -> bool {
+        if !self.recording { return false; }
+        self.skipped += 1;
+        if self.skipped < self.stride { return false; }
+        self.skipped = 0;
+        true
+    }
+
+    pub fn push(&mut self, img: &DynamicImage) {
+        let max = self.max_dim.clamp(16, SESSION_MAX_DIM);
+        let frame = if img.width() > max || img.height() > max { img.thumbnail(max, max).to_rgba8() } else { img.to_rgba8() };
+        if self.frames.back().is_some_and(|last| last.dimensions() == frame.dimensions() && last.as_raw() == frame.as_raw()) { return; }
+        self.bytes += frame.as_raw().len();
+        self.frames.push_back(Arc::new(frame));
+        while self.bytes > self.budget && self.frames.len() > 1 {
+            let kept: VecDeque<Arc<RgbaImage>> = self.frames.drain(..).enumerate().filter(|(i, _)| i % 2 == 0).map(|(_, f)| f).collect();
+            self.frames = kept;
+            self.bytes = self.frames.iter().map(|f| f.as_raw().len()).sum();
+            self.stride = self.stride.saturating_mul(2);
+        }
+    }
+
+    /// Writes the recorded frames to `target` on a background task; `poll_export` picks up the result.
+    pub fn start_export(&mut self, target: PathBuf) -> EditorResult {
+        if self.frames.is_empty() { return Err(EditorError::Empty("no frames have been recorded")); }
+        if self.exporting.is_some() { return Ok(()); }
+        let frames: Vec<Arc<RgbaImage>> = self.frames.iter().cloned().collect();
+        let (format, delay_ms) = (self.format, self.frame_delay_ms);
+        let (tx, rx) = sync_channel(1);
+        let task = tasks::spawn("Export time-lapse", true, move |task| {
+            let result = if task.cancelled() { Err(EditorError::Cancelled) } else { match format {
+                TimeLapseFormat::Gif => export_gif(&frames, delay_ms, &target, task),
+                TimeLapseFormat::PngSequence => export_pngs(&frames, &target, task),
+            } };
+            let toast = result.is_ok().then(|| format!("Exported {} frames", frames.len()));
+            let _ = tx.send(result);
+            task.finish(toast);
+        });
+        self.exporting = Some((rx, task));
+        Ok(())
+    }
+
+    pub fn poll_export(&mut self) -> Option<EditorResult> {
+        let (rx, task) = self.exporting.as_ref()?;
+        let result = match rx.try_recv() {
+            Ok(r) => r,
+            Err(TryRecvError::Empty) => return None,
+            Err(TryRecvError::Disconnected) if task.cancelled() => Err(EditorError::Cancelled),
+            Err(TryRecvError::Disconnected) => Err(EditorError::Other("Time-lapse export stopped before writing every frame".to_string())),
+        };
+        self.exporting = None;
+        Some(result)
+    }
+}
+
+fn export_gif(frames: &[Arc<RgbaImage>], delay_ms: u32, path: &Path, task: &Task) -> EditorResult {
+    let (w, h) = frames.iter().fold((0, 0), |(w, h), f| (w.max(f.width()), h.max(f.height())));
+    let file = File::create(path).map_err(|e| EditorError::io(path, e))?;
+    let mut enc = GifEncoder::new_with_speed(file, 10);
+    enc.set_repeat(Repeat::Infinite).map_err(|e| EditorError::encode("GIF", path, e))?;
+    let delay = Delay::from_numer_denom_ms(delay_ms.max(10), 1);
+    for (i, f) in frames.iter().enumerate() {
+        if task.cancelled() { return Err(EditorError::Cancelled); }
+        let mut canvas = RgbaImage::from_pixel(w, h, image::Rgba([255, 255, 255, 255]));
+        image::imageops::overlay(&mut canvas, f.as_ref(), ((w - f.width()) / 2) as i64, ((h - f.height()) / 2) as i64);
+        enc.encode_frame(Frame::from_parts(canvas, 0, 0, delay)).map_err(|e| EditorError::encode("GIF", path, e))?;
+        task.progress((i + 1) as f32 / frames.len() as f32);
+    }
+    Ok(())
+}
+
+fn export_pngs(frames: &[Arc<RgbaImage>], dir: &Path, task: &Task) -> EditorResult {
+    std::fs::create_dir_all(dir).map_err(|e| EditorError::io(dir, e))?;
+    let digits = frames.len().to_string().len().max(4);
+    for (i, f) in frames.iter().enumerate() {
+        if task.cancelled() { return Err(EditorError::Cancelled); }
+        let p = dir.join(format!("frame_{:0width$}.png", i + 1, width = digits));
+        f.save(&p).map_err(|e| EditorError::encode("PNG", &p, e))?;
+        task.progress((i + 1) as f32 / frames.len() as f32);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_are_capped_in_size_and_thinned_to_the_byte_budget_then_exported_on_a_task() {
+        let mut rec = SessionRecorder { max_dim: 2048, budget: 40 * 1024 * 1024, ..SessionRecorder::default() };
+        rec.start();
+        for i in 0..21u8 {
+            rec.push(&DynamicImage::ImageRgba8(RgbaImage::from_pixel(1100, 550, image::Rgba([i, 0, 0, 255]))));
+        }
+        assert_eq!(rec.frames[0].dimensions(), (SESSION_MAX_DIM, SESSION_MAX_DIM / 2));
+        assert!(rec.memory_kb() * 1024 <= 40 * 1024 * 1024);
+        assert_eq!((rec.frames.len(), rec.stride), (11, 2));
+        assert_eq!(rec.frames[1].get_pixel(0, 0)[0], 2, "thinning keeps every other frame");
+
+        rec.frames.truncate(2);
+        let path = std::env::temp_dir().join(format!("ue_timelapse_{}.gif", std::process::id()));
+        rec.start_export(path.clone()).unwrap();
+        rec.stop();
+        let result = loop {
+            if let Some(r) = rec.poll_export() { break r; }
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        };
+        result.unwrap();
+        let decoder = image::codecs::gif::GifDecoder::new(std::io::BufReader::new(File::open(&path).unwrap())).unwrap();
+        let frames = image::AnimationDecoder::into_frames(decoder).collect_frames().unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].buffer().dimensions(), (SESSION_MAX_DIM, SESSION_MAX_DIM / 2));
+    }
+
+    #[test]
+    fn a_cancelled_export_comes_back_as_cancelled_not_as_a_failure() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        let gate = Arc::new(AtomicBool::new(false));
+        let blockers: Vec<Task> = (0..tasks::MAX_RUNNING).map(|i| {
+            let gate = gate.clone();
+            tasks::spawn(format!("time-lapse blocker {}", i), false, move |_| while !gate.load(Ordering::Relaxed) { std::thread::sleep(std::time::Duration::from_millis(5)); })
+        }).collect();
+        while blockers.iter().any(|t| t.is_queued()) { std::thread::sleep(std::time::Duration::from_millis(5)); }
+        let mut rec = SessionRecorder { format: TimeLapseFormat::PngSequence, ..SessionRecorder::default() };
+        rec.start();
+        rec.push(&DynamicImage::ImageRgba8(RgbaImage::from_pixel(8, 8, image::Rgba([0, 0, 0, 255]))));
+        let dir = std::env::temp_dir().join(format!("ue_timelapse_cancel_{}", std::process::id()));
+        rec.start_export(dir.clone()).unwrap();
+        rec.exporting.as_ref().unwrap().1.cancel();
+        let result = loop {
+            if let Some(r) = rec.poll_export() { break r; }
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        };
+        gate.store(true, Ordering::Relaxed);
+        assert!(!dir.exists(), "nothing is written once the export is cancelled");
+        assert!(result.is_err_and(|e| e.is_cancelled() && e.user_message().is_none()));
+    }
+}
@@ -0,0 +1,352 @@
+use eframe::egui;
+use image::{DynamicImage, ImageBuffer, Rgba};
+use serde::{Deserialize, Serialize};
+use super::ie_helpers::blend_pixels_linear;
+use super::ie_main::{ImageEditor, ImageLayer, LayerKind, BlendMode, RgbaColor, BoxDrag, TransformHandleSet, THandle, Tool};
+
+const ELLIPSE_SEGMENTS: usize = 64;
+const CORNER_SEGMENTS: usize = 8;
+const LINE_BOX_H: f32 = 24.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ShapeKind { Rect, Ellipse, Line, Arrow }
+
+impl ShapeKind {
+    pub(super) const ALL: [ShapeKind; 4] = [ShapeKind::Rect, ShapeKind::Ellipse, ShapeKind::Line, ShapeKind::Arrow];
+    pub(super) fn label(&self) -> &'static str {
+        match self { ShapeKind::Rect => "Rectangle", ShapeKind::Ellipse => "Ellipse", ShapeKind::Line => "Line", ShapeKind::Arrow => "Arrow" }
+    }
+    pub(super) fn is_open(&self) -> bool { matches!(self, ShapeKind::Line | ShapeKind::Arrow) }
+    pub(super) fn for_tool(tool: Tool) -> Option<Self> {
+        match tool { Tool::Line => Some(ShapeKind::Line), Tool::Rectangle => Some(ShapeKind::Rect), Tool::Ellipse => Some(ShapeKind::Ellipse), _ => None }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(super) struct ShapeLayer {
+    pub id: u64, pub kind: ShapeKind,
+    pub x: f32, pub y: f32, pub w: f32, pub h: f32, pub rotation: f32,
+    pub stroke_width: f32, pub stroke: RgbaColor, pub fill: Option<RgbaColor>, pub corner_radius: f32,
+}
+
+impl ShapeLayer {
+    /// Unstyled shape for a drag from `a` to `b` with a shape tool. `constrain` (Shift) snaps lines to 45° and
+    /// makes rectangles square and ellipses round.
+    pub(super) fn from_drag(kind: ShapeKind, a: (f32, f32), b: (f32, f32), constrain: bool) -> Self {
+        let base = ShapeLayer {
+            id: 0, kind, x: a.0, y: a.1, w: 0.0, h: 0.0, rotation: 0.0,
+            stroke_width: 1.0, stroke: RgbaColor::from_egui(egui::Color32::BLACK), fill: None, corner_radius: 0.0,
+        };
+        let (mut dx, mut dy) = (b.0 - a.0, b.1 - a.1);
+        if kind.is_open() {
+            let len = dx.hypot(dy);
+            let mut angle = dy.atan2(dx);
+            if constrain { angle = (angle / std::f32::consts::FRAC_PI_4).round() * std::f32::consts::FRAC_PI_4; }
+            let (cx, cy) = (a.0 + angle.cos() * len / 2.0, a.1 + angle.sin() * len / 2.0);
+            return ShapeLayer { x: cx - len / 2.0, y: cy - LINE_BOX_H / 2.0, w: len, h: LINE_BOX_H, rotation: angle.to_degrees(), ..base };
+        }
+        if constrain { let side = dx.abs().max(dy.abs()); (dx, dy) = (side.copysign(dx), side.copysign(dy)); }
+        ShapeLayer { x: a.0.min(a.0 + dx), y: a.1.min(a.1 + dy), w: dx.abs(), h: dy.abs(), ..base }
+    }
+
+    pub(super) fn center(&self) -> (f32, f32) { (self.x + self.w / 2.0, self.y + self.h / 2.0) }
+    fn to_canvas(&self, (lx, ly): (f32, f32)) -> (f32, f32) {
+        let (cx, cy) = self.center();
+        let (sa, ca) = self.rotation.to_radians().sin_cos();
+        (cx + lx * ca - ly * sa, cy + lx * sa + ly * ca)
+    }
+    pub(super) fn handles(&self, origin: egui::Pos2, zoom: f32) -> TransformHandleSet {
+        let rect = egui::Rect::from_min_size(origin + egui::vec2(self.x * zoom, self.y * zoom), egui::vec2(self.w * zoom, self.h * zoom));
+        TransformHandleSet::with_rotation(rect, self.rotation.to_radians())
+    }
+    fn outline(&self) -> Vec<(f32, f32)> {
+        let (hw, hh) = (self.w / 2.0, self.h / 2.0);
+        if self.kind == ShapeKind::Ellipse {
+            return (0..ELLIPSE_SEGMENTS).map(|i| { let t = i as f32 / ELLIPSE_SEGMENTS as f32 * std::f32::consts::TAU; (hw * t.cos(), hh * t.sin()) }).collect();
+        }
+        let r = self.corner_radius.clamp(0.0, hw.min(hh));
+        if r < 0.5 { return vec![(-hw, -hh), (hw, -hh), (hw, hh), (-hw, hh)]; }
+        let corners = [(hw - r, -hh + r), (hw - r, hh - r), (-hw + r, hh - r), (-hw + r, -hh + r)];
+        corners.iter().enumerate().flat_map(|(q, &(cx, cy))| (0..=CORNER_SEGMENTS).map(move |i| {
+            let a = (q as f32 - 1.0 + i as f32 / CORNER_SEGMENTS as f32) * std::f32::consts::FRAC_PI_2;
+            (cx + r * a.cos(), cy + r * a.sin())
+        })).collect()
+    }
+    fn arrow_head(&self) -> Option<[(f32, f32); 3]> {
+        if self.kind != ShapeKind::Arrow { return None; }
+        let len = (self.stroke_width * 4.0).max(10.0).min(self.w * 0.5);
+        let tip = self.w / 2.0;
+        Some([(tip, 0.0), (tip - len, len * 0.6), (tip - len, -len * 0.6)])
+    }
+    fn shaft(&self) -> ((f32, f32), (f32, f32)) {
+        let end = self.arrow_head().map_or(self.w / 2.0, |h| (h[0].0 + h[1].0) / 2.0);
+        ((-self.w / 2.0, 0.0), (end, 0.0))
+    }
+
+    pub(super) fn paint(&self, painter: &egui::Painter, origin: egui::Pos2, zoom: f32, opacity: f32) {
+        let to_screen = |p: (f32, f32)| { let (x, y) = self.to_canvas(p); origin + egui::vec2(x * zoom, y * zoom) };
+        let fade = |c: RgbaColor| egui::Color32::from_rgba_unmultiplied(c.r, c.g, c.b, (c.a as f32 * opacity.clamp(0.0, 1.0)).round() as u8);
+        let stroke = egui::Stroke::new(self.stroke_width * zoom, fade(self.stroke));
+        if self.kind.is_open() {
+            let (a, b) = self.shaft();
+            if self.stroke_width > 0.0 { painter.line_segment([to_screen(a), to_screen(b)], stroke); }
+            if let Some(head) = self.arrow_head() { painter.add(egui::Shape::convex_polygon(head.map(to_screen).to_vec(), stroke.color, egui::Stroke::NONE)); }
+            return;
+        }
+        let pts: Vec<egui::Pos2> = self.outline().into_iter().map(to_screen).collect();
+        if let Some(fill) = self.fill { painter.add(egui::Shape::convex_polygon(pts.clone(), fade(fill), egui::Stroke::NONE)); }
+        if self.stroke_width > 0.0 { painter.add(egui::Shape::closed_line(pts, stroke)); }
+    }
+
+    pub(super) fn stamp(&self, dst: &mut ImageBuffer<Rgba<u8>, Vec<u8>>, opacity: f32, mode: BlendMode) {
+        let half = self.stroke_width.max(0.0) / 2.0;
+        let outline = if self.kind.is_open() { Vec::new() } else { self.outline() };
+        let (a, b) = self.shaft();
+        let head = self.arrow_head();
+        let pts: Vec<(f32, f32)> = outline.iter().copied().chain([a, b]).chain(head.into_iter().flatten()).map(|p| self.to_canvas(p)).collect();
+        let pad = half + 1.0;
+        let (x0, x1) = pts.iter().fold((f32::MAX, f32::MIN), |(lo, hi), p| (lo.min(p.0 - pad), hi.max(p.0 + pad)));
+        let (y0, y1) = pts.iter().fold((f32::MAX, f32::MIN), |(lo, hi), p| (lo.min(p.1 - pad), hi.max(p.1 + pad)));
+        let (x0, y0) = (x0.max(0.0) as u32, y0.max(0.0) as u32);
+        let (x1, y1) = ((x1.ceil().max(0.0) as u32).min(dst.width()), (y1.ceil().max(0.0) as u32).min(dst.height()));
+        let (cx, cy) = self.center();
+        let (sa, ca) = self.rotation.to_radians().sin_cos();
+        for py in y0..y1 {
+            for px in x0..x1 {
+                let (dx, dy) = (px as f32 + 0.5 - cx, py as f32 + 0.5 - cy);
+                let (lx, ly) = (dx * ca + dy * sa, -dx * sa + dy * ca);
+                let (fill_cov, stroke_cov) = if self.kind.is_open() {
+                    let shaft = if half > 0.0 { (0.5 - (ly.abs() - half).max(a.0 - lx).max(lx - b.0)).clamp(0.0, 1.0) } else { 0.0 };
+                    (0.0, head.map_or(shaft, |h| shaft.max((0.5 - polygon_distance(&h, lx, ly)).clamp(0.0, 1.0))))
+                } else {
+                    let d = polygon_distance(&outline, lx, ly);
+                    ((0.5 - d).clamp(0.0, 1.0), if half > 0.0 { (half + 0.5 - d.abs()).clamp(0.0, 1.0) } else { 0.0 })
+                };
+                if fill_cov <= 0.0 && stroke_cov <= 0.0 { continue; }
+                let mut p = dst.get_pixel(px, py).0;
+                for (c, cov) in [(self.fill, fill_cov), (Some(self.stroke), stroke_cov)] {
+                    if let Some(c) = c.filter(|_| cov > 0.0) { p = blend_pixels_linear(p, [c.r, c.g, c.b, (c.a as f32 * cov).round() as u8], opacity, mode); }
+                }
+                dst.put_pixel(px, py, Rgba(p));
+            }
+        }
+    }
+}
+
+fn polygon_distance(pts: &[(f32, f32)], x: f32, y: f32) -> f32 {
+    let (mut dist, mut inside) = (f32::MAX, false);
+    for (i, &a) in pts.iter().enumerate() {
+        let b = pts[(i + 1) % pts.len()];
+        let (ex, ey) = (b.0 - a.0, b.1 - a.1);
+        let t = (((x - a.0) * ex + (y - a.1) * ey) / (ex * ex + ey * ey).max(1e-6)).clamp(0.0, 1.0);
+        dist = dist.min((x - a.0 - ex * t).hypot(y - a.1 - ey * t));
+        if (a.1 > y) != (b.1 > y) && x < a.0 + (y - a.1) / (b.1 - a.1) * ex { inside = !inside; }
+    }
+    if inside { -dist } else { dist }
+}
+
+impl ImageEditor {
+    pub(super) fn insert_shape_layer(&mut self, kind: ShapeKind) {
+        let Some((cw, ch)) = self.image.as_ref().map(|i| (i.width() as f32, i.height() as f32)) else { return };
+        self.commit_or_discard_active_text();
+        self.push_undo();
+        let size = (cw.min(ch) * 0.4).max(16.0);
+        let (w, h) = if kind.is_open() { (size, LINE_BOX_H) } else { (size, size * 0.6) };
+        let id = self.next_shape_id; self.next_shape_id += 1;
+        self.shape_layers.push(ShapeLayer {
+            id, kind, x: (cw - w) / 2.0, y: (ch - h) / 2.0, w, h, rotation: 0.0,
+            stroke_width: 4.0, stroke: RgbaColor::from_egui(self.color), fill: None, corner_radius: 0.0,
+        });
+        let lid = self.next_layer_id; self.next_layer_id += 1;
+        let layer = ImageLayer {
+            id: lid, name: format!("{} {}", kind.label(), id + 1), opacity: 1.0, visible: true, locked: false,
+            blend_mode: BlendMode::Normal, kind: LayerKind::Shape,
+            linked_text_id: None, linked_image_id: None, linked_shape_id: Some(id),
+        };
+        let pos = self.insert_above_active();
+        self.layers.insert(pos, layer);
+        self.select_shape(id);
+        self.tool = Tool::Pan;
+        self.composite_dirty = true;
+        self.dirty = true;
+    }
+
+    pub(super) fn select_shape(&mut self, id: u64) {
+        if self.selected_text.is_some() { self.commit_or_discard_active_text(); }
+        self.selected_image_layer = None;
+        self.image_drag = None;
+        self.selected_shape = Some(id);
+        if let Some(layer) = self.layers.iter().find(|l| l.linked_shape_id == Some(id)) { self.active_layer_id = layer.id; }
+    }
+
+    pub(super) fn shape_transform_handles(&self) -> Option<TransformHandleSet> {
+        let shape = self.selected_shape.and_then(|id| self.shape_layers.iter().find(|s| s.id == id))?;
+        Some(shape.handles(self.image_to_screen(0.0, 0.0), self.zoom))
+    }
+
+    pub(super) fn hit_shape_layer(&self, pos: egui::Pos2) -> Option<u64> {
+        let origin = self.image_to_screen(0.0, 0.0);
+        self.layers.iter().rev().filter(|l| l.kind == LayerKind::Shape && l.visible).filter_map(|l| l.linked_shape_id)
+            .find(|&id| self.shape_layers.iter().any(|s| s.id == id && s.handles(origin, self.zoom).contains(pos)))
+    }
+
+    pub(super) fn begin_shape_drag(&mut self, pos: egui::Pos2) -> bool {
+        if self.image_drag.is_some() { return false; }
+        let handle = self.shape_transform_handles().and_then(|h| h.hit_test(pos));
+        let Some(id) = (if handle.is_some() { self.selected_shape } else { self.hit_shape_layer(pos) }) else { return false };
+        self.select_shape(id);
+        let Some(shape) = self.shape_layers.iter().find(|s| s.id == id) else { return false };
+        let handles = shape.handles(self.image_to_screen(0.0, 0.0), self.zoom);
+        self.shape_drag = Some(BoxDrag {
+            handle: handle.unwrap_or(THandle::Move), start: pos,
+            orig_x: shape.x, orig_y: shape.y, orig_w: shape.w, orig_h: shape.h,
+            orig_rotation: shape.rotation, orig_rot_start_angle: (pos - handles.rect.center()).angle(),
+        });
+        self.push_undo();
+        true
+    }
+
+    pub(super) fn drag_selected_shape(&mut self, pos: egui::Pos2) {
+        let origin = self.image_to_screen(0.0, 0.0);
+        let (Some(drag), Some(id)) = (&self.shape_drag, self.selected_shape) else { return };
+        if let Some(shape) = self.shape_layers.iter_mut().find(|s| s.id == id) {
+            (shape.x, shape.y, shape.w, shape.h, shape.rotation) = drag.apply(pos, origin, self.zoom, None);
+            self.dirty = true;
+        }
+    }
+
+    pub(super) fn transform_shapes(&mut self, map: impl Fn(f32, f32) -> (f32, f32), angle: impl Fn(f32) -> f32) {
+        for shape in &mut self.shape_layers {
+            let (cx, cy) = shape.center();
+            let (nx, ny) = map(cx, cy);
+            shape.x = nx - shape.w / 2.0; shape.y = ny - shape.h / 2.0;
+            shape.rotation = angle(shape.rotation);
+        }
+    }
+
+    pub(super) fn shape_layer_to_raster(&mut self, idx: usize) -> bool {
+        let Some(id) = self.layers[idx].linked_shape_id else { return false };
+        let Some(shape) = self.shape_layers.iter().find(|s| s.id == id).cloned() else { return false };
+        let Some((cw, ch)) = self.image.as_ref().map(|i| (i.width(), i.height())) else { return false };
+        let mut raster = ImageBuffer::from_pixel(cw, ch, Rgba([0u8, 0, 0, 0]));
+        shape.stamp(&mut raster, 1.0, BlendMode::Normal);
+        let new_lid = self.next_layer_id; self.next_layer_id += 1;
+        self.layer_images.insert(new_lid, DynamicImage::ImageRgba8(raster));
+        self.raster_layer_texture_dirty.insert(new_lid);
+        self.shape_layers.retain(|s| s.id != id);
+        if self.selected_shape == Some(id) { self.selected_shape = None; self.shape_drag = None; }
+        let old = &self.layers[idx];
+        self.layers[idx] = ImageLayer {
+            id: new_lid, name: old.name.clone(), opacity: old.opacity, visible: old.visible, locked: old.locked,
+            blend_mode: old.blend_mode, kind: LayerKind::Raster, linked_text_id: None, linked_image_id: None, linked_shape_id: None,
+        };
+        self.active_layer_id = new_lid;
+        true
+    }
+
+    /// The shape the Line, Rectangle or Ellipse tool is dragging out, styled with the current color and options.
+    pub(super) fn shape_tool_shape(&self, constrain: bool) -> Option<ShapeLayer> {
+        let kind = ShapeKind::for_tool(self.tool)?;
+        let (a, b) = self.shape_tool_drag?;
+        let color = RgbaColor::from_egui(self.color);
+        let shape = ShapeLayer {
+            stroke_width: self.shape_tool_width, stroke: color, fill: (self.shape_tool_filled && !kind.is_open()).then_some(color),
+            ..ShapeLayer::from_drag(kind, a, b, constrain)
+        };
+        (shape.w >= 1.0 && (kind.is_open() || shape.h >= 1.0)).then_some(shape)
+    }
+
+    /// Draws the dragged shape into the active pixel layer as one undo step, anti-aliased like a rasterized shape layer.
+    pub(super) fn commit_shape_tool(&mut self, constrain: bool) {
+        let shape = self.shape_tool_shape(constrain);
+        self.shape_tool_drag = None;
+        let Some(shape) = shape else { return };
+        let active = self.active_layer_id;
+        let Some(layer) = self.layers.iter().find(|l| l.id == active) else { return };
+        if layer.locked { return; }
+        let kind = layer.kind;
+        let before = match kind {
+            LayerKind::Background => self.image.clone(),
+            LayerKind::Raster => self.layer_images.get(&active).cloned(),
+            _ => { self.pending_notice = Some("Shapes draw onto the background or a raster layer. Pick one in the Layers panel.".to_string()); return; }
+        };
+        let Some(before) = before else { return };
+        self.push_undo();
+        let mut buf = before.to_rgba8();
+        shape.stamp(&mut buf, 1.0, BlendMode::Normal);
+        let result = self.within_selection(Some(&before), DynamicImage::ImageRgba8(buf));
+        if kind == LayerKind::Background { self.image = Some(result); } else { self.layer_images.insert(active, result); }
+        self.composite_dirty = true; self.texture_dirty = true; self.dirty = true;
+        self.add_color_to_history();
+    }
+
+    pub(super) fn rasterize_shape_layer(&mut self) {
+        let Some(idx) = self.layers.iter().position(|l| l.id == self.active_layer_id && l.kind == LayerKind::Shape) else { return };
+        self.push_undo();
+        if self.shape_layer_to_raster(idx) { self.composite_dirty = true; self.dirty = true; }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shape(kind: ShapeKind) -> ShapeLayer {
+        ShapeLayer {
+            id: 0, kind, x: 10.0, y: 10.0, w: 20.0, h: 10.0, rotation: 0.0, stroke_width: 2.0,
+            stroke: RgbaColor { r: 255, g: 0, b: 0, a: 255 }, fill: Some(RgbaColor { r: 0, g: 0, b: 255, a: 255 }), corner_radius: 0.0,
+        }
+    }
+
+    #[test]
+    fn shapes_stamp_fill_stroke_and_follow_rotation() {
+        let blank = || ImageBuffer::from_pixel(40, 40, Rgba([0u8, 0, 0, 0]));
+        let mut buf = blank();
+        shape(ShapeKind::Rect).stamp(&mut buf, 1.0, BlendMode::Normal);
+        assert_eq!(buf.get_pixel(20, 15).0, [0, 0, 255, 255]);
+        assert_eq!(buf.get_pixel(20, 10).0, [255, 0, 0, 255]);
+        assert_eq!(buf.get_pixel(20, 25).0[3], 0);
+        let mut buf = blank();
+        shape(ShapeKind::Ellipse).stamp(&mut buf, 1.0, BlendMode::Normal);
+        assert_eq!(buf.get_pixel(20, 15).0, [0, 0, 255, 255]);
+        assert_eq!(buf.get_pixel(11, 11).0[2], 0);
+        let mut buf = blank();
+        ShapeLayer { rotation: 90.0, ..shape(ShapeKind::Arrow) }.stamp(&mut buf, 1.0, BlendMode::Normal);
+        assert_eq!(buf.get_pixel(20, 8).0, [255, 0, 0, 255]);
+        assert_eq!(buf.get_pixel(28, 15).0[3], 0);
+        assert_eq!(buf.get_pixel(23, 17).0, [255, 0, 0, 255]);
+        let drag = BoxDrag { handle: THandle::E, start: egui::pos2(30.0, 15.0), orig_x: 10.0, orig_y: 10.0, orig_w: 20.0, orig_h: 10.0, orig_rotation: 0.0, orig_rot_start_angle: 0.0 };
+        assert_eq!(drag.apply(egui::pos2(50.0, 15.0), egui::Pos2::ZERO, 1.0, None), (10.0, 10.0, 40.0, 10.0, 0.0));
+    }
+
+    #[test]
+    fn shape_tools_rasterize_drags_and_shift_constrains_them() {
+        let blank = || ImageBuffer::from_pixel(32, 32, Rgba([0u8, 0, 0, 0]));
+        let styled = |s: ShapeLayer, fill: bool| ShapeLayer { stroke_width: 2.0, stroke: RgbaColor { r: 255, g: 0, b: 0, a: 255 }, fill: fill.then_some(RgbaColor { r: 255, g: 0, b: 0, a: 255 }), ..s };
+
+        let rect = ShapeLayer::from_drag(ShapeKind::Rect, (24.0, 20.0), (4.0, 4.0), false);
+        assert_eq!((rect.x, rect.y, rect.w, rect.h), (4.0, 4.0, 20.0, 16.0));
+        let mut buf = blank();
+        styled(rect, false).stamp(&mut buf, 1.0, BlendMode::Normal);
+        assert_eq!((buf.get_pixel(10, 4).0, buf.get_pixel(23, 12).0), ([255, 0, 0, 255], [255, 0, 0, 255]));
+        assert_eq!((buf.get_pixel(14, 12).0[3], buf.get_pixel(28, 12).0[3]), (0, 0));
+        let square = ShapeLayer::from_drag(ShapeKind::Rect, (4.0, 4.0), (24.0, 10.0), true);
+        assert_eq!((square.w, square.h), (20.0, 20.0));
+
+        let mut buf = blank();
+        styled(ShapeLayer::from_drag(ShapeKind::Ellipse, (4.0, 4.0), (28.0, 20.0), true), true).stamp(&mut buf, 1.0, BlendMode::Normal);
+        assert_eq!((buf.get_pixel(16, 16).0, buf.get_pixel(27, 16).0), ([255, 0, 0, 255], [255, 0, 0, 255]));
+        assert_eq!(buf.get_pixel(5, 5).0[3], 0);
+        assert!(buf.pixels().any(|p| p[3] > 0 && p[3] < 255), "ellipse edges should be anti-aliased");
+
+        let mut buf = blank();
+        let line = ShapeLayer::from_drag(ShapeKind::Line, (4.0, 4.0), (28.0, 26.0), true);
+        assert!((line.rotation - 45.0).abs() < 1e-4);
+        styled(line, true).stamp(&mut buf, 1.0, BlendMode::Normal);
+        assert_eq!((buf.get_pixel(4, 4).0, buf.get_pixel(16, 16).0), ([255, 0, 0, 255], [255, 0, 0, 255]));
+        assert_eq!((buf.get_pixel(16, 8).0[3], buf.get_pixel(8, 16).0[3]), (0, 0));
+        let mut buf = blank();
+        styled(ShapeLayer::from_drag(ShapeKind::Line, (4.0, 10.5), (28.0, 10.5), false), false).stamp(&mut buf, 1.0, BlendMode::Normal);
+        assert_eq!((buf.get_pixel(4, 10).0, buf.get_pixel(27, 10).0, buf.get_pixel(16, 12).0[3]), ([255, 0, 0, 255], [255, 0, 0, 255], 0));
+    }
+}
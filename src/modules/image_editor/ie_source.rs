@@ -0,0 +1,141 @@
+use image::{DynamicImage, ExtendedColorType, ImageDecoder, ImageFormat, ImageReader, ImageResult};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use super::ie_helpers::{load_persisted, save_persisted};
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct SourceInfo { pub format: Option<ImageFormat>, pub color: ExtendedColorType, pub profile: Option<String> }
+
+impl SourceInfo {
+    pub(super) fn bit_depth(&self) -> u16 { self.color.bits_per_pixel() / self.color.channel_count().max(1) as u16 }
+    pub(super) fn is_float(&self) -> bool { matches!(self.color, ExtendedColorType::Rgb32F | ExtendedColorType::Rgba32F) }
+    pub(super) fn is_cmyk(&self) -> bool { matches!(self.color, ExtendedColorType::Cmyk8 | ExtendedColorType::Cmyk16) }
+    pub(super) fn foreign_profile(&self) -> Option<&str> { self.profile.as_deref().filter(|p| !p.contains("sRGB")) }
+    pub(super) fn is_lossy(&self) -> bool { self.bit_depth() > 8 || self.is_cmyk() || self.foreign_profile().is_some() }
+
+    pub(super) fn describe(&self) -> String {
+        let depth = if self.is_float() { format!("{}-bit float", self.bit_depth()) } else { format!("{}-bit", self.bit_depth()) };
+        let space = match (self.is_cmyk(), self.profile.as_deref()) {
+            (true, Some(p)) if p != "embedded" => format!("CMYK ({})", p),
+            (true, _) => "CMYK".to_string(),
+            (false, Some("embedded")) => "RGB with an embedded profile".to_string(),
+            (false, Some(p)) => p.to_string(),
+            (false, None) => if matches!(self.color, ExtendedColorType::L16 | ExtendedColorType::La16) { "grayscale".to_string() } else { "RGB".to_string() },
+        };
+        format!("{} {}", depth, space)
+    }
+
+    pub(super) fn conversion(&self) -> Option<String> {
+        self.is_lossy().then(|| format!("Converted from {} to 8-bit sRGB for editing", self.describe()))
+    }
+
+    pub(super) fn kind(&self) -> String {
+        let format = self.format.and_then(|f| f.extensions_str().first().copied()).unwrap_or("image");
+        let mut kind = format.to_uppercase();
+        if self.bit_depth() > 8 { kind.push_str(&format!(" {}-bit", self.bit_depth())); }
+        if self.is_cmyk() { kind.push_str(" CMYK"); }
+        if self.foreign_profile().is_some() { kind.push_str(" profile"); }
+        kind
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub(super) struct MutedConversions { pub kinds: Vec<String> }
+
+impl MutedConversions {
+    pub(super) fn load() -> Self { load_persisted("muted_conversions.json", "Muted conversion warnings") }
+    pub(super) fn save(&self) { save_persisted("muted_conversions.json", self); }
+    pub(super) fn contains(&self, kind: &str) -> bool { self.kinds.iter().any(|k| k == kind) }
+}
+
+pub(super) fn read_with_source(path: &Path) -> ImageResult<(DynamicImage, SourceInfo)> {
+    let reader = ImageReader::open(path)?.with_guessed_format()?;
+    let format = reader.format();
+    let mut decoder = reader.into_decoder()?;
+    let color = decoder.original_color_type();
+    let profile = decoder.icc_profile().ok().flatten().map(|icc| icc_description(&icc).unwrap_or_else(|| "embedded".to_string()));
+    Ok((DynamicImage::from_decoder(decoder)?, SourceInfo { format, color, profile }))
+}
+
+fn be32(b: &[u8], at: usize) -> Option<usize> { b.get(at..at + 4).map(|s| u32::from_be_bytes([s[0], s[1], s[2], s[3]]) as usize) }
+
+pub(super) fn icc_description(icc: &[u8]) -> Option<String> {
+    let count = be32(icc, 128)?.min(256);
+    let entry = (0..count).map(|i| 132 + i * 12).find(|&at| icc.get(at..at + 4) == Some(b"desc"))?;
+    let (off, len) = (be32(icc, entry + 4)?, be32(icc, entry + 8)?);
+    let tag = icc.get(off..off.checked_add(len)?)?;
+    let text = match tag.get(..4)? {
+        b"desc" => { let n = be32(tag, 8)?; String::from_utf8_lossy(tag.get(12..12 + n)?).trim_end_matches('\0').to_string() }
+        b"mluc" => {
+            let (n, at) = (be32(tag, 20)?, be32(tag, 24)?);
+            let units: Vec<u16> = tag.get(at..at + n)?.chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect();
+            String::from_utf16_lossy(&units).trim_end_matches('\0').to_string()
+        }
+        _ => return None,
+    };
+    (!text.trim().is_empty()).then(|| text.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::ImageEncoder;
+
+    fn icc_v2(name: &str) -> Vec<u8> {
+        let mut icc = vec![0u8; 128];
+        icc.extend(1u32.to_be_bytes());
+        let tag_len = 12 + name.len() + 1;
+        icc.extend(b"desc"); icc.extend(144u32.to_be_bytes()); icc.extend((tag_len as u32).to_be_bytes());
+        icc.extend(b"desc"); icc.extend([0; 4]); icc.extend(((name.len() + 1) as u32).to_be_bytes()); icc.extend(name.as_bytes()); icc.push(0);
+        let size = icc.len() as u32;
+        icc[..4].copy_from_slice(&size.to_be_bytes());
+        icc
+    }
+
+    fn cmyk_tiff() -> Vec<u8> {
+        let entries: [(u16, u16, u32, u32); 10] = [
+            (256, 3, 1, 2), (257, 3, 1, 1), (258, 3, 4, 134), (259, 3, 1, 1), (262, 3, 1, 5),
+            (273, 4, 1, 142), (277, 3, 1, 4), (278, 3, 1, 1), (279, 4, 1, 8), (284, 3, 1, 1),
+        ];
+        let mut t = b"II*\0".to_vec();
+        t.extend(8u32.to_le_bytes());
+        t.extend((entries.len() as u16).to_le_bytes());
+        for (tag, kind, count, value) in entries {
+            t.extend(tag.to_le_bytes()); t.extend(kind.to_le_bytes()); t.extend(count.to_le_bytes());
+            if kind == 3 && count == 1 { t.extend((value as u16).to_le_bytes()); t.extend([0; 2]); } else { t.extend(value.to_le_bytes()); }
+        }
+        t.extend(0u32.to_le_bytes());
+        for _ in 0..4 { t.extend(8u16.to_le_bytes()); }
+        t.extend([0, 0, 0, 0, 255, 0, 0, 0]);
+        t
+    }
+
+    fn png(color: ExtendedColorType, bytes: &[u8], icc: Option<Vec<u8>>) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut enc = image::codecs::png::PngEncoder::new(&mut out);
+        if let Some(icc) = icc { enc.set_icc_profile(icc).unwrap(); }
+        enc.write_image(bytes, 1, 1, color).unwrap();
+        out
+    }
+
+    #[test]
+    fn load_reports_lossy_normalisation_for_each_flavour() {
+        let dir = std::env::temp_dir().join(format!("ue_source_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let probe = |name: &str, bytes: Vec<u8>| { let p = dir.join(name); std::fs::write(&p, bytes).unwrap(); read_with_source(&p).unwrap() };
+        let (_, plain) = probe("plain.png", png(ExtendedColorType::Rgb8, &[1, 2, 3], Some(icc_v2("sRGB IEC61966-2.1"))));
+        assert_eq!((plain.conversion(), plain.profile.as_deref()), (None, Some("sRGB IEC61966-2.1")));
+        let (img, deep) = probe("deep.png", png(ExtendedColorType::Rgb16, &[0, 1, 0, 2, 0, 3], Some(icc_v2("ProPhoto RGB"))));
+        assert_eq!(img.width(), 1);
+        assert_eq!(deep.conversion().as_deref(), Some("Converted from 16-bit ProPhoto RGB to 8-bit sRGB for editing"));
+        assert_eq!(deep.kind(), "PNG 16-bit profile");
+        let (_, gray) = probe("gray.png", png(ExtendedColorType::L16, &[1, 0], None));
+        assert_eq!(gray.conversion().as_deref(), Some("Converted from 16-bit grayscale to 8-bit sRGB for editing"));
+        let (img, cmyk) = probe("print.tif", cmyk_tiff());
+        assert_eq!((img.width(), img.height()), (2, 1));
+        assert_eq!(cmyk.conversion().as_deref(), Some("Converted from 8-bit CMYK to 8-bit sRGB for editing"));
+        assert_eq!(cmyk.kind(), "TIFF CMYK");
+        assert_eq!(icc_description(&[0; 40]), None);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
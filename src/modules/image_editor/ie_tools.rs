@@ -1,19 +1,42 @@
 use eframe::egui;
 use image::{DynamicImage, GenericImage, GenericImageView, ImageBuffer, Rgba};
-use crate::modules::helpers::image_export::export_image;
-use std::path::PathBuf;
+use crate::modules::helpers::image_export::{export_image, export_svg, encode_image, ExportFormat, SvgDocument, SvgLayer, SvgText};
+use crate::modules::helpers::share;
+use crate::modules::helpers::text_bounds::{prev_grapheme_boundary, next_grapheme_boundary, prev_word_boundary, next_word_boundary};
+use crate::error::{EditorError, EditorResult};
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, OnceLock};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{sync_channel, TryRecvError};
 use std::thread;
-use ab_glyph::{Font as AbFont, FontRef, PxScale, ScaleFont, point};
+use crate::tasks;
+use ab_glyph::{Font as AbFont, FontRef, GlyphImageFormat, PxScale, ScaleFont, point};
+use unicode_segmentation::UnicodeSegmentation;
 use crate::style::{FONT_UB_REG, FONT_UB_BLD, FONT_UB_ITL, FONT_RB_REG, FONT_RB_BLD, FONT_RB_ITL, FONT_GS_REG, FONT_GS_BLD, FONT_GS_ITL, FONT_OS_REG, FONT_OS_BLD, FONT_OS_ITL};
-use super::ie_helpers::{rgb_to_hsv, hsv_to_rgb, srgb_to_linear, smooth_hash_2d, brush_rand, retouch_lerp_u8};
+use super::ie_gpu::{self, GpuJob, GpuStage};
+use super::ie_filters as filters;
+use super::ie_helpers::{tab_advance, layout_char_to_text, composite_over_u8, stamp_u8, linear_to_srgb_u8, rgb_to_hsv, hsv_to_rgb, srgb_to_linear, smooth_hash_2d, brush_rand, retouch_lerp_u8, snap_grapheme_boundary, has_rtl, rtl_at, bidi_visual_runs, word_range_at, fill_mask, gradient_color, FillMask};
 use super::ie_main::{
-    ImageEditor, Tool, FilterPanel, TextLayer, CropState, TransformHandleSet,
-    BrushShape, BrushTextureMode, RetouchMode, LayerKind, RgbaColor,
+    ImageEditor, Tool, FilterPanel, BlendMode, TextLayer, TextStyle, TextDirection, CropState, DimensionSnap, TransformHandleSet, THandle,
+    BrushShape, BrushTextureMode, RetouchMode, LayerKind, RgbaColor, ImageSlice, Measurement, MAX_MEASUREMENTS, RepeatableFilter, ADAPTIVE_FRAME_MS, COARSE_STEP_SCALE, EqualizeMode, StrokePosition, ExportPreset, RememberedExport,
+    VariantExport, ViewportExport, VARIANT_SCALES, VARIANT_UPSCALE_WARN, is_known_font, FillPreview, FILL_PREVIEW_DELAY, FILL_PREVIEW_BUDGET, FILL_HOVER_SLOP, WandJob, SavedComparison, DataUriJob, DATA_URI_WARN_BYTES, FillMode,
+    RECENTLY_DELETED_LIMIT, ImageClip, PASTE_OFFSET, FloatingPixels,
 };
+use super::ie_select::{self, Selection, SelectOp};
+use super::ie_compare::{self, CompareMode};
+use super::ie_source::read_with_source;
+
+const EDGE_SCROLL_MARGIN: f32 = 24.0;
+const EDGE_SCROLL_MAX: f32 = 160.0;
+const EDGE_SCROLL_SPEED: f32 = 10.0;
 
 static FONT_CACHE: OnceLock<[FontRef<'static>; 12]> = OnceLock::new();
 
+fn emoji_fonts() -> &'static [FontRef<'static>] {
+    static EMOJI_FONTS: OnceLock<Vec<FontRef<'static>>> = OnceLock::new();
+    EMOJI_FONTS.get_or_init(|| crate::style::emoji_font_data().iter().filter_map(|b| FontRef::try_from_slice_and_index(b, 0).ok()).collect())
+}
+
 fn cached_fonts() -> &'static [FontRef<'static>; 12] {
     FONT_CACHE.get_or_init(|| [
         FontRef::try_from_slice(FONT_UB_REG).expect("ub"),
@@ -45,7 +68,7 @@ impl ImageEditor {
         let active_id = self.active_layer_id;
         let (kind, locked) = self.layers.iter().find(|l| l.id == active_id)
             .map(|l| (l.kind, l.locked)).unwrap_or((LayerKind::Background, false));
-        if locked || matches!(kind, LayerKind::Text) { return; }
+        if locked || matches!(kind, LayerKind::Text | LayerKind::Shape) { return; }
         if kind == LayerKind::Image { self.apply_brush_stroke_on_image_layer(); return; }
 
         let swapped_bg = if kind == LayerKind::Raster {
@@ -81,7 +104,8 @@ impl ImageEditor {
         let aspect = bs.aspect_ratio.clamp(0.05, 1.0);
         let wetness = if is_eraser { 0.0 } else { bs.wetness.clamp(0.0, 1.0) };
         let spray_mode = !is_eraser && bs.spray_mode;
-        let step_dist = if spray_mode { radius.max(1.0) } else { (radius * 2.0 * bs.step).max(0.5) };
+        let blend = if is_eraser { BlendMode::Normal } else { self.stroke_blend };
+        let step_dist = if spray_mode { radius.max(1.0) } else { (radius * 2.0 * bs.step).max(0.5) } * if self.perf.coarse { COARSE_STEP_SCALE } else { 1.0 };
 
         let (mut dr_x0, mut dr_y0, mut dr_x1, mut dr_y1) = (u32::MAX, u32::MAX, 0u32, 0u32);
 
@@ -104,21 +128,13 @@ impl ImageEditor {
                     let alpha = ((1.0 - t*t) * flow * opacity * 255.0).clamp(0.0, 255.0) as u8;
                     if alpha == 0 { continue; }
                     unsafe {
-                        let [er,eg,eb,ea] = buf.unsafe_get_pixel(px, py).0;
-                        let fa = alpha as u16;
-                        let bf = (base_a as u16 * fa) / 255;
-                        let ba = 255 - bf;
-                        buf.unsafe_put_pixel(px, py, Rgba([
-                            ((r as u16*bf + er as u16*ba)/255) as u8,
-                            ((g as u16*bf + eg as u16*ba)/255) as u8,
-                            ((b_ch as u16*bf + eb as u16*ba)/255) as u8,
-                            ((bf + ea as u16*ba/255).min(255)) as u8,
-                        ]));
+                        let bf = ((base_a as u16 * alpha as u16 + 127) / 255) as u8;
+                        buf.unsafe_put_pixel(px, py, Rgba(stamp_u8(buf.unsafe_get_pixel(px, py).0, [r, g, b_ch], bf, blend)));
                     }
                 }
             }
             self.dirty = true;
-            if dr_x1 > dr_x0 && dr_y1 > dr_y0 { self.expand_dirty_rect(dr_x0, dr_y0, dr_x1, dr_y1); }
+            if dr_x1 > dr_x0 && dr_y1 > dr_y0 { self.clip_stroke_to_selection(kind, active_id, [dr_x0, dr_y0, dr_x1, dr_y1]); self.expand_dirty_rect(dr_x0, dr_y0, dr_x1, dr_y1); }
             self.texture_dirty = true;
             if let Some(old_bg) = swapped_bg { self.restore_layer_swap(active_id, old_bg); } else { self.promote_dirty_to_composite(); }
             return;
@@ -162,9 +178,7 @@ impl ImageEditor {
                             let new_pixel = if is_eraser && eraser_transparent_eff {
                                 Rgba([er, eg, eb, ea.saturating_sub(alpha)])
                             } else {
-                                let fa = alpha as u16;
-                                let bf = (base_a as u16 * fa) / 255;
-                                let ba = 255 - bf;
+                                let bf = ((base_a as u16 * alpha as u16 + 127) / 255) as u8;
                                 let (paint_r, paint_g, paint_b) = if wetness > 0.0 {
                                     let (vis_r, vis_g, vis_b) = if let Some((bd_ptr, bd_w, bd_h)) = backdrop_raw {
                                         if px < bd_w && py < bd_h {
@@ -187,12 +201,7 @@ impl ImageEditor {
                                      ((g as f32*(1.0-w) + vis_g as f32*w) as u16).min(255) as u8,
                                      ((b_ch as f32*(1.0-w) + vis_b as f32*w) as u16).min(255) as u8)
                                 } else { (r, g, b_ch) };
-                                Rgba([
-                                    ((paint_r as u16*bf + er as u16*ba)/255) as u8,
-                                    ((paint_g as u16*bf + eg as u16*ba)/255) as u8,
-                                    ((paint_b as u16*bf + eb as u16*ba)/255) as u8,
-                                    ((bf + ea as u16*ba/255).min(255)) as u8,
-                                ])
+                                Rgba(stamp_u8([er, eg, eb, ea], [paint_r, paint_g, paint_b], bf, blend))
                             };
                             buf.unsafe_put_pixel(px, py, new_pixel);
                         }
@@ -201,11 +210,57 @@ impl ImageEditor {
             }
         }
         self.dirty = true;
-        if dr_x1 > dr_x0 && dr_y1 > dr_y0 { self.expand_dirty_rect(dr_x0, dr_y0, dr_x1, dr_y1); }
+        if dr_x1 > dr_x0 && dr_y1 > dr_y0 { self.clip_stroke_to_selection(kind, active_id, [dr_x0, dr_y0, dr_x1, dr_y1]); self.expand_dirty_rect(dr_x0, dr_y0, dr_x1, dr_y1); }
         self.texture_dirty = true;
         if let Some(old_bg) = swapped_bg { self.restore_layer_swap(active_id, old_bg); } else { self.promote_dirty_to_composite(); }
     }
 
+    fn clip_stroke_to_selection(&mut self, kind: LayerKind, id: u64, rect: [u32; 4]) {
+        let (Some(sel), Some(entry)) = (&self.selection, self.history.last()) else { return };
+        let before = match kind { LayerKind::Background => entry.image.as_ref(), LayerKind::Raster => entry.layer_images.get(&id), _ => None };
+        if let (Some(before), Some(DynamicImage::ImageRgba8(buf))) = (before, self.image.as_mut()) { sel.restore_outside(buf, before, rect); }
+    }
+
+    pub(super) fn within_selection(&self, before: Option<&DynamicImage>, after: DynamicImage) -> DynamicImage {
+        match (&self.selection, before) { (Some(sel), Some(before)) => sel.keep_outside(before, after), _ => after }
+    }
+
+    pub(super) fn timed_brush_stroke(&mut self) {
+        let started = std::time::Instant::now();
+        if self.adaptive_strokes && self.image_layer_for_active().is_none() {
+            if self.perf.stroke_path.is_empty() { self.perf.stroke_path.extend_from_slice(&self.stroke_points); }
+            else if let Some(p) = self.stroke_points.last() { self.perf.stroke_path.push(*p); }
+            if self.perf.frame_ms > ADAPTIVE_FRAME_MS { self.perf.coarse = true; }
+        }
+        self.apply_brush_stroke();
+        self.perf.record_stroke(started);
+    }
+
+    pub(super) fn refine_coarse_stroke(&mut self) {
+        let path = std::mem::take(&mut self.perf.stroke_path);
+        if !std::mem::take(&mut self.perf.coarse) || path.len() < 2 { return; }
+        let id = self.active_layer_id;
+        let Some(entry) = self.history.last() else { return };
+        match self.layers.iter().find(|l| l.id == id).map(|l| l.kind) {
+            Some(LayerKind::Background) => self.image = entry.image.clone(),
+            Some(LayerKind::Raster) => if let Some(img) = entry.layer_images.get(&id).cloned() { self.layer_images.insert(id, img); },
+            _ => return,
+        }
+        for seg in path.windows(2) {
+            self.stroke_points = seg.to_vec();
+            self.apply_brush_stroke();
+        }
+        self.stroke_points.clear();
+        self.raster_layer_dirty_rects.remove(&id);
+        self.texture_dirty_rect = None;
+        self.composite_dirty_rect = None;
+        self.composite_dirty = true;
+    }
+
+    pub(super) fn undo_memory_bytes(&self) -> usize {
+        self.history.bytes()
+    }
+
     pub(super) fn promote_dirty_to_composite(&mut self) {
         if self.layers.iter().any(|l| l.visible && l.kind == LayerKind::Image) {
             let rect = self.texture_dirty_rect.take();
@@ -230,11 +285,90 @@ impl ImageEditor {
         self.raster_layer_texture_dirty.insert(active_id);
     }
 
-    pub(super) fn flood_fill(&mut self, start_x: u32, start_y: u32) {
+    fn fill_source(&self) -> Option<&DynamicImage> {
+        let layer = self.layers.iter().find(|l| l.id == self.active_layer_id).filter(|l| !l.locked)?;
+        match layer.kind { LayerKind::Background => self.image.as_ref(), LayerKind::Raster => self.layer_images.get(&layer.id), _ => None }
+    }
+
+    pub(super) fn clear_fill_preview(&mut self) {
+        if let Some(p) = self.fill_preview.take() { p.cancel.store(true, Ordering::Relaxed); }
+    }
+
+    pub(super) fn update_fill_preview(&mut self, ctx: &egui::Context, hover: Option<egui::Pos2>) {
+        let target = hover.filter(|_| !self.base_frozen() && !self.is_processing()).and_then(|mp| self.screen_to_image(mp).map(|px| (mp, px)));
+        let Some((mp, (x, y))) = target else { self.fill_hover = None; self.clear_fill_preview(); return };
+        let now = ctx.input(|i| i.time);
+        let settled_at = match self.fill_hover {
+            Some((anchor, t)) if anchor.distance(mp) <= FILL_HOVER_SLOP => t,
+            _ => { self.fill_hover = Some((mp, now)); now }
+        };
+        let key = (self.active_layer_id, self.pixel_revision, x, y, self.fill_tolerance, self.fill_global);
+        if self.fill_preview.as_ref().is_some_and(|p| p.key == key) { return; }
+        self.clear_fill_preview();
+        if now - settled_at < FILL_PREVIEW_DELAY { ctx.request_repaint_after(std::time::Duration::from_secs_f64(FILL_PREVIEW_DELAY - (now - settled_at))); return; }
+        let Some(buf) = self.fill_source().map(|img| img.to_rgba8()) else { return };
+        let cancel = Arc::new(AtomicBool::new(false));
+        let (tx, rx) = sync_channel(1);
+        let (flag, tolerance, contiguous) = (cancel.clone(), self.fill_tolerance, !self.fill_global);
+        thread::spawn(move || { let _ = tx.send(fill_mask(&buf, x, y, tolerance, contiguous, Some(std::time::Instant::now() + FILL_PREVIEW_BUDGET), &flag)); });
+        self.fill_preview = Some(FillPreview { key, cancel, job: Some(rx), mask: None, texture: None });
+    }
+
+    pub(super) fn check_fill_preview(&mut self, ctx: &egui::Context) {
+        let Some(p) = &mut self.fill_preview else { return };
+        let Some(rx) = &p.job else { return };
+        match rx.try_recv() {
+            Ok(mask) => {
+                let tint = crate::style::ColorPalette::BLUE_500.gamma_multiply(0.4);
+                p.texture = mask.as_ref().map(|m| ctx.load_texture("fill_preview", m.to_color_image(tint), egui::TextureOptions::NEAREST));
+                p.mask = mask; p.job = None;
+            }
+            Err(TryRecvError::Empty) => ctx.request_repaint_after(std::time::Duration::from_millis(30)),
+            Err(TryRecvError::Disconnected) => p.job = None,
+        }
+    }
+
+    pub(super) fn take_fill_preview_mask(&mut self, x: u32, y: u32) -> Option<FillMask> {
+        let p = self.fill_preview.take()?;
+        p.cancel.store(true, Ordering::Relaxed);
+        if self.pixels_pending() || p.key != (self.active_layer_id, self.pixel_revision, x, y, self.fill_tolerance, self.fill_global) { return None; }
+        p.mask
+    }
+
+    /// The dragged gradient axis, when the fill mode uses one.
+    fn fill_axis(&self) -> Option<((f32, f32), (f32, f32))> { self.fill_gradient.filter(|_| self.fill_mode != FillMode::Solid) }
+
+    /// Fill color per pixel: the primary color, or along `axis` (in the same coordinates as the pixels) the blend
+    /// toward the secondary color, sampled at pixel centers.
+    fn fill_colors(&self, axis: Option<((f32, f32), (f32, f32))>) -> impl Fn(u32, u32) -> [u8; 4] + use<> {
+        let rgba = |c: egui::Color32| [c.r(), c.g(), c.b(), c.a()];
+        let (from, to, mode) = (rgba(self.color), rgba(self.secondary_color), self.fill_mode);
+        move |x, y| match axis {
+            Some((start, end)) => gradient_color(mode, start, end, from, to, (x as f32 + 0.5, y as f32 + 0.5)),
+            None => from,
+        }
+    }
+
+    /// Fills the region under the start of the dragged gradient axis and clears the axis.
+    pub(super) fn finish_gradient_fill(&mut self) {
+        let Some(((sx, sy), _)) = self.fill_gradient else { return };
+        if self.image_layer_for_active().is_some() {
+            self.push_undo();
+            self.flood_fill_image_layer(sx as u32, sy as u32);
+            self.composite_dirty = true;
+            self.add_color_to_history();
+        } else if let Some((w, h)) = self.image.as_ref().map(|i| i.dimensions()) && sx >= 0.0 && sy >= 0.0 && (sx as u32) < w && (sy as u32) < h {
+            let mask = self.take_fill_preview_mask(sx as u32, sy as u32);
+            self.push_undo(); self.flood_fill(sx as u32, sy as u32, mask); self.add_color_to_history();
+        }
+        self.fill_gradient = None;
+    }
+
+    pub(super) fn flood_fill(&mut self, start_x: u32, start_y: u32, mask: Option<FillMask>) {
         let active_id = self.active_layer_id;
         let (kind, locked) = self.layers.iter().find(|l| l.id == active_id)
             .map(|l| (l.kind, l.locked)).unwrap_or((LayerKind::Background, false));
-        if locked || matches!(kind, LayerKind::Text) { return; }
+        if locked || matches!(kind, LayerKind::Text | LayerKind::Shape) { return; }
         if kind == LayerKind::Image { self.flood_fill_image_layer(start_x, start_y); return; }
 
         let swapped_bg = if kind == LayerKind::Raster {
@@ -245,31 +379,20 @@ impl ImageEditor {
 
         let img = match self.image.as_mut() { Some(i) => i, None => return };
         let mut buf = img.to_rgba8();
-        let (width, height) = (buf.width(), buf.height());
         let target = buf.get_pixel(start_x, start_y).0;
-        let fill = [self.color.r(), self.color.g(), self.color.b(), self.color.a()];
-        if target == fill {
+        let axis = self.fill_axis();
+        let fill = self.fill_colors(axis);
+        let mask = if axis.is_none() && target == fill(start_x, start_y) { None } else { mask.or_else(|| fill_mask(&buf, start_x, start_y, self.fill_tolerance, !self.fill_global, None, &AtomicBool::new(false))) };
+        let Some(mask) = mask else {
             if let Some(old_bg) = swapped_bg {
                 self.layer_images.insert(active_id, self.image.take().unwrap());
                 self.image = Some(old_bg);
             }
             return;
-        }
-        let mut visited = vec![false; (width * height) as usize];
-        let mut stack = vec![(start_x, start_y)];
-        while let Some((x, y)) = stack.pop() {
-            let idx = (y * width + x) as usize;
-            if visited[idx] { continue; }
-            visited[idx] = true;
-            let cur = buf.get_pixel(x, y).0;
-            if (0..4).map(|i| (cur[i] as i32 - target[i] as i32).abs()).sum::<i32>() > 30 { continue; }
-            buf.put_pixel(x, y, Rgba(fill));
-            if x > 0 { stack.push((x-1, y)); }
-            if x+1 < width { stack.push((x+1, y)); }
-            if y > 0 { stack.push((x, y-1)); }
-            if y+1 < height { stack.push((x, y+1)); }
-        }
-        let result = DynamicImage::ImageRgba8(buf);
+        };
+        mask.paint(&mut buf, &fill);
+        if self.fill_antialias { mask.antialias_edges(&mut buf, target, &fill, self.fill_tolerance); }
+        let result = self.within_selection(self.image.as_ref(), DynamicImage::ImageRgba8(buf));
         if let Some(old_bg) = swapped_bg {
             self.layer_images.insert(active_id, result);
             self.image = Some(old_bg);
@@ -280,6 +403,8 @@ impl ImageEditor {
 
     pub(super) fn flood_fill_image_layer(&mut self, start_x: u32, start_y: u32) {
         let iid = match self.image_layer_for_active() { Some(id) => id, None => return };
+        let axis = self.image_layer_data.get(&iid).and_then(|ild| self.fill_axis().map(|(s, e)| (ild.canvas_to_local_f32(s.0, s.1), ild.canvas_to_local_f32(e.0, e.1))));
+        let fill = self.fill_colors(axis);
         let ild = match self.image_layer_data.get_mut(&iid) { Some(d) => d, None => return };
         let (lx_f, ly_f) = ild.canvas_to_local_f32(start_x as f32, start_y as f32);
         if lx_f < 0.0 || ly_f < 0.0 || lx_f >= ild.orig_w() as f32 || ly_f >= ild.orig_h() as f32 { return; }
@@ -289,35 +414,41 @@ impl ImageEditor {
         let (width, height) = (buf.width(), buf.height());
         if lx >= width || ly >= height { return; }
         let target = buf.get_pixel(lx, ly).0;
-        let fill = [self.color.r(), self.color.g(), self.color.b(), self.color.a()];
-        if target == fill { return; }
-        let mut visited = vec![false; (width * height) as usize];
-        let mut stack = vec![(lx, ly)];
-        let (mut dr_x0, mut dr_y0, mut dr_x1, mut dr_y1) = (width, height, 0u32, 0u32);
-        while let Some((x, y)) = stack.pop() {
-            let idx = (y * width + x) as usize;
-            if visited[idx] { continue; }
-            visited[idx] = true;
-            let cur = buf.get_pixel(x, y).0;
-            if (0..4).map(|i| (cur[i] as i32 - target[i] as i32).abs()).sum::<i32>() > 30 { continue; }
-            buf.put_pixel(x, y, Rgba(fill));
-            dr_x0=dr_x0.min(x); dr_y0=dr_y0.min(y); dr_x1=dr_x1.max(x); dr_y1=dr_y1.max(y);
-            if x > 0 { stack.push((x-1, y)); }
-            if x+1 < width { stack.push((x+1, y)); }
-            if y > 0 { stack.push((x, y-1)); }
-            if y+1 < height { stack.push((x, y+1)); }
-        }
-        if dr_x1 >= dr_x0 && dr_y1 >= dr_y0 {
-            let entry = self.image_layer_stroke_rects.entry(iid).or_insert([width, height, 0, 0]);
-            entry[0]=entry[0].min(dr_x0); entry[1]=entry[1].min(dr_y0);
-            entry[2]=entry[2].max(dr_x1); entry[3]=entry[3].max(dr_y1);
-        }
+        if axis.is_none() && target == fill(lx, ly) { return; }
+        let Some(mask) = fill_mask(buf, lx, ly, self.fill_tolerance, !self.fill_global, None, &AtomicBool::new(false)) else { return };
+        mask.paint(buf, &fill);
+        let grow = if self.fill_antialias { mask.antialias_edges(buf, target, &fill, self.fill_tolerance); 1 } else { 0 };
+        let entry = self.image_layer_stroke_rects.entry(iid).or_insert([width, height, 0, 0]);
+        entry[0]=entry[0].min(mask.x0.saturating_sub(grow)); entry[1]=entry[1].min(mask.y0.saturating_sub(grow));
+        entry[2]=entry[2].max((mask.x0 + mask.w - 1 + grow).min(width - 1)); entry[3]=entry[3].max((mask.y0 + mask.h - 1 + grow).min(height - 1));
         self.image_layer_texture_dirty.insert(iid);
         self.composite_dirty = true; self.composite_dirty_rect = None;
         self.texture_dirty = true; self.dirty = true;
     }
 
     pub(super) fn sample_color(&mut self, x: u32, y: u32) {
+        let result = self.composite_pixel_at(x, y);
+        self.color = egui::Color32::from_rgba_unmultiplied(result[0], result[1], result[2], result[3]);
+        self.add_color_to_history();
+        self.hex_input = RgbaColor::from_egui(self.color).to_hex();
+    }
+
+    pub(super) fn cursor_outline_color(&self, pos: egui::Pos2, eraser: bool) -> egui::Color32 {
+        let lum = match self.screen_to_image(pos) {
+            Some((x, y)) => {
+                let p = self.composite_pixel_at(x, y);
+                let a = p[3] as f32 / 255.0;
+                (0.299 * p[0] as f32 + 0.587 * p[1] as f32 + 0.114 * p[2] as f32) / 255.0 * a + 0.9 * (1.0 - a)
+            }
+            None => self.settings.canvas_surround.color().map_or(0.9, |c| c.r() as f32 / 255.0),
+        };
+        match (eraser, lum < 0.5) {
+            (false, true) => egui::Color32::WHITE, (false, false) => egui::Color32::BLACK,
+            (true, true) => crate::style::ColorPalette::RED_300, (true, false) => crate::style::ColorPalette::RED_700,
+        }
+    }
+
+    pub(super) fn composite_pixel_at(&self, x: u32, y: u32) -> [u8; 4] {
         let mut result = [0u8; 4];
         for layer in &self.layers {
             if !layer.visible { continue; }
@@ -337,7 +468,7 @@ impl ImageEditor {
                         })
                     } else { None }
                 },
-                LayerKind::Text => None,
+                LayerKind::Text | LayerKind::Shape => None,
             };
             if let Some(p) = pixel {
                 let sa = (p[3] as f32 / 255.0) * layer.opacity.clamp(0.0, 1.0);
@@ -352,22 +483,89 @@ impl ImageEditor {
                 }
             }
         }
-        self.color = egui::Color32::from_rgba_unmultiplied(result[0], result[1], result[2], result[3]);
-        self.add_color_to_history();
-        self.hex_input = RgbaColor::from_egui(self.color).to_hex();
+        result
     }
 
-    pub(super) fn stamp_single_text_layer(&self, base: &DynamicImage, tl: &TextLayer, opacity: f32) -> DynamicImage {
-        let fonts = cached_fonts();
-        let font: &FontRef = match (tl.font_name.as_str(), tl.bold, tl.italic) {
-            ("Roboto", true, _) => &fonts[4], ("Roboto", _, true) => &fonts[5], ("Roboto", ..) => &fonts[3],
-            ("GoogleSans", true, _) => &fonts[7], ("GoogleSans", _, true) => &fonts[8], ("GoogleSans", ..) => &fonts[6],
-            ("OpenSans", true, _) => &fonts[10], ("OpenSans", _, true) => &fonts[11], ("OpenSans", ..) => &fonts[9],
-            (_, true, _) => &fonts[1], (_, _, true) => &fonts[2], _ => &fonts[0],
-        };
+    fn text_layer_font_index(tl: &TextLayer) -> usize {
+        match (tl.effective_font(), tl.bold, tl.italic) {
+            ("Roboto", true, _) => 4, ("Roboto", _, true) => 5, ("Roboto", ..) => 3,
+            ("GoogleSans", true, _) => 7, ("GoogleSans", _, true) => 8, ("GoogleSans", ..) => 6,
+            ("OpenSans", true, _) => 10, ("OpenSans", _, true) => 11, ("OpenSans", ..) => 9,
+            (_, true, _) => 1, (_, _, true) => 2, _ => 0,
+        }
+    }
+
+    fn text_layer_font(tl: &TextLayer) -> &'static FontRef<'static> { &cached_fonts()[Self::text_layer_font_index(tl)] }
+
+    fn text_layer_font_data(tl: &TextLayer) -> &'static [u8] {
+        [FONT_UB_REG, FONT_UB_BLD, FONT_UB_ITL, FONT_RB_REG, FONT_RB_BLD, FONT_RB_ITL, FONT_GS_REG, FONT_GS_BLD, FONT_GS_ITL, FONT_OS_REG, FONT_OS_BLD, FONT_OS_ITL][Self::text_layer_font_index(tl)]
+    }
+
+    fn text_layer_char_advance(tl: &TextLayer, scale: PxScale) -> impl Fn(char) -> f32 {
+        let scaled = Self::text_layer_font(tl).as_scaled(scale);
+        let tab_w = scaled.h_advance(scaled.glyph_id(' ')) * tl.tab_width.max(1) as f32;
+        move |c| if c == '\t' { tab_w } else { scaled.h_advance(scaled.glyph_id(c)) }
+    }
+
+    fn text_layer_line_ranges(tl: &TextLayer) -> Vec<std::ops::Range<usize>> {
+        let advance = Self::text_layer_char_advance(tl, PxScale::from(tl.font_size));
+        let wrap_w = tl.box_width.unwrap_or(f32::MAX);
+        let (mut out, mut start) = (Vec::new(), 0usize);
+        for paragraph in tl.content.split('\n') {
+            let (mut line_start, mut pos, mut w) = (start, start, 0.0f32);
+            for word in paragraph.split_inclusive([' ', '\t']) {
+                let ww: f32 = word.chars().map(&advance).sum();
+                if ww > wrap_w {
+                    for ch in word.chars() {
+                        let cw = advance(ch);
+                        if w + cw > wrap_w && pos > line_start { out.push(line_start..pos); line_start = pos; w = 0.0; }
+                        w += cw; pos += ch.len_utf8();
+                    }
+                } else {
+                    if w + ww > wrap_w && pos > line_start { out.push(line_start..pos); line_start = pos; w = 0.0; }
+                    w += ww; pos += word.len();
+                }
+            }
+            out.push(line_start..start + paragraph.len());
+            start += paragraph.len() + 1;
+        }
+        out
+    }
+
+    pub(super) fn text_layer_uses_bidi(tl: &TextLayer) -> bool {
+        tl.direction == TextDirection::Rtl || (tl.direction == TextDirection::Ltr && has_rtl(&tl.content))
+    }
+
+    pub(super) fn text_layer_bidi_display(tl: &TextLayer) -> (String, Vec<(usize, bool)>, Vec<String>) {
+        let rtl_base = tl.direction == TextDirection::Rtl;
+        let starts: Vec<usize> = tl.content.char_indices().map(|(b, _)| b).collect();
+        let mut caret = vec![(0usize, rtl_base); starts.len() + 1];
+        let (mut display, mut d, mut lines) = (String::new(), 0usize, Vec::new());
+        for (li, r) in Self::text_layer_line_ranges(tl).into_iter().enumerate() {
+            if li > 0 { display.push('\n'); d += 1; }
+            let (line_start_d, line) = (d, &tl.content[r.clone()]);
+            for (run, rtl) in bidi_visual_runs(line, rtl_base) {
+                let chars: Vec<(usize, char)> = line[run.clone()].char_indices().map(|(b, c)| (r.start + run.start + b, c)).collect();
+                let ordered: Box<dyn Iterator<Item = &(usize, char)>> = if rtl { Box::new(chars.iter().rev()) } else { Box::new(chars.iter()) };
+                for &(b, c) in ordered {
+                    caret[starts.partition_point(|&s| s < b)] = (d, rtl);
+                    display.push(c); d += 1;
+                }
+            }
+            let end_ci = starts.partition_point(|&s| s < r.end);
+            caret[end_ci] = match line.chars().next_back() {
+                Some(prev) => { let (pd, prtl) = caret[starts.partition_point(|&s| s < r.end - prev.len_utf8())]; (pd, !prtl) }
+                None => (line_start_d, false),
+            };
+            lines.push(line.to_string());
+        }
+        (display, caret, lines)
+    }
+
+    fn text_layer_lines(tl: &TextLayer) -> Vec<String> {
+        let advance = Self::text_layer_char_advance(tl, PxScale::from(tl.font_size));
         let wrap_w = tl.box_width.unwrap_or(f32::MAX);
-        let early_scaled = font.as_scaled(PxScale::from(tl.font_size));
-        let visual_lines: Vec<String> = if !tl.cached_lines.is_empty() {
+        if !tl.cached_lines.is_empty() {
             tl.cached_lines.clone()
         } else {
             let mut lines = Vec::new();
@@ -375,11 +573,11 @@ impl ImageEditor {
                 if paragraph.is_empty() { lines.push(String::new()); continue; }
                 let mut cur_line = String::new();
                 let mut cur_w = 0.0f32;
-                for word in paragraph.split_inclusive(' ') {
-                    let w: f32 = word.chars().map(|c| early_scaled.h_advance(font.glyph_id(c))).sum();
+                for word in paragraph.split_inclusive([' ', '\t']) {
+                    let w: f32 = word.chars().map(&advance).sum();
                     if w > wrap_w {
                         for ch in word.chars() {
-                            let cw = early_scaled.h_advance(font.glyph_id(ch));
+                            let cw = advance(ch);
                             if cur_w + cw > wrap_w && !cur_line.is_empty() { lines.push(cur_line.clone()); cur_line.clear(); cur_w = 0.0; }
                             cur_line.push(ch); cur_w += cw;
                         }
@@ -390,18 +588,27 @@ impl ImageEditor {
                 lines.push(cur_line);
             }
             lines
-        };
-        let num_lines = visual_lines.len().max(1);
-        let line_h = if tl.rendered_height > 0.0 { tl.rendered_height / num_lines as f32 } else { tl.font_size * 1.35 };
-        let actual_h = if tl.rendered_height > 0.0 { tl.rendered_height } else { num_lines as f32 * line_h };
+        }
+    }
+
+    pub(super) fn stamp_single_text_layer(&self, base: &DynamicImage, tl: &TextLayer, opacity: f32) -> DynamicImage {
+        let font = Self::text_layer_font(tl);
+        let visual_lines = Self::text_layer_lines(tl);
+        let bidi = Self::text_layer_uses_bidi(tl);
+        let line_ranges = if bidi { Self::text_layer_line_ranges(tl) } else { Vec::new() };
+        let num_lines = if bidi { line_ranges.len() } else { visual_lines.len() }.max(1);
+        let vertical = tl.direction == TextDirection::Vertical;
+        let line_h = if vertical { tl.font_size } else if tl.rendered_height > 0.0 { tl.rendered_height / num_lines as f32 } else { tl.font_size * 1.35 };
+        let actual_h = if vertical { tl.box_height.unwrap_or_else(|| tl.auto_height(1.0)) } else if tl.rendered_height > 0.0 { tl.rendered_height } else { num_lines as f32 * line_h };
         let bw = tl.box_width.unwrap_or_else(|| tl.auto_width(1.0));
         let scale = PxScale::from(line_h);
         let scaled = font.as_scaled(scale);
+        let space_w = scaled.h_advance(font.glyph_id(' '));
         let (ibw, ibh) = (bw.ceil() as usize, actual_h.ceil() as usize);
         let mut tbuf: Vec<[f32; 4]> = vec![[0.0; 4]; ibw * ibh];
         let (cr, cg, cb) = (srgb_to_linear(tl.color.r()), srgb_to_linear(tl.color.g()), srgb_to_linear(tl.color.b()));
         let ca = tl.color.a() as f32 / 255.0 * opacity;
-        let put = |tbuf: &mut Vec<[f32;4]>, tx: i32, ty: i32, cov: f32| {
+        let put_rgb = |tbuf: &mut Vec<[f32;4]>, tx: i32, ty: i32, cov: f32, (cr, cg, cb): (f32, f32, f32)| {
             if tx < 0 || ty < 0 || tx >= ibw as i32 || ty >= ibh as i32 { return; }
             let idx = ty as usize * ibw + tx as usize;
             let src_a = (cov * ca).min(1.0); let dst = &mut tbuf[idx];
@@ -412,21 +619,130 @@ impl ImageEditor {
             dst[2] = (cb * src_a + dst[2] * dst[3] * (1.0 - src_a)) / out_a;
             dst[3] = out_a;
         };
-        for (li, line) in visual_lines.iter().enumerate() {
-            let base_y = li as f32 * line_h + scaled.ascent();
-            let mut cx2 = 0.0f32;
-            for ch in line.chars() {
-                let gid = font.glyph_id(ch); let adv = scaled.h_advance(gid);
-                let glyph = gid.with_scale_and_position(scale, point(cx2, 0.0));
-                if let Some(o) = font.outline_glyph(glyph) {
-                    let b = o.px_bounds();
-                    o.draw(|gx, gy, cov| put(&mut tbuf, (b.min.x + gx as f32) as i32, (base_y + b.min.y + gy as f32) as i32, cov));
+        let put = |tbuf: &mut Vec<[f32;4]>, tx: i32, ty: i32, cov: f32| put_rgb(tbuf, tx, ty, cov, (cr, cg, cb));
+        let em_px = line_h * font.units_per_em().unwrap_or(1000.0) / font.height_unscaled();
+        let pick = |ch: char| -> (&'static FontRef<'static>, PxScale) {
+            if font.glyph_id(ch).0 == 0 && !ch.is_whitespace() {
+                emoji_fonts().iter().find(|f| f.glyph_id(ch).0 != 0)
+                    .map(|f| (f, PxScale::from(em_px * f.height_unscaled() / f.units_per_em().unwrap_or(1000.0))))
+                    .unwrap_or((font, scale))
+            } else { (font, scale) }
+        };
+        let cluster = |g: &str| -> (Vec<(&'static FontRef<'static>, ab_glyph::GlyphId, PxScale, f32)>, f32) {
+            let mut chars = g.chars();
+            let base = chars.next().unwrap_or(' ');
+            let (gfont, gscale) = pick(base);
+            let gid = gfont.glyph_id(base); let adv = gfont.as_scaled(gscale).h_advance(gid);
+            let mut glyphs = vec![(gfont, gid, gscale, 0.0)];
+            for c in chars.take_while(|&c| c != '\u{200D}') {
+                let mid = gfont.glyph_id(c);
+                if mid.0 == 0 || matches!(c, '\u{FE00}'..='\u{FE0F}' | '\u{1F3FB}'..='\u{1F3FF}' | '\u{E0020}'..='\u{E007F}') { continue; }
+                let centre = |id: ab_glyph::GlyphId| gfont.outline_glyph(id.with_scale(gscale)).map_or(adv / 2.0, |o| { let b = o.px_bounds(); (b.min.x + b.max.x) / 2.0 });
+                glyphs.push((gfont, mid, gscale, centre(gid) - centre(mid)));
+            }
+            (glyphs, adv)
+        };
+        let draw_glyph = |tbuf: &mut Vec<[f32;4]>, gfont: &FontRef<'static>, gid: ab_glyph::GlyphId, gscale: PxScale, x: f32, base_y: f32| {
+            let glyph = gid.with_scale_and_position(gscale, point(x, 0.0));
+            if let Some(o) = gfont.outline_glyph(glyph) {
+                let b = o.px_bounds();
+                o.draw(|gx, gy, cov| put(tbuf, (b.min.x + gx as f32) as i32, (base_y + b.min.y + gy as f32) as i32, cov));
+            } else if let Some((img, bmp)) = gfont.glyph_raster_image2(gid, em_px.ceil() as u16)
+                .filter(|g| matches!(g.format, GlyphImageFormat::Png) && g.pixels_per_em > 0)
+                .and_then(|g| image::load_from_memory(g.data).ok().map(|d| (g, d.to_rgba8())))
+            {
+                let s = em_px / img.pixels_per_em as f32;
+                let (dw, dh) = ((bmp.width() as f32 * s).round().max(1.0) as i32, (bmp.height() as f32 * s).round().max(1.0) as i32);
+                let (ox, oy) = ((x + img.origin.x * s).round() as i32, (base_y - (img.origin.y + bmp.height() as f32) * s).round() as i32);
+                for dy in 0..dh {
+                    for dx in 0..dw {
+                        let sx = ((dx as f32 + 0.5) / s) as u32; let sy = ((dy as f32 + 0.5) / s) as u32;
+                        let p = bmp.get_pixel(sx.min(bmp.width() - 1), sy.min(bmp.height() - 1)).0;
+                        if p[3] == 0 { continue; }
+                        put_rgb(tbuf, ox + dx, oy + dy, p[3] as f32 / 255.0, (srgb_to_linear(p[0]), srgb_to_linear(p[1]), srgb_to_linear(p[2])));
+                    }
+                }
+            }
+        };
+        let underline = |tbuf: &mut Vec<[f32;4]>, x0: f32, x1: f32, base_y: f32| {
+            let uly = (base_y + scaled.descent() + 2.0) as i32;
+            for ux in x0 as i32..x1 as i32 { put(tbuf, ux, uly, 1.0); }
+        };
+        if tl.direction == TextDirection::Vertical {
+            let (step, colw) = (tl.vertical_step(), tl.column_width());
+            for (ci, (_, col)) in tl.vertical_columns().iter().enumerate() {
+                let col_x = bw - (ci + 1) as f32 * colw;
+                for (ri, &(_, ch)) in col.iter().enumerate() {
+                    if ch == '\t' { continue; }
+                    let (gfont, gscale) = pick(ch);
+                    let gid = gfont.glyph_id(ch); let adv = gfont.as_scaled(gscale).h_advance(gid);
+                    draw_glyph(&mut tbuf, gfont, gid, gscale, col_x + (colw - adv) / 2.0, ri as f32 * step + scaled.ascent());
+                }
+                if tl.underline && !col.is_empty() {
+                    let ux = (col_x + colw - 1.0) as i32;
+                    for uy in 0..(col.len() as f32 * step) as i32 { put(&mut tbuf, ux, uy, 1.0); }
                 }
-                if tl.underline {
-                    let uly = (base_y + scaled.descent() + 2.0) as i32;
-                    for ux in cx2 as i32..(cx2+adv) as i32 { put(&mut tbuf, ux, uly, 1.0); }
+            }
+        } else if bidi {
+            let face = rustybuzz::Face::from_slice(Self::text_layer_font_data(tl), 0);
+            let factor = scaled.h_scale_factor();
+            for (li, range) in line_ranges.iter().enumerate() {
+                let base_y = li as f32 * line_h + scaled.ascent();
+                let line = &tl.content[range.clone()];
+                let mut placed: Vec<(&'static FontRef<'static>, ab_glyph::GlyphId, PxScale, f32, f32)> = Vec::new();
+                let mut x = 0.0f32;
+                for (run, rtl) in bidi_visual_runs(line, tl.direction == TextDirection::Rtl) {
+                    let text = &line[run];
+                    if let Some(face) = &face {
+                        let mut ubuf = rustybuzz::UnicodeBuffer::new();
+                        ubuf.push_str(text);
+                        ubuf.set_direction(if rtl { rustybuzz::Direction::RightToLeft } else { rustybuzz::Direction::LeftToRight });
+                        let shaped = rustybuzz::shape(face, &[], ubuf);
+                        let mut fallback_cluster = None;
+                        for (info, gp) in shaped.glyph_infos().iter().zip(shaped.glyph_positions()) {
+                            if text[info.cluster as usize..].starts_with('\t') {
+                                x += tab_advance(x, space_w, tl.tab_width);
+                            } else if info.glyph_id == 0 {
+                                if fallback_cluster.replace(info.cluster) == Some(info.cluster) { continue; }
+                                let g = text[info.cluster as usize..].graphemes(true).next().unwrap_or(" ");
+                                let (glyphs, adv) = cluster(g);
+                                placed.extend(glyphs.into_iter().map(|(gfont, gid, gscale, dx)| (gfont, gid, gscale, x + dx, 0.0)));
+                                x += adv;
+                            } else {
+                                placed.push((font, ab_glyph::GlyphId(info.glyph_id as u16), scale, x + gp.x_offset as f32 * factor, -(gp.y_offset as f32) * factor));
+                                x += gp.x_advance as f32 * factor;
+                            }
+                        }
+                    } else {
+                        let graphemes: Vec<&str> = if rtl { text.graphemes(true).rev().collect() } else { text.graphemes(true).collect() };
+                        for g in graphemes {
+                            if g == "\t" { x += tab_advance(x, space_w, tl.tab_width); continue; }
+                            let (glyphs, adv) = cluster(g);
+                            placed.extend(glyphs.into_iter().map(|(gfont, gid, gscale, dx)| (gfont, gid, gscale, x + dx, 0.0)));
+                            x += adv;
+                        }
+                    }
+                }
+                let x0 = if tl.direction == TextDirection::Rtl { bw - x } else { 0.0 };
+                for (gfont, gid, gscale, gx, gy) in placed { draw_glyph(&mut tbuf, gfont, gid, gscale, x0 + gx, base_y + gy); }
+                if tl.underline { underline(&mut tbuf, x0, x0 + x, base_y); }
+            }
+        } else {
+            for (li, line) in visual_lines.iter().enumerate() {
+                let base_y = li as f32 * line_h + scaled.ascent();
+                let mut cx2 = 0.0f32;
+                for g in line.graphemes(true) {
+                    if g == "\t" {
+                        let adv = tab_advance(cx2, space_w, tl.tab_width);
+                        if tl.underline { underline(&mut tbuf, cx2, cx2 + adv, base_y); }
+                        cx2 += adv;
+                        continue;
+                    }
+                    let (glyphs, adv) = cluster(g);
+                    for (gfont, gid, gscale, dx) in glyphs { draw_glyph(&mut tbuf, gfont, gid, gscale, cx2 + dx, base_y); }
+                    if tl.underline { underline(&mut tbuf, cx2, cx2 + adv, base_y); }
+                    cx2 += adv;
                 }
-                cx2 += adv;
             }
         }
         let rcx = tl.img_x + bw/2.0; let rcy = tl.img_y + actual_h/2.0;
@@ -453,32 +769,27 @@ impl ImageEditor {
                 let (tx0, ty0) = (lx as usize, ly as usize);
                 let (tx1, ty1) = ((tx0+1).min(ibw.saturating_sub(1)), (ty0+1).min(ibh.saturating_sub(1)));
                 let (fx, fy) = (lx - tx0 as f32, ly - ty0 as f32);
+                let premul = |p: [f32;4]| [p[0]*p[3], p[1]*p[3], p[2]*p[3], p[3]];
                 let lerp4 = |a: [f32;4], b: [f32;4], t: f32| -> [f32;4] {
                     [a[0]+(b[0]-a[0])*t, a[1]+(b[1]-a[1])*t, a[2]+(b[2]-a[2])*t, a[3]+(b[3]-a[3])*t]
                 };
                 let texel = lerp4(
-                    lerp4(tbuf[ty0*ibw+tx0], tbuf[ty0*ibw+tx1], fx),
-                    lerp4(tbuf[ty1*ibw+tx0], tbuf[ty1*ibw+tx1], fx),
+                    lerp4(premul(tbuf[ty0*ibw+tx0]), premul(tbuf[ty0*ibw+tx1]), fx),
+                    lerp4(premul(tbuf[ty1*ibw+tx0]), premul(tbuf[ty1*ibw+tx1]), fx),
                     fy,
                 );
-                if texel[3] < 1e-5 { continue; }
+                let alpha = (texel[3] * 255.0).round().clamp(0.0, 255.0) as u8;
+                if alpha == 0 { continue; }
+                let rgb = [linear_to_srgb_u8(texel[0] / texel[3]), linear_to_srgb_u8(texel[1] / texel[3]), linear_to_srgb_u8(texel[2] / texel[3])];
                 let e = buf.get_pixel(px as u32, py as u32).0;
-                let ea = e[3] as f32/255.0;
-                let sa = texel[3]; let out_a = sa + ea*(1.0-sa);
-                if out_a < 1e-5 { buf.put_pixel(px as u32, py as u32, Rgba([0,0,0,0])); continue; }
-                buf.put_pixel(px as u32, py as u32, Rgba([
-                    ((texel[0]*sa + e[0] as f32/255.0*ea*(1.0-sa))/out_a*255.0).clamp(0.0,255.0) as u8,
-                    ((texel[1]*sa + e[1] as f32/255.0*ea*(1.0-sa))/out_a*255.0).clamp(0.0,255.0) as u8,
-                    ((texel[2]*sa + e[2] as f32/255.0*ea*(1.0-sa))/out_a*255.0).clamp(0.0,255.0) as u8,
-                    (out_a*255.0).clamp(0.0,255.0) as u8,
-                ]));
+                buf.put_pixel(px as u32, py as u32, Rgba(composite_over_u8(e, rgb, alpha)));
             }
         }
         DynamicImage::ImageRgba8(buf)
     }
 
     pub(super) fn hit_text_layer(&self, pos: egui::Pos2) -> Option<u64> {
-        for layer in self.text_layers.iter().rev() {
+        for layer in self.text_layers.iter().rev().filter(|l| self.text_shown(l)) {
             let anchor = self.image_to_screen(layer.img_x, layer.img_y);
             if layer.screen_rect(anchor, self.zoom).contains(pos) { return Some(layer.id); }
         }
@@ -487,11 +798,66 @@ impl ImageEditor {
 
     pub(super) fn text_transform_handles(&self) -> Option<TransformHandleSet> {
         let id = self.selected_text?;
-        let layer = self.text_layers.iter().find(|l| l.id == id)?;
+        let layer = self.text_layers.iter().find(|l| l.id == id).filter(|l| self.text_shown(l))?;
         let anchor = self.image_to_screen(layer.img_x, layer.img_y);
         Some(TransformHandleSet::with_rotation(layer.screen_rect(anchor, self.zoom), layer.rotation.to_radians()))
     }
 
+    pub(super) fn text_shown(&self, layer: &TextLayer) -> bool { !self.hide_all_text && !layer.canvas_hidden }
+
+    pub(super) fn hidden_text_count(&self) -> usize { self.text_layers.iter().filter(|l| !self.text_shown(l)).count() }
+
+    pub(super) fn set_text_hidden(&mut self, id: u64, hidden: bool) {
+        let Some(layer) = self.text_layers.iter_mut().find(|l| l.id == id) else { return };
+        layer.canvas_hidden = hidden;
+        if hidden && self.selected_text == Some(id) { self.commit_or_discard_active_text(); }
+        self.refresh_dirty_from_hash();
+    }
+
+    pub(super) fn toggle_hide_all_text(&mut self) {
+        self.hide_all_text = !self.hide_all_text;
+        if self.hide_all_text { self.commit_or_discard_active_text(); }
+        self.refresh_dirty_from_hash();
+    }
+
+    pub(super) fn delete_text_layer(&mut self, id: u64) {
+        if self.selected_text == Some(id) { self.selected_text = None; self.editing_text = false; self.text_drag = None; }
+        match self.layers.iter().find(|l| l.linked_text_id == Some(id)).map(|l| l.id) {
+            Some(lid) if self.layers.len() > 1 => { self.active_layer_id = lid; self.delete_active_layer(); }
+            _ => { self.push_undo(); self.bin_text_layer(id); self.composite_dirty = true; self.dirty = true; }
+        }
+    }
+
+    /// Removes a text layer, keeping it in the recently deleted list unless it was empty.
+    pub(super) fn bin_text_layer(&mut self, id: u64) {
+        let Some(idx) = self.text_layers.iter().position(|l| l.id == id) else { return };
+        let layer = self.text_layers.remove(idx);
+        if layer.content.trim().is_empty() { return; }
+        self.deleted_text.retain(|d| d.id != id);
+        self.deleted_text.insert(0, layer);
+        self.deleted_text.truncate(RECENTLY_DELETED_LIMIT);
+    }
+
+    /// Drops recently deleted entries that are back on the canvas, as after undoing their deletion.
+    pub(super) fn prune_deleted_text(&mut self) {
+        let live: std::collections::HashSet<u64> = self.text_layers.iter().map(|l| l.id).collect();
+        self.deleted_text.retain(|d| !live.contains(&d.id));
+    }
+
+    /// Puts a deleted layer back under its old id, so a later undo or delete can't leave two copies of it.
+    pub(super) fn restore_deleted_text(&mut self, idx: usize) {
+        self.prune_deleted_text();
+        if idx >= self.deleted_text.len() { return; }
+        self.push_undo();
+        let layer = self.deleted_text.remove(idx);
+        let id = layer.id;
+        self.next_text_id = self.next_text_id.max(id + 1);
+        let layer = TextLayer { rendered_height: 0.0, cached_lines: Vec::new(), canvas_hidden: false, ..layer };
+        self.text_layers.push(layer);
+        self.ensure_layer_entry_for_text(id);
+        self.composite_dirty = true; self.dirty = true;
+    }
+
     pub(super) fn commit_or_discard_active_text(&mut self) {
         if let Some(id) = self.selected_text {
             let empty = self.text_layers.iter().find(|l| l.id == id).map(|l| l.content.is_empty()).unwrap_or(true);
@@ -506,6 +872,74 @@ impl ImageEditor {
         self.composite_dirty = true;
     }
 
+    pub(super) fn edit_text_layer_at(&mut self, hit: u64, pos: egui::Pos2, select_word: bool) {
+        if self.selected_text != Some(hit) { self.commit_or_discard_active_text(); }
+        self.selected_text = Some(hit); self.editing_text = true; self.text_sel_anchor = None;
+        self.composite_dirty = true;
+        if let Some(layer) = self.text_layers.iter().find(|l| l.id == hit) { self.text_cursor = layer.content.len(); }
+        if let Some(byte) = self.text_byte_at(hit, pos) {
+            self.text_cursor = byte;
+            if select_word {
+                let (lo, hi) = self.text_layers.iter().find(|l| l.id == hit).map(|l| word_range_at(&l.content, byte)).unwrap_or((byte, byte));
+                self.text_sel_anchor = Some(lo); self.text_cursor = hi;
+            }
+        }
+        if let Some(linked_layer) = self.layers.iter().find(|l| l.linked_text_id == Some(hit)) {
+            self.active_layer_id = linked_layer.id;
+        }
+    }
+
+    pub(super) fn text_style(&self) -> TextStyle {
+        self.selected_text.and_then(|id| self.text_layers.iter().find(|l| l.id == id)).map_or_else(|| self.default_text_style(), |l| l.style())
+    }
+
+    pub(super) fn default_text_style(&self) -> TextStyle {
+        TextStyle {
+            font_name: self.text_font_name.clone(), font_size: self.text_font_size, bold: self.text_bold, italic: self.text_italic, underline: self.text_underline,
+            direction: self.text_direction, tab_width: self.text_tab_width, color: self.color,
+        }
+    }
+
+    pub(super) fn set_text_style(&mut self, style: TextStyle) {
+        let sel_id = self.selected_text;
+        match self.text_layers.iter_mut().find(|l| Some(l.id) == sel_id) {
+            Some(layer) => { layer.apply_style(&style); self.composite_dirty = true; self.dirty = true; }
+            None => self.set_default_text_style(style),
+        }
+    }
+
+    pub(super) fn set_default_text_style(&mut self, style: TextStyle) {
+        self.text_font_name = style.font_name; self.text_font_size = style.font_size;
+        self.text_bold = style.bold; self.text_italic = style.italic; self.text_underline = style.underline;
+        self.text_direction = style.direction; self.text_tab_width = style.tab_width;
+        if self.color != style.color { self.color = style.color; self.hex_input = RgbaColor::from_egui(self.color).to_hex(); }
+    }
+
+    pub(super) fn text_byte_at(&self, id: u64, pos: egui::Pos2) -> Option<usize> {
+        let tl = self.text_layers.iter().find(|l| l.id == id)?;
+        let anchor = self.image_to_screen(tl.img_x, tl.img_y);
+        let (sin_a, cos_a) = tl.rotation.to_radians().sin_cos();
+        let p = pos - tl.text_origin(anchor, self.zoom);
+        let local = egui::pos2(p.x * cos_a + p.y * sin_a, -p.x * sin_a + p.y * cos_a);
+        if tl.direction == TextDirection::Vertical {
+            let (step, colw) = (tl.vertical_step() * self.zoom, tl.column_width() * self.zoom);
+            let cols = tl.vertical_columns();
+            let ci = (((tl.screen_rect(anchor, self.zoom).width() - local.x) / colw).floor().max(0.0) as usize).min(cols.len().saturating_sub(1));
+            let (start, col) = cols.get(ci)?;
+            let ri = ((local.y / step).round().max(0.0) as usize).min(col.len());
+            let byte = col.get(ri).map(|&(b, _)| b).or_else(|| col.last().map(|&(b, c)| b + c.len_utf8())).unwrap_or(*start);
+            return Some(snap_grapheme_boundary(&tl.content, byte));
+        }
+        let galley = self.text_galley_cache.get(&id)?;
+        let d = galley.cursor_from_pos(local.to_vec2()).index;
+        let ci = match self.text_caret_maps.get(&id) {
+            Some(map) => map.iter().enumerate().min_by_key(|(_, (md, right))| (md + *right as usize).abs_diff(d)).map(|(i, _)| i).unwrap_or(0),
+            None => layout_char_to_text(&galley.job.text, &tl.content, d),
+        };
+        let byte = tl.content.char_indices().nth(ci).map(|(b, _)| b).unwrap_or(tl.content.len());
+        Some(snap_grapheme_boundary(&tl.content, byte))
+    }
+
     pub(super) fn process_text_input(&mut self, ctx: &egui::Context) {
         if !self.editing_text || self.selected_text.is_none() { return; }
         let id = self.selected_text.unwrap();
@@ -538,54 +972,49 @@ impl ImageEditor {
                         }
                     } else { should_deselect = true; }
                 }
-                egui::Event::Key { key: egui::Key::Backspace, pressed: true, .. } => {
+                egui::Event::Key { key: egui::Key::Backspace, pressed: true, modifiers, .. } => {
                     if let Some(layer) = self.text_layers.iter_mut().find(|l| l.id == id) {
                         if let Some(anchor) = sel {
                             let (lo, hi) = (anchor.min(cursor), anchor.max(cursor));
                             layer.content.drain(lo..hi); self.text_cursor = lo; self.text_sel_anchor = None;
                             text_content_changed = true;
                         } else if cursor > 0 {
-                            let prev = layer.content[..cursor].char_indices().next_back().map(|(i,_)| i).unwrap_or(0);
+                            let prev = if modifiers.command { prev_word_boundary(&layer.content, cursor) } else { prev_grapheme_boundary(&layer.content, cursor) };
                             layer.content.drain(prev..cursor); self.text_cursor = prev;
                             text_content_changed = true;
                         }
                     }
                 }
-                egui::Event::Key { key: egui::Key::Delete, pressed: true, .. } => {
+                egui::Event::Key { key: egui::Key::Delete, pressed: true, modifiers, .. } => {
                     if let Some(layer) = self.text_layers.iter_mut().find(|l| l.id == id) {
                         if let Some(anchor) = sel {
                             let (lo, hi) = (anchor.min(cursor), anchor.max(cursor));
                             layer.content.drain(lo..hi); self.text_cursor = lo; self.text_sel_anchor = None;
                             text_content_changed = true;
                         } else if cursor < layer.content.len() {
-                            let next = layer.content[cursor..].char_indices().nth(1).map(|(i,_)| cursor+i).unwrap_or(layer.content.len());
+                            let next = if modifiers.command { next_word_boundary(&layer.content, cursor) } else { next_grapheme_boundary(&layer.content, cursor) };
                             layer.content.drain(cursor..next); text_content_changed = true;
                         }
                     }
                 }
-                egui::Event::Key { key: egui::Key::ArrowLeft, pressed: true, modifiers, .. } => {
-                    let shift = modifiers.shift;
-                    if let Some(layer) = self.text_layers.iter().find(|l| l.id == id) {
-                        if !shift && sel.is_some() {
-                            self.text_cursor = cursor.min(sel.unwrap()); self.text_sel_anchor = None;
-                        } else {
-                            if shift && self.text_sel_anchor.is_none() { self.text_sel_anchor = Some(cursor); }
-                            if cursor > 0 {
-                                self.text_cursor = layer.content[..cursor].char_indices().next_back().map(|(i,_)| i).unwrap_or(0);
-                            }
-                        }
-                    }
-                }
-                egui::Event::Key { key: egui::Key::ArrowRight, pressed: true, modifiers, .. } => {
+                egui::Event::Key { key: key @ (egui::Key::ArrowLeft | egui::Key::ArrowRight | egui::Key::ArrowUp | egui::Key::ArrowDown), pressed: true, modifiers, .. } => {
                     let shift = modifiers.shift;
                     if let Some(layer) = self.text_layers.iter().find(|l| l.id == id) {
+                        let vertical = layer.direction == TextDirection::Vertical;
+                        if matches!(key, egui::Key::ArrowUp | egui::Key::ArrowDown) && !vertical { continue; }
+                        let rtl = Self::text_layer_uses_bidi(layer) && rtl_at(&layer.content, cursor, layer.direction == TextDirection::Rtl);
+                        let forward = match key { egui::Key::ArrowDown => true, egui::Key::ArrowUp => false, egui::Key::ArrowRight => !rtl, _ => rtl };
                         if !shift && sel.is_some() {
-                            self.text_cursor = cursor.max(sel.unwrap()); self.text_sel_anchor = None;
+                            let anchor = sel.unwrap_or(cursor);
+                            self.text_cursor = if forward { cursor.max(anchor) } else { cursor.min(anchor) }; self.text_sel_anchor = None;
                         } else {
                             if shift && self.text_sel_anchor.is_none() { self.text_sel_anchor = Some(cursor); }
-                            if cursor < layer.content.len() {
-                                self.text_cursor = layer.content[cursor..].char_indices().nth(1).map(|(i,_)| cursor+i).unwrap_or(layer.content.len());
-                            }
+                            self.text_cursor = match (forward, modifiers.command) {
+                                (true, true) => next_word_boundary(&layer.content, cursor),
+                                (true, false) => next_grapheme_boundary(&layer.content, cursor),
+                                (false, true) => prev_word_boundary(&layer.content, cursor),
+                                (false, false) => prev_grapheme_boundary(&layer.content, cursor),
+                            };
                         }
                     }
                 }
@@ -639,11 +1068,7 @@ impl ImageEditor {
             }
         }
         if let Some(layer) = self.text_layers.iter().find(|l| l.id == id) {
-            let clamp = |c: usize| -> usize {
-                let c = c.min(layer.content.len());
-                if layer.content.is_char_boundary(c) { c }
-                else { (0..c).rev().find(|&i| layer.content.is_char_boundary(i)).unwrap_or(0) }
-            };
+            let clamp = |c: usize| -> usize { snap_grapheme_boundary(&layer.content, c) };
             self.text_cursor = clamp(self.text_cursor);
             if let Some(a) = self.text_sel_anchor { self.text_sel_anchor = Some(clamp(a)); }
         }
@@ -652,12 +1077,43 @@ impl ImageEditor {
         let _ = ctrl;
     }
 
+    /// The pixel rectangle `[x0, y0, x1, y1]` the crop will cut, the same numbers the crop readout shows.
+    pub(super) fn crop_rect(&self) -> Option<[u32; 4]> {
+        let img = self.image.as_ref()?;
+        let (s, e) = self.crop_state.start.zip(self.crop_state.end)?;
+        let px = |v: f32, max: u32| (v.round().max(0.0) as u32).min(max);
+        let (x0, x1) = (px(s.0.min(e.0), img.width()), px(s.0.max(e.0), img.width()));
+        let (y0, y1) = (px(s.1.min(e.1), img.height()), px(s.1.max(e.1), img.height()));
+        (x1 > x0 && y1 > y0).then_some([x0, y0, x1, y1])
+    }
+
+    /// Rounds the crop to whole pixels and snaps its size by moving only the dragged edges. Per axis, `Some(true)`
+    /// means the start edge is the one being dragged, `Some(false)` the end edge and `None` leaves that size alone.
+    pub(super) fn snap_crop(&mut self, x: Option<bool>, y: Option<bool>) {
+        let Some((w, h)) = self.image.as_ref().map(|i| (i.width() as f32, i.height() as f32)) else { return };
+        let (Some(s), Some(e)) = (self.crop_state.start, self.crop_state.end) else { return };
+        let (mut s, mut e) = ((s.0.round(), s.1.round()), (e.0.round(), e.1.round()));
+        let snap = self.dimension_snap;
+        match x { Some(true) => s.0 = snap_edge(e.0, s.0, snap, w), Some(false) => e.0 = snap_edge(s.0, e.0, snap, w), None => {} }
+        match y { Some(true) => s.1 = snap_edge(e.1, s.1, snap, h), Some(false) => e.1 = snap_edge(s.1, e.1, snap, h), None => {} }
+        self.crop_state = CropState { start: Some(s), end: Some(e) };
+    }
+
+    /// Sets the crop size from typed values, keeping the top-left corner where it is.
+    pub(super) fn set_crop_size(&mut self, w: u32, h: u32) {
+        let Some([x0, y0, x1, y1]) = self.crop_rect() else { return };
+        let snap = self.dimension_snap;
+        let Some((iw, ih)) = self.image.as_ref().map(|i| (i.width(), i.height())) else { return };
+        let w = if w == x1 - x0 { w } else { snap.snap_from(w, x1 - x0, iw - x0) };
+        let h = if h == y1 - y0 { h } else { snap.snap_from(h, y1 - y0, ih - y0) };
+        self.crop_state = CropState { start: Some((x0 as f32, y0 as f32)), end: Some(((x0 + w) as f32, (y0 + h) as f32)) };
+    }
+
     pub(super) fn apply_crop(&mut self) {
+        let Some([x0, y0, x1, y1]) = self.crop_rect() else { return };
+        let Some((w, h)) = self.image.as_ref().map(|i| i.dimensions()) else { return };
+        if self.refuse_crop_size(x1 - x0, y1 - y0, w, h) { return; }
         let img = match &self.image { Some(i) => i, None => return };
-        let (s, e) = match (self.crop_state.start, self.crop_state.end) { (Some(s), Some(e)) => (s, e), _ => return };
-        let x0 = s.0.min(e.0).max(0.0) as u32; let y0 = s.1.min(e.1).max(0.0) as u32;
-        let x1 = (s.0.max(e.0) as u32).min(img.width()); let y1 = (s.1.max(e.1) as u32).min(img.height());
-        if x1 <= x0 || y1 <= y0 { return; }
         let cropped = img.crop_imm(x0, y0, x1-x0, y1-y0);
         self.resize_w = cropped.width(); self.resize_h = cropped.height();
         self.image = Some(cropped);
@@ -675,77 +1131,239 @@ impl ImageEditor {
             }
         }
         for tl in &mut self.text_layers { tl.img_x -= x0 as f32; tl.img_y -= y0 as f32; }
+        self.transform_shapes(|x, y| (x - x0 as f32, y - y0 as f32), |a| a);
         self.texture_dirty = true; self.composite_dirty = true; self.dirty = true;
         self.crop_state = CropState::default(); self.fit_on_next_frame = true;
     }
 
-    fn run_filter_threaded<F>(&mut self, f: F)
+    pub(super) fn average_color_under_text(&self, tid: u64) -> Option<egui::Color32> {
+        let tl = self.text_layers.iter().find(|t| t.id == tid)?;
+        let img = self.composite_layers(false)?.to_rgba8();
+        let (w, h) = (tl.box_width.unwrap_or_else(|| tl.auto_width(1.0)), tl.auto_height(1.0));
+        let (x0, y0) = (tl.img_x.max(0.0) as u32, tl.img_y.max(0.0) as u32);
+        let (x1, y1) = (((tl.img_x + w).ceil().max(0.0) as u32).min(img.width()), ((tl.img_y + h).ceil().max(0.0) as u32).min(img.height()));
+        let (mut sum, mut n) = ([0u64; 3], 0u64);
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let p = img.get_pixel(x, y).0;
+                for c in 0..3 { sum[c] += p[c] as u64; }
+                n += 1;
+            }
+        }
+        (n > 0).then(|| egui::Color32::from_rgb((sum[0] / n) as u8, (sum[1] / n) as u8, (sum[2] / n) as u8))
+    }
+
+    pub(super) fn remember_filter(&mut self, panel: FilterPanel) {
+        let filter = match panel {
+            FilterPanel::BrightnessContrast => RepeatableFilter::BrightnessContrast { brightness: self.brightness, contrast: self.contrast },
+            FilterPanel::HueSaturation => RepeatableFilter::HueSaturation { hue: self.hue, saturation: self.saturation },
+            FilterPanel::Blur => RepeatableFilter::Blur { radius: self.blur_radius },
+            FilterPanel::Sharpen => RepeatableFilter::Sharpen { amount: self.sharpen_amount },
+            FilterPanel::Resize => RepeatableFilter::Resize { w: self.resize_w, h: self.resize_h, stretch: self.resize_stretch },
+            _ => return,
+        };
+        self.set_last_filter(filter);
+    }
+
+    pub(super) fn set_last_filter(&mut self, filter: RepeatableFilter) {
+        if self.settings.last_filter == Some(filter) { return; }
+        self.settings.last_filter = Some(filter);
+        self.settings_changed = true;
+    }
+
+    pub(super) fn repeat_last_filter(&mut self, adjust: bool) {
+        let Some(filter) = self.settings.last_filter else { return };
+        if self.is_processing() || self.image.is_none() { return; }
+        match filter {
+            RepeatableFilter::BrightnessContrast { brightness, contrast } => { self.brightness = brightness; self.contrast = contrast; }
+            RepeatableFilter::HueSaturation { hue, saturation } => { self.hue = hue; self.saturation = saturation; }
+            RepeatableFilter::Blur { radius } => self.blur_radius = radius,
+            RepeatableFilter::Sharpen { amount } => self.sharpen_amount = amount,
+            RepeatableFilter::Resize { w, h, stretch } => { self.resize_w = w; self.resize_h = h; self.resize_stretch = stretch; }
+            RepeatableFilter::Grayscale | RepeatableFilter::Invert | RepeatableFilter::Sepia => {}
+        }
+        if let (true, Some(panel)) = (adjust, filter.panel()) { self.filter_panel = panel; return; }
+        if let RepeatableFilter::Resize { w, h, .. } = filter && self.refuse_canvas_size(w, h) { return; }
+        self.push_undo();
+        match filter {
+            RepeatableFilter::BrightnessContrast { .. } => { self.apply_brightness_contrast(); self.brightness = 0.0; self.contrast = 0.0; }
+            RepeatableFilter::HueSaturation { .. } => { self.apply_hue_saturation(); self.hue = 0.0; self.saturation = 0.0; }
+            RepeatableFilter::Blur { .. } => self.apply_blur(),
+            RepeatableFilter::Sharpen { .. } => self.apply_sharpen(),
+            RepeatableFilter::Resize { .. } => self.apply_resize(),
+            RepeatableFilter::Grayscale => self.apply_grayscale(),
+            RepeatableFilter::Invert => self.apply_invert(),
+            RepeatableFilter::Sepia => self.apply_sepia(),
+        }
+    }
+
+    fn run_filter_threaded<F>(&mut self, name: &str, f: F)
     where F: FnOnce(DynamicImage) -> DynamicImage + Send + 'static
     {
         let img = match self.active_filterable_image() { Some(i) => i, None => return };
         self.filter_target_layer_id = self.active_layer_id;
-        let result = Arc::clone(&self.pending_filter_result);
-        let progress = Arc::clone(&self.filter_progress);
-        self.is_processing = true; *progress.lock().unwrap() = 0.0;
+        let job = self.start_filter_job(name, false);
         thread::spawn(move || {
-            *progress.lock().unwrap() = 0.5;
-            *result.lock().unwrap() = Some(f(img));
-            *progress.lock().unwrap() = 1.0;
+            job.progress(0.5);
+            job.finish(f(img));
         });
     }
 
     pub(super) fn apply_brightness_contrast(&mut self) {
         let img = match self.active_filterable_image() { Some(i) => i, None => return };
         self.filter_target_layer_id = self.active_layer_id;
-        let (b, c) = (self.brightness, 1.0 + self.contrast / 100.0);
-        let progress = Arc::clone(&self.filter_progress);
-        let result = Arc::clone(&self.pending_filter_result);
-        self.is_processing = true; *progress.lock().unwrap() = 0.0;
+        let (b, c) = (self.brightness, self.contrast);
+        let job = self.start_filter_job("Brightness / Contrast", false);
         thread::spawn(move || {
-            let mut buf = img.to_rgba8();
-            let total = (buf.width() * buf.height()) as usize;
-            let mut processed = 0i32;
-            for pixel in buf.pixels_mut() {
-                for i in 0..3 { pixel[i] = ((pixel[i] as f32 - 128.0) * c + 128.0 + b).clamp(0.0, 255.0) as u8; }
-                processed += 1;
-                if processed % 5000 == 0 { *progress.lock().unwrap() = processed as f32 / total as f32; }
-            }
-            *result.lock().unwrap() = Some(DynamicImage::ImageRgba8(buf));
-            *progress.lock().unwrap() = 1.0;
+            let out = filters::brightness_contrast(&img, b, c, &|p| job.progress(p));
+            job.finish(out);
         });
     }
 
     pub(super) fn apply_hue_saturation(&mut self) {
         let img = match self.active_filterable_image() { Some(i) => i, None => return };
         self.filter_target_layer_id = self.active_layer_id;
-        let (sat_factor, hue_shift) = (1.0 + self.saturation / 100.0, self.hue);
-        let progress = Arc::clone(&self.filter_progress);
-        let result = Arc::clone(&self.pending_filter_result);
-        self.is_processing = true; *progress.lock().unwrap() = 0.0;
+        let (hue, saturation) = (self.hue, self.saturation);
+        let job = self.start_filter_job("Hue / Saturation", false);
         thread::spawn(move || {
-            let mut buf = img.to_rgba8();
-            for y in 0..buf.height() {
-                for x in 0..buf.width() {
-                    let p = buf.get_pixel(x, y).0;
-                    let (h, s, v) = rgb_to_hsv(p[0], p[1], p[2]);
-                    let (nr, ng, nb) = hsv_to_rgb((h + hue_shift).rem_euclid(360.0), (s * sat_factor).clamp(0.0, 1.0), v);
-                    buf.put_pixel(x, y, Rgba([nr, ng, nb, p[3]]));
-                }
-                if y % 10 == 0 { *progress.lock().unwrap() = y as f32 / buf.height() as f32; }
-            }
-            *result.lock().unwrap() = Some(DynamicImage::ImageRgba8(buf));
-            *progress.lock().unwrap() = 1.0;
+            let out = filters::hue_saturation(&img, hue, saturation, &|p| job.progress(p));
+            job.finish(out);
+        });
+    }
+
+    fn run_gpu_filter_threaded<F>(&mut self, name: &str, img: DynamicImage, gpu_job: GpuJob, cpu: F)
+    where F: FnOnce(DynamicImage) -> DynamicImage + Send + 'static
+    {
+        let use_gpu = self.settings.use_gpu;
+        let job = self.start_filter_job(name, true);
+        thread::spawn(move || {
+            let gpu = if use_gpu {
+                let report = |s: GpuStage, done: f32| { job.stage(Some(s)); job.progress(done); };
+                ie_gpu::run(&img.to_rgba8(), gpu_job, &report, job.task.cancel_flag()).map(DynamicImage::ImageRgba8)
+            } else { None };
+            job.stage(None);
+            if job.cancelled() { return; }
+            let out = gpu.unwrap_or_else(|| { job.progress(0.5); cpu(img) });
+            if !job.cancelled() { job.finish(out); }
         });
     }
 
     pub(super) fn apply_blur(&mut self) {
+        let Some(img) = self.active_filterable_image() else { return };
+        self.filter_target_layer_id = self.active_layer_id;
         let radius = self.blur_radius;
-        self.run_filter_threaded(move |img| img.blur(radius));
+        self.run_gpu_filter_threaded("Blur", img, GpuJob::Blur(radius), move |img| filters::blur(&img, radius));
     }
 
     pub(super) fn apply_sharpen(&mut self) {
         let amount = self.sharpen_amount;
-        self.run_filter_threaded(move |img| img.unsharpen(amount, 0));
+        self.run_filter_threaded("Sharpen", move |img| filters::sharpen(&img, amount));
+    }
+
+    pub(super) fn apply_equalize(&mut self) {
+        let img = match self.active_filterable_image() { Some(i) => i, None => return };
+        self.filter_target_layer_id = self.active_layer_id;
+        let (mode, tiles, clip) = (self.equalize_mode, self.clahe_tile.max(1), self.clahe_clip.max(1.0));
+        let job = self.start_filter_job("Equalize", false);
+        thread::spawn(move || {
+            let mut buf = img.to_rgba8();
+            equalize(&mut buf, mode, tiles, clip, &|p| job.progress(p));
+            job.finish(DynamicImage::ImageRgba8(buf));
+        });
+    }
+
+    pub(super) fn open_threshold_panel(&mut self) {
+        self.threshold_hist = self.active_filterable_image().map(|img| filters::luma_histogram(&img));
+        self.filter_panel = FilterPanel::Threshold;
+    }
+
+    pub(super) fn apply_threshold(&mut self) {
+        let img = match self.active_filterable_image() { Some(i) => i, None => return };
+        self.filter_target_layer_id = self.active_layer_id;
+        let (level, soft) = (self.threshold_level, self.threshold_soft);
+        let job = self.start_filter_job("Threshold", false);
+        thread::spawn(move || {
+            let out = filters::threshold(&img, level, soft, &|p| job.progress(p));
+            job.finish(out);
+        });
+    }
+
+    pub(super) fn apply_channel_mixer(&mut self) {
+        let img = match self.active_filterable_image() { Some(i) => i, None => return };
+        self.filter_target_layer_id = self.active_layer_id;
+        let m = self.mixer;
+        let job = self.start_filter_job("Channel Mixer", false);
+        thread::spawn(move || {
+            let mut buf = img.to_rgba8();
+            let h = buf.height();
+            for (y, row) in buf.rows_mut().enumerate() {
+                for px in row {
+                    let (r, g, b) = (px[0] as f32, px[1] as f32, px[2] as f32);
+                    for (c, w) in m.iter().enumerate() { px[c] = (w[0] * r + w[1] * g + w[2] * b + w[3]).round().clamp(0.0, 255.0) as u8; }
+                }
+                if y % 16 == 0 { job.progress(y as f32 / h as f32); }
+            }
+            job.finish(DynamicImage::ImageRgba8(buf));
+        });
+    }
+
+    pub(super) fn stroke_region(&self) -> Option<(f32, f32, f32, f32)> {
+        let img = self.image.as_ref()?;
+        if let Some([x0, y0, x1, y1]) = self.selection.as_ref().filter(|s| s.fits(img.width(), img.height())).and_then(|s| s.bounds()) {
+            return Some((x0 as f32, y0 as f32, x1 as f32, y1 as f32));
+        }
+        if let (Some(s), Some(e)) = (self.crop_state.start, self.crop_state.end) {
+            let (x0, y0, x1, y1) = (s.0.min(e.0).max(0.0), s.1.min(e.1).max(0.0), s.0.max(e.0).min(img.width() as f32), s.1.max(e.1).min(img.height() as f32));
+            if x1 - x0 >= 1.0 && y1 - y0 >= 1.0 { return Some((x0, y0, x1, y1)); }
+        }
+        Some((0.0, 0.0, img.width() as f32, img.height() as f32))
+    }
+
+    pub(super) fn apply_stroke(&mut self) {
+        let Some((x0, y0, x1, y1)) = self.stroke_region() else { return };
+        let kind = self.layers.iter().find(|l| l.id == self.active_layer_id).map(|l| l.kind).unwrap_or(LayerKind::Background);
+        if self.stroke_new_layer || !matches!(kind, LayerKind::Background | LayerKind::Raster) { self.new_raster_layer(); } else { self.push_undo(); }
+        let id = self.active_layer_id;
+        let kind = self.layers.iter().find(|l| l.id == id).map(|l| l.kind).unwrap_or(LayerKind::Background);
+        let src = match kind { LayerKind::Background => self.image.as_ref(), _ => self.layer_images.get(&id) };
+        let Some(mut buf) = src.map(|i| i.to_rgba8()) else { return };
+        let (cx, cy, hx, hy) = ((x0 + x1) / 2.0, (y0 + y1) / 2.0, (x1 - x0) / 2.0, (y1 - y0) / 2.0);
+        let radius = self.stroke_corner_radius.clamp(0.0, hx.min(hy));
+        let width = self.stroke_width.max(0.5);
+        let (lo, hi) = match self.stroke_position {
+            StrokePosition::Inside => (-width.min(hx.min(hy)), 0.0),
+            StrokePosition::Center => (-(width / 2.0).min(hx.min(hy)), width / 2.0),
+            StrokePosition::Outside => (0.0, width),
+        };
+        let c = self.stroke_color.to_srgba_unmultiplied();
+        let (bw, bh) = (buf.width() as f32, buf.height() as f32);
+        let (bx0, by0) = ((x0 - hi - 1.0).max(0.0) as u32, (y0 - hi - 1.0).max(0.0) as u32);
+        let (bx1, by1) = ((x1 + hi + 1.0).min(bw) as u32, (y1 + hi + 1.0).min(bh) as u32);
+        for y in by0..by1 {
+            for x in bx0..bx1 {
+                let (qx, qy) = ((x as f32 + 0.5 - cx).abs() - (hx - radius), (y as f32 + 0.5 - cy).abs() - (hy - radius));
+                let d = (qx.max(0.0).powi(2) + qy.max(0.0).powi(2)).sqrt() + qx.max(qy).min(0.0) - radius;
+                let cov = (d - lo + 0.5).clamp(0.0, 1.0) * (hi - d + 0.5).clamp(0.0, 1.0);
+                if cov <= 0.0 { continue; }
+                let sa = c[3] as f32 / 255.0 * cov;
+                let p = buf.get_pixel_mut(x, y);
+                let da = p[3] as f32 / 255.0;
+                let oa = sa + da * (1.0 - sa);
+                if oa <= 0.0 { continue; }
+                for i in 0..3 { p[i] = ((c[i] as f32 * sa + p[i] as f32 * da * (1.0 - sa)) / oa).round().clamp(0.0, 255.0) as u8; }
+                p[3] = (oa * 255.0).round() as u8;
+            }
+        }
+        let res = DynamicImage::ImageRgba8(buf);
+        match kind {
+            LayerKind::Background => self.image = Some(res),
+            _ => {
+                self.layer_images.insert(id, res);
+                self.raster_layer_texture_dirty.insert(id);
+                self.raster_layer_dirty_rects.remove(&id);
+            }
+        }
+        self.composite_dirty = true; self.texture_dirty = true; self.dirty = true;
     }
 
     fn apply_pixel_op_to_active<F: Fn(&mut [u8])>(&mut self, op: F) {
@@ -754,9 +1372,7 @@ impl ImageEditor {
         if kind == LayerKind::Image {
             if let Some(iid) = self.image_layer_for_active() {
                 if let Some(ild) = self.image_layer_data.get_mut(&iid) {
-                    let mut buf = ild.image.to_rgba8();
-                    for chunk in buf.as_flat_samples_mut().as_mut_slice().chunks_exact_mut(4) { op(chunk); }
-                    ild.image = DynamicImage::ImageRgba8(buf);
+                    ild.image = filters::map_pixels(&ild.image, &op);
                     self.image_layer_texture_dirty.insert(iid);
                 }
             }
@@ -767,9 +1383,7 @@ impl ImageEditor {
                 _ => return,
             };
             if let Some(src) = src {
-                let mut buf = src.to_rgba8();
-                for chunk in buf.as_flat_samples_mut().as_mut_slice().chunks_exact_mut(4) { op(chunk); }
-                let res = DynamicImage::ImageRgba8(buf);
+                let res = self.within_selection(Some(src), filters::map_pixels(src, &op));
                 match kind {
                     LayerKind::Background => self.image = Some(res),
                     LayerKind::Raster => {
@@ -790,7 +1404,7 @@ impl ImageEditor {
         if kind == LayerKind::Image {
             if let Some(iid) = self.image_layer_for_active() {
                 if let Some(ild) = self.image_layer_data.get_mut(&iid) {
-                    ild.image = DynamicImage::ImageRgba8(ild.image.grayscale().to_rgba8());
+                    ild.image = filters::grayscale(&ild.image);
                     self.image_layer_texture_dirty.insert(iid);
                 }
             }
@@ -801,7 +1415,7 @@ impl ImageEditor {
                 _ => return,
             };
             if let Some(src) = src {
-                let g = DynamicImage::ImageRgba8(src.grayscale().to_rgba8());
+                let g = self.within_selection(Some(src), filters::grayscale(src));
                 match kind {
                     LayerKind::Background => self.image = Some(g),
                     LayerKind::Raster => {
@@ -817,18 +1431,11 @@ impl ImageEditor {
     }
 
     pub(super) fn apply_invert(&mut self) {
-        self.apply_pixel_op_to_active(|chunk| {
-            chunk[0] = 255 - chunk[0]; chunk[1] = 255 - chunk[1]; chunk[2] = 255 - chunk[2];
-        });
+        self.apply_pixel_op_to_active(filters::invert_pixel);
     }
 
     pub(super) fn apply_sepia(&mut self) {
-        self.apply_pixel_op_to_active(|chunk| {
-            let (rf, gf, bf) = (chunk[0] as f32, chunk[1] as f32, chunk[2] as f32);
-            chunk[0] = (rf*0.393 + gf*0.769 + bf*0.189).min(255.0) as u8;
-            chunk[1] = (rf*0.349 + gf*0.686 + bf*0.168).min(255.0) as u8;
-            chunk[2] = (rf*0.272 + gf*0.534 + bf*0.131).min(255.0) as u8;
-        });
+        self.apply_pixel_op_to_active(filters::sepia_pixel);
     }
 
     fn transform_text_rotate_cw(&mut self, _old_w: u32, old_h: u32) {
@@ -891,7 +1498,7 @@ impl ImageEditor {
         let active_id = self.active_layer_id;
         let (kind, locked) = self.layers.iter().find(|l| l.id == active_id)
             .map(|l| (l.kind, l.locked)).unwrap_or((LayerKind::Background, false));
-        if locked || matches!(kind, LayerKind::Text) { return; }
+        if locked || matches!(kind, LayerKind::Text | LayerKind::Shape) { return; }
         if kind == LayerKind::Image { self.apply_retouch_stroke_on_image_layer(); return; }
 
         let swapped_bg = if kind == LayerKind::Raster {
@@ -1055,7 +1662,7 @@ impl ImageEditor {
         }
         self.retouch_smudge_sample = smudge;
         self.dirty = true;
-        if dr_x1 > dr_x0 && dr_y1 > dr_y0 { self.expand_dirty_rect(dr_x0, dr_y0, dr_x1, dr_y1); }
+        if dr_x1 > dr_x0 && dr_y1 > dr_y0 { self.clip_stroke_to_selection(kind, active_id, [dr_x0, dr_y0, dr_x1, dr_y1]); self.expand_dirty_rect(dr_x0, dr_y0, dr_x1, dr_y1); }
         self.texture_dirty = true;
         if let Some(old_bg) = swapped_bg { self.restore_layer_swap(active_id, old_bg); } else { self.promote_dirty_to_composite(); }
     }
@@ -1074,6 +1681,7 @@ impl ImageEditor {
         let flow = if is_eraser { 1.0 } else { self.brush.flow };
         let softness = if is_eraser { 0.0 } else { self.brush.softness };
         let shape = if is_eraser { BrushShape::Circle } else { self.brush.shape };
+        let blend = if is_eraser { BlendMode::Normal } else { self.stroke_blend };
         let step_dist = (radius * (if is_eraser { 0.25 } else { self.brush.step })).max(0.5);
         let (flip_h, flip_v, display_w, display_h, orig_w, orig_h) =
             (ild.flip_h, ild.flip_v, ild.display_w, ild.display_h, ild.orig_w(), ild.orig_h());
@@ -1120,8 +1728,8 @@ impl ImageEditor {
                     unsafe {
                         let [er,eg,eb,ea]=buf.unsafe_get_pixel(px,py).0;
                         let new_pixel=if is_eraser{Rgba([er,eg,eb,ea.saturating_sub(alpha)])}else{
-                            let fa=alpha as u16; let bf=(base_a as u16*fa)/255; let ba=255-bf;
-                            Rgba([((r as u16*bf+er as u16*ba)/255) as u8,((g as u16*bf+eg as u16*ba)/255) as u8,((b_ch as u16*bf+eb as u16*ba)/255) as u8,((bf+ea as u16*ba/255).min(255)) as u8])
+                            let bf=((base_a as u16*alpha as u16+127)/255) as u8;
+                            Rgba(stamp_u8([er,eg,eb,ea],[r,g,b_ch],bf,blend))
                         };
                         buf.unsafe_put_pixel(px,py,new_pixel);
                     }
@@ -1327,7 +1935,8 @@ impl ImageEditor {
         let x0 = lx0.min(lx1).max(0.0) as u32; let y0 = ly0.min(ly1).max(0.0) as u32;
         let x1 = (lx0.max(lx1).ceil() as u32).min(ild.orig_w());
         let y1 = (ly0.max(ly1).ceil() as u32).min(ild.orig_h());
-        if x1 <= x0 || y1 <= y0 { return; }
+        let (orig_w, orig_h) = (ild.orig_w(), ild.orig_h());
+        if x1 <= x0 || y1 <= y0 || self.refuse_crop_size(x1 - x0, y1 - y0, orig_w, orig_h) { return; }
         self.push_undo();
         let ild = self.image_layer_data.get_mut(&iid).unwrap();
         let (scale_x, scale_y) = (ild.display_w / ild.orig_w() as f32, ild.display_h / ild.orig_h() as f32);
@@ -1356,28 +1965,34 @@ impl ImageEditor {
         }
     }
 
+    /// Flips and rotations move text and shape layers along with the pixels, all under one undo entry.
     pub(super) fn apply_flip_h(&mut self) {
+        if self.image.is_none() { return; }
+        self.push_undo();
         if let Some(iid) = self.image_layer_for_active() {
-            self.push_undo();
             if let Some(ild) = self.image_layer_data.get_mut(&iid) { ild.flip_h = !ild.flip_h; }
             self.image_layer_texture_dirty.insert(iid);
             self.composite_dirty = true; self.dirty = true;
             return;
         }
-        let (old_w, flipped) = match &self.image { Some(img) => (img.width(), img.fliph()), None => return };
+        let Some((old_w, flipped)) = self.image.as_ref().map(|img| (img.width(), img.fliph())) else { return };
         self.transform_text_flip_h(old_w); self.image = Some(flipped);
+        self.transform_shapes(|x, y| (old_w as f32 - x, y), |a| (180.0 - a).rem_euclid(360.0));
         self.texture_dirty = true; self.composite_dirty = true; self.dirty = true;
     }
 
     pub(super) fn apply_flip_v(&mut self) {
-        let (old_h, flipped) = match &self.image { Some(img) => (img.height(), img.flipv()), None => return };
+        let Some((old_h, flipped)) = self.image.as_ref().map(|img| (img.height(), img.flipv())) else { return };
+        self.push_undo();
         self.transform_text_flip_v(old_h); self.image = Some(flipped);
+        self.transform_shapes(|x, y| (x, old_h as f32 - y), |a| (-a).rem_euclid(360.0));
         self.texture_dirty = true; self.composite_dirty = true; self.dirty = true;
     }
 
     pub(super) fn apply_rotate_cw(&mut self) {
+        if self.image.is_none() { return; }
+        self.push_undo();
         if let Some(iid) = self.image_layer_for_active() {
-            self.push_undo();
             if let Some(ild) = self.image_layer_data.get_mut(&iid) {
                 let rotated = ild.image.rotate90();
                 let old_dw = ild.display_w;
@@ -1391,13 +2006,15 @@ impl ImageEditor {
         }
         let (old_w, old_h, rotated) = match &self.image { Some(img) => (img.width(), img.height(), img.rotate90()), None => return };
         self.transform_text_rotate_cw(old_w, old_h); self.image = Some(rotated);
+        self.transform_shapes(|x, y| (old_h as f32 - y, x), |a| (a + 90.0).rem_euclid(360.0));
         self.resize_w = self.image.as_ref().unwrap().width(); self.resize_h = self.image.as_ref().unwrap().height();
         self.texture_dirty = true; self.composite_dirty = true; self.dirty = true; self.fit_on_next_frame = true;
     }
 
     pub(super) fn apply_rotate_ccw(&mut self) {
+        if self.image.is_none() { return; }
+        self.push_undo();
         if let Some(iid) = self.image_layer_for_active() {
-            self.push_undo();
             if let Some(ild) = self.image_layer_data.get_mut(&iid) {
                 let rotated = ild.image.rotate270();
                 let old_dw = ild.display_w;
@@ -1411,46 +2028,812 @@ impl ImageEditor {
         }
         let (old_w, old_h, rotated) = match &self.image { Some(img) => (img.width(), img.height(), img.rotate270()), None => return };
         self.transform_text_rotate_ccw(old_w, old_h); self.image = Some(rotated);
+        self.transform_shapes(|x, y| (y, old_w as f32 - x), |a| (a - 90.0).rem_euclid(360.0));
         self.resize_w = self.image.as_ref().unwrap().width(); self.resize_h = self.image.as_ref().unwrap().height();
         self.texture_dirty = true; self.composite_dirty = true; self.dirty = true; self.fit_on_next_frame = true;
     }
 
     pub(super) fn apply_resize(&mut self) {
         let img = match self.image.clone() { Some(i) => i, None => return };
-        if self.resize_w == 0 || self.resize_h == 0 { return; }
+        if self.refuse_canvas_size(self.resize_w, self.resize_h) { return; }
         let (w, h, stretch) = (self.resize_w, self.resize_h, self.resize_stretch);
-        let result = Arc::clone(&self.pending_filter_result);
-        let progress = Arc::clone(&self.filter_progress);
         self.filter_target_layer_id = 0;
-        self.is_processing = true;
+        if stretch { return self.run_gpu_filter_threaded("Resize", img, GpuJob::Resize(w, h), move |img| filters::resize(&img, w, h, true)); }
+        let job = self.start_filter_job("Resize Canvas", false);
         thread::spawn(move || {
-            *progress.lock().unwrap() = 0.5;
-            let final_img = if stretch {
-                img.resize_exact(w, h, image::imageops::FilterType::Lanczos3)
-            } else {
-                let mut new_buf: ImageBuffer<Rgba<u8>, Vec<u8>> = ImageBuffer::from_pixel(w, h, Rgba([255,255,255,255]));
-                image::imageops::overlay(&mut new_buf, &img, 0, 0);
-                DynamicImage::ImageRgba8(new_buf)
-            };
-            *result.lock().unwrap() = Some(final_img);
-            *progress.lock().unwrap() = 1.0;
+            job.progress(0.5);
+            job.finish(filters::resize(&img, w, h, false));
         });
     }
 
-    pub(super) fn export_image_to_file(&mut self) -> Result<PathBuf, String> {
-        let composite = self.composite_all_layers().ok_or("No image to export")?;
-        let default_name = self.file_path.as_ref().and_then(|p| p.file_stem()).and_then(|s| s.to_str()).unwrap_or("export");
+    fn svg_text(&self, tl: &TextLayer, opacity: f32) -> SvgText {
+        let font = Self::text_layer_font(tl);
+        let vertical = tl.direction == TextDirection::Vertical;
+        let lines: Vec<String> = if vertical { tl.vertical_columns().into_iter().map(|(_, c)| c.into_iter().map(|(_, ch)| ch).collect()).collect() }
+            else if Self::text_layer_uses_bidi(tl) { Self::text_layer_line_ranges(tl).into_iter().map(|r| tl.content[r].to_string()).collect() }
+            else { Self::text_layer_lines(tl) };
+        let line_h = if vertical { tl.column_width() } else if tl.rendered_height > 0.0 { tl.rendered_height / lines.len().max(1) as f32 } else { tl.font_size * 1.35 };
+        let height = if vertical { tl.box_height.unwrap_or_else(|| tl.auto_height(1.0)) } else if tl.rendered_height > 0.0 { tl.rendered_height } else { lines.len().max(1) as f32 * line_h };
+        let em = font.units_per_em().unwrap_or(1000.0) / font.height_unscaled();
+        SvgText {
+            lines, x: tl.img_x, y: tl.img_y, width: tl.box_width.unwrap_or_else(|| tl.auto_width(1.0)), height,
+            line_height: line_h, ascent: font.as_scaled(PxScale::from(line_h)).ascent(), font_size: line_h * em,
+            font_family: match tl.effective_font() { "GoogleSans" => "Google Sans".into(), "OpenSans" => "Open Sans".into(), n => n.to_string() }, bold: tl.bold, italic: tl.italic, underline: tl.underline,
+            color: tl.color.to_srgba_unmultiplied(), opacity, rotation: tl.rotation,
+            rtl: tl.direction == TextDirection::Rtl, vertical,
+        }
+    }
+
+    /// The layer stack as SVG, bottom to top: each run of pixel layers between text layers flattened to one image,
+    /// each text layer as text. A viewport export has no text to keep, so it is the flattened crop.
+    pub(super) fn svg_export(&self, viewport: Option<ViewportExport>) -> EditorResult<SvgDocument> {
+        if viewport.is_some() {
+            let composite = self.export_composite(viewport)?;
+            return Ok(SvgDocument { width: composite.width(), height: composite.height(), layers: vec![SvgLayer::Image(composite)] });
+        }
+        let (width, height) = self.image.as_ref().map(|i| i.dimensions()).ok_or(EditorError::Empty("no image is loaded"))?;
+        let mut layers = Vec::new();
+        for run in self.layers.split_inclusive(|l| l.kind == LayerKind::Text) {
+            let (text, pixels) = match run.split_last() { Some((l, rest)) if l.kind == LayerKind::Text => (Some(l), rest), _ => (None, run) };
+            if pixels.iter().any(|l| l.visible) && let Some(img) = self.composite_stack(pixels, false) {
+                layers.push(SvgLayer::Image(DynamicImage::ImageRgba8(img)));
+            }
+            let Some(layer) = text.filter(|l| l.visible) else { continue };
+            if let Some(tl) = layer.linked_text_id.and_then(|tid| self.text_layers.iter().find(|t| t.id == tid)).filter(|t| !t.content.is_empty()) {
+                layers.push(SvgLayer::Text(self.svg_text(tl, layer.opacity)));
+            }
+        }
+        for tl in self.text_layers.iter().filter(|t| self.unlinked_text(t.id) && !t.content.is_empty()) { layers.push(SvgLayer::Text(self.svg_text(tl, 1.0))); }
+        Ok(SvgDocument { width, height, layers })
+    }
+
+    /// Every layer flattened the way a raster export sees it.
+    fn export_composite(&self, viewport: Option<ViewportExport>) -> EditorResult<DynamicImage> {
+        let composite = self.composite_layers(true).ok_or(EditorError::Empty("no image is loaded"))?;
+        Ok(match viewport { Some(v) => crop_to_viewport(&composite, v), None => composite })
+    }
+
+    fn fit_export_max_dim(&self, composite: DynamicImage) -> DynamicImage {
+        match self.export_max_dim {
+            Some(m) => {
+                let (w, h) = export_size(composite.width(), composite.height(), m, self.dimension_snap);
+                if (w, h) == composite.dimensions() { composite } else { composite.resize_exact(w, h, image::imageops::FilterType::Lanczos3) }
+            }
+            None => composite,
+        }
+    }
+
+    pub(super) fn export_image_to_file(&mut self) -> EditorResult<PathBuf> {
+        let viewport = self.export_viewport;
+        if self.image.is_none() { return Err(EditorError::Empty("no image is loaded")); }
+        let stem = self.file_path.as_ref().and_then(|p| p.file_stem()).and_then(|s| s.to_str()).unwrap_or("export");
+        let default_name = match viewport { Some(v) => { let (w, h) = v.output_size(); format!("{}_viewport_{}x{}", stem, w, h) } None => stem.to_string() };
         let path = match rfd::FileDialog::new()
             .set_file_name(&format!("{}.{}", default_name, self.export_format.extension()))
             .add_filter(self.export_format.as_str(), &[self.export_format.extension()])
             .save_file()
-        { Some(p) => p, None => return Err("Export cancelled".to_string()) };
+        { Some(p) => p, None => return Err(EditorError::Cancelled) };
+        self.export_viewport = None;
+        if self.export_format == ExportFormat::Svg {
+            export_svg(&self.svg_export(viewport)?, &path)?;
+            self.filter_panel = FilterPanel::None;
+            return Ok(path);
+        }
+        let composite = self.fit_export_max_dim(self.export_composite(viewport)?);
         export_image(&composite, &path, self.export_format, self.export_jpeg_quality, 6, 100.0, self.export_auto_scale_ico, self.export_avif_quality, self.export_avif_speed)?;
         self.filter_panel = FilterPanel::None;
         Ok(path)
     }
 
-    pub(super) fn render_brush_preview_to_pixels(&self, w: u32, h: u32) -> Vec<egui::Color32> {
+    pub(super) fn current_export_preset(&self, name: &str) -> ExportPreset {
+        ExportPreset {
+            name: name.to_string(), format: self.export_format.as_str().to_string(), jpeg_quality: self.export_jpeg_quality,
+            avif_quality: self.export_avif_quality, avif_speed: self.export_avif_speed, max_dim: self.export_max_dim,
+            preserve_metadata: self.export_preserve_metadata, auto_scale_ico: self.export_auto_scale_ico,
+        }
+    }
+
+    pub(super) fn matching_export_preset(&self) -> Option<usize> {
+        self.export_presets.presets.iter().position(|p| *p == self.current_export_preset(&p.name))
+    }
+
+    pub(super) fn apply_export_preset(&mut self, idx: usize) {
+        let Some(p) = self.export_presets.presets.get(idx).cloned() else { return };
+        self.export_format = ExportFormat::from_name(&p.format).unwrap_or(ExportFormat::Png);
+        self.export_jpeg_quality = p.jpeg_quality.clamp(1, 100);
+        self.export_avif_quality = p.avif_quality.clamp(1, 100);
+        self.export_avif_speed = p.avif_speed.min(10);
+        self.export_max_dim = p.max_dim.map(|m| m.max(1));
+        self.export_preserve_metadata = p.preserve_metadata;
+        self.export_auto_scale_ico = p.auto_scale_ico;
+        self.export_preset_name = p.name;
+        self.export_preset_chosen = true;
+    }
+
+    pub(super) fn source_extension(&self) -> Option<String> {
+        self.file_path.as_ref().and_then(|p| p.extension()).map(|e| e.to_string_lossy().to_lowercase())
+    }
+
+    pub(super) fn remembered_export(&self) -> Option<(String, &RememberedExport)> {
+        let ext = self.source_extension()?;
+        let remembered = self.export_memory.by_extension.get(&ext)?;
+        Some((ext, remembered))
+    }
+
+    /// Pre-selects what was last exported from this file type, unless a preset was picked this session.
+    pub(super) fn apply_remembered_export(&mut self) {
+        if self.export_preset_chosen { return; }
+        let Some((_, r)) = self.remembered_export() else { return };
+        let Some(format) = ExportFormat::from_name(&r.format) else { return };
+        let (jpeg, avif, speed) = (r.jpeg_quality.clamp(1, 100), r.avif_quality.clamp(1, 100), r.avif_speed.min(10));
+        self.export_format = format;
+        self.export_jpeg_quality = jpeg; self.export_avif_quality = avif; self.export_avif_speed = speed;
+    }
+
+    pub(super) fn remember_export(&mut self) {
+        let Some(ext) = self.source_extension() else { return };
+        let remembered = RememberedExport {
+            format: self.export_format.as_str().to_string(), jpeg_quality: self.export_jpeg_quality,
+            avif_quality: self.export_avif_quality, avif_speed: self.export_avif_speed,
+        };
+        if self.export_memory.by_extension.insert(ext, remembered.clone()).as_ref() != Some(&remembered) { self.export_memory.save(); }
+    }
+
+    pub(super) fn forget_remembered_export(&mut self) {
+        let Some(ext) = self.source_extension() else { return };
+        if self.export_memory.by_extension.remove(&ext).is_some() { self.export_memory.save(); }
+    }
+
+    pub(super) fn open_export_panel(&mut self) {
+        self.export_viewport = None;
+        self.apply_remembered_export();
+        self.filter_panel = FilterPanel::Export;
+    }
+
+    pub(super) fn save_export_preset(&mut self) {
+        let name = self.export_preset_name.trim().to_string();
+        if name.is_empty() { return; }
+        let preset = self.current_export_preset(&name);
+        match self.export_presets.presets.iter_mut().find(|p| p.name == name) {
+            Some(existing) => *existing = preset,
+            None => self.export_presets.presets.push(preset),
+        }
+        self.export_presets.save();
+    }
+
+    pub(super) fn delete_export_preset(&mut self, idx: usize) {
+        if idx >= self.export_presets.presets.len() { return; }
+        self.export_presets.presets.remove(idx);
+        self.export_presets.save();
+    }
+
+    pub(super) fn begin_viewport_export(&mut self) {
+        let Some(rect) = self.visible_image_rect() else { return };
+        self.export_viewport = Some(ViewportExport { rect, zoom: self.zoom, scaled: false });
+        self.apply_remembered_export();
+        self.filter_panel = FilterPanel::Export;
+    }
+
+    pub(super) fn export_with_preset(&mut self, idx: usize) {
+        self.apply_export_preset(idx);
+        self.export_viewport = None;
+        match self.export_image_to_file() {
+            Ok(path) => { if let Some(cb) = &self.export_callback { cb(path); } }
+            Err(e) => { if !e.is_cancelled() { self.pending_error = Some(e); } }
+        }
+    }
+
+    pub(super) fn share_image(&mut self) {
+        let Some(img) = self.composite_all_layers() else { return };
+        let name = self.file_path.as_deref().and_then(|p| p.file_stem()).map(|s| s.to_string_lossy().into_owned()).unwrap_or_else(|| "Untitled".to_string());
+        match share::stage(&img, &name, &share::share_dir()) {
+            Ok(path) => self.pending_share = Some(path),
+            Err(e) => self.pending_error = Some(e),
+        }
+    }
+
+    /// Encodes the image off the UI thread exactly as a file export would and puts it on the clipboard as a
+    /// `data:` URI, or as a Markdown image wrapping one. SVG keeps its text layers as text, like the file export.
+    pub(super) fn copy_as_data_uri(&mut self, format: ExportFormat, markdown: bool) {
+        if self.data_uri_job.is_some() { return; }
+        let source = match format {
+            ExportFormat::Svg => self.svg_export(None).map(Err),
+            _ => self.export_composite(None).map(|c| Ok(self.fit_export_max_dim(c))),
+        };
+        let source = match source { Ok(s) => s, Err(e) => { self.pending_error = Some(e); return; } };
+        let (jpeg_quality, auto_scale_ico, avif_quality, avif_speed) = (self.export_jpeg_quality, self.export_auto_scale_ico, self.export_avif_quality, self.export_avif_speed);
+        let (tx, rx) = sync_channel(1);
+        tasks::spawn(format!("Encode {} data URI", format.as_str()), false, move |_| {
+            let bytes = match source {
+                Ok(composite) => encode_image(&composite, format, jpeg_quality, 6, auto_scale_ico, avif_quality, avif_speed),
+                Err(doc) => doc.render().map(String::into_bytes).map_err(|e| EditorError::encode_in_memory("SVG", e)),
+            };
+            let _ = tx.send(bytes.map(|b| data_uri(&b, format, markdown)));
+        });
+        self.data_uri_job = Some(DataUriJob { rx, format, markdown });
+    }
+
+    pub(super) fn check_data_uri_job(&mut self, ctx: &egui::Context) {
+        let Some(job) = &self.data_uri_job else { return };
+        let result = match job.rx.try_recv() {
+            Ok(r) => r,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => Err(EditorError::Other("Encoding the data URI stopped unexpectedly".to_string())),
+        };
+        let (format, markdown) = (job.format, job.markdown);
+        self.data_uri_job = None;
+        match result {
+            Ok(text) => {
+                let what = if markdown { "a Markdown image" } else { "a data URI" };
+                let mut notice = format!("Copied {} as {} ({})", format.as_str(), what, data_uri_size(text.len()));
+                if text.len() > DATA_URI_WARN_BYTES { notice.push_str(". Data URIs this large make HTML and Markdown files slow to open and edit."); }
+                ctx.copy_text(text);
+                self.pending_notice = Some(notice);
+            }
+            Err(e) => self.pending_error = Some(e),
+        }
+    }
+
+    pub(super) fn start_wand(&mut self, x: u32, y: u32, op: SelectOp) {
+        let Some(buf) = self.fill_source().map(|img| img.to_rgba8()) else { return };
+        if let Some(job) = self.wand_job.take() { job.task.cancel(); }
+        let (tx, rx) = sync_channel(1);
+        let (tolerance, contiguous) = (self.wand_tolerance, self.wand_contiguous);
+        let task = tasks::spawn("Magic wand", true, move |task| {
+            let _ = tx.send(ie_select::wand(&buf, x, y, tolerance, contiguous, &|p| task.progress(p), task.cancel_flag()));
+        });
+        self.wand_job = Some(WandJob { rx, op, task });
+    }
+
+    pub(super) fn check_wand_job(&mut self, ctx: &egui::Context) {
+        let Some(job) = &self.wand_job else { return };
+        match job.rx.try_recv() {
+            Ok(sel) => {
+                let op = job.op;
+                self.wand_job = None;
+                if let Some(sel) = sel { self.selection = Selection::combine(self.selection.take(), sel, op); }
+            }
+            Err(TryRecvError::Empty) => ctx.request_repaint_after(std::time::Duration::from_millis(30)),
+            Err(TryRecvError::Disconnected) => self.wand_job = None,
+        }
+    }
+
+    pub(super) fn toggle_saved_comparison(&mut self) {
+        if let Some(c) = self.comparison.take() {
+            if let Some((_, task)) = c.job { task.cancel(); }
+            return;
+        }
+        let (Some(path), Some(current)) = (self.file_path.clone(), self.composite_all_layers()) else { return };
+        let current = current.into_rgba8();
+        let (tx, rx) = sync_channel(1);
+        let task = tasks::spawn("Compare with saved", true, move |task| {
+            if task.cancelled() { return; }
+            let saved = read_with_source(&path).map(|(img, _)| img).or_else(|_| image::open(&path));
+            let _ = tx.send(saved.map(|saved| ie_compare::diff(&saved.into_rgba8(), &current, &|p| task.progress(p), task.cancel_flag())));
+        });
+        self.comparison = Some(SavedComparison { job: Some((rx, task)), diff: None, mode: CompareMode::Overlay, texture: None });
+    }
+
+    pub(super) fn check_saved_comparison(&mut self, ctx: &egui::Context) {
+        let Some(c) = &mut self.comparison else { return };
+        if let Some((rx, _)) = &c.job {
+            match rx.try_recv() {
+                Ok(Ok(Some(diff))) => { c.diff = Some(diff); c.job = None; }
+                Ok(Err(source)) => {
+                    self.pending_error = self.file_path.as_deref().map(|p| EditorError::decode(p, source));
+                    self.comparison = None;
+                    return;
+                }
+                Ok(Ok(None)) | Err(TryRecvError::Disconnected) => { self.comparison = None; return; }
+                Err(TryRecvError::Empty) => { ctx.request_repaint_after(std::time::Duration::from_millis(30)); return; }
+            }
+        }
+        if let Some(diff) = &c.diff && c.texture.as_ref().is_none_or(|(mode, _)| *mode != c.mode) {
+            c.texture = Some((c.mode, ctx.load_texture("saved_comparison", diff.image(c.mode).clone(), egui::TextureOptions::NEAREST)));
+        }
+    }
+
+    pub(super) fn invert_selection(&mut self) {
+        if let Some(sel) = &self.selection { self.selection = sel.inverted(); }
+    }
+
+    pub(super) fn delete_selected_pixels(&mut self) {
+        let id = self.active_layer_id;
+        let Some(kind) = self.layers.iter().find(|l| l.id == id).map(|l| l.kind) else { return };
+        let src = match kind { LayerKind::Background => self.image.as_ref(), LayerKind::Raster => self.layer_images.get(&id), _ => return };
+        let Some(cleared) = src.zip(self.selection.as_ref()).filter(|(img, sel)| sel.fits(img.width(), img.height())).map(|(img, sel)| sel.clear_inside(img)) else { return };
+        if self.pixels_blocked() { return; }
+        self.push_undo();
+        match kind {
+            LayerKind::Background => self.image = Some(cleared),
+            _ => {
+                self.layer_images.insert(id, cleared);
+                self.raster_layer_texture_dirty.insert(id);
+                self.raster_layer_dirty_rects.remove(&id);
+            }
+        }
+        self.composite_dirty = true; self.texture_dirty = true; self.dirty = true;
+    }
+
+    pub(super) fn export_selection(&mut self) {
+        let Some(img) = self.composite_all_layers() else { return };
+        let Some(cut) = self.selection.as_ref().filter(|s| s.fits(img.width(), img.height())).and_then(|s| s.cut(&img)) else { return };
+        let name = self.file_path.as_ref().and_then(|p| p.file_stem()).and_then(|s| s.to_str()).unwrap_or("export");
+        let Some(path) = rfd::FileDialog::new().set_file_name(format!("{}_selection.png", name)).add_filter("PNG", &["png"]).save_file() else { return };
+        match cut.save_with_format(&path, image::ImageFormat::Png) {
+            Ok(()) => if let Some(cb) = &self.export_callback { cb(path); },
+            Err(source) => self.pending_error = Some(EditorError::ImageEncode { format: "PNG".into(), source }),
+        }
+    }
+
+    pub(super) fn place_floating_image(&mut self, img: DynamicImage) {
+        self.commit_placed_image();
+        self.insert_image_layer(img, true);
+        self.placing_image = self.selected_image_layer.map(|iid| (iid, self.undo_pushes));
+        if self.placing_image.is_some() { self.pending_notice = Some("Drag to position the image, Shift toggles the aspect lock. Enter places it, Esc cancels.".to_string()); }
+    }
+
+    pub(super) fn commit_placed_image(&mut self) {
+        let Some((iid, _)) = self.placing_image.take() else { return };
+        let Some(idx) = self.layers.iter().position(|l| l.linked_image_id == Some(iid)) else { return };
+        if idx == 0 || !matches!(self.layers[idx - 1].kind, LayerKind::Background | LayerKind::Raster) {
+            self.pending_notice = Some("The layer below holds no pixels, so the placed image was kept as a layer".to_string());
+            return;
+        }
+        if self.pixels_blocked() { return; }
+        self.active_layer_id = self.layers[idx].id;
+        self.merge_layer_down(idx);
+        self.composite_dirty = true;
+        self.dirty = true;
+    }
+
+    pub(super) fn cancel_placed_image(&mut self) {
+        let Some((iid, mark)) = self.placing_image.take() else { return };
+        if self.undo_pushes == mark && let Some(entry) = self.history.discard_last() {
+            self.restore_undo_snapshot(entry);
+            self.refresh_dirty_from_hash();
+        } else if self.image_layer_for_active() == Some(iid) { self.delete_active_layer(); }
+    }
+
+    /// The selection as it looks on the canvas, or all of it when `whole` or nothing is selected, tagged with the
+    /// document's DPI.
+    pub(super) fn image_clip(&self, whole: bool) -> Option<ImageClip> {
+        let img = self.composite_all_layers()?;
+        let image = match self.selection.as_ref().filter(|s| !whole && s.fits(img.width(), img.height())) {
+            Some(sel) => sel.cut(&img)?.to_rgba8(),
+            None => img.to_rgba8(),
+        };
+        Some(ImageClip { image, dpi: self.measure_dpi })
+    }
+
+    pub(super) fn copy_image(&mut self, whole: bool) {
+        let Some(clip) = self.image_clip(whole).map(|c| self.hold_clip(c)) else { return };
+        let (w, h) = (clip.image.width() as usize, clip.image.height() as usize);
+        let data = arboard::ImageData { width: w, height: h, bytes: clip.image.as_raw().as_slice().into() };
+        if arboard::Clipboard::new().and_then(|mut c| c.set_image(data)).is_err() {
+            self.pending_notice = Some("Copied for pasting in this app only; the system clipboard is unavailable".to_string());
+        }
+    }
+
+    /// Makes `clip` the app's image clip, handed on through the settings, and remembers that this editor copied it.
+    pub(super) fn hold_clip(&mut self, clip: ImageClip) -> Arc<ImageClip> {
+        let clip = Arc::new(clip);
+        self.own_clip = Arc::downgrade(&clip);
+        self.settings.image_clip = Some(clip.clone());
+        self.settings_changed = true;
+        clip
+    }
+
+    /// Pastes the newest image on the clipboard: as the canvas when none is open, otherwise as a floating object.
+    pub(super) fn paste_image(&mut self) {
+        if self.pixels_locked { return; }
+        let os = arboard::Clipboard::new().and_then(|mut c| c.get_image());
+        let unreadable = match &os { Err(arboard::Error::ContentNotAvailable) | Ok(_) => None, Err(e) => Some(e.to_string()) };
+        match ImageClip::newest(self.settings.image_clip.clone(), os.ok()) {
+            Some(clip) => self.paste_clip(&clip),
+            None => self.pending_notice = Some(match unreadable {
+                Some(e) => format!("Could not read an image from the system clipboard: {}", e),
+                None => "The clipboard holds no image".to_string(),
+            }),
+        }
+    }
+
+    fn paste_clip(&mut self, clip: &ImageClip) {
+        if self.image.is_none() || self.untouched_blank() { self.open_image_clip(clip); }
+        else if !(self.tool == Tool::Select && self.paste_floating_pixels(clip)) { self.paste_image_clip(clip); }
+    }
+
+    pub(super) fn open_image_clip(&mut self, clip: &ImageClip) {
+        let (w, h) = clip.image.dimensions();
+        if self.refuse_canvas_size(w, h) { return; }
+        self.push_undo();
+        self.image = Some(DynamicImage::ImageRgba8(clip.image.clone()));
+        if clip.dpi > 0.0 { self.measure_dpi = clip.dpi; }
+        self.resize_w = w; self.resize_h = h;
+        self.texture_dirty = true; self.composite_dirty = true;
+        self.file_path = None; self.dirty = true; self.fit_on_next_frame = true;
+    }
+
+    /// Where a pasted `clip` lands as `(x, y, w, h)`: centered on the view, resized by the ratio of the two DPIs
+    /// when both are known and shrunk to fit the canvas. A paste into the document it came from is nudged so it
+    /// doesn't hide the original.
+    fn paste_placement(&self, clip: &ImageClip) -> Option<(f32, f32, f32, f32)> {
+        let (cw, ch) = self.image.as_ref().map(|i| (i.width(), i.height()))?;
+        let [x0, y0, x1, y1] = self.visible_image_rect().unwrap_or([0, 0, cw, ch]);
+        let dpi_scale = if clip.dpi > 0.0 && self.measure_dpi > 0.0 { self.measure_dpi / clip.dpi } else { 1.0 };
+        let (w, h) = (clip.image.width() as f32 * dpi_scale, clip.image.height() as f32 * dpi_scale);
+        let fit = (cw as f32 / w).min(ch as f32 / h).min(1.0);
+        let (w, h) = (w * fit, h * fit);
+        let offset = if std::ptr::eq(self.own_clip.as_ptr(), clip) { PASTE_OFFSET } else { 0.0 };
+        Some(((x0 + x1) as f32 / 2.0 - w / 2.0 + offset, (y0 + y1) as f32 / 2.0 - h / 2.0 + offset, w, h))
+    }
+
+    pub(super) fn paste_image_clip(&mut self, clip: &ImageClip) {
+        let Some((x, y, w, h)) = self.paste_placement(clip) else { return };
+        self.place_floating_image(DynamicImage::ImageRgba8(clip.image.clone()));
+        let Some((iid, _)) = self.placing_image else { return };
+        let Some(ild) = self.image_layer_data.get_mut(&iid) else { return };
+        (ild.canvas_x, ild.canvas_y, ild.display_w, ild.display_h) = (x, y, w, h);
+        self.composite_dirty = true;
+    }
+
+    /// Background or raster pixels of layer `id`, the only kind the Select tool lifts from and drops onto.
+    pub(super) fn pixel_layer(&self, id: u64) -> Option<&DynamicImage> {
+        match self.layers.iter().find(|l| l.id == id)?.kind {
+            LayerKind::Background => self.image.as_ref(),
+            LayerKind::Raster => self.layer_images.get(&id),
+            _ => None,
+        }
+    }
+
+    fn set_pixel_layer(&mut self, id: u64, img: DynamicImage) {
+        if self.layers.iter().any(|l| l.id == id && l.kind == LayerKind::Background) { self.image = Some(img); } else {
+            self.layer_images.insert(id, img);
+            self.raster_layer_texture_dirty.insert(id);
+            self.raster_layer_dirty_rects.remove(&id);
+        }
+        self.composite_dirty = true; self.texture_dirty = true; self.dirty = true;
+    }
+
+    pub(super) fn floating_contains(&self, p: (f32, f32)) -> bool {
+        self.selection_state.floating.as_ref().is_some_and(|f| {
+            let (x, y) = (p.0 - f.offset.0 as f32, p.1 - f.offset.1 as f32);
+            x >= 0.0 && y >= 0.0 && x < f.pixels.width() as f32 && y < f.pixels.height() as f32
+        })
+    }
+
+    pub(super) fn selection_contains(&self, p: (f32, f32)) -> bool {
+        p.0 >= 0.0 && p.1 >= 0.0 && self.selection.as_ref().is_some_and(|s| s.contains(p.0 as u32, p.1 as u32))
+    }
+
+    pub(super) fn select_marquee(&mut self, a: (f32, f32), b: (f32, f32), op: SelectOp) {
+        let Some((w, h)) = self.image.as_ref().map(|i| i.dimensions()) else { return };
+        match Selection::rect(w, h, a, b) {
+            Some(rect) => self.selection = Selection::combine(self.selection.take(), rect, op),
+            None if op == SelectOp::Replace => self.selection = None,
+            None => {}
+        }
+    }
+
+    /// Takes the selected pixels off the active layer into a floating buffer, leaving transparency behind. The one
+    /// undo step covers the lift and wherever the pixels end up.
+    pub(super) fn lift_selection(&mut self) -> bool {
+        if self.selection_state.floating.is_some() { return true; }
+        let id = self.active_layer_id;
+        let Some(sel) = self.selection.clone() else { return false };
+        let Some(src) = self.pixel_layer(id).filter(|img| sel.fits(img.width(), img.height())).cloned() else {
+            self.pending_notice = Some("Selected pixels move on the background or a raster layer. Pick one in the Layers panel.".to_string());
+            return false;
+        };
+        let (Some([x0, y0, _, _]), Some(cut)) = (sel.bounds(), sel.cut(&src)) else { return false };
+        if self.pixels_blocked() { return false; }
+        self.push_undo();
+        self.set_pixel_layer(id, sel.clear_inside(&src));
+        let at = (x0 as i32, y0 as i32);
+        self.selection_state.floating = Some(FloatingPixels { pixels: cut.into_rgba8(), offset: at, origin: at, mask: Some(sel), layer: id, undo_mark: self.undo_pushes, texture: None });
+        self.selection = None;
+        true
+    }
+
+    /// Pastes `clip` as floating pixels on the active layer. False when that layer holds no pixels to paste onto.
+    fn paste_floating_pixels(&mut self, clip: &ImageClip) -> bool {
+        let id = self.active_layer_id;
+        if self.pixel_layer(id).is_none() { return false; }
+        let Some((x, y, w, h)) = self.paste_placement(clip) else { return false };
+        self.commit_floating_selection();
+        if self.pixels_blocked() { return true; }
+        let (w, h) = ((w.round() as u32).max(1), (h.round() as u32).max(1));
+        let pixels = if (w, h) == clip.image.dimensions() { clip.image.clone() } else { image::imageops::resize(&clip.image, w, h, image::imageops::FilterType::Triangle) };
+        self.push_undo();
+        self.selection = None;
+        let at = (x.round() as i32, y.round() as i32);
+        self.selection_state.floating = Some(FloatingPixels { pixels, offset: at, origin: at, mask: None, layer: id, undo_mark: self.undo_pushes, texture: None });
+        true
+    }
+
+    /// Drops the floating pixels onto their layer and selects them where they landed. Pixels that never moved
+    /// just go back, without leaving an undo step.
+    pub(super) fn commit_floating_selection(&mut self) {
+        self.selection_state.move_from = None;
+        let Some(f) = self.selection_state.floating.take() else { return };
+        if f.offset == f.origin && f.mask.is_some() && self.undo_pushes == f.undo_mark {
+            self.selection_state.floating = Some(f);
+            self.cancel_floating_selection();
+            return;
+        }
+        let Some(mut buf) = self.pixel_layer(f.layer).map(|img| img.to_rgba8()) else { return };
+        image::imageops::overlay(&mut buf, &f.pixels, f.offset.0 as i64, f.offset.1 as i64);
+        let (w, h) = buf.dimensions();
+        self.set_pixel_layer(f.layer, DynamicImage::ImageRgba8(buf));
+        let (dx, dy) = (f.offset.0 - f.origin.0, f.offset.1 - f.origin.1);
+        self.selection = match f.mask {
+            Some(mask) => mask.translated(dx, dy),
+            None => Selection::rect(w, h, (f.offset.0 as f32, f.offset.1 as f32), ((f.offset.0 + f.pixels.width() as i32) as f32, (f.offset.1 + f.pixels.height() as i32) as f32)),
+        };
+    }
+
+    /// Puts lifted pixels back (or drops pasted ones) and takes back the undo step that made them float.
+    pub(super) fn cancel_floating_selection(&mut self) {
+        self.selection_state.move_from = None;
+        let Some(f) = self.selection_state.floating.take() else { return };
+        if self.undo_pushes != f.undo_mark {
+            self.selection_state.floating = Some(f);
+            self.commit_floating_selection();
+            return;
+        }
+        if let Some(entry) = self.history.discard_last() {
+            self.restore_undo_snapshot(entry);
+            self.refresh_dirty_from_hash();
+        }
+        if f.mask.is_some() { self.selection = f.mask; }
+    }
+
+    pub(super) fn cut_selection(&mut self) {
+        if self.selection.is_none() { return; }
+        self.copy_image(false);
+        self.delete_selected_pixels();
+    }
+
+    pub(super) fn resolve_text_fonts(&mut self) {
+        for tl in &mut self.text_layers {
+            tl.font_substitute = if is_known_font(&tl.font_name) { None } else {
+                Some(self.font_relinks.get(&tl.font_name).filter(|f| is_known_font(f)).cloned().unwrap_or_else(|| "Ubuntu".to_string()))
+            };
+        }
+        self.composite_dirty = true;
+    }
+
+    pub(super) fn missing_fonts(&self) -> Vec<(String, usize)> {
+        let mut out: Vec<(String, usize)> = Vec::new();
+        for tl in self.text_layers.iter().filter(|t| t.font_substitute.is_some() && !self.font_relinks.contains_key(&t.font_name)) {
+            match out.iter_mut().find(|(n, _)| *n == tl.font_name) { Some((_, c)) => *c += 1, None => out.push((tl.font_name.clone(), 1)) }
+        }
+        out
+    }
+
+    pub(super) fn open_font_relink_dialog(&mut self) {
+        let mut names: Vec<String> = Vec::new();
+        for tl in self.text_layers.iter().filter(|t| t.font_substitute.is_some()) { if !names.contains(&tl.font_name) { names.push(tl.font_name.clone()); } }
+        self.font_relink_dialog = Some(names.into_iter().map(|n| { let to = self.font_relinks.get(&n).cloned().unwrap_or_else(|| "Ubuntu".to_string()); (n, to) }).collect());
+    }
+
+    pub(super) fn apply_font_relinks(&mut self, relinks: Vec<(String, String)>) {
+        if relinks.is_empty() { return; }
+        self.font_relinks.extend(relinks);
+        self.resolve_text_fonts();
+        self.dirty = true;
+    }
+
+    pub(super) fn nudge_brush_size(&mut self, dir: f32) {
+        let size = match self.tool {
+            Tool::Eraser => &mut self.eraser_size,
+            Tool::Retouch => &mut self.retouch_size,
+            _ => { self.brush_preview_cache_key = None; &mut self.brush.size }
+        };
+        *size = (*size + dir * (*size * 0.1).max(1.0)).round().clamp(1.0, 200.0);
+    }
+
+    pub(super) fn move_keyboard_cursor(&mut self, dx: f32, dy: f32, paint: bool) {
+        let Some((w, h)) = self.image.as_ref().map(|i| (i.width() as f32, i.height() as f32)) else { return };
+        let (x0, y0) = self.kb_cursor.unwrap_or(((w / 2.0).floor(), (h / 2.0).floor()));
+        let next = ((x0 + dx).clamp(0.0, w - 1.0), (y0 + dy).clamp(0.0, h - 1.0));
+        self.kb_cursor = Some(next);
+        if !paint || !matches!(self.tool, Tool::Brush | Tool::Eraser | Tool::Retouch) { return; }
+        self.stroke_points.clear();
+        self.stroke_points.push((x0, y0));
+        self.stroke_points.push(next);
+        if self.tool == Tool::Retouch { self.apply_retouch_stroke(); } else { self.apply_brush_stroke(); }
+        self.stroke_points.clear();
+        self.composite_dirty = true;
+    }
+
+    pub(super) fn nudge_crop(&mut self, dx: f32, dy: f32, move_start: bool, move_end: bool) {
+        let Some((w, h)) = self.image.as_ref().map(|i| (i.width() as f32, i.height() as f32)) else { return };
+        let (Some(s), Some(e)) = (self.crop_state.start, self.crop_state.end) else {
+            self.crop_state = CropState { start: Some((0.0, 0.0)), end: Some((w, h)) };
+            return;
+        };
+        let (mut x0, mut y0, mut x1, mut y1) = (s.0.min(e.0), s.1.min(e.1), s.0.max(e.0), s.1.max(e.1));
+        let step = if move_start || move_end { self.dimension_snap.step() as f32 } else { 1.0 };
+        let (dx, dy) = (dx * step, dy * step);
+        if move_start {
+            x0 = (x0 + dx).clamp(0.0, x1 - 1.0); y0 = (y0 + dy).clamp(0.0, y1 - 1.0);
+        } else if move_end {
+            x1 = (x1 + dx).clamp(x0 + 1.0, w); y1 = (y1 + dy).clamp(y0 + 1.0, h);
+        } else {
+            let (cw, ch) = (x1 - x0, y1 - y0);
+            x0 = (x0 + dx).clamp(0.0, (w - cw).max(0.0)); y0 = (y0 + dy).clamp(0.0, (h - ch).max(0.0));
+            x1 = x0 + cw; y1 = y0 + ch;
+        }
+        self.crop_state = CropState { start: Some((x0, y0)), end: Some((x1, y1)) };
+        if move_start { self.snap_crop(Some(true), Some(true)); } else if move_end { self.snap_crop(Some(false), Some(false)); }
+    }
+
+    pub(super) fn edge_drag_active(&self) -> bool {
+        self.crop_drag.is_some() || (self.tool == Tool::Crop && self.crop_state.start.is_some())
+            || self.text_drag.is_some() || self.image_drag.is_some() || self.slice_drag.is_some() || self.slice_draft.is_some()
+    }
+
+    pub(super) fn edge_autoscroll(&mut self, ctx: &egui::Context, canvas: egui::Rect, pos: egui::Pos2) {
+        let inner = canvas.shrink(EDGE_SCROLL_MARGIN);
+        let over = |p: f32, lo: f32, hi: f32| if p < lo { lo - p } else if p > hi { hi - p } else { 0.0 };
+        let push = egui::vec2(over(pos.x, inner.min.x, inner.max.x), over(pos.y, inner.min.y, inner.max.y)).clamp(egui::Vec2::splat(-EDGE_SCROLL_MAX), egui::Vec2::splat(EDGE_SCROLL_MAX));
+        if push == egui::Vec2::ZERO { return; }
+        let d = push * EDGE_SCROLL_SPEED * ctx.input(|i| i.stable_dt).min(0.1);
+        self.pan += d;
+        if let Some(drag) = &mut self.text_drag { drag.start += d; }
+        if let Some(drag) = &mut self.image_drag { drag.start += d; }
+        if let Some((_, start)) = &mut self.slice_drag { *start += d; }
+        if let (Some(THandle::Move), Some((x0, y0, x1, y1))) = (self.crop_drag, &mut self.crop_drag_orig) {
+            let (dx, dy) = (d.x / self.zoom, d.y / self.zoom);
+            *x0 -= dx; *y0 -= dy; *x1 -= dx; *y1 -= dy;
+        }
+        ctx.request_repaint();
+    }
+
+    pub(super) fn commit_slice_draft(&mut self) {
+        let Some((a, b)) = self.slice_draft.take() else { return };
+        let name = format!("slice_{}", self.slices.len() + 1);
+        let slice = ImageSlice::from_corners(name, a, b);
+        let (img_w, img_h) = self.image.as_ref().map(|i| (i.width(), i.height())).unwrap_or((0, 0));
+        if slice.pixel_rect(img_w, img_h).is_none() {
+            self.slice_message = Some("Slices must be at least 1x1 px and overlap the image".to_string());
+            return;
+        }
+        self.slice_message = None;
+        self.slices.push(slice);
+        self.selected_slice = Some(self.slices.len() - 1);
+        self.dirty = true;
+    }
+
+    pub(super) fn delete_selected_slice(&mut self) {
+        let Some(idx) = self.selected_slice.take() else { return };
+        if idx < self.slices.len() { self.slices.remove(idx); self.dirty = true; }
+        self.slice_drag = None;
+    }
+
+    pub(super) fn begin_measurement(&mut self, p: (f32, f32)) {
+        if let Some(done) = self.measure_active.take() { self.archive_measurement(done); }
+        self.measure_active = Some(Measurement::new(p));
+    }
+
+    pub(super) fn update_measurement(&mut self, p: (f32, f32), constrain: bool) {
+        let Some(m) = &mut self.measure_active else { return };
+        match m.c {
+            Some(_) => m.c = Some(if constrain { Measurement::constrain(m.b, p) } else { p }),
+            None => m.b = if constrain { Measurement::constrain(m.a, p) } else { p },
+        }
+    }
+
+    pub(super) fn click_measurement(&mut self, p: (f32, f32), constrain: bool) {
+        match self.measure_active {
+            Some(m) if m.c.is_none() && m.length() > 0.0 => self.measure_active = Some(Measurement { c: Some(if constrain { Measurement::constrain(m.b, p) } else { p }), ..m }),
+            Some(m) => { self.measure_active = None; self.archive_measurement(m); }
+            None => {}
+        }
+    }
+
+    fn archive_measurement(&mut self, m: Measurement) {
+        if m.length() <= 0.0 { return; }
+        self.measurements.push(m);
+        if self.measurements.len() > MAX_MEASUREMENTS { self.measurements.remove(0); }
+    }
+
+    pub(super) fn clear_measurements(&mut self) { self.measure_active = None; self.measurements.clear(); }
+
+    pub(super) fn duplicate_selected_slice(&mut self) {
+        let Some(src) = self.selected_slice.and_then(|i| self.slices.get(i)).cloned() else { return };
+        let offset = (src.w.min(src.h) * 0.1).clamp(4.0, 24.0).round();
+        self.slices.push(ImageSlice { name: format!("{}_copy", src.name), x: src.x + offset, y: src.y + offset, ..src });
+        self.selected_slice = Some(self.slices.len() - 1);
+        self.dirty = true;
+    }
+
+    pub(super) fn export_slices(&mut self, dir: &Path) -> EditorResult<usize> {
+        if self.slices.is_empty() { return Err(EditorError::Empty("no slices have been defined")); }
+        let composite = self.composite_all_layers().ok_or(EditorError::Empty("no image is loaded"))?;
+        let mut used: std::collections::HashSet<String> = std::collections::HashSet::new();
+        for (i, slice) in self.slices.iter().enumerate() {
+            let (x, y, w, h) = slice.pixel_rect(composite.width(), composite.height())
+                .ok_or_else(|| EditorError::Other(format!("Slice \"{}\" is empty or outside the image", slice.name)))?;
+            let stem = slice_file_stem(&self.slice_pattern, slice, i + 1, w, h);
+            let mut name = stem.clone();
+            let mut n = 2;
+            while !used.insert(name.to_lowercase()) { name = format!("{}_{}", stem, n); n += 1; }
+            let path = dir.join(format!("{}.{}", name, self.export_format.extension()));
+            export_image(&composite.crop_imm(x, y, w, h), &path, self.export_format, self.export_jpeg_quality, 6, 100.0, self.export_auto_scale_ico, self.export_avif_quality, self.export_avif_speed)?;
+        }
+        Ok(self.slices.len())
+    }
+
+    pub(super) fn selected_variant_scales(&self) -> Vec<f32> {
+        let mut scales: Vec<f32> = VARIANT_SCALES.iter().zip(self.variant_scales).filter(|(_, on)| *on).map(|(s, _)| *s).chain(self.variant_custom).collect();
+        scales.sort_by(f32::total_cmp);
+        scales.dedup_by(|a, b| format_scale(*a) == format_scale(*b));
+        scales
+    }
+
+    pub(super) fn variant_upscales(&self) -> Vec<f32> {
+        self.selected_variant_scales().into_iter().filter(|s| s / self.variant_base > VARIANT_UPSCALE_WARN).collect()
+    }
+
+    pub(super) fn start_variant_export(&mut self, ctx: &egui::Context, dir: &Path) -> EditorResult {
+        let scales = self.selected_variant_scales();
+        if scales.is_empty() { return Err(EditorError::Empty("no export scales are selected")); }
+        let composite = self.composite_all_layers().ok_or(EditorError::Empty("no image is loaded"))?;
+        let name = self.file_path.as_ref().and_then(|p| p.file_stem()).and_then(|s| s.to_str()).unwrap_or("export").to_string();
+        let mut used: std::collections::HashSet<String> = std::collections::HashSet::new();
+        let targets: Vec<(PathBuf, u32, u32)> = scales.iter().map(|&scale| {
+            let stem = variant_file_stem(&self.variant_pattern, &name, scale);
+            let mut file = stem.clone();
+            let mut n = 2;
+            while !used.insert(file.to_lowercase()) { file = format!("{}_{}", stem, n); n += 1; }
+            let f = scale / self.variant_base;
+            let (w, h) = ((composite.width() as f32 * f).round().max(1.0) as u32, (composite.height() as f32 * f).round().max(1.0) as u32);
+            (dir.join(format!("{}.{}", file, self.export_format.extension())), w, h)
+        }).collect();
+        let (format, jpeg_quality, auto_scale_ico, avif_quality, avif_speed) = (self.export_format, self.export_jpeg_quality, self.export_auto_scale_ico, self.export_avif_quality, self.export_avif_speed);
+        let done = Arc::new(AtomicUsize::new(0));
+        let (tx, rx) = sync_channel(1);
+        let (worker_done, ctx) = (done.clone(), ctx.clone());
+        let total = targets.len();
+        let task = tasks::spawn(format!("Export {} scaled copies", total), true, move |task| {
+            let result = targets.into_iter().map(|(path, w, h)| {
+                if task.cancelled() { return Err(EditorError::Cancelled); }
+                let img = if composite.dimensions() == (w, h) { composite.clone() } else { composite.resize_exact(w, h, image::imageops::FilterType::Lanczos3) };
+                export_image(&img, &path, format, jpeg_quality, 6, 100.0, auto_scale_ico, avif_quality, avif_speed)?;
+                task.progress((worker_done.fetch_add(1, Ordering::Relaxed) + 1) as f32 / total as f32);
+                ctx.request_repaint();
+                Ok(path)
+            }).collect::<EditorResult<Vec<PathBuf>>>();
+            let toast = result.as_ref().ok().map(|paths| {
+                let names: Vec<String> = paths.iter().filter_map(|p| p.file_name()).map(|n| n.to_string_lossy().into_owned()).collect();
+                format!("Exported {} variants: {}", names.len(), names.join(", "))
+            });
+            let _ = tx.send(result);
+            task.finish(toast);
+        });
+        self.variant_export = Some(VariantExport { done, total, task, rx });
+        self.variant_confirm = false;
+        Ok(())
+    }
+
+    pub(super) fn check_variant_export(&mut self) {
+        let Some(job) = &self.variant_export else { return };
+        let result = match job.rx.try_recv() {
+            Ok(r) => r,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) if job.task.cancelled() => Err(EditorError::Cancelled),
+            Err(TryRecvError::Disconnected) => Err(EditorError::Other("Scaled export stopped before writing every file".to_string())),
+        };
+        self.variant_export = None;
+        match result {
+            Ok(paths) => if let Some(cb) = &self.export_callback { for p in paths { cb(p); } },
+            Err(e) => if !e.is_cancelled() { self.pending_error = Some(e); },
+        }
+    }
+
+    pub(super) fn render_brush_preview_to_pixels(&self, w: u32, h: u32) -> Vec<egui::Color32> {
         let bg = [255u8, 255, 255, 255];
         let mut buf: Vec<[u8; 4]> = vec![bg; (w * h) as usize];
         let (r, g, b_ch, base_a) = (self.color.r(), self.color.g(), self.color.b(), self.color.a().max(180));
@@ -1585,3 +2968,990 @@ pub(super) fn brush_texture_noise(px: u32, py: u32, mode: BrushTextureMode) -> f
         BrushTextureMode::Paper => paper_noise(px, py),
     }
 }
+
+/// Zoomed-in views are magnified with nearest-neighbour on screen, so the scaled export keeps hard pixel edges too.
+/// `data:<mime>;base64,...`, optionally wrapped as a Markdown image.
+pub(super) fn data_uri(bytes: &[u8], format: ExportFormat, markdown: bool) -> String {
+    use base64::Engine;
+    let uri = format!("data:{};base64,{}", format.mime_type(), base64::engine::general_purpose::STANDARD.encode(bytes));
+    if markdown { format!("![]({})", uri) } else { uri }
+}
+
+fn data_uri_size(len: usize) -> String {
+    if len < 1024 * 1024 { format!("{:.1} KB", len as f64 / 1024.0) } else { format!("{:.1} MB", len as f64 / (1024.0 * 1024.0)) }
+}
+
+fn crop_to_viewport(img: &DynamicImage, v: ViewportExport) -> DynamicImage {
+    let (w, h) = v.native_size();
+    let cut = img.crop_imm(v.rect[0], v.rect[1], w, h);
+    let (ow, oh) = v.output_size();
+    if (ow, oh) == (w, h) { return cut; }
+    let filter = if v.zoom > 1.0 { image::imageops::FilterType::Nearest } else { image::imageops::FilterType::Lanczos3 };
+    cut.resize_exact(ow, oh, filter)
+}
+
+fn clean_file_stem(raw: &str) -> String {
+    let clean: String = raw.chars().map(|c| if matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') || c.is_control() { '_' } else { c }).collect();
+    clean.trim().trim_matches('.').to_string()
+}
+
+fn slice_file_stem(pattern: &str, slice: &ImageSlice, index: usize, w: u32, h: u32) -> String {
+    let pattern = if pattern.trim().is_empty() { "{name}" } else { pattern };
+    let clean = clean_file_stem(&pattern.replace("{name}", &slice.name).replace("{index}", &index.to_string()).replace("{w}", &w.to_string()).replace("{h}", &h.to_string()));
+    if clean.is_empty() { format!("slice_{}", index) } else { clean }
+}
+
+pub(super) fn format_scale(scale: f32) -> String {
+    format!("{:.2}", scale).trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+pub(super) fn variant_file_stem(pattern: &str, name: &str, scale: f32) -> String {
+    let pattern = if pattern.trim().is_empty() { "{name}@{scale}x" } else { pattern };
+    let clean = clean_file_stem(&pattern.replace("{name}", name).replace("{scale}", &format_scale(scale)));
+    if clean.is_empty() { format!("{}@{}x", name, format_scale(scale)) } else { clean }
+}
+
+/// Equalizes luma in place; CLAHE blends the four nearest tile maps so tile edges leave no seams.
+fn equalize(buf: &mut image::RgbaImage, mode: EqualizeMode, tiles: u32, clip: f32, progress: &dyn Fn(f32)) {
+    let (w, h) = (buf.width() as usize, buf.height() as usize);
+    let luma: Vec<u8> = buf.pixels().map(|p| (0.299 * p[0] as f32 + 0.587 * p[1] as f32 + 0.114 * p[2] as f32).round().clamp(0.0, 255.0) as u8).collect();
+    progress(0.1);
+    let (tx, ty) = match mode { EqualizeMode::Global => (1, 1), EqualizeMode::Clahe => (tiles.min(w as u32).max(1) as usize, tiles.min(h as u32).max(1) as usize) };
+    let maps: Vec<[u8; 256]> = match mode {
+        EqualizeMode::Global => vec![equalize_map(luma.iter().zip(buf.pixels()).filter(|(_, p)| p[3] > 0).map(|(l, _)| *l), None)],
+        EqualizeMode::Clahe => {
+            let mut maps = Vec::with_capacity(tx * ty);
+            for j in 0..ty {
+                let (y0, y1) = (j * h / ty, (j + 1) * h / ty);
+                for i in 0..tx {
+                    let (x0, x1) = (i * w / tx, (i + 1) * w / tx);
+                    maps.push(equalize_map((y0..y1).flat_map(|y| luma[y * w + x0..y * w + x1].iter().copied()), Some(clip)));
+                }
+                progress(0.1 + 0.3 * (j + 1) as f32 / ty as f32);
+            }
+            maps
+        }
+    };
+    let axis = |p: usize, len: usize, n: usize| -> (usize, usize, f32) {
+        let t = ((p as f32 + 0.5) * n as f32 / len as f32 - 0.5).clamp(0.0, (n - 1) as f32);
+        let a = t.floor() as usize;
+        (a, (a + 1).min(n - 1), t - a as f32)
+    };
+    for y in 0..h {
+        let (j0, j1, fy) = axis(y, h, ty);
+        for x in 0..w {
+            let px = buf.get_pixel_mut(x as u32, y as u32);
+            if px[3] == 0 { continue; }
+            let l = luma[y * w + x] as usize;
+            let (i0, i1, fx) = axis(x, w, tx);
+            let top = maps[j0 * tx + i0][l] as f32 * (1.0 - fx) + maps[j0 * tx + i1][l] as f32 * fx;
+            let bot = maps[j1 * tx + i0][l] as f32 * (1.0 - fx) + maps[j1 * tx + i1][l] as f32 * fx;
+            let dy = top * (1.0 - fy) + bot * fy - l as f32;
+            for c in 0..3 { px[c] = (px[c] as f32 + dy).round().clamp(0.0, 255.0) as u8; }
+        }
+        if y % 16 == 0 { progress(0.4 + 0.6 * y as f32 / h as f32); }
+    }
+}
+
+fn equalize_map(values: impl Iterator<Item = u8>, clip: Option<f32>) -> [u8; 256] {
+    let mut hist = [0u32; 256];
+    for v in values { hist[v as usize] += 1; }
+    let total: u32 = hist.iter().sum();
+    let mut map = [0u8; 256];
+    if total == 0 { for (i, m) in map.iter_mut().enumerate() { *m = i as u8; } return map; }
+    if let Some(clip) = clip {
+        let limit = ((clip * total as f32 / 256.0).ceil() as u32).max(1);
+        let excess: u32 = hist.iter().map(|&c| c.saturating_sub(limit)).sum();
+        let (share, rest) = (excess / 256, (excess % 256) as usize);
+        for (i, c) in hist.iter_mut().enumerate() { *c = (*c).min(limit) + share + (i < rest) as u32; }
+    }
+    let cdf_min = hist.iter().copied().find(|&c| c > 0).unwrap_or(0);
+    let mut acc = 0u32;
+    for (i, c) in hist.iter().enumerate() {
+        acc += c;
+        map[i] = if total > cdf_min { ((acc.saturating_sub(cdf_min)) as f32 * 255.0 / (total - cdf_min) as f32).round().clamp(0.0, 255.0) as u8 } else { i as u8 };
+    }
+    map
+}
+
+/// Moves `moving` so its distance from `anchor` is an allowed length without leaving `0..=limit`.
+pub(super) fn snap_edge(anchor: f32, moving: f32, snap: DimensionSnap, limit: f32) -> f32 {
+    let len = (moving - anchor).abs() as u32;
+    if moving >= anchor { anchor + snap.snap(len, (limit - anchor).max(0.0) as u32) as f32 }
+    else { anchor - snap.snap(len, anchor.max(0.0) as u32) as f32 }
+}
+
+/// Output size for "Limit longest side": fitted inside `max_dim` with the aspect kept, then snapped.
+pub(super) fn export_size(w: u32, h: u32, max_dim: u32, snap: DimensionSnap) -> (u32, u32) {
+    let (w, h) = if w.max(h) > max_dim {
+        let scale = max_dim as f64 / w.max(h) as f64;
+        (((w as f64 * scale).round() as u32).max(1), ((h as f64 * scale).round() as u32).max(1))
+    } else { (w, h) };
+    (snap.snap(w, max_dim), snap.snap(h, max_dim))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::ie_main::{BrushSettings, ColorHistory, HistoryColor, ToolMemory, fit_zoom, check_canvas_size, MIN_ZOOM, MAX_ZOOM};
+    use super::super::ie_helpers::within_tolerance;
+    use crate::modules::EditorModule;
+
+    #[test]
+    fn clahe_tiles_meet_without_seams() {
+        let (w, h) = (128u32, 96u32);
+        let mut buf = ImageBuffer::from_fn(w, h, |x, y| {
+            let v = if x < w / 2 { 40 + (x + y) * 30 / (w + h) } else { 150 + (x + y) * 90 / (w + h) };
+            Rgba([v as u8, v as u8, v as u8, 255])
+        });
+        let src = buf.clone();
+        equalize(&mut buf, EqualizeMode::Clahe, 4, 2.0, &|_| {});
+        let tile = w / 4;
+        let step = |img: &image::RgbaImage, x: u32, y: u32| (img.get_pixel(x + 1, y)[0] as i32 - img.get_pixel(x, y)[0] as i32).abs();
+        for y in 0..h {
+            for x in [tile - 1, 3 * tile - 1] {
+                assert!(step(&buf, x, y) <= 4, "seam at x={} y={} jumps {}", x + 1, y, step(&buf, x, y));
+            }
+            assert!(step(&buf, w / 2 - 1, y) >= step(&src, w / 2 - 1, y) / 2, "the real edge at the middle must survive");
+        }
+        for x in 0..w {
+            for y in [h / 4 - 1, h / 2 - 1, 3 * h / 4 - 1] {
+                let d = (buf.get_pixel(x, y + 1)[0] as i32 - buf.get_pixel(x, y)[0] as i32).abs();
+                assert!(d <= 4, "seam at y={} x={} jumps {}", y + 1, x, d);
+            }
+        }
+    }
+
+    fn transparent_editor() -> ImageEditor {
+        let mut ed = ImageEditor::new();
+        ed.image = Some(DynamicImage::ImageRgba8(ImageBuffer::from_pixel(96, 64, Rgba([0, 0, 0, 0]))));
+        ed.color = egui::Color32::WHITE;
+        ed
+    }
+
+    fn white_text() -> TextLayer {
+        TextLayer {
+            id: 1, content: "Halo".into(), img_x: 8.0, img_y: 8.0, font_size: 36.0,
+            box_width: None, box_height: None, rotation: 0.0, color: egui::Color32::WHITE,
+            bold: false, italic: false, underline: true, font_name: "Ubuntu".into(),
+            rendered_height: 0.0, cached_lines: Vec::new(), direction: TextDirection::Ltr, tab_width: 4, font_substitute: None, canvas_hidden: false,
+        }
+    }
+
+    fn assert_no_dark_fringe(img: &DynamicImage, what: &str) {
+        let buf = img.to_rgba8();
+        assert!(buf.pixels().any(|p| p[3] > 0 && p[3] < 255), "{what}: expected anti-aliased edge pixels");
+        for (x, y, p) in buf.enumerate_pixels() {
+            if p[3] == 0 { continue; }
+            assert!(p[0] >= 254 && p[1] >= 254 && p[2] >= 254, "{what}: dark fringe {:?} at {x},{y}", p.0);
+        }
+    }
+
+    #[test]
+    fn locked_pixels_refuse_edits_but_keep_undo() {
+        let mut ed = transparent_editor();
+        ed.push_undo(); ed.apply_invert();
+        let inverted = ed.image.as_ref().unwrap().as_bytes().to_vec();
+        ed.toggle_pixel_lock();
+        ed.crop_state.start = Some((0.0, 0.0)); ed.crop_state.end = Some((10.0, 10.0));
+        ed.apply_crop_selection();
+        assert_eq!(ed.image.as_ref().unwrap().width(), 96);
+        assert!(ed.pending_notice.take().is_some());
+        ed.undo();
+        assert_ne!(ed.image.as_ref().unwrap().as_bytes(), &inverted[..]);
+    }
+
+    #[test]
+    fn placed_image_commits_into_pixels_as_one_undo_step_or_cancels() {
+        let mut ed = transparent_editor();
+        let sticker = DynamicImage::ImageRgba8(ImageBuffer::from_pixel(200, 100, Rgba([255, 0, 0, 255])));
+        ed.place_floating_image(sticker.clone());
+        let iid = ed.placing_image.unwrap().0;
+        assert_eq!((ed.image_layer_data[&iid].display_w, ed.image_layer_data[&iid].display_h), (96.0, 48.0));
+        ed.cancel_placed_image();
+        assert_eq!((ed.layers.len(), ed.history.undo_len(), ed.image_layer_data.len()), (1, 0, 0));
+        ed.place_floating_image(sticker);
+        let iid = ed.placing_image.unwrap().0;
+        ed.image_layer_data.get_mut(&iid).unwrap().canvas_y = 0.0;
+        ed.commit_placed_image();
+        assert_eq!((ed.layers.len(), ed.history.undo_len(), ed.placing_image), (1, 1, None));
+        let img = ed.image.as_ref().unwrap().to_rgba8();
+        assert_eq!((img.get_pixel(48, 20).0, img.get_pixel(48, 60).0), ([255, 0, 0, 255], [0, 0, 0, 0]));
+        ed.undo();
+        assert_eq!(ed.image.as_ref().unwrap().to_rgba8().get_pixel(48, 20).0, [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn pasted_clip_floats_centered_scaled_by_dpi_and_offset_in_its_own_document() {
+        let mut a = transparent_editor();
+        a.image = Some(DynamicImage::ImageRgba8(ImageBuffer::from_pixel(96, 64, Rgba([0, 0, 255, 255]))));
+        a.measure_dpi = 300.0;
+        a.selection = Some(Selection::new(96, 64, (0..96 * 64).map(|i| i % 96 < 40 && i / 96 < 30).collect()));
+        let clip = a.image_clip(false).map(|c| a.hold_clip(c)).unwrap();
+        assert_eq!((clip.image.dimensions(), clip.image.get_pixel(0, 0).0), ((40, 30), [0, 0, 255, 255]));
+
+        let mut b = transparent_editor();
+        b.measure_dpi = 150.0;
+        b.paste_image_clip(&clip);
+        let iid = b.placing_image.unwrap().0;
+        let ild = &b.image_layer_data[&iid];
+        assert_eq!((ild.display_w, ild.display_h, ild.canvas_x, ild.canvas_y), (20.0, 15.0, 38.0, 24.5));
+        b.cancel_placed_image();
+        assert_eq!((b.layers.len(), b.history.undo_len(), b.image_layer_data.len()), (1, 0, 0));
+        b.measure_dpi = 1200.0;
+        b.paste_image_clip(&clip);
+        let ild = &b.image_layer_data[&b.placing_image.unwrap().0];
+        assert!((ild.display_h - 64.0).abs() < 1e-3 && ild.display_w <= 96.0);
+
+        a.paste_image_clip(&clip);
+        let ild = &a.image_layer_data[&a.placing_image.unwrap().0];
+        assert_eq!((ild.display_w, ild.canvas_x, ild.canvas_y), (40.0, 28.0 + PASTE_OFFSET, 17.0 + PASTE_OFFSET));
+        a.commit_placed_image();
+        assert_eq!((a.layers.len(), a.history.undo_len(), a.placing_image), (1, 1, None));
+    }
+
+    #[test]
+    fn marquee_pixels_float_move_and_land_as_one_undo_step() {
+        let red = Rgba([255, 0, 0, 255]);
+        let mut ed = transparent_editor();
+        ed.image = Some(DynamicImage::ImageRgba8(ImageBuffer::from_fn(96, 64, |x, y| if x < 10 && y < 10 { red } else { Rgba([255, 255, 255, 255]) })));
+        let px = |ed: &ImageEditor, x, y| ed.image.as_ref().unwrap().get_pixel(x, y).0;
+        ed.select_marquee((10.4, 9.6), (-3.0, 0.0), SelectOp::Replace);
+        assert_eq!((ed.selection.as_ref().unwrap().count(), ed.selection.as_ref().unwrap().bounds()), (100, Some([0, 0, 10, 10])));
+
+        assert!(ed.lift_selection());
+        assert_eq!((px(&ed, 5, 5), ed.history.undo_len(), ed.selection.is_none()), ([0, 0, 0, 0], 1, true));
+        ed.commit_floating_selection();
+        assert_eq!((px(&ed, 5, 5), ed.history.undo_len()), (red.0, 0));
+
+        assert!(ed.lift_selection());
+        ed.selection_state.floating.as_mut().unwrap().offset = (30, 25);
+        ed.commit_floating_selection();
+        assert_eq!((px(&ed, 5, 5), px(&ed, 35, 30), px(&ed, 25, 20)), ([0, 0, 0, 0], red.0, [255, 255, 255, 255]));
+        assert_eq!((ed.history.undo_len(), ed.selection.as_ref().unwrap().bounds()), (1, Some([30, 25, 40, 35])));
+        ed.undo();
+        assert_eq!((px(&ed, 5, 5), px(&ed, 35, 30)), (red.0, [255, 255, 255, 255]));
+
+        let clip = Arc::new(ImageClip { image: ImageBuffer::from_pixel(4, 4, Rgba([0, 255, 0, 255])), dpi: 0.0 });
+        ed.own_clip = Arc::downgrade(&clip);
+        assert!(ed.paste_floating_pixels(&clip));
+        assert_eq!(ed.selection_state.floating.as_ref().unwrap().offset, (46 + PASTE_OFFSET as i32, 30 + PASTE_OFFSET as i32));
+        ed.undo();
+        assert!(ed.selection_state.floating.is_none() && ed.history.undo_len() == 0);
+
+        let mut empty = ImageEditor::new();
+        assert!(!empty.paste_floating_pixels(&clip));
+        empty.paste_image_clip(&clip);
+        assert!(empty.selection_state.floating.is_none() && empty.placing_image.is_none() && !empty.history.can_undo());
+    }
+
+    #[test]
+    fn a_copied_clip_reaches_the_next_editor_through_the_app_settings() {
+        let mut first = transparent_editor();
+        let clip = first.image_clip(true).map(|c| first.hold_clip(c)).unwrap();
+        let mut settings = crate::modules::EditorSettings::default();
+        assert!(first.take_settings_change(&mut settings));
+        let mut next = transparent_editor();
+        assert!(ImageClip::newest(next.settings.image_clip.clone(), None).is_none());
+        next.apply_settings(&settings);
+        let pasted = ImageClip::newest(next.settings.image_clip.clone(), None).unwrap();
+        assert!(Arc::ptr_eq(&pasted, &clip));
+        let (back, across) = (first.paste_placement(&pasted).unwrap(), next.paste_placement(&pasted).unwrap());
+        assert_eq!((back.0 - across.0, back.1 - across.1), (PASTE_OFFSET, PASTE_OFFSET), "only a paste back into the copying editor is nudged");
+    }
+
+    #[test]
+    fn tool_preferences_from_the_app_reach_the_live_editor_through_its_settings() {
+        let mut ed = ImageEditor::new();
+        ed.tool = Tool::Eraser;
+        ed.brush.size = 77.0;
+        ed.loupe_zoom = 3;
+        let mut settings = crate::modules::EditorSettings::default();
+        settings.image.remember_tools = false;
+        ed.apply_settings(&settings);
+        assert_eq!((ed.tool, ed.brush.size), (Tool::Eraser, 77.0), "a plain settings change leaves the tools alone");
+        assert!(!ed.capture_tool_memory().remember);
+        settings.image.reset_tools = true;
+        ed.apply_settings(&settings);
+        let defaults = ToolMemory { remember: false, ..ToolMemory::default() };
+        assert_eq!((ed.tool, ed.brush.size, ed.loupe_zoom), (defaults.tool, defaults.brush.size, defaults.loupe_zoom));
+        assert_eq!((ed.capture_tool_memory(), ed.tool_memory_changed_at), (defaults, None), "nothing is left for the debounced save to write back");
+        assert!(!ed.settings.reset_tools);
+    }
+
+    #[test]
+    fn rotating_undoes_pixels_and_text_layers_as_one_step() {
+        let mut ed = transparent_editor();
+        ed.text_layers.push(TextLayer { img_x: 10.0, img_y: 20.0, rotation: 15.0, ..white_text() });
+        let pose = |ed: &ImageEditor| (ed.image.as_ref().map(|i| i.dimensions()), ed.text_layers[0].img_x, ed.text_layers[0].img_y, ed.text_layers[0].rotation);
+        let before = pose(&ed);
+        ed.apply_rotate_cw();
+        let rotated = pose(&ed);
+        assert_eq!((rotated.0, rotated.3, ed.history.undo_len()), (Some((64, 96)), 105.0, 1));
+        ed.undo();
+        assert_eq!(pose(&ed), before);
+        assert!(!ed.history.can_undo());
+        ed.redo();
+        assert_eq!(pose(&ed), rotated);
+    }
+
+    #[test]
+    fn a_paste_replaces_the_untouched_starting_canvas_but_floats_over_an_edited_one() {
+        let clip = transparent_editor().image_clip(true).unwrap();
+        let mut ed = ImageEditor::new();
+        ed.new_image(800, 600);
+        ed.blank_canvas = Some(ed.undo_pushes);
+        assert!(ed.untouched_blank());
+        ed.paste_clip(&clip);
+        assert_eq!((ed.image.as_ref().unwrap().dimensions(), ed.placing_image), ((96, 64), None));
+
+        let mut ed = ImageEditor::new();
+        ed.new_image(800, 600);
+        ed.blank_canvas = Some(ed.undo_pushes);
+        ed.push_undo();
+        assert!(!ed.untouched_blank());
+        ed.paste_clip(&clip);
+        assert_eq!(ed.image.as_ref().unwrap().dimensions(), (800, 600));
+        assert!(ed.placing_image.is_some());
+    }
+
+    #[test]
+    fn whole_canvas_clip_ignores_the_selection_and_opens_as_a_new_canvas() {
+        let mut ed = transparent_editor();
+        ed.text_layers.push(white_text());
+        ed.ensure_layer_entry_for_text(1);
+        ed.measure_dpi = 144.0;
+        ed.select_marquee((0.0, 0.0), (8.0, 8.0), SelectOp::Replace);
+        assert_eq!(ed.image_clip(false).unwrap().image.dimensions(), (8, 8));
+        let whole = ed.image_clip(true).unwrap();
+        assert_eq!(whole.image.dimensions(), (96, 64));
+        assert!(whole.image.pixels().any(|p| p.0 == [255, 255, 255, 255]), "text layers are stamped into the copy");
+
+        let mut empty = ImageEditor::new();
+        empty.open_image_clip(&whole);
+        assert_eq!((empty.image.as_ref().map(|i| i.dimensions()), empty.measure_dpi, empty.dirty), (Some((96, 64)), 144.0, true));
+        assert_eq!(empty.image.as_ref().unwrap().to_rgba8().as_raw(), whole.image.as_raw());
+    }
+
+    #[test]
+    fn snapped_crops_move_the_dragged_edge_and_apply_exactly_what_is_shown() {
+        let (even, mod16) = (DimensionSnap::Even, DimensionSnap::Multiple(16));
+        assert_eq!((even.snap(1919, 4000), even.snap(1, 4000), mod16.snap(1085, 4000), mod16.snap(1085, 1087)), (1920, 2, 1088, 1072));
+        assert_eq!((even.snap_from(101, 100, 4000), even.snap_from(99, 100, 4000), DimensionSnap::None.snap(7, 5)), (102, 98, 5));
+        assert_eq!(export_size(4000, 2251, 1920, even), (1920, 1080));
+        assert_eq!(export_size(1000, 501, 1920, DimensionSnap::None), (1000, 501));
+        let mut ed = transparent_editor();
+        ed.dimension_snap = DimensionSnap::Multiple(4);
+        ed.crop_state = CropState { start: Some((10.4, 5.0)), end: Some((71.0, 44.6)) };
+        ed.snap_crop(Some(true), Some(false));
+        assert_eq!(ed.crop_rect(), Some([11, 5, 71, 45]));
+        assert_eq!((ed.crop_state.end.unwrap().0, ed.crop_state.start.unwrap().1), (71.0, 5.0));
+        ed.crop_state = CropState { start: Some((80.0, 60.0)), end: Some((2.0, 3.0)) };
+        ed.snap_crop(Some(false), Some(false));
+        assert_eq!(ed.crop_rect(), Some([0, 4, 80, 60]));
+        ed.set_crop_size(31, 57);
+        assert_eq!(ed.crop_rect(), Some([0, 4, 28, 64]));
+        let [x0, y0, x1, y1] = ed.crop_rect().unwrap();
+        ed.apply_crop();
+        assert_eq!(ed.image.as_ref().unwrap().dimensions(), (x1 - x0, y1 - y0));
+    }
+
+    #[test]
+    fn pinned_history_colors_survive_eviction_and_old_files_still_load() {
+        let old: ColorHistory = serde_json::from_str(r#"{"colors":[{"r":1,"g":2,"b":3,"a":255},{"r":9,"g":9,"b":9,"a":255}]}"#).unwrap();
+        assert_eq!((old.colors.len(), old.colors[0].uses, old.pinned.len(), old.limit), (2, 1, 0, 40));
+        let grey = |v: u8| RgbaColor { r: v, g: v, b: v, a: 255 };
+        let mut history = ColorHistory { limit: 10, ..Default::default() };
+        history.push(grey(0)); history.push(grey(0));
+        history.pinned.push(history.colors.pop_front().unwrap());
+        for v in 1..=30 { history.push(grey(v)); }
+        history.push(grey(0));
+        assert_eq!((history.colors.len(), history.pinned[0].uses), (10, 3));
+        assert_eq!(history.colors.front().unwrap().color, grey(30));
+        let reloaded: ColorHistory = serde_json::from_str(&serde_json::to_string(&history).unwrap()).unwrap();
+        assert_eq!((reloaded.pinned, reloaded.limit), (history.pinned, 10));
+    }
+
+    #[test]
+    fn history_over_its_limit_is_trimmed_before_it_is_written() {
+        let grey = |v: u8| HistoryColor { color: RgbaColor { r: v, g: v, b: v, a: 255 }, uses: 1 };
+        let mut history = ColorHistory { colors: (1..=12).map(grey).collect(), pinned: vec![grey(0)], limit: 12 };
+        history.limit = 10;
+        assert!(history.trim());
+        assert_eq!((history.colors.len(), history.colors.back().copied()), (10, Some(grey(10))));
+        assert!(!history.trim());
+        let stored: ColorHistory = serde_json::from_str(&serde_json::to_string(&history).unwrap()).unwrap();
+        assert_eq!(stored.colors.len(), 10);
+    }
+
+    #[test]
+    fn text_options_edit_the_selected_layer_without_touching_defaults() {
+        let mut ed = transparent_editor();
+        ed.color = egui::Color32::BLUE;
+        ed.text_layers.push(TextLayer { color: egui::Color32::RED, ..white_text() });
+        ed.edit_text_layer_at(1, egui::Pos2::ZERO, false);
+        assert_eq!((ed.text_style().color, ed.text_layers[0].color, ed.color), (egui::Color32::RED, egui::Color32::RED, egui::Color32::BLUE));
+        ed.set_text_style(TextStyle { font_size: 60.0, ..ed.text_style() });
+        assert_eq!((ed.text_layers[0].font_size, ed.text_font_size, ed.text_layers[0].color), (60.0, 24.0, egui::Color32::RED));
+        ed.set_default_text_style(ed.text_style());
+        ed.commit_or_discard_active_text();
+        assert_eq!((ed.text_font_size, ed.color, ed.text_underline), (60.0, egui::Color32::RED, true));
+        ed.set_text_style(TextStyle { bold: true, ..ed.text_style() });
+        assert!(ed.text_bold && !ed.text_layers[0].bold);
+    }
+
+    #[test]
+    fn hidden_text_layers_ignore_clicks_but_still_export() {
+        let mut ed = transparent_editor();
+        ed.text_layers.push(white_text());
+        ed.ensure_layer_entry_for_text(1);
+        ed.saved_hash = Some(ed.document_hash());
+        ed.canvas_rect = Some(egui::Rect::from_min_size(egui::Pos2::ZERO, egui::vec2(96.0, 64.0)));
+        let inside = ed.image_to_screen(10.0, 10.0);
+        assert_eq!(ed.hit_text_layer(inside), Some(1));
+        ed.edit_text_layer_at(1, inside, false);
+        ed.set_text_hidden(1, true);
+        assert_eq!((ed.hit_text_layer(inside), ed.selected_text, ed.hidden_text_count()), (None, None, 1));
+        assert!(ed.dirty);
+        assert!(ed.composite_layers(true).unwrap().to_rgba8().pixels().any(|p| p[3] > 0));
+        ed.set_text_hidden(1, false);
+        ed.toggle_hide_all_text();
+        assert_eq!((ed.hit_text_layer(inside), ed.hidden_text_count(), ed.dirty), (None, 1, true));
+        ed.toggle_hide_all_text();
+        assert_eq!((ed.hit_text_layer(inside), ed.dirty), (Some(1), false));
+    }
+
+    #[test]
+    fn export_panel_preselects_what_was_last_used_for_the_file_type() {
+        let mut ed = transparent_editor();
+        ed.export_memory = Default::default();
+        ed.export_presets.presets = vec![ExportPreset { name: "Web".into(), format: "AVIF".into(), ..ExportPreset::default() }];
+        ed.export_memory.by_extension.insert("jpg".into(), RememberedExport { format: "JPEG".into(), jpeg_quality: 85, avif_quality: 80, avif_speed: 4 });
+        ed.file_path = Some(PathBuf::from("/photos/beach.png"));
+        ed.open_export_panel();
+        assert_eq!((ed.export_format, ed.remembered_export().is_none()), (ExportFormat::Png, true));
+        ed.file_path = Some(PathBuf::from("/photos/beach.JPG"));
+        ed.open_export_panel();
+        assert_eq!((ed.export_format, ed.export_jpeg_quality, ed.filter_panel), (ExportFormat::Jpeg, 85, FilterPanel::Export));
+        assert_eq!(ed.remembered_export().map(|(ext, _)| ext).as_deref(), Some("jpg"));
+        ed.apply_export_preset(0);
+        ed.open_export_panel();
+        assert_eq!(ed.export_format, ExportFormat::Avif, "an explicit preset wins for the rest of the session");
+    }
+
+    #[test]
+    fn viewport_export_crops_to_the_visible_region() {
+        let mut ed = transparent_editor();
+        ed.canvas_rect = Some(egui::Rect::from_min_size(egui::Pos2::ZERO, egui::vec2(96.0, 64.0)));
+        assert!(!ed.viewport_crops_image());
+        ed.set_zoom(4.0);
+        assert_eq!(ed.visible_image_rect(), Some([36, 24, 60, 40]));
+        ed.pan = egui::vec2(40.0, 0.0);
+        assert_eq!(ed.visible_image_rect(), Some([26, 24, 50, 40]));
+        assert!(ed.viewport_crops_image());
+        ed.pan = egui::vec2(400.0, 0.0);
+        assert_eq!(ed.visible_image_rect(), None);
+        ed.pan = egui::vec2(40.0, 0.0);
+        if let Some(DynamicImage::ImageRgba8(img)) = &mut ed.image { img.put_pixel(26, 24, Rgba([255, 0, 0, 255])); }
+        ed.begin_viewport_export();
+        let mut v = ed.export_viewport.unwrap();
+        let native = crop_to_viewport(ed.image.as_ref().unwrap(), v);
+        assert_eq!((native.width(), native.height(), native.to_rgba8().get_pixel(0, 0).0), (24, 16, [255, 0, 0, 255]));
+        v.scaled = true;
+        let scaled = crop_to_viewport(ed.image.as_ref().unwrap(), v).to_rgba8();
+        assert_eq!((scaled.width(), scaled.height()), (96, 64));
+        assert_eq!((scaled.get_pixel(3, 3).0, scaled.get_pixel(4, 4).0[3]), ([255, 0, 0, 255], 0));
+    }
+
+    #[test]
+    fn deleted_text_layers_can_be_restored_with_their_styling() {
+        let mut ed = transparent_editor();
+        for id in 1..=12 {
+            ed.text_layers.push(TextLayer { id, content: format!("Line {id}\nmore"), img_x: id as f32, ..white_text() });
+            ed.ensure_layer_entry_for_text(id);
+        }
+        ed.next_text_id = 13;
+        ed.selected_text = Some(3);
+        ed.delete_text_layer(3);
+        assert!(ed.selected_text.is_none() && !ed.layers.iter().any(|l| l.linked_text_id == Some(3)));
+        for id in [1, 2, 4, 5, 6, 7, 8, 9, 10, 11, 12] { ed.delete_text_layer(id); }
+        assert_eq!(ed.deleted_text.len(), RECENTLY_DELETED_LIMIT);
+        assert_eq!((ed.deleted_text[0].preview(), ed.deleted_text.last().unwrap().id), ("Line 12".to_string(), 2));
+        ed.restore_deleted_text(8);
+        let restored = ed.text_layers.last().unwrap();
+        assert_eq!((restored.id, restored.content.as_str(), restored.img_x, restored.underline), (4, "Line 4\nmore", 4.0, true));
+        assert!(ed.layers.iter().any(|l| l.linked_text_id == Some(4)));
+        assert_eq!(ed.deleted_text.len(), RECENTLY_DELETED_LIMIT - 1);
+    }
+
+    #[test]
+    fn restoring_after_undoing_the_delete_does_not_duplicate_the_layer() {
+        let mut ed = transparent_editor();
+        ed.text_layers.push(TextLayer { id: 1, content: "Keep me".into(), ..white_text() });
+        ed.ensure_layer_entry_for_text(1);
+        ed.next_text_id = 2;
+        ed.delete_text_layer(1);
+        assert_eq!(ed.deleted_text.len(), 1);
+        ed.undo();
+        assert_eq!((ed.text_layers.len(), ed.deleted_text.len()), (1, 0));
+        ed.restore_deleted_text(0);
+        assert_eq!(ed.text_layers.len(), 1);
+        ed.delete_text_layer(1);
+        assert_eq!(ed.deleted_text.len(), 1);
+        ed.restore_deleted_text(0);
+        assert_eq!(ed.text_layers.iter().filter(|l| l.id == 1).count(), 1);
+        assert!(ed.deleted_text.is_empty());
+    }
+
+    #[test]
+    fn restoring_a_text_journal_rebuilds_layer_entries() {
+        let mut ed = transparent_editor();
+        ed.text_layers.push(white_text());
+        ed.ensure_layer_entry_for_text(1);
+        let mut moved = white_text();
+        moved.img_x = 40.0;
+        let added = TextLayer { id: 5, content: "Caption".into(), ..white_text() };
+        ed.journal_offer = Some(vec![moved, added]);
+        ed.restore_text_journal();
+        assert_eq!(ed.text_layers.iter().map(|t| (t.id, t.img_x)).collect::<Vec<_>>(), vec![(1, 40.0), (5, 8.0)]);
+        assert_eq!(ed.layers.iter().filter(|l| l.kind == LayerKind::Text).count(), 2);
+        assert!(ed.next_text_id > 5 && ed.dirty);
+        ed.undo();
+        assert_eq!(ed.text_layers.len(), 1);
+    }
+
+    fn assert_composites_cleanly(img: &DynamicImage, bg: [u8; 3]) {
+        for p in img.to_rgba8().pixels() {
+            let a = p[3] as f32 / 255.0;
+            for c in 0..3 {
+                let got = p[c] as f32 * a + bg[c] as f32 * (1.0 - a);
+                let want = 255.0 * a + bg[c] as f32 * (1.0 - a);
+                assert!((got - want).abs() <= 1.5, "pixel {:?} over {:?}: {got} vs {want}", p.0, bg);
+            }
+        }
+    }
+
+    #[test]
+    fn soft_brush_on_transparent_has_no_dark_fringe() {
+        let mut ed = transparent_editor();
+        ed.tool = Tool::Brush;
+        ed.perf.coarse = false;
+        ed.brush = BrushSettings { size: 24.0, softness: 1.0, flow: 0.35, ..BrushSettings::default() };
+        ed.stroke_points = vec![(16.0, 32.0), (48.0, 30.0), (80.0, 34.0)];
+        ed.apply_brush_stroke();
+        assert_no_dark_fringe(ed.image.as_ref().unwrap(), "soft brush");
+    }
+
+    #[test]
+    fn text_on_transparent_has_no_dark_fringe() {
+        let ed = transparent_editor();
+        let out = ed.stamp_single_text_layer(ed.image.as_ref().unwrap(), &white_text(), 1.0);
+        assert_no_dark_fringe(&out, "text");
+        let mut rotated = white_text();
+        rotated.rotation = 17.0;
+        assert_no_dark_fringe(&ed.stamp_single_text_layer(ed.image.as_ref().unwrap(), &rotated, 0.6), "rotated text");
+    }
+
+    #[test]
+    fn tabs_stamp_as_advances_to_the_next_stop() {
+        let ed = transparent_editor();
+        let base = ed.image.as_ref().unwrap();
+        let layer = |content: &str| TextLayer { content: content.into(), underline: false, font_size: 16.0, ..white_text() };
+        assert!(ed.stamp_single_text_layer(base, &layer("\t\t"), 1.0).to_rgba8().pixels().all(|p| p[3] == 0), "tabs should not draw glyphs");
+        assert_eq!(ed.stamp_single_text_layer(base, &layer("\tb"), 1.0), ed.stamp_single_text_layer(base, &layer("    b"), 1.0));
+    }
+
+    #[test]
+    fn a_text_layer_loses_a_whole_grapheme_per_backspace_or_delete() {
+        let mut ed = transparent_editor();
+        ed.text_layers.push(TextLayer { content: "ae\u{301}👩\u{200D}💻".into(), ..white_text() });
+        (ed.selected_text, ed.editing_text) = (Some(1), true);
+        let press = |ed: &mut ImageEditor, key: egui::Key| {
+            let event = egui::Event::Key { key, physical_key: None, pressed: true, repeat: false, modifiers: egui::Modifiers::NONE };
+            let _ = egui::Context::default().run(egui::RawInput { events: vec![event], ..Default::default() }, |ctx| ed.process_text_input(ctx));
+        };
+        ed.text_cursor = ed.text_layers[0].content.len();
+        press(&mut ed, egui::Key::Backspace);
+        assert_eq!(ed.text_layers[0].content, "ae\u{301}");
+        press(&mut ed, egui::Key::Backspace);
+        assert_eq!((ed.text_layers[0].content.as_str(), ed.text_cursor), ("a", 1));
+        ed.text_layers[0].content = "👩\u{200D}💻e\u{301}".into();
+        ed.text_cursor = 0;
+        press(&mut ed, egui::Key::Delete);
+        assert_eq!(ed.text_layers[0].content, "e\u{301}");
+        press(&mut ed, egui::Key::Delete);
+        assert_eq!(ed.text_layers[0].content, "");
+    }
+
+    #[test]
+    fn text_stamps_a_glyph_per_grapheme() {
+        let ed = transparent_editor();
+        let base = ed.image.as_ref().unwrap();
+        let stamp = |content: &str| ed.stamp_single_text_layer(base, &TextLayer { content: content.into(), underline: false, font_size: 16.0, ..white_text() }, 1.0).to_rgba8();
+        let out = stamp("🎉 done");
+        let inked = |x0: u32, x1: u32| (x0..x1).any(|x| (0..out.height()).any(|y| out.get_pixel(x, y)[3] > 0));
+        assert!(inked(8, 22), "the emoji should be drawn");
+        assert!(inked(30, 60), "the word after it should be drawn");
+        assert_eq!(stamp("🎉\u{FE0F} done"), out, "a variation selector draws nothing and takes no space");
+        assert_eq!(stamp("👩\u{200D}💻 done"), stamp("👩 done"), "a ZWJ sequence takes one cell");
+        let roboto = |content: &str| ed.stamp_single_text_layer(base, &TextLayer { content: content.into(), underline: false, font_size: 16.0, font_name: "Roboto".into(), ..white_text() }, 1.0).to_rgba8();
+        let (marked, plain) = (roboto("e\u{301} done"), roboto("e done"));
+        let diff: Vec<(u32, u32)> = marked.enumerate_pixels().filter(|(x, y, p)| plain.get_pixel(*x, *y) != *p).map(|(x, y, _)| (x, y)).collect();
+        assert!(!diff.is_empty() && diff.iter().all(|&(x, _)| (8..18).contains(&x)), "a combining mark draws over its base and takes no space: {diff:?}");
+    }
+
+    #[test]
+    fn newline_only_text_keeps_every_empty_line() {
+        let layer = TextLayer { content: "\n\n\n".into(), ..white_text() };
+        assert_eq!(layer.line_count(), 4);
+        assert_eq!(ImageEditor::text_layer_lines(&layer).len(), 4);
+        assert_eq!(ImageEditor::text_layer_line_ranges(&layer).len(), 4);
+    }
+
+    #[test]
+    fn data_uri_holds_the_same_bytes_as_the_file_export() {
+        use base64::Engine;
+        let mut ed = transparent_editor();
+        ed.text_layers.push(white_text());
+        ed.export_max_dim = Some(48);
+        ed.copy_as_data_uri(ExportFormat::Jpeg, true);
+        let text = ed.data_uri_job.take().unwrap().rx.recv().unwrap().unwrap();
+        let encoded = text.strip_prefix("![](data:image/jpeg;base64,").and_then(|t| t.strip_suffix(')')).unwrap();
+        let bytes = base64::engine::general_purpose::STANDARD.decode(encoded).unwrap();
+        let path = std::env::temp_dir().join(format!("ue_data_uri_{}.jpg", std::process::id()));
+        let composite = ed.fit_export_max_dim(ed.export_composite(None).unwrap());
+        export_image(&composite, &path, ExportFormat::Jpeg, ed.export_jpeg_quality, 6, 100.0, false, 80, 6).unwrap();
+        let written = std::fs::read(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(bytes, written);
+        let decoded = image::load_from_memory(&bytes).unwrap();
+        assert_eq!(decoded.width().max(decoded.height()), 48);
+        assert_eq!(data_uri(&[1, 2, 3], ExportFormat::Svg, false), "data:image/svg+xml;base64,AQID");
+    }
+
+    #[test]
+    fn exported_png_composites_over_red_and_black() {
+        let mut ed = transparent_editor();
+        ed.tool = Tool::Brush;
+        ed.brush = BrushSettings { size: 20.0, softness: 1.0, flow: 0.5, ..BrushSettings::default() };
+        ed.stroke_points = vec![(10.0, 50.0), (86.0, 50.0)];
+        ed.apply_brush_stroke();
+        ed.text_layers.push(white_text());
+        let composite = ed.composite_all_layers().unwrap();
+        let path = std::env::temp_dir().join(format!("ue_alpha_{}.png", std::process::id()));
+        export_image(&composite, &path, ExportFormat::Png, 90, 6, 90.0, false, 80, 6).unwrap();
+        let reloaded = image::open(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_no_dark_fringe(&reloaded, "exported png");
+        assert_composites_cleanly(&reloaded, [255, 0, 0]);
+        assert_composites_cleanly(&reloaded, [0, 0, 0]);
+    }
+
+    #[test]
+    fn missing_fonts_are_substituted_and_relinked_without_renaming() {
+        let mut ed = transparent_editor();
+        ed.text_layers = vec![TextLayer { font_name: "Futura PT".into(), ..white_text() }, TextLayer { id: 2, font_name: "Futura PT".into(), ..white_text() }, TextLayer { id: 3, ..white_text() }];
+        ed.resolve_text_fonts();
+        assert_eq!(ed.missing_fonts(), vec![("Futura PT".to_string(), 2)]);
+        assert_eq!(ed.text_layers[0].effective_font(), "Ubuntu");
+        assert!(ed.text_layers[2].font_substitute.is_none());
+        ed.apply_font_relinks(vec![("Futura PT".into(), "Roboto".into())]);
+        assert!(ed.missing_fonts().is_empty());
+        assert!(ed.text_layers[..2].iter().all(|t| t.font_name == "Futura PT" && t.effective_font() == "Roboto"));
+    }
+
+    fn solid(w: u32) -> DynamicImage { DynamicImage::ImageRgba8(ImageBuffer::from_pixel(w, 4, Rgba([9, 9, 9, 255]))) }
+
+    #[test]
+    fn variant_names_use_compact_scales() {
+        assert_eq!(variant_file_stem("{name}@{scale}x", "icon", 2.0), "icon@2x");
+        assert_eq!(variant_file_stem("{name}-{scale}", "icon", 1.5), "icon-1.5");
+        assert_eq!(variant_file_stem("", "icon", 0.75), "icon@0.75x");
+        let mut ed = transparent_editor();
+        ed.variant_custom = Some(2.001);
+        assert_eq!(ed.selected_variant_scales(), vec![1.0, 2.0, 3.0]);
+        assert_eq!(ed.variant_upscales(), vec![3.0]);
+    }
+
+    #[test]
+    fn overlapping_filter_jobs_keep_only_the_latest_result() {
+        let mut ed = transparent_editor();
+        let first = ed.start_filter_job("Test", false);
+        let second = ed.start_filter_job("Test", false);
+        assert!(first.cancelled() && !second.cancelled());
+        second.finish(solid(7));
+        first.finish(solid(5));
+        ed.check_filter_completion();
+        assert!(!ed.is_processing());
+        assert_eq!(ed.image.as_ref().map(|i| i.width()), Some(7));
+
+        let late = ed.start_filter_job("Test", false);
+        ed.check_filter_completion();
+        assert!(ed.is_processing());
+        let stale = ed.start_filter_job("Test", true);
+        ed.cancel_filter_job();
+        late.finish(solid(3));
+        stale.finish(solid(2));
+        ed.check_filter_completion();
+        assert_eq!(ed.image.as_ref().map(|i| i.width()), Some(7));
+    }
+
+    #[test]
+    fn worker_that_dies_without_a_result_ends_the_job() {
+        let mut ed = transparent_editor();
+        ed.push_undo();
+        let job = ed.start_filter_job("Test", false);
+        let _ = std::thread::spawn(move || { job.progress(0.5); panic!("filter crashed"); }).join();
+        ed.check_filter_completion();
+        assert!(!ed.is_processing() && !ed.history.can_undo());
+        assert!(ed.pending_error.take().is_some());
+        assert_eq!(ed.image.as_ref().map(|i| i.width()), Some(96));
+    }
+
+    #[test]
+    fn fill_tolerance_compares_each_channel_and_global_mode_ignores_connectivity() {
+        let grey = |v: u8| Rgba([v, v, v, 255]);
+        let source = ImageBuffer::from_fn(6, 4, |x, y| match (x, y) {
+            (1, 0) => grey(105), (2, _) => grey(0), (3, _) => Rgba([110, 100, 100, 255]), (4, _) => Rgba([100, 140, 100, 255]), _ => grey(100),
+        });
+        let filled = |tolerance: u32, global: bool| {
+            let mut ed = transparent_editor();
+            ed.image = Some(DynamicImage::ImageRgba8(source.clone()));
+            (ed.color, ed.fill_tolerance, ed.fill_global) = (egui::Color32::RED, tolerance, global);
+            ed.flood_fill(0, 0, None);
+            ed.image.as_ref().unwrap().to_rgba8().pixels().filter(|p| p.0 == [255, 0, 0, 255]).count()
+        };
+        let counts: Vec<usize> = [(0, false), (5, false), (30, false), (100, false), (0, true), (10, true), (40, true)].iter().map(|&(t, g)| filled(t, g)).collect();
+        assert_eq!(counts, [7, 8, 8, 24, 11, 16, 20]);
+        assert!(!within_tolerance([100, 100, 140, 255], [100, 100, 100, 255], 39));
+        assert!(within_tolerance([110, 110, 110, 245], [100, 100, 100, 255], 10));
+    }
+
+    #[test]
+    fn antialiased_fill_leaves_no_old_color_halo_around_a_soft_circle() {
+        let blue = [0, 0, 255, 255];
+        let source = ImageBuffer::from_fn(24, 24, |x, y| {
+            let coverage = (8.5 - ((x as f32 - 12.0).powi(2) + (y as f32 - 12.0).powi(2)).sqrt()).clamp(0.0, 1.0);
+            Rgba([0, 0, (255.0 * coverage).round() as u8, 255])
+        });
+        let halo = |antialias: bool| {
+            let mut ed = transparent_editor();
+            ed.image = Some(DynamicImage::ImageRgba8(source.clone()));
+            (ed.color, ed.fill_tolerance, ed.fill_antialias) = (egui::Color32::RED, 40, antialias);
+            ed.flood_fill(12, 12, None);
+            let out = ed.image.as_ref().unwrap().to_rgba8();
+            let red = |x: i32, y: i32| x >= 0 && y >= 0 && out.get_pixel_checked(x as u32, y as u32).is_some_and(|p| p.0 == [255, 0, 0, 255]);
+            out.enumerate_pixels().filter(|(x, y, p)| {
+                let (x, y) = (*x as i32, *y as i32);
+                p.0 != [255, 0, 0, 255] && (-1..=1).any(|dy| (-1..=1).any(|dx| red(x + dx, y + dy)))
+                    && p.0 == source.get_pixel(x as u32, y as u32).0 && within_tolerance(p.0, blue, 80)
+            }).count()
+        };
+        assert!(halo(false) > 0);
+        assert_eq!(halo(true), 0);
+
+        let mut ed = transparent_editor();
+        ed.image = Some(DynamicImage::ImageRgba8(ImageBuffer::from_vec(3, 1, vec![0, 0, 255, 255, 0, 0, 255, 0, 0, 0, 200, 255]).unwrap()));
+        (ed.color, ed.fill_tolerance) = (egui::Color32::RED, 128);
+        ed.flood_fill(0, 0, None);
+        let out = ed.image.as_ref().unwrap().to_rgba8();
+        assert_eq!((out.get_pixel(0, 0).0, out.get_pixel(1, 0).0), ([255, 0, 0, 255], [0, 0, 255, 0]));
+    }
+
+    #[test]
+    fn fill_preview_mask_is_reused_only_for_the_hovered_pixel_and_unchanged_pixels() {
+        let mut ed = transparent_editor();
+        if let Some(DynamicImage::ImageRgba8(b)) = &mut ed.image { for y in 0..64 { b.put_pixel(40, y, Rgba([255, 255, 255, 255])); } }
+        let buf = ed.image.as_ref().unwrap().to_rgba8();
+        let mask = fill_mask(&buf, 5, 5, ed.fill_tolerance, true, None, &AtomicBool::new(false)).unwrap();
+        assert_eq!((mask.x0, mask.y0, mask.w, mask.h), (0, 0, 40, 64));
+        assert!(fill_mask(&buf, 5, 5, ed.fill_tolerance, true, Some(std::time::Instant::now()), &AtomicBool::new(false)).is_none());
+        (ed.texture_dirty, ed.composite_dirty) = (false, false);
+        let key = (ed.active_layer_id, ed.pixel_revision, 5, 5, ed.fill_tolerance, false);
+        ed.fill_preview = Some(FillPreview { key, cancel: Arc::new(AtomicBool::new(false)), job: None, mask: Some(mask), texture: None });
+        assert!(ed.take_fill_preview_mask(6, 5).is_none() && ed.fill_preview.is_none());
+        let preview = |ed: &ImageEditor| Some(FillPreview { key, cancel: Arc::new(AtomicBool::new(false)), job: None, mask: fill_mask(&buf, 5, 5, ed.fill_tolerance, true, None, &AtomicBool::new(false)), texture: None });
+        ed.fill_preview = preview(&ed);
+        ed.texture_dirty = true;
+        assert!(ed.take_fill_preview_mask(5, 5).is_none(), "pixels changed since the mask was taken");
+        (ed.texture_dirty, ed.pixel_revision) = (false, ed.pixel_revision + 1);
+        ed.fill_preview = preview(&ed);
+        assert!(ed.take_fill_preview_mask(5, 5).is_none(), "the mask is from an older revision");
+        ed.pixel_revision -= 1;
+        ed.fill_preview = preview(&ed);
+        let mask = ed.take_fill_preview_mask(5, 5);
+        assert!(mask.is_some());
+        ed.color = egui::Color32::RED;
+        ed.flood_fill(5, 5, mask);
+        let out = ed.image.as_ref().unwrap().to_rgba8();
+        assert_eq!((out.get_pixel(0, 63).0, out.get_pixel(40, 0).0, out.get_pixel(50, 0).0), ([255, 0, 0, 255], [255, 255, 255, 255], [0, 0, 0, 0]));
+    }
+
+    #[test]
+    fn degenerate_canvases_fit_draw_crop_and_export_without_panicking() {
+        for (w, h) in [(1, 1), (1, 65536), (65536, 1)] {
+            let mut ed = ImageEditor::new();
+            ed.image = Some(DynamicImage::ImageRgba8(ImageBuffer::from_pixel(w, h, Rgba([0, 0, 0, 255]))));
+            ed.color = egui::Color32::WHITE;
+            ed.canvas_rect = Some(egui::Rect::from_min_size(egui::Pos2::ZERO, egui::vec2(800.0, 600.0)));
+            ed.fit_image();
+            assert!(ed.zoom.is_finite() && (MIN_ZOOM..=MAX_ZOOM).contains(&ed.zoom), "{w}x{h}: zoom {}", ed.zoom);
+            assert!(w.min(h) as f32 * ed.zoom >= 1.0, "{w}x{h}: short side vanishes at zoom {}", ed.zoom);
+            assert_eq!(ed.pan, egui::Vec2::ZERO);
+            let corner = ed.image_to_screen(w as f32, h as f32);
+            assert!(corner.x.is_finite() && corner.y.is_finite());
+
+            ed.tool = Tool::Brush;
+            ed.stroke_points = vec![(0.0, 0.0), (w as f32 / 2.0, h as f32 / 2.0), (w as f32, h as f32)];
+            ed.apply_brush_stroke();
+            assert_eq!(ed.image.as_ref().unwrap().dimensions(), (w, h));
+
+            ed.crop_state = CropState { start: Some((0.0, 0.0)), end: Some((w.min(2) as f32, h.min(2) as f32)) };
+            ed.apply_crop_selection();
+            let refused = w.min(h) == 1 && w.max(h) > 1;
+            assert_eq!(ed.pending_notice.take().is_some(), refused, "{w}x{h}: sliver crop");
+            if refused { assert_eq!(ed.image.as_ref().unwrap().dimensions(), (w, h)); }
+
+            let composite = ed.export_composite(None).unwrap();
+            assert!(encode_image(&composite, ExportFormat::Png, 90, 1, false, 80, 6).is_ok_and(|b| !b.is_empty()));
+        }
+
+        let huge = fit_zoom(20000, 20000, egui::vec2(800.0, 600.0));
+        assert!((huge - 0.03).abs() < 1e-4);
+        assert_eq!(fit_zoom(0, 10, egui::vec2(800.0, 600.0)), 1.0);
+        assert_eq!(fit_zoom(10, 10, egui::vec2(f32::NAN, 0.0)), 1.0);
+        let mut ed = transparent_editor();
+        ed.new_image(20000, 20000);
+        assert!(ed.pending_error.take().is_some_and(|e| e.to_string().contains("Settings")));
+        ed.new_image(0, 600);
+        assert!(ed.pending_error.take().is_some());
+        assert_eq!(ed.image.as_ref().unwrap().dimensions(), (96, 64));
+        (ed.resize_w, ed.resize_h) = (20000, 20000);
+        ed.apply_resize();
+        assert_eq!(ed.image.as_ref().unwrap().dimensions(), (96, 64));
+        assert!(check_canvas_size(16000, 16000, 256).is_ok() && check_canvas_size(16001, 16000, 256).is_err());
+        ed.set_zoom(f32::INFINITY);
+        assert!(ed.zoom.is_finite());
+    }
+
+    #[test]
+    fn the_channel_view_is_rebuilt_only_for_a_new_channel_or_new_pixels() {
+        let ctx = egui::Context::default();
+        let mut ed = transparent_editor();
+        ed.channel_view = Some(super::super::ie_main::ChannelView::Alpha);
+        let tex = ed.ensure_channel_view_texture(&ctx);
+        assert!(tex.is_some());
+        let image = ed.image.take();
+        assert_eq!(ed.ensure_channel_view_texture(&ctx), tex, "unchanged pixels reuse the texture");
+        ed.pixel_revision += 1;
+        assert_eq!(ed.ensure_channel_view_texture(&ctx), None, "a new revision rebuilds it");
+        ed.image = image;
+        ed.channel_view = Some(super::super::ie_main::ChannelView::Red);
+        assert_eq!(ed.ensure_channel_view_texture(&ctx), tex);
+    }
+
+    #[test]
+    fn the_loupe_reads_pixels_into_a_nearest_filtered_texture() {
+        let ctx = egui::Context::default();
+        let mut ed = transparent_editor();
+        if let Some(DynamicImage::ImageRgba8(b)) = &mut ed.image { b.put_pixel(0, 0, Rgba([255, 0, 0, 255])); }
+        let tex = ed.loupe_pixels(&ctx, -1, -1, 3);
+        let meta = ctx.tex_manager().read().meta(tex).cloned().unwrap();
+        assert_eq!((meta.options, meta.size), (egui::TextureOptions::NEAREST, [3, 3]));
+        assert_eq!(ed.loupe_texture.as_ref().unwrap().0, (-1, -1, 3, ed.pixel_revision));
+        ed.pixel_revision += 1;
+        assert_eq!(ed.loupe_pixels(&ctx, -1, -1, 3), tex);
+        assert_eq!(ed.loupe_texture.as_ref().unwrap().0.3, ed.pixel_revision);
+    }
+
+    #[test]
+    fn adjustments_apply_off_the_ui_thread_and_bake_as_one_undo_step() {
+        let ctx = egui::Context::default();
+        let mut ed = ImageEditor::new();
+        ed.image = Some(DynamicImage::ImageRgba8(ImageBuffer::from_pixel(8, 8, Rgba([100, 150, 200, 255]))));
+        ed.adjustments.push(super::super::ie_adjust::Adjustment::new(super::super::ie_adjust::AdjustKind::Levels { black: 100, white: 200, gamma: 1.0 }));
+        ed.refresh_adjustments();
+        assert_eq!(ed.composite_all_layers().unwrap().to_rgba8().get_pixel(0, 0).0, [0, 128, 255, 255], "output waits for the running task");
+        let undo_before = ed.history.undo_len();
+        ed.bake_adjustments();
+        let started = std::time::Instant::now();
+        while !ed.adjustments.is_empty() && started.elapsed() < std::time::Duration::from_secs(10) { ed.check_adjustments(&ctx); std::thread::sleep(std::time::Duration::from_millis(5)); }
+        assert!(ed.adjustments.is_empty(), "the bake lands once the task finishes");
+        assert_eq!(ed.history.undo_len(), undo_before + 1);
+        assert_eq!(ed.image.as_ref().unwrap().to_rgba8().get_pixel(0, 0).0, [0, 128, 255, 255]);
+        ed.undo();
+        assert_eq!((ed.adjustments.len(), ed.image.as_ref().unwrap().to_rgba8().get_pixel(0, 0).0), (1, [100, 150, 200, 255]));
+    }
+
+    #[test]
+    fn a_manual_zoom_keeps_the_centred_point_through_a_resize_and_the_zoom_mode_outlives_the_editor() {
+        let mut ed = ImageEditor::new();
+        ed.image = Some(DynamicImage::new_rgba8(400, 300));
+        ed.canvas_rect = Some(egui::Rect::from_min_size(egui::pos2(0.0, 40.0), egui::vec2(800.0, 600.0)));
+        ed.follow_canvas_resize();
+        ed.set_zoom(2.0);
+        ed.pan = egui::vec2(120.0, -80.0);
+        let centred = ed.screen_to_image_f32(ed.canvas_rect.unwrap().center());
+        ed.canvas_rect = Some(egui::Rect::from_min_size(egui::pos2(200.0, 40.0), egui::vec2(500.0, 900.0)));
+        ed.follow_canvas_resize();
+        assert_eq!(ed.screen_to_image_f32(ed.canvas_rect.unwrap().center()), centred);
+        assert_eq!(ed.zoom, 2.0);
+
+        let path = std::env::temp_dir().join(format!("ue_view_{}.png", std::process::id()));
+        image::RgbaImage::new(4, 4).save(&path).unwrap();
+        let mut ed = ImageEditor::load(path.clone()).unwrap();
+        ed.set_zoom(3.0);
+        ed.pan = egui::vec2(5.0, 6.0);
+        ed.unload();
+        let ed = ImageEditor::load(path.clone()).unwrap();
+        assert_eq!((ed.zoom_mode, ed.zoom, ed.pan, ed.fit_on_next_frame), (super::super::ie_main::ZoomMode::Manual, 3.0, egui::vec2(5.0, 6.0), false));
+        let mut ed = ed;
+        ed.canvas_rect = Some(egui::Rect::from_min_size(egui::Pos2::ZERO, egui::vec2(100.0, 100.0)));
+        ed.fit_image();
+        drop(ed);
+        assert!(!ImageEditor::load(path.clone()).unwrap().fit_on_next_frame, "dropping an editor leaves the saved view alone");
+        let mut ed = ImageEditor::load(path.clone()).unwrap();
+        ed.canvas_rect = Some(egui::Rect::from_min_size(egui::Pos2::ZERO, egui::vec2(100.0, 100.0)));
+        ed.fit_image();
+        ed.unload();
+        assert!(ImageEditor::load(path.clone()).unwrap().fit_on_next_frame);
+        assert!(crate::modules::helpers::config_file::config_dir().starts_with(std::env::temp_dir()), "tests keep their view state out of the user's config");
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn svg_export_keeps_the_layer_order_and_reads_back_as_xml() {
+        use base64::Engine;
+        use quick_xml::{Reader, events::Event};
+        let mut ed = transparent_editor();
+        ed.text_layers.push(TextLayer { content: "Halo & <co>".into(), ..white_text() });
+        ed.ensure_layer_entry_for_text(1);
+        ed.new_raster_layer();
+        if let Some(DynamicImage::ImageRgba8(img)) = ed.layer_images.get_mut(&ed.active_layer_id) { img.put_pixel(8, 8, Rgba([255, 0, 0, 255])); }
+        let svg = ed.svg_export(None).unwrap().render().unwrap();
+
+        let mut reader = Reader::from_str(&svg);
+        let (mut order, mut text, mut hrefs) = (Vec::new(), String::new(), Vec::new());
+        loop {
+            match reader.read_event().unwrap() {
+                Event::Start(e) | Event::Empty(e) => {
+                    order.push(String::from_utf8(e.name().as_ref().to_vec()).unwrap());
+                    if let Some(href) = e.try_get_attribute("xlink:href").unwrap() { hrefs.push(href.unescape_value().unwrap().into_owned()); }
+                }
+                Event::Text(t) => text.push_str(&t.decode().unwrap()),
+                Event::GeneralRef(r) => text.push_str(&quick_xml::escape::unescape(&format!("&{};", r.decode().unwrap())).unwrap()),
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+        assert_eq!(order, ["svg", "image", "text", "tspan", "image"]);
+        assert!(text.contains("Halo & <co>"));
+        let top = hrefs[1].strip_prefix("data:image/png;base64,").unwrap();
+        let top = image::load_from_memory(&base64::engine::general_purpose::STANDARD.decode(top).unwrap()).unwrap().to_rgba8();
+        assert_eq!((top.get_pixel(8, 8).0, top.get_pixel(9, 9).0[3]), ([255, 0, 0, 255], 0));
+
+        assert!(!ExportFormat::all().contains(&ExportFormat::Svg));
+        assert!(ExportFormat::with_svg().contains(&ExportFormat::Svg));
+        assert_eq!(ExportFormat::from_name("svg"), Some(ExportFormat::Svg));
+    }
+}
@@ -1,8 +1,21 @@
 use eframe::egui;
 use crate::style::{ColorPalette, ThemeMode, toolbar_action_btn, toolbar_toggle_btn};
+use crate::focus::Region;
 use crate::modules::helpers::image_export::ExportFormat;
-use super::ie_main::{ImageEditor, Tool, FilterPanel, TransformHandleSet, THandle, RgbaColor, CropState, TextDrag, HANDLE_HIT, BrushShape, BrushTextureMode, BrushPreset, SavedBrush, RetouchMode, LayerKind, BlendMode, TextLayer, ColorHistory, MAX_COLOR_FAVORITES, COLOR_FAV_HOTKEYS, ImageDrag};
-use super::ie_helpers::{rgb_to_hsv_f32, hsv_to_rgb_f32, crop_hit_handle, draw_crop_handles};
+use crate::modules::helpers::hsv_picker;
+use super::ie_main::{ImageEditor, Tool, FilterPanel, EqualizeMode, MIXER_IDENTITY, StrokePosition, TransformHandleSet, THandle, RgbaColor, CropState, TextDrag, HANDLE_HIT, BrushShape, BrushTextureMode, BrushPreset, SavedBrush, RetouchMode, LayerKind, BlendMode, TextLayer, TextDirection, TEXT_FONTS, DimensionSnap, FillMode, HistoryColor, COLOR_HISTORY_LIMITS, MAX_COLOR_FAVORITES, COLOR_FAV_HOTKEYS, VARIANT_SCALES, VARIANT_UPSCALE_WARN, BoxDrag, ImageSlice, Measurement, PerfStats, ADAPTIVE_FRAME_MS, ZoomMode};
+use super::ie_helpers::{layout_with_tabs, caret_blink, space_pan_next, rgb_to_hsv_f32, hsv_to_rgb_f32, hsv_to_hsl, hsl_to_hsv, color_harmonies, contrast_ratio, crop_hit_handle, draw_crop_handles, brush_footprint, textured_disc};
+use super::ie_adjust::{AdjustKind, Adjustment};
+use super::ie_filters as filters;
+use super::ie_shape::ShapeKind;
+use super::ie_session::{TimeLapseFormat, SESSION_MAX_DIM};
+use super::ie_tools::{format_scale, variant_file_stem, export_size};
+use super::ie_select::SelectOp;
+use super::ie_cursor::{bucket_paths, eyedropper_paths, draw_glyph, draw_crosshair, snap};
+use crate::modules::helpers::file_info::file_info_popover;
+use crate::modules::EditorModule;
+use crate::i18n::{self, tr, tr_args, tr_n};
+use crate::tasks;
 
 impl ImageEditor {
     pub(super) fn render_toolbar(&mut self, ui: &mut egui::Ui, theme: ThemeMode) {
@@ -31,11 +44,83 @@ impl ImageEditor {
                             self.tool_btn(ui, "Crop", Tool::Crop, Some("C"), theme);
                             self.tool_btn(ui, "Select/Pan", Tool::Pan, Some("P"), theme);
                             self.tool_btn(ui, "Retouch", Tool::Retouch, Some("R"), theme);
+                            self.tool_btn(ui, "Slice", Tool::Slice, Some("S"), theme);
+                            self.tool_btn(ui, "Measure", Tool::Measure, Some("M"), theme);
+                            self.tool_btn(ui, "Wand", Tool::Wand, Some("W"), theme);
+                            self.tool_btn(ui, "Line", Tool::Line, Some("L"), theme);
+                            self.tool_btn(ui, "Rectangle", Tool::Rectangle, Some("U"), theme);
+                            self.tool_btn(ui, "Ellipse", Tool::Ellipse, Some("O"), theme);
+                            self.tool_btn(ui, "Marquee", Tool::Select, Some("Q"), theme);
+                            ui.separator();
+                            let name = self.file_path.as_ref().and_then(|p| p.file_name()).map_or("Untitled".to_string(), |n| n.to_string_lossy().into_owned());
+                            let file_resp = ui.add(egui::Label::new(egui::RichText::new(name).size(12.5)).sense(egui::Sense::click()))
+                                .on_hover_text("File details").on_hover_cursor(egui::CursorIcon::PointingHand);
+                            let info_id = file_resp.id.with("file_info");
+                            let action = egui::Popup::from_toggle_button_response(&file_resp).id(info_id)
+                                .close_behavior(egui::PopupCloseBehavior::CloseOnClickOutside)
+                                .show(|ui: &mut egui::Ui| {
+                                    let action = file_info_popover(ui, self.file_path.as_deref(), self.dirty);
+                                    if let Some(source) = &self.source_info {
+                                        ui.separator();
+                                        ui.label(egui::RichText::new(format!("Source: {}", source.describe())).size(12.0));
+                                        if source.is_lossy() { ui.label(egui::RichText::new("Edited as 8-bit sRGB").size(11.5).color(ColorPalette::AMBER_500)); }
+                                    }
+                                    action
+                                })
+                                .and_then(|r| r.inner);
+                            if let Some(action) = action {
+                                egui::Popup::close_id(ui.ctx(), info_id);
+                                self.handle_file_info_action(action);
+                            }
                         });
                     });
             });
     }
 
+    fn text_layer_list(&mut self, ui: &mut egui::Ui, theme: ThemeMode) {
+        let hidden = self.hidden_text_count();
+        let label = if hidden > 0 { format!("Text Layers ({} hidden)", hidden) } else { "Text Layers".to_string() };
+        let btn = toolbar_toggle_btn(ui, egui::RichText::new(label).size(12.0), hidden > 0, theme).on_hover_text("Show or hide text layers on the canvas");
+        let (mut hide_all, mut toggle) = (false, None);
+        egui::Popup::from_toggle_button_response(&btn).close_behavior(egui::PopupCloseBehavior::CloseOnClickOutside).show(|ui: &mut egui::Ui| {
+            ui.set_min_width(200.0);
+            let mut all = self.hide_all_text;
+            if ui.checkbox(&mut all, egui::RichText::new("Hide all text layers").size(12.0)).on_hover_text("Shift+T").changed() { hide_all = true; }
+            ui.separator();
+            if self.text_layers.is_empty() { ui.label(egui::RichText::new("No text layers").size(12.0).weak()); }
+            for layer in self.text_layers.iter().rev() {
+                ui.horizontal(|ui: &mut egui::Ui| {
+                    let eye = if layer.canvas_hidden { "🚫" } else { "👁" };
+                    if ui.add_enabled(!self.hide_all_text, egui::Button::new(egui::RichText::new(eye).size(12.0)).frame(false))
+                        .on_hover_text(if layer.canvas_hidden { "Show on canvas" } else { "Hide on canvas" }).clicked() {
+                        toggle = Some((layer.id, !layer.canvas_hidden));
+                    }
+                    let text = egui::RichText::new(layer.preview()).size(12.0);
+                    ui.label(if self.text_shown(layer) { text } else { text.weak().italics() });
+                });
+            }
+        });
+        if hide_all { self.toggle_hide_all_text(); }
+        if let Some((id, hidden)) = toggle { self.set_text_hidden(id, hidden); }
+    }
+
+    fn deleted_text_list(&mut self, ui: &mut egui::Ui, theme: ThemeMode) {
+        if self.deleted_text.is_empty() { return; }
+        let btn = toolbar_action_btn(ui, egui::RichText::new(format!("Recently Deleted ({})", self.deleted_text.len())).size(12.0), theme)
+            .on_hover_text("Restore text layers deleted from this document");
+        let mut restore = None;
+        egui::Popup::from_toggle_button_response(&btn).close_behavior(egui::PopupCloseBehavior::CloseOnClickOutside).show(|ui: &mut egui::Ui| {
+            ui.set_min_width(220.0);
+            for (i, layer) in self.deleted_text.iter().enumerate() {
+                ui.horizontal(|ui: &mut egui::Ui| {
+                    if ui.small_button("Restore").clicked() { restore = Some(i); }
+                    ui.label(egui::RichText::new(layer.preview()).size(12.0));
+                });
+            }
+        });
+        if let Some(i) = restore { self.restore_deleted_text(i); }
+    }
+
     fn tool_btn(&mut self, ui: &mut egui::Ui, label: &str, tool: Tool, shortcut: Option<&str>, theme: ThemeMode) {
         let active: bool = self.tool == tool;
         let btn = toolbar_toggle_btn(ui, egui::RichText::new(label).size(12.0), active, theme);
@@ -68,6 +153,8 @@ impl ImageEditor {
                             ui.add(egui::Slider::new(&mut self.brush.size, 1.0..=200.0));
                             ui.label(egui::RichText::new("Opacity:").size(12.0).color(label_col));
                             ui.add(egui::Slider::new(&mut self.brush.opacity, 0.0..=1.0).custom_formatter(|v, _| format!("{:.0}%", v * 100.0)));
+                            ui.label(egui::RichText::new("Blend:").size(12.0).color(label_col));
+                            blend_mode_combo(ui, "brush_blend_bar", &mut self.brush.blend_mode, 90.0);
                             ui.separator();
 
                             let settings_active = self.filter_panel == FilterPanel::Brush;
@@ -83,55 +170,47 @@ impl ImageEditor {
                             cb.on_hover_text("When checked, erases pixels to transparent instead of white.\nUseful for removing image backgrounds.");
                         }
                         Tool::Text => {
+                            let mut style = self.text_style();
+                            let before = style.clone();
                             ui.label(egui::RichText::new("Font:").size(12.0).color(label_col));
                             egui::ComboBox::from_id_salt("text_font_pick")
-                                .selected_text(self.text_font_name.clone()).width(100.0)
+                                .selected_text(style.font_name.clone()).width(100.0)
                                 .show_ui(ui, |ui| {
-                                    for (name, label) in &[("Ubuntu", "Ubuntu"), ("Roboto", "Roboto"), ("GoogleSans", "Google Sans"), ("OpenSans", "Open Sans")] {
-                                        if ui.selectable_label(self.text_font_name == *name, *label).clicked() {
-                                            self.text_font_name = name.to_string();
-                                            if let Some(id) = self.selected_text {
-                                                if let Some(layer) = self.text_layers.iter_mut().find(|l: &&mut TextLayer| l.id == id) {
-                                                    layer.font_name = name.to_string();
-                                                }
-                                            }
-                                        }
+                                    for (name, label) in TEXT_FONTS {
+                                        if ui.selectable_label(style.font_name == *name, *label).clicked() { style.font_name = name.to_string(); }
                                     }
                                 });
                             ui.separator();
                             ui.label(egui::RichText::new("Size:").size(12.0).color(label_col));
-                            let mut fs: f32 = self.text_font_size;
-                            if ui.add(egui::DragValue::new(&mut fs).range(6.0..=400.0).speed(1.0)).changed() {
-                                self.text_font_size = fs;
-                                if let Some(id) = self.selected_text {
-                                    if let Some(layer) = self.text_layers.iter_mut().find(|l: &&mut TextLayer| l.id == id) { layer.font_size = fs; }
-                                }
-                            }
+                            ui.add(egui::DragValue::new(&mut style.font_size).range(6.0..=400.0).speed(1.0));
+                            ui.label(egui::RichText::new("Tab:").size(12.0).color(label_col));
+                            ui.add(egui::DragValue::new(&mut style.tab_width).range(1..=16).speed(0.1)).on_hover_text("Tab width in spaces");
                             ui.separator();
 
-                            if toolbar_toggle_btn(ui, egui::RichText::new("B").strong().size(13.0), self.text_bold, theme).clicked() {
-                                self.text_bold = !self.text_bold;
-                                if let Some(id) = self.selected_text {
-                                    if let Some(layer) = self.text_layers.iter_mut().find(|l: &&mut TextLayer| l.id == id) { layer.bold = self.text_bold; }
-                                }
-                            }
-                            if toolbar_toggle_btn(ui, egui::RichText::new("I").italics().size(13.0), self.text_italic, theme).clicked() {
-                                self.text_italic = !self.text_italic;
-                                if let Some(id) = self.selected_text {
-                                    if let Some(layer) = self.text_layers.iter_mut().find(|l: &&mut TextLayer| l.id == id) { layer.italic = self.text_italic; }
-                                }
-                            }
-                            if toolbar_toggle_btn(ui, egui::RichText::new("U").underline().size(13.0), self.text_underline, theme).clicked() {
-                                self.text_underline = !self.text_underline;
-                                if let Some(id) = self.selected_text {
-                                    if let Some(layer) = self.text_layers.iter_mut().find(|l: &&mut TextLayer| l.id == id) { layer.underline = self.text_underline; }
-                                }
-                            }
+                            if toolbar_toggle_btn(ui, egui::RichText::new("B").strong().size(13.0), style.bold, theme).clicked() { style.bold = !style.bold; }
+                            if toolbar_toggle_btn(ui, egui::RichText::new("I").italics().size(13.0), style.italic, theme).clicked() { style.italic = !style.italic; }
+                            if toolbar_toggle_btn(ui, egui::RichText::new("U").underline().size(13.0), style.underline, theme).clicked() { style.underline = !style.underline; }
+                            egui::color_picker::color_edit_button_srgba(ui, &mut style.color, egui::color_picker::Alpha::OnlyBlend)
+                                .on_hover_text(if self.selected_text.is_some() { "Color of the selected text" } else { "Color for new text" });
+                            ui.separator();
+                            egui::ComboBox::from_id_salt("text_direction_pick")
+                                .selected_text(style.direction.label()).width(100.0)
+                                .show_ui(ui, |ui| {
+                                    for dir in [TextDirection::Ltr, TextDirection::Rtl, TextDirection::Vertical] {
+                                        if ui.selectable_label(style.direction == dir, dir.label()).clicked() { style.direction = dir; }
+                                    }
+                                });
+                            if style != before { self.set_text_style(style.clone()); }
+                            ui.separator();
+                            self.text_layer_list(ui, theme);
+                            self.deleted_text_list(ui, theme);
 
                             if let Some(id) = self.selected_text {
-                                let cur_color = self.color;
-                                if let Some(layer) = self.text_layers.iter_mut().find(|l: &&mut TextLayer| l.id == id) {
-                                    if layer.color != cur_color { layer.color = cur_color; }
+                                if toolbar_action_btn(ui, egui::RichText::new("👁").size(13.0), theme).on_hover_text("Hide this layer on the canvas (it is still exported)").clicked() {
+                                    self.set_text_hidden(id, true);
+                                }
+                                if ui.add_enabled(style != self.default_text_style(), egui::Button::new(egui::RichText::new("Set as Default").size(12.0))).on_hover_text("Use this layer's styling for new text").clicked() {
+                                    self.set_default_text_style(style);
                                 }
                                 if let Some(layer) = self.text_layers.iter_mut().find(|l: &&mut TextLayer| l.id == id) {
                                     ui.separator();
@@ -139,10 +218,7 @@ impl ImageEditor {
                                     ui.add(egui::DragValue::new(&mut layer.rotation).speed(1.0).range(-360.0..=360.0).suffix("°")).on_hover_text("Rotation in degrees");
                                 }
                                 if ui.button("Deselect").clicked() { self.commit_or_discard_active_text(); }
-                                if ui.button("Delete").clicked() {
-                                    self.text_layers.retain(|l: &TextLayer| l.id != id);
-                                    self.selected_text = None; self.editing_text = false;
-                                }
+                                if ui.button("Delete").on_hover_text("Deleted layers can be restored from Recently Deleted").clicked() { self.delete_text_layer(id); }
                                 ui.separator();
                                 if toolbar_action_btn(ui, egui::RichText::new("Rasterize").size(12.0), theme).on_hover_text("Convert text layer to a raster layer").clicked() { self.rasterize_text_layer(); }
                             }
@@ -150,13 +226,14 @@ impl ImageEditor {
                         Tool::Pan => {
                             let has_img_sel = self.selected_image_layer.is_some();
                             let has_txt_sel = self.selected_text.is_some();
-                            let in_select = has_img_sel || has_txt_sel;
+                            let in_select = has_img_sel || has_txt_sel || self.selected_shape.is_some();
                             let mode_label = if in_select { "Mode: Select" } else { "Mode: Pan" };
                             ui.label(egui::RichText::new(mode_label).size(12.0).color(if in_select { ColorPalette::GREEN_400 } else { label_col }));
                             if in_select {
                                 ui.separator();
                                 if ui.button(egui::RichText::new("Deselect").size(12.0)).clicked() {
                                     self.selected_image_layer = None;
+                                    self.selected_shape = None;
                                     self.commit_or_discard_active_text();
                                 }
                             }
@@ -205,17 +282,62 @@ impl ImageEditor {
                                     if toolbar_action_btn(ui, egui::RichText::new("Fit").size(12.0), theme).on_hover_text("Fit image layer to canvas").clicked() { self.push_undo(); self.fit_image_layer_to_canvas(); }
                                     if toolbar_action_btn(ui, egui::RichText::new("1:1").size(12.0), theme).on_hover_text("Reset to native size").clicked() { self.push_undo(); self.reset_image_layer_size(); }
                                     if toolbar_action_btn(ui, egui::RichText::new("Rasterize").size(12.0), theme).on_hover_text("Merge image layer into a raster layer").clicked() { self.rasterize_image_layer(); }
+                                    ui.separator();
+                                    if let Some(idx) = self.layers.iter().position(|l| l.id == self.active_layer_id) {
+                                        ui.label(egui::RichText::new("Blend:").size(12.0).color(label_col));
+                                        let before = self.layers[idx].blend_mode;
+                                        blend_mode_combo(ui, "image_layer_blend", &mut self.layers[idx].blend_mode, 90.0);
+                                        if self.layers[idx].blend_mode != before { self.composite_dirty = true; self.dirty = true; }
+                                        let can_commit = idx > 0 && matches!(self.layers[idx - 1].kind, LayerKind::Background | LayerKind::Raster);
+                                        if ui.add_enabled(can_commit, egui::Button::new(egui::RichText::new("Commit").size(12.0))).on_hover_text("Stamp the image onto the layer below using this blend mode").clicked() { self.merge_down(); }
+                                    }
                                 }
+                            } else if self.selected_shape.is_some() {
+                                self.render_shape_options(ui, theme, label_col);
                             }
                         }
-                        Tool::Eyedropper | Tool::Fill => {}
+                        Tool::Fill => {
+                            egui::ComboBox::from_id_salt("fill_mode_pick").selected_text(egui::RichText::new(self.fill_mode.label()).size(12.0)).width(110.0).show_ui(ui, |ui| {
+                                for m in FillMode::ALL { ui.selectable_value(&mut self.fill_mode, m, m.label()); }
+                            }).response.on_hover_text("Gradients blend from the color to the secondary color along a line you drag from the region to fill");
+                            ui.separator();
+                            ui.label(egui::RichText::new("Tolerance:").size(12.0).color(label_col));
+                            ui.add(egui::Slider::new(&mut self.fill_tolerance, 0..=255)).on_hover_text("How different a pixel can be from the clicked one and still be filled.\nHover the canvas to preview the region.");
+                            ui.checkbox(&mut self.fill_global, egui::RichText::new("Global").size(12.0)).on_hover_text("Replace matching pixels anywhere in the image, not just the connected region");
+                            ui.checkbox(&mut self.fill_antialias, egui::RichText::new("Antialias").size(12.0)).on_hover_text("Blend the pixels bordering the fill toward the new color so anti-aliased edges keep no halo of the old one");
+                        }
+                        Tool::Wand => {
+                            ui.label(egui::RichText::new("Tolerance:").size(12.0).color(label_col));
+                            ui.add(egui::Slider::new(&mut self.wand_tolerance, 0..=255)).on_hover_text("How different a pixel can be from the clicked one and still be selected");
+                            ui.checkbox(&mut self.wand_contiguous, egui::RichText::new("Contiguous").size(12.0)).on_hover_text("Off selects matching pixels anywhere in the image");
+                            ui.separator();
+                            let readout = match &self.selection { Some(sel) => format!("{} px selected", sel.count()), None => "Click to select, Shift+click adds, Alt+click subtracts".to_string() };
+                            ui.label(egui::RichText::new(readout).size(12.0).color(label_col));
+                            if self.selection.is_some() {
+                                if ui.button(egui::RichText::new("Invert").size(12.0)).clicked() { self.invert_selection(); }
+                                if ui.button(egui::RichText::new("Deselect").size(12.0)).clicked() { self.selection = None; }
+                            }
+                        }
+                        Tool::Eyedropper => {}
                         Tool::Crop => {
+                            if dimension_snap_combo(ui, "crop_dimension_snap", &mut self.dimension_snap, label_col) { self.snap_crop(Some(false), Some(false)); }
+                            if let Some([x0, y0, x1, y1]) = self.crop_rect() {
+                                ui.separator();
+                                let (mut w, mut h) = (x1 - x0, y1 - y0);
+                                ui.label(egui::RichText::new("W").size(12.0).color(label_col));
+                                let wr = ui.add(egui::DragValue::new(&mut w).range(1..=u32::MAX));
+                                ui.label(egui::RichText::new("H").size(12.0).color(label_col));
+                                let hr = ui.add(egui::DragValue::new(&mut h).range(1..=u32::MAX));
+                                if wr.changed() || hr.changed() { self.set_crop_size(w, h); }
+                                let snap = self.dimension_snap;
+                                if !(snap.fits(x1 - x0) && snap.fits(y1 - y0)) {
+                                    ui.label(egui::RichText::new(format!("{} is not a multiple of {}", i18n::format_dims(x1 - x0, y1 - y0), snap.step())).size(12.0).color(ColorPalette::RED_500));
+                                }
+                            }
                             if self.crop_state.start.is_some() && self.crop_state.end.is_some() {
+                                ui.separator();
                                 let is_img_layer = self.image_layer_for_active().is_some();
-                                if ui.button("Apply Crop").clicked() {
-                                    if is_img_layer { self.apply_crop_to_image_layer(); }
-                                    else { self.push_undo(); self.apply_crop(); }
-                                }
+                                if ui.button("Apply Crop").clicked() { self.apply_crop_selection(); }
                                 if ui.button("Cancel").clicked() { self.crop_state = CropState::default(); }
                                 if is_img_layer {
                                     ui.separator();
@@ -223,6 +345,59 @@ impl ImageEditor {
                                 }
                             }
                         }
+                        Tool::Slice => {
+                            ui.label(egui::RichText::new(i18n::tr_n("{n} slice", "{n} slices", self.slices.len())).size(12.0).color(label_col));
+                            ui.separator();
+                            ui.label(egui::RichText::new("Filename:").size(12.0).color(label_col));
+                            ui.add(egui::TextEdit::singleline(&mut self.slice_pattern).desired_width(110.0))
+                                .on_hover_text("Tokens: {name} {index} {w} {h}\nFormat and quality come from the Export settings");
+                            ui.label(egui::RichText::new(format!(".{}", self.export_format.extension())).size(12.0).color(label_col));
+                            let export_clicked = ui.add_enabled(!self.slices.is_empty(), egui::Button::new(egui::RichText::new("Export Slices...").size(12.0))).clicked();
+                            if let Some(dir) = export_clicked.then(|| rfd::FileDialog::new().pick_folder()).flatten() {
+                                match self.export_slices(&dir) {
+                                    Ok(n) => self.slice_message = Some(i18n::tr_n("Exported {n} slice", "Exported {n} slices", n)),
+                                    Err(e) => { self.slice_message = None; self.pending_error = Some(e); }
+                                }
+                            }
+                            if let Some(msg) = &self.slice_message {
+                                ui.separator();
+                                ui.label(egui::RichText::new(msg).size(12.0).color(ColorPalette::AMBER_500));
+                            }
+                        }
+                        Tool::Select => {
+                            let floating = self.selection_state.floating.is_some();
+                            let readout = match &self.selection {
+                                _ if floating => "Drag to move the pixels. Enter or a click outside places them, Esc puts them back".to_string(),
+                                Some(sel) => format!("{} px selected, drag inside to move them", sel.count()),
+                                None => "Drag to select, Shift adds, Alt subtracts".to_string(),
+                            };
+                            ui.label(egui::RichText::new(readout).size(12.0).color(label_col));
+                            if floating {
+                                if ui.button(egui::RichText::new("Place").size(12.0)).clicked() { self.commit_floating_selection(); }
+                                if ui.button(egui::RichText::new("Cancel").size(12.0)).clicked() { self.cancel_floating_selection(); }
+                            } else if self.selection.is_some() {
+                                if ui.button(egui::RichText::new("Invert").size(12.0)).clicked() { self.invert_selection(); }
+                                if ui.button(egui::RichText::new("Deselect").size(12.0)).clicked() { self.selection = None; }
+                            }
+                        }
+                        Tool::Line | Tool::Rectangle | Tool::Ellipse => {
+                            ui.label(egui::RichText::new("Width:").size(12.0).color(label_col));
+                            ui.add(egui::Slider::new(&mut self.shape_tool_width, 0.5..=100.0).suffix(" px"));
+                            if self.tool != Tool::Line {
+                                ui.checkbox(&mut self.shape_tool_filled, egui::RichText::new("Filled").size(12.0)).on_hover_text("Fill the inside with the color as well");
+                            }
+                            ui.separator();
+                            ui.label(egui::RichText::new("Shift: constrain to 45° lines, squares and circles").size(12.0).color(label_col));
+                        }
+                        Tool::Measure => {
+                            let readout = self.measure_active.or(self.measurements.last().copied()).map(|m| m.summary(self.measure_dpi));
+                            ui.label(egui::RichText::new(readout.unwrap_or_else(|| "Drag to measure, click again for an angle".to_string())).size(12.0).color(label_col));
+                            ui.separator();
+                            ui.label(egui::RichText::new("DPI:").size(12.0).color(label_col));
+                            ui.add(egui::DragValue::new(&mut self.measure_dpi).range(0.0..=2400.0).speed(1.0)).on_hover_text("Set to show physical units, 0 for pixels only");
+                            ui.separator();
+                            if ui.add_enabled(self.measure_active.is_some() || !self.measurements.is_empty(), egui::Button::new(egui::RichText::new("Clear").size(12.0))).clicked() { self.clear_measurements(); }
+                        }
                         Tool::Retouch => {
                             egui::ScrollArea::horizontal()
                                 .auto_shrink([false, true])
@@ -303,14 +478,45 @@ impl ImageEditor {
                         }
                     }
                     ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui: &mut egui::Ui| {
-                        if self.tool != Tool::Retouch && self.tool != Tool::Pan {
-                            if ui.add(egui::Button::new("").fill(self.color).min_size(egui::vec2(28.0, 28.0))).clicked() { self.show_color_picker = !self.show_color_picker; }
-                            ui.label(egui::RichText::new("Color:").size(12.0).color(label_col));
+                        let lock_tip = if self.pixels_locked { "Unlock pixels" } else { "Lock pixels (text layers stay editable)" };
+                        let lock = toolbar_toggle_btn(ui, egui::RichText::new(if self.pixels_locked { "🔒" } else { "🔓" }).size(13.0), self.pixels_locked, theme).on_hover_text(lock_tip);
+                        a11y_label(&lock, egui::WidgetType::Button, lock_tip);
+                        if lock.clicked() { self.toggle_pixel_lock(); }
+                        ui.separator();
+                        if self.session.recording {
+                            let rec = ui.label(egui::RichText::new(format!("REC {}", self.session.frames.len())).size(12.0).color(ColorPalette::RED_500));
+                            let rec = rec.on_hover_text("Recording session. Click to stop.").interact(egui::Sense::click());
+                            a11y_label(&rec, egui::WidgetType::Button, "Stop session recording");
+                            if rec.clicked() { self.toggle_session_recording(); }
+                            ui.separator();
+                        }
+                        if !matches!(self.tool, Tool::Retouch | Tool::Pan | Tool::Slice | Tool::Measure) {
+                            let swatch = ui.add(egui::Button::new("").fill(self.color).min_size(egui::vec2(28.0, 28.0))).on_hover_text("Open color picker");
+                            a11y_label(&swatch, egui::WidgetType::ColorButton, &format!("Brush color #{:02X}{:02X}{:02X}", self.color.r(), self.color.g(), self.color.b()));
+                            crate::tour::anchor(ui.ctx(), "ie_color_swatch", swatch.rect);
+                            if swatch.clicked() { self.show_color_picker = !self.show_color_picker; }
+                            if self.tool == Tool::Fill {
+                                let second = ui.add(egui::Button::new("").fill(self.secondary_color).min_size(egui::vec2(20.0, 20.0))).on_hover_text("Secondary color, where gradients end. Click to swap with the color.");
+                                a11y_label(&second, egui::WidgetType::ColorButton, &format!("Secondary color #{:02X}{:02X}{:02X}", self.secondary_color.r(), self.secondary_color.g(), self.secondary_color.b()));
+                                if second.clicked() { std::mem::swap(&mut self.color, &mut self.secondary_color); self.hex_input = RgbaColor::from_egui(self.color).to_hex(); }
+                            }
+                            ui.label(egui::RichText::new(tr("Color:")).size(12.0).color(label_col));
 
                             if let Some(img) = &self.image {
-                                ui.label(egui::RichText::new(format!("{}x{}", img.width(), img.height())).size(12.0).color(label_col));
-                                ui.label(egui::RichText::new(format!("{:.0}%", self.zoom * 100.0)).size(12.0).color(label_col));
-                                ui.label(egui::RichText::new("Zoom:").size(12.0).color(label_col));
+                                let dims = i18n::format_dims(img.width(), img.height());
+                                let dims = if self.pixels_locked { format!("🔒 {}", dims) } else { dims };
+                                let dims = ui.label(egui::RichText::new(dims).size(12.0).color(label_col));
+                                if self.pixels_locked { dims.on_hover_text("Pixels are locked"); }
+                                let zoom_label = if self.zoom_mode == ZoomMode::Fit { format!("{} · {}", tr("Fit"), i18n::format_percent(self.zoom as f64)) } else { i18n::format_percent(self.zoom as f64) };
+                                ui.menu_button(egui::RichText::new(zoom_label).size(12.0).color(label_col), |ui| {
+                                    if ui.selectable_label(self.zoom_mode == ZoomMode::Fit, tr("Fit to Window")).clicked() { self.fit_image(); ui.close(); }
+                                    ui.separator();
+                                    for z in [0.25f32, 0.5, 1.0, 2.0, 4.0] {
+                                        let on = self.zoom_mode == ZoomMode::Manual && (self.zoom - z).abs() < 1e-4;
+                                        if ui.selectable_label(on, i18n::format_percent(z as f64)).clicked() { self.set_zoom(z); ui.close(); }
+                                    }
+                                });
+                                ui.label(egui::RichText::new(tr("Zoom:")).size(12.0).color(label_col));
                             }
                         }
                     });
@@ -318,6 +524,39 @@ impl ImageEditor {
             });
     }
 
+    fn render_shape_options(&mut self, ui: &mut egui::Ui, theme: ThemeMode, label_col: egui::Color32) {
+        let primary = RgbaColor::from_egui(self.color);
+        let Some(shape) = self.selected_shape.and_then(|id| self.shape_layers.iter_mut().find(|s| s.id == id)) else { return };
+        let before = shape.clone();
+        ui.separator();
+        egui::ComboBox::from_id_salt("shape_kind_pick").selected_text(shape.kind.label()).width(90.0).show_ui(ui, |ui| {
+            for kind in ShapeKind::ALL { ui.selectable_value(&mut shape.kind, kind, kind.label()); }
+        });
+        ui.label(egui::RichText::new("Stroke:").size(12.0).color(label_col));
+        ui.add(egui::DragValue::new(&mut shape.stroke_width).range(0.0..=200.0).speed(0.5).suffix("px"));
+        let mut stroke = shape.stroke.to_egui();
+        if egui::color_picker::color_edit_button_srgba(ui, &mut stroke, egui::color_picker::Alpha::OnlyBlend).changed() { shape.stroke = RgbaColor::from_egui(stroke); }
+        if !shape.kind.is_open() {
+            let mut filled = shape.fill.is_some();
+            if ui.checkbox(&mut filled, egui::RichText::new("Fill").size(12.0).color(label_col)).changed() { shape.fill = filled.then_some(primary); }
+            if let Some(fill) = &mut shape.fill {
+                let mut c = fill.to_egui();
+                if egui::color_picker::color_edit_button_srgba(ui, &mut c, egui::color_picker::Alpha::OnlyBlend).changed() { *fill = RgbaColor::from_egui(c); }
+            }
+        }
+        if shape.kind == ShapeKind::Rect {
+            ui.label(egui::RichText::new("Radius:").size(12.0).color(label_col));
+            ui.add(egui::DragValue::new(&mut shape.corner_radius).range(0.0..=1000.0).speed(0.5).suffix("px"));
+        }
+        ui.separator();
+        ui.label(egui::RichText::new("Rot:").size(12.0).color(label_col));
+        ui.add(egui::DragValue::new(&mut shape.rotation).range(-360.0..=360.0).speed(0.5).suffix("°"));
+        if *shape != before { self.composite_dirty = true; self.dirty = true; }
+        ui.separator();
+        if toolbar_action_btn(ui, egui::RichText::new("Rasterize").size(12.0), theme).on_hover_text("Convert shape layer to a raster layer").clicked() { self.rasterize_shape_layer(); }
+        if ui.button(egui::RichText::new("Delete").size(12.0)).clicked() { self.delete_active_layer(); }
+    }
+
     pub(super) fn render_filter_panel(&mut self, ui: &mut egui::Ui, ctx: &egui::Context, theme: ThemeMode) {
         if self.filter_panel == FilterPanel::None {
             if self.filter_preview_active {
@@ -335,24 +574,32 @@ impl ImageEditor {
             FilterPanel::HueSaturation => "Hue / Saturation",
             FilterPanel::Blur => "Gaussian Blur",
             FilterPanel::Sharpen => "Sharpen",
+            FilterPanel::Equalize => "Equalize",
+            FilterPanel::Threshold => "Threshold",
+            FilterPanel::ChannelMixer => "Channel Mixer",
+            FilterPanel::Stroke => "Stroke Selection",
             FilterPanel::Resize => "Resize",
             FilterPanel::Export => "Export",
+            FilterPanel::TimeLapse => "Export Time-lapse",
+            FilterPanel::Adjustments => "Adjustment Stack",
             FilterPanel::Brush => return self.render_brush_panel(ui, ctx, theme),
             FilterPanel::None => "",
         };
 
         let canvas_origin: egui::Pos2 = ui.available_rect_before_wrap().min;
-        let modal_pos: egui::Pos2 = canvas_origin + egui::vec2(10.0, 10.0);
+        let modal_pos: egui::Pos2 = self.panel_positions.resolve(title, canvas_origin + egui::vec2(10.0, 10.0), ctx.content_rect());
         let win_resp: Option<egui::InnerResponse<Option<()>>> = egui::Window::new(title)
             .collapsible(false).resizable(false)
-            .fixed_pos(modal_pos)
+            .current_pos(modal_pos)
             .fixed_size(egui::vec2(380.0, 0.0))
             .frame(egui::Frame::new().fill(bg).stroke(egui::Stroke::new(1.5, border)).corner_radius(8.0).inner_margin(16.0))
             .show(ctx, |ui: &mut egui::Ui| {
                 ui.spacing_mut().slider_width = 250.0;
-                if self.is_processing {
-                    let progress_val: f32 = *self.filter_progress.lock().unwrap();
-                    ui.label(egui::RichText::new("Processing Filter...").size(13.0).color(text_col));
+                if let Some(job) = &self.filter_job {
+                    let (progress_val, stage, cancellable) = (job.task.fraction(), job.task.detail(), job.cancellable);
+                    let subject = job.dims.map_or(tr(job.task.name()), |(w, h)| format!("{} · {}", tr(job.task.name()), i18n::format_dims(w, h)));
+                    ui.label(egui::RichText::new(subject).size(14.0).strong().color(text_col));
+                    ui.label(egui::RichText::new(tr(stage.unwrap_or("Processing Filter..."))).size(13.0).color(text_col));
                     ui.add_space(8.0);
                     let (rect, _) = ui.allocate_exact_size(egui::vec2(ui.available_width().min(300.0), 28.0), egui::Sense::hover());
                     ui.painter().rect_filled(rect, 4.0, if matches!(theme, ThemeMode::Dark) { ColorPalette::ZINC_700 } else { ColorPalette::GRAY_200 });
@@ -361,9 +608,20 @@ impl ImageEditor {
                         4.0, ColorPalette::BLUE_500,
                     );
                     ui.painter().text(rect.center(), egui::Align2::CENTER_CENTER,
-                        format!("{:.0}%", progress_val * 100.0),
+                        i18n::format_percent(progress_val as f64),
                         egui::FontId::proportional(13.0), egui::Color32::WHITE,
                     );
+                    ui.add_space(6.0);
+                    let elapsed = tasks::format_elapsed(job.task.elapsed());
+                    let timing = if job.task.stalled() { tr_args("Elapsed {elapsed} · still working…", &[("elapsed", &elapsed)]) }
+                        else if let Some(eta) = job.task.eta() { tr_args("Elapsed {elapsed} · about {eta} left", &[("elapsed", &elapsed), ("eta", &tasks::format_elapsed(eta))]) }
+                        else { tr_args("Elapsed {elapsed}", &[("elapsed", &elapsed)]) };
+                    ui.label(egui::RichText::new(timing).size(12.0).color(label_col));
+                    ui.ctx().request_repaint_after(std::time::Duration::from_millis(250));
+                    if cancellable {
+                        ui.add_space(8.0);
+                        if ui.button("Cancel").clicked() { self.cancel_filter_job(); }
+                    }
                     return;
                 }
                 match self.filter_panel {
@@ -397,6 +655,7 @@ impl ImageEditor {
                                 }
                             }
                             FilterAction::Apply => {
+                                self.remember_filter(FilterPanel::BrightnessContrast);
                                 if self.filter_preview_active { self.accept_filter_preview(); } else { self.push_undo(); self.apply_brightness_contrast(); }
                                 self.brightness = 0.0; self.contrast = 0.0; self.filter_panel = FilterPanel::None;
                             }
@@ -437,6 +696,7 @@ impl ImageEditor {
                                 }
                             }
                             FilterAction::Apply => {
+                                self.remember_filter(FilterPanel::HueSaturation);
                                 if self.filter_preview_active { self.accept_filter_preview(); } else { self.push_undo(); self.apply_hue_saturation(); }
                                 self.hue = 0.0; self.saturation = 0.0; self.filter_panel = FilterPanel::None;
                             }
@@ -464,6 +724,7 @@ impl ImageEditor {
                                 }
                             }
                             FilterAction::Apply => {
+                                self.remember_filter(FilterPanel::Blur);
                                 if self.filter_preview_active { self.accept_filter_preview(); } else { self.push_undo(); self.apply_blur(); }
                                 self.blur_radius = 3.0; self.filter_panel = FilterPanel::None;
                             }
@@ -474,6 +735,138 @@ impl ImageEditor {
                             FilterAction::None => {}
                         }
                     }
+                    FilterPanel::Equalize => {
+                        ui.horizontal(|ui: &mut egui::Ui| {
+                            ui.label(egui::RichText::new("Mode:").size(12.0).color(label_col));
+                            ui.selectable_value(&mut self.equalize_mode, EqualizeMode::Global, "Global");
+                            ui.selectable_value(&mut self.equalize_mode, EqualizeMode::Clahe, "Adaptive (CLAHE)");
+                        });
+                        if self.equalize_mode == EqualizeMode::Clahe {
+                            ui.horizontal(|ui: &mut egui::Ui| { ui.label(egui::RichText::new("Tiles:").size(12.0).color(label_col)); ui.add(egui::Slider::new(&mut self.clahe_tile, 2..=32)); });
+                            ui.horizontal(|ui: &mut egui::Ui| { ui.label(egui::RichText::new("Clip Limit:").size(12.0).color(label_col)); ui.add(egui::Slider::new(&mut self.clahe_clip, 1.0..=8.0)); });
+                        }
+                        ui.add_space(4.0);
+                        match filter_action_row(ui, theme, self.filter_preview_active) {
+                            FilterAction::Preview => {
+                                if self.filter_preview_active { self.cancel_filter_preview(); }
+                                else {
+                                    self.filter_preview_snapshot = Some(self.take_undo_snapshot());
+                                    self.filter_preview_active = true;
+                                    self.processing_is_preview = true;
+                                    self.apply_equalize();
+                                }
+                            }
+                            FilterAction::Apply => {
+                                if self.filter_preview_active { self.accept_filter_preview(); } else { self.push_undo(); self.apply_equalize(); }
+                                self.filter_panel = FilterPanel::None;
+                            }
+                            FilterAction::Cancel => {
+                                if self.filter_preview_active { self.cancel_filter_preview(); }
+                                self.filter_panel = FilterPanel::None;
+                            }
+                            FilterAction::None => {}
+                        }
+                    }
+                    FilterPanel::Threshold => {
+                        if let Some(hist) = &self.threshold_hist { threshold_histogram(ui, hist, self.threshold_level, self.threshold_soft, theme); }
+                        ui.add_space(4.0);
+                        ui.horizontal(|ui: &mut egui::Ui| {
+                            ui.label(egui::RichText::new("Threshold:").size(12.0).color(label_col));
+                            ui.add(egui::Slider::new(&mut self.threshold_level, 0..=255));
+                        });
+                        ui.horizontal(|ui: &mut egui::Ui| {
+                            ui.label(egui::RichText::new("Soft Band:").size(12.0).color(label_col));
+                            ui.add(egui::Slider::new(&mut self.threshold_soft, 0..=128)).on_hover_text("Blend this many levels around the threshold into grey instead of a hard edge");
+                        });
+                        let auto = ui.add_enabled(self.threshold_hist.is_some(), egui::Button::new(egui::RichText::new("Auto (Otsu)").size(12.0)));
+                        if auto.on_hover_text("Pick the level that best separates the dark and light parts of the histogram").clicked()
+                            && let Some(hist) = &self.threshold_hist { self.threshold_level = filters::otsu(hist); }
+                        ui.add_space(4.0);
+                        match filter_action_row(ui, theme, self.filter_preview_active) {
+                            FilterAction::Preview => {
+                                if self.filter_preview_active { self.cancel_filter_preview(); }
+                                else {
+                                    self.filter_preview_snapshot = Some(self.take_undo_snapshot());
+                                    self.filter_preview_active = true;
+                                    self.processing_is_preview = true;
+                                    self.apply_threshold();
+                                }
+                            }
+                            FilterAction::Apply => {
+                                if self.filter_preview_active { self.accept_filter_preview(); } else { self.push_undo(); self.apply_threshold(); }
+                                self.filter_panel = FilterPanel::None;
+                            }
+                            FilterAction::Cancel => {
+                                if self.filter_preview_active { self.cancel_filter_preview(); }
+                                self.filter_panel = FilterPanel::None;
+                            }
+                            FilterAction::None => {}
+                        }
+                    }
+                    FilterPanel::ChannelMixer => {
+                        ui.horizontal(|ui: &mut egui::Ui| {
+                            ui.label(egui::RichText::new("Preset:").size(12.0).color(label_col));
+                            if ui.small_button("Identity").clicked() { self.mixer = MIXER_IDENTITY; }
+                            if ui.small_button("Swap R/B").clicked() { self.mixer = [[0.0, 0.0, 1.0, 0.0], [0.0, 1.0, 0.0, 0.0], [1.0, 0.0, 0.0, 0.0]]; }
+                            if ui.small_button("Sepia").clicked() { self.mixer = [[0.393, 0.769, 0.189, 0.0], [0.349, 0.686, 0.168, 0.0], [0.272, 0.534, 0.131, 0.0]]; }
+                            if ui.small_button("Black & White").clicked() { self.mixer = [[0.299, 0.587, 0.114, 0.0]; 3]; }
+                        });
+                        ui.add_space(4.0);
+                        egui::Grid::new("channel_mixer_grid").spacing([8.0, 4.0]).show(ui, |ui: &mut egui::Ui| {
+                            ui.label("");
+                            for h in ["Red", "Green", "Blue", "Offset"] { ui.label(egui::RichText::new(h).size(11.0).color(label_col)); }
+                            ui.end_row();
+                            for (row, name) in self.mixer.iter_mut().zip(["Red out", "Green out", "Blue out"]) {
+                                ui.label(egui::RichText::new(name).size(12.0).color(label_col));
+                                for w in row.iter_mut().take(3) { ui.add(egui::DragValue::new(w).speed(0.01).range(-2.0..=2.0).fixed_decimals(2)); }
+                                ui.add(egui::DragValue::new(&mut row[3]).speed(1.0).range(-255.0..=255.0).fixed_decimals(0));
+                                ui.end_row();
+                            }
+                        });
+                        ui.add_space(4.0);
+                        match filter_action_row(ui, theme, self.filter_preview_active) {
+                            FilterAction::Preview => {
+                                if self.filter_preview_active { self.cancel_filter_preview(); }
+                                else {
+                                    self.filter_preview_snapshot = Some(self.take_undo_snapshot());
+                                    self.filter_preview_active = true;
+                                    self.processing_is_preview = true;
+                                    self.apply_channel_mixer();
+                                }
+                            }
+                            FilterAction::Apply => {
+                                if self.filter_preview_active { self.accept_filter_preview(); } else { self.push_undo(); self.apply_channel_mixer(); }
+                                self.mixer = MIXER_IDENTITY; self.filter_panel = FilterPanel::None;
+                            }
+                            FilterAction::Cancel => {
+                                if self.filter_preview_active { self.cancel_filter_preview(); }
+                                self.mixer = MIXER_IDENTITY; self.filter_panel = FilterPanel::None;
+                            }
+                            FilterAction::None => {}
+                        }
+                    }
+                    FilterPanel::Stroke => {
+                        let region = if self.selection.is_some() { "Selection bounds" } else if self.crop_state.start.is_some() && self.crop_state.end.is_some() { "Crop rectangle" } else { "Whole image" };
+                        ui.label(egui::RichText::new(format!("Region: {}", region)).size(12.0).color(label_col));
+                        ui.horizontal(|ui: &mut egui::Ui| { ui.label(egui::RichText::new("Width:").size(12.0).color(label_col)); ui.add(egui::Slider::new(&mut self.stroke_width, 1.0..=100.0).suffix(" px")); });
+                        ui.horizontal(|ui: &mut egui::Ui| {
+                            ui.label(egui::RichText::new(tr("Color:")).size(12.0).color(label_col));
+                            egui::color_picker::color_edit_button_srgba(ui, &mut self.stroke_color, egui::color_picker::Alpha::OnlyBlend);
+                        });
+                        ui.horizontal(|ui: &mut egui::Ui| {
+                            ui.label(egui::RichText::new("Position:").size(12.0).color(label_col));
+                            ui.selectable_value(&mut self.stroke_position, StrokePosition::Inside, "Inside");
+                            ui.selectable_value(&mut self.stroke_position, StrokePosition::Center, "Center");
+                            ui.selectable_value(&mut self.stroke_position, StrokePosition::Outside, "Outside");
+                        });
+                        ui.horizontal(|ui: &mut egui::Ui| { ui.label(egui::RichText::new("Corner Radius:").size(12.0).color(label_col)); ui.add(egui::Slider::new(&mut self.stroke_corner_radius, 0.0..=200.0).suffix(" px")); });
+                        ui.checkbox(&mut self.stroke_new_layer, "Stroke onto a new layer");
+                        ui.add_space(4.0);
+                        ui.horizontal(|ui: &mut egui::Ui| {
+                            if ui.button("Apply").clicked() { self.apply_stroke(); self.filter_panel = FilterPanel::None; }
+                            if ui.button("Cancel").clicked() { self.filter_panel = FilterPanel::None; }
+                        });
+                    }
                     FilterPanel::Sharpen => {
                         ui.horizontal(|ui: &mut egui::Ui| { ui.label(egui::RichText::new("Amount:").size(12.0).color(label_col)); ui.add(egui::Slider::new(&mut self.sharpen_amount, 0.1..=1.5)); });
                         ui.add_space(4.0);
@@ -488,6 +881,7 @@ impl ImageEditor {
                                 }
                             }
                             FilterAction::Apply => {
+                                self.remember_filter(FilterPanel::Sharpen);
                                 if self.filter_preview_active { self.accept_filter_preview(); } else { self.push_undo(); self.apply_sharpen(); }
                                 self.sharpen_amount = 1.0; self.filter_panel = FilterPanel::None;
                             }
@@ -501,24 +895,37 @@ impl ImageEditor {
                     FilterPanel::Resize => {
                         ui.horizontal(|ui: &mut egui::Ui| {
                             ui.label(egui::RichText::new("Width:").size(12.0).color(label_col));
+                            let snap = self.dimension_snap;
                             let old_w: u32 = self.resize_w;
                             ui.add(egui::DragValue::new(&mut self.resize_w).range(1..=8192));
+                            if self.resize_w != old_w { self.resize_w = snap.snap_from(self.resize_w, old_w, 8192); }
                             if self.resize_locked && self.resize_w != old_w && old_w > 0 {
                                 let ratio: f64 = self.resize_w as f64 / old_w as f64;
-                                self.resize_h = (self.resize_h as f64 * ratio).max(1.0) as u32;
+                                self.resize_h = snap.snap((self.resize_h as f64 * ratio).max(1.0) as u32, 8192);
                             }
                             ui.label(egui::RichText::new("Height:").size(12.0).color(label_col));
                             let old_h: u32 = self.resize_h;
                             ui.add(egui::DragValue::new(&mut self.resize_h).range(1..=8192));
+                            if self.resize_h != old_h { self.resize_h = snap.snap_from(self.resize_h, old_h, 8192); }
                             if self.resize_locked && self.resize_h != old_h && old_h > 0 {
                                 let ratio: f64 = self.resize_h as f64 / old_h as f64;
-                                self.resize_w = (self.resize_w as f64 * ratio).max(1.0) as u32;
+                                self.resize_w = snap.snap((self.resize_w as f64 * ratio).max(1.0) as u32, 8192);
+                            }
+                        });
+                        ui.horizontal(|ui: &mut egui::Ui| {
+                            if dimension_snap_combo(ui, "resize_dimension_snap", &mut self.dimension_snap, label_col) {
+                                self.resize_w = self.dimension_snap.snap(self.resize_w, 8192);
+                                self.resize_h = self.dimension_snap.snap(self.resize_h, 8192);
                             }
                         });
                         ui.checkbox(&mut self.resize_locked,  "Lock Aspect Ratio");
                         ui.checkbox(&mut self.resize_stretch, "Stretch Image").on_hover_text("If unchecked, resizes canvas and pads with white/crops");
                         ui.horizontal(|ui: &mut egui::Ui| {
-                            if ui.button("Apply").clicked()  { self.push_undo(); self.apply_resize(); }
+                            if ui.button("Apply").clicked()  {
+                                self.resize_w = self.dimension_snap.snap(self.resize_w, 8192);
+                                self.resize_h = self.dimension_snap.snap(self.resize_h, 8192);
+                                if !self.refuse_canvas_size(self.resize_w, self.resize_h) { self.remember_filter(FilterPanel::Resize); self.push_undo(); self.apply_resize(); }
+                            }
                             if ui.button("Cancel").clicked() {
                                 if let Some(img) = &self.image { self.resize_w = img.width(); self.resize_h = img.height(); }
                                 self.filter_panel = FilterPanel::None;
@@ -526,9 +933,43 @@ impl ImageEditor {
                         });
                     }
                     FilterPanel::Export => {
-                        ui.label(egui::RichText::new("Format:").size(12.0).color(label_col));
+                        if let Some(v) = &mut self.export_viewport {
+                            let ((w, h), (sw, sh)) = (v.native_size(), v.screen_size());
+                            ui.label(egui::RichText::new(format!("Visible region: {} at ({}, {})", i18n::format_dims(w, h), v.rect[0], v.rect[1])).size(12.0).color(text_col));
+                            ui.horizontal(|ui: &mut egui::Ui| {
+                                ui.selectable_value(&mut v.scaled, false, egui::RichText::new(format!("Native ({})", i18n::format_dims(w, h))).size(12.0));
+                                ui.selectable_value(&mut v.scaled, true, egui::RichText::new(format!("As on screen ({})", i18n::format_dims(sw, sh))).size(12.0));
+                            });
+                            ui.separator();
+                        }
+                        let matching = self.matching_export_preset();
+                        ui.horizontal(|ui: &mut egui::Ui| {
+                            ui.label(egui::RichText::new("Preset:").size(12.0).color(label_col));
+                            let selected = matching.and_then(|i| self.export_presets.presets.get(i)).map_or("Custom".to_string(), |p| p.name.clone());
+                            let mut pick = None;
+                            egui::ComboBox::from_id_salt("export_preset_pick").selected_text(egui::RichText::new(selected).size(12.0)).width(140.0).show_ui(ui, |ui| {
+                                for (i, p) in self.export_presets.presets.iter().enumerate() {
+                                    if ui.selectable_label(matching == Some(i), &p.name).clicked() { pick = Some(i); }
+                                }
+                            });
+                            if let Some(i) = pick { self.apply_export_preset(i); }
+                            if let Some(i) = matching && ui.button(egui::RichText::new("Delete").size(12.0)).clicked() { self.delete_export_preset(i); }
+                        });
+                        ui.horizontal(|ui: &mut egui::Ui| {
+                            ui.add(egui::TextEdit::singleline(&mut self.export_preset_name).desired_width(140.0).hint_text("Preset name…"));
+                            if ui.add_enabled(!self.export_preset_name.trim().is_empty(), egui::Button::new(egui::RichText::new("Save as Preset").size(12.0))).clicked() { self.save_export_preset(); }
+                        });
+                        ui.separator();
+                        ui.horizontal(|ui: &mut egui::Ui| {
+                            ui.label(egui::RichText::new("Format:").size(12.0).color(label_col));
+                            let remembered = self.remembered_export().map(|(ext, _)| ext);
+                            if let Some(ext) = remembered {
+                                ui.label(egui::RichText::new(format!("(remembered for .{})", ext)).size(11.0).color(label_col).italics());
+                                if ui.small_button("Forget").on_hover_text("Stop pre-selecting this format for this file type").clicked() { self.forget_remembered_export(); }
+                            }
+                        });
                         ui.horizontal_wrapped(|ui: &mut egui::Ui| {
-                            for format in ExportFormat::all() {
+                            for format in ExportFormat::with_svg() {
                                 let is_selected: bool = self.export_format == format;
                                 let (bg_color, txt_color) = if is_selected {
                                     (ColorPalette::BLUE_600, egui::Color32::WHITE)
@@ -542,6 +983,9 @@ impl ImageEditor {
                                 if ui.add(button).clicked() { self.export_format = format; }
                             }
                         });
+                        if let Some(source) = self.source_info.as_ref().filter(|s| s.is_lossy()) {
+                            ui.label(egui::RichText::new(format!("Exports are 8-bit sRGB; the original was {}", source.describe())).size(11.0).color(ColorPalette::AMBER_500).italics());
+                        }
                         ui.add_space(8.0);
                         match self.export_format {
                             ExportFormat::Jpeg => {
@@ -573,22 +1017,436 @@ impl ImageEditor {
                             }
                             _ => {}
                         }
+                        if self.export_format != ExportFormat::Svg {
+                            ui.horizontal(|ui: &mut egui::Ui| {
+                                let mut limit = self.export_max_dim.is_some();
+                                if ui.checkbox(&mut limit, egui::RichText::new("Limit longest side").size(12.0).color(label_col)).changed() {
+                                    self.export_max_dim = limit.then(|| self.image.as_ref().map_or(1600, |img| img.width().max(img.height())));
+                                }
+                                if let Some(m) = &mut self.export_max_dim { ui.add(egui::DragValue::new(m).range(1..=16384).suffix("px")); }
+                            });
+                            if let Some(m) = self.export_max_dim {
+                                ui.horizontal(|ui: &mut egui::Ui| {
+                                    dimension_snap_combo(ui, "export_dimension_snap", &mut self.dimension_snap, label_col);
+                                    if let Some((w, h)) = self.image.as_ref().map(|img| export_size(img.width(), img.height(), m, self.dimension_snap)) {
+                                        ui.label(egui::RichText::new(format!("→ {}", i18n::format_dims(w, h))).size(12.0).color(label_col));
+                                    }
+                                });
+                            }
+                        }
                         ui.checkbox(&mut self.export_preserve_metadata, egui::RichText::new("Preserve metadata").size(12.0).color(label_col));
+                        let hidden = self.hidden_text_count();
+                        if hidden > 0 {
+                            ui.label(egui::RichText::new(format!("⚠ {} text layer{} hidden on the canvas but will still be exported", hidden, if hidden == 1 { " is" } else { "s are" }))
+                                .size(11.0).color(ColorPalette::AMBER_500));
+                        }
                         ui.add_space(4.0);
                         ui.horizontal(|ui: &mut egui::Ui| {
-                            if ui.button("Export").clicked() {
+                            if ui.add_enabled(self.session.exporting.is_none(), egui::Button::new("Export")).clicked() {
                                 match self.export_image_to_file() {
-                                    Ok(path) => { if let Some(cb) = &self.export_callback { cb(path); } }
-                                    Err(e) => { eprintln!("Export error: {}", e); }
+                                    Ok(path) => { self.remember_export(); if let Some(cb) = &self.export_callback { cb(path); } }
+                                    Err(e) => { if !e.is_cancelled() { self.pending_error = Some(e); } }
+                                }
+                            }
+                            let can_copy = self.export_viewport.is_none() && self.data_uri_job.is_none();
+                            if ui.add_enabled(can_copy, egui::Button::new("Copy as Data URI")).on_hover_text("Copy the export as a data: URI for inlining into HTML or Markdown").clicked() {
+                                self.copy_as_data_uri(self.export_format, false);
+                            }
+                            if ui.button("Cancel").clicked() { self.filter_panel = FilterPanel::None; self.export_viewport = None; }
+                        });
+                        if self.export_format != ExportFormat::Svg && self.export_viewport.is_none() {
+                            ui.add_space(4.0);
+                            egui::CollapsingHeader::new(egui::RichText::new("Export scaled variants").size(12.0).color(label_col))
+                                .id_salt("export_variants").show(ui, |ui| self.render_variant_export(ui, ctx, label_col));
+                        }
+                    }
+                    FilterPanel::TimeLapse => {
+                        ui.label(egui::RichText::new(format!("{} frames recorded ({:.1} MB)", self.session.frames.len(), self.session.memory_kb() as f32 / 1024.0)).size(12.0).color(text_col));
+                        ui.add_space(6.0);
+                        ui.horizontal(|ui: &mut egui::Ui| {
+                            ui.label(egui::RichText::new("Format:").size(12.0).color(label_col));
+                            ui.selectable_value(&mut self.session.format, TimeLapseFormat::Gif, "Animated GIF");
+                            ui.selectable_value(&mut self.session.format, TimeLapseFormat::PngSequence, "PNG Sequence");
+                        });
+                        ui.horizontal(|ui: &mut egui::Ui| {
+                            ui.label(egui::RichText::new("Frame Delay:").size(12.0).color(label_col));
+                            ui.add(egui::DragValue::new(&mut self.session.frame_delay_ms).range(10..=5000).speed(5.0).suffix(" ms"));
+                        });
+                        ui.horizontal(|ui: &mut egui::Ui| {
+                            ui.label(egui::RichText::new("Max Frame Size:").size(12.0).color(label_col));
+                            ui.add(egui::DragValue::new(&mut self.session.max_dim).range(64..=SESSION_MAX_DIM).speed(4.0).suffix("px"))
+                                .on_hover_text("Applies to frames captured from now on");
+                        });
+                        ui.add_space(4.0);
+                        ui.horizontal(|ui: &mut egui::Ui| {
+                            if ui.add_enabled(self.session.exporting.is_none(), egui::Button::new("Export")).clicked() {
+                                let name = self.file_path.as_ref().and_then(|p| p.file_stem()).and_then(|s| s.to_str()).unwrap_or("timelapse").to_string();
+                                let target = match self.session.format {
+                                    TimeLapseFormat::Gif => rfd::FileDialog::new().set_file_name(format!("{}_timelapse.gif", name)).add_filter("GIF", &["gif"]).save_file(),
+                                    TimeLapseFormat::PngSequence => rfd::FileDialog::new().pick_folder(),
+                                };
+                                if let Some(target) = target {
+                                    match self.session.start_export(target) {
+                                        Ok(()) => self.filter_panel = FilterPanel::None,
+                                        Err(e) => self.pending_error = Some(e),
+                                    }
                                 }
                             }
                             if ui.button("Cancel").clicked() { self.filter_panel = FilterPanel::None; }
                         });
                     }
-                    FilterPanel::None | FilterPanel::Brush => {}
-                }
+                    FilterPanel::Adjustments => self.render_adjustment_stack(ui, theme, text_col, label_col),
+                    FilterPanel::None | FilterPanel::Brush => {}
+                }
+            });
+        if let Some(r) = &win_resp { crate::focus::register(ctx, Region::Panel, r.response.layer_id, r.response.rect); }
+        self.filter_panel_rect = win_resp.map(|r| r.response.rect);
+        if let Some(rect) = self.filter_panel_rect { self.panel_positions.track(ctx, title, modal_pos, rect); }
+    }
+
+    fn render_adjustment_stack(&mut self, ui: &mut egui::Ui, theme: ThemeMode, text_col: egui::Color32, label_col: egui::Color32) {
+        let before = self.adjustments.clone();
+        let (mut remove, mut raise, mut bake) = (None, None, false);
+        if self.adjustments.is_empty() {
+            ui.label(egui::RichText::new("No adjustments yet. They stay editable until you bake them into the image.").size(12.0).color(label_col));
+        }
+        for (i, adj) in self.adjustments.iter_mut().enumerate() {
+            ui.push_id(i, |ui: &mut egui::Ui| {
+                ui.horizontal(|ui: &mut egui::Ui| {
+                    ui.checkbox(&mut adj.enabled, egui::RichText::new(adj.kind.label()).size(12.5).color(text_col));
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui: &mut egui::Ui| {
+                        if ui.small_button("✕").on_hover_text("Remove").clicked() { remove = Some(i); }
+                        if i > 0 && ui.small_button("⏶").on_hover_text("Apply earlier").clicked() { raise = Some(i); }
+                    });
+                });
+                ui.add_enabled_ui(adj.enabled, |ui: &mut egui::Ui| match &mut adj.kind {
+                    AdjustKind::BrightnessContrast { brightness, contrast } => {
+                        ui.add(egui::Slider::new(brightness, -100.0..=100.0).text("Brightness"));
+                        ui.add(egui::Slider::new(contrast, -100.0..=100.0).text("Contrast"));
+                    }
+                    AdjustKind::HueSaturation { hue, saturation } => {
+                        ui.add(egui::Slider::new(hue, -180.0..=180.0).suffix("°").text("Hue"));
+                        ui.add(egui::Slider::new(saturation, -100.0..=100.0).text("Saturation"));
+                    }
+                    AdjustKind::Levels { black, white, gamma } => {
+                        ui.add(egui::Slider::new(black, 0..=254).text("Black"));
+                        ui.add(egui::Slider::new(white, 1..=255).text("White"));
+                        ui.add(egui::Slider::new(gamma, 0.1..=4.0).text("Gamma"));
+                        if *white <= *black { *white = *black + 1; }
+                    }
+                    AdjustKind::Blur { radius } => { ui.add(egui::Slider::new(radius, 0.5..=20.0).text("Radius")); }
+                });
+                ui.separator();
+            });
+        }
+        if let Some(i) = remove { self.adjustments.remove(i); }
+        if let Some(i) = raise { self.adjustments.swap(i - 1, i); }
+        ui.horizontal(|ui: &mut egui::Ui| {
+            ui.menu_button(egui::RichText::new("Add Adjustment").size(12.0), |ui: &mut egui::Ui| {
+                for kind in AdjustKind::DEFAULTS {
+                    if ui.button(kind.label()).clicked() { self.adjustments.push(Adjustment::new(kind)); ui.close(); }
+                }
+            });
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui: &mut egui::Ui| {
+                if toolbar_action_btn(ui, egui::RichText::new("Close").size(12.0), theme).clicked() { self.filter_panel = FilterPanel::None; }
+                let button = ui.add_enabled(self.adjustments_active() && !self.pixels_locked, egui::Button::new(egui::RichText::new("Bake").size(12.0)));
+                bake = button.on_hover_text("Apply the enabled adjustments to the image pixels and clear the stack").clicked();
+            });
+        });
+        if bake { self.bake_adjustments(); return; }
+        if self.adjustments != before {
+            if !self.adjust_gesture {
+                let mut entry = self.take_undo_snapshot();
+                entry.adjustments = before;
+                self.push_undo_entry(entry);
+                self.adjust_gesture = true;
+            }
+            self.refresh_adjustments();
+            self.refresh_dirty_from_hash();
+        }
+        if !ui.input(|i| i.pointer.any_down()) { self.adjust_gesture = false; }
+    }
+
+    pub(super) fn render_bake_confirm(&mut self, ctx: &egui::Context, theme: ThemeMode) {
+        let (bg, border, text_col) = if matches!(theme, ThemeMode::Dark) {
+            (ColorPalette::ZINC_800, ColorPalette::BLUE_600, ColorPalette::ZINC_100)
+        } else {
+            (ColorPalette::GRAY_50, ColorPalette::BLUE_600, ColorPalette::GRAY_900)
+        };
+        let n = self.adjustments.iter().filter(|a| a.enabled).count();
+        egui::Window::new("Bake Adjustments")
+            .collapsible(false).resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+            .frame(egui::Frame::new().fill(bg).stroke(egui::Stroke::new(1.5, border)).corner_radius(8.0).inner_margin(16.0))
+            .show(ctx, |ui: &mut egui::Ui| {
+                ui.label(egui::RichText::new("This edit changes the image pixels, which would make the adjustment stack stale.").size(13.0).color(text_col));
+                ui.label(egui::RichText::new(format!("Bake the {} enabled adjustment{} into the image first?", n, if n == 1 { "" } else { "s" })).size(13.0).color(text_col));
+                ui.add_space(8.0);
+                ui.horizontal(|ui: &mut egui::Ui| {
+                    if ui.button("Bake").clicked() { self.bake_adjustments(); }
+                    if ui.button("Cancel").clicked() { self.bake_confirm = false; }
+                });
+            });
+    }
+
+    fn render_variant_export(&mut self, ui: &mut egui::Ui, ctx: &egui::Context, label_col: egui::Color32) {
+        if let Some(job) = &self.variant_export {
+            ui.add(egui::ProgressBar::new(job.done() as f32 / job.total as f32).text(format!("Writing {} of {}...", (job.done() + 1).min(job.total), job.total)));
+            return;
+        }
+        ui.horizontal(|ui: &mut egui::Ui| {
+            for (on, scale) in self.variant_scales.iter_mut().zip(VARIANT_SCALES) {
+                if ui.checkbox(on, egui::RichText::new(format!("{}x", format_scale(scale))).size(12.0)).changed() { self.variant_confirm = false; }
+            }
+            let mut custom = self.variant_custom.is_some();
+            if ui.checkbox(&mut custom, egui::RichText::new("Custom").size(12.0)).changed() { self.variant_custom = custom.then_some(1.5); self.variant_confirm = false; }
+            if let Some(s) = &mut self.variant_custom && ui.add(egui::DragValue::new(s).range(0.1..=8.0).speed(0.05).max_decimals(2).suffix("x")).changed() { self.variant_confirm = false; }
+        });
+        ui.horizontal(|ui: &mut egui::Ui| {
+            ui.label(egui::RichText::new("Canvas is drawn at:").size(12.0).color(label_col));
+            if ui.add(egui::DragValue::new(&mut self.variant_base).range(0.25..=8.0).speed(0.05).max_decimals(2).suffix("x")).changed() { self.variant_confirm = false; }
+        });
+        ui.horizontal(|ui: &mut egui::Ui| {
+            ui.label(egui::RichText::new("File name:").size(12.0).color(label_col));
+            ui.add(egui::TextEdit::singleline(&mut self.variant_pattern).desired_width(130.0).hint_text("{name}@{scale}x"))
+                .on_hover_text("Tokens: {name} {scale}");
+            ui.label(egui::RichText::new(format!(".{}", self.export_format.extension())).size(12.0).color(label_col));
+        });
+        let name = self.file_path.as_ref().and_then(|p| p.file_stem()).and_then(|s| s.to_str()).unwrap_or("export");
+        let scales = self.selected_variant_scales();
+        if let Some(&first) = scales.first() {
+            ui.label(egui::RichText::new(format!("e.g. {}.{}", variant_file_stem(&self.variant_pattern, name, first), self.export_format.extension())).size(11.0).color(label_col).italics());
+        }
+        let upscales = self.variant_upscales();
+        if self.variant_confirm && !upscales.is_empty() {
+            let list: Vec<String> = upscales.iter().map(|s| format!("{}x", format_scale(*s))).collect();
+            ui.label(egui::RichText::new(format!("{} enlarges the canvas more than {}x and will look soft.", list.join(", "), format_scale(VARIANT_UPSCALE_WARN))).size(11.5).color(ColorPalette::AMBER_500));
+        }
+        ui.horizontal(|ui: &mut egui::Ui| {
+            let confirming = self.variant_confirm && !upscales.is_empty();
+            let label = if confirming { "Export Anyway..." } else { "Export Variants..." };
+            if ui.add_enabled(!scales.is_empty() && self.image.is_some(), egui::Button::new(egui::RichText::new(label).size(12.0))).clicked() {
+                if !upscales.is_empty() && !confirming { self.variant_confirm = true; }
+                else if let Some(dir) = rfd::FileDialog::new().pick_folder() && let Err(e) = self.start_variant_export(ctx, &dir) { self.pending_error = Some(e); }
+            }
+            if confirming && ui.button(egui::RichText::new("Cancel").size(12.0)).clicked() { self.variant_confirm = false; }
+        });
+    }
+
+    pub(super) fn render_slices_panel(&mut self, ctx: &egui::Context, theme: ThemeMode) {
+        let (bg, border, text_col, label_col) = if matches!(theme, ThemeMode::Dark) {
+            (ColorPalette::ZINC_800, ColorPalette::ZINC_700, ColorPalette::ZINC_100, ColorPalette::ZINC_400)
+        } else {
+            (ColorPalette::GRAY_50, ColorPalette::GRAY_300, ColorPalette::GRAY_900, ColorPalette::ZINC_600)
+        };
+        let canvas = self.canvas_rect.unwrap_or(ctx.content_rect());
+        let est_h = self.panel_positions.sizes.get("Slices").map_or(120.0, |s| s.y);
+        let panel_pos = self.panel_positions.resolve("Slices", egui::pos2(canvas.min.x + 10.0, canvas.max.y - 10.0 - est_h), ctx.content_rect());
+        let win_resp = egui::Window::new("Slices")
+            .collapsible(true).resizable(false)
+            .current_pos(panel_pos)
+            .fixed_size(egui::vec2(240.0, 0.0))
+            .frame(egui::Frame::new().fill(bg).stroke(egui::Stroke::new(1.0, border)).corner_radius(8.0).inner_margin(10.0))
+            .show(ctx, |ui: &mut egui::Ui| {
+                if self.slices.is_empty() {
+                    ui.label(egui::RichText::new("Drag on the canvas to add a slice").size(12.0).color(label_col));
+                    return;
+                }
+                egui::ScrollArea::vertical().max_height(180.0).auto_shrink([false, true]).show(ui, |ui: &mut egui::Ui| {
+                    for i in 0..self.slices.len() {
+                        let sl = &self.slices[i];
+                        let label = format!("{}  ({}x{})", sl.name, sl.w as u32, sl.h as u32);
+                        if ui.selectable_label(self.selected_slice == Some(i), egui::RichText::new(label).size(12.0).color(text_col)).clicked() {
+                            self.selected_slice = Some(i);
+                        }
+                    }
+                });
+                let Some(idx) = self.selected_slice.filter(|&i| i < self.slices.len()) else { return };
+                ui.separator();
+                let img_dims = self.image.as_ref().map(|i| (i.width() as f32, i.height() as f32)).unwrap_or((1.0, 1.0));
+                let mut changed = false;
+                let sl = &mut self.slices[idx];
+                ui.horizontal(|ui: &mut egui::Ui| {
+                    ui.label(egui::RichText::new("Name:").size(12.0).color(label_col));
+                    changed |= ui.add(egui::TextEdit::singleline(&mut sl.name).desired_width(150.0)).changed();
+                });
+                ui.horizontal(|ui: &mut egui::Ui| {
+                    ui.label(egui::RichText::new("X:").size(12.0).color(label_col));
+                    changed |= ui.add(egui::DragValue::new(&mut sl.x).range(0.0..=img_dims.0 - 1.0).speed(1.0)).changed();
+                    ui.label(egui::RichText::new("Y:").size(12.0).color(label_col));
+                    changed |= ui.add(egui::DragValue::new(&mut sl.y).range(0.0..=img_dims.1 - 1.0).speed(1.0)).changed();
+                });
+                ui.horizontal(|ui: &mut egui::Ui| {
+                    ui.label(egui::RichText::new("W:").size(12.0).color(label_col));
+                    changed |= ui.add(egui::DragValue::new(&mut sl.w).range(1.0..=img_dims.0).speed(1.0)).changed();
+                    ui.label(egui::RichText::new("H:").size(12.0).color(label_col));
+                    changed |= ui.add(egui::DragValue::new(&mut sl.h).range(1.0..=img_dims.1).speed(1.0)).changed();
+                });
+                if changed { self.dirty = true; }
+                ui.horizontal(|ui: &mut egui::Ui| {
+                    if toolbar_action_btn(ui, egui::RichText::new("Duplicate").size(12.0), theme).clicked() { self.duplicate_selected_slice(); }
+                    if toolbar_action_btn(ui, egui::RichText::new("Delete").size(12.0), theme).clicked() { self.delete_selected_slice(); }
+                });
+            });
+        if let Some(r) = win_resp {
+            crate::focus::register(ctx, Region::Panel, r.response.layer_id, r.response.rect);
+            self.panel_positions.track(ctx, "Slices", panel_pos, r.response.rect);
+        }
+    }
+
+    pub(super) fn render_rename_dialog(&mut self, ctx: &egui::Context, theme: ThemeMode) {
+        let (bg, border, text_col) = if matches!(theme, ThemeMode::Dark) {
+            (ColorPalette::ZINC_800, ColorPalette::BLUE_600, ColorPalette::ZINC_100)
+        } else {
+            (ColorPalette::GRAY_50, ColorPalette::BLUE_600, ColorPalette::GRAY_900)
+        };
+        let ext = self.file_path.as_ref().and_then(|p| p.extension()).map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+        let (mut apply, mut cancel) = (false, false);
+        egui::Window::new("Rename File")
+            .collapsible(false).resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+            .frame(egui::Frame::new().fill(bg).stroke(egui::Stroke::new(1.5, border)).corner_radius(8.0).inner_margin(16.0))
+            .show(ctx, |ui: &mut egui::Ui| {
+                let Some(buf) = &mut self.rename_buffer else { return };
+                ui.label(egui::RichText::new("New filename:").size(13.0).color(text_col));
+                ui.horizontal(|ui: &mut egui::Ui| {
+                    let resp = ui.text_edit_singleline(buf);
+                    if resp.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) { apply = true; }
+                    ui.label(egui::RichText::new(&ext).color(text_col));
+                });
+                ui.add_space(8.0);
+                ui.horizontal(|ui: &mut egui::Ui| {
+                    if ui.button("Rename").clicked() { apply = true; }
+                    if ui.button("Cancel").clicked() { cancel = true; }
+                });
+            });
+        if apply { self.apply_rename(); } else if cancel { self.rename_buffer = None; }
+    }
+
+    pub(super) fn render_session_discard_confirm(&mut self, ctx: &egui::Context, theme: ThemeMode) {
+        let (bg, border, text_col) = if matches!(theme, ThemeMode::Dark) {
+            (ColorPalette::ZINC_800, ColorPalette::BLUE_600, ColorPalette::ZINC_100)
+        } else {
+            (ColorPalette::GRAY_50, ColorPalette::BLUE_600, ColorPalette::GRAY_900)
+        };
+        egui::Window::new("Stop Recording")
+            .collapsible(false).resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+            .frame(egui::Frame::new().fill(bg).stroke(egui::Stroke::new(1.5, border)).corner_radius(8.0).inner_margin(16.0))
+            .show(ctx, |ui: &mut egui::Ui| {
+                ui.label(egui::RichText::new(format!("Discard the {} recorded frames?", self.session.frames.len())).size(13.0).color(text_col));
+                ui.add_space(8.0);
+                ui.horizontal(|ui: &mut egui::Ui| {
+                    if ui.button("Export First").clicked() { self.session.confirm_discard = false; self.filter_panel = FilterPanel::TimeLapse; }
+                    if ui.button("Discard").clicked() { self.session.stop(); if self.filter_panel == FilterPanel::TimeLapse { self.filter_panel = FilterPanel::None; } }
+                    if ui.button("Keep Recording").clicked() { self.session.confirm_discard = false; }
+                });
+            });
+    }
+
+    pub(super) fn render_text_journal_offer(&mut self, ctx: &egui::Context, theme: ThemeMode) {
+        let (bg, border, text_col) = if matches!(theme, ThemeMode::Dark) {
+            (ColorPalette::ZINC_800, ColorPalette::BLUE_600, ColorPalette::ZINC_100)
+        } else {
+            (ColorPalette::GRAY_50, ColorPalette::BLUE_600, ColorPalette::GRAY_900)
+        };
+        let n = self.journal_offer.as_ref().map_or(0, Vec::len);
+        let (mut restore, mut discard) = (false, false);
+        egui::Window::new("Recover Text Layers")
+            .collapsible(false).resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+            .frame(egui::Frame::new().fill(bg).stroke(egui::Stroke::new(1.5, border)).corner_radius(8.0).inner_margin(16.0))
+            .show(ctx, |ui: &mut egui::Ui| {
+                ui.label(egui::RichText::new("This image was not saved after its text layers last changed.").size(13.0).color(text_col));
+                ui.label(egui::RichText::new(format!("Restore the {} text layer{} from that session?", n, if n == 1 { "" } else { "s" })).size(13.0).color(text_col));
+                ui.add_space(8.0);
+                ui.horizontal(|ui: &mut egui::Ui| {
+                    if ui.button("Restore").clicked() { restore = true; }
+                    if ui.button("Discard").clicked() { discard = true; }
+                });
+            });
+        if restore { self.restore_text_journal(); } else if discard { self.discard_text_journal(); }
+    }
+
+    pub(super) fn render_missing_font_banner(&mut self, ui: &mut egui::Ui, theme: ThemeMode) {
+        let missing = self.missing_fonts();
+        if missing.is_empty() { return; }
+        let (bg, border, text_col) = if matches!(theme, ThemeMode::Dark) {
+            (egui::Color32::from_rgb(58, 46, 20), ColorPalette::AMBER_500, ColorPalette::ZINC_100)
+        } else {
+            (ColorPalette::AMBER_50, ColorPalette::AMBER_500, ColorPalette::GRAY_900)
+        };
+        let msg = missing.iter().map(|(name, n)| format!("{} text layer{} {} '{}' which is not installed", n, if *n == 1 { "" } else { "s" }, if *n == 1 { "uses" } else { "use" }, name)).collect::<Vec<_>>().join("; ");
+        egui::Frame::new().fill(bg).stroke(egui::Stroke::new(1.0, border)).corner_radius(6.0).inner_margin(egui::Margin::symmetric(10, 6)).show(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(egui::RichText::new(format!("⚠ {} — substituted with Ubuntu", msg)).size(12.5).color(text_col));
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    if ui.small_button("Dismiss").clicked() { self.font_banner_dismissed = true; }
+                    if ui.small_button("Relink Font...").clicked() { self.open_font_relink_dialog(); }
+                });
+            });
+        });
+        ui.add_space(4.0);
+    }
+
+    pub(super) fn render_conversion_banner(&mut self, ui: &mut egui::Ui, theme: ThemeMode) {
+        let Some(source) = &self.source_info else { return };
+        let Some(msg) = source.conversion() else { return };
+        let kind = source.kind();
+        if self.muted_conversions.contains(&kind) { return; }
+        let (bg, border, text_col) = if matches!(theme, ThemeMode::Dark) {
+            (egui::Color32::from_rgb(58, 46, 20), ColorPalette::AMBER_500, ColorPalette::ZINC_100)
+        } else {
+            (ColorPalette::AMBER_50, ColorPalette::AMBER_500, ColorPalette::GRAY_900)
+        };
+        egui::Frame::new().fill(bg).stroke(egui::Stroke::new(1.0, border)).corner_radius(6.0).inner_margin(egui::Margin::symmetric(10, 6)).show(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(egui::RichText::new(format!("⚠ {}", msg)).size(12.5).color(text_col));
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    if ui.small_button("Dismiss").clicked() { self.conversion_dismissed = true; }
+                    if ui.small_button("Don't show again for this kind of file").on_hover_text(format!("Stop warning about {} files", kind)).clicked() {
+                        self.muted_conversions.kinds.push(kind.clone());
+                        self.muted_conversions.save();
+                        self.conversion_dismissed = true;
+                    }
+                });
+            });
+        });
+        ui.add_space(4.0);
+    }
+
+    pub(super) fn render_font_relink_dialog(&mut self, ctx: &egui::Context, theme: ThemeMode) {
+        let (bg, border, text_col) = if matches!(theme, ThemeMode::Dark) {
+            (ColorPalette::ZINC_800, ColorPalette::BLUE_600, ColorPalette::ZINC_100)
+        } else {
+            (ColorPalette::GRAY_50, ColorPalette::BLUE_600, ColorPalette::GRAY_900)
+        };
+        let Some(mut rows) = self.font_relink_dialog.take() else { return };
+        let (mut apply, mut cancel) = (false, false);
+        egui::Window::new("Relink Fonts")
+            .collapsible(false).resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+            .frame(egui::Frame::new().fill(bg).stroke(egui::Stroke::new(1.5, border)).corner_radius(8.0).inner_margin(16.0))
+            .show(ctx, |ui: &mut egui::Ui| {
+                ui.label(egui::RichText::new("Choose an installed font for each missing one. The original names are kept in the document.").size(12.5).color(text_col));
+                ui.add_space(8.0);
+                egui::Grid::new("font_relink_grid").num_columns(2).spacing([12.0, 6.0]).show(ui, |ui| {
+                    for (i, (from, to)) in rows.iter_mut().enumerate() {
+                        ui.label(egui::RichText::new(from.as_str()).size(12.5).color(text_col));
+                        let label = TEXT_FONTS.iter().find(|(n, _)| n == to).map_or(to.as_str(), |(_, l)| *l);
+                        egui::ComboBox::from_id_salt(("font_relink", i)).selected_text(label).width(120.0).show_ui(ui, |ui| {
+                            for (name, label) in TEXT_FONTS { if ui.selectable_label(to == name, *label).clicked() { *to = name.to_string(); } }
+                        });
+                        ui.end_row();
+                    }
+                });
+                ui.add_space(8.0);
+                ui.horizontal(|ui: &mut egui::Ui| {
+                    if ui.button("Apply").clicked() { apply = true; }
+                    if ui.button("Cancel").clicked() { cancel = true; }
+                });
             });
-        self.filter_panel_rect = win_resp.map(|r| r.response.rect);
+        if apply { self.apply_font_relinks(rows); } else if !cancel { self.font_relink_dialog = Some(rows); }
     }
 
     pub(super) fn render_color_picker(&mut self, _ui: &mut egui::Ui, ctx: &egui::Context, theme: ThemeMode) {
@@ -598,9 +1456,12 @@ impl ImageEditor {
         } else {
             (ColorPalette::GRAY_50, ColorPalette::BLUE_600, ColorPalette::GRAY_900, ColorPalette::ZINC_600)
         };
+        let screen = ctx.content_rect();
+        let picker_w = self.panel_positions.sizes.get("Color Picker").map_or(362.0, |s| s.x);
+        let picker_pos = self.panel_positions.resolve("Color Picker", egui::pos2(screen.max.x - 10.0 - picker_w, screen.min.y + 60.0), screen);
         let win_resp = egui::Window::new("Color Picker")
             .collapsible(false).resizable(false)
-            .anchor(egui::Align2::RIGHT_TOP, egui::vec2(-10.0, 60.0))
+            .current_pos(picker_pos)
             .default_size(egui::vec2(330.0, 580.0))
             .frame(egui::Frame::new().fill(bg).stroke(egui::Stroke::new(1.5, border)).corner_radius(8.0).inner_margin(16.0))
             .show(ctx, |ui| {
@@ -629,72 +1490,28 @@ impl ImageEditor {
 
                 let (outer_sq, _) = ui.allocate_exact_size(egui::vec2(avail_w, picker_w), egui::Sense::hover());
                 let rect = egui::Rect::from_min_size(egui::pos2(outer_sq.min.x + x_offset, outer_sq.min.y), egui::vec2(picker_w, picker_w));
-                let response = ui.interact(rect, ui.id().with("cp_sq"), egui::Sense::click_and_drag());
-                if ui.is_rect_visible(rect) {
-                    let painter = ui.painter_at(rect);
-                    let steps = 40i32;
-                    let (cw, ch) = (rect.width() / steps as f32, rect.height() / steps as f32);
-                    for cy in 0..steps {
-                        for cx in 0..steps {
-                            let (sc, vc) = (cx as f32 / (steps - 1) as f32, 1.0 - cy as f32 / (steps - 1) as f32);
-                            let (r, g, b) = hsv_to_rgb_f32(h, sc, vc);
-                            painter.rect_filled(egui::Rect::from_min_size(
-                                egui::pos2(rect.min.x + cx as f32 * cw, rect.min.y + cy as f32 * ch),
-                                egui::vec2(cw.ceil(), ch.ceil()),
-                            ), 0.0, egui::Color32::from_rgb((r * 255.0) as u8, (g * 255.0) as u8, (b * 255.0) as u8));
-                        }
-                    }
-                    let cur = egui::pos2(rect.min.x + s * rect.width(), rect.min.y + (1.0 - v) * rect.height());
-                    painter.circle_stroke(cur, 6.0, egui::Stroke::new(2.0, egui::Color32::WHITE));
-                    painter.circle_stroke(cur, 6.0, egui::Stroke::new(1.0, egui::Color32::BLACK));
-                }
-                if response.dragged() || response.clicked() {
-                    if let Some(pos) = response.interact_pointer_pos() {
-                        let (x, y) = (((pos.x - rect.min.x) / rect.width()).clamp(0.0, 1.0), ((pos.y - rect.min.y) / rect.height()).clamp(0.0, 1.0));
-                        s = x; v = 1.0 - y;
-                        ctx.data_mut(|d| d.insert_temp(sv_id, (s, v)));
-                        let (r, g, b) = hsv_to_rgb_f32(h, s, v);
-                        rgb = [r, g, b]; color_changed = true; sq_used = true;
-                    }
+                if hsv_picker::sv_square(ui, rect, ui.id().with("cp_sq"), h, &mut s, &mut v) {
+                    ctx.data_mut(|d| d.insert_temp(sv_id, (s, v)));
+                    let (r, g, b) = hsv_to_rgb_f32(h, s, v);
+                    rgb = [r, g, b]; color_changed = true; sq_used = true;
                 }
 
                 ui.add_space(4.0);
 
                 let (outer_hue, _) = ui.allocate_exact_size(egui::vec2(avail_w, 24.0), egui::Sense::hover());
                 let hue_rect = egui::Rect::from_min_size(egui::pos2(outer_hue.min.x + x_offset, outer_hue.min.y), egui::vec2(picker_w, 24.0));
-                let hue_resp = ui.interact(hue_rect, ui.id().with("cp_hue"), egui::Sense::click_and_drag());
-                if ui.is_rect_visible(hue_rect) {
-                    let painter = ui.painter_at(hue_rect);
-                    let steps = 60i32;
-                    let sw = hue_rect.width() / steps as f32;
-                    for i in 0..steps {
-                        let (r, g, b) = hsv_to_rgb_f32((i as f32 / steps as f32) * 360.0, 1.0, 1.0);
-                        painter.rect_filled(egui::Rect::from_min_size(
-                            egui::pos2(hue_rect.min.x + i as f32 * sw, hue_rect.min.y),
-                            egui::vec2(sw.ceil(), hue_rect.height()),
-                        ), 0.0, egui::Color32::from_rgb((r * 255.0) as u8, (g * 255.0) as u8, (b * 255.0) as u8));
-                    }
-                    painter.rect_stroke(hue_rect, 2.0, egui::Stroke::new(1.0,
-                        if matches!(theme, ThemeMode::Dark) { ColorPalette::ZINC_600 } else { ColorPalette::GRAY_400 }
-                    ), egui::StrokeKind::Outside);
-                    let hx = hue_rect.min.x + (h / 360.0) * hue_rect.width();
-                    let hcr = egui::Rect::from_center_size(egui::pos2(hx, hue_rect.center().y), egui::vec2(4.0, hue_rect.height() + 2.0));
-                    painter.rect_filled(hcr, 2.0, egui::Color32::WHITE);
-                    painter.rect_stroke(hcr, 2.0, egui::Stroke::new(1.0, egui::Color32::BLACK), egui::StrokeKind::Outside);
-                }
-                if hue_resp.dragged() || hue_resp.clicked() {
-                    if let Some(pos) = hue_resp.interact_pointer_pos() {
-                        h = ((pos.x - hue_rect.min.x) / hue_rect.width()).clamp(0.0, 1.0) * 360.0;
-                        ctx.data_mut(|d| d.insert_temp(hue_id, h));
-                        let (r, g, b) = hsv_to_rgb_f32(h, s, v);
-                        rgb = [r, g, b]; color_changed = true; hue_used = true;
-                    }
+                let outline = if matches!(theme, ThemeMode::Dark) { ColorPalette::ZINC_600 } else { ColorPalette::GRAY_400 };
+                if hsv_picker::hue_strip(ui, hue_rect, ui.id().with("cp_hue"), &mut h, outline) {
+                    ctx.data_mut(|d| d.insert_temp(hue_id, h));
+                    let (r, g, b) = hsv_to_rgb_f32(h, s, v);
+                    rgb = [r, g, b]; color_changed = true; hue_used = true;
                 }
 
                 if !sq_used && !hue_used {
                     let (er, eg, eb) = hsv_to_rgb_f32(h, s, v);
                     let expected = egui::Color32::from_rgb((er * 255.0) as u8, (eg * 255.0) as u8, (eb * 255.0) as u8);
                     if expected != self.color {
+                        (h, s, v) = (h_curr, s_curr, v_curr);
                         ctx.data_mut(|d| { d.insert_temp(hue_id, h_curr); d.insert_temp(sv_id, (s_curr, v_curr)); });
                     }
                 }
@@ -721,48 +1538,112 @@ impl ImageEditor {
                     if ui.small_button("Copy").clicked() { ctx.copy_text(self.hex_input.clone()); }
                 });
 
-                ui.add_space(4.0); ui.separator(); ui.add_space(4.0);
+                let field = [52.0, 20.0];
+                let mut channels = [self.color.r(), self.color.g(), self.color.b()];
+                let mut rgb_edited = false;
                 ui.horizontal(|ui: &mut egui::Ui| {
-                    ui.label(egui::RichText::new("Recent").size(13.0).color(text_col));
-                    if ui.small_button("Clear").clicked() { self.color_history = ColorHistory::default(); }
+                    for (label, val) in ["R", "G", "B"].into_iter().zip(channels.iter_mut()) {
+                        ui.label(egui::RichText::new(label).size(12.0).color(weak_col).monospace());
+                        rgb_edited |= ui.add_sized(field, egui::DragValue::new(val).range(0..=255).speed(1.0)).changed();
+                    }
                 });
+                if rgb_edited {
+                    let [r, g, b] = channels.map(|c| c as f32 / 255.0);
+                    let (nh, ns, nv) = rgb_to_hsv_f32(r, g, b);
+                    if nv > 0.0 { s = ns; }
+                    if nv > 0.0 && ns > 0.0 { h = nh; }
+                    v = nv;
+                    ctx.data_mut(|d| { d.insert_temp(hue_id, h); d.insert_temp(sv_id, (s, v)); });
+                    self.color = egui::Color32::from_rgb(channels[0], channels[1], channels[2]);
+                    self.hex_input = RgbaColor::from_egui(self.color).to_hex();
+                }
 
-                {
-                    let history = self.color_history.get_colors().clone();
-                    let n = history.len();
-                    let (sw, sp) = (28.0f32, 4.0f32);
-                    let avail = ui.available_width();
-                    let per_row = ((avail + sp) / (sw + sp)).floor().max(1.0) as usize;
-                    let rows = if n == 0 { 1 } else { (n + per_row - 1) / per_row };
-                    let total_h = if n == 0 { sw } else { rows as f32 * (sw + sp) - sp };
-                    let origin = ui.cursor().min;
-                    let (rec_rect, _) = ui.allocate_exact_size(egui::vec2(avail, total_h), egui::Sense::hover());
-                    let painter = ui.painter_at(rec_rect);
-                    let ptr = ctx.pointer_latest_pos();
-                    let released = ctx.input(|i| i.pointer.any_released());
-                    for (idx, color) in history.iter().enumerate() {
-                        let (row, col) = (idx / per_row, idx % per_row);
-                        let items_this_row = if (row + 1) * per_row <= n { per_row } else { n - row * per_row };
-                        let row_w = items_this_row as f32 * (sw + sp) - sp;
-                        let lpad = ((avail - row_w) / 2.0).max(0.0);
-                        let sr = egui::Rect::from_min_size(
-                            egui::pos2(origin.x + lpad + col as f32 * (sw + sp), origin.y + row as f32 * (sw + sp)),
-                            egui::vec2(sw, sw),
-                        );
-                        painter.rect_filled(sr, 4.0, color.to_egui());
-                        painter.rect_stroke(sr, 4.0, egui::Stroke::new(1.0,
-                            if matches!(theme, ThemeMode::Dark) { egui::Color32::from_rgba_unmultiplied(255,255,255,40) }
-                            else { egui::Color32::from_rgba_unmultiplied(0,0,0,40) }
-                        ), egui::StrokeKind::Outside);
-                        if let Some(pp) = ptr {
-                            if sr.contains(pp) {
-                                ctx.output_mut(|o| o.cursor_icon = egui::CursorIcon::PointingHand);
-                                if released { let mut c = *color; c.a = 255; self.color = c.to_egui(); self.hex_input = c.to_hex(); }
-                            }
+                let hsl = self.color_picker_hsl;
+                let (s2, t2) = if hsl { hsv_to_hsl(s, v) } else { (s, v) };
+                let (mut hue_deg, mut sat_pct, mut third_pct) = (h, s2 * 100.0, t2 * 100.0);
+                let mut hsv_edited = false;
+                ui.horizontal(|ui: &mut egui::Ui| {
+                    ui.label(egui::RichText::new("H").size(12.0).color(weak_col).monospace());
+                    hsv_edited |= ui.add_sized(field, egui::DragValue::new(&mut hue_deg).range(0.0..=360.0).speed(1.0).max_decimals(0).suffix("°")).changed();
+                    ui.label(egui::RichText::new("S").size(12.0).color(weak_col).monospace());
+                    hsv_edited |= ui.add_sized(field, egui::DragValue::new(&mut sat_pct).range(0.0..=100.0).speed(0.5).max_decimals(0).suffix("%")).changed();
+                    ui.label(egui::RichText::new(if hsl { "L" } else { "V" }).size(12.0).color(weak_col).monospace());
+                    hsv_edited |= ui.add_sized(field, egui::DragValue::new(&mut third_pct).range(0.0..=100.0).speed(0.5).max_decimals(0).suffix("%")).changed();
+                    if ui.selectable_label(hsl, "HSL").on_hover_text("Edit saturation and lightness instead of saturation and value").clicked() { self.color_picker_hsl = !hsl; }
+                });
+                if hsv_edited {
+                    let (sat, third) = ((sat_pct / 100.0).clamp(0.0, 1.0), (third_pct / 100.0).clamp(0.0, 1.0));
+                    h = hue_deg.clamp(0.0, 360.0);
+                    (s, v) = if hsl { hsl_to_hsv(sat, third) } else { (sat, third) };
+                    ctx.data_mut(|d| { d.insert_temp(hue_id, h); d.insert_temp(sv_id, (s, v)); });
+                    let (r, g, b) = hsv_to_rgb_f32(h, s, v);
+                    self.color = egui::Color32::from_rgb((r * 255.0) as u8, (g * 255.0) as u8, (b * 255.0) as u8);
+                    self.hex_input = RgbaColor::from_egui(self.color).to_hex();
+                }
+
+                ui.add_space(4.0); ui.separator(); ui.add_space(4.0);
+                ui.label(egui::RichText::new("Harmonies").size(13.0).color(text_col));
+                for (name, swatches) in color_harmonies(self.color) {
+                    ui.horizontal(|ui: &mut egui::Ui| {
+                        ui.add_sized([96.0, 22.0], egui::Label::new(egui::RichText::new(name).size(11.0).color(weak_col)));
+                        for c in swatches {
+                            let (sr, resp) = ui.allocate_exact_size(egui::vec2(34.0, 22.0), egui::Sense::click());
+                            ui.painter().rect_filled(sr, 4.0, c);
+                            ui.painter().rect_stroke(sr, 4.0, egui::Stroke::new(1.0, weak_col), egui::StrokeKind::Outside);
+                            let resp = resp.on_hover_cursor(egui::CursorIcon::PointingHand)
+                                .on_hover_text(format!("{}\nClick to use, right-click to add to favorites", RgbaColor::from_egui(c).to_hex()));
+                            if resp.clicked() { self.color = c; self.hex_input = RgbaColor::from_egui(c).to_hex(); self.add_color_to_history(); }
+                            if resp.secondary_clicked() && !self.color_favorites.contains(RgbaColor::from_egui(c)) { self.color_favorites.toggle(RgbaColor::from_egui(c)); }
                         }
+                    });
+                }
+
+                ui.add_space(4.0); ui.separator(); ui.add_space(4.0);
+                ui.label(egui::RichText::new("Contrast Check").size(13.0).color(text_col));
+                ui.horizontal(|ui: &mut egui::Ui| {
+                    ui.label(egui::RichText::new("Background:").size(12.0).color(weak_col));
+                    egui::color_picker::color_edit_button_srgba(ui, &mut self.contrast_bg, egui::color_picker::Alpha::Opaque);
+                    if ui.small_button("White").clicked() { self.contrast_bg = egui::Color32::WHITE; }
+                    if ui.small_button("Black").clicked() { self.contrast_bg = egui::Color32::BLACK; }
+                    let under_text = ui.add_enabled(self.selected_text.is_some(), egui::Button::new(egui::RichText::new("Under Text").size(11.0)))
+                        .on_hover_text("Average color behind the selected text layer").on_disabled_hover_text("Select a text layer first");
+                    if under_text.clicked() && let Some(c) = self.selected_text.and_then(|tid| self.average_color_under_text(tid)) { self.contrast_bg = c; }
+                });
+                let ratio = contrast_ratio(self.color, self.contrast_bg);
+                ui.horizontal(|ui: &mut egui::Ui| {
+                    let (pr, _) = ui.allocate_exact_size(egui::vec2(44.0, 24.0), egui::Sense::hover());
+                    ui.painter().rect_filled(pr, 4.0, self.contrast_bg);
+                    ui.painter().text(pr.center(), egui::Align2::CENTER_CENTER, "Aa", egui::FontId::proportional(15.0), self.color);
+                    ui.label(egui::RichText::new(format!("{:.2}:1", ratio)).size(13.0).strong().color(text_col));
+                    for (label, min) in [("AA", 4.5), ("AA Large", 3.0), ("AAA", 7.0)] {
+                        let pass = ratio >= min;
+                        let fill = if pass { ColorPalette::GREEN_600 } else { ColorPalette::RED_600 };
+                        egui::Frame::new().fill(fill).corner_radius(3.0).inner_margin(egui::Margin::symmetric(5, 1)).show(ui, |ui| {
+                            ui.label(egui::RichText::new(format!("{} {}", label, if pass { "✔" } else { "✖" })).size(10.0).color(egui::Color32::WHITE));
+                        });
                     }
+                });
+                if ui.small_button("Use Background as Color").clicked() {
+                    self.color = self.contrast_bg; self.hex_input = RgbaColor::from_egui(self.color).to_hex(); self.add_color_to_history();
                 }
 
+                ui.add_space(4.0); ui.separator(); ui.add_space(4.0);
+                ui.horizontal(|ui: &mut egui::Ui| {
+                    ui.label(egui::RichText::new("Recent").size(13.0).color(text_col));
+                    if ui.small_button("Clear").on_hover_text("Clears everything except pinned colors").clicked() { self.color_history.clear_recent(); }
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        let mut limit = self.color_history.limit;
+                        if ui.add(egui::DragValue::new(&mut limit).range(COLOR_HISTORY_LIMITS).suffix(" kept")).changed() { self.color_history.set_limit(limit); }
+                    });
+                });
+                ui.label(egui::RichText::new("Right-click a color to pin it. Drag pinned colors to reorder.").size(10.0).color(weak_col));
+                ui.add_space(2.0);
+                if !self.color_history.pinned.is_empty() {
+                    self.history_swatches(ui, true, theme);
+                    ui.add_space(4.0);
+                }
+                self.history_swatches(ui, false, theme);
+
                 ui.add_space(4.0); ui.separator(); ui.add_space(4.0);
 
                 let current_rgba = RgbaColor::from_egui(self.color);
@@ -927,16 +1808,66 @@ impl ImageEditor {
                 });
                     });
             });
+        if let Some(r) = &win_resp { crate::focus::register(ctx, Region::Panel, r.response.layer_id, r.response.rect); }
         self.color_picker_rect = win_resp.map(|r| r.response.rect);
+        if let Some(rect) = self.color_picker_rect { self.panel_positions.track(ctx, "Color Picker", picker_pos, rect); }
+    }
+
+    fn history_swatches(&mut self, ui: &mut egui::Ui, pinned: bool, theme: ThemeMode) {
+        let entries: Vec<HistoryColor> = if pinned { self.color_history.pinned.clone() } else { self.color_history.colors.iter().copied().collect() };
+        let (n, sw, sp) = (entries.len(), 28.0f32, 4.0f32);
+        let avail = ui.available_width();
+        let per_row = ((avail + sp) / (sw + sp)).floor().max(1.0) as usize;
+        let total_h = if n == 0 { sw } else { n.div_ceil(per_row) as f32 * (sw + sp) - sp };
+        let origin = ui.cursor().min;
+        ui.allocate_exact_size(egui::vec2(avail, total_h), egui::Sense::hover());
+        let rects: Vec<egui::Rect> = (0..n).map(|idx| {
+            let (row, col) = (idx / per_row, idx % per_row);
+            let row_w = per_row.min(n - row * per_row) as f32 * (sw + sp) - sp;
+            let lpad = ((avail - row_w) / 2.0).max(0.0);
+            egui::Rect::from_min_size(egui::pos2(origin.x + lpad + col as f32 * (sw + sp), origin.y + row as f32 * (sw + sp)), egui::vec2(sw, sw))
+        }).collect();
+        let drop_idx = if pinned && self.pinned_drag_src.is_some() { ui.ctx().pointer_latest_pos().and_then(|pp| rects.iter().position(|r| r.expand(2.0).contains(pp))) } else { None };
+        let border = if matches!(theme, ThemeMode::Dark) { egui::Color32::from_rgba_unmultiplied(255,255,255,40) } else { egui::Color32::from_rgba_unmultiplied(0,0,0,40) };
+        for (idx, (entry, sr)) in entries.iter().zip(&rects).enumerate() {
+            let resp = ui.interact(*sr, ui.id().with(("color_history", pinned, idx)), if pinned { egui::Sense::click_and_drag() } else { egui::Sense::click() })
+                .on_hover_cursor(egui::CursorIcon::PointingHand)
+                .on_hover_text(format!("{}\n{}", entry.color.to_hex(), tr_n("Used {n} time", "Used {n} times", entry.uses as usize)));
+            let dragging = self.pinned_drag_src == Some(idx);
+            ui.painter().rect_filled(*sr, 4.0, if dragging { entry.color.to_egui().gamma_multiply(0.3) } else { entry.color.to_egui() });
+            let target = drop_idx == Some(idx) && !dragging;
+            ui.painter().rect_stroke(*sr, 4.0, if target { egui::Stroke::new(2.5, egui::Color32::WHITE) } else { egui::Stroke::new(1.0, border) }, egui::StrokeKind::Outside);
+            if pinned {
+                let dot = egui::pos2(sr.max.x - 5.0, sr.min.y + 5.0);
+                ui.painter().circle(dot, 3.0, ColorPalette::BLUE_500, egui::Stroke::new(1.0, egui::Color32::WHITE));
+            }
+            if resp.drag_started() { self.pinned_drag_src = Some(idx); }
+            if resp.clicked() { let mut c = entry.color; c.a = 255; self.color = c.to_egui(); self.hex_input = c.to_hex(); }
+            if resp.secondary_clicked() { self.color_history.toggle_pin(entry.color); }
+        }
+        if pinned && let Some(src) = self.pinned_drag_src && ui.input(|i| i.pointer.any_released()) {
+            if let Some(dst) = drop_idx { self.color_history.move_pinned(src, dst); }
+            self.pinned_drag_src = None;
+        }
     }
 
     pub(super) fn render_canvas(&mut self, ui: &mut egui::Ui, ctx: &egui::Context) {
         let canvas_rect: egui::Rect = ui.available_rect_before_wrap();
         self.canvas_rect = Some(canvas_rect);
-        if self.fit_on_next_frame { self.fit_image(); self.fit_on_next_frame = false; }
+        if self.fit_on_next_frame { self.fit_image(); self.fit_on_next_frame = false; } else { self.follow_canvas_resize(); }
+        let pixels_changed = self.pixels_pending();
+        if pixels_changed { self.pixel_revision += 1; }
+        self.update_space_pan(ctx);
+        PerfStats::sample(&mut self.perf.frame_ms, ctx.input(|i| i.unstable_dt) * 1000.0);
+        let upload_started = std::time::Instant::now();
         self.ensure_texture(ctx);
+        if let Some(level) = self.canvas_proxy.poll(ctx, self.zoom) && !self.is_dragging && let Some(source) = self.composite_for_display() { self.canvas_proxy.build(level, source); }
+        let mut upload_time = upload_started.elapsed();
         let (rect, response) = ui.allocate_exact_size(canvas_rect.size(), egui::Sense::click_and_drag());
+        a11y_label(&response, egui::WidgetType::Other, &format!("Image canvas, {:?} tool", self.tool));
         let painter: egui::Painter = ui.painter_at(rect);
+        crate::tour::anchor(ctx, "ie_canvas", rect);
+        crate::focus::register(ctx, Region::Canvas, response.layer_id, rect);
 
         let checker_tid = self.ensure_checker_texture(ctx);
         let tile = 32.0_f32;
@@ -944,7 +1875,11 @@ impl ImageEditor {
             egui::pos2(0.0, 0.0),
             egui::pos2(rect.width() / tile, rect.height() / tile),
         );
-        painter.image(checker_tid, rect, uv, egui::Color32::WHITE);
+        let surround = self.settings.canvas_surround.color();
+        match surround {
+            Some(fill) => { painter.rect_filled(rect, 0.0, fill); }
+            None => { painter.image(checker_tid, rect, uv, egui::Color32::WHITE); }
+        }
 
         if let (Some(tex), Some(img)) = (&self.texture, &self.image) {
             let (img_w, img_h) = (img.width() as f32, img.height() as f32);
@@ -953,21 +1888,37 @@ impl ImageEditor {
                 egui::pos2(center.x + self.pan.x, center.y + self.pan.y),
                 egui::vec2(img_w * self.zoom, img_h * self.zoom),
             );
-            painter.image(*tex, img_rect, egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)), egui::Color32::WHITE);
+            if surround.is_some() {
+                painter.image(checker_tid, img_rect, egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(img_rect.width() / tile, img_rect.height() / tile)), egui::Color32::WHITE);
+            }
+            let tex = self.canvas_proxy.texture(self.zoom).unwrap_or(*tex);
+            painter.image(tex, img_rect, egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)), egui::Color32::WHITE);
             painter.rect_stroke(img_rect, 0.0, egui::Stroke::new(1.0, ColorPalette::ZINC_500), egui::StrokeKind::Outside);
         }
 
+        let upload_started = std::time::Instant::now();
         self.ensure_raster_layer_textures(ctx);
         self.ensure_image_layer_textures(ctx);
+        upload_time += upload_started.elapsed();
+        if pixels_changed { PerfStats::sample(&mut self.perf.upload_ms, upload_time.as_secs_f32() * 1000.0); }
 
         let zoom = self.zoom;
         let editing_text = self.editing_text;
         let selected_text = self.selected_text;
         let text_cursor = self.text_cursor;
         let text_sel_anchor = self.text_sel_anchor;
+        let caret_on = editing_text && self.caret_visible(ctx);
         let mut text_galleys: std::collections::HashMap<u64, std::sync::Arc<egui::Galley>> = std::collections::HashMap::new();
+        self.text_caret_maps.clear();
         for i in 0..self.text_layers.len() {
             let tl = &self.text_layers[i];
+            if tl.direction == TextDirection::Vertical {
+                self.text_layers[i].rendered_height = 0.0;
+                self.text_layers[i].cached_lines.clear();
+                continue;
+            }
+            let bidi = Self::text_layer_uses_bidi(tl).then(|| Self::text_layer_bidi_display(tl));
+            let rtl = tl.direction == TextDirection::Rtl;
             let font_size_screen = tl.font_size * zoom;
             let font_family = egui::FontFamily::Name(tl.font_family_name().into());
             let font_id = egui::FontId::new(font_size_screen, font_family);
@@ -976,17 +1927,18 @@ impl ImageEditor {
             let layer_underline = tl.underline;
             let content_snap = tl.content.clone();
             let layer_font_size = tl.font_size;
+            let tab_width = tl.tab_width;
             let tid = tl.id;
-            let mut job = egui::text::LayoutJob::default();
-            job.wrap.max_width = box_w_screen;
-            job.append(&content_snap, 0.0, egui::TextFormat {
+            let format = egui::TextFormat {
                 font_id: font_id.clone(), color: layer_color, italics: false,
                 underline: if layer_underline {
                     egui::Stroke::new((font_size_screen * 0.06).max(1.0), layer_color)
                 } else { egui::Stroke::NONE },
                 ..Default::default()
-            });
-            let galley = ui.painter().layout_job(job);
+            };
+            let wrap_w = if bidi.is_some() { f32::INFINITY } else { box_w_screen };
+            let halign = if rtl { egui::Align::RIGHT } else { egui::Align::LEFT };
+            let galley = layout_with_tabs(ui.painter(), bidi.as_ref().map_or(content_snap.as_str(), |b| b.0.as_str()), &format, wrap_w, halign, tab_width);
             self.text_layers[i].rendered_height = (galley.rect.height() / zoom).max(layer_font_size);
             let content_chars: Vec<char> = content_snap.chars().collect();
             let mut char_ptr = 0usize;
@@ -998,15 +1950,21 @@ impl ImageEditor {
                 char_ptr = end;
                 if char_ptr < content_chars.len() && content_chars[char_ptr] == '\n' { char_ptr += 1; }
             }
-            self.text_layers[i].cached_lines = new_cached;
+            if let Some((_, caret, lines)) = bidi {
+                self.text_layers[i].cached_lines = lines;
+                self.text_caret_maps.insert(tid, caret);
+            } else {
+                self.text_layers[i].cached_lines = new_cached;
+            }
             text_galleys.insert(tid, galley);
         }
+        self.text_galley_cache = text_galleys.clone();
 
         {
             let (img_w, img_h) = self.image.as_ref().map(|i| (i.width() as f32, i.height() as f32)).unwrap_or((1.0, 1.0));
             let selected_iid = self.selected_image_layer;
             let layers_snap: Vec<(u64, LayerKind, Option<u64>, f32, bool)> = self.layers.iter().filter(|l| l.visible && l.kind != LayerKind::Background)
-                .map(|l| (l.id, l.kind, l.linked_image_id.or(l.linked_text_id), l.opacity, l.id == self.active_layer_id)).collect();
+                .map(|l| (l.id, l.kind, l.linked_image_id.or(l.linked_text_id).or(l.linked_shape_id), l.opacity, l.id == self.active_layer_id)).collect();
             for (lid, kind, linked_id, layer_opacity, is_active) in &layers_snap {
                 let alpha = (layer_opacity.clamp(0.0, 1.0) * 255.0).round() as u8;
                 match kind {
@@ -1085,7 +2043,7 @@ impl ImageEditor {
                     LayerKind::Text => {
                         if let Some(tid) = linked_id {
                             let tid = *tid;
-                            if let Some(tl_idx) = self.text_layers.iter().position(|t| t.id == tid) {
+                            if let Some(tl_idx) = self.text_layers.iter().position(|t| t.id == tid && self.text_shown(t)) {
                                 let tl = &self.text_layers[tl_idx];
                                 let anchor = self.image_to_screen(tl.img_x, tl.img_y);
                                 let font_size_screen = tl.font_size * zoom;
@@ -1094,15 +2052,42 @@ impl ImageEditor {
                                 let angle_rad = tl.rotation.to_radians();
                                 let (cos_a, sin_a) = (angle_rad.cos(), angle_rad.sin());
                                 let sel_rect = tl.screen_rect(anchor, zoom);
-                                let center = sel_rect.center();
-                                let d = anchor - center;
-                                let text_pos = center + egui::vec2(d.x * cos_a - d.y * sin_a, d.x * sin_a + d.y * cos_a);
+                                let text_pos = tl.text_origin(anchor, zoom);
                                 let is_editing = editing_text && selected_text == Some(tid);
                                 let effective_alpha = (layer_color.a() as f32 * layer_opacity).clamp(0.0, 255.0) as u8;
                                 let draw_color = egui::Color32::from_rgba_unmultiplied(
                                     layer_color.r(), layer_color.g(), layer_color.b(), effective_alpha);
 
-                                if let Some(galley) = text_galleys.get(&tid).cloned() {
+                                if tl.direction == TextDirection::Vertical {
+                                    let font_id = egui::FontId::new(font_size_screen, egui::FontFamily::Name(tl.font_family_name().into()));
+                                    let (step, colw, width) = (tl.vertical_step() * zoom, tl.column_width() * zoom, sel_rect.width());
+                                    let to_canvas = |lp: egui::Pos2| -> egui::Pos2 { text_pos + egui::vec2(lp.x * cos_a - lp.y * sin_a, lp.x * sin_a + lp.y * cos_a) };
+                                    let sel = if is_editing { text_sel_anchor.map(|a| (a.min(text_cursor), a.max(text_cursor))) } else { None };
+                                    for (ci, (_, col)) in tl.vertical_columns().iter().enumerate() {
+                                        let col_x = width - (ci + 1) as f32 * colw;
+                                        for (ri, &(b, ch)) in col.iter().enumerate() {
+                                            let cell = egui::Rect::from_min_size(egui::pos2(col_x, ri as f32 * step), egui::vec2(colw, step));
+                                            if sel.is_some_and(|(lo, hi)| b >= lo && b < hi) {
+                                                let corners = [cell.left_top(), cell.right_top(), cell.right_bottom(), cell.left_bottom()].map(to_canvas);
+                                                painter.add(egui::Shape::convex_polygon(corners.to_vec(), egui::Color32::from_rgba_unmultiplied(100, 140, 255, 80), egui::Stroke::NONE));
+                                            }
+                                            let g = painter.layout_no_wrap(ch.to_string(), font_id.clone(), draw_color);
+                                            let mut glyph_shape = egui::epaint::TextShape::new(to_canvas(egui::pos2(col_x + (colw - g.size().x) / 2.0, ri as f32 * step)), g, draw_color);
+                                            glyph_shape.angle = angle_rad;
+                                            painter.add(egui::Shape::Text(glyph_shape));
+                                        }
+                                        if tl.underline && !col.is_empty() {
+                                            painter.line_segment([to_canvas(egui::pos2(col_x + colw, 0.0)), to_canvas(egui::pos2(col_x + colw, col.len() as f32 * step))],
+                                                egui::Stroke::new((font_size_screen * 0.06).max(1.0), draw_color));
+                                        }
+                                    }
+                                    if is_editing && caret_on {
+                                        let (ci, ri) = tl.vertical_caret_cell(text_cursor);
+                                        let (x0, y) = (width - (ci + 1) as f32 * colw, ri as f32 * step);
+                                        painter.line_segment([to_canvas(egui::pos2(x0, y)), to_canvas(egui::pos2(x0 + colw, y))], egui::Stroke::new(2.0, layer_color));
+                                    }
+                                } else if let Some(galley) = text_galleys.get(&tid).cloned() {
+                                    let caret_map = self.text_caret_maps.get(&tid);
                                     let mut text_shape = egui::epaint::TextShape::new(text_pos, galley.clone(), draw_color);
                                     text_shape.angle = angle_rad;
 
@@ -1114,6 +2099,9 @@ impl ImageEditor {
                                         };
                                         let glyph_pos_for = |byte_off: usize| -> egui::Pos2 {
                                             let char_idx = content_snap[..byte_off.min(content_snap.len())].chars().count();
+                                            if let Some(&(d, right)) = caret_map.and_then(|m| m.get(char_idx)) {
+                                                return galley.pos_from_cursor(egui::text::CCursor::new(d + right as usize)).min;
+                                            }
                                             let mut ci = 0usize;
                                             for row in &galley.rows {
                                                 for g in &row.glyphs {
@@ -1124,7 +2112,17 @@ impl ImageEditor {
                                             }
                                             galley.rows.last().map(|r: &egui::epaint::text::PlacedRow| egui::pos2(r.rect().max.x, r.rect().min.y)).unwrap_or(egui::pos2(0.0, 0.0))
                                         };
-                                        if let Some(anchor_sel) = sel_anchor_opt {
+                                        if let (Some(anchor_sel), Some(map)) = (sel_anchor_opt, caret_map) {
+                                            let (lo, hi) = (anchor_sel.min(cursor_byte), anchor_sel.max(cursor_byte));
+                                            let char_lo = content_snap[..lo.min(content_snap.len())].chars().count();
+                                            let char_hi = content_snap[..hi.min(content_snap.len())].chars().count();
+                                            for &(d, _) in map.get(char_lo..char_hi.min(map.len().saturating_sub(1))).unwrap_or(&[]) {
+                                                let (a, b) = (galley.pos_from_cursor(egui::text::CCursor::new(d)), galley.pos_from_cursor(egui::text::CCursor::new(d + 1)));
+                                                if (a.min.y - b.min.y).abs() > 0.5 { continue; }
+                                                let corners = [a.min, b.min, egui::pos2(b.min.x, b.max.y), egui::pos2(a.min.x, a.max.y)].map(galley_to_canvas);
+                                                painter.add(egui::Shape::convex_polygon(corners.to_vec(), egui::Color32::from_rgba_unmultiplied(100, 140, 255, 80), egui::Stroke::NONE));
+                                            }
+                                        } else if let Some(anchor_sel) = sel_anchor_opt {
                                             let (lo, hi) = (anchor_sel.min(cursor_byte), anchor_sel.max(cursor_byte));
                                             let char_lo = content_snap[..lo.min(content_snap.len())].chars().count();
                                             let char_hi = content_snap[..hi.min(content_snap.len())].chars().count();
@@ -1147,8 +2145,7 @@ impl ImageEditor {
                                                 ci = row_end;
                                             }
                                         }
-                                        let blink = (ctx.input(|i: &egui::InputState| i.time) * 2.0) as u32 % 2 == 0;
-                                        if blink {
+                                        if caret_on {
                                             let lp = glyph_pos_for(cursor_byte);
                                             let row_h = galley.rows.iter()
                                                 .find(|r| r.rect().min.y <= lp.y && lp.y <= r.rect().max.y)
@@ -1157,19 +2154,56 @@ impl ImageEditor {
                                                 [galley_to_canvas(lp), galley_to_canvas(egui::pos2(lp.x, lp.y + row_h))],
                                                 egui::Stroke::new(2.0, layer_color));
                                         }
-                                        ctx.request_repaint_after(std::time::Duration::from_millis(500));
                                     }
                                     painter.add(egui::Shape::Text(text_shape));
                                 }
                             }
                         }
                     }
+                    LayerKind::Shape => {
+                        if let Some(shape) = linked_id.and_then(|sid| self.shape_layers.iter().find(|s| s.id == sid)) {
+                            shape.paint(&painter, self.image_to_screen(0.0, 0.0), zoom, *layer_opacity);
+                        }
+                    }
                 }
             }
         }
 
+        if let (Some(view), Some(tid)) = (self.channel_view, self.ensure_channel_view_texture(ctx)) {
+            let (img_w, img_h) = self.image.as_ref().map(|i| (i.width() as f32, i.height() as f32)).unwrap_or((1.0, 1.0));
+            let img_rect = egui::Rect::from_min_size(self.image_to_screen(0.0, 0.0), egui::vec2(img_w * self.zoom, img_h * self.zoom));
+            painter.image(tid, img_rect, egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)), egui::Color32::WHITE);
+            let badge = format!("{} channel", view.label());
+            let galley = painter.layout_no_wrap(badge, egui::FontId::proportional(12.0), egui::Color32::WHITE);
+            let badge_rect = egui::Rect::from_min_size(canvas_rect.min + egui::vec2(10.0, 10.0), galley.size() + egui::vec2(16.0, 8.0));
+            painter.rect_filled(badge_rect, 4.0, egui::Color32::from_black_alpha(180));
+            painter.galley(badge_rect.min + egui::vec2(8.0, 4.0), galley, egui::Color32::WHITE);
+        }
+
+        if let Some(c) = &self.comparison {
+            let badge = match (&c.diff, &c.texture) {
+                (Some(diff), Some((_, tex))) => {
+                    let diff_rect = egui::Rect::from_min_size(self.image_to_screen(0.0, 0.0), egui::vec2(diff.w as f32 * self.zoom, diff.h as f32 * self.zoom));
+                    painter.image(tex.id(), diff_rect, egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)), egui::Color32::WHITE);
+                    if let Some([x0, y0, x1, y1]) = diff.bounds {
+                        let r = egui::Rect::from_min_max(self.image_to_screen(x0 as f32, y0 as f32), self.image_to_screen(x1 as f32, y1 as f32));
+                        painter.rect_stroke(r, 0.0, egui::Stroke::new(1.5, ColorPalette::PURPLE_400), egui::StrokeKind::Outside);
+                    }
+                    format!("Compared with saved ({}): {}  ·  Esc to exit", c.mode.label(), diff.summary())
+                }
+                _ => "Loading saved version...".to_string(),
+            };
+            let galley = painter.layout_no_wrap(badge, egui::FontId::proportional(12.0), egui::Color32::WHITE);
+            let size = galley.size() + egui::vec2(16.0, 8.0);
+            let badge_rect = egui::Rect::from_min_size(canvas_rect.left_bottom() + egui::vec2(10.0, -10.0 - size.y), size);
+            painter.rect_filled(badge_rect, 4.0, egui::Color32::from_black_alpha(180));
+            painter.galley(badge_rect.min + egui::vec2(8.0, 4.0), galley, egui::Color32::WHITE);
+        }
+
+        if self.perf.overlay { self.draw_diagnostics(&painter, canvas_rect); }
+
         if let Some(sel_tid) = self.selected_text {
-            if let Some(tl) = self.text_layers.iter().find(|t| t.id == sel_tid) {
+            if let Some(tl) = self.text_layers.iter().find(|t| t.id == sel_tid && self.text_shown(t)) {
                 let anchor = self.image_to_screen(tl.img_x, tl.img_y);
                 let sel_rect = tl.screen_rect(anchor, self.zoom);
                 let angle_rad = tl.rotation.to_radians();
@@ -1177,10 +2211,20 @@ impl ImageEditor {
                     .draw(&painter, ColorPalette::BLUE_400);
             }
         }
+        if let Some(handles) = self.shape_transform_handles() { handles.draw(&painter, ColorPalette::BLUE_400); }
+        for tl in self.text_layers.iter().filter(|t| t.font_substitute.is_some() && !self.font_relinks.contains_key(&t.font_name)) {
+            let rect = tl.screen_rect(self.image_to_screen(tl.img_x, tl.img_y), self.zoom);
+            let (sin_a, cos_a) = tl.rotation.to_radians().sin_cos();
+            let d = rect.right_top() - rect.center();
+            let corner = rect.center() + egui::vec2(d.x * cos_a - d.y * sin_a, d.x * sin_a + d.y * cos_a);
+            painter.circle_filled(corner, 8.0, ColorPalette::AMBER_500);
+            painter.text(corner, egui::Align2::CENTER_CENTER, "!", egui::FontId::proportional(11.0), egui::Color32::BLACK);
+        }
 
         let dropped_files = ctx.input(|i| i.raw.dropped_files.clone());
         for dropped in dropped_files {
             if let Some(path) = &dropped.path {
+                if !self.accepts_dropped_file(path) { continue; }
                 let img_opt = image::ImageReader::open(path)
                     .ok()
                     .and_then(|r| r.with_guessed_format().ok())
@@ -1216,34 +2260,139 @@ impl ImageEditor {
                 painter.rect_stroke(crop_rect, 0.0, egui::Stroke::new(2.0, ColorPalette::BLUE_400), egui::StrokeKind::Outside);
                 draw_crop_handles(&painter, crop_rect, ColorPalette::BLUE_400);
 
-                let (img_w, img_h) = self.image.as_ref().map(|i| (i.width() as f32, i.height() as f32)).unwrap_or((1.0, 1.0));
-                let min_img = egui::pos2(s.0.min(e.0).clamp(0.0, img_w), s.1.min(e.1).clamp(0.0, img_h));
-                let max_img = egui::pos2(s.0.max(e.0).clamp(0.0, img_w), s.1.max(e.1).clamp(0.0, img_h));
-                let pw = (max_img.x - min_img.x).round() as u32;
-                let ph = (max_img.y - min_img.y).round() as u32;
+                let (pw, ph) = self.crop_rect().map_or((0, 0), |[x0, y0, x1, y1]| (x1 - x0, y1 - y0));
                 let label = format!("{} x {}", pw, ph);
+                let fits = self.dimension_snap.fits(pw) && self.dimension_snap.fits(ph);
                 let raw_tp = egui::pos2(crop_rect.min.x + 4.0, crop_rect.min.y - 18.0);
                 let text_pos = egui::pos2(raw_tp.x.max(canvas_rect.min.x + 4.0), raw_tp.y.max(canvas_rect.min.y + 4.0));
 
                 painter.text(text_pos + egui::vec2(1.0, 1.0), egui::Align2::LEFT_TOP, &label, egui::FontId::proportional(12.0), egui::Color32::from_black_alpha(160));
-                painter.text(text_pos, egui::Align2::LEFT_TOP, &label, egui::FontId::proportional(12.0), egui::Color32::WHITE);
+                painter.text(text_pos, egui::Align2::LEFT_TOP, &label, egui::FontId::proportional(12.0), if fits { egui::Color32::WHITE } else { ColorPalette::RED_400 });
+            }
+        }
+
+        let ants = |painter: &egui::Painter, pts: &[egui::Pos2], phase: f32| {
+            painter.line_segment([pts[0], pts[1]], egui::Stroke::new(1.0, egui::Color32::BLACK));
+            painter.extend(egui::Shape::dashed_line_with_offset(pts, egui::Stroke::new(1.0, egui::Color32::WHITE), &[4.0], &[4.0], phase));
+        };
+        let phase = (ctx.input(|i| i.time) * 8.0) as f32 % 8.0;
+        if let Some(((x, y), (w, h))) = self.selection_state.floating.as_ref().map(|f| (f.offset, f.pixels.dimensions())) {
+            let r = egui::Rect::from_min_max(self.image_to_screen(x as f32, y as f32), self.image_to_screen((x + w as i32) as f32, (y + h as i32) as f32));
+            if let Some(f) = &mut self.selection_state.floating {
+                let tex = f.texture.get_or_insert_with(|| ctx.load_texture("floating_selection", egui::ColorImage::from_rgba_unmultiplied([w as usize, h as usize], f.pixels.as_raw()), egui::TextureOptions::NEAREST));
+                painter.image(tex.id(), r, egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)), egui::Color32::WHITE);
+            }
+            for side in [[r.left_top(), r.right_top()], [r.right_top(), r.right_bottom()], [r.right_bottom(), r.left_bottom()], [r.left_bottom(), r.left_top()]] { ants(&painter, &side, phase); }
+            ctx.request_repaint_after(std::time::Duration::from_millis(120));
+        }
+        if let Some((a, b)) = self.selection_state.marquee {
+            let r = egui::Rect::from_two_pos(self.image_to_screen(a.0, a.1), self.image_to_screen(b.0, b.1));
+            for side in [[r.left_top(), r.right_top()], [r.right_top(), r.right_bottom()], [r.right_bottom(), r.left_bottom()], [r.left_bottom(), r.left_top()]] { ants(&painter, &side, phase); }
+        }
+
+        if let Some(sel) = &self.selection {
+            for &[(ax, ay), (bx, by)] in sel.outline() {
+                let (a, b) = (self.image_to_screen(ax as f32, ay as f32), self.image_to_screen(bx as f32, by as f32));
+                if !canvas_rect.intersects(egui::Rect::from_two_pos(a, b)) { continue; }
+                ants(&painter, &[a, b], phase);
+            }
+            ctx.request_repaint_after(std::time::Duration::from_millis(120));
+        }
+
+        if self.tool == Tool::Slice {
+            for (i, sl) in self.slices.iter().enumerate() {
+                let r = egui::Rect::from_min_max(self.image_to_screen(sl.x, sl.y), self.image_to_screen(sl.x + sl.w, sl.y + sl.h));
+                let selected = self.selected_slice == Some(i);
+                let col = if selected { ColorPalette::BLUE_400 } else { ColorPalette::AMBER_400 };
+                painter.rect_filled(r, 0.0, col.gamma_multiply(0.12));
+                painter.rect_stroke(r, 0.0, egui::Stroke::new(if selected { 2.0 } else { 1.5 }, col), egui::StrokeKind::Outside);
+                let tp = egui::pos2(r.min.x + 4.0, r.min.y + 3.0);
+                painter.text(tp + egui::vec2(1.0, 1.0), egui::Align2::LEFT_TOP, &sl.name, egui::FontId::proportional(11.0), egui::Color32::from_black_alpha(180));
+                painter.text(tp, egui::Align2::LEFT_TOP, &sl.name, egui::FontId::proportional(11.0), egui::Color32::WHITE);
+                if selected { draw_crop_handles(&painter, r, col); }
+            }
+            if let Some((a, b)) = self.slice_draft {
+                let r = egui::Rect::from_two_pos(self.image_to_screen(a.0, a.1), self.image_to_screen(b.0, b.1));
+                painter.rect_stroke(r, 0.0, egui::Stroke::new(1.5, ColorPalette::BLUE_400), egui::StrokeKind::Outside);
+                let label = format!("{} x {}", (a.0 - b.0).abs().round(), (a.1 - b.1).abs().round());
+                painter.text(egui::pos2(r.min.x, r.min.y - 16.0), egui::Align2::LEFT_TOP, label, egui::FontId::proportional(11.0), egui::Color32::WHITE);
+            }
+        }
+
+        if self.show_guides {
+            let stroke = egui::Stroke::new(1.0, ColorPalette::TEAL_400);
+            for g in &self.guides {
+                let r = egui::Rect::from_min_max(self.image_to_screen(g.x, g.y), self.image_to_screen(g.x + g.w, g.y + g.h));
+                painter.extend(egui::Shape::dashed_line(&[r.left_top(), r.right_top(), r.right_bottom(), r.left_bottom(), r.left_top()], stroke, 6.0, 4.0));
+                painter.text(r.min + egui::vec2(4.0, 3.0), egui::Align2::LEFT_TOP, &g.name, egui::FontId::proportional(11.0), ColorPalette::TEAL_400);
+            }
+        }
+
+        let measures: Vec<Measurement> = self.measurements.iter().copied().chain(self.measure_active).collect();
+        for (i, m) in measures.iter().enumerate() {
+            let live = self.measure_active.is_some() && i + 1 == measures.len();
+            let col = if live { ColorPalette::BLUE_400 } else { ColorPalette::AMBER_400 };
+            let (a, b) = (self.image_to_screen(m.a.0, m.a.1), self.image_to_screen(m.b.0, m.b.1));
+            let c = m.c.map(|c| self.image_to_screen(c.0, c.1));
+            for stroke in [egui::Stroke::new(3.0, egui::Color32::from_black_alpha(140)), egui::Stroke::new(1.5, col)] {
+                painter.line_segment([a, b], stroke);
+                if let Some(c) = c { painter.line_segment([b, c], stroke); }
+            }
+            for p in [Some(a), Some(b), c].into_iter().flatten() { painter.circle(p, 3.0, col, egui::Stroke::new(1.0, egui::Color32::BLACK)); }
+            let (dx, dy) = m.delta();
+            let mut label = format!("{}\n{:.1}°  ({:.0}, {:.0})", Measurement::format_length(m.length(), self.measure_dpi), m.angle(), dx, dy);
+            if let Some(angle) = m.vertex_angle() { label.push_str(&format!("\n∠ {:.1}°", angle)); }
+            let tp = egui::pos2((a.x + b.x) / 2.0 + 8.0, (a.y + b.y) / 2.0 + 8.0);
+            painter.text(tp + egui::vec2(1.0, 1.0), egui::Align2::LEFT_TOP, &label, egui::FontId::proportional(11.0), egui::Color32::from_black_alpha(180));
+            painter.text(tp, egui::Align2::LEFT_TOP, &label, egui::FontId::proportional(11.0), egui::Color32::WHITE);
+        }
+
+        if let Some(shape) = self.shape_tool_shape(ctx.input(|i| i.modifiers.shift)) {
+            shape.paint(&painter, self.image_to_screen(0.0, 0.0), self.zoom, 1.0);
+        }
+
+        if let Some((s, e)) = self.fill_gradient.filter(|_| self.tool == Tool::Fill) {
+            let (sp, ep) = (self.image_to_screen(s.0, s.1), self.image_to_screen(e.0, e.1));
+            for stroke in [egui::Stroke::new(3.0, egui::Color32::from_black_alpha(160)), egui::Stroke::new(1.5, egui::Color32::WHITE)] {
+                painter.line_segment([sp, ep], stroke);
+                if self.fill_mode == FillMode::RadialGradient { painter.circle_stroke(sp, sp.distance(ep), stroke); }
+            }
+            painter.circle(sp, 4.0, self.color, egui::Stroke::new(1.5, egui::Color32::WHITE));
+            painter.circle(ep, 4.0, self.secondary_color, egui::Stroke::new(1.5, egui::Color32::WHITE));
+        }
+
+        if let Some((kx, ky)) = self.kb_cursor.filter(|_| matches!(self.tool, Tool::Brush | Tool::Eraser | Tool::Retouch | Tool::Fill | Tool::Eyedropper)) {
+            let kp = self.image_to_screen(kx + 0.5, ky + 0.5);
+            let radius = match self.tool { Tool::Brush => self.brush.size, Tool::Eraser => self.eraser_size, Tool::Retouch => self.retouch_size, _ => 0.0 } / 2.0 * self.zoom;
+            let arm = (radius + 6.0).max(10.0);
+            for stroke in [egui::Stroke::new(3.0, egui::Color32::from_black_alpha(160)), egui::Stroke::new(1.5, egui::Color32::WHITE)] {
+                painter.line_segment([kp - egui::vec2(arm, 0.0), kp + egui::vec2(arm, 0.0)], stroke);
+                painter.line_segment([kp - egui::vec2(0.0, arm), kp + egui::vec2(0.0, arm)], stroke);
+                if radius > 0.0 { painter.circle_stroke(kp, radius, stroke); }
             }
         }
 
         let mouse_pos: Option<egui::Pos2> = ui.input(|i: &egui::InputState| i.pointer.latest_pos());
+        let mut fill_hover = None;
         if let Some(mp) = mouse_pos {
-            let over_picker: bool = self.show_color_picker && self.color_picker_rect.map_or(false, |r| r.contains(mp));
-            let over_filter: bool = self.filter_panel != FilterPanel::None && self.filter_panel_rect.map_or(false, |r| r.contains(mp));
-            let over_modal: bool = over_picker || over_filter;
-            if response.hovered() && !over_modal {
+            let over_modal: bool = self.over_modal_panel(mp) || !canvas_rect.contains(mp);
+            if response.hovered() && !over_modal && self.space_pan {
+                ctx.set_cursor_icon(if response.dragged() { egui::CursorIcon::Grabbing } else { egui::CursorIcon::Grab });
+            } else if response.hovered() && !over_modal {
                 match self.tool {
-                    Tool::Brush | Tool::Eraser => ctx.set_cursor_icon(egui::CursorIcon::None),
-                    Tool::Fill | Tool::Eyedropper | Tool::Crop => ctx.set_cursor_icon(egui::CursorIcon::Crosshair),
+                    Tool::Brush | Tool::Eraser | Tool::Fill | Tool::Eyedropper | Tool::Crop => ctx.set_cursor_icon(egui::CursorIcon::None),
+                    Tool::Wand if self.wand_job.is_some() => ctx.set_cursor_icon(egui::CursorIcon::Progress),
+                    Tool::Slice | Tool::Measure | Tool::Wand | Tool::Line | Tool::Rectangle | Tool::Ellipse => ctx.set_cursor_icon(egui::CursorIcon::Crosshair),
+                    Tool::Select => {
+                        let p = self.screen_to_image_f32(mp);
+                        let grabbable = self.floating_contains(p) || (self.selection_state.floating.is_none() && self.selection_contains(p));
+                        ctx.set_cursor_icon(if grabbable { egui::CursorIcon::Move } else { egui::CursorIcon::Crosshair });
+                    }
                     Tool::Pan => {
                         let dragging = response.dragged_by(egui::PointerButton::Primary);
                         if let Some(h) = self.image_layer_transform_handles().and_then(|hs| hs.hit_test(mp)) {
                             ctx.set_cursor_icon(TransformHandleSet::cursor_for(h));
-                        } else if let Some(h) = self.text_transform_handles().and_then(|hs| hs.hit_test(mp)) {
+                        } else if let Some(h) = self.text_transform_handles().or_else(|| self.shape_transform_handles()).and_then(|hs| hs.hit_test(mp)) {
                             ctx.set_cursor_icon(TransformHandleSet::cursor_for(h));
                         } else {
                             let over_image = self.layers.iter().any(|l| l.kind == LayerKind::Image && l.visible && l.linked_image_id.map_or(false, |iid| {
@@ -1252,7 +2401,7 @@ impl ImageEditor {
                                     ild.hit_test(mp, img_w, img_h, canvas_rect, self.zoom, self.pan)
                                 })
                             }));
-                            let over_text = self.hit_text_layer(mp).is_some();
+                            let over_text = self.hit_text_layer(mp).is_some() || self.hit_shape_layer(mp).is_some();
                             ctx.set_cursor_icon(if dragging { egui::CursorIcon::Grabbing } else if over_image || over_text { egui::CursorIcon::PointingHand } else { egui::CursorIcon::AllScroll });
                         }
                     }
@@ -1260,8 +2409,22 @@ impl ImageEditor {
                     Tool::Retouch => ctx.set_cursor_icon(egui::CursorIcon::None),
                 }
                 match self.tool {
-                    Tool::Brush  => { painter.circle_stroke(mp, self.brush.size  / 2.0 * self.zoom, egui::Stroke::new(1.5, self.color)); }
-                    Tool::Eraser => { painter.circle_stroke(mp, self.eraser_size / 2.0 * self.zoom, egui::Stroke::new(1.5, ColorPalette::RED_400)); }
+                    Tool::Brush | Tool::Eraser => {
+                        let eraser = self.tool == Tool::Eraser;
+                        let (shape, radius) = if eraser { (BrushShape::Circle, self.eraser_size / 2.0) } else { (self.brush.shape, self.brush.size / 2.0) };
+                        let angle = if eraser { 0.0 } else { self.brush.angle.to_radians() };
+                        let outline = self.settings.cursor_outline;
+                        let stroke = outline.stroke(self.cursor_outline_color(mp, eraser));
+                        let center = self.stamp_center_on_screen(&painter, mp);
+                        if radius * self.zoom < 1.5 {
+                            let tick = 5.0;
+                            painter.line_segment([center - egui::vec2(tick, 0.0), center + egui::vec2(tick, 0.0)], egui::Stroke::new(outline.width.min(1.0), stroke.color));
+                            painter.line_segment([center - egui::vec2(0.0, tick), center + egui::vec2(0.0, tick)], egui::Stroke::new(outline.width.min(1.0), stroke.color));
+                        } else {
+                            let pts: Vec<egui::Pos2> = brush_footprint(shape, radius, self.brush.aspect_ratio.clamp(0.05, 1.0), angle).into_iter().map(|v| center + v * self.zoom).collect();
+                            painter.add(egui::Shape::closed_line(pts, stroke));
+                        }
+                    }
                     Tool::Retouch => {
                         let r: f32 = self.retouch_size / 2.0 * self.zoom;
                         painter.circle_stroke(mp, r, egui::Stroke::new(1.5, ColorPalette::PURPLE_400));
@@ -1274,29 +2437,48 @@ impl ImageEditor {
                             if let Some(h) = handles.hit_test(mp) { ctx.set_cursor_icon(TransformHandleSet::cursor_for(h)); }
                         }
                     }
-                    Tool::Crop => {
-                        if let (Some(s), Some(e)) = (self.crop_state.start, self.crop_state.end) {
-                            let p0 = self.image_to_screen(s.0, s.1);
-                            let p1 = self.image_to_screen(e.0, e.1);
-                            let cr = egui::Rect::from_two_pos(p0, p1);
-                            if let Some(h) = crop_hit_handle(mp, cr) {
-                                ctx.set_cursor_icon(match h {
-                                    THandle::Move => egui::CursorIcon::Move,
-                                    THandle::N | THandle::S => egui::CursorIcon::ResizeVertical,
-                                    THandle::E | THandle::W => egui::CursorIcon::ResizeHorizontal,
-                                    THandle::NE | THandle::SW => egui::CursorIcon::ResizeNeSw,
-                                    THandle::NW | THandle::SE => egui::CursorIcon::ResizeNwSe,
-                                    _ => egui::CursorIcon::Crosshair,
-                                });
-                            }
+                    Tool::Fill => {
+                        if self.fill_gradient.is_none() { fill_hover = Some(mp); }
+                        if let Some(p) = &self.fill_preview && let (Some(m), Some(tex)) = (&p.mask, &p.texture) && self.screen_to_image(mp) == Some((p.key.2, p.key.3)) {
+                            let r = egui::Rect::from_min_max(self.image_to_screen(m.x0 as f32, m.y0 as f32), self.image_to_screen((m.x0 + m.w) as f32, (m.y0 + m.h) as f32));
+                            painter.image(tex.id(), r, egui::Rect::from_min_max(egui::Pos2::ZERO, egui::pos2(1.0, 1.0)), egui::Color32::WHITE);
+                        }
+                        let stroke = self.settings.cursor_outline.stroke(self.cursor_outline_color(mp, false));
+                        draw_glyph(&painter, bucket_paths(snap(&painter, mp)), stroke, self.color);
+                    }
+                    Tool::Eyedropper => {
+                        let stroke = self.settings.cursor_outline.stroke(self.cursor_outline_color(mp, false));
+                        let under = self.screen_to_image(mp).map(|(x, y)| self.composite_pixel_at(x, y)).unwrap_or([0; 4]);
+                        draw_glyph(&painter, eyedropper_paths(snap(&painter, mp)), stroke, egui::Color32::from_rgb(under[0], under[1], under[2]));
+                    }
+                    Tool::Crop | Tool::Slice => {
+                        let target = if self.tool == Tool::Slice {
+                            self.selected_slice.and_then(|i| self.slices.get(i)).map(|sl| ((sl.x, sl.y), (sl.x + sl.w, sl.y + sl.h)))
+                        } else { self.crop_state.start.zip(self.crop_state.end) };
+                        let handle = target.and_then(|(s, e)| crop_hit_handle(mp, egui::Rect::from_two_pos(self.image_to_screen(s.0, s.1), self.image_to_screen(e.0, e.1))));
+                        if let Some(h) = handle {
+                            ctx.set_cursor_icon(match h {
+                                THandle::Move => egui::CursorIcon::Move,
+                                THandle::N | THandle::S => egui::CursorIcon::ResizeVertical,
+                                THandle::E | THandle::W => egui::CursorIcon::ResizeHorizontal,
+                                THandle::NE | THandle::SW => egui::CursorIcon::ResizeNeSw,
+                                THandle::NW | THandle::SE => egui::CursorIcon::ResizeNwSe,
+                                _ => egui::CursorIcon::Crosshair,
+                            });
+                        } else if self.tool == Tool::Crop {
+                            draw_crosshair(&painter, snap(&painter, mp), self.settings.cursor_outline.stroke(self.cursor_outline_color(mp, false)));
                         }
                     }
                     _ => {}
                 }
+                if self.loupe_visible(ctx) { self.draw_loupe(&painter, rect, mp, checker_tid); }
             }
         }
+        self.update_fill_preview(ctx, fill_hover);
 
-        if response.drag_started_by(egui::PointerButton::Primary) && self.tool == Tool::Retouch {
+        let space_pan = self.space_pan;
+
+        if !space_pan && response.drag_started_by(egui::PointerButton::Primary) && self.tool == Tool::Retouch {
             let pos: egui::Pos2 = response.interact_pointer_pos().unwrap_or(canvas_rect.center());
             if self.image_layer_for_active().is_some() {
                 let (img_w, img_h) = self.image.as_ref().map(|i| (i.width() as f32, i.height() as f32)).unwrap_or((1.0, 1.0));
@@ -1310,7 +2492,7 @@ impl ImageEditor {
             }
         }
 
-        if response.drag_started_by(egui::PointerButton::Primary) {
+        if !space_pan && response.drag_started_by(egui::PointerButton::Primary) {
             let pos: egui::Pos2 = response.interact_pointer_pos().unwrap_or(canvas_rect.center());
             if let Some(iid) = self.selected_image_layer {
                 let allow_move = self.tool == Tool::Pan;
@@ -1320,7 +2502,7 @@ impl ImageEditor {
                         if use_handle {
                             if let Some(ild) = self.image_layer_data.get(&iid) {
                                 let rot_start = (pos - handles.rect.center()).angle();
-                                self.image_drag = Some(ImageDrag {
+                                self.image_drag = Some(BoxDrag {
                                     handle: h, start: pos,
                                     orig_x: ild.canvas_x, orig_y: ild.canvas_y,
                                     orig_w: ild.display_w, orig_h: ild.display_h,
@@ -1333,7 +2515,7 @@ impl ImageEditor {
             }
         }
 
-        if response.drag_started_by(egui::PointerButton::Primary) && self.tool == Tool::Crop {
+        if !space_pan && response.drag_started_by(egui::PointerButton::Primary) && self.tool == Tool::Crop {
             let pos = response.interact_pointer_pos().unwrap_or(canvas_rect.center());
             let handle_hit = if let (Some(s), Some(e)) = (self.crop_state.start, self.crop_state.end) {
                 let p0 = self.image_to_screen(s.0, s.1);
@@ -1354,18 +2536,73 @@ impl ImageEditor {
             }
         }
 
-        if response.drag_started_by(egui::PointerButton::Primary) && (self.tool == Tool::Text || self.tool == Tool::Pan) {
+        if !space_pan && response.drag_started_by(egui::PointerButton::Primary) && self.tool == Tool::Fill && self.fill_mode != FillMode::Solid {
+            if self.base_frozen() { self.pixels_blocked(); } else {
+                let pos = ui.input(|i| i.pointer.press_origin()).or(response.interact_pointer_pos()).unwrap_or(canvas_rect.center());
+                let p = self.screen_to_image_f32(pos);
+                self.fill_gradient = Some((p, p));
+            }
+        }
+
+        if !space_pan && response.drag_started_by(egui::PointerButton::Primary) && ShapeKind::for_tool(self.tool).is_some() {
+            if self.base_frozen() { self.pixels_blocked(); } else {
+                let pos = ui.input(|i| i.pointer.press_origin()).or(response.interact_pointer_pos()).unwrap_or(canvas_rect.center());
+                let p = self.screen_to_image_f32(pos);
+                self.shape_tool_drag = Some((p, p));
+            }
+        }
+
+        if !space_pan && response.drag_started_by(egui::PointerButton::Primary) && self.tool == Tool::Select {
+            let pos = ui.input(|i| i.pointer.press_origin()).or(response.interact_pointer_pos()).unwrap_or(canvas_rect.center());
+            let p = self.screen_to_image_f32(pos);
+            let op = SelectOp::from_modifiers(ui.input(|i| i.modifiers));
+            if !self.floating_contains(p) { self.commit_floating_selection(); }
+            let grab = self.floating_contains(p) || (op == SelectOp::Replace && self.selection_contains(p) && self.lift_selection());
+            match self.selection_state.floating.as_ref().filter(|_| grab) {
+                Some(f) => self.selection_state.move_from = Some((p, f.offset)),
+                None => self.selection_state.marquee = Some((p, p)),
+            }
+        }
+
+        if !space_pan && response.drag_started_by(egui::PointerButton::Primary) && self.tool == Tool::Measure {
+            let pos = ui.input(|i| i.pointer.press_origin()).or(response.interact_pointer_pos()).unwrap_or(canvas_rect.center());
+            self.begin_measurement(self.screen_to_image_f32(pos));
+        }
+
+        if !space_pan && response.drag_started_by(egui::PointerButton::Primary) && self.tool == Tool::Slice {
+            let pos = response.interact_pointer_pos().unwrap_or(canvas_rect.center());
+            let screen_rect = |sl: &ImageSlice| egui::Rect::from_min_max(self.image_to_screen(sl.x, sl.y), self.image_to_screen(sl.x + sl.w, sl.y + sl.h));
+            let handle_hit = self.selected_slice.and_then(|i| self.slices.get(i)).and_then(|sl| crop_hit_handle(pos, screen_rect(sl)));
+            let body_hit = self.slices.iter().rposition(|sl| screen_rect(sl).contains(pos));
+            self.slice_draft = None;
+            if let (Some(h), Some(i)) = (handle_hit, self.selected_slice) {
+                let sl = &self.slices[i];
+                self.slice_drag = Some((h, pos));
+                self.slice_drag_orig = Some((sl.x, sl.y, sl.w, sl.h));
+            } else if let Some(i) = body_hit {
+                let sl = &self.slices[i];
+                self.selected_slice = Some(i);
+                self.slice_drag = Some((THandle::Move, pos));
+                self.slice_drag_orig = Some((sl.x, sl.y, sl.w, sl.h));
+            } else {
+                self.selected_slice = None;
+                let p = self.screen_to_image_f32(pos);
+                self.slice_draft = Some((p, p));
+            }
+        }
+
+        if !space_pan && response.drag_started_by(egui::PointerButton::Primary) && (self.tool == Tool::Text || self.tool == Tool::Pan) {
             let pos: egui::Pos2 = response.interact_pointer_pos().unwrap_or(canvas_rect.center());
             self.text_drag = None;
-            if self.tool == Tool::Pan && self.selected_text.is_none() {
-                if let Some(hit) = self.hit_text_layer(pos) {
-                    if self.selected_text != Some(hit) { self.commit_or_discard_active_text(); }
-                    self.selected_text = Some(hit);
-                    self.selected_image_layer = None;
-                    self.image_drag = None;
-                    if let Some(linked_layer) = self.layers.iter().find(|l| l.linked_text_id == Some(hit)) {
-                        self.active_layer_id = linked_layer.id;
-                    }
+            if self.tool == Tool::Pan && self.selected_text.is_none() && !self.begin_shape_drag(pos)
+                && let Some(hit) = self.hit_text_layer(pos) {
+                if self.selected_text != Some(hit) { self.commit_or_discard_active_text(); }
+                self.selected_text = Some(hit);
+                self.selected_image_layer = None;
+                self.image_drag = None;
+                self.selected_shape = None;
+                if let Some(linked_layer) = self.layers.iter().find(|l| l.linked_text_id == Some(hit)) {
+                    self.active_layer_id = linked_layer.id;
                 }
             }
 
@@ -1388,45 +2625,29 @@ impl ImageEditor {
             }
         }
 
-        if response.dragged_by(egui::PointerButton::Primary) {
+        if !space_pan && response.dragged_by(egui::PointerButton::Primary) {
             let pos: egui::Pos2 = response.interact_pointer_pos().unwrap_or(canvas_rect.center());
 
-            if let Some(drag_data) = self.image_drag.as_ref().map(|d| (d.handle, d.start, d.orig_x, d.orig_y, d.orig_w, d.orig_h, d.orig_rotation, d.orig_rot_start_angle)) {
-                let (handle, drag_start, orig_x, orig_y, orig_w, orig_h, orig_rot, orig_rot_start) = drag_data;
-                let zoom = self.zoom;
-                if let Some(iid) = self.selected_image_layer {
-                    if let Some(ild) = self.image_layer_data.get_mut(&iid) {
-                        let aspect = ild.native_aspect();
-                        let aspect_lock = self.image_aspect_lock;
-                        let (img_w, img_h) = self.image.as_ref().map(|i| (i.width() as f32, i.height() as f32)).unwrap_or((1.0, 1.0));
-                        let canvas = self.canvas_rect.unwrap_or(egui::Rect::NOTHING);
-                        let ox = canvas.center().x - img_w * zoom / 2.0 + self.pan.x;
-                        let oy = canvas.center().y - img_h * zoom / 2.0 + self.pan.y;
-                        let anchor_screen = egui::pos2(ox + orig_x * zoom, oy + orig_y * zoom);
-                        let orig_w_screen = orig_w * zoom;
-                        let orig_h_screen = orig_h * zoom;
-                        let rot_center = anchor_screen + egui::vec2(orig_w_screen / 2.0, orig_h_screen / 2.0);
-                        let min_sz = 8.0_f32;
-                        match handle {
-                            THandle::Move => { let delta = pos - drag_start; ild.canvas_x = orig_x + delta.x / zoom; ild.canvas_y = orig_y + delta.y / zoom; }
-                            THandle::E => { ild.display_w = ((pos.x - anchor_screen.x).max(min_sz) / zoom).max(1.0); if aspect_lock { ild.display_h = (ild.display_w / aspect).max(1.0); } }
-                            THandle::W => { let right = anchor_screen.x + orig_w_screen; let nw = (right - pos.x).max(min_sz); ild.display_w = (nw / zoom).max(1.0); ild.canvas_x = (pos.x - ox) / zoom; if aspect_lock { ild.display_h = (ild.display_w / aspect).max(1.0); } }
-                            THandle::S => { ild.display_h = ((pos.y - anchor_screen.y).max(min_sz) / zoom).max(1.0); if aspect_lock { ild.display_w = (ild.display_h * aspect).max(1.0); } }
-                            THandle::N => { let bottom = anchor_screen.y + orig_h_screen; let nh = (bottom - pos.y).max(min_sz); ild.display_h = (nh / zoom).max(1.0); ild.canvas_y = (pos.y - oy) / zoom; if aspect_lock { ild.display_w = (ild.display_h * aspect).max(1.0); } }
-                            THandle::SE => { ild.display_w = ((pos.x - anchor_screen.x).max(min_sz) / zoom).max(1.0); ild.display_h = if aspect_lock { (ild.display_w / aspect).max(1.0) } else { ((pos.y - anchor_screen.y).max(min_sz) / zoom).max(1.0) }; }
-                            THandle::NE => { let bottom = anchor_screen.y + orig_h_screen; let nh = (bottom - pos.y).max(min_sz); ild.display_w = ((pos.x - anchor_screen.x).max(min_sz) / zoom).max(1.0); ild.display_h = if aspect_lock { (ild.display_w / aspect).max(1.0) } else { (nh / zoom).max(1.0) }; ild.canvas_y = if aspect_lock { orig_y + orig_h - ild.display_h } else { (pos.y - oy) / zoom }; }
-                            THandle::NW => { let right = anchor_screen.x + orig_w_screen; let bottom = anchor_screen.y + orig_h_screen; let nw = (right - pos.x).max(min_sz); let nh = (bottom - pos.y).max(min_sz); ild.display_w = (nw / zoom).max(1.0); ild.display_h = if aspect_lock { (ild.display_w / aspect).max(1.0) } else { (nh / zoom).max(1.0) }; ild.canvas_x = orig_x + orig_w - ild.display_w; ild.canvas_y = if aspect_lock { orig_y + orig_h - ild.display_h } else { ((bottom - nh) - (canvas_rect.center().y - img_h * zoom / 2.0 + self.pan.y)) / zoom }; }
-                            THandle::SW => { let right = anchor_screen.x + orig_w_screen; let nw = (right - pos.x).max(min_sz); ild.display_w = (nw / zoom).max(1.0); ild.canvas_x = orig_x + orig_w - ild.display_w; ild.display_h = if aspect_lock { (ild.display_w / aspect).max(1.0) } else { ((pos.y - anchor_screen.y).max(min_sz) / zoom).max(1.0) }; }
-                            THandle::Rotate => { let cur_angle = (pos - rot_center).angle(); ild.rotation = orig_rot + (cur_angle - orig_rot_start).to_degrees(); }
-                        }
-                        self.dirty = true;
-                    }
+            if self.shape_drag.is_some() {
+                self.drag_selected_shape(pos);
+            } else if let (Some(drag), Some(iid)) = (&self.image_drag, self.selected_image_layer) {
+                let origin = self.image_to_screen(0.0, 0.0);
+                let lock = self.image_aspect_lock != ui.input(|i| i.modifiers.shift);
+                if let Some(ild) = self.image_layer_data.get_mut(&iid) {
+                    let aspect = lock.then(|| ild.native_aspect());
+                    (ild.canvas_x, ild.canvas_y, ild.display_w, ild.display_h, ild.rotation) = drag.apply(pos, origin, self.zoom, aspect);
+                    self.dirty = true;
                 }
             } else {
             match self.tool {
+                Tool::Brush | Tool::Eraser | Tool::Retouch if self.base_frozen() => {
+                    let started = response.drag_started_by(egui::PointerButton::Primary);
+                    if started { self.pixels_blocked(); }
+                }
                 Tool::Brush | Tool::Eraser => {
                     if !self.is_dragging {
                         self.push_undo(); self.is_dragging = true; self.stroke_points.clear();
+                        self.perf.coarse = false; self.perf.stroke_path.clear(); self.stroke_blend = self.brush.blend_mode;
                         let aid = self.active_layer_id;
                         let needs_backdrop = self.tool == Tool::Brush && self.brush.wetness > 0.0
                             && self.layers.iter().find(|l| l.id == aid).map_or(false, |l| l.kind == LayerKind::Raster);
@@ -1441,14 +2662,14 @@ impl ImageEditor {
                         let cx = (pos.x - ox) / self.zoom; let cy = (pos.y - oy) / self.zoom;
                         self.stroke_points.push((cx, cy));
                         if self.stroke_points.len() >= 2 {
-                            self.apply_brush_stroke();
+                            self.timed_brush_stroke();
                             let last = *self.stroke_points.last().unwrap();
                             self.stroke_points.clear(); self.stroke_points.push(last);
                         }
                     } else if let Some((ix, iy)) = self.screen_to_image(pos) {
                         self.stroke_points.push((ix as f32, iy as f32));
                         if self.stroke_points.len() >= 2 {
-                            self.apply_brush_stroke();
+                            self.timed_brush_stroke();
                             let last: (f32, f32) = *self.stroke_points.last().unwrap();
                             self.stroke_points.clear(); self.stroke_points.push(last);
                         }
@@ -1509,13 +2730,68 @@ impl ImageEditor {
                                 }
                                 self.crop_state.start = Some(s);
                                 self.crop_state.end   = Some(e);
+                                let (x, y) = match handle {
+                                    THandle::N => (None, Some(true)), THandle::S => (None, Some(false)),
+                                    THandle::W => (Some(true), None), THandle::E => (Some(false), None),
+                                    THandle::NW => (Some(true), Some(true)), THandle::NE => (Some(false), Some(true)),
+                                    THandle::SW => (Some(true), Some(false)), THandle::SE => (Some(false), Some(false)),
+                                    _ => (None, None),
+                                };
+                                self.snap_crop(x, y);
                             }
                         }
                     } else if !response.drag_started_by(egui::PointerButton::Primary) {
                         if let Some((ix, iy)) = self.screen_to_image(pos) {
                             if self.crop_state.start.is_none() { self.crop_state.start = Some((ix as f32, iy as f32)); }
                             self.crop_state.end = Some((ix as f32, iy as f32));
+                            self.snap_crop(Some(false), Some(false));
+                        }
+                    }
+                }
+                Tool::Measure => {
+                    let shift = ui.input(|i| i.modifiers.shift);
+                    self.update_measurement(self.screen_to_image_f32(pos), shift);
+                }
+                Tool::Fill => {
+                    let end = self.screen_to_image_f32(pos);
+                    if let Some((_, e)) = &mut self.fill_gradient { *e = end; }
+                }
+                Tool::Line | Tool::Rectangle | Tool::Ellipse => {
+                    let end = self.screen_to_image_f32(pos);
+                    if let Some((_, e)) = &mut self.shape_tool_drag { *e = end; }
+                }
+                Tool::Select => {
+                    let p = self.screen_to_image_f32(pos);
+                    if let (Some((start, from)), Some(f)) = (self.selection_state.move_from, &mut self.selection_state.floating) {
+                        f.offset = (from.0 + (p.0 - start.0).round() as i32, from.1 + (p.1 - start.1).round() as i32);
+                    } else if let Some((_, e)) = &mut self.selection_state.marquee { *e = p; }
+                }
+                Tool::Slice => {
+                    let (img_w, img_h) = self.image.as_ref().map(|i| (i.width() as f32, i.height() as f32)).unwrap_or((1.0, 1.0));
+                    let (ix, iy) = self.screen_to_image_f32(pos);
+                    let (ix, iy) = (ix.clamp(0.0, img_w).round(), iy.clamp(0.0, img_h).round());
+                    if let (Some((handle, start)), Some((ox, oy, ow, oh)), Some(idx)) = (self.slice_drag, self.slice_drag_orig, self.selected_slice) {
+                        let (mut x0, mut y0, mut x1, mut y1) = (ox, oy, ox + ow, oy + oh);
+                        match handle {
+                            THandle::Move => {
+                                let d = (pos - start) / self.zoom;
+                                x0 = (ox + d.x).round().clamp(0.0, (img_w - ow).max(0.0)); y0 = (oy + d.y).round().clamp(0.0, (img_h - oh).max(0.0));
+                                x1 = x0 + ow; y1 = y0 + oh;
+                            }
+                            THandle::N => y0 = iy.min(y1 - 1.0),
+                            THandle::S => y1 = iy.max(y0 + 1.0),
+                            THandle::W => x0 = ix.min(x1 - 1.0),
+                            THandle::E => x1 = ix.max(x0 + 1.0),
+                            THandle::NW => { x0 = ix.min(x1 - 1.0); y0 = iy.min(y1 - 1.0); }
+                            THandle::NE => { x1 = ix.max(x0 + 1.0); y0 = iy.min(y1 - 1.0); }
+                            THandle::SW => { x0 = ix.min(x1 - 1.0); y1 = iy.max(y0 + 1.0); }
+                            THandle::SE => { x1 = ix.max(x0 + 1.0); y1 = iy.max(y0 + 1.0); }
+                            THandle::Rotate => {}
                         }
+                        if let Some(sl) = self.slices.get_mut(idx) { sl.x = x0; sl.y = y0; sl.w = x1 - x0; sl.h = y1 - y0; }
+                        self.dirty = true;
+                    } else if let Some((a, _)) = self.slice_draft {
+                        self.slice_draft = Some((a, (ix, iy)));
                     }
                 }
                 Tool::Text | Tool::Pan => {
@@ -1568,18 +2844,42 @@ impl ImageEditor {
             }
         }
 
+        if !space_pan && response.dragged_by(egui::PointerButton::Primary) && self.edge_drag_active()
+            && let Some(pos) = response.interact_pointer_pos().filter(|p| !self.over_modal_panel(*p)) { self.edge_autoscroll(ctx, canvas_rect, pos); }
+
         if response.drag_stopped_by(egui::PointerButton::Primary) {
             match self.tool {
-                Tool::Brush | Tool::Eraser | Tool::Retouch => { self.stroke_points.clear(); self.is_dragging = false; self.stroke_backdrop = None; }
+                Tool::Brush | Tool::Eraser | Tool::Retouch => { self.refine_coarse_stroke(); self.stroke_points.clear(); self.is_dragging = false; self.stroke_backdrop = None; }
                 Tool::Text | Tool::Pan => { if self.text_drag.is_some() { self.composite_dirty = true; } self.text_drag = None; }
                 Tool::Crop => { self.crop_drag = None; self.crop_drag_orig = None; }
+                Tool::Fill => self.finish_gradient_fill(),
+                Tool::Line | Tool::Rectangle | Tool::Ellipse => { let shift = ui.input(|i| i.modifiers.shift); self.commit_shape_tool(shift); }
+                Tool::Select => {
+                    self.selection_state.move_from = None;
+                    if let Some((a, b)) = self.selection_state.marquee.take() { self.select_marquee(a, b, SelectOp::from_modifiers(ui.input(|i| i.modifiers))); }
+                }
+                Tool::Slice => { self.slice_drag = None; self.slice_drag_orig = None; self.commit_slice_draft(); }
                 _ => {}
             }
             if self.image_drag.is_some() { self.image_drag = None; self.composite_dirty = true; self.dirty = true; }
+            if self.shape_drag.take().is_some() { self.composite_dirty = true; }
         }
 
-        if response.clicked_by(egui::PointerButton::Primary) {
-            let pos: egui::Pos2 = response.interact_pointer_pos().unwrap_or(canvas_rect.center());
+        if response.hovered() && ui.input(|i| i.pointer.delta() != egui::Vec2::ZERO) { self.kb_cursor = None; }
+        let kb_click = std::mem::take(&mut self.kb_click_pending) && self.kb_cursor.is_some();
+        let text_double_click = (!space_pan && !kb_click && self.tool != Tool::Text && response.double_clicked())
+            .then(|| response.interact_pointer_pos()).flatten()
+            .and_then(|pos| self.hit_text_layer(pos).map(|hit| (hit, pos)));
+        if let Some((hit, pos)) = text_double_click {
+            if self.click_undo_mark == Some(self.undo_pushes) { self.undo(); self.history.discard_redo(); }
+            self.tool = Tool::Text;
+            self.selected_image_layer = None;
+            self.edit_text_layer_at(hit, pos, false);
+        }
+        if (!space_pan && text_double_click.is_none() && response.clicked_by(egui::PointerButton::Primary)) || kb_click {
+            let pushes_before = self.undo_pushes;
+            let kb_pos = self.kb_cursor.filter(|_| kb_click).map(|(x, y)| self.image_to_screen(x + 0.5, y + 0.5));
+            let pos: egui::Pos2 = kb_pos.or(response.interact_pointer_pos()).unwrap_or(canvas_rect.center());
             let (img_w, img_h) = self.image.as_ref().map(|i| (i.width() as f32, i.height() as f32)).unwrap_or((1.0, 1.0));
             let ox = canvas_rect.center().x - img_w * self.zoom / 2.0 + self.pan.x;
             let oy = canvas_rect.center().y - img_h * self.zoom / 2.0 + self.pan.y;
@@ -1596,13 +2896,16 @@ impl ImageEditor {
             if let Some((lid, iid)) = hit_image_iid {
                 if self.tool != Tool::Text && self.selected_image_layer != Some(iid) {
                     self.selected_image_layer = Some(iid);
+                    self.selected_shape = None;
                     self.active_layer_id = lid;
                     self.composite_dirty = true;
                 }
             }
 
             match self.tool {
+                Tool::Brush | Tool::Eraser | Tool::Retouch | Tool::Fill if self.base_frozen() => { self.pixels_blocked(); }
                 Tool::Brush | Tool::Eraser => {
+                    self.stroke_blend = self.brush.blend_mode;
                     if self.image_layer_for_active().is_some() {
                         self.push_undo();
                         self.stroke_points.clear();
@@ -1658,28 +2961,44 @@ impl ImageEditor {
                         self.add_color_to_history();
                         self.composite_dirty = true;
                     } else if let Some((ix, iy)) = self.screen_to_image(pos) {
-                        self.push_undo(); self.flood_fill(ix, iy); self.add_color_to_history();
+                        let mask = self.take_fill_preview_mask(ix, iy);
+                        self.push_undo(); self.flood_fill(ix, iy, mask); self.add_color_to_history();
                     }
                 }
                 Tool::Eyedropper => {
                     if let Some((ix, iy)) = self.screen_to_image(pos) { self.sample_color(ix, iy); }
                 }
+                Tool::Select => {
+                    let p = self.screen_to_image_f32(pos);
+                    if self.selection_state.floating.is_some() {
+                        if !self.floating_contains(p) { self.commit_floating_selection(); }
+                    } else if SelectOp::from_modifiers(ui.input(|i| i.modifiers)) == SelectOp::Replace { self.selection = None; }
+                }
+                Tool::Wand => {
+                    let op = SelectOp::from_modifiers(ui.input(|i| i.modifiers));
+                    match self.screen_to_image(pos) {
+                        Some((ix, iy)) => self.start_wand(ix, iy, op),
+                        None if op == SelectOp::Replace => self.selection = None,
+                        None => {}
+                    }
+                }
+                Tool::Measure => {
+                    let shift = ui.input(|i| i.modifiers.shift);
+                    self.click_measurement(self.screen_to_image_f32(pos), shift);
+                }
+                Tool::Slice => {
+                    self.selected_slice = self.slices.iter().rposition(|sl| {
+                        egui::Rect::from_min_max(self.image_to_screen(sl.x, sl.y), self.image_to_screen(sl.x + sl.w, sl.y + sl.h)).contains(pos)
+                    });
+                }
                 Tool::Text => {
                     if let Some(hit) = self.hit_text_layer(pos) {
-                        if self.selected_text != Some(hit) { self.commit_or_discard_active_text(); }
-                        self.selected_text = Some(hit); self.editing_text = true; self.text_sel_anchor = None;
-                        self.composite_dirty = true;
-                        if let Some(layer) = self.text_layers.iter().find(|l| l.id == hit) {
-                            self.text_font_size = layer.font_size; self.text_bold = layer.bold;
-                            self.text_italic = layer.italic; self.text_underline = layer.underline;
-                            self.text_font_name = layer.font_name.clone(); self.text_cursor = layer.content.len();
-                        }
-                        if let Some(linked_layer) = self.layers.iter().find(|l| l.linked_text_id == Some(hit)) {
-                            self.active_layer_id = linked_layer.id;
-                        }
+                        self.edit_text_layer_at(hit, pos, response.double_clicked());
                     } else {
+                        let was_editing = self.selected_text.and_then(|id| self.text_layers.iter().find(|l| l.id == id)).is_some_and(|l| !l.content.is_empty());
                         self.commit_or_discard_active_text();
-                        if let Some((ix, iy)) = self.screen_to_image(pos) {
+                        if !was_editing && let Some((ix, iy)) = self.screen_to_image(pos) {
+                            if self.hide_all_text { self.toggle_hide_all_text(); }
                             let id: u64 = self.next_text_id; self.next_text_id += 1;
                             self.text_layers.push(TextLayer {
                                 id, content: String::new(),
@@ -1687,7 +3006,7 @@ impl ImageEditor {
                                 font_size: self.text_font_size, box_width: Some(300.0), box_height: None,
                                 rotation: 0.0, color: self.color,
                                 bold: self.text_bold, italic: self.text_italic, underline: self.text_underline,
-                                font_name: self.text_font_name.clone(), rendered_height: 0.0, cached_lines: Vec::new(),
+                                font_name: self.text_font_name.clone(), rendered_height: 0.0, cached_lines: Vec::new(), direction: self.text_direction, tab_width: self.text_tab_width, font_substitute: None, canvas_hidden: false,
                             });
                             self.ensure_layer_entry_for_text(id);
                             self.selected_text = Some(id); self.editing_text = true;
@@ -1700,14 +3019,18 @@ impl ImageEditor {
                         if self.selected_text != Some(hit) { self.commit_or_discard_active_text(); }
                         self.selected_text = Some(hit);
                         self.selected_image_layer = None;
+                        self.selected_shape = None;
                         self.composite_dirty = true;
                         if let Some(linked_layer) = self.layers.iter().find(|l| l.linked_text_id == Some(hit)) {
                             self.active_layer_id = linked_layer.id;
                         }
+                    } else if let Some(sid) = self.hit_shape_layer(pos) {
+                        self.select_shape(sid);
                     } else if hit_image_iid.is_none() {
-                        let handles_hit = self.image_layer_transform_handles().and_then(|h| h.hit_test(pos)).is_some();
+                        let handles_hit = self.image_layer_transform_handles().or_else(|| self.shape_transform_handles()).and_then(|h| h.hit_test(pos)).is_some();
                         if !handles_hit {
                             self.selected_image_layer = None;
+                            self.selected_shape = None;
                             self.commit_or_discard_active_text();
                         }
                     }
@@ -1721,6 +3044,7 @@ impl ImageEditor {
                     }
                 }
             }
+            self.click_undo_mark = (self.undo_pushes != pushes_before).then_some(self.undo_pushes);
         }
 
         let scroll: f32 = ui.input(|i| i.raw_scroll_delta.y);
@@ -1732,10 +3056,130 @@ impl ImageEditor {
                 && self.color_picker_rect.map_or(false, |r| r.contains(mp));
             if canvas_rect.contains(mp) && !over_filter_panel && !over_color_picker {
                 let factor: f32 = if scroll > 0.0 { 1.1 } else { 1.0 / 1.1 };
-                self.zoom = (self.zoom * factor).clamp(0.01, 50.0);
+                self.set_zoom(self.zoom * factor);
+            }
+        }
+        if response.dragged_by(egui::PointerButton::Middle) || (space_pan && response.dragged_by(egui::PointerButton::Primary)) { self.pan += response.drag_delta(); }
+    }
+
+    fn draw_diagnostics(&self, painter: &egui::Painter, canvas: egui::Rect) {
+        let undo_mb = self.undo_memory_bytes() as f32 / (1024.0 * 1024.0);
+        let fps = if self.perf.frame_ms > 0.0 { 1000.0 / self.perf.frame_ms } else { 0.0 };
+        let adaptive = match (self.adaptive_strokes, self.perf.coarse) { (false, _) => "off", (true, false) => "on", (true, true) => "on (coarse)" };
+        let text = format!(
+            "Frame   {:>6.1} ms  ({:.0} fps)\nStroke  {:>6.2} ms\nUpload  {:>6.2} ms\nUndo    {:>6.1} MB  ({} / {})\nAdaptive {}",
+            self.perf.frame_ms, fps, self.perf.stroke_ms, self.perf.upload_ms, undo_mb, self.history.undo_len(), self.history.redo_len(), adaptive,
+        );
+        let col = if self.perf.frame_ms > ADAPTIVE_FRAME_MS { ColorPalette::AMBER_400 } else { egui::Color32::WHITE };
+        let galley = painter.layout_no_wrap(text, egui::FontId::monospace(11.0), col);
+        let rect = egui::Rect::from_min_size(egui::pos2(canvas.max.x - galley.size().x - 26.0, canvas.min.y + 10.0), galley.size() + egui::vec2(16.0, 10.0));
+        painter.rect_filled(rect, 4.0, egui::Color32::from_black_alpha(190));
+        painter.galley(rect.min + egui::vec2(8.0, 5.0), galley, col);
+    }
+
+    fn caret_visible(&mut self, ctx: &egui::Context) -> bool {
+        let (now, focused) = ctx.input(|i| (i.time, i.focused));
+        let key = (self.selected_text.unwrap_or_default(), self.text_cursor);
+        let epoch = match self.caret_epoch { Some((k, t)) if k == key => t, _ => { self.caret_epoch = Some((key, now)); now } };
+        if !focused { return true; }
+        let (on, next) = caret_blink(now - epoch);
+        ctx.request_repaint_after(std::time::Duration::from_secs_f64(next));
+        on
+    }
+
+    fn update_space_pan(&mut self, ctx: &egui::Context) {
+        let held = self.kb_cursor.is_none() && !self.editing_text && crate::focus::canvas_focused(ctx) && ctx.input(|i| i.key_down(egui::Key::Space));
+        self.space_pan = space_pan_next(self.space_pan, held, ctx.input(|i| i.pointer.primary_down()));
+    }
+
+    /// Raster dabs are centred on the pixel under the pointer, not on the pointer itself.
+    fn stamp_center_on_screen(&self, painter: &egui::Painter, mp: egui::Pos2) -> egui::Pos2 {
+        let center = match self.screen_to_image(mp) {
+            Some((ix, iy)) if self.image_layer_for_active().is_none() => self.image_to_screen(ix as f32 + 0.5, iy as f32 + 0.5),
+            _ => mp,
+        };
+        snap(painter, center)
+    }
+
+    fn over_modal_panel(&self, p: egui::Pos2) -> bool {
+        (self.show_color_picker && self.color_picker_rect.is_some_and(|r| r.contains(p)))
+            || (self.filter_panel != FilterPanel::None && self.filter_panel_rect.is_some_and(|r| r.contains(p)))
+    }
+
+    fn loupe_visible(&self, ctx: &egui::Context) -> bool {
+        self.loupe_pinned || (!self.editing_text && crate::focus::canvas_focused(ctx) && ctx.input(|i| i.key_down(egui::Key::Z) && !i.modifiers.any()))
+    }
+
+    /// A nearest-filtered texture of the `n`×`n` composite pixels from (`x0`, `y0`), so the loupe shows hard pixel edges
+    /// whatever filtering the canvas texture uses. Pixels off the canvas are transparent.
+    pub(super) fn loupe_pixels(&mut self, ctx: &egui::Context, x0: i64, y0: i64, n: u32) -> egui::TextureId {
+        let key = (x0, y0, n, self.pixel_revision);
+        if let Some((k, tex)) = &self.loupe_texture && *k == key { return tex.id(); }
+        let (w, h) = self.image.as_ref().map_or((0, 0), |i| (i64::from(i.width()), i64::from(i.height())));
+        let pixels = (0..n as i64).flat_map(|dy| (0..n as i64).map(move |dx| (x0 + dx, y0 + dy))).map(|(x, y)| {
+            let [r, g, b, a] = if (0..w).contains(&x) && (0..h).contains(&y) { self.composite_pixel_at(x as u32, y as u32) } else { [0; 4] };
+            egui::Color32::from_rgba_unmultiplied(r, g, b, a)
+        }).collect();
+        let image = egui::ColorImage { size: [n as usize; 2], source_size: egui::vec2(n as f32, n as f32), pixels };
+        match &mut self.loupe_texture {
+            Some((k, tex)) => { tex.set(image, egui::TextureOptions::NEAREST); *k = key; }
+            None => self.loupe_texture = Some((key, ctx.load_texture("image_editor_loupe", image, egui::TextureOptions::NEAREST))),
+        }
+        self.loupe_texture.as_ref().map(|(_, t)| t.id()).unwrap_or_default()
+    }
+
+    fn draw_loupe(&mut self, painter: &egui::Painter, canvas: egui::Rect, mp: egui::Pos2, checker: egui::TextureId) {
+        let Some(img) = &self.image else { return };
+        let (iw, ih) = (img.width() as f32, img.height() as f32);
+        let (fx, fy) = self.screen_to_image_f32(mp);
+        if fx < 0.0 || fy < 0.0 || fx >= iw || fy >= ih { return; }
+        let (px, py) = (fx.floor(), fy.floor());
+        let (size, mag) = (160.0_f32, self.loupe_zoom as f32);
+        let (radius, span) = (size / 2.0, size / mag);
+        let origin = |c: f32, extent: f32| if extent <= span { (extent - span) / 2.0 } else { (c + 0.5 - span / 2.0).clamp(0.0, extent - span) };
+        let (sx, sy) = (origin(px, iw), origin(py, ih));
+        let (x0, y0, n) = (sx.floor(), sy.floor(), span.ceil() as u32 + 1);
+        let tex = self.loupe_pixels(painter.ctx(), x0 as i64, y0 as i64, n);
+        let uv = egui::Rect::from_min_size(egui::pos2((sx - x0) / n as f32, (sy - y0) / n as f32), egui::vec2(span / n as f32, span / n as f32));
+        let mut center = mp + egui::vec2(radius + 24.0, radius + 24.0);
+        if center.x + radius > canvas.right() { center.x = mp.x - radius - 24.0; }
+        if center.y + radius + 28.0 > canvas.bottom() { center.y = mp.y - radius - 24.0; }
+        let rect = egui::Rect::from_center_size(center, egui::vec2(size, size));
+        let checker_uv = egui::Rect::from_min_max(egui::Pos2::ZERO, egui::pos2(size / 16.0, size / 16.0));
+        if self.loupe_round {
+            painter.add(textured_disc(checker, center, radius, checker_uv));
+            painter.add(textured_disc(tex, center, radius, uv));
+        } else {
+            painter.image(checker, rect, checker_uv, egui::Color32::WHITE);
+            painter.image(tex, rect, uv, egui::Color32::WHITE);
+        }
+        let to_screen = |x: f32, y: f32| rect.min + egui::vec2((x - sx) * mag, (y - sy) * mag);
+        if self.loupe_grid && mag >= 4.0 {
+            let grid = egui::Stroke::new(1.0, egui::Color32::from_black_alpha(70));
+            let half = |d: f32| if self.loupe_round { (radius * radius - d * d).max(0.0).sqrt() } else { radius };
+            for x in (sx.ceil().max(0.0) as i32)..=((sx + span).floor().min(iw) as i32) {
+                let lx = to_screen(x as f32, 0.0).x;
+                let h = half(lx - center.x);
+                painter.line_segment([egui::pos2(lx, center.y - h), egui::pos2(lx, center.y + h)], grid);
+            }
+            for y in (sy.ceil().max(0.0) as i32)..=((sy + span).floor().min(ih) as i32) {
+                let ly = to_screen(0.0, y as f32).y;
+                let h = half(ly - center.y);
+                painter.line_segment([egui::pos2(center.x - h, ly), egui::pos2(center.x + h, ly)], grid);
             }
         }
-        if response.dragged_by(egui::PointerButton::Middle) { self.pan += response.drag_delta(); }
+        let cell = egui::Rect::from_min_size(to_screen(px, py), egui::vec2(mag, mag));
+        painter.rect_stroke(cell, 0.0, egui::Stroke::new(1.0, egui::Color32::BLACK), egui::StrokeKind::Outside);
+        painter.rect_stroke(cell, 0.0, egui::Stroke::new(1.0, egui::Color32::WHITE), egui::StrokeKind::Inside);
+        let border = egui::Stroke::new(2.0, ColorPalette::ZINC_500);
+        if self.loupe_round { painter.circle_stroke(center, radius, border); } else { painter.rect_stroke(rect, 0.0, border, egui::StrokeKind::Outside); }
+        let [r, g, b, a] = self.composite_pixel_at(px as u32, py as u32);
+        let alpha = if a < 255 { format!("  α {}", a) } else { String::new() };
+        let caption = format!("{}, {}  #{:02X}{:02X}{:02X}{}  {}x", px, py, r, g, b, alpha, self.loupe_zoom);
+        let galley = painter.layout_no_wrap(caption, egui::FontId::monospace(11.0), egui::Color32::WHITE);
+        let cap_rect = egui::Rect::from_center_size(egui::pos2(center.x, rect.bottom() + 6.0 + galley.size().y / 2.0 + 3.0), galley.size() + egui::vec2(12.0, 6.0));
+        painter.rect_filled(cap_rect, 4.0, egui::Color32::from_black_alpha(190));
+        painter.galley(cap_rect.min + egui::vec2(6.0, 3.0), galley, egui::Color32::WHITE);
     }
 
     pub(super) fn render_brush_panel(&mut self, ui: &mut egui::Ui, ctx: &egui::Context, theme: ThemeMode) {
@@ -1748,13 +3192,13 @@ impl ImageEditor {
         let screen_h = ctx.content_rect().height();
         let panel_max_h = (screen_h - 130.0).max(300.0);
         let canvas_origin: egui::Pos2 = ui.available_rect_before_wrap().min;
-        let modal_pos: egui::Pos2 = canvas_origin + egui::vec2(10.0, 10.0);
+        let modal_pos: egui::Pos2 = self.panel_positions.resolve("Brush Settings", canvas_origin + egui::vec2(10.0, 10.0), ctx.content_rect());
 
         self.ensure_brush_preview(ctx);
         let win_resp = egui::Window::new("Brush Settings")
             .collapsible(false)
             .resizable(true)
-            .fixed_pos(modal_pos)
+            .current_pos(modal_pos)
             .min_size(egui::vec2(420.0, (screen_h * 0.55).min(560.0).max(300.0)))
             .max_size(egui::vec2(460.0, panel_max_h))
             .frame(egui::Frame::new()
@@ -1830,6 +3274,7 @@ impl ImageEditor {
                                         };
                                         let border_col = if is_active { ColorPalette::BLUE_400 } else { if matches!(theme, ThemeMode::Dark) { ColorPalette::ZINC_600 } else { ColorPalette::GRAY_400 } };
                                         let (rect, resp) = ui.allocate_exact_size(egui::vec2(68.0, 46.0), egui::Sense::click());
+                                        resp.widget_info(|| egui::WidgetInfo::selected(egui::WidgetType::SelectableLabel, true, is_active, format!("{} brush shape", shape.label())));
                                         if ui.is_rect_visible(rect) {
                                             let painter = ui.painter_at(rect);
                                             painter.rect_filled(rect, 6.0, frame_fill);
@@ -1909,6 +3354,12 @@ impl ImageEditor {
                                         }
                                     });
                                 });
+                                ui.horizontal(|ui: &mut egui::Ui| {
+                                    ui.label(egui::RichText::new("Blend").size(12.0).color(label_col)).on_hover_text("How each stroke mixes with the pixels underneath.\nCaptured when the stroke starts.");
+                                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                                        blend_mode_combo(ui, "brush_blend_panel", &mut self.brush.blend_mode, 110.0);
+                                    });
+                                });
                                 ui.horizontal(|ui: &mut egui::Ui| {
                                     ui.label(egui::RichText::new("Softness").size(12.0).color(label_col)).on_hover_text("0% = hard pixel-sharp edge.\n100% = fully feathered, airbrushed falloff.");
                                     ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
@@ -2260,7 +3711,9 @@ impl ImageEditor {
                             });
                     });
             });
+        if let Some(r) = &win_resp { crate::focus::register(ctx, Region::Panel, r.response.layer_id, r.response.rect); }
         self.filter_panel_rect = win_resp.map(|r| r.response.rect);
+        if let Some(rect) = self.filter_panel_rect { self.panel_positions.track(ctx, "Brush Settings", modal_pos, rect); }
     }
 
     pub(super) fn render_layers_panel(&mut self, ui: &mut egui::Ui, theme: ThemeMode) {
@@ -2280,7 +3733,8 @@ impl ImageEditor {
             .inner_margin(egui::Margin { left: 10, right: 6, top: 8, bottom: 8 })
             .show(ui, |ui| {
                 ui.horizontal(|ui| {
-                    ui.label(egui::RichText::new("Layers").size(13.0).strong().color(text_prim));
+                    ui.label(egui::RichText::new(tr("Layers")).size(13.0).strong().color(text_prim));
+                    ui.label(egui::RichText::new(i18n::tr_n("{n} layer", "{n} layers", self.layers.len())).size(11.0).color(text_mute));
                     ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                         if layer_icon_btn(ui, "+", "New Raster Layer (Ctrl+Shift+N)", accent, egui::Color32::WHITE, is_dark) {
                             self.new_raster_layer();
@@ -2348,11 +3802,17 @@ impl ImageEditor {
                                         .on_hover_text("Background layer cannot be moved or deleted");
                                 } else {
                                     let eye = if layer_visible { "👁" } else { "🚫" };
-                                    if ui.add(egui::Button::new(egui::RichText::new(eye).size(13.0)).frame(false).min_size(egui::vec2(18.0, 18.0))).clicked() {
+                                    let eye_resp = ui.add(egui::Button::new(egui::RichText::new(eye).size(13.0)).frame(false).min_size(egui::vec2(18.0, 18.0)))
+                                        .on_hover_text(if layer_visible { "Hide layer" } else { "Show layer" });
+                                    a11y_label(&eye_resp, egui::WidgetType::Button, if layer_visible { "Hide layer" } else { "Show layer" });
+                                    if eye_resp.clicked() {
                                         action = Some(LayerPanelAction::ToggleVisible(stack_idx));
                                     }
                                     let lock_icon = if layer_locked { "🔒" } else { "🔓" };
-                                    if ui.add(egui::Button::new(egui::RichText::new(lock_icon).size(12.0)).frame(false).min_size(egui::vec2(18.0, 18.0))).clicked() {
+                                    let lock_resp = ui.add(egui::Button::new(egui::RichText::new(lock_icon).size(12.0)).frame(false).min_size(egui::vec2(18.0, 18.0)))
+                                        .on_hover_text(if layer_locked { "Unlock layer" } else { "Lock layer" });
+                                    a11y_label(&lock_resp, egui::WidgetType::Button, if layer_locked { "Unlock layer" } else { "Lock layer" });
+                                    if lock_resp.clicked() {
                                         action = Some(LayerPanelAction::ToggleLocked(stack_idx));
                                     }
                                 }
@@ -2362,6 +3822,7 @@ impl ImageEditor {
                                     LayerKind::Raster => ("R",  egui::Color32::from_rgb(80, 100, 180)),
                                     LayerKind::Text => ("T",  egui::Color32::from_rgb(180, 100, 60)),
                                     LayerKind::Image => ("I",  egui::Color32::from_rgb(100, 60, 180)),
+                                    LayerKind::Shape => ("S",  egui::Color32::from_rgb(60, 150, 160)),
                                 };
                                 egui::Frame::new()
                                     .fill(kind_badge.1.linear_multiply(if is_dark { 0.6 } else { 0.3 }))
@@ -2460,7 +3921,7 @@ impl ImageEditor {
                             }
                             if n > 1 {
                                 let can_ctx_merge = stack_idx > 0
-                                    && !matches!(self.layers.get(stack_idx.saturating_sub(1)).map(|l| l.kind), Some(LayerKind::Text | LayerKind::Image));
+                                    && !matches!(self.layers.get(stack_idx.saturating_sub(1)).map(|l| l.kind), Some(LayerKind::Text | LayerKind::Image | LayerKind::Shape));
                                 if ui.add_enabled(can_ctx_merge, egui::Button::new("Merge Down")).clicked() {
                                     action = Some(LayerPanelAction::MergeDown(stack_idx));
                                     ui.close();
@@ -2498,6 +3959,7 @@ impl ImageEditor {
                             self.active_layer_id = self.layers[idx].id;
                             let aid = self.active_layer_id;
                             self.kick_backdrop_compute(aid);
+                            self.selected_shape = None;
                             match self.layers[idx].kind {
                                 LayerKind::Text => {
                                     if let Some(tid) = self.layers[idx].linked_text_id {
@@ -2519,6 +3981,11 @@ impl ImageEditor {
                                     self.editing_text = false;
                                     self.composite_dirty = true;
                                 }
+                                LayerKind::Shape => {
+                                    self.selected_text = None;
+                                    self.editing_text = false;
+                                    if let Some(sid) = self.layers[idx].linked_shape_id { self.select_shape(sid); self.tool = Tool::Pan; }
+                                }
                                 _ => {
                                     self.selected_text = None;
                                     self.editing_text = false;
@@ -2543,9 +4010,7 @@ impl ImageEditor {
                                 self.active_layer_id = self.layers[if idx > 0 { idx - 1 } else { 1.min(self.layers.len()-1) }].id;
                             }
                             self.push_undo();
-                            if let Some(tid) = self.layers[idx].linked_text_id {
-                                self.text_layers.retain(|t| t.id != tid);
-                            }
+                            if let Some(tid) = self.layers[idx].linked_text_id { self.bin_text_layer(tid); }
                             self.layer_images.remove(&self.layers[idx].id);
                             self.layers.remove(idx);
                             self.composite_dirty = true;
@@ -2642,13 +4107,17 @@ impl ImageEditor {
                         let can_up = !is_bg && idx < self.layers.len() - 1;
                         let can_down = !is_bg && idx > 1;
                         let can_merge = !is_bg && idx > 0
-                            && !matches!(self.layers[idx - 1].kind, LayerKind::Text | LayerKind::Image);
+                            && !matches!(self.layers[idx - 1].kind, LayerKind::Text | LayerKind::Image | LayerKind::Shape);
 
-                        if ui.add_enabled(can_up, egui::Button::new(egui::RichText::new("⬆").size(11.0)).min_size(egui::vec2(28.0, 24.0))).on_hover_text("Move layer up").clicked() {
+                        let up_resp = ui.add_enabled(can_up, egui::Button::new(egui::RichText::new("⬆").size(11.0)).min_size(egui::vec2(28.0, 24.0))).on_hover_text("Move layer up");
+                        a11y_label(&up_resp, egui::WidgetType::Button, "Move layer up");
+                        if up_resp.clicked() {
                             self.push_undo();
                             self.move_layer_up();
                         }
-                        if ui.add_enabled(can_down, egui::Button::new(egui::RichText::new("⬇").size(11.0)).min_size(egui::vec2(28.0, 24.0))).on_hover_text("Move layer down").clicked() {
+                        let down_resp = ui.add_enabled(can_down, egui::Button::new(egui::RichText::new("⬇").size(11.0)).min_size(egui::vec2(28.0, 24.0))).on_hover_text("Move layer down");
+                        a11y_label(&down_resp, egui::WidgetType::Button, "Move layer down");
+                        if down_resp.clicked() {
                             self.push_undo();
                             self.move_layer_down();
                         }
@@ -2757,6 +4226,30 @@ fn filter_action_row(ui: &mut egui::Ui, theme: ThemeMode, preview_active: bool)
     action
 }
 
+/// Luminance histogram (square-root scaled so sparse levels stay visible) with the threshold marked and its soft band shaded.
+fn threshold_histogram(ui: &mut egui::Ui, hist: &[u32; 256], level: u8, soft: u8, theme: ThemeMode) {
+    let (rect, _) = ui.allocate_exact_size(egui::vec2(ui.available_width().min(348.0), 64.0), egui::Sense::hover());
+    let dark = matches!(theme, ThemeMode::Dark);
+    let painter = ui.painter();
+    painter.rect_filled(rect, 4.0, if dark { ColorPalette::ZINC_900 } else { ColorPalette::GRAY_100 });
+    let x_at = |l: f32| rect.left() + rect.width() * l / 256.0;
+    if soft > 0 {
+        let (lo, hi) = (level as f32 - soft as f32 / 2.0, level as f32 + soft as f32 / 2.0);
+        painter.rect_filled(egui::Rect::from_x_y_ranges(x_at(lo.max(0.0))..=x_at(hi.min(256.0)), rect.y_range()), 0.0, ColorPalette::BLUE_500.gamma_multiply(0.25));
+    }
+    let peak = hist.iter().copied().max().unwrap_or(0).max(1) as f32;
+    let bar = if dark { ColorPalette::ZINC_400 } else { ColorPalette::ZINC_600 };
+    for (l, &count) in hist.iter().enumerate().filter(|(_, c)| **c > 0) {
+        let h = (count as f32 / peak).sqrt() * (rect.height() - 4.0);
+        painter.line_segment([egui::pos2(x_at(l as f32 + 0.5), rect.bottom()), egui::pos2(x_at(l as f32 + 0.5), rect.bottom() - h)], egui::Stroke::new(rect.width() / 256.0, bar));
+    }
+    painter.vline(x_at(level as f32), rect.y_range(), egui::Stroke::new(1.5, ColorPalette::BLUE_500));
+}
+
+fn a11y_label(resp: &egui::Response, typ: egui::WidgetType, label: &str) {
+    resp.widget_info(|| egui::WidgetInfo::labeled(typ, resp.enabled(), label));
+}
+
 fn gradient_slider_ui(ui: &mut egui::Ui, value: &mut f32, min: f32, max: f32, left_col: egui::Color32, right_col: egui::Color32, left_label: &str,
     right_label: &str, fmt: impl Fn(f32) -> String, drag_input: bool, drag_display_scale: f32, drag_suffix: &str) -> bool
 {
@@ -2844,6 +4337,7 @@ fn gradient_slider_ui(ui: &mut egui::Ui, value: &mut f32, min: f32, max: f32, le
     });
 
     let resp: egui::Response = inner.inner;
+    resp.widget_info(|| egui::WidgetInfo::slider(true, *value as f64, format!("{} to {}", left_label, right_label)));
     if resp.dragged() || resp.clicked() {
         if let Some(ptr) = resp.interact_pointer_pos() {
             let new_t   = ((ptr.x - resp.rect.min.x) / resp.rect.width()).clamp(0.0, 1.0);
@@ -2856,3 +4350,24 @@ fn gradient_slider_ui(ui: &mut egui::Ui, value: &mut f32, min: f32, max: f32, le
     }
     changed
 }
+
+/// "Snap to" picker shared by the crop bar, the Resize panel and export. Returns true when the choice changed.
+fn dimension_snap_combo(ui: &mut egui::Ui, id: &str, snap: &mut DimensionSnap, label_col: egui::Color32) -> bool {
+    let before = *snap;
+    ui.label(egui::RichText::new("Snap to:").size(12.0).color(label_col));
+    egui::ComboBox::from_id_salt(id).selected_text(egui::RichText::new(snap.label()).size(12.0)).width(100.0).show_ui(ui, |ui| {
+        ui.selectable_value(snap, DimensionSnap::None, "None");
+        ui.selectable_value(snap, DimensionSnap::Even, "Even");
+        if ui.selectable_label(matches!(snap, DimensionSnap::Multiple(_)), "Multiple of N").clicked() && !matches!(snap, DimensionSnap::Multiple(_)) {
+            *snap = DimensionSnap::Multiple(16);
+        }
+    });
+    if let DimensionSnap::Multiple(n) = snap { ui.add(egui::DragValue::new(n).range(2..=512).prefix("N ")); }
+    *snap != before
+}
+
+fn blend_mode_combo(ui: &mut egui::Ui, id: &str, mode: &mut BlendMode, width: f32) {
+    egui::ComboBox::from_id_salt(id).selected_text(egui::RichText::new(mode.label()).size(12.0)).width(width).show_ui(ui, |ui| {
+        for &m in BlendMode::all() { ui.selectable_value(mode, m, m.label()); }
+    });
+}
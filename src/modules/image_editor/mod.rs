@@ -2,6 +2,19 @@ mod ie_main;
 mod ie_tools;
 mod ie_ui;
 mod ie_helpers;
+mod ie_session;
+mod ie_gpu;
+mod ie_adjust;
+mod ie_filters;
+#[cfg(test)]
+mod ie_golden;
+mod ie_shape;
+mod ie_select;
+mod ie_compare;
+mod ie_proxy;
+mod ie_cursor;
+mod ie_source;
 pub mod ie_cache;
 
-pub use ie_main::ImageEditor;
+pub use ie_main::{ImageEditor, ImageEditorSettings, ImageClip, CanvasSurround, ImageMenuAction, RepeatableFilter, key_bindings};
+pub use ie_cursor::{CursorColor, CursorOutline};
@@ -3,7 +3,8 @@ use ropey::Rope;
 use serde_json::Value;
 use std::collections::{HashSet, VecDeque};
 use std::path::PathBuf;
-use crate::modules::{EditorModule, MenuAction, MenuItem, MenuContribution};
+use crate::modules::{EditorModule, SaveState, MenuAction, MenuEntry, MenuContribution, KeyBinding, Shortcut, shortcut, ctrl, ctrl_shift, menu_keys};
+use crate::error::{EditorError, EditorResult};
 use super::je_tools::{
     SortMode, SearchTarget, FlatNode,
     build_flat, serialize_value, parse_text, expand_recursive, collapse_recursive,
@@ -21,6 +22,9 @@ pub struct EditCell { pub path: Vec<String>, pub buffer: String, pub editing_key
 #[derive(Debug, Clone)]
 pub struct AddKeyDialog { pub parent_path: Vec<String>, pub key_buf: String, pub val_buf: String, pub error: Option<String>, }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JsonMenuAction { ToggleSearch }
+
 pub struct JsonEditor {
     pub(super) file_path: Option<PathBuf>,
     pub(super) dirty: bool,
@@ -414,6 +418,20 @@ impl JsonEditor {
     }
 }
 
+/// What the JSON editor's keys do; `JE_SHORTCUTS` binds them and `handle_keyboard` runs them.
+#[derive(Clone, Copy, PartialEq)]
+pub(super) enum JsonKey { Save, SaveAs, Undo, Redo, Search }
+
+pub(super) const JE_SHORTCUTS: &[Shortcut<JsonKey>] = &[
+    shortcut(&[ctrl(egui::Key::S)], JsonKey::Save, "Save"),
+    shortcut(&[ctrl_shift(egui::Key::S)], JsonKey::SaveAs, "Save As"),
+    shortcut(&[ctrl(egui::Key::Z)], JsonKey::Undo, "Undo"),
+    shortcut(&[ctrl(egui::Key::Y), ctrl_shift(egui::Key::Z)], JsonKey::Redo, "Redo"),
+    shortcut(&[ctrl(egui::Key::F)], JsonKey::Search, "Toggle search"),
+];
+
+pub fn key_bindings() -> Vec<KeyBinding> { crate::modules::key_bindings(JE_SHORTCUTS) }
+
 impl EditorModule for JsonEditor {
     fn as_any(&self) -> &dyn std::any::Any { self }
 
@@ -421,12 +439,15 @@ impl EditorModule for JsonEditor {
         self.open_in_converter_path.take()
     }
 
+    fn key_bindings(&self) -> Vec<KeyBinding> { key_bindings() }
+    fn file_path(&self) -> Option<&std::path::Path> { self.file_path.as_deref() }
+
     fn get_title(&self) -> String {
         let name = self.get_file_name();
         if self.dirty { format!("{} *", name) } else { name }
     }
 
-    fn save(&mut self) -> Result<(), String> {
+    fn save(&mut self) -> EditorResult<SaveState> {
         if self.file_path.is_none() {
             return self.save_as();
         }
@@ -434,26 +455,27 @@ impl EditorModule for JsonEditor {
             if !self.commit_text_to_root() {
                 let msg = "Cannot save: the JSON has syntax errors. Fix them in Text view first.".to_string();
                 self.save_error = Some(msg.clone());
-                return Err(msg);
+                return Err(EditorError::Other(msg));
             }
         }
         let content = serialize_value(&self.root, self.export_pretty);
-        match std::fs::write(self.file_path.as_ref().unwrap(), &content) {
+        let path = self.file_path.as_ref().unwrap();
+        match std::fs::write(path, &content) {
             Ok(_) => {
                 self.dirty = false;
                 self.text_modified = false;
                 self.save_error = None;
-                Ok(())
+                Ok(SaveState::Saved)
             }
             Err(e) => {
-                let msg = format!("Save failed: {}", e);
-                self.save_error = Some(msg.clone());
-                Err(msg)
+                let err = EditorError::io(path, e);
+                self.save_error = Some(format!("Save failed: {}", err));
+                Err(err)
             }
         }
     }
 
-    fn save_as(&mut self) -> Result<(), String> {
+    fn save_as(&mut self) -> EditorResult<SaveState> {
         if let Some(path) = rfd::FileDialog::new()
             .add_filter("JSON", &["json"])
             .add_filter("All Files", &["*"])
@@ -462,7 +484,7 @@ impl EditorModule for JsonEditor {
             self.file_path = Some(path);
             self.save()
         } else {
-            Err("Cancelled".to_string())
+            Err(EditorError::Cancelled)
         }
     }
 
@@ -470,23 +492,11 @@ impl EditorModule for JsonEditor {
         MenuContribution {
             file_items: Vec::new(),
             edit_items: vec![
-                (MenuItem {
-                    label: "Undo".to_string(),
-                    shortcut: Some("Ctrl+Z".to_string()),
-                    enabled: self.can_undo(),
-                }, MenuAction::Undo),
-                (MenuItem {
-                    label: "Redo".to_string(),
-                    shortcut: Some("Ctrl+Y".to_string()),
-                    enabled: self.can_redo(),
-                }, MenuAction::Redo),
+                MenuEntry::item("Undo", MenuAction::Undo).shortcut(&menu_keys(JE_SHORTCUTS, JsonKey::Undo)).enabled(self.can_undo()),
+                MenuEntry::item("Redo", MenuAction::Redo).shortcut(&menu_keys(JE_SHORTCUTS, JsonKey::Redo)).enabled(self.can_redo()),
             ],
             view_items: vec![
-                (MenuItem {
-                    label: if self.show_search { "Hide Search".into() } else { "Show Search (Ctrl+F)".into() },
-                    shortcut: Some("Ctrl+F".into()),
-                    enabled: true,
-                }, MenuAction::Custom("ToggleSearch".into())),
+                MenuEntry::item(if self.show_search { "Hide Search" } else { "Show Search (Ctrl+F)" }, JsonMenuAction::ToggleSearch).shortcut(&menu_keys(JE_SHORTCUTS, JsonKey::Search)),
             ], image_items: Vec::new(), filter_items: Vec::new(), layer_items: Vec::new(), insert_items: Vec::new(), format_items: Vec::new()
         }
     }
@@ -495,7 +505,9 @@ impl EditorModule for JsonEditor {
         match action {
             MenuAction::Undo => { self.undo(); true }
             MenuAction::Redo => { self.redo(); true }
-            MenuAction::Custom(ref v) if v == "ToggleSearch" => { self.show_search = !self.show_search; true }
+            MenuAction::Json(action) => match action {
+                JsonMenuAction::ToggleSearch => { self.show_search = !self.show_search; true }
+            },
             _ => false,
         }
     }
@@ -1,8 +1,8 @@
 use eframe::egui;
 use serde_json::Value;
 use crate::style::{self, ColorPalette, ThemeMode, toolbar_action_btn};
-use crate::modules::EditorModule;
-use super::je_main::{JsonEditor, JsonViewMode, EditCell, AddKeyDialog};
+use crate::modules::{EditorModule, pressed_shortcuts};
+use super::je_main::{JsonEditor, JsonViewMode, EditCell, AddKeyDialog, JsonKey, JE_SHORTCUTS};
 use super::je_tools::{
     SortMode, SearchTarget, FlatNode,
     sort_label, search_target_label, parse_cell_value, parse_edit_value, serialize_value, value_at_path,
@@ -313,21 +313,17 @@ impl JsonEditor {
     }
 
     fn handle_keyboard(&mut self, ctx: &egui::Context) {
-        let mut do_save = false;
-        let mut do_save_as = false;
         let in_text = matches!(self.view_mode, JsonViewMode::Text);
-        ctx.input_mut(|i| {
-            if i.consume_shortcut(&egui::KeyboardShortcut::new(egui::Modifiers::CTRL, egui::Key::S)) { do_save = true; }
-            if i.consume_shortcut(&egui::KeyboardShortcut::new(egui::Modifiers::CTRL | egui::Modifiers::SHIFT, egui::Key::S)) { do_save_as = true; }
-            if i.consume_key(egui::Modifiers::CTRL, egui::Key::F) { self.show_search = !self.show_search; }
-            if !in_text {
-                if i.consume_shortcut(&egui::KeyboardShortcut::new(egui::Modifiers::CTRL, egui::Key::Z)) { self.undo(); }
-                if i.consume_shortcut(&egui::KeyboardShortcut::new(egui::Modifiers::CTRL, egui::Key::Y)) { self.redo(); }
-                if i.consume_shortcut(&egui::KeyboardShortcut::new(egui::Modifiers::CTRL | egui::Modifiers::SHIFT, egui::Key::Z)) { self.redo(); }
+        let pressed = ctx.input_mut(|i| pressed_shortcuts(i, JE_SHORTCUTS, |a| !in_text || !matches!(a, JsonKey::Undo | JsonKey::Redo)));
+        for (action, _) in pressed {
+            match action {
+                JsonKey::Save => { let _ = self.save(); }
+                JsonKey::SaveAs => { let _ = self.save_as(); }
+                JsonKey::Undo => self.undo(),
+                JsonKey::Redo => self.redo(),
+                JsonKey::Search => self.show_search = !self.show_search,
             }
-        });
-        if do_save { let _ = self.save(); }
-        if do_save_as { let _ = self.save_as(); }
+        }
     }
 
     fn render_view_tabs(&mut self, ui: &mut egui::Ui, dark: bool) {
@@ -3,4 +3,4 @@ mod je_tools;
 mod je_ui;
 mod je_style;
 
-pub use je_main::JsonEditor;
+pub use je_main::{JsonEditor, JsonMenuAction, key_bindings};
@@ -0,0 +1,86 @@
+use eframe::egui;
+use std::path::{Path, PathBuf};
+use crate::error::{EditorError, EditorResult};
+use crate::registry::{self, CreateModule};
+use crate::style::{self, ColorPalette, ThemeMode};
+use super::{EditorModule, SaveState};
+
+pub struct LoadFailed {
+    path: PathBuf,
+    module: CreateModule,
+    error: String,
+    reopen: Option<CreateModule>,
+    close_requested: bool,
+}
+
+impl LoadFailed {
+    pub fn new(path: PathBuf, module: CreateModule, error: EditorError) -> Self {
+        Self { path, module, error: error.to_string(), reopen: None, close_requested: false }
+    }
+
+    fn file_name(&self) -> String {
+        self.path.file_name().map_or_else(|| self.path.display().to_string(), |n| n.to_string_lossy().into_owned())
+    }
+}
+
+impl EditorModule for LoadFailed {
+    fn as_any(&self) -> &dyn std::any::Any { self }
+    fn save(&mut self) -> EditorResult<SaveState> { Err(EditorError::Empty("the file could not be opened")) }
+    fn save_as(&mut self) -> EditorResult<SaveState> { self.save() }
+    fn get_title(&self) -> String { format!("{} (not opened)", self.file_name()) }
+    fn file_path(&self) -> Option<&Path> { Some(&self.path) }
+    fn take_close_request(&mut self) -> bool { std::mem::take(&mut self.close_requested) }
+    fn take_reopen_request(&mut self) -> Option<(PathBuf, CreateModule)> { self.reopen.take().map(|m| (self.path.clone(), m)) }
+
+    fn ui(&mut self, ui: &mut egui::Ui, _ctx: &egui::Context, _show_toolbar: bool, _show_file_info: bool) {
+        let theme = if ui.visuals().dark_mode { ThemeMode::Dark } else { ThemeMode::Light };
+        let (text, sub) = match theme {
+            ThemeMode::Dark => (ColorPalette::ZINC_100, ColorPalette::ZINC_400),
+            ThemeMode::Light => (ColorPalette::STONE_900, ColorPalette::STONE_500),
+        };
+        ui.vertical_centered(|ui| {
+            ui.add_space((ui.available_height() * 0.25).max(24.0));
+            ui.label(egui::RichText::new(format!("{} could not be opened", self.file_name())).size(20.0).color(text));
+            ui.add_space(6.0);
+            ui.label(egui::RichText::new(self.path.display().to_string()).size(12.0).color(sub));
+            ui.add_space(12.0);
+            ui.label(egui::RichText::new(&self.error).size(13.0).color(ColorPalette::RED_500));
+            ui.add_space(6.0);
+            ui.label(egui::RichText::new("The file was left untouched.").size(12.0).color(sub));
+            ui.add_space(20.0);
+            if style::primary_button(ui, "Retry").on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { self.reopen = Some(self.module); }
+            ui.add_space(6.0);
+            for screen in registry::SCREENS.iter().filter(|s| s.create != self.module) {
+                if style::secondary_button(ui, &format!("Open in {}", screen.name), theme).on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { self.reopen = Some(screen.create); }
+                ui.add_space(4.0);
+            }
+            ui.add_space(2.0);
+            if style::secondary_button(ui, "Close", theme).on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { self.close_requested = true; }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::{image_edit::ImageEditor, text_edit::TextEditor};
+
+    #[test]
+    fn unreadable_files_open_as_an_error_view_that_never_saves() {
+        let dir = std::env::temp_dir().join(format!("ue_load_failed_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (png, txt) = (dir.join("notes.png"), dir.join("blob.txt"));
+        std::fs::write(&png, b"just some text").unwrap();
+        std::fs::write(&txt, [0x89, 0x50, 0xff, 0x00]).unwrap();
+        let err = ImageEditor::load(png.clone()).err().unwrap();
+        assert!(matches!(err, EditorError::ImageDecode { .. }));
+        assert!(TextEditor::load(txt.clone(), Default::default()).err().unwrap().to_string().ends_with("Not valid UTF-8 text"));
+        let mut view = LoadFailed::new(png.clone(), CreateModule::ImageEditor, err);
+        assert!(view.save().is_err() && view.save_as().is_err());
+        assert!(view.take_reopen_request().is_none());
+        view.reopen = Some(CreateModule::TextEditor);
+        assert!(matches!(view.take_reopen_request(), Some((p, CreateModule::TextEditor)) if p == png));
+        assert_eq!(std::fs::read(&png).unwrap(), b"just some text");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
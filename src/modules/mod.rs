@@ -1,5 +1,6 @@
 use eframe::egui;
 use std::any::Any;
+use crate::error::EditorResult;
 
 pub mod json_editor;
 pub mod text_editor;
@@ -7,43 +8,348 @@ pub mod image_editor;
 pub mod converters;
 pub mod helpers;
 pub mod document_editor;
+pub mod load_failed;
 
 pub mod doc_edit { pub use super::document_editor::DocumentEditor; }
 pub mod json_edit {pub use super::json_editor::JsonEditor; }
-pub mod image_edit { pub use super::image_editor::ImageEditor; }
+pub mod image_edit { pub use super::image_editor::{ImageEditor, ImageEditorSettings, ImageClip, CanvasSurround, CursorColor, CursorOutline, RepeatableFilter}; }
 pub mod image_converter { pub use super::converters::image_converter::ImageConverter; }
 pub mod data_converter { pub use super::converters::data_converter::DataConverter; }
 pub mod archive_converter { pub use super::converters::archive_converter::ArchiveConverter; }
 pub mod image_export { pub use super::helpers::image_export::{ExportFormat, export_image}; }
-pub mod text_edit { pub use super::text_editor::TextEditor; }
+pub mod text_edit { pub use super::text_editor::{TextEditor, TextEditorSettings, PasteCleanup}; }
 
-#[derive(Clone, Debug)]
-pub enum MenuAction { Undo, Redo, Export, None, Custom(String) }
+use text_editor::TextMenuAction;
+use image_editor::ImageMenuAction;
+use document_editor::DocumentMenuAction;
+use json_editor::JsonMenuAction;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MenuAction { Undo, Redo, Export, Text(TextMenuAction), Image(ImageMenuAction), Document(DocumentMenuAction), Json(JsonMenuAction) }
+
+impl From<TextMenuAction> for MenuAction { fn from(a: TextMenuAction) -> Self { Self::Text(a) } }
+impl From<ImageMenuAction> for MenuAction { fn from(a: ImageMenuAction) -> Self { Self::Image(a) } }
+impl From<DocumentMenuAction> for MenuAction { fn from(a: DocumentMenuAction) -> Self { Self::Document(a) } }
+impl From<JsonMenuAction> for MenuAction { fn from(a: JsonMenuAction) -> Self { Self::Json(a) } }
+
+#[derive(Clone, Default)]
+pub struct MenuItem { pub label: String, pub shortcut: Option<String>, pub enabled: bool, pub checked: Option<bool>, pub tooltip: Option<&'static str> }
 
 #[derive(Clone)]
-pub struct MenuItem { pub label: String, pub shortcut: Option<String>, pub enabled: bool }
+pub enum MenuEntry { Item(MenuItem, MenuAction), Separator }
+
+impl MenuEntry {
+    pub fn item(label: impl Into<String>, action: impl Into<MenuAction>) -> Self {
+        Self::Item(MenuItem { label: label.into(), enabled: true, ..Default::default() }, action.into())
+    }
+    pub fn shortcut(self, keys: &str) -> Self { self.with(|i| i.shortcut = Some(keys.into())) }
+    pub fn enabled(self, enabled: bool) -> Self { self.with(|i| i.enabled = enabled) }
+    pub fn checked(self, checked: bool) -> Self { self.with(|i| i.checked = Some(checked)) }
+    pub fn tooltip(self, text: &'static str) -> Self { self.with(|i| i.tooltip = Some(text)) }
+    fn with(mut self, f: impl FnOnce(&mut MenuItem)) -> Self { if let Self::Item(item, _) = &mut self { f(item); } self }
+}
+
+/// A key and the modifiers it has to be pressed with.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Chord { pub modifiers: egui::Modifiers, pub key: egui::Key }
+
+const fn mods(ctrl: bool, alt: bool, shift: bool) -> egui::Modifiers { egui::Modifiers { alt, ctrl, shift, mac_cmd: false, command: false } }
+pub const fn key(key: egui::Key) -> Chord { Chord { modifiers: mods(false, false, false), key } }
+pub const fn ctrl(key: egui::Key) -> Chord { Chord { modifiers: mods(true, false, false), key } }
+pub const fn shift(key: egui::Key) -> Chord { Chord { modifiers: mods(false, false, true), key } }
+pub const fn alt(key: egui::Key) -> Chord { Chord { modifiers: mods(false, true, false), key } }
+pub const fn ctrl_shift(key: egui::Key) -> Chord { Chord { modifiers: mods(true, false, true), key } }
+pub const fn ctrl_alt(key: egui::Key) -> Chord { Chord { modifiers: mods(true, true, false), key } }
+
+impl Chord {
+    fn specificity(&self) -> u8 { let m = self.modifiers; m.ctrl as u8 + m.command as u8 + m.alt as u8 + m.shift as u8 }
+
+    fn key_name(&self) -> &'static str {
+        match self.key {
+            egui::Key::Escape => "Esc",
+            egui::Key::Minus => "-",
+            egui::Key::ArrowUp => "Up",
+            egui::Key::ArrowDown => "Down",
+            egui::Key::ArrowLeft => "Left",
+            egui::Key::ArrowRight => "Right",
+            k => k.symbol_or_name(),
+        }
+    }
+
+    fn digit(&self) -> Option<u8> { self.key_name().parse().ok() }
+
+    pub fn text(&self) -> String {
+        let m = self.modifiers;
+        let mut s = String::new();
+        if m.ctrl || m.command { s.push_str("Ctrl+"); }
+        if m.alt { s.push_str("Alt+"); }
+        if m.shift { s.push_str("Shift+"); }
+        s + self.key_name()
+    }
+}
+
+/// One entry of an editor's shortcut registry. Entries with an action are what the editor dispatches from;
+/// gestures (clicks, drags, held keys, clipboard chords that arrive as events) are only listed.
+pub struct Shortcut<A: 'static> {
+    pub chords: &'static [Chord],
+    pub gesture: &'static str,
+    pub action: Option<A>,
+    pub label: &'static str,
+}
+
+pub const fn shortcut<A>(chords: &'static [Chord], action: A, label: &'static str) -> Shortcut<A> {
+    Shortcut { chords, gesture: "", action: Some(action), label }
+}
+
+pub const fn gesture<A>(keys: &'static str, label: &'static str) -> Shortcut<A> {
+    Shortcut { chords: &[], gesture: keys, action: None, label }
+}
+
+impl<A> Shortcut<A> {
+    /// The keys as listed: chords joined by " / ", with runs of digits on the same modifiers shown as "Ctrl+1-4".
+    pub fn keys(&self) -> String {
+        let mut parts = Vec::new();
+        let mut i = 0;
+        while i < self.chords.len() {
+            let run = self.chords[i..].windows(2).take_while(|w| w[0].modifiers == w[1].modifiers
+                && w[0].digit().zip(w[1].digit()).is_some_and(|(a, b)| a + 1 == b)).count();
+            let first = self.chords[i].text();
+            parts.push(if run >= 2 { format!("{}-{}", first, self.chords[i + run].key_name()) } else { first });
+            i += if run >= 2 { run + 1 } else { 1 };
+        }
+        if parts.is_empty() { self.gesture.to_string() } else { parts.join(" / ") }
+    }
+}
+
+/// Takes this frame's key presses that belong to a `live` action. Each press goes to the chord in the whole registry
+/// with the most modifiers it satisfies, so Ctrl+Shift+Z is never read as Ctrl+Z; a press whose best chord is not
+/// live here is left for another call site. Returns the actions with the index of the chord that fired them.
+pub fn pressed_shortcuts<A: Copy>(i: &mut egui::InputState, shortcuts: &[Shortcut<A>], live: impl Fn(A) -> bool) -> Vec<(A, usize)> {
+    let mut pressed = Vec::new();
+    i.events.retain(|e| {
+        let egui::Event::Key { key, pressed: true, modifiers, .. } = e else { return true };
+        let best = shortcuts.iter()
+            .filter_map(|s| Some((s.action?, s.chords)))
+            .flat_map(|(a, chords)| chords.iter().enumerate().map(move |(n, c)| (a, n, c)))
+            .filter(|(_, _, c)| c.key == *key && modifiers.matches_logically(c.modifiers))
+            .min_by_key(|(_, _, c)| std::cmp::Reverse(c.specificity()));
+        match best {
+            Some((a, n, _)) if live(a) => { pressed.push((a, n)); false }
+            _ => true,
+        }
+    });
+    pressed
+}
+
+/// The first chord of `action`'s entry, for menu items.
+pub fn menu_keys<A: PartialEq>(shortcuts: &[Shortcut<A>], action: A) -> String {
+    shortcuts.iter().find(|s| s.action.as_ref() == Some(&action)).and_then(|s| s.chords.first()).map(Chord::text).unwrap_or_default()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyBinding { pub keys: String, pub action: &'static str }
+
+pub fn key_bindings<A>(shortcuts: &[Shortcut<A>]) -> Vec<KeyBinding> {
+    shortcuts.iter().map(|s| KeyBinding { keys: s.keys(), action: s.label }).collect()
+}
 
 #[derive(Default)]
 pub struct MenuContribution {
-    pub file_items: Vec<(MenuItem, MenuAction)>,
-    pub edit_items: Vec<(MenuItem, MenuAction)>,
-    pub view_items: Vec<(MenuItem, MenuAction)>,
-    pub image_items: Vec<(MenuItem, MenuAction)>,
-    pub filter_items: Vec<(MenuItem, MenuAction)>,
-    pub layer_items: Vec<(MenuItem, MenuAction)>,
-    pub insert_items: Vec<(MenuItem, MenuAction)>,
-    pub format_items: Vec<(MenuItem, MenuAction)>
+    pub file_items: Vec<MenuEntry>,
+    pub edit_items: Vec<MenuEntry>,
+    pub view_items: Vec<MenuEntry>,
+    pub image_items: Vec<MenuEntry>,
+    pub filter_items: Vec<MenuEntry>,
+    pub layer_items: Vec<MenuEntry>,
+    pub insert_items: Vec<MenuEntry>,
+    pub format_items: Vec<MenuEntry>
 }
 
+/// Preferences the app owns and hands to the active editor, so they never live in process-wide statics.
+#[derive(Clone, Default, PartialEq)]
+pub struct EditorSettings { pub text: text_editor::TextEditorSettings, pub image: image_editor::ImageEditorSettings }
+
+#[derive(Clone, Copy, Default)]
+pub struct ViewState { pub scroll: f32, pub cursor: Option<usize>, pub zoom: Option<f32>, pub pan: Option<egui::Vec2>, pub zoom_fit: bool }
+
+/// How a save ended: written, or waiting on the user to confirm replacing a file, after which it finishes or is dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SaveState { Saved, AwaitingConfirm }
+
 #[allow(dead_code)]
 pub trait EditorModule {
     fn ui(&mut self, ui: &mut egui::Ui, ctx: &egui::Context, show_toolbar: bool, show_file_info: bool);
-    fn save(&mut self) -> Result<(), String>;
-    fn save_as(&mut self) -> Result<(), String>;
+    fn save(&mut self) -> EditorResult<SaveState>;
+    fn save_as(&mut self) -> EditorResult<SaveState>;
+    /// Whether a save that returned `AwaitingConfirm` is still waiting on the user.
+    fn save_pending(&self) -> bool { false }
     fn get_title(&self) -> String;
     fn as_any(&self) -> &dyn Any;
     fn get_menu_contributions(&self) -> MenuContribution { MenuContribution::default() }
     fn handle_menu_action(&mut self, action: MenuAction) -> bool { let _ = action; false }
     fn take_converter_path(&mut self) -> Option<std::path::PathBuf> { None }
+    /// A staged file for the app to hand to the system share sheet, which needs the window.
+    fn take_share(&mut self) -> Option<std::path::PathBuf> { None }
     fn take_open_in_image_editor(&mut self) -> Option<Vec<u8>> { None }
+    fn take_error(&mut self) -> Option<crate::error::EditorError> { None }
+    fn take_notice(&mut self) -> Option<String> { None }
+    fn take_new_document(&mut self) -> Option<Box<dyn EditorModule>> { None }
+    fn take_close_request(&mut self) -> bool { false }
+    fn take_reopen_request(&mut self) -> Option<(std::path::PathBuf, crate::registry::CreateModule)> { None }
+    fn template_content(&self) -> Option<crate::templates::TemplateContent> { None }
+    fn key_bindings(&self) -> Vec<KeyBinding> { Vec::new() }
+    fn set_focus_mode(&mut self, focus: bool) { let _ = focus; }
+    fn tour(&self) -> Option<(&'static str, &'static [crate::tour::TourStep])> { None }
+    fn file_path(&self) -> Option<&std::path::Path> { None }
+    fn view_state(&self) -> Option<ViewState> { None }
+    fn restore_view_state(&mut self, state: ViewState) { let _ = state; }
+    fn accepts_text_paste(&self) -> bool { false }
+    /// Files the module takes itself when dropped on the window; the rest are opened as documents.
+    fn accepts_dropped_file(&self, path: &std::path::Path) -> bool { let _ = path; false }
+    /// How far along the document's running job is, mirrored onto the OS taskbar.
+    fn progress(&self) -> Option<f32> { None }
+    fn reload_config(&mut self) {}
+    /// Called by the app when it closes the document or exits, so per-file state is kept for next time.
+    fn unload(&mut self) {}
+    fn apply_settings(&mut self, settings: &EditorSettings) { let _ = settings; }
+    /// Settings the module changed from its own UI, written into `settings` so the app keeps them for the next editor.
+    fn take_settings_change(&mut self, settings: &mut EditorSettings) -> bool { let _ = settings; false }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(c: MenuContribution) -> Vec<MenuEntry> {
+        [c.file_items, c.edit_items, c.view_items, c.image_items, c.filter_items, c.layer_items, c.insert_items, c.format_items].concat()
+    }
+
+    fn assert_all_handled(module: &mut dyn EditorModule) {
+        let opens_dialog = |a: &MenuAction| matches!(a, MenuAction::Image(ImageMenuAction::PlaceImage | ImageMenuAction::ExportWithPreset(_)) | MenuAction::Document(DocumentMenuAction::InsertImage));
+        for entry in entries(module.get_menu_contributions()) {
+            let MenuEntry::Item(item, action) = entry else { continue };
+            if !item.enabled || opens_dialog(&action) { continue; }
+            assert!(module.handle_menu_action(action.clone()), "{} ({:?}) was not handled", item.label, action);
+        }
+    }
+
+    #[test]
+    fn every_enabled_menu_item_is_handled() {
+        let path = std::env::temp_dir().join(format!("ue_menu_{}.png", std::process::id()));
+        image::RgbaImage::from_pixel(8, 8, image::Rgba([40, 80, 120, 255])).save(&path).unwrap();
+        let mut ie = image_edit::ImageEditor::load(path.clone()).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_all_handled(&mut ie);
+        assert_all_handled(&mut text_edit::TextEditor::new_empty());
+        assert_all_handled(&mut doc_edit::DocumentEditor::new_empty());
+        assert_all_handled(&mut json_edit::JsonEditor::new_empty());
+    }
+
+    #[test]
+    fn a_press_goes_to_its_most_specific_chord_and_the_list_is_generated_from_the_same_chords() {
+        #[derive(Clone, Copy, PartialEq, Debug)]
+        enum K { Undo, Redo, Heading, Select, Checklist }
+        const KEYS: &[Shortcut<K>] = &[
+            shortcut(&[ctrl(egui::Key::Z)], K::Undo, "Undo"),
+            shortcut(&[ctrl(egui::Key::Y), ctrl_shift(egui::Key::Z)], K::Redo, "Redo"),
+            shortcut(&[ctrl(egui::Key::Num1), ctrl(egui::Key::Num2), ctrl(egui::Key::Num3)], K::Heading, "Heading"),
+            shortcut(&[ctrl(egui::Key::L)], K::Select, "Select line"),
+            shortcut(&[ctrl_shift(egui::Key::L)], K::Checklist, "Checklist"),
+            gesture("Shift+drag", "Constrain"),
+        ];
+        let press = |c: Chord| egui::Event::Key { key: c.key, physical_key: None, pressed: true, repeat: false, modifiers: c.modifiers };
+        let mut input = egui::InputState::default();
+        input.events = vec![press(ctrl_shift(egui::Key::Z)), press(ctrl(egui::Key::Num2)), press(ctrl_shift(egui::Key::L))];
+        assert_eq!(pressed_shortcuts(&mut input, KEYS, |a| a != K::Checklist), vec![(K::Redo, 1), (K::Heading, 1)]);
+        assert_eq!(input.events.len(), 1, "Ctrl+Shift+L is left for whoever runs the checklist, not taken as Ctrl+L");
+        assert_eq!(pressed_shortcuts(&mut input, KEYS, |a| a == K::Checklist), vec![(K::Checklist, 0)]);
+
+        let listed: Vec<String> = key_bindings(KEYS).into_iter().map(|b| b.keys).collect();
+        assert_eq!(listed, ["Ctrl+Z", "Ctrl+Y / Ctrl+Shift+Z", "Ctrl+1-3", "Ctrl+L", "Ctrl+Shift+L", "Shift+drag"]);
+        assert_eq!(menu_keys(KEYS, K::Redo), "Ctrl+Y");
+        let text = text_editor::key_bindings();
+        assert!(text.contains(&KeyBinding { keys: "Ctrl+Shift+S".into(), action: "Strikethrough" }));
+        assert!(image_editor::key_bindings().iter().any(|b| b.keys == "Ctrl+1-9 / Ctrl+0"));
+    }
+
+    #[test]
+    fn only_modules_that_take_a_dropped_file_claim_it() {
+        let (png, txt) = (std::path::Path::new("photo.PNG"), std::path::Path::new("notes.txt"));
+        let ie = image_edit::ImageEditor::new();
+        assert!(ie.accepts_dropped_file(png) && !ie.accepts_dropped_file(txt));
+        assert!(!text_edit::TextEditor::new_empty().accepts_dropped_file(png));
+        assert!(data_converter::DataConverter::new().accepts_dropped_file(txt));
+    }
+
+    #[test]
+    fn a_setting_changed_in_one_editor_reaches_the_next_through_the_app() {
+        let mut settings = EditorSettings::default();
+        let mut first = image_edit::ImageEditor::new();
+        first.apply_settings(&settings);
+        toggle(&mut first, "Surround: Dark Gray");
+        assert!(first.take_settings_change(&mut settings));
+        assert!(!first.take_settings_change(&mut settings));
+
+        let mut next = image_edit::ImageEditor::new();
+        assert_eq!(checked(&next, "Surround: Dark Gray"), Some(false));
+        next.apply_settings(&settings);
+        assert_eq!(checked(&next, "Surround: Dark Gray"), Some(true));
+    }
+
+    fn checked(module: &dyn EditorModule, label: &str) -> Option<bool> {
+        entries(module.get_menu_contributions()).into_iter().find_map(|e| match e {
+            MenuEntry::Item(item, _) if item.label == label => item.checked,
+            _ => None,
+        })
+    }
+
+    fn toggle(module: &mut dyn EditorModule, label: &str) {
+        let action = entries(module.get_menu_contributions()).into_iter().find_map(|e| match e {
+            MenuEntry::Item(item, action) if item.label == label => Some(action),
+            _ => None,
+        }).unwrap();
+        assert!(module.handle_menu_action(action));
+    }
+
+    #[test]
+    fn toggling_a_menu_item_flips_its_checkmark_on_the_next_fetch() {
+        let mut ie = image_edit::ImageEditor::new();
+        assert_eq!(checked(&ie, "Lock Pixels"), Some(false));
+        toggle(&mut ie, "Lock Pixels");
+        assert_eq!(checked(&ie, "Lock Pixels"), Some(true));
+
+        let mut te = text_edit::TextEditor::new_empty();
+        let before = (checked(&te, "Markdown View"), checked(&te, "Plain Text View"));
+        assert!(matches!(before, (Some(a), Some(b)) if a != b));
+        toggle(&mut te, if before.0 == Some(true) { "Plain Text View" } else { "Markdown View" });
+        assert_eq!((checked(&te, "Markdown View"), checked(&te, "Plain Text View")), (before.1, before.0));
+        assert_eq!(checked(&te, "Word Count in File Info"), Some(false));
+        toggle(&mut te, "Word Count in File Info");
+        assert_eq!(checked(&te, "Word Count in File Info"), Some(true));
+    }
+
+    fn enabled(module: &dyn EditorModule, label: &str) -> Option<bool> {
+        entries(module.get_menu_contributions()).into_iter().find_map(|e| match e {
+            MenuEntry::Item(item, _) if item.label == label => Some(item.enabled),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn the_last_filter_is_remembered_through_the_app_not_a_global() {
+        let path = std::env::temp_dir().join(format!("ue_repeat_{}.png", std::process::id()));
+        image::RgbaImage::from_pixel(8, 8, image::Rgba([40, 80, 120, 255])).save(&path).unwrap();
+        let mut first = image_edit::ImageEditor::load(path.clone()).unwrap();
+        let _ = std::fs::remove_file(&path);
+        let mut settings = EditorSettings::default();
+        first.apply_settings(&settings);
+        assert_eq!(enabled(&first, "Repeat Last Filter"), Some(false));
+        toggle(&mut first, "Invert");
+        assert_eq!(enabled(&first, "Repeat Invert"), Some(true));
+        assert!(first.take_settings_change(&mut settings));
+        assert_eq!(enabled(&image_edit::ImageEditor::new(), "Repeat Last Filter"), Some(false));
+        let mut next = image_edit::ImageEditor::new();
+        next.apply_settings(&settings);
+        assert!(enabled(&next, "Repeat Invert").is_some());
+    }
 }
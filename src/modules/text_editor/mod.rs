@@ -1,5 +1,7 @@
 pub mod te_main;
+mod te_colors;
+mod te_count;
 mod te_tools;
 mod te_ui;
 
-pub use te_main::TextEditor;
+pub use te_main::{TextEditor, TextEditorSettings, TextMenuAction, PasteCleanup, key_bindings};
@@ -0,0 +1,332 @@
+use eframe::egui;
+use std::hash::{Hash, Hasher};
+use crate::modules::helpers::hsv_picker::{self, rgb_to_hsv_f32, hsv_to_rgb_f32};
+use super::te_main::TextEditor;
+
+const PICKER_SIZE: f32 = 168.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum ColorFormat { Hex3, Hex6, Hex8, Rgb, Rgba }
+
+impl ColorFormat {
+    fn has_alpha(self) -> bool { matches!(self, Self::Hex8 | Self::Rgba) }
+}
+
+/// A color written in the text, `start..end` in bytes of its line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) struct ColorLiteral { pub start: usize, pub end: usize, pub rgba: [u8; 4], pub format: ColorFormat, pub upper: bool, pub compact: bool }
+
+fn is_word(b: u8) -> bool { b.is_ascii_alphanumeric() || b == b'_' || b == b'-' }
+
+fn parse_hex(line: &[u8], at: usize) -> Option<ColorLiteral> {
+    if at > 0 && (is_word(line[at - 1]) || line[at - 1] == b'&') { return None; }
+    let digits = line[at + 1..].iter().take_while(|b| b.is_ascii_hexdigit()).count();
+    if line.get(at + 1 + digits).is_some_and(|b| is_word(*b)) { return None; }
+    let text = std::str::from_utf8(&line[at + 1..at + 1 + digits]).ok()?;
+    let nib = |i: usize| u8::from_str_radix(&text[i..i + 1], 16).ok();
+    let byte = |i: usize| u8::from_str_radix(&text[i..i + 2], 16).ok();
+    let (rgba, format) = match digits {
+        3 => ([nib(0)? * 17, nib(1)? * 17, nib(2)? * 17, 255], ColorFormat::Hex3),
+        6 => ([byte(0)?, byte(2)?, byte(4)?, 255], ColorFormat::Hex6),
+        8 => ([byte(0)?, byte(2)?, byte(4)?, byte(6)?], ColorFormat::Hex8),
+        _ => return None,
+    };
+    Some(ColorLiteral { start: at, end: at + 1 + digits, rgba, format, upper: text.bytes().any(|b| b.is_ascii_uppercase()), compact: false })
+}
+
+fn parse_channel(s: &str) -> Option<u8> {
+    match s.strip_suffix('%') {
+        Some(p) => p.trim().parse::<f32>().ok().filter(|v| (0.0..=100.0).contains(v)).map(|v| (v * 2.55).round() as u8),
+        None => s.parse::<u16>().ok().filter(|v| *v <= 255).map(|v| v as u8),
+    }
+}
+
+fn parse_alpha(s: &str) -> Option<u8> {
+    let v = match s.strip_suffix('%') { Some(p) => p.trim().parse::<f32>().ok()? / 100.0, None => s.parse::<f32>().ok()? };
+    (0.0..=1.0).contains(&v).then(|| (v * 255.0).round() as u8)
+}
+
+fn parse_rgb(line: &str, at: usize) -> Option<ColorLiteral> {
+    if at > 0 && is_word(line.as_bytes()[at - 1]) { return None; }
+    let rest = &line[at + 3..];
+    let (alpha_name, rest) = match rest.strip_prefix('a') { Some(r) => (true, r), None => (false, rest) };
+    let args = rest.strip_prefix('(')?;
+    let close = args.find(')')?;
+    let parts: Vec<&str> = args[..close].split(',').map(str::trim).collect();
+    let [r, g, b] = [parse_channel(parts.first()?)?, parse_channel(parts.get(1)?)?, parse_channel(parts.get(2)?)?];
+    let (a, format) = match (parts.len(), alpha_name) {
+        (3, false) => (255, ColorFormat::Rgb),
+        (4, _) => (parse_alpha(parts[3])?, ColorFormat::Rgba),
+        _ => return None,
+    };
+    let end = line.len() - args.len() + close + 1;
+    Some(ColorLiteral { start: at, end, rgba: [r, g, b, a], format, upper: false, compact: !args[..close].contains(", ") })
+}
+
+/// Byte ranges of a line that are comments in the usual config/CSS forms. Block comments are only followed within the line.
+fn comment_ranges(line: &str) -> Vec<std::ops::Range<usize>> {
+    let trimmed = line.trim_start();
+    let indent = line.len() - trimmed.len();
+    if trimmed.starts_with(';') || trimmed.starts_with("# ") || trimmed == "#" { return std::iter::once(indent..line.len()).collect(); }
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < line.len() {
+        let rest = &line[i..];
+        let close = if rest.starts_with("/*") { Some("*/") } else if rest.starts_with("<!--") { Some("-->") } else { None };
+        if let Some(close) = close {
+            let end = rest[2..].find(close).map_or(line.len(), |e| i + 2 + e + close.len());
+            out.push(i..end);
+            i = end;
+        } else if rest.starts_with("//") && !line[..i].ends_with(':') {
+            out.push(i..line.len());
+            break;
+        } else {
+            i += rest.chars().next().map_or(1, char::len_utf8);
+        }
+    }
+    out
+}
+
+pub(super) fn find_colors(line: &str, skip_comments: bool) -> Vec<ColorLiteral> {
+    let bytes = line.as_bytes();
+    let mut found = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let lit = match bytes[i] {
+            b'#' => parse_hex(bytes, i),
+            b'r' if line[i..].starts_with("rgb") => parse_rgb(line, i),
+            _ => None,
+        };
+        match lit {
+            Some(l) => { found.push(l); i = l.end; }
+            None => i += 1,
+        }
+    }
+    if skip_comments && !found.is_empty() {
+        let comments = comment_ranges(line);
+        found.retain(|l| !comments.iter().any(|c| c.contains(&l.start)));
+    }
+    found
+}
+
+fn format_alpha(a: u8) -> String {
+    let s = format!("{:.2}", a as f32 / 255.0);
+    let s = s.trim_end_matches('0').trim_end_matches('.');
+    if s.is_empty() { "0".to_string() } else { s.to_string() }
+}
+
+/// Writes `rgba` the way `like` was written: same notation, hex case and comma spacing.
+pub(super) fn format_color(rgba: [u8; 4], like: &ColorLiteral) -> String {
+    let [r, g, b, a] = rgba;
+    let sep = if like.compact { "," } else { ", " };
+    let hex = match like.format {
+        ColorFormat::Hex3 if [r, g, b].iter().all(|c| c % 17 == 0) => format!("#{:x}{:x}{:x}", r / 17, g / 17, b / 17),
+        ColorFormat::Hex3 | ColorFormat::Hex6 => format!("#{:02x}{:02x}{:02x}", r, g, b),
+        ColorFormat::Hex8 => format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a),
+        ColorFormat::Rgb => return format!("rgb({r}{sep}{g}{sep}{b})"),
+        ColorFormat::Rgba => return format!("rgba({r}{sep}{g}{sep}{b}{sep}{})", format_alpha(a)),
+    };
+    if like.upper { hex.to_uppercase() } else { hex }
+}
+
+fn line_hash(line: &str) -> u64 {
+    let mut h = std::collections::hash_map::DefaultHasher::new();
+    line.hash(&mut h);
+    h.finish()
+}
+
+/// Literals found per line, kept until that line's text changes, plus where each line starts in the text and in
+/// the galley so a frame only visits the lines on screen.
+#[derive(Default)]
+pub(super) struct ColorCache { lines: Vec<Option<(u64, Vec<ColorLiteral>)>>, skip_comments: bool, layout_key: Option<(u64, usize, usize)>, line_starts: Vec<usize>, line_rows: Vec<usize> }
+
+impl ColorCache {
+    /// Rebuilds the line index when the text or its layout changed; both are a plain pass over bytes and rows.
+    fn index(&mut self, content: &str, version: u64, galley: &std::sync::Arc<egui::Galley>) {
+        let key = (version, std::sync::Arc::as_ptr(galley) as usize, galley.rows.len());
+        if self.layout_key == Some(key) { return; }
+        self.layout_key = Some(key);
+        self.line_starts = std::iter::once(0).chain(content.match_indices('\n').map(|(i, _)| i + 1)).collect();
+        self.line_rows = std::iter::once(0).chain(galley.rows.iter().enumerate().filter(|(_, r)| r.ends_with_newline).map(|(i, _)| i + 1)).collect();
+    }
+
+    /// The first line whose last row reaches `top`, found by bisecting the line index.
+    fn first_line_below(&self, galley: &egui::Galley, top: f32) -> usize {
+        let next_starts = self.line_rows.get(1..).unwrap_or_default();
+        next_starts.partition_point(|&next| galley.rows.get(next - 1).is_some_and(|r| r.rect().bottom() < top))
+    }
+
+    fn get(&mut self, line_no: usize, line: &str, skip_comments: bool) -> &[ColorLiteral] {
+        if skip_comments != self.skip_comments { self.lines.clear(); self.skip_comments = skip_comments; }
+        if self.lines.len() <= line_no { self.lines.resize(line_no + 1, None); }
+        let hash = line_hash(line);
+        let slot = &mut self.lines[line_no];
+        if slot.as_ref().is_none_or(|(h, _)| *h != hash) { *slot = Some((hash, find_colors(line, skip_comments))); }
+        slot.as_ref().map_or(&[], |(_, l)| l.as_slice())
+    }
+}
+
+/// The swatch the mini picker was opened from. `start` is a byte offset into the whole text.
+pub(super) struct ColorEdit { start: usize, literal: ColorLiteral, original: String, hsv: (f32, f32, f32), alpha: u8, anchor: egui::Pos2, opened: bool }
+
+impl ColorEdit {
+    fn rgba(&self) -> [u8; 4] {
+        let [r0, g0, b0, _] = self.literal.rgba;
+        let alpha = if self.literal.format.has_alpha() { self.alpha } else { 255 };
+        if self.hsv == rgb_to_hsv_f32(r0 as f32 / 255.0, g0 as f32 / 255.0, b0 as f32 / 255.0) { return [r0, g0, b0, alpha]; }
+        let (r, g, b) = hsv_to_rgb_f32(self.hsv.0, self.hsv.1, self.hsv.2);
+        let c = |v: f32| (v * 255.0).round() as u8;
+        [c(r), c(g), c(b), alpha]
+    }
+}
+
+fn swatch_color([r, g, b, a]: [u8; 4]) -> egui::Color32 { egui::Color32::from_rgba_unmultiplied(r, g, b, a) }
+
+fn paint_swatch(painter: &egui::Painter, rect: egui::Rect, rgba: [u8; 4]) {
+    if rgba[3] < 255 {
+        let half = egui::Rect::from_min_max(rect.min, egui::pos2(rect.center().x, rect.max.y));
+        painter.rect_filled(rect, 1.0, egui::Color32::WHITE);
+        painter.rect_filled(half, 1.0, egui::Color32::from_gray(120));
+    }
+    painter.rect_filled(rect, 1.0, swatch_color(rgba));
+}
+
+impl TextEditor {
+    /// Draws a color bar under each color literal on the visible lines and opens the mini picker when one is clicked.
+    pub(super) fn paint_color_swatches(&mut self, ui: &mut egui::Ui, output: &egui::text_edit::TextEditOutput) {
+        if !self.settings.color_swatches { self.color_edit = None; return; }
+        let clip = ui.clip_rect();
+        let (galley, origin) = (&output.galley, output.galley_pos);
+        let skip = self.settings.skip_comment_colors;
+        self.color_cache.index(&self.content, self.content_version, galley);
+        let mut clicked = None;
+        let first_line = self.color_cache.first_line_below(galley, clip.top() - origin.y);
+        for line_no in first_line..self.color_cache.line_starts.len().min(self.color_cache.line_rows.len()) {
+            let (first, byte_start) = (self.color_cache.line_rows[line_no], self.color_cache.line_starts[line_no]);
+            let row = self.color_cache.line_rows.get(line_no + 1).copied().unwrap_or(galley.rows.len());
+            let Some(first_row) = galley.rows.get(first) else { break };
+            if origin.y + first_row.rect().top() > clip.bottom() { break; }
+            let line_end = self.color_cache.line_starts.get(line_no + 1).map_or(self.content.len(), |e| e - 1);
+            let line = &self.content[byte_start..line_end];
+            for lit in self.color_cache.get(line_no, line, skip).to_vec() {
+                let (mut col, mut r) = (line[..lit.start].chars().count(), first);
+                while r < row - 1 && col >= galley.rows[r].char_count_excluding_newline() { col -= galley.rows[r].char_count_excluding_newline(); r += 1; }
+                let placed = &galley.rows[r];
+                let len = line[lit.start..lit.end].chars().count();
+                let x0 = origin.x + placed.pos.x + placed.x_offset(col);
+                let x1 = origin.x + placed.pos.x + placed.x_offset((col + len).min(placed.char_count_excluding_newline()));
+                let bottom = origin.y + placed.rect().bottom();
+                let bar = egui::Rect::from_min_max(egui::pos2(x0, bottom - 3.0), egui::pos2(x1.max(x0 + 4.0), bottom));
+                let shown = match &self.color_edit { Some(e) if e.start == byte_start + lit.start => e.rgba(), _ => lit.rgba };
+                paint_swatch(ui.painter(), bar, shown);
+                let hit = ui.interact(bar.expand2(egui::vec2(0.0, 2.0)), ui.id().with(("te_swatch", byte_start + lit.start)), egui::Sense::click())
+                    .on_hover_cursor(egui::CursorIcon::PointingHand).on_hover_text("Pick a color");
+                if hit.clicked() { clicked = Some((byte_start, lit, bar.left_bottom())); }
+            }
+        }
+        if let Some((line_byte, lit, anchor)) = clicked {
+            let [r, g, b, a] = lit.rgba;
+            let (r, g, b) = (r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0);
+            let start = line_byte + lit.start;
+            self.color_edit = Some(ColorEdit { start, literal: lit, original: self.content[start..line_byte + lit.end].to_string(), hsv: rgb_to_hsv_f32(r, g, b), alpha: a, anchor: anchor + egui::vec2(0.0, 4.0), opened: true });
+        }
+    }
+
+    pub(super) fn render_color_edit(&mut self, ctx: &egui::Context) {
+        let Some(edit) = &mut self.color_edit else { return };
+        if self.content.get(edit.start..edit.start + edit.original.len()) != Some(edit.original.as_str()) { self.color_edit = None; return; }
+        let outline = if ctx.style().visuals.dark_mode { egui::Color32::from_gray(90) } else { egui::Color32::from_gray(170) };
+        let (mut apply, mut cancel) = (false, ctx.input(|i| i.key_pressed(egui::Key::Escape)));
+        let area = egui::Area::new(egui::Id::new("te_color_edit")).order(egui::Order::Foreground).fixed_pos(edit.anchor).show(ctx, |ui| {
+            egui::Frame::popup(ui.style()).inner_margin(8.0).show(ui, |ui| {
+                let (h, s, v) = &mut edit.hsv;
+                let (sq, _) = ui.allocate_exact_size(egui::vec2(PICKER_SIZE, PICKER_SIZE), egui::Sense::hover());
+                hsv_picker::sv_square(ui, sq, ui.id().with("te_sv"), *h, s, v);
+                let (strip, _) = ui.allocate_exact_size(egui::vec2(PICKER_SIZE, 14.0), egui::Sense::hover());
+                hsv_picker::hue_strip(ui, strip, ui.id().with("te_hue"), h, outline);
+                if edit.literal.format.has_alpha() {
+                    ui.add(egui::Slider::new(&mut edit.alpha, 0..=255).text("Alpha"));
+                }
+                let text = format_color(edit.rgba(), &edit.literal);
+                ui.horizontal(|ui| {
+                    let (before, _) = ui.allocate_exact_size(egui::vec2(18.0, 18.0), egui::Sense::hover());
+                    paint_swatch(ui.painter(), before, edit.literal.rgba);
+                    let (after, _) = ui.allocate_exact_size(egui::vec2(18.0, 18.0), egui::Sense::hover());
+                    paint_swatch(ui.painter(), after, edit.rgba());
+                    ui.label(egui::RichText::new(&text).monospace());
+                });
+                ui.horizontal(|ui| {
+                    apply = ui.button("Apply").clicked();
+                    cancel |= ui.button("Cancel").clicked();
+                });
+            });
+        });
+        if area.response.clicked_elsewhere() && !apply && !std::mem::take(&mut edit.opened) { cancel = true; }
+        if cancel { self.color_edit = None; return; }
+        if apply && let Some(edit) = self.color_edit.take() {
+            let text = format_color(edit.rgba(), &edit.literal);
+            if text != edit.original { self.replace_color(edit.start..edit.start + edit.original.len(), &text); }
+        }
+    }
+
+    /// Swaps a color literal for `text` as one undo step, leaving the caret after it.
+    pub(super) fn replace_color(&mut self, range: std::ops::Range<usize>, text: &str) {
+        let start = range.start;
+        self.splice(range, text);
+        self.pending_cursor_pos = Some(self.content[..start + text.len()].chars().count());
+        self.refresh_dirty();
+        self.content_version = self.content_version.wrapping_add(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_hex_and_rgb_literals_and_writes_them_back_in_kind() {
+        let line = "a { color: #3FA7D6; border: 1px solid #fff; bg: rgba(10, 20, 30, 0.5); fg: rgb(0,128,255); x: #12345; id#abc }";
+        let found = find_colors(line, false);
+        let texts: Vec<&str> = found.iter().map(|l| &line[l.start..l.end]).collect();
+        assert_eq!(texts, ["#3FA7D6", "#fff", "rgba(10, 20, 30, 0.5)", "rgb(0,128,255)"]);
+        assert_eq!(found[0].rgba, [0x3f, 0xa7, 0xd6, 255]);
+        assert_eq!(found[2].rgba, [10, 20, 30, 128]);
+        assert_eq!(format_color([0x11, 0x22, 0xaa, 255], &found[0]), "#1122AA");
+        assert_eq!(format_color([0x11, 0x22, 0x33, 255], &found[1]), "#123");
+        assert_eq!(format_color([0x10, 0x22, 0x33, 255], &found[1]), "#102233");
+        assert_eq!(format_color([1, 2, 3, 64], &found[2]), "rgba(1, 2, 3, 0.25)");
+        assert_eq!(format_color([1, 2, 3, 255], &found[3]), "rgb(1,2,3)");
+        let eight = find_colors("#11223380", false);
+        assert_eq!((eight[0].format, eight[0].rgba[3]), (ColorFormat::Hex8, 0x80));
+    }
+
+    #[test]
+    fn comments_can_be_skipped_and_the_cache_follows_line_edits() {
+        let line = "color: #000; /* was #fff */ // or #eee";
+        assert_eq!(find_colors(line, false).len(), 3);
+        assert_eq!(find_colors(line, true).len(), 1);
+        assert_eq!(find_colors("; accent = #abcdef", true).len(), 0);
+        assert_eq!(find_colors("url: http://x.io/#abc", true).len(), 1);
+        let mut cache = ColorCache::default();
+        assert_eq!(cache.get(4, "#fff", false).len(), 1);
+        assert_eq!(cache.get(4, "#fff #000", false).len(), 2);
+        assert_eq!(cache.get(4, "/* #fff */", true).len(), 0);
+    }
+
+    #[test]
+    fn the_line_index_finds_the_first_line_on_screen() {
+        let ctx = egui::Context::default();
+        let text = (0..200).map(|i| format!("line {i} #fff")).collect::<Vec<_>>().join("\n");
+        let mut galley = None;
+        let _ = ctx.run(Default::default(), |ctx| galley = Some(ctx.fonts_mut(|f| f.layout_no_wrap(text.clone(), egui::FontId::monospace(10.0), egui::Color32::WHITE))));
+        let galley = galley.unwrap();
+        let mut cache = ColorCache::default();
+        cache.index(&text, 1, &galley);
+        assert_eq!((cache.line_starts.len(), cache.line_rows.len()), (200, 200));
+        let row_h = galley.rows[0].rect().height();
+        let first = cache.first_line_below(&galley, row_h * 120.5);
+        assert_eq!(first, 120);
+        assert!(text[cache.line_starts[first]..].starts_with("line 120 "));
+        assert_eq!(cache.first_line_below(&galley, -5.0), 0);
+    }
+}
@@ -0,0 +1,131 @@
+//! Word counting for the status bar and the Word Count dialog. Words come from unicode segmentation, so text
+//! without spaces (Chinese, Japanese) still counts, and markdown syntax can be left out with code counted apart.
+//! The text is counted a block at a time (split at blank lines) and blocks are cached by hash, so an edit only
+//! recounts the block it touched.
+
+use std::collections::HashMap;
+use unicode_segmentation::UnicodeSegmentation;
+use super::te_main::content_hash;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum CjkCounting { PerCharacter, PerSegment }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct WordCountOptions { pub cjk: CjkCounting, pub markdown: bool }
+
+impl Default for WordCountOptions { fn default() -> Self { Self { cjk: CjkCounting::PerCharacter, markdown: false } } }
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(super) struct WordCounts { pub words: usize, pub code: usize }
+
+impl std::fmt::Display for WordCounts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} words", self.words)?;
+        if self.code > 0 { write!(f, " + {} in code", self.code)?; }
+        Ok(())
+    }
+}
+
+/// Counts of one block plus whether it ends inside a code fence, keyed by the block's hash and fence state at its start.
+#[derive(Clone, Copy)]
+struct BlockCount { counts: WordCounts, fenced_after: bool }
+
+#[derive(Default)]
+pub(super) struct WordCountCache { version: Option<u64>, options: WordCountOptions, blocks: HashMap<(u64, bool), BlockCount>, total: WordCounts }
+
+impl WordCountCache {
+    pub(super) fn counts(&mut self, text: &str, version: u64, options: WordCountOptions) -> WordCounts {
+        if self.version == Some(version) && self.options == options { return self.total; }
+        if self.options != options { self.blocks.clear(); }
+        let mut blocks = HashMap::with_capacity(self.blocks.len());
+        let (mut total, mut fenced) = (WordCounts::default(), false);
+        for block in text.split("\n\n") {
+            let key = (content_hash(block), fenced);
+            let count = self.blocks.get(&key).copied().unwrap_or_else(|| count_block(block, fenced, options));
+            blocks.insert(key, count);
+            total.words += count.counts.words; total.code += count.counts.code;
+            fenced = count.fenced_after;
+        }
+        *self = Self { version: Some(version), options, blocks, total };
+        total
+    }
+}
+
+fn count_block(block: &str, mut fenced: bool, options: WordCountOptions) -> BlockCount {
+    let mut counts = WordCounts::default();
+    for line in block.lines() {
+        if !options.markdown { counts.words += count_prose(line, options.cjk); continue; }
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") { fenced = !fenced; continue; }
+        if fenced { counts.code += count_prose(line, options.cjk); }
+        else { counts.words += count_prose(&without_link_targets(line), options.cjk); }
+    }
+    BlockCount { counts, fenced_after: fenced }
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32, 0x3040..=0x30FF | 0x31F0..=0x31FF | 0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xF900..=0xFAFF | 0xFF66..=0xFF9F | 0x20000..=0x2FA1F)
+}
+
+/// Segmentation splits CJK text into single ideographs and kana runs; those count per character, or each unbroken
+/// run of them counts once.
+fn count_prose(text: &str, cjk: CjkCounting) -> usize {
+    let (mut n, mut run_end) = (0, None);
+    for (at, word) in text.unicode_word_indices() {
+        if !word.chars().all(is_cjk) { n += 1; run_end = None; continue; }
+        n += match cjk {
+            CjkCounting::PerCharacter => word.chars().count(),
+            CjkCounting::PerSegment => usize::from(run_end != Some(at)),
+        };
+        run_end = Some(at + word.len());
+    }
+    n
+}
+
+/// A markdown line without the targets of `[text](url)` links and `<scheme://...>` autolinks.
+fn without_link_targets(line: &str) -> std::borrow::Cow<'_, str> {
+    if !line.contains("](") && !line.contains("://") { return line.into(); }
+    let (mut out, mut rest) = (String::with_capacity(line.len()), line);
+    loop {
+        let link = rest.find("](").map(|i| (i, i + 2, ')'));
+        let auto = rest.find('<').filter(|&i| rest[i..].split('>').next().is_some_and(|t| t.contains("://"))).map(|i| (i, i + 1, '>'));
+        let Some((at, from, close)) = [link, auto].into_iter().flatten().min_by_key(|t| t.0) else { break };
+        let Some(len) = rest[from..].find(close) else { break };
+        out.push_str(&rest[..at]); out.push(' ');
+        rest = &rest[from + len + 1..];
+    }
+    out.push_str(rest);
+    out.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count(text: &str, cjk: CjkCounting, markdown: bool) -> WordCounts {
+        WordCountCache::default().counts(text, 0, WordCountOptions { cjk, markdown })
+    }
+
+    #[test]
+    fn chinese_counts_per_character_or_per_run_alongside_english() {
+        let text = "Hello world, 我们喜欢编程。Rust 很好";
+        assert_eq!(count(text, CjkCounting::PerCharacter, false).words, 2 + 6 + 1 + 2);
+        assert_eq!(count(text, CjkCounting::PerSegment, false).words, 2 + 1 + 1 + 1);
+        assert_eq!(count("コンピュータを使う", CjkCounting::PerCharacter, false).words, 9);
+    }
+
+    #[test]
+    fn markdown_counting_skips_syntax_and_link_targets_and_counts_fences_as_code() {
+        let doc = "## Getting started\n\nRead the [install guide](https://example.com/docs/install) or <https://example.com>.\n\n```rust\nfn main() {\n\n    println!(\"hi\");\n}\n```\n\n- **Done** now\n";
+        assert_eq!(count(doc, CjkCounting::PerCharacter, true), WordCounts { words: 2 + 5 + 2, code: 2 + 2 });
+        assert_eq!(count(doc, CjkCounting::PerCharacter, true).to_string(), "9 words + 4 in code");
+        assert!(count(doc, CjkCounting::PerCharacter, false).words > 13);
+
+        let mut cache = WordCountCache::default();
+        let options = WordCountOptions { cjk: CjkCounting::PerCharacter, markdown: true };
+        cache.counts(doc, 1, options);
+        let edited = doc.replace("now", "now and then");
+        assert_eq!(cache.counts(&edited, 2, options).words, 11);
+        assert_eq!(cache.blocks.len(), 5);
+    }
+}
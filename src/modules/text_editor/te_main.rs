@@ -1,13 +1,260 @@
 use eframe::egui;
 use ropey::Rope;
 use std::fs::File;
-use std::io::{BufReader, BufWriter};
-use std::path::PathBuf;
-use crate::modules::{EditorModule, MenuAction, MenuItem, MenuContribution};
+use std::io::{BufReader, BufWriter, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use serde::{Deserialize, Serialize};
+use crate::modules::{EditorModule, EditorSettings, SaveState, MenuAction, MenuEntry, MenuContribution, KeyBinding, ViewState, Shortcut, shortcut, gesture, key, ctrl, shift, alt, ctrl_shift, menu_keys};
+use crate::error::{EditorError, EditorResult};
+use crate::modules::helpers::config_file;
+use crate::modules::helpers::undo::{UndoHistory, UndoableEdit};
+use crate::modules::helpers::file_info::{needs_overwrite_confirm, open_in_another_window, release_overwrite_target, overwrite_confirm_window};
+use super::te_colors::{ColorCache, ColorEdit};
+use super::te_count::{WordCountCache, WordCountOptions, WordCounts};
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum ViewMode { Plain, Markdown, }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextMenuAction { WordCount, ToggleBookmark, NextBookmark, PrevBookmark, ClearBookmarks, BookmarksPanel, ViewMode(ViewMode), WordCountInInfo }
+
+pub(super) const LARGE_PASTE_BYTES: usize = 1 << 20;
+pub(super) const PASTE_CHUNK_BYTES: usize = 256 << 10;
+
+pub(super) const PREVIEW_BYTES: u64 = 16 << 20;
+const READ_CHUNK: usize = 1 << 20;
+
+const UNDO_DEPTH: usize = 100;
+const UNDO_BYTES_IN_MEMORY: usize = 256 << 20;
+const TYPING_COALESCE: std::time::Duration = std::time::Duration::from_secs(1);
+const UNDO_STEPS_PER_FILE: usize = 100;
+const UNDO_BYTES_PER_FILE: usize = 256 << 10;
+const UNDO_BYTES_TOTAL: usize = 4 << 20;
+const MARKDOWN_SNIFF_LINES: usize = 200;
+const MARKDOWN_SNIFF_MARKERS: usize = 3;
+
+pub(super) type UndoState = (egui::text::CCursorRange, String);
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PasteCleanup { pub ascii_punctuation: bool, pub strip_invisible: bool, pub nbsp_to_space: bool, pub normalize_line_endings: bool }
+
+impl Default for PasteCleanup {
+    fn default() -> Self { Self { ascii_punctuation: true, strip_invisible: true, nbsp_to_space: true, normalize_line_endings: true } }
+}
+
+impl PasteCleanup {
+    pub(super) fn clean(&self, text: &str) -> (String, usize) {
+        let mut out = String::with_capacity(text.len());
+        let mut cleaned = 0;
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            let replacement: Option<&str> = match c {
+                '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}' | '\u{2032}' if self.ascii_punctuation => Some("'"),
+                '\u{201C}' | '\u{201D}' | '\u{201E}' | '\u{201F}' | '\u{2033}' if self.ascii_punctuation => Some("\""),
+                '\u{2010}' | '\u{2011}' | '\u{2012}' | '\u{2013}' | '\u{2212}' if self.ascii_punctuation => Some("-"),
+                '\u{2014}' | '\u{2015}' if self.ascii_punctuation => Some("--"),
+                '\u{2026}' if self.ascii_punctuation => Some("..."),
+                '\u{200B}' | '\u{200C}' | '\u{200D}' | '\u{2060}' | '\u{FEFF}' | '\u{00AD}' if self.strip_invisible => Some(""),
+                '\u{00A0}' | '\u{2007}' | '\u{202F}' if self.nbsp_to_space => Some(" "),
+                '\r' if self.normalize_line_endings => { if chars.peek() == Some(&'\n') { chars.next(); } Some("\n") }
+                _ => None,
+            };
+            match replacement {
+                Some(r) => { out.push_str(r); cleaned += 1; }
+                None => out.push(c),
+            }
+        }
+        (out, cleaned)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub(super) struct FileViewState { pub bookmarks: Vec<usize>, pub undo: Option<SavedUndo>, pub view_mode: Option<ViewMode> }
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(super) struct UndoStep { at: usize, remove: usize, insert: String, cursor: [usize; 2] }
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(super) struct SavedUndo { pub hash: u64, pub saved: u64, pub steps: Vec<UndoStep> }
+
+/// The text and cursor from before a change. Keystrokes in quick succession merge into one undo step;
+/// everything else (formatting, line moves, replacing a color, ...) stays a step of its own.
+#[derive(Debug, Clone)]
+pub(super) struct TextSnapshot { pub state: UndoState, pub typing: bool }
+
+impl UndoableEdit for TextSnapshot {
+    fn size_bytes(&self) -> usize { self.state.1.len() }
+    fn coalesces_with(&self, older: &Self) -> bool { self.typing && older.typing }
+}
+
+impl UndoStep {
+    fn between(newer: &str, older: &UndoState) -> Self {
+        let (a, b) = (newer.as_bytes(), older.1.as_bytes());
+        let mut prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+        while !newer.is_char_boundary(prefix) || !older.1.is_char_boundary(prefix) { prefix -= 1; }
+        let mut suffix = a[prefix..].iter().rev().zip(b[prefix..].iter().rev()).take_while(|(x, y)| x == y).count();
+        while !newer.is_char_boundary(a.len() - suffix) || !older.1.is_char_boundary(b.len() - suffix) { suffix -= 1; }
+        let cursor = [older.0.primary.index, older.0.secondary.index];
+        Self { at: prefix, remove: a.len() - suffix - prefix, insert: older.1[prefix..b.len() - suffix].to_string(), cursor }
+    }
+
+    fn undo(&self, text: &str) -> Option<UndoState> {
+        let end = self.at.checked_add(self.remove)?;
+        if end > text.len() || !text.is_char_boundary(self.at) || !text.is_char_boundary(end) { return None; }
+        let mut older = text.to_string();
+        older.replace_range(self.at..end, &self.insert);
+        let cursor = |i: usize| egui::text::CCursor::new(i);
+        Some((egui::text::CCursorRange::two(cursor(self.cursor[1]), cursor(self.cursor[0])), older))
+    }
+}
+
+impl SavedUndo {
+    pub(super) fn capture(history: &UndoHistory<TextSnapshot>, current: &UndoState) -> Option<Self> {
+        let (mut newer, mut steps, mut bytes) = (current, Vec::new(), 0);
+        for older in history.undo_entries().take(UNDO_STEPS_PER_FILE) {
+            let step = UndoStep::between(&newer.1, &older.state);
+            bytes += step.insert.len() + 32;
+            if bytes > UNDO_BYTES_PER_FILE { break; }
+            steps.push(step);
+            newer = &older.state;
+        }
+        let saved = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        (!steps.is_empty()).then(|| Self { hash: content_hash(&current.1), saved, steps })
+    }
+
+    pub(super) fn rebuild(&self, current: &UndoState) -> Option<UndoHistory<TextSnapshot>> {
+        if self.hash != content_hash(&current.1) { return None; }
+        let mut states = vec![current.clone()];
+        for step in &self.steps { states.push(step.undo(&states[states.len() - 1].1)?); }
+        let mut history = TextEditor::new_history();
+        for state in states.drain(1..).rev() { history.push(TextSnapshot { state, typing: false }, "Edit"); }
+        history.mark_saved();
+        Some(history)
+    }
+
+    fn bytes(&self) -> usize { self.steps.iter().map(|s| s.insert.len() + 32).sum() }
+}
+
+impl FileViewState {
+    fn store_path() -> PathBuf { config_file::config_path("text_view_state.json") }
+
+    fn key(path: &Path) -> String { std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()).to_string_lossy().into_owned() }
+
+    fn load_all() -> std::collections::HashMap<String, FileViewState> {
+        config_file::read(&Self::store_path()).ok().and_then(|b| serde_json::from_slice(&b).ok()).unwrap_or_default()
+    }
+
+    pub(super) fn load(path: &Path) -> Self { Self::load_all().remove(&Self::key(path)).unwrap_or_default() }
+
+    fn is_empty(&self) -> bool { self.bookmarks.is_empty() && self.undo.is_none() && self.view_mode.is_none() }
+
+    pub(super) fn save(&self, path: &Path) {
+        let mut all = Self::load_all();
+        if self.is_empty() { all.remove(&Self::key(path)); } else { all.insert(Self::key(path), self.clone()); }
+        Self::trim_undo(&mut all);
+        if let Ok(json) = serde_json::to_string(&all) { let _ = config_file::write_atomic(&Self::store_path(), json.as_bytes()); }
+    }
+
+    fn trim_undo(all: &mut std::collections::HashMap<String, FileViewState>) {
+        let mut by_age: Vec<(u64, String, usize)> = all.iter().filter_map(|(k, v)| v.undo.as_ref().map(|u| (u.saved, k.clone(), u.bytes()))).collect();
+        by_age.sort_unstable_by_key(|(saved, _, _)| std::cmp::Reverse(*saved));
+        let mut total = 0;
+        for (_, key, bytes) in by_age {
+            total += bytes;
+            if total > UNDO_BYTES_TOTAL && let Some(state) = all.get_mut(&key) { state.undo = None; }
+        }
+        all.retain(|_, v| !v.is_empty());
+    }
+
+    pub(super) fn forget_all_undo() {
+        let mut all = Self::load_all();
+        if !all.values().any(|v| v.undo.is_some()) { return; }
+        for v in all.values_mut() { v.undo = None; }
+        all.retain(|_, v| !v.is_empty());
+        if let Ok(json) = serde_json::to_string(&all) { let _ = config_file::write_atomic(&Self::store_path(), json.as_bytes()); }
+    }
+}
+
+/// A large paste is cleaned on a task, then spliced in a chunk per frame so the window keeps drawing.
+pub(super) enum PasteStage {
+    Cleaning(crate::tasks::Task, std::sync::mpsc::Receiver<(String, usize)>),
+    Splicing { text: String, done: usize, cleaned: Option<usize> },
+}
+
+pub(super) struct PendingPaste {
+    pub stage: PasteStage,
+    pub range: (usize, usize),
+    pub version: u64,
+}
+
+impl PendingPaste {
+    pub(super) fn label(&self) -> String {
+        match &self.stage {
+            PasteStage::Cleaning(..) => "Cleaning paste...".to_string(),
+            PasteStage::Splicing { text, done, .. } => format!("Pasting... {}%", done * 100 / text.len().max(1)),
+        }
+    }
+}
+
+/// Closing the editor mid-paste stops the cleanup task instead of letting it finish for nobody.
+impl Drop for PendingPaste {
+    fn drop(&mut self) { if let PasteStage::Cleaning(task, _) = &self.stage { task.cancel(); } }
+}
+
+pub(super) enum LargeFile {
+    Prompt { size: u64 },
+    Loading { rx: std::sync::mpsc::Receiver<std::io::Result<String>>, read: Arc<AtomicU64>, total: u64, partial: bool },
+}
+
+pub(super) fn read_text(path: &Path, limit: Option<u64>, read: &AtomicU64) -> std::io::Result<String> {
+    let mut reader = BufReader::with_capacity(READ_CHUNK, File::open(path)?);
+    let (mut bytes, mut buf) = (Vec::new(), vec![0u8; READ_CHUNK]);
+    loop {
+        let want = limit.map_or(READ_CHUNK, |l| l.saturating_sub(bytes.len() as u64).min(READ_CHUNK as u64) as usize);
+        if want == 0 { break; }
+        let n = match reader.read(&mut buf[..want]) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        bytes.extend_from_slice(&buf[..n]);
+        read.store(bytes.len() as u64, Ordering::Relaxed);
+    }
+    let text = match String::from_utf8(bytes) {
+        Ok(s) => s,
+        Err(e) if limit.is_some() && e.utf8_error().error_len().is_none() => {
+            let valid = e.utf8_error().valid_up_to();
+            let mut b = e.into_bytes(); b.truncate(valid);
+            String::from_utf8(b).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?
+        }
+        Err(e) => return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, e)),
+    };
+    Ok(text.replace("\r\n", "\n"))
+}
+
+pub(super) fn looks_like_markdown(text: &str) -> bool {
+    let marker = |line: &str| {
+        let line = line.trim_start();
+        let hashes = line.len() - line.trim_start_matches('#').len();
+        let ordered = line.find(". ").is_some_and(|i| i > 0 && line[..i].bytes().all(|b| b.is_ascii_digit()));
+        (1..=6).contains(&hashes) && line[hashes..].starts_with(' ')
+            || ["- ", "* ", "+ ", "> ", "```"].iter().any(|m| line.starts_with(m)) || ordered
+    };
+    text.lines().take(MARKDOWN_SNIFF_LINES).filter(|l| marker(l)).count() >= MARKDOWN_SNIFF_MARKERS
+}
+
+pub(super) fn megabytes(bytes: u64) -> String { format!("{:.1} MB", bytes as f64 / (1u64 << 20) as f64) }
+
+#[derive(Default)]
+pub(super) struct WordIndex { pub counts: std::collections::HashMap<String, usize>, pub snapshot: String, pub version: u64 }
+
+pub(super) struct Completion { pub start: usize, pub cursor: usize, pub items: Vec<String>, pub selected: usize }
+
 pub(super) struct LineHeightCache {
     pub version: u64,
     pub font_size: f32,
@@ -17,7 +264,34 @@ pub(super) struct LineHeightCache {
     pub heights: Vec<Vec<f32>>,
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextEditorSettings {
+    pub large_file_mb: u32,
+    pub word_completion: bool,
+    pub highlight_current_line: bool,
+    pub typewriter_scrolling: bool,
+    pub persistent_undo: bool,
+    pub color_swatches: bool,
+    pub skip_comment_colors: bool,
+    pub paste_cleanup: PasteCleanup,
+    pub keep_backup: bool,
+}
+
+impl Default for TextEditorSettings {
+    fn default() -> Self {
+        Self {
+            large_file_mb: 200, word_completion: true, highlight_current_line: true, typewriter_scrolling: false, persistent_undo: false,
+            color_swatches: true, skip_comment_colors: false, paste_cleanup: PasteCleanup::default(), keep_backup: false,
+        }
+    }
+}
+
+impl TextEditorSettings {
+    fn large_file_limit(&self) -> u64 { (self.large_file_mb.max(1) as u64) << 20 }
+}
+
 pub struct TextEditor {
+    pub(super) settings: TextEditorSettings,
     pub(super) file_path: Option<PathBuf>,
     pub(super) content: String,
     pub(super) dirty: bool,
@@ -26,10 +300,15 @@ pub struct TextEditor {
     pub(super) view_mode: ViewMode,
     pub(super) last_cursor_range: Option<egui::text::CCursorRange>,
     pub(super) pending_cursor_pos: Option<usize>,
+    pub(super) pending_selection: Option<egui::text::CCursorRange>,
+    pub(super) editor_id: Option<egui::Id>,
+    pub(super) pending_paste: Option<PendingPaste>,
     pub(super) content_version: u64,
     pub(super) show_word_count_modal: bool,
     pub(super) show_word_count_in_info: bool,
-    pub(super) modal_word_count: usize,
+    pub(super) modal_word_count: WordCounts,
+    pub(super) word_count_options: WordCountOptions,
+    pub(super) word_count_cache: WordCountCache,
     pub(super) modal_char_count: usize,
     pub(super) modal_char_no_spaces: usize,
     pub(super) word_count_display_version: u64,
@@ -40,11 +319,35 @@ pub struct TextEditor {
     pub(super) path_replace_tx: Option<std::sync::mpsc::SyncSender<(PathBuf, PathBuf)>>,
     pub(super) table_picker_hover: (usize, usize),
     pub(super) scroll_offset: f32,
+    pub(super) pending_error: Option<EditorError>,
+    pub(super) overwrite_confirm: Option<PathBuf>,
+    pub(super) saved_hash: u64,
+    pub(super) pending_notice: Option<String>,
+    pub(super) bookmarks: Vec<usize>,
+    pub(super) show_bookmarks_panel: bool,
+    pub(super) pending_scroll_line: Option<usize>,
+    pub(super) large_file: Option<LargeFile>,
+    pub(super) preview_of: Option<u64>,
+    pub(super) close_requested: bool,
+    pub(super) word_index: WordIndex,
+    pub(super) completion: Option<Completion>,
+    pub(super) typewriter_cursor: Option<egui::text::CCursorRange>,
+    /// Last frame's layout with the content version it shows and the visible height, for moving by visual rows.
+    pub(super) row_layout: Option<(u64, std::sync::Arc<egui::Galley>, f32)>,
+    pub(super) restored_undo: Option<SavedUndo>,
+    pub(super) history: UndoHistory<TextSnapshot>,
+    pub(super) undo_base: Option<UndoState>,
+    pub(super) undo_version: u64,
+    pub(super) undo_persist_pending: bool,
+    pub(super) suggest_markdown: bool,
+    pub(super) color_cache: ColorCache,
+    pub(super) color_edit: Option<ColorEdit>,
 }
 
 impl TextEditor {
     pub fn new_empty() -> Self {
         Self {
+            settings: TextEditorSettings::default(),
             file_path: None,
             content: String::new(),
             dirty: false,
@@ -53,10 +356,15 @@ impl TextEditor {
             view_mode: ViewMode::Plain,
             last_cursor_range: None,
             pending_cursor_pos: None,
+            pending_selection: None,
+            editor_id: None,
+            pending_paste: None,
             content_version: 0,
             show_word_count_modal: false,
             show_word_count_in_info: false,
-            modal_word_count: 0,
+            modal_word_count: WordCounts::default(),
+            word_count_options: WordCountOptions::default(),
+            word_count_cache: WordCountCache::default(),
             modal_char_count: 0,
             modal_char_no_spaces: 0,
             word_count_display_version: u64::MAX,
@@ -67,43 +375,120 @@ impl TextEditor {
             path_replace_tx: None,
             table_picker_hover: (0, 0),
             scroll_offset: 0.0,
+            pending_error: None,
+            overwrite_confirm: None,
+            saved_hash: content_hash(""),
+            pending_notice: None,
+            bookmarks: Vec::new(),
+            show_bookmarks_panel: false,
+            pending_scroll_line: None,
+            large_file: None,
+            preview_of: None,
+            close_requested: false,
+            word_index: WordIndex { version: u64::MAX, ..Default::default() },
+            completion: None,
+            typewriter_cursor: None,
+            row_layout: None,
+            restored_undo: None,
+            history: Self::new_history(),
+            undo_base: None,
+            undo_version: 0,
+            undo_persist_pending: false,
+            suggest_markdown: false,
+            color_cache: ColorCache::default(),
+            color_edit: None,
         }
     }
 
-    pub fn load(path: PathBuf) -> Self {
-        let content: String = File::open(&path).ok()
-            .map(BufReader::new)
-            .and_then(|r: BufReader<File>| Rope::from_reader(r).ok())
-            .map(|rope: Rope| rope.to_string().replace("\r\n", "\n"))
-            .unwrap_or_default();
-
+    pub fn load(path: PathBuf, settings: TextEditorSettings) -> EditorResult<Self> {
         let view_mode: ViewMode = Self::detect_view_mode(&path);
-        Self {
-            file_path: Some(path),
-            content,
-            dirty: false,
-            font_size: 14.0,
-            font_family: egui::FontFamily::Name("Ubuntu".into()),
-            view_mode,
-            last_cursor_range: None,
-            pending_cursor_pos: None,
-            content_version: 0,
-            show_word_count_modal: false,
-            show_word_count_in_info: false,
-            modal_word_count: 0,
-            modal_char_count: 0,
-            modal_char_no_spaces: 0,
-            word_count_display_version: u64::MAX,
-            line_height_cache: None,
-            rename_modal_open: false,
-            rename_buffer: String::new(),
-            rename_ext: None,
-            path_replace_tx: None,
-            table_picker_hover: (0, 0),
-            scroll_offset: 0.0,
+        let size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        if size > settings.large_file_limit() {
+            return Ok(Self { file_path: Some(path), view_mode: ViewMode::Plain, large_file: Some(LargeFile::Prompt { size }), settings, ..Self::new_empty() });
+        }
+        let content: String = read_text(&path, None, &AtomicU64::new(0)).map_err(|e| EditorError::read(&path, e))?;
+        let mut editor = Self { file_path: Some(path), content, view_mode, settings, ..Self::new_empty() };
+        editor.restore_bookmarks();
+        Ok(editor.with_saved_hash())
+    }
+
+    pub fn from_template(text: String, markdown: bool) -> Self {
+        let mut editor = Self { content: text, view_mode: if markdown { ViewMode::Markdown } else { ViewMode::Plain }, ..Self::new_empty() };
+        editor.dirty = true;
+        editor.history.forget_save_point();
+        editor
+    }
+
+    pub(super) fn new_history() -> UndoHistory<TextSnapshot> {
+        UndoHistory::new(UNDO_DEPTH).with_coalesce_window(TYPING_COALESCE)
+            .with_byte_budget(UNDO_BYTES_IN_MEMORY, |freed| log::info!("Text undo history over budget, dropped {} KB of old steps", freed >> 10))
+    }
+
+    /// Drops the undo history kept in every file's saved state, for when persistent undo gets turned off.
+    pub fn forget_saved_undo() { FileViewState::forget_all_undo(); }
+
+    fn restore_bookmarks(&mut self) {
+        let Some(path) = &self.file_path else { return };
+        let line_count = self.content.split('\n').count();
+        let state = FileViewState::load(path);
+        self.restored_undo = state.undo.filter(|u| self.settings.persistent_undo && u.hash == content_hash(&self.content));
+        if let Some(mode) = state.view_mode { self.view_mode = mode; }
+        self.suggest_markdown = state.view_mode.is_none() && self.view_mode == ViewMode::Plain && looks_like_markdown(&self.content);
+        self.bookmarks = state.bookmarks.into_iter().filter(|l| *l < line_count).collect();
+        self.bookmarks.sort_unstable(); self.bookmarks.dedup();
+    }
+
+    pub(super) fn start_large_load(&mut self, ctx: &egui::Context, partial: bool) {
+        let Some(path) = self.file_path.clone() else { return };
+        let total = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        let total = if partial { total.min(PREVIEW_BYTES) } else { total };
+        let (tx, rx) = std::sync::mpsc::sync_channel(1);
+        let read = Arc::new(AtomicU64::new(0));
+        let (read2, ctx2) = (read.clone(), ctx.clone());
+        std::thread::spawn(move || {
+            let _ = tx.send(read_text(&path, partial.then_some(PREVIEW_BYTES), &read2));
+            ctx2.request_repaint();
+        });
+        self.large_file = Some(LargeFile::Loading { rx, read, total, partial });
+    }
+
+    pub(super) fn poll_large_load(&mut self) {
+        let Some(LargeFile::Loading { rx, partial, .. }) = &self.large_file else { return };
+        let partial = *partial;
+        let Ok(result) = rx.try_recv() else { return };
+        self.large_file = None;
+        match result {
+            Ok(content) => {
+                self.preview_of = partial.then(|| self.file_path.as_ref().and_then(|p| std::fs::metadata(p).ok()).map_or(0, |m| m.len()));
+                if !partial { self.view_mode = self.file_path.as_ref().map_or(ViewMode::Plain, Self::detect_view_mode); }
+                self.content = content;
+                self.saved_hash = content_hash(&self.content);
+                self.content_version = self.content_version.wrapping_add(1);
+                self.line_height_cache = None;
+                self.history.clear();
+                self.history.mark_saved();
+                self.undo_base = None;
+                self.dirty = false;
+                if partial { self.bookmarks.clear(); } else { self.restore_bookmarks(); }
+            }
+            Err(e) => {
+                let path = self.file_path.as_ref().map(|p| p.display().to_string()).unwrap_or_default();
+                self.pending_error = Some(EditorError::Other(format!("Could not read {}: {}", path, e)));
+                if self.preview_of.is_none() { self.close_requested = true; }
+            }
         }
     }
 
+    fn ensure_writable(&self) -> EditorResult {
+        if let Some(total) = self.preview_of { return Err(EditorError::Other(format!("This is a read-only preview of the first {} of a {} file. Load the entire file before saving.", megabytes(PREVIEW_BYTES), megabytes(total)))); }
+        if self.large_file.is_some() { return Err(EditorError::Other("The file has not finished loading yet".to_string())); }
+        Ok(())
+    }
+
+    fn with_saved_hash(mut self) -> Self { self.saved_hash = content_hash(&self.content); self }
+
+    pub(super) fn refresh_dirty(&mut self) { self.dirty = content_hash(&self.content) != self.saved_hash; }
+
     pub(super) fn detect_view_mode(path: &PathBuf) -> ViewMode {
         path.extension()
             .and_then(|e: &std::ffi::OsStr| e.to_str())
@@ -114,10 +499,55 @@ impl TextEditor {
             .unwrap_or(ViewMode::Plain)
     }
 
+    pub(super) fn set_view_mode(&mut self, mode: ViewMode) {
+        self.suggest_markdown = false;
+        if self.view_mode == mode { return; }
+        self.view_mode = mode;
+        self.line_height_cache = None;
+        if let Some(path) = &self.file_path {
+            let mut view = FileViewState::load(path);
+            view.view_mode = (mode != Self::detect_view_mode(path)).then_some(mode);
+            view.save(path);
+        }
+    }
+
+    pub(super) fn dismiss_markdown_suggestion(&mut self) {
+        self.suggest_markdown = false;
+        if let Some(path) = &self.file_path {
+            let mut view = FileViewState::load(path);
+            view.view_mode = Some(ViewMode::Plain);
+            view.save(path);
+        }
+    }
+
     pub fn is_dirty(&self) -> bool { self.dirty }
     pub fn set_default_font(&mut self, family: egui::FontFamily, size: f32) { self.font_family = family; self.font_size = size; }
     pub fn set_path_replace_tx(&mut self, tx: std::sync::mpsc::SyncSender<(std::path::PathBuf, std::path::PathBuf)>) { self.path_replace_tx = Some(tx); }
 
+    fn write_to_disk(&mut self) -> EditorResult {
+        let Some(path) = self.file_path.as_ref() else { return Err(EditorError::Cancelled) };
+        let f: File = File::create(path).map_err(|e: std::io::Error| EditorError::io(path, e))?;
+        let mut writer: BufWriter<File> = BufWriter::new(f);
+        let rope: Rope = Rope::from_str(&self.content);
+        rope.write_to(&mut writer).map_err(|e: std::io::Error| EditorError::io(path, e))?;
+        self.dirty = false;
+        self.saved_hash = content_hash(&self.content);
+        self.history.mark_saved();
+        self.save_view_state();
+        self.undo_persist_pending = self.settings.persistent_undo;
+        Ok(())
+    }
+
+    pub(super) fn confirm_overwrite(&mut self) {
+        let Some(path) = self.overwrite_confirm.take() else { return };
+        if let Err(e) = release_overwrite_target(&path, self.settings.keep_backup) {
+            self.pending_error = Some(EditorError::Other(format!("Could not move {} to the trash: {}", path.display(), e)));
+            return;
+        }
+        let previous = self.file_path.replace(path);
+        if let Err(e) = self.write_to_disk() { self.file_path = previous; self.pending_error = Some(e); }
+    }
+
     pub(super) fn get_file_name(&self) -> String {
         self.file_path.as_ref()
             .and_then(|p: &PathBuf| p.file_name())
@@ -127,6 +557,50 @@ impl TextEditor {
     }
 }
 
+pub(super) fn content_hash(content: &str) -> u64 {
+    use std::hash::{Hash, Hasher};
+    let mut h = std::collections::hash_map::DefaultHasher::new();
+    content.hash(&mut h);
+    h.finish()
+}
+
+/// What the text editor's keys do; `TE_SHORTCUTS` binds them and the handlers that run before and after the text
+/// field each take their share.
+#[derive(Clone, Copy, PartialEq)]
+pub(super) enum TextKey {
+    Save, SaveAs, Undo, Redo, Bold, Italic, Underline, Strikethrough, Code, Highlight, Heading, Blockquote, Checklist,
+    DuplicateLines, MoveLinesUp, MoveLinesDown, DeleteLines, SelectLines, RowEdge, Page, ToggleBookmark, NextBookmark, PrevBookmark,
+}
+
+pub(super) const TE_SHORTCUTS: &[Shortcut<TextKey>] = &[
+    shortcut(&[ctrl(egui::Key::S)], TextKey::Save, "Save"),
+    shortcut(&[ctrl_shift(egui::Key::A)], TextKey::SaveAs, "Save As"),
+    shortcut(&[ctrl(egui::Key::Z)], TextKey::Undo, "Undo"),
+    shortcut(&[ctrl(egui::Key::Y), ctrl_shift(egui::Key::Z)], TextKey::Redo, "Redo"),
+    shortcut(&[ctrl(egui::Key::B)], TextKey::Bold, "Bold"),
+    shortcut(&[ctrl(egui::Key::I)], TextKey::Italic, "Italic"),
+    shortcut(&[ctrl(egui::Key::U)], TextKey::Underline, "Underline"),
+    shortcut(&[ctrl_shift(egui::Key::S)], TextKey::Strikethrough, "Strikethrough"),
+    shortcut(&[ctrl(egui::Key::E)], TextKey::Code, "Inline code"),
+    shortcut(&[ctrl_shift(egui::Key::H)], TextKey::Highlight, "Highlight"),
+    shortcut(&[ctrl(egui::Key::Num1), ctrl(egui::Key::Num2), ctrl(egui::Key::Num3), ctrl(egui::Key::Num4)], TextKey::Heading, "Heading 1-4"),
+    shortcut(&[ctrl_shift(egui::Key::Q)], TextKey::Blockquote, "Blockquote"),
+    shortcut(&[ctrl_shift(egui::Key::L)], TextKey::Checklist, "Checklist item"),
+    shortcut(&[ctrl_shift(egui::Key::D)], TextKey::DuplicateLines, "Duplicate line or selection"),
+    shortcut(&[alt(egui::Key::ArrowUp)], TextKey::MoveLinesUp, "Move line up"),
+    shortcut(&[alt(egui::Key::ArrowDown)], TextKey::MoveLinesDown, "Move line down"),
+    shortcut(&[ctrl_shift(egui::Key::K)], TextKey::DeleteLines, "Delete line"),
+    shortcut(&[ctrl(egui::Key::L)], TextKey::SelectLines, "Select line (repeat to extend)"),
+    shortcut(&[key(egui::Key::Home), key(egui::Key::End)], TextKey::RowEdge, "Start / end of the wrapped row, again for the whole line"),
+    shortcut(&[key(egui::Key::PageUp), key(egui::Key::PageDown)], TextKey::Page, "Move by a screen of rows"),
+    gesture("Ctrl+Shift+V", "Paste as clean text"),
+    shortcut(&[ctrl(egui::Key::F2)], TextKey::ToggleBookmark, "Toggle bookmark"),
+    shortcut(&[key(egui::Key::F2)], TextKey::NextBookmark, "Next bookmark"),
+    shortcut(&[shift(egui::Key::F2)], TextKey::PrevBookmark, "Previous bookmark"),
+];
+
+pub fn key_bindings() -> Vec<KeyBinding> { crate::modules::key_bindings(TE_SHORTCUTS) }
+
 impl EditorModule for TextEditor {
     fn as_any(&self) -> &dyn std::any::Any { self }
 
@@ -135,58 +609,301 @@ impl EditorModule for TextEditor {
         if self.dirty { format!("{} *", name) } else { name }
     }
 
-    fn save(&mut self) -> Result<(), String> {
-        if self.file_path.is_none() {
-            return self.save_as();
+    fn save(&mut self) -> EditorResult<SaveState> {
+        self.ensure_writable()?;
+        let Some(path) = self.file_path.as_ref() else { return self.save_as() };
+        if content_hash(&self.content) == self.saved_hash && path.exists() {
+            self.dirty = false;
+            self.pending_notice = Some("No changes to save".to_string());
+            return Ok(SaveState::Saved);
         }
-        let path: &PathBuf = self.file_path.as_ref().unwrap();
-        let f: File = File::create(path).map_err(|e: std::io::Error| e.to_string())?;
-        let mut writer: BufWriter<File> = BufWriter::new(f);
-        let rope: Rope = Rope::from_str(&self.content);
-        rope.write_to(&mut writer).map_err(|e: std::io::Error| e.to_string())?;
-        self.dirty = false;
-        Ok(())
+        self.write_to_disk().map(|_| SaveState::Saved)
     }
 
-    fn save_as(&mut self) -> Result<(), String> {
-        if let Some(path) = rfd::FileDialog::new()
+    fn save_as(&mut self) -> EditorResult<SaveState> {
+        self.ensure_writable()?;
+        let Some(path) = rfd::FileDialog::new()
             .add_filter("Text", &["txt", "md"])
-            .save_file()
-        {
-            self.file_path = Some(path);
-            self.save()
-        } else {
-            Err("Cancelled".to_string())
+            .save_file() else { return Err(EditorError::Cancelled) };
+        if needs_overwrite_confirm(&path, self.file_path.as_deref()) {
+            if open_in_another_window(&path) { return Err(EditorError::open_elsewhere(&path)); }
+            self.overwrite_confirm = Some(path);
+            return Ok(SaveState::AwaitingConfirm);
         }
+        self.file_path = Some(path);
+        self.write_to_disk().map(|_| SaveState::Saved)
+    }
+
+    fn save_pending(&self) -> bool { self.overwrite_confirm.is_some() }
+
+    fn take_error(&mut self) -> Option<EditorError> { self.pending_error.take() }
+    fn take_notice(&mut self) -> Option<String> { self.pending_notice.take() }
+    fn key_bindings(&self) -> Vec<KeyBinding> { key_bindings() }
+
+    fn apply_settings(&mut self, settings: &EditorSettings) { self.settings = settings.text.clone(); }
+    fn file_path(&self) -> Option<&Path> { self.file_path.as_deref() }
+    fn take_close_request(&mut self) -> bool { std::mem::take(&mut self.close_requested) }
+    fn template_content(&self) -> Option<crate::templates::TemplateContent> {
+        if self.preview_of.is_some() || self.large_file.is_some() { return None; }
+        Some(crate::templates::TemplateContent::Text { text: self.content.clone(), markdown: self.view_mode == ViewMode::Markdown })
+    }
+    fn view_state(&self) -> Option<ViewState> { Some(ViewState { scroll: self.scroll_offset, cursor: self.last_cursor_range.map(|r| r.primary.index), ..Default::default() }) }
+    fn restore_view_state(&mut self, state: ViewState) {
+        self.scroll_offset = state.scroll;
+        if let Some(c) = state.cursor { self.pending_cursor_pos = Some(c.min(self.content.chars().count())); }
     }
 
     fn get_menu_contributions(&self) -> MenuContribution {
         MenuContribution {
             file_items: vec![
-                (MenuItem { label: "Word Count".to_string(), shortcut: None, enabled: true }, MenuAction::Custom("WordCount".to_string())),
+                MenuEntry::item("Word Count", TextMenuAction::WordCount),
             ],
             edit_items: vec![
-                (MenuItem { label: "Undo".to_string(), shortcut: Some("Ctrl+Z".to_string()), enabled: false }, MenuAction::Undo),
-                (MenuItem { label: "Redo".to_string(), shortcut: Some("Ctrl+Y".to_string()), enabled: false }, MenuAction::Redo),
+                MenuEntry::item(self.history.undo_label().map_or("Undo".to_string(), |l| format!("Undo {}", l)), MenuAction::Undo).shortcut(&menu_keys(TE_SHORTCUTS, TextKey::Undo)).enabled(self.history.can_undo()),
+                MenuEntry::item(self.history.redo_label().map_or("Redo".to_string(), |l| format!("Redo {}", l)), MenuAction::Redo).shortcut(&menu_keys(TE_SHORTCUTS, TextKey::Redo)).enabled(self.history.can_redo()),
+                MenuEntry::Separator,
+                MenuEntry::item("Toggle Bookmark", TextMenuAction::ToggleBookmark).shortcut(&menu_keys(TE_SHORTCUTS, TextKey::ToggleBookmark)),
+                MenuEntry::item("Next Bookmark", TextMenuAction::NextBookmark).shortcut(&menu_keys(TE_SHORTCUTS, TextKey::NextBookmark)).enabled(!self.bookmarks.is_empty()),
+                MenuEntry::item("Previous Bookmark", TextMenuAction::PrevBookmark).shortcut(&menu_keys(TE_SHORTCUTS, TextKey::PrevBookmark)).enabled(!self.bookmarks.is_empty()),
+                MenuEntry::item("Clear Bookmarks", TextMenuAction::ClearBookmarks).enabled(!self.bookmarks.is_empty()),
+            ],
+            view_items: vec![
+                MenuEntry::item("Markdown View", TextMenuAction::ViewMode(ViewMode::Markdown)).enabled(self.large_file.is_none()).checked(self.view_mode == ViewMode::Markdown),
+                MenuEntry::item("Plain Text View", TextMenuAction::ViewMode(ViewMode::Plain)).enabled(self.large_file.is_none()).checked(self.view_mode == ViewMode::Plain),
+                MenuEntry::Separator,
+                MenuEntry::item("Bookmarks Panel", TextMenuAction::BookmarksPanel).checked(self.show_bookmarks_panel),
+                MenuEntry::item("Word Count in File Info", TextMenuAction::WordCountInInfo).checked(self.show_word_count_in_info),
             ],
-            view_items: Vec::new(), image_items: Vec::new(), filter_items: Vec::new(), layer_items: Vec::new(), insert_items: Vec::new(), format_items: Vec::new()
+            image_items: Vec::new(), filter_items: Vec::new(), layer_items: Vec::new(), insert_items: Vec::new(), format_items: Vec::new()
         }
     }
 
     fn handle_menu_action(&mut self, action: MenuAction) -> bool {
-        if let MenuAction::Custom(ref v) = action {
-            if v == "WordCount" {
+        let action = match action {
+            MenuAction::Undo => { self.undo(); return true; }
+            MenuAction::Redo => { self.redo(); return true; }
+            MenuAction::Text(action) => action,
+            _ => return false,
+        };
+        match action {
+            TextMenuAction::WordCount => {
                 self.modal_word_count = self.count_words();
                 self.modal_char_count = self.content.chars().count();
                 self.modal_char_no_spaces = self.content.chars().filter(|c| !c.is_whitespace()).count();
                 self.show_word_count_modal = true;
-                return true;
             }
+            TextMenuAction::ToggleBookmark => self.toggle_bookmark(),
+            TextMenuAction::NextBookmark => self.jump_bookmark(true),
+            TextMenuAction::PrevBookmark => self.jump_bookmark(false),
+            TextMenuAction::ClearBookmarks => self.clear_bookmarks(),
+            TextMenuAction::BookmarksPanel => self.show_bookmarks_panel = !self.show_bookmarks_panel,
+            TextMenuAction::ViewMode(mode) => self.set_view_mode(mode),
+            TextMenuAction::WordCountInInfo => self.show_word_count_in_info = !self.show_word_count_in_info,
         }
-        false
+        true
     }
 
     fn ui(&mut self, ui: &mut egui::Ui, ctx: &egui::Context, show_toolbar: bool, show_file_info: bool) {
         self.render_editor_ui(ui, ctx, show_toolbar, show_file_info);
+        if let Some(confirmed) = self.overwrite_confirm.as_deref().and_then(|p| overwrite_confirm_window(ctx, p, self.settings.keep_backup)) {
+            if confirmed { self.confirm_overwrite(); } else { self.overwrite_confirm = None; }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(text: &str, i: usize) -> UndoState { (egui::text::CCursorRange::one(egui::text::CCursor::new(i)), text.to_string()) }
+
+    #[test]
+    fn saved_undo_history_survives_a_reopen_and_is_dropped_when_the_file_changed() {
+        let mut history = TextEditor::new_history();
+        for (text, i) in [("Grüße", 5), ("Grüße, Welt", 11), ("Hallo, Welt", 5)] { history.push(TextSnapshot { state: at(text, i), typing: false }, "Edit"); }
+        let saved = at("Hallo, Welt!", 12);
+        let history = SavedUndo::capture(&history, &saved).unwrap();
+        assert_eq!(history.steps.iter().map(|s| s.insert.as_str()).collect::<Vec<_>>(), ["", "Grüße", ""]);
+        let json = serde_json::to_string(&FileViewState { undo: Some(history), ..Default::default() }).unwrap();
+        let reopened: FileViewState = serde_json::from_str(&json).unwrap();
+        let history = reopened.undo.unwrap();
+        assert!(history.rebuild(&at("Hallo, Welt?", 12)).is_none());
+        let mut history = history.rebuild(&saved).unwrap();
+        assert!(history.is_saved());
+        let undone = history.undo(TextSnapshot { state: saved, typing: false }).unwrap().state;
+        assert_eq!(undone.1, "Hallo, Welt");
+        assert_eq!(undone.0.primary.index, 5);
+        assert_eq!(history.undo(TextSnapshot { state: undone, typing: false }).map(|s| s.state.1).as_deref(), Some("Grüße, Welt"));
+    }
+
+    #[test]
+    fn the_incremental_word_index_matches_a_full_rescan_after_random_edits() {
+        let pieces = ["alpha", "beta ", " gamma", "Grüße", "naïve-word", "\n", "  ", "🎉", "e\u{301}tude", "x", "don't", "delta\tepsilon"];
+        let mut seed = 0x9e37_79b9_7f4a_7c15u64;
+        let mut next = |n: usize| { seed ^= seed << 13; seed ^= seed >> 7; seed ^= seed << 17; (seed % n as u64) as usize };
+        let mut editor = TextEditor::new_empty();
+        for step in 0..400 {
+            let bounds: Vec<usize> = editor.content.char_indices().map(|(i, _)| i).chain([editor.content.len()]).collect();
+            let (a, b) = (bounds[next(bounds.len())], bounds[next(bounds.len())]);
+            let (start, end) = (a.min(b), if next(3) == 0 { a.max(b) } else { a.min(b) });
+            let insert = if next(4) == 0 { "" } else { pieces[next(pieces.len())] };
+            editor.content.replace_range(start..end, insert);
+            editor.content_version += 1;
+            editor.update_word_index();
+            let mut fresh = TextEditor::new_empty();
+            fresh.content = editor.content.clone();
+            fresh.content_version = 1;
+            fresh.update_word_index();
+            assert_eq!(editor.word_index.snapshot, editor.content, "snapshot drifted at step {step}");
+            assert_eq!(editor.word_index.counts, fresh.word_index.counts, "index drifted at step {step} on {:?}", editor.content);
+        }
+        assert!(!editor.word_index.counts.is_empty());
+    }
+
+    #[test]
+    fn backspace_delete_and_arrows_take_a_whole_grapheme_per_press() {
+        use egui::{Key, Modifiers, text::{CCursor, CCursorRange}};
+        let mut editor = TextEditor::new_empty();
+        editor.content = "ae\u{301}👩\u{200D}💻b".to_string();
+        let end = editor.content.chars().count();
+        let (r, changed) = editor.grapheme_key(CCursorRange::one(CCursor::new(end - 1)), Key::Backspace, Modifiers::NONE);
+        assert!(changed);
+        assert_eq!((editor.content.as_str(), r.primary.index), ("ae\u{301}b", 3));
+        let (r, _) = editor.grapheme_key(r, Key::Backspace, Modifiers::NONE);
+        assert_eq!((editor.content.as_str(), r.primary.index), ("ab", 1));
+        editor.content = "a👩\u{200D}💻e\u{301}".to_string();
+        let (r, _) = editor.grapheme_key(CCursorRange::one(CCursor::new(1)), Key::Delete, Modifiers::NONE);
+        assert_eq!((editor.content.as_str(), r.primary.index), ("ae\u{301}", 1));
+        let (r, _) = editor.grapheme_key(r, Key::ArrowRight, Modifiers::SHIFT);
+        assert_eq!((r.secondary.index, r.primary.index), (1, 3), "shift+right selects the accented e whole");
+        let (r, changed) = editor.grapheme_key(CCursorRange::one(CCursor::new(3)), Key::ArrowLeft, Modifiers::NONE);
+        assert_eq!((r.primary.index, r.secondary.index, changed), (1, 1, false));
+        editor.content = "hello wörld".to_string();
+        let (r, _) = editor.grapheme_key(CCursorRange::one(CCursor::new(11)), Key::Backspace, Modifiers::CTRL);
+        assert_eq!((editor.content.as_str(), r.primary.index), ("hello ", 6));
+    }
+
+    #[test]
+    fn a_large_clean_paste_is_cleaned_on_a_task_and_spliced_a_chunk_per_frame() {
+        let mut editor = TextEditor::new_empty();
+        editor.content = "[]".to_string();
+        let pasted = "“é”\r\n".repeat(LARGE_PASTE_BYTES / 8 + 1);
+        editor.start_large_paste(pasted.clone(), (1, 1), true);
+        let mut frames = 0;
+        while editor.pending_paste.is_some() {
+            if !editor.poll_pending_paste() { std::thread::sleep(std::time::Duration::from_millis(5)); }
+            frames += 1;
+            assert!(frames < 2000, "paste never finished");
+        }
+        let (expected, cleaned) = PasteCleanup::default().clean(&pasted);
+        assert_eq!(editor.content, format!("[{expected}]"));
+        assert_eq!(editor.pending_cursor_pos, Some(1 + expected.chars().count()));
+        assert!(expected.len() > 2 * PASTE_CHUNK_BYTES);
+        assert!(editor.pending_notice.as_deref().is_some_and(|n| n.contains(&cleaned.to_string())));
+    }
+
+    #[test]
+    fn a_save_waiting_on_replace_stays_pending_until_confirmed() {
+        let dir = std::env::temp_dir().join(format!("ue_replace_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let target = dir.join("old.txt");
+        std::fs::write(&target, "old").unwrap();
+        let mut editor = TextEditor::new_empty();
+        editor.content = "new".to_string();
+        editor.overwrite_confirm = Some(target.clone());
+        assert!(editor.save_pending());
+        editor.confirm_overwrite();
+        assert!(!editor.save_pending() && editor.pending_error.is_none());
+        assert_eq!((std::fs::read_to_string(&target).unwrap().as_str(), editor.file_path.as_deref()), ("new", Some(target.as_path())));
+        assert!(FileViewState::store_path().starts_with(std::env::temp_dir()), "the view state the save writes stays out of the user's config");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn switching_view_mode_keeps_content_clean_and_sniffs_markdown() {
+        assert!(looks_like_markdown("# Notes\n\nSome text\n- one\n- two\n"));
+        assert!(looks_like_markdown("1. first\n2. second\n## Done\n"));
+        assert!(!looks_like_markdown("#hashtag\n-5 degrees\nplain * notes\n"));
+        let mut editor = TextEditor::from_template("# Notes\n- a\n- b\n".to_string(), false);
+        editor.dirty = false;
+        editor.line_height_cache = Some(LineHeightCache { version: 0, font_size: 14.0, font_family: egui::FontFamily::Proportional, wrap_width: 100.0, is_dark: false, heights: Vec::new() });
+        editor.set_view_mode(ViewMode::Markdown);
+        assert_eq!((editor.view_mode, editor.dirty, editor.content.as_str()), (ViewMode::Markdown, false, "# Notes\n- a\n- b\n"));
+        assert!(editor.line_height_cache.is_none());
+    }
+
+    #[test]
+    fn home_end_and_paging_follow_the_visual_rows_of_a_wrapped_line() {
+        use super::super::te_tools::{row_edge, move_rows};
+        use egui::text::CCursor;
+        let text = format!("{}\nshort", "word ".repeat(200));
+        let ctx = egui::Context::default();
+        let layout = |width: f32| {
+            let mut galley = None;
+            let _ = ctx.run(Default::default(), |ctx| {
+                galley = Some(ctx.fonts_mut(|f| f.layout(text.clone(), egui::FontId::monospace(14.0), egui::Color32::WHITE, width)));
+            });
+            galley.unwrap()
+        };
+        let row_start = |g: &egui::Galley, row: usize| g.rows[..row].iter().map(|r| r.char_count_including_newline()).sum::<usize>();
+        let row_of = |g: &egui::Galley, c: CCursor| g.layout_from_cursor(c).row;
+
+        let galley = layout(200.0);
+        assert!(galley.rows.len() > 20);
+        let mid = CCursor { index: row_start(&galley, 3) + 2, prefer_next_row: true };
+        let home = row_edge(&galley, mid, false);
+        assert_eq!((home.index, row_of(&galley, home)), (row_start(&galley, 3), 3));
+        assert_eq!(row_edge(&galley, home, false).index, 0);
+        let end = row_edge(&galley, mid, true);
+        assert_eq!((end.index, row_of(&galley, end)), (row_start(&galley, 4), 3));
+        assert_eq!(row_edge(&galley, end, true).index, 1000);
+
+        let x = |g: &egui::Galley, c: CCursor| g.pos_from_cursor(c).center().x;
+        let (down, h) = move_rows(&galley, mid, 1, None);
+        assert_eq!((row_of(&galley, down), down.index - row_start(&galley, 4)), (4, 2));
+        let (paged, h) = move_rows(&galley, down, 5, h);
+        assert_eq!(row_of(&galley, paged), 9);
+        assert!((x(&galley, paged) - x(&galley, mid)).abs() < 1.0 && h.is_some());
+        assert_eq!(move_rows(&galley, paged, -100, h), (CCursor::default(), None));
+        let last = galley.rows.len() - 1;
+        assert_eq!(row_of(&galley, move_rows(&galley, mid, 100, None).0), last);
+
+        let narrow = layout(120.0);
+        assert!(narrow.rows.len() > galley.rows.len());
+        let same = CCursor { index: mid.index, prefer_next_row: true };
+        let row = row_of(&narrow, same);
+        assert_ne!(row, 3);
+        assert_eq!(row_edge(&narrow, same, false).index, row_start(&narrow, row));
+    }
+
+    #[test]
+    fn bookmarks_toggle_jump_clear_and_follow_the_lines_through_edits() {
+        use egui::{TextBuffer, text::{CCursor, CCursorRange}};
+        use super::super::te_tools::BookmarkedText;
+        let mut editor = TextEditor::new_empty();
+        editor.content = "zero\none\ntwo\nthree\nfour\n".to_string();
+        let caret = |editor: &mut TextEditor, i: usize| editor.last_cursor_range = Some(CCursorRange::one(CCursor::new(i)));
+        for i in [5, 13, 19, 19] { caret(&mut editor, i); editor.toggle_bookmark(); }
+        assert_eq!(editor.bookmarks, [1, 3]);
+        editor.jump_bookmark(true);
+        assert_eq!((editor.pending_scroll_line, editor.pending_cursor_pos), (Some(1), Some(5)), "wraps past the last bookmark");
+        caret(&mut editor, 0);
+        editor.jump_bookmark(false);
+        assert_eq!(editor.pending_scroll_line, Some(3));
+
+        let mut typed = BookmarkedText { text: &mut editor.content, bookmarks: &mut editor.bookmarks };
+        typed.insert_text("new\n", 5);
+        assert_eq!(*typed.bookmarks, [2, 4], "a line typed above pushes them down");
+        typed.insert_text("x", 12);
+        typed.delete_char_range(14..18);
+        assert_eq!((typed.text.as_str(), typed.bookmarks.as_slice()), ("zero\nnew\nonex\nthree\nfour\n", &[2, 3][..]));
+        editor.splice(9..14, "");
+        assert_eq!(editor.bookmarks, [2], "deleting a bookmarked line drops its bookmark");
+        editor.splice(7..11, "");
+        assert_eq!((editor.content.as_str(), editor.bookmarks.as_slice()), ("zero\nneree\nfour\n", &[1][..]));
+        BookmarkedText { text: &mut editor.content, bookmarks: &mut editor.bookmarks }.replace_with("intro\nzero\nneree\nfour\n");
+        assert_eq!(editor.bookmarks, [2]);
+        editor.clear_bookmarks();
+        assert!(editor.bookmarks.is_empty());
     }
 }
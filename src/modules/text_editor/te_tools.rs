@@ -1,4 +1,101 @@
-use super::te_main::TextEditor;
+use eframe::egui;
+use unicode_segmentation::UnicodeSegmentation;
+use crate::modules::helpers::text_bounds::{prev_grapheme_boundary, next_grapheme_boundary, prev_word_boundary, next_word_boundary};
+use super::te_count::WordCounts;
+use super::te_main::{TextEditor, PasteStage, PendingPaste, PASTE_CHUNK_BYTES, FileViewState, SavedUndo, TextSnapshot, UndoState, WordIndex, Completion, LARGE_PASTE_BYTES, content_hash, TextKey, TE_SHORTCUTS};
+use crate::error::EditorError;
+use crate::modules::{EditorModule, pressed_shortcuts};
+use crate::modules::helpers::file_info::{FileInfoAction, rename_file, reveal_in_file_manager};
+
+const MIN_COMPLETION_CHARS: usize = 3;
+const MAX_COMPLETIONS: usize = 6;
+const PROXIMITY_WINDOW: usize = 4096;
+
+/// Moves bookmarked lines to follow `content[range]` being replaced by `inserted`. Lines wholly inside the range go,
+/// and a line the range cuts into stays with what is left of it.
+fn shift_bookmarks(bookmarks: &mut Vec<usize>, content: &[u8], range: std::ops::Range<usize>, inserted: &[u8]) {
+    if bookmarks.is_empty() { return; }
+    let newlines = |s: &[u8]| s.iter().filter(|b| **b == b'\n').count();
+    let line_start = |i: usize| i == 0 || content[i - 1] == b'\n';
+    let first = newlines(&content[..range.start]);
+    let last = first + newlines(&content[range.clone()]);
+    let added = newlines(inserted);
+    let (starts_line, ends_line, to_end) = (line_start(range.start), line_start(range.end), range.end == content.len());
+    bookmarks.retain_mut(|line| {
+        let l = *line;
+        if l < first || (l == first && !starts_line) { return true; }
+        if l > last || (l == last && ends_line) { *line = l - (last - first) + added; return true; }
+        if l < last || to_end { return false; }
+        *line = first + added;
+        true
+    });
+    bookmarks.sort_unstable(); bookmarks.dedup();
+}
+
+/// `shift_bookmarks` for the content being swapped wholesale, as undo does: the edit is what lies between the common ends.
+fn follow_replacement(bookmarks: &mut Vec<usize>, old: &str, new: &str) {
+    if bookmarks.is_empty() || old == new { return; }
+    let (old, new) = (old.as_bytes(), new.as_bytes());
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let max_suffix = old.len().min(new.len()) - prefix;
+    let suffix = old.iter().rev().zip(new.iter().rev()).take(max_suffix).take_while(|(a, b)| a == b).count();
+    shift_bookmarks(bookmarks, old, prefix..old.len() - suffix, &new[prefix..new.len() - suffix]);
+}
+
+/// The text field's view of the content, keeping bookmarks on their lines as keystrokes edit it.
+pub(super) struct BookmarkedText<'a> { pub text: &'a mut String, pub bookmarks: &'a mut Vec<usize> }
+
+impl egui::TextBuffer for BookmarkedText<'_> {
+    fn is_mutable(&self) -> bool { true }
+    fn as_str(&self) -> &str { self.text }
+
+    fn insert_text(&mut self, text: &str, char_index: usize) -> usize {
+        let at = self.byte_index_from_char_index(char_index);
+        shift_bookmarks(self.bookmarks, self.text.as_bytes(), at..at, text.as_bytes());
+        self.text.insert_text(text, char_index)
+    }
+
+    fn delete_char_range(&mut self, char_range: std::ops::Range<usize>) {
+        let range = self.byte_index_from_char_index(char_range.start)..self.byte_index_from_char_index(char_range.end);
+        shift_bookmarks(self.bookmarks, self.text.as_bytes(), range, b"");
+        self.text.delete_char_range(char_range);
+    }
+
+    fn replace_with(&mut self, text: &str) {
+        follow_replacement(self.bookmarks, self.text, text);
+        self.text.replace_with(text);
+    }
+
+    fn type_id(&self) -> std::any::TypeId { std::any::TypeId::of::<BookmarkedText<'static>>() }
+}
+
+fn count_words(counts: &mut std::collections::HashMap<String, usize>, text: &str, add: bool) {
+    for w in text.unicode_words().filter(|w| w.chars().count() >= MIN_COMPLETION_CHARS) {
+        if add { *counts.entry(w.to_string()).or_insert(0) += 1; }
+        else if let Some(c) = counts.get_mut(w) { *c -= 1; if *c == 0 { counts.remove(w); } }
+    }
+}
+
+/// Where Home (`end` false) or End sends the caret: the edge of its visual row, or the edge of its logical line
+/// when it already sits on the row's edge, so a second press reaches the start or end of a wrapped line.
+pub(super) fn row_edge(galley: &egui::Galley, cursor: egui::text::CCursor, end: bool) -> egui::text::CCursor {
+    let row = if end { galley.cursor_end_of_row(&cursor) } else { galley.cursor_begin_of_row(&cursor) };
+    if row.index != cursor.index { return row; }
+    if end { galley.cursor_end_of_paragraph(&cursor) } else { galley.cursor_begin_of_paragraph(&cursor) }
+}
+
+/// The caret `rows` visual rows down, or up when negative, keeping the screen x that Up/Down keep.
+pub(super) fn move_rows(galley: &egui::Galley, cursor: egui::text::CCursor, rows: isize, h_pos: Option<f32>) -> (egui::text::CCursor, Option<f32>) {
+    let (mut cursor, mut h_pos) = (cursor, h_pos);
+    for _ in 0..rows.unsigned_abs() {
+        let (next, h) = if rows < 0 { galley.cursor_up_one_row(&cursor, h_pos) } else { galley.cursor_down_one_row(&cursor, h_pos) };
+        if h.is_none() { return (next, None); }
+        (cursor, h_pos) = (next, h);
+    }
+    (cursor, h_pos)
+}
+
+fn whitespace_before(s: &str, end: usize) -> usize { s[..end].char_indices().rev().find(|(_, c)| c.is_whitespace()).map_or(0, |(i, c)| i + c.len_utf8()) }
 
 impl TextEditor {
     pub(super) fn insert_table(&mut self, rows: usize, cols: usize) {
@@ -12,7 +109,7 @@ impl TextEditor {
             .unwrap_or(self.content.len());
         let needs_newline: bool = byte_idx > 0 && !self.content[..byte_idx].ends_with('\n');
         let insert: String = if needs_newline { format!("\n{}", table) } else { table };
-        self.content.insert_str(byte_idx, &insert);
+        self.splice(byte_idx..byte_idx, &insert);
         self.dirty = true;
         self.content_version = self.content_version.wrapping_add(1);
     }
@@ -24,11 +121,338 @@ impl TextEditor {
             .unwrap_or(self.content.len())
     }
 
+    pub(super) fn sync_cursor_state(&mut self, ctx: &egui::Context, id: egui::Id) {
+        self.editor_id = Some(id);
+        let Some(mut state) = egui::TextEdit::load_state(ctx, id) else { return };
+        let pending = self.pending_selection.take().or(self.pending_cursor_pos.take().map(|p| egui::text::CCursorRange::one(egui::text::CCursor::new(p))));
+        if let Some(range) = pending {
+            state.cursor.set_char_range(Some(range));
+            state.clone().store(ctx, id);
+        }
+        if let Some(r) = state.cursor.char_range() { self.last_cursor_range = Some(r); }
+        state.clear_undoer();
+        state.clone().store(ctx, id);
+        if let Some(saved) = self.restored_undo.take() && let Some(history) = saved.rebuild(&(state.cursor.char_range().unwrap_or_default(), self.content.clone())) {
+            self.history = history;
+        }
+        if std::mem::take(&mut self.undo_persist_pending) && content_hash(&self.content) == self.saved_hash && let Some(path) = &self.file_path {
+            let mut view = FileViewState::load(path);
+            view.undo = SavedUndo::capture(&self.history, &(state.cursor.char_range().unwrap_or_default(), self.content.clone()));
+            view.save(path);
+        }
+    }
+
+    /// Pushes the text from before the latest change onto the history and takes the current text as the new base.
+    pub(super) fn record_undo(&mut self, typing: bool) {
+        let current = (self.last_cursor_range.unwrap_or_default(), self.content.clone());
+        if let Some(base) = self.undo_base.replace(current) && base.1 != self.content {
+            self.history.push(TextSnapshot { state: base, typing }, if typing { "Typing" } else { "Edit" });
+        }
+        self.undo_version = self.content_version;
+    }
+
+    /// Runs before the text field each frame: records edits made outside it (formatting, line moves, ...) as their
+    /// own undo step, and takes Ctrl+Z / Ctrl+Y so the field's built-in history never sees them.
+    pub(super) fn prepare_undo(&mut self, ctx: &egui::Context) {
+        if self.undo_base.is_none() || self.content_version != self.undo_version { self.record_undo(false); }
+        else if let (Some(base), Some(cursor)) = (&mut self.undo_base, self.last_cursor_range) { base.0 = cursor; }
+        let elsewhere = ctx.memory(|m| m.focused()).is_some_and(|f| Some(f) != self.editor_id);
+        if elsewhere { return; }
+        for (action, _) in ctx.input_mut(|i| pressed_shortcuts(i, TE_SHORTCUTS, |a| matches!(a, TextKey::Undo | TextKey::Redo))) {
+            if action == TextKey::Undo { self.undo(); } else { self.redo(); }
+        }
+    }
+
+    /// Called when typing in the text field changed the content.
+    pub(super) fn text_changed(&mut self) {
+        self.refresh_dirty();
+        self.content_version = self.content_version.wrapping_add(1);
+        self.record_undo(true);
+    }
+
+    fn current_snapshot(&mut self) -> TextSnapshot {
+        if self.content_version != self.undo_version { self.record_undo(false); }
+        TextSnapshot { state: (self.last_cursor_range.unwrap_or_default(), self.content.clone()), typing: false }
+    }
+
+    pub(super) fn undo(&mut self) {
+        if self.content_version == self.undo_version && !self.history.can_undo() { return; }
+        let current = self.current_snapshot();
+        if let Some(prev) = self.history.undo(current) { self.restore_text(prev.state); }
+    }
+
+    pub(super) fn redo(&mut self) {
+        if !self.history.can_redo() { return; }
+        let current = self.current_snapshot();
+        if let Some(next) = self.history.redo(current) { self.restore_text(next.state); }
+    }
+
+    fn restore_text(&mut self, (cursor, text): UndoState) {
+        follow_replacement(&mut self.bookmarks, &self.content, &text);
+        self.content.clone_from(&text);
+        self.pending_selection = Some(cursor);
+        self.undo_base = Some((cursor, text));
+        self.content_version = self.content_version.wrapping_add(1);
+        self.undo_version = self.content_version;
+        if self.history.is_saved() { self.dirty = false; } else { self.refresh_dirty(); }
+    }
+
+    fn byte_to_char(&self, byte: usize) -> usize { self.content[..byte].chars().count() }
+
+    fn selected_lines(&self) -> Option<(usize, usize)> {
+        let r = self.last_cursor_range?;
+        let a = self.char_index_to_byte_index(r.primary.index.min(r.secondary.index));
+        let mut b = self.char_index_to_byte_index(r.primary.index.max(r.secondary.index));
+        if b > a && self.content[..b].ends_with('\n') { b -= 1; }
+        let start = self.content[..a].rfind('\n').map_or(0, |i| i + 1);
+        let end = self.content[b..].find('\n').map_or(self.content.len(), |i| b + i);
+        Some((start, end))
+    }
+
+    fn replace_lines(&mut self, range: std::ops::Range<usize>, text: &str, shift: isize) {
+        let Some(r) = self.last_cursor_range else { return };
+        self.splice(range, text);
+        let moved = |i: usize| i.saturating_add_signed(shift);
+        self.pending_selection = Some(egui::text::CCursorRange::two(egui::text::CCursor::new(moved(r.secondary.index)), egui::text::CCursor::new(moved(r.primary.index))));
+        self.refresh_dirty();
+        self.content_version = self.content_version.wrapping_add(1);
+    }
+
+    pub(super) fn duplicate_lines(&mut self) {
+        let Some((start, end)) = self.selected_lines() else { return };
+        let block = self.content[start..end].to_string();
+        self.replace_lines(end..end, &format!("\n{}", block), block.chars().count() as isize + 1);
+    }
+
+    pub(super) fn move_lines_up(&mut self) {
+        let Some((start, end)) = self.selected_lines() else { return };
+        if start == 0 { return; }
+        let prev_start = self.content[..start - 1].rfind('\n').map_or(0, |i| i + 1);
+        let prev = self.content[prev_start..start - 1].to_string();
+        let text = format!("{}\n{}", &self.content[start..end], prev);
+        self.replace_lines(prev_start..end, &text, -(prev.chars().count() as isize + 1));
+    }
+
+    pub(super) fn move_lines_down(&mut self) {
+        let Some((start, end)) = self.selected_lines() else { return };
+        if end >= self.content.len() { return; }
+        let next_end = self.content[end + 1..].find('\n').map_or(self.content.len(), |i| end + 1 + i);
+        let next = self.content[end + 1..next_end].to_string();
+        let text = format!("{}\n{}", next, &self.content[start..end]);
+        self.replace_lines(start..next_end, &text, next.chars().count() as isize + 1);
+    }
+
+    pub(super) fn delete_lines(&mut self) {
+        let Some((start, end)) = self.selected_lines() else { return };
+        let Some(r) = self.last_cursor_range else { return };
+        let column = r.primary.index - self.byte_to_char(self.content[..self.char_index_to_byte_index(r.primary.index)].rfind('\n').map_or(0, |i| i + 1));
+        let last = end >= self.content.len() && start > 0;
+        let range = if end < self.content.len() { start..end + 1 } else if last { start - 1..end } else { start..end };
+        self.splice(range, "");
+        let line_start = if last { self.content[..start - 1].rfind('\n').map_or(0, |i| i + 1) } else { start };
+        let line_len = self.content[line_start..].find('\n').map_or(self.content.len() - line_start, |i| i);
+        let col_byte = self.content[line_start..line_start + line_len].char_indices().nth(column).map_or(line_len, |(b, _)| b);
+        self.pending_cursor_pos = Some(self.byte_to_char(line_start + col_byte));
+        self.refresh_dirty();
+        self.content_version = self.content_version.wrapping_add(1);
+    }
+
+    pub(super) fn select_lines(&mut self) {
+        let Some(r) = self.last_cursor_range else { return };
+        let a = self.char_index_to_byte_index(r.primary.index.min(r.secondary.index));
+        let b = self.char_index_to_byte_index(r.primary.index.max(r.secondary.index));
+        let start = self.content[..a].rfind('\n').map_or(0, |i| i + 1);
+        let whole_lines = a == start && b > a && (self.content[..b].ends_with('\n') || b == self.content.len());
+        let from = if whole_lines { b } else { b.max(start) };
+        let end = self.content[from..].find('\n').map_or(self.content.len(), |i| from + i + 1);
+        self.pending_selection = Some(egui::text::CCursorRange::two(egui::text::CCursor::new(self.byte_to_char(start)), egui::text::CCursor::new(self.byte_to_char(end))));
+    }
+
+    /// Keeps the layout the text field just drew; call after `text_changed` so the version matches it.
+    pub(super) fn remember_rows(&mut self, ui: &egui::Ui, output: &egui::text_edit::TextEditOutput) {
+        self.row_layout = Some((self.content_version, output.galley.clone(), ui.clip_rect().height()));
+    }
+
+    /// egui already moves Up/Down and Home/End within wrapped rows; this adds the second Home/End press that goes
+    /// to the logical line and PageUp/PageDown, which it ignores. Uses last frame's layout, so it follows the wrap
+    /// width the text was drawn with, and steps aside when the text changed since.
+    pub(super) fn handle_row_navigation(&mut self, ctx: &egui::Context) {
+        let (Some(id), Some((version, galley, view_h))) = (self.editor_id, &self.row_layout) else { return };
+        if *version != self.content_version || ctx.memory(|m| m.focused()) != Some(id) { return; }
+        let Some(mut state) = egui::TextEdit::load_state(ctx, id) else { return };
+        let Some(mut range) = state.cursor.char_range() else { return };
+        let pressed = ctx.input_mut(|i| {
+            if i.modifiers.alt { return None; }
+            let shift = i.modifiers.shift;
+            pressed_shortcuts(i, TE_SHORTCUTS, |a| matches!(a, TextKey::RowEdge | TextKey::Page)).first().map(|&(a, chord)| (a, chord == 1, shift))
+        });
+        let Some((action, forward, shift)) = pressed else { return };
+        let row_h = galley.rows.get(galley.layout_from_cursor(range.primary).row).map_or(self.font_size, |r| r.height()).max(1.0);
+        let page = ((view_h / row_h).floor() as isize).max(1);
+        let (primary, h_pos) = match action {
+            TextKey::RowEdge => (row_edge(galley, range.primary, forward), None),
+            _ => move_rows(galley, range.primary, if forward { page } else { -page }, range.h_pos),
+        };
+        if action == TextKey::Page {
+            let by = page as f32 * row_h;
+            self.scroll_offset = (self.scroll_offset + if forward { by } else { -by }).max(0.0);
+        }
+        range.primary = primary; range.h_pos = h_pos;
+        if !shift { range.secondary = primary; }
+        state.cursor.set_char_range(Some(range));
+        state.store(ctx, id);
+        self.last_cursor_range = Some(range);
+    }
+
+    /// Backspace, Delete and Left/Right step over whole graphemes, so an "é" spelled e + U+0301 or a ZWJ emoji goes
+    /// in one press; with Ctrl (Alt on macOS) they use the word boundaries text layers use. Deleting a selection and
+    /// collapsing one with an arrow stay with egui.
+    pub(super) fn handle_grapheme_keys(&mut self, ctx: &egui::Context) {
+        let Some(id) = self.editor_id else { return };
+        if ctx.memory(|m| m.focused()) != Some(id) { return; }
+        let Some(mut state) = egui::TextEdit::load_state(ctx, id) else { return };
+        let Some(mut range) = state.cursor.char_range() else { return };
+        let collapsed = range.primary.index == range.secondary.index;
+        let keys = ctx.input_mut(|i| {
+            let mut keys = Vec::new();
+            i.events.retain(|e| {
+                let egui::Event::Key { key, pressed: true, modifiers, .. } = e else { return true };
+                let arrow = matches!(key, egui::Key::ArrowLeft | egui::Key::ArrowRight);
+                let ours = (arrow || matches!(key, egui::Key::Backspace | egui::Key::Delete)) && !modifiers.mac_cmd && (collapsed || arrow && modifiers.shift);
+                if ours { keys.push((*key, *modifiers)); }
+                !ours
+            });
+            keys
+        });
+        if keys.is_empty() { return; }
+        let mut edited = false;
+        for (key, modifiers) in keys {
+            let (next, changed) = self.grapheme_key(range, key, modifiers);
+            range = next; edited |= changed;
+        }
+        if edited { self.text_changed(); }
+        state.cursor.set_char_range(Some(range));
+        state.store(ctx, id);
+        self.last_cursor_range = Some(range);
+    }
+
+    pub(super) fn grapheme_key(&mut self, range: egui::text::CCursorRange, key: egui::Key, modifiers: egui::Modifiers) -> (egui::text::CCursorRange, bool) {
+        let at = self.char_index_to_byte_index(range.primary.index);
+        let word = modifiers.alt || modifiers.ctrl;
+        let to = match (matches!(key, egui::Key::Backspace | egui::Key::ArrowLeft), word) {
+            (true, true) => prev_word_boundary(&self.content, at),
+            (true, false) => prev_grapheme_boundary(&self.content, at),
+            (false, true) => next_word_boundary(&self.content, at),
+            (false, false) => next_grapheme_boundary(&self.content, at),
+        };
+        if matches!(key, egui::Key::Backspace | egui::Key::Delete) {
+            if to == at { return (range, false); }
+            let (a, b) = (at.min(to), at.max(to));
+            self.splice(a..b, "");
+            return (egui::text::CCursorRange::one(egui::text::CCursor::new(self.byte_to_char(a))), true);
+        }
+        let mut range = range;
+        range.primary = egui::text::CCursor::new(self.byte_to_char(to));
+        if !modifiers.shift { range.secondary = range.primary; }
+        range.h_pos = None;
+        (range, false)
+    }
+
+    pub(super) fn handle_line_shortcuts(&mut self, ctx: &egui::Context) {
+        let line = |a| matches!(a, TextKey::DuplicateLines | TextKey::MoveLinesUp | TextKey::MoveLinesDown | TextKey::DeleteLines | TextKey::SelectLines);
+        for (action, _) in ctx.input_mut(|i| pressed_shortcuts(i, TE_SHORTCUTS, line)) {
+            match action {
+                TextKey::DuplicateLines => self.duplicate_lines(),
+                TextKey::MoveLinesUp => self.move_lines_up(),
+                TextKey::MoveLinesDown => self.move_lines_down(),
+                TextKey::DeleteLines => self.delete_lines(),
+                _ => self.select_lines(),
+            }
+        }
+    }
+
+    pub(super) fn handle_paste(&mut self, ctx: &egui::Context) {
+        if self.poll_pending_paste() { ctx.request_repaint(); }
+        let Some(r) = self.last_cursor_range else { return };
+        if self.pending_paste.is_some() || !self.editor_id.is_some_and(|id| ctx.memory(|m| m.has_focus(id))) { return; }
+        let taken = ctx.input_mut(|i| {
+            let clean = i.modifiers.shift;
+            let idx = i.events.iter().position(|e| matches!(e, egui::Event::Paste(t) if clean || t.len() >= LARGE_PASTE_BYTES))?;
+            match i.events.remove(idx) { egui::Event::Paste(t) => Some((t, clean)), _ => None }
+        });
+        let Some((text, clean)) = taken else { return };
+        let range = (r.primary.index.min(r.secondary.index), r.primary.index.max(r.secondary.index));
+        if text.len() < LARGE_PASTE_BYTES {
+            let (text, cleaned) = if clean { self.settings.paste_cleanup.clean(&text) } else { (text, 0) };
+            self.splice_paste(range, &text);
+            if clean { self.paste_notice(cleaned); }
+            return;
+        }
+        self.start_large_paste(text, range, clean);
+    }
+
+    pub(super) fn start_large_paste(&mut self, text: String, range: (usize, usize), clean: bool) {
+        let stage = if clean {
+            let (tx, rx) = std::sync::mpsc::sync_channel(1);
+            let opts = self.settings.paste_cleanup;
+            let task = crate::tasks::spawn("Cleaning pasted text", true, move |task| {
+                if !task.cancelled() { let _ = tx.send(opts.clean(&text)); }
+            });
+            PasteStage::Cleaning(task, rx)
+        } else {
+            PasteStage::Splicing { text, done: 0, cleaned: None }
+        };
+        self.pending_paste = Some(PendingPaste { stage, range, version: self.content_version });
+    }
+
+    /// Moves a large paste along: takes the cleaned text once the task sends it, then splices the next chunk.
+    /// Returns whether there is more to do. If the text was edited meanwhile, the rest goes in at the caret.
+    pub(super) fn poll_pending_paste(&mut self) -> bool {
+        let Some(mut p) = self.pending_paste.take() else { return false };
+        if let PasteStage::Cleaning(_, rx) = &p.stage {
+            match rx.try_recv() {
+                Ok((text, cleaned)) => p.stage = PasteStage::Splicing { text, done: 0, cleaned: Some(cleaned) },
+                Err(std::sync::mpsc::TryRecvError::Empty) => { self.pending_paste = Some(p); return false; }
+                Err(std::sync::mpsc::TryRecvError::Disconnected) => return false,
+            }
+        }
+        let PasteStage::Splicing { text, done, cleaned } = &mut p.stage else { return false };
+        if p.version != self.content_version {
+            let at = self.last_cursor_range.map_or(self.content.chars().count(), |r| r.primary.index);
+            p.range = (at, at);
+        }
+        let mut end = (*done + PASTE_CHUNK_BYTES).min(text.len());
+        while !text.is_char_boundary(end) { end -= 1; }
+        let chunk = &text[*done..end];
+        self.splice_paste(p.range, chunk);
+        let at = p.range.0 + chunk.chars().count();
+        (p.range, p.version, *done) = ((at, at), self.content_version, end);
+        if end < text.len() { self.pending_paste = Some(p); return true; }
+        if let Some(n) = *cleaned { self.paste_notice(n); }
+        false
+    }
+
+    fn splice_paste(&mut self, (a, b): (usize, usize), text: &str) {
+        let (start, end) = (self.char_index_to_byte_index(a), self.char_index_to_byte_index(b));
+        self.splice(start..end, text);
+        self.pending_cursor_pos = Some(a + text.chars().count());
+        self.refresh_dirty();
+        self.content_version = self.content_version.wrapping_add(1);
+    }
+
+    fn paste_notice(&mut self, cleaned: usize) {
+        self.pending_notice = Some(match cleaned {
+            0 => "Pasted as clean text, nothing to clean".to_string(),
+            n => format!("Pasted as clean text, {} character{} cleaned", n, if n == 1 { "" } else { "s" }),
+        });
+    }
+
     pub(super) fn insert_wrapper_at_cursor(&mut self, wrapper: &str) {
         if let Some(range) = self.last_cursor_range {
             let cursor_pos: usize = self.char_index_to_byte_index(range.primary.index);
-            self.content.insert_str(cursor_pos, &format!("{}{}", wrapper, wrapper));
+            self.splice(cursor_pos..cursor_pos, &format!("{}{}", wrapper, wrapper));
             self.dirty = true;
+            self.content_version = self.content_version.wrapping_add(1);
             self.pending_cursor_pos = Some(range.primary.index + wrapper.chars().count());
         }
     }
@@ -61,16 +485,17 @@ impl TextEditor {
             let has_suffix: bool = suffix_end_byte <= self.content.len() && &self.content[end_byte..suffix_end_byte] == wrapper;
 
             if has_prefix && has_suffix {
-                self.content.replace_range(end_byte..suffix_end_byte, "");
-                self.content.replace_range(prefix_start_byte..start_byte, "");
+                self.splice(end_byte..suffix_end_byte, "");
+                self.splice(prefix_start_byte..start_byte, "");
                 self.pending_cursor_pos = Some(start_char + selected.chars().count());
             } else {
                 let wrapped: String = format!("{}{}{}", wrapper, selected, wrapper);
-                self.content.replace_range(start_byte..end_byte, &wrapped);
+                self.splice(start_byte..end_byte, &wrapped);
                 self.pending_cursor_pos = Some(start_char + selected.chars().count() + wlen * 2);
             }
 
             self.dirty = true;
+            self.content_version = self.content_version.wrapping_add(1);
         }
     }
 
@@ -90,13 +515,14 @@ impl TextEditor {
             let content_start: usize = line.find(|c: char| c != '#' && !c.is_whitespace()).unwrap_or(line.len());
             let clean: &str = &line[content_start..];
             let new_line: String = if level > 0 { format!("{} {}", "#".repeat(level), clean) } else { clean.to_string() };
-            self.content.replace_range(start_byte..end_byte, &new_line);
+            self.splice(start_byte..end_byte, &new_line);
             self.dirty = true;
+            self.content_version = self.content_version.wrapping_add(1);
         }
     }
 
-    pub(super) fn count_words(&self) -> usize {
-        self.content.split_whitespace().filter(|w: &&str| !w.is_empty()).count()
+    pub(super) fn count_words(&mut self) -> WordCounts {
+        self.word_count_cache.counts(&self.content, self.content_version, self.word_count_options)
     }
 
     pub(super) fn is_horizontal_rule(line: &str) -> bool {
@@ -122,8 +548,9 @@ impl TextEditor {
             } else {
                 format!("> {}", line)
             };
-            self.content.replace_range(start_byte..end_byte, &new_line);
+            self.splice(start_byte..end_byte, &new_line);
             self.dirty = true;
+            self.content_version = self.content_version.wrapping_add(1);
         }
     }
 
@@ -140,8 +567,9 @@ impl TextEditor {
             } else {
                 format!("- [ ] {}", line)
             };
-            self.content.replace_range(start_byte..end_byte, &new_line);
+            self.splice(start_byte..end_byte, &new_line);
             self.dirty = true;
+            self.content_version = self.content_version.wrapping_add(1);
         }
     }
 
@@ -170,7 +598,7 @@ impl TextEditor {
                     let list_char: char = line.chars().next().unwrap();
                     let checked_prefix: String = format!("{} [x] ", list_char);
                     let end: usize = line_start_byte + prefix.len();
-                    self.content.replace_range(line_start_byte..end, &checked_prefix);
+                    self.splice(line_start_byte..end, &checked_prefix);
                     self.dirty = true;
                     self.content_version = self.content_version.wrapping_add(1);
                     return;
@@ -181,7 +609,7 @@ impl TextEditor {
                     let list_char: char = line.chars().next().unwrap();
                     let unchecked_prefix: String = format!("{} [ ] ", list_char);
                     let end: usize = line_start_byte + prefix.len();
-                    self.content.replace_range(line_start_byte..end, &unchecked_prefix);
+                    self.splice(line_start_byte..end, &unchecked_prefix);
                     self.dirty = true;
                     self.content_version = self.content_version.wrapping_add(1);
                     return;
@@ -230,11 +658,29 @@ impl TextEditor {
         chars[start..].iter().position(|&c| c == ')').map(|i: usize| start + i)
     }
 
-    pub(super) fn open_file_location(&self) {
-        if let Some(path) = &self.file_path {
-            let dir = path.parent().unwrap_or(path.as_path());
-            #[cfg(target_os = "windows")]
-            { let _ = std::process::Command::new("explorer").arg(dir).spawn(); }
+    pub(super) fn open_file_location(&mut self) {
+        if let Some(path) = &self.file_path && let Err(e) = reveal_in_file_manager(path) {
+            self.pending_error = Some(EditorError::Other(format!("Could not open the file manager: {}", e)));
+        }
+    }
+
+    pub(super) fn handle_file_info_action(&mut self, action: FileInfoAction) {
+        if self.file_path.is_none() || matches!(action, FileInfoAction::SaveAs) {
+            if let Err(e) = self.save_as() && !e.is_cancelled() { self.pending_error = Some(e); }
+            return;
+        }
+        match action {
+            FileInfoAction::Reveal => self.open_file_location(),
+            FileInfoAction::Rename => {
+                if self.dirty && let Err(e) = self.save() { self.pending_error = Some(e); return; }
+                let current_ext = self.file_path.as_ref().and_then(|p| p.extension()).and_then(|e| e.to_str())
+                    .map(|e| e.to_lowercase()).unwrap_or_else(|| "txt".to_string());
+                self.rename_buffer = self.file_path.as_ref().and_then(|p| p.file_stem()).and_then(|s| s.to_str())
+                    .unwrap_or("untitled").to_string();
+                self.rename_ext = Some(if current_ext == "md" { "md".to_string() } else { "txt".to_string() });
+                self.rename_modal_open = true;
+            }
+            FileInfoAction::SaveAs => {}
         }
     }
 
@@ -245,14 +691,16 @@ impl TextEditor {
             let ext = self.rename_ext.as_deref().unwrap_or("txt");
             let new_name = format!("{}.{}", stem, ext);
             let new_path = old_path.with_file_name(&new_name);
-            if std::fs::rename(&old_path, &new_path).is_ok() {
+            if let Err(e) = rename_file(&old_path, &new_path) {
+                self.pending_error = Some(EditorError::Other(format!("Could not rename {}: {}", old_path.display(), e)));
+                self.file_path = Some(old_path);
+            } else {
                 if let Some(tx) = &self.path_replace_tx {
                     let _ = tx.send((old_path, new_path.clone()));
                 }
                 self.file_path = Some(new_path.clone());
                 self.view_mode = Self::detect_view_mode(&new_path);
-            } else {
-                self.file_path = Some(old_path);
+                self.save_view_state();
             }
         }
     }
@@ -272,9 +720,139 @@ impl TextEditor {
                 }
                 self.file_path = Some(new_path.clone());
                 self.view_mode = Self::detect_view_mode(&new_path);
+                self.save_view_state();
             } else {
                 self.file_path = Some(path);
             }
         }
     }
+
+    pub(super) fn current_line(&self) -> usize {
+        let at = self.last_cursor_range.map_or(0, |r| self.char_index_to_byte_index(r.primary.index));
+        self.content[..at].bytes().filter(|b| *b == b'\n').count()
+    }
+
+    pub(super) fn jump_to_line(&mut self, line: usize) {
+        let byte = if line == 0 { 0 } else { self.content.match_indices('\n').nth(line - 1).map_or(self.content.len(), |(i, _)| i + 1) };
+        self.pending_cursor_pos = Some(self.byte_to_char(byte));
+        self.pending_scroll_line = Some(line);
+    }
+
+    pub(super) fn toggle_bookmark(&mut self) {
+        let line = self.current_line();
+        match self.bookmarks.binary_search(&line) {
+            Ok(i) => { self.bookmarks.remove(i); }
+            Err(i) => self.bookmarks.insert(i, line),
+        }
+        self.save_view_state();
+    }
+
+    pub(super) fn jump_bookmark(&mut self, forward: bool) {
+        let line = self.current_line();
+        let target = if forward {
+            self.bookmarks.iter().find(|l| **l > line).or(self.bookmarks.first())
+        } else {
+            self.bookmarks.iter().rev().find(|l| **l < line).or(self.bookmarks.last())
+        };
+        if let Some(&target) = target { self.jump_to_line(target); }
+    }
+
+    pub(super) fn clear_bookmarks(&mut self) {
+        self.bookmarks.clear();
+        self.save_view_state();
+    }
+
+    pub(super) fn save_view_state(&self) {
+        if let Some(path) = &self.file_path {
+            let mut view = FileViewState::load(path);
+            view.bookmarks = self.bookmarks.clone();
+            view.save(path);
+        }
+    }
+
+    /// Replaces `range` of the content, moving bookmarks along with the lines around it.
+    pub(super) fn splice(&mut self, range: std::ops::Range<usize>, text: &str) {
+        shift_bookmarks(&mut self.bookmarks, self.content.as_bytes(), range.clone(), text.as_bytes());
+        self.content.replace_range(range, text);
+    }
+
+    pub(super) fn update_word_index(&mut self) {
+        if self.word_index.version == self.content_version { return; }
+        if !self.settings.word_completion { self.word_index = WordIndex { version: u64::MAX, ..Default::default() }; return; }
+        let WordIndex { counts, snapshot, version } = &mut self.word_index;
+        *version = self.content_version;
+        let (old, new) = (snapshot.as_bytes(), self.content.as_bytes());
+        if old == new { return; }
+        let mut prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+        while !snapshot.is_char_boundary(prefix) { prefix -= 1; }
+        let max_suffix = old.len().min(new.len()) - prefix;
+        let mut suffix = old.iter().rev().zip(new.iter().rev()).take(max_suffix).take_while(|(a, b)| a == b).count();
+        while !snapshot.is_char_boundary(old.len() - suffix) { suffix -= 1; }
+        let start = whitespace_before(snapshot, prefix);
+        let tail = snapshot[old.len() - suffix..].find(char::is_whitespace).unwrap_or(suffix);
+        let (old_end, new_end) = (old.len() - suffix + tail, new.len() - suffix + tail);
+        count_words(counts, &snapshot[start..old_end], false);
+        count_words(counts, &self.content[start..new_end], true);
+        snapshot.replace_range(start..old_end, &self.content[start..new_end]);
+    }
+
+    fn completion_at(&self, cursor: usize) -> Option<Completion> {
+        let byte = self.char_index_to_byte_index(cursor);
+        if self.content[byte..].chars().next().is_some_and(char::is_alphanumeric) { return None; }
+        let seg = whitespace_before(&self.content, byte);
+        let (off, word) = self.content[seg..byte].unicode_word_indices().next_back()?;
+        if seg + off + word.len() != byte || word.chars().count() < MIN_COMPLETION_CHARS { return None; }
+        let lower = word.to_lowercase();
+        let mut scored: Vec<(f32, &String)> = self.word_index.counts.iter()
+            .filter(|(w, _)| w.len() > word.len() && w.to_lowercase().starts_with(&lower))
+            .map(|(w, &c)| ((c as f32).ln_1p(), w)).collect();
+        if scored.is_empty() { return None; }
+        let (mut lo, mut hi) = (byte.saturating_sub(PROXIMITY_WINDOW), (byte + PROXIMITY_WINDOW).min(self.content.len()));
+        while !self.content.is_char_boundary(lo) { lo -= 1; }
+        while !self.content.is_char_boundary(hi) { hi += 1; }
+        let mut bonus = vec![0.0f32; scored.len()];
+        for (i, w) in self.content[lo..hi].unicode_word_indices() {
+            let Some(k) = scored.iter().position(|(_, s)| s.as_str() == w) else { continue };
+            bonus[k] = bonus[k].max(2.0 * (1.0 - (lo + i).abs_diff(byte) as f32 / PROXIMITY_WINDOW as f32));
+        }
+        for (s, b) in scored.iter_mut().zip(&bonus) { s.0 += b; }
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| a.1.cmp(b.1)));
+        let items = scored.into_iter().take(MAX_COMPLETIONS).map(|(_, w)| w.clone()).collect();
+        Some(Completion { start: cursor - word.chars().count(), cursor, items, selected: 0 })
+    }
+
+    pub(super) fn update_completion(&mut self, changed: bool) {
+        if !self.settings.word_completion { self.completion = None; return; }
+        self.update_word_index();
+        let Some(cursor) = self.last_cursor_range.filter(|r| r.primary.index == r.secondary.index).map(|r| r.primary.index) else { self.completion = None; return };
+        if changed { self.completion = self.completion_at(cursor); }
+        else if self.completion.as_ref().is_some_and(|c| c.cursor != cursor) { self.completion = None; }
+    }
+
+    pub(super) fn handle_completion_keys(&mut self, ctx: &egui::Context) {
+        if self.completion.is_none() { return; }
+        if !self.editor_id.is_some_and(|id| ctx.memory(|m| m.has_focus(id))) { self.completion = None; return; }
+        let (accept, dismiss, down, up) = ctx.input_mut(|i| (
+            i.consume_key(egui::Modifiers::NONE, egui::Key::Tab) || i.consume_key(egui::Modifiers::NONE, egui::Key::Enter),
+            i.consume_key(egui::Modifiers::NONE, egui::Key::Escape),
+            i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowDown),
+            i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowUp),
+        ));
+        let Some(c) = &mut self.completion else { return };
+        let n = c.items.len();
+        if down { c.selected = (c.selected + 1) % n; }
+        if up { c.selected = (c.selected + n - 1) % n; }
+        if dismiss { self.completion = None; } else if accept { let i = c.selected; self.accept_completion(ctx, i); }
+    }
+
+    pub(super) fn accept_completion(&mut self, ctx: &egui::Context, index: usize) {
+        let Some(c) = self.completion.take() else { return };
+        let Some(word) = c.items.get(index) else { return };
+        let (a, b) = (self.char_index_to_byte_index(c.start), self.char_index_to_byte_index(c.cursor));
+        self.splice(a..b, word);
+        self.pending_cursor_pos = Some(c.start + word.chars().count());
+        self.refresh_dirty();
+        self.content_version = self.content_version.wrapping_add(1);
+        if let Some(id) = self.editor_id { ctx.memory_mut(|m| m.request_focus(id)); }
+    }
 }
@@ -1,10 +1,15 @@
 use eframe::egui;
-use crate::{modules::EditorModule, style::{ColorPalette, ThemeMode, toolbar_action_btn}};
-use super::te_main::{TextEditor, ViewMode};
+use crate::{modules::{EditorModule, pressed_shortcuts}, style::{ColorPalette, ThemeMode, toolbar_action_btn}};
+use crate::modules::helpers::file_info::{FileInfoAction, file_info_popover};
+use super::te_main::{TextEditor, ViewMode, LargeFile, PREVIEW_BYTES, megabytes, TextKey, TE_SHORTCUTS};
+use super::te_count::CjkCounting;
+use super::te_tools::BookmarkedText;
 
 impl TextEditor {
     pub(super) fn render_editor_ui(&mut self, ui: &mut egui::Ui, ctx: &egui::Context, show_toolbar: bool, show_file_info: bool) {
-        if show_toolbar {
+        self.poll_large_load();
+        if self.large_file.is_some() { self.render_large_file_state(ui, ctx); return; }
+        if show_toolbar && self.preview_of.is_none() {
             ui.horizontal(|ui: &mut egui::Ui| {
                 let dark = ui.visuals().dark_mode;
                 let theme = if dark { ThemeMode::Dark } else { ThemeMode::Light };
@@ -65,8 +70,8 @@ impl TextEditor {
                     egui::ComboBox::from_id_salt("view_mode")
                         .selected_text(match self.view_mode { ViewMode::Markdown => "Markdown", ViewMode::Plain => "Plain Text" })
                         .show_ui(ui, |ui: &mut egui::Ui| {
-                            ui.selectable_value(&mut self.view_mode, ViewMode::Markdown, "Markdown");
-                            ui.selectable_value(&mut self.view_mode, ViewMode::Plain, "Plain Text");
+                            if ui.selectable_label(self.view_mode == ViewMode::Markdown, "Markdown").clicked() { self.set_view_mode(ViewMode::Markdown); }
+                            if ui.selectable_label(self.view_mode == ViewMode::Plain, "Plain Text").clicked() { self.set_view_mode(ViewMode::Plain); }
                         });
                 });
 
@@ -107,19 +112,23 @@ impl TextEditor {
                     egui::Label::new(format!("File: {}", self.get_file_name()))
                         .sense(egui::Sense::click()),
                 );
-                file_label_resp.clone().on_hover_text("Right-click for file options");
+                let info_id = file_label_resp.id.with("file_info");
+                let info_action = egui::Popup::from_toggle_button_response(&file_label_resp).id(info_id)
+                    .close_behavior(egui::PopupCloseBehavior::CloseOnClickOutside)
+                    .show(|ui: &mut egui::Ui| file_info_popover(ui, self.file_path.as_deref(), self.dirty))
+                    .and_then(|r| r.inner);
+                if let Some(action) = info_action {
+                    egui::Popup::close_id(ui.ctx(), info_id);
+                    self.handle_file_info_action(action);
+                }
+                file_label_resp.clone().on_hover_text("Click for file details, right-click for file options");
                 file_label_resp.context_menu(|ui: &mut egui::Ui| {
                     if ui.button("Open File Location").on_hover_cursor(egui::CursorIcon::PointingHand).clicked() {
                         self.open_file_location();
                         ui.close();
                     }
                     if ui.button("Rename File").on_hover_cursor(egui::CursorIcon::PointingHand).clicked() {
-                        let current_ext = self.file_path.as_ref().and_then(|p| p.extension()).and_then(|e| e.to_str())
-                            .map(|e| e.to_lowercase()).unwrap_or_else(|| "txt".to_string());
-                        self.rename_buffer = self.file_path.as_ref().and_then(|p| p.file_stem()).and_then(|s| s.to_str())
-                            .unwrap_or("untitled").to_string();
-                        self.rename_ext = Some(if current_ext == "md" { "md".to_string() } else { "txt".to_string() });
-                        self.rename_modal_open = true;
+                        self.handle_file_info_action(FileInfoAction::Rename);
                         ui.close();
                     }
                     let convert_label = match self.file_path.as_ref().and_then(|p| p.extension()).and_then(|e| e.to_str())
@@ -137,7 +146,9 @@ impl TextEditor {
                     }
                 });
                 ui.separator();
-                let (status, color) = if self.dirty {
+                let (status, color) = if self.preview_of.is_some() {
+                    ("Read-only preview", if is_dark { ColorPalette::BLUE_400 } else { ColorPalette::BLUE_600 })
+                } else if self.dirty {
                     ("Unsaved", if is_dark { ColorPalette::AMBER_400 } else { ColorPalette::AMBER_600 })
                 } else {
                     ("Saved", if is_dark { ColorPalette::GREEN_400 } else { ColorPalette::GREEN_600 })
@@ -145,7 +156,12 @@ impl TextEditor {
                 ui.label(egui::RichText::new(status).color(color));
                 if self.show_word_count_in_info {
                     ui.separator();
-                    ui.label(format!("Words: {}", self.modal_word_count));
+                    ui.label(self.modal_word_count.to_string());
+                }
+                if let Some(paste) = &self.pending_paste {
+                    ui.separator();
+                    ui.spinner();
+                    ui.label(egui::RichText::new(paste.label()).weak());
                 }
             });
 
@@ -183,62 +199,78 @@ impl TextEditor {
             ui.separator();
         }
 
-        match self.view_mode {
-            ViewMode::Markdown => self.markdown_editable(ui, ctx),
-            ViewMode::Plain => {
-                let avail_rect = ui.available_rect_before_wrap();
-                if ctx.input(|i| i.pointer.button_down(egui::PointerButton::Primary)) {
-                    if let Some(p) = ctx.pointer_hover_pos() {
-                        let (e, s) = (50.0f32, 6.0f32);
-                        if p.y < avail_rect.min.y + e {
-                            self.scroll_offset = (self.scroll_offset - s * (1.0 - (p.y - avail_rect.min.y).max(0.0) / e)).max(0.0);
-                            ctx.request_repaint();
-                        } else if p.y > avail_rect.max.y - e {
-                            self.scroll_offset += s * (p.y - (avail_rect.max.y - e)).max(0.0) / e;
-                            ctx.request_repaint();
+        self.prepare_undo(ctx);
+        if let Some(total) = self.preview_of { self.render_preview(ui, ctx, total); } else {
+            self.handle_row_navigation(ctx);
+            self.handle_grapheme_keys(ctx);
+            self.handle_line_shortcuts(ctx);
+            self.handle_paste(ctx);
+            self.handle_completion_keys(ctx);
+            if self.show_bookmarks_panel { self.render_bookmarks_panel(ui); }
+            if self.suggest_markdown { self.render_markdown_suggestion(ui); }
+            match self.view_mode {
+                ViewMode::Markdown => self.markdown_editable(ui, ctx),
+                ViewMode::Plain => {
+                    let avail_rect = ui.available_rect_before_wrap();
+                    if ctx.input(|i| i.pointer.button_down(egui::PointerButton::Primary)) {
+                        if let Some(p) = ctx.pointer_hover_pos() {
+                            let (e, s) = (50.0f32, 6.0f32);
+                            if p.y < avail_rect.min.y + e {
+                                self.scroll_offset = (self.scroll_offset - s * (1.0 - (p.y - avail_rect.min.y).max(0.0) / e)).max(0.0);
+                                ctx.request_repaint();
+                            } else if p.y > avail_rect.max.y - e {
+                                self.scroll_offset += s * (p.y - (avail_rect.max.y - e)).max(0.0) / e;
+                                ctx.request_repaint();
+                            }
                         }
+                        let sw = ctx.input(|i| i.smooth_scroll_delta.y);
+                        if sw != 0.0 { self.scroll_offset = (self.scroll_offset - sw).max(0.0); ctx.request_repaint(); }
                     }
-                    let sw = ctx.input(|i| i.smooth_scroll_delta.y);
-                    if sw != 0.0 { self.scroll_offset = (self.scroll_offset - sw).max(0.0); ctx.request_repaint(); }
+                    let sa_out = egui::ScrollArea::vertical().vertical_scroll_offset(self.scroll_offset).show(ui, |ui: &mut egui::Ui| {
+                        let font_id: egui::FontId = egui::FontId::new(self.font_size, self.font_family.clone());
+                        let margin: egui::Margin = self.gutter_margin();
+                        let mut buffer = BookmarkedText { text: &mut self.content, bookmarks: &mut self.bookmarks };
+                        let text_edit: egui::TextEdit<'_> = egui::TextEdit::multiline(&mut buffer)
+                            .font(font_id).lock_focus(true).frame(false).margin(margin);
+                        let layout = egui::Layout::centered_and_justified(ui.layout().main_dir());
+                        let highlight = ui.painter().add(egui::Shape::Noop);
+                        let output = ui.allocate_ui_with_layout(ui.available_size(), layout, |ui| text_edit.show(ui)).inner;
+                        self.sync_cursor_state(ctx, output.response.id);
+                        if output.response.changed() { self.text_changed(); }
+                        self.remember_rows(ui, &output);
+                        self.update_completion(output.response.changed());
+                        self.render_completion_popup(ctx, &output);
+                        self.paint_color_swatches(ui, &output);
+                        let typewriter = self.finish_focus_aids(ui, &output, highlight);
+                        self.finish_bookmark_frame(ui, &output).or(typewriter)
+                    });
+                    self.scroll_offset = sa_out.inner.unwrap_or(sa_out.state.offset.y);
+                    self.render_color_edit(ctx);
                 }
-                let sa_out = egui::ScrollArea::vertical().vertical_scroll_offset(self.scroll_offset).show(ui, |ui: &mut egui::Ui| {
-                    let font_id: egui::FontId = egui::FontId::new(self.font_size, self.font_family.clone());
-                    let text_edit: egui::TextEdit<'_> = egui::TextEdit::multiline(&mut self.content)
-                        .font(font_id).lock_focus(true).frame(false);
-                    let response: egui::Response = ui.add_sized(ui.available_size(), text_edit);
-                    if let Some(new_pos) = self.pending_cursor_pos.take() {
-                        if let Some(mut state) = egui::TextEdit::load_state(ctx, response.id) {
-                            let ccursor: egui::text::CCursor = egui::text::CCursor::new(new_pos);
-                            state.cursor.set_char_range(Some(egui::text::CCursorRange::one(ccursor)));
-                            state.store(ctx, response.id);
-                        }
-                    }
-                    if let Some(state) = egui::TextEdit::load_state(ctx, response.id) {
-                        if let Some(r) = state.cursor.char_range() { self.last_cursor_range = Some(r); }
-                    }
-                    if response.changed() { self.dirty = true; self.content_version = self.content_version.wrapping_add(1); }
-                });
-                self.scroll_offset = sa_out.state.offset.y;
             }
-        }
 
-        ctx.input_mut(|i: &mut egui::InputState| {
-            if i.consume_key(egui::Modifiers::CTRL, egui::Key::S) {
-                if !i.modifiers.shift { let _ = self.save(); } else { self.format_strikethrough(); }
+            let format = |a| !matches!(a, TextKey::Undo | TextKey::Redo | TextKey::DuplicateLines | TextKey::MoveLinesUp
+                | TextKey::MoveLinesDown | TextKey::DeleteLines | TextKey::SelectLines | TextKey::RowEdge | TextKey::Page);
+            for (action, chord) in ctx.input_mut(|i| pressed_shortcuts(i, TE_SHORTCUTS, format)) {
+                match action {
+                    TextKey::Save => self.pending_error = self.save().err(),
+                    TextKey::SaveAs => self.pending_error = self.save_as().err(),
+                    TextKey::Bold => self.format_bold(),
+                    TextKey::Italic => self.format_italic(),
+                    TextKey::Underline => self.format_underline(),
+                    TextKey::Strikethrough => self.format_strikethrough(),
+                    TextKey::Code => self.format_code(),
+                    TextKey::Highlight => self.format_highlight(),
+                    TextKey::Heading => self.format_heading(chord + 1),
+                    TextKey::Blockquote => self.format_blockquote(),
+                    TextKey::Checklist => self.insert_checklist_item(),
+                    TextKey::ToggleBookmark => self.toggle_bookmark(),
+                    TextKey::NextBookmark => self.jump_bookmark(true),
+                    TextKey::PrevBookmark => self.jump_bookmark(false),
+                    _ => {}
+                }
             }
-            if i.consume_key(egui::Modifiers::CTRL, egui::Key::B) { self.format_bold(); }
-            if i.consume_key(egui::Modifiers::CTRL, egui::Key::I) { self.format_italic(); }
-            if i.consume_key(egui::Modifiers::CTRL, egui::Key::U) { self.format_underline(); }
-            if i.consume_key(egui::Modifiers::CTRL, egui::Key::E) { self.format_code(); }
-            if i.consume_key(egui::Modifiers::CTRL | egui::Modifiers::SHIFT, egui::Key::H) { self.format_highlight(); }
-            if i.consume_key(egui::Modifiers::CTRL | egui::Modifiers::SHIFT, egui::Key::A) { let _ = self.save_as(); }
-            if i.consume_key(egui::Modifiers::CTRL, egui::Key::Num1) { self.format_heading(1); }
-            if i.consume_key(egui::Modifiers::CTRL, egui::Key::Num2) { self.format_heading(2); }
-            if i.consume_key(egui::Modifiers::CTRL, egui::Key::Num3) { self.format_heading(3); }
-            if i.consume_key(egui::Modifiers::CTRL, egui::Key::Num4) { self.format_heading(4); }
-            if i.consume_key(egui::Modifiers::CTRL | egui::Modifiers::SHIFT, egui::Key::Q) { self.format_blockquote(); }
-            if i.consume_key(egui::Modifiers::CTRL | egui::Modifiers::SHIFT, egui::Key::L) { self.insert_checklist_item(); }
-        });
+        }
 
         if self.show_word_count_modal {
             let (bg, border, text, muted) = if ui.visuals().dark_mode {
@@ -263,7 +295,11 @@ impl TextEditor {
                             });
                         });
                     };
-                    row(ui, "Words", self.modal_word_count);
+                    row(ui, "Words", self.modal_word_count.words);
+                    if self.word_count_options.markdown {
+                        ui.add_space(4.0);
+                        row(ui, "Words in code", self.modal_word_count.code);
+                    }
                     ui.add_space(4.0);
                     row(ui, "Characters", self.modal_char_count);
                     ui.add_space(4.0);
@@ -273,6 +309,15 @@ impl TextEditor {
                     ui.add_space(8.0);
                     ui.checkbox(&mut self.show_word_count_in_info,
                         egui::RichText::new("Display word count in file information").size(12.0).color(text));
+                    let before = self.word_count_options;
+                    let mut per_char = before.cjk == CjkCounting::PerCharacter;
+                    ui.checkbox(&mut per_char, egui::RichText::new("Count each Chinese or Japanese character as a word").size(12.0).color(text));
+                    self.word_count_options.cjk = if per_char { CjkCounting::PerCharacter } else { CjkCounting::PerSegment };
+                    ui.checkbox(&mut self.word_count_options.markdown, egui::RichText::new("Skip Markdown syntax and count code separately").size(12.0).color(text));
+                    if self.word_count_options != before {
+                        self.modal_word_count = self.count_words();
+                        self.word_count_display_version = self.content_version;
+                    }
                 });
             if let Some(r) = win_resp {
                 let clicked_outside = ctx.input(|i| {
@@ -284,6 +329,86 @@ impl TextEditor {
         }
     }
 
+    fn render_large_file_state(&mut self, ui: &mut egui::Ui, ctx: &egui::Context) {
+        let (bg, border, text, muted) = if ui.visuals().dark_mode {
+            (ColorPalette::ZINC_900, ColorPalette::ZINC_700, ColorPalette::SLATE_200, ColorPalette::ZINC_400)
+        } else {
+            (egui::Color32::WHITE, ColorPalette::GRAY_200, ColorPalette::GRAY_800, ColorPalette::GRAY_500)
+        };
+        let name = self.get_file_name();
+        match &self.large_file {
+            Some(LargeFile::Prompt { size }) => {
+                let size = *size;
+                let (mut full, mut preview, mut cancel) = (false, false, false);
+                crate::style::draw_modal_overlay(ctx, "large_file_overlay", 160);
+                egui::Window::new("Large File")
+                    .collapsible(false).resizable(false)
+                    .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+                    .frame(egui::Frame::new().fill(bg).stroke(egui::Stroke::new(1.0, border)).corner_radius(10.0).inner_margin(24.0))
+                    .order(egui::Order::Tooltip)
+                    .show(ctx, |ui| {
+                        ui.set_max_width(380.0);
+                        ui.label(egui::RichText::new(format!("{} is {}.", name, megabytes(size))).size(14.0).strong().color(text));
+                        ui.add_space(4.0);
+                        ui.label(egui::RichText::new("Opening the whole file can take a while and use a lot of memory. A preview opens only the beginning, read-only.").size(12.5).color(muted));
+                        ui.add_space(12.0);
+                        ui.horizontal(|ui| {
+                            if ui.button("Open Entire File").on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { full = true; }
+                            if ui.button(format!("Preview First {}", megabytes(PREVIEW_BYTES))).on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { preview = true; }
+                            if ui.button("Cancel").on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { cancel = true; }
+                        });
+                    });
+                if full || preview { self.start_large_load(ctx, preview); }
+                if cancel || ctx.input(|i| i.key_pressed(egui::Key::Escape)) { self.close_requested = true; }
+            }
+            Some(LargeFile::Loading { read, total, partial, .. }) => {
+                let done = read.load(std::sync::atomic::Ordering::Relaxed);
+                let frac = if *total == 0 { 0.0 } else { done as f32 / *total as f32 };
+                let what = if *partial { "Loading preview of" } else { "Loading" };
+                ui.vertical_centered(|ui| {
+                    ui.add_space(ui.available_height() / 3.0);
+                    ui.label(egui::RichText::new(format!("{} {}...", what, name)).size(14.0).color(text));
+                    ui.add_space(8.0);
+                    ui.add(egui::ProgressBar::new(frac).desired_width(320.0).text(format!("{} of {}", megabytes(done), megabytes(*total))));
+                });
+                ctx.request_repaint_after(std::time::Duration::from_millis(100));
+            }
+            None => {}
+        }
+    }
+
+    fn render_markdown_suggestion(&mut self, ui: &mut egui::Ui) {
+        let (bg, text) = if ui.visuals().dark_mode { (egui::Color32::from_rgb(24, 38, 64), ColorPalette::ZINC_100) } else { (ColorPalette::BLUE_50, ColorPalette::GRAY_900) };
+        egui::Frame::new().fill(bg).stroke(egui::Stroke::new(1.0, ColorPalette::BLUE_500)).corner_radius(6.0).inner_margin(egui::Margin::symmetric(10, 6)).show(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(egui::RichText::new("Looks like Markdown — switch view?").size(12.5).color(text));
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    if ui.button("Keep Plain Text").on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { self.dismiss_markdown_suggestion(); }
+                    if ui.button("Markdown View").on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { self.set_view_mode(ViewMode::Markdown); }
+                });
+            });
+        });
+        ui.add_space(4.0);
+    }
+
+    fn render_preview(&mut self, ui: &mut egui::Ui, ctx: &egui::Context, total: u64) {
+        let (bg, text) = if ui.visuals().dark_mode { (egui::Color32::from_rgb(58, 46, 20), ColorPalette::ZINC_100) } else { (ColorPalette::AMBER_50, ColorPalette::GRAY_900) };
+        egui::Frame::new().fill(bg).stroke(egui::Stroke::new(1.0, ColorPalette::AMBER_500)).corner_radius(6.0).inner_margin(egui::Margin::symmetric(10, 6)).show(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(egui::RichText::new(format!("Read-only preview: showing the first {} of {}. Saving is disabled.", megabytes(PREVIEW_BYTES), megabytes(total))).size(12.5).color(text));
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    if ui.button("Load Entire File").on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { self.start_large_load(ctx, false); }
+                });
+            });
+        });
+        ui.add_space(4.0);
+        let font_id = egui::FontId::new(self.font_size, self.font_family.clone());
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            let mut content: &str = &self.content;
+            ui.add_sized(ui.available_size(), egui::TextEdit::multiline(&mut content).font(font_id).frame(false).desired_width(f32::INFINITY));
+        });
+    }
+
     pub(super) fn markdown_editable(&mut self, ui: &mut egui::Ui, ctx: &egui::Context) {
         use egui::{pos2, vec2, Rect, Sense};
         let avail_rect = ui.available_rect_before_wrap();
@@ -309,7 +434,8 @@ impl TextEditor {
             let is_dark_mode: bool = ui.visuals().dark_mode;
             let available_width: f32 = ui.available_width();
             let top_padding: f32 = 2.0_f32;
-            let wrap_width: f32 = available_width.max(10.0);
+            let gutter_margin: egui::Margin = self.gutter_margin();
+            let wrap_width: f32 = (available_width - (gutter_margin.left - 4) as f32).max(10.0);
 
             let mut lines: Vec<&str> = Vec::new();
             let mut code_line_flags: Vec<bool> = Vec::new();
@@ -640,8 +766,11 @@ impl TextEditor {
                 ui.fonts_mut(|f: &mut egui::epaint::FontsView<'_>| f.layout_job(job))
             };
 
-            let text_edit: egui::TextEdit<'_> = egui::TextEdit::multiline(&mut self.content).layouter(&mut layouter).lock_focus(true).frame(false);
-            let response: egui::Response = ui.put(outer_rect, text_edit);
+            let text_edit: egui::TextEdit<'_> = egui::TextEdit::multiline(&mut self.content).layouter(&mut layouter).lock_focus(true).frame(false).margin(gutter_margin);
+            let builder = egui::UiBuilder::new().max_rect(outer_rect).layout(egui::Layout::centered_and_justified(egui::Direction::TopDown));
+            let highlight = ui.painter().add(egui::Shape::Noop);
+            let output = ui.scope_builder(builder, |ui| text_edit.show(ui)).inner;
+            let response: egui::Response = output.response.clone();
             if response.clicked() && ctx.input(|i: &egui::InputState| i.modifiers.ctrl || i.modifiers.command) {
                 if let Some(cursor_range) = self.last_cursor_range {
                     let chars: Vec<char> = self.content.chars().collect();
@@ -656,19 +785,121 @@ impl TextEditor {
                 self.try_toggle_checkbox();
             }
 
-            if let Some(new_pos) = self.pending_cursor_pos.take() {
-                if let Some(mut state) = egui::TextEdit::load_state(ctx, response.id) {
-                    let ccursor: egui::text::CCursor = egui::text::CCursor::new(new_pos);
-                    state.cursor.set_char_range(Some(egui::text::CCursorRange::one(ccursor)));
-                    state.store(ctx, response.id);
+            self.sync_cursor_state(ctx, response.id);
+            if response.changed() { self.text_changed(); }
+            self.remember_rows(ui, &output);
+            self.update_completion(response.changed());
+            self.render_completion_popup(ctx, &output);
+            let typewriter = self.finish_focus_aids(ui, &output, highlight);
+            self.finish_bookmark_frame(ui, &output).or(typewriter)
+        });
+        self.scroll_offset = sa_out.inner.unwrap_or(sa_out.state.offset.y);
+    }
+
+    fn gutter_margin(&self) -> egui::Margin {
+        egui::Margin { left: if self.bookmarks.is_empty() { 4 } else { 16 }, right: 4, top: 2, bottom: 2 }
+    }
+
+    fn line_rows(galley: &egui::Galley) -> impl Iterator<Item = (usize, &egui::epaint::text::PlacedRow)> {
+        galley.rows.iter().scan((0usize, true), |(line, at_start), row| {
+            let item = at_start.then_some(*line);
+            *at_start = row.ends_with_newline;
+            if row.ends_with_newline { *line += 1; }
+            Some(item.map(|l| (l, row)))
+        }).flatten()
+    }
+
+    fn render_completion_popup(&mut self, ctx: &egui::Context, output: &egui::text_edit::TextEditOutput) {
+        let Some(c) = &self.completion else { return };
+        let caret = output.galley.pos_from_cursor(egui::text::CCursor::new(c.start));
+        let pos = output.galley_pos + caret.left_bottom().to_vec2() + egui::vec2(0.0, 2.0);
+        let font_id = egui::FontId::new((self.font_size * 0.9).max(11.0), self.font_family.clone());
+        let mut clicked = None;
+        egui::Area::new(egui::Id::new("te_completion")).order(egui::Order::Foreground).fixed_pos(pos).show(ctx, |ui| {
+            egui::Frame::popup(ui.style()).inner_margin(4.0).show(ui, |ui| {
+                ui.set_min_width(120.0);
+                for (i, item) in c.items.iter().enumerate() {
+                    let label = egui::RichText::new(item).font(font_id.clone());
+                    if ui.add(egui::Button::selectable(i == c.selected, label).frame_when_inactive(false)).clicked() { clicked = Some(i); }
                 }
+            });
+        });
+        if let Some(i) = clicked { self.accept_completion(ctx, i); }
+    }
+
+    fn finish_focus_aids(&mut self, ui: &mut egui::Ui, output: &egui::text_edit::TextEditOutput, highlight: egui::layers::ShapeIdx) -> Option<f32> {
+        let moved = output.cursor_range != self.typewriter_cursor || output.response.changed();
+        self.typewriter_cursor = output.cursor_range;
+        let range = output.cursor_range?;
+        let galley = &output.galley;
+        if self.settings.highlight_current_line && range.primary.index == range.secondary.index && !galley.rows.is_empty() {
+            let row = galley.layout_from_cursor(range.primary).row.min(galley.rows.len() - 1);
+            let (mut start, mut end) = (row, row);
+            while start > 0 && !galley.rows[start - 1].ends_with_newline { start -= 1; }
+            while end + 1 < galley.rows.len() && !galley.rows[end].ends_with_newline { end += 1; }
+            let y = (output.galley_pos.y + galley.rows[start].rect().top())..=(output.galley_pos.y + galley.rows[end].rect().bottom());
+            let tint = if ui.visuals().dark_mode { egui::Color32::from_white_alpha(10) } else { egui::Color32::from_black_alpha(10) };
+            ui.painter().set(highlight, egui::Shape::rect_filled(egui::Rect::from_x_y_ranges(output.response.rect.x_range(), y), 0.0, tint));
+        }
+        if !self.settings.typewriter_scrolling { return None; }
+        let clip = ui.clip_rect();
+        ui.add_space(clip.height() / 2.0);
+        if !moved || ui.input(|i| i.pointer.primary_down()) { return None; }
+        let caret = galley.pos_from_cursor(range.primary);
+        Some((self.scroll_offset + output.galley_pos.y + caret.center().y - clip.center().y).max(0.0))
+    }
+
+    fn finish_bookmark_frame(&mut self, ui: &mut egui::Ui, output: &egui::text_edit::TextEditOutput) -> Option<f32> {
+        if !self.bookmarks.is_empty() {
+            let color = if ui.visuals().dark_mode { ColorPalette::BLUE_400 } else { ColorPalette::BLUE_500 };
+            for (line, row) in Self::line_rows(&output.galley) {
+                if self.bookmarks.binary_search(&line).is_err() { continue; }
+                let y = output.galley_pos.y + row.rect().center().y;
+                ui.painter().circle_filled(egui::pos2(output.galley_pos.x - 8.0, y), 3.5, color);
             }
-            if let Some(state) = egui::TextEdit::load_state(ctx, response.id) {
-                if let Some(r) = state.cursor.char_range() { self.last_cursor_range = Some(r); }
+        }
+        let line = self.pending_scroll_line.take()?;
+        let (_, row) = Self::line_rows(&output.galley).find(|(l, _)| *l == line)?;
+        ui.memory_mut(|m| m.request_focus(output.response.id));
+        let clip = ui.clip_rect();
+        Some((self.scroll_offset + output.galley_pos.y + row.rect().top() - clip.top() - clip.height() / 3.0).max(0.0))
+    }
+
+    fn render_bookmarks_panel(&mut self, ui: &mut egui::Ui) {
+        let is_dark = ui.visuals().dark_mode;
+        let muted = if is_dark { ColorPalette::ZINC_400 } else { ColorPalette::GRAY_500 };
+        let mut previews: Vec<(usize, String)> = Vec::with_capacity(self.bookmarks.len());
+        if !self.bookmarks.is_empty() {
+            for (i, text) in self.content.split('\n').enumerate() {
+                if self.bookmarks.binary_search(&i).is_ok() { previews.push((i, text.trim().chars().take(60).collect())); }
+                if previews.len() == self.bookmarks.len() { break; }
             }
-            if response.changed() { self.dirty = true; self.content_version = self.content_version.wrapping_add(1); }
+        }
+        let mut jump: Option<usize> = None;
+        egui::SidePanel::right("te_bookmarks").resizable(true).default_width(220.0).show_inside(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(egui::RichText::new("Bookmarks").strong());
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    if ui.small_button("x").on_hover_text("Close").clicked() { self.show_bookmarks_panel = false; }
+                    if ui.add_enabled(!self.bookmarks.is_empty(), egui::Button::new("Clear All").small()).clicked() { self.clear_bookmarks(); }
+                });
+            });
+            ui.separator();
+            if previews.is_empty() {
+                ui.label(egui::RichText::new("No bookmarks. Press Ctrl+F2 to bookmark the current line.").size(12.0).color(muted).italics());
+                return;
+            }
+            egui::ScrollArea::vertical().auto_shrink([false, false]).show(ui, |ui| {
+                for (line, text) in &previews {
+                    ui.horizontal(|ui| {
+                        ui.label(egui::RichText::new(format!("{:>4}", line + 1)).monospace().color(muted));
+                        let label = if text.is_empty() { egui::RichText::new("(empty line)").italics().color(muted) } else { egui::RichText::new(text) };
+                        if ui.add(egui::Label::new(label).truncate().sense(egui::Sense::click())).on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { jump = Some(*line); }
+                    });
+                }
+            });
         });
-        self.scroll_offset = sa_out.state.offset.y;
+        if let Some(line) = jump { self.jump_to_line(line); }
     }
 
     fn is_table_row(line: &str) -> bool {
@@ -721,6 +952,15 @@ impl TextEditor {
         }
     }
 
+    pub fn markdown_preview_job(line: &str, font_size: f32, wrap_width: f32, is_dark_mode: bool) -> egui::text::LayoutJob {
+        let font_family = egui::FontFamily::Name("Ubuntu".into());
+        let mut job = egui::text::LayoutJob::default();
+        job.wrap.max_width = wrap_width;
+        if line.trim().is_empty() { job.append(line, 0.0, Self::default_format_static(font_size, &font_family, is_dark_mode)); }
+        else { Self::parse_markdown_line_static(line, &mut job, font_size, &font_family, None, 0, is_dark_mode); }
+        job
+    }
+
     pub(super) fn parse_markdown_line_static(
         line: &str,
         job: &mut egui::text::LayoutJob,
@@ -0,0 +1,144 @@
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use crate::style::{ColorPalette, ThemeMode};
+use crate::modules::helpers::config_file;
+
+const FORMAT: &str = "universal-editor-profile";
+pub const PROFILE_VERSION: u32 = 1;
+
+pub const CATEGORIES: [(&str, &str); 5] = [
+    ("Application settings", "app_settings.json"),
+    ("Saved brushes", "brush_favorites.json"),
+    ("Color palette", "color_favorites.json"),
+    ("Export presets", "export_presets.json"),
+    ("Image tool settings", "tool_settings.json"),
+];
+
+#[derive(Serialize, Deserialize)]
+struct ProfileFile {
+    format: String,
+    version: u32,
+    #[serde(default)] app_version: String,
+    #[serde(default)] created: String,
+    #[serde(default)] entries: BTreeMap<String, serde_json::Value>,
+}
+
+pub struct ProfileImport {
+    pub path: PathBuf,
+    app_version: String,
+    created: String,
+    entries: Vec<(usize, serde_json::Value)>,
+    selected: Vec<bool>,
+}
+
+pub fn config_dir() -> PathBuf { config_file::config_dir() }
+
+pub fn export_profile(dir: &Path, path: &Path) -> io::Result<usize> {
+    let entries: BTreeMap<String, serde_json::Value> = CATEGORIES.iter().filter_map(|(_, file)| {
+        let text = String::from_utf8(config_file::read(&dir.join(file)).ok()?).ok()?;
+        Some((file.to_string(), serde_json::from_str(&text).ok()?))
+    }).collect();
+    let count = entries.len();
+    let profile = ProfileFile {
+        format: FORMAT.into(), version: PROFILE_VERSION, app_version: env!("CARGO_PKG_VERSION").into(),
+        created: chrono::Local::now().format("%Y-%m-%d %H:%M").to_string(), entries,
+    };
+    config_file::save_json(path, &profile)?;
+    Ok(count)
+}
+
+pub fn read_profile(path: &Path) -> Result<ProfileImport, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+    let profile: ProfileFile = serde_json::from_str(&text).map_err(|_| "This file is not a Universal Editor profile".to_string())?;
+    if profile.format != FORMAT { return Err("This file is not a Universal Editor profile".into()); }
+    if profile.version > PROFILE_VERSION {
+        return Err(format!("This profile was exported by a newer Universal Editor ({}, profile format {}). Update to import it; nothing was changed.", profile.app_version, profile.version));
+    }
+    let mut entries = Vec::new();
+    for (name, value) in profile.entries {
+        let Some(idx) = CATEGORIES.iter().position(|(_, file)| *file == name) else { continue };
+        if !value.is_object() { return Err(format!("The profile's {} are damaged; nothing was changed.", CATEGORIES[idx].0.to_lowercase())); }
+        entries.push((idx, value));
+    }
+    if entries.is_empty() { return Err("This profile contains no settings to import".into()); }
+    entries.sort_by_key(|(idx, _)| *idx);
+    let selected = vec![true; entries.len()];
+    Ok(ProfileImport { path: path.to_path_buf(), app_version: profile.app_version, created: profile.created, entries, selected })
+}
+
+impl ProfileImport {
+    pub fn apply(&self, dir: &Path) -> io::Result<Vec<&'static str>> {
+        let mut applied = Vec::new();
+        for ((idx, value), _) in self.entries.iter().zip(&self.selected).filter(|(_, s)| **s) {
+            let (label, file) = CATEGORIES[*idx];
+            config_file::save_json(&dir.join(file), value)?;
+            applied.push(label);
+        }
+        Ok(applied)
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context, theme: ThemeMode, dir: &Path) -> Option<bool> {
+        let is_dark = matches!(theme, ThemeMode::Dark);
+        let (muted, text_col) = if is_dark { (ColorPalette::ZINC_500, ColorPalette::ZINC_100) } else { (ColorPalette::STONE_500, ColorPalette::STONE_900) };
+        let mut outcome = None;
+        egui::Window::new("Import Profile")
+            .collapsible(false).resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .fixed_size(egui::vec2(380.0, 0.0))
+            .show(ctx, |ui| {
+                let name = self.path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+                ui.label(egui::RichText::new(name).size(13.0).color(text_col).strong());
+                let origin = if self.app_version.is_empty() { self.created.clone() } else { format!("Version {}  ·  {}", self.app_version, self.created) };
+                ui.label(egui::RichText::new(origin).size(11.0).color(muted));
+                ui.add_space(8.0);
+                for ((idx, _), on) in self.entries.iter().zip(self.selected.iter_mut()) {
+                    let (label, file) = CATEGORIES[*idx];
+                    ui.horizontal(|ui| {
+                        ui.checkbox(on, egui::RichText::new(label).size(13.0).color(text_col));
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                            let note = if dir.join(file).exists() { "replaces yours" } else { "new" };
+                            ui.label(egui::RichText::new(note).size(11.0).color(muted));
+                        });
+                    });
+                }
+                ui.add_space(6.0);
+                ui.label(egui::RichText::new("Keyboard shortcuts are built in and already match on every machine.").size(11.0).color(muted).italics());
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(self.selected.contains(&true), egui::Button::new("Import")).clicked() { outcome = Some(true); }
+                    if ui.button("Cancel").clicked() { outcome = Some(false); }
+                });
+            });
+        outcome
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profile_round_trips_selected_categories_and_refuses_newer_versions() {
+        let root = std::env::temp_dir().join(format!("ue_profile_{}", std::process::id()));
+        let (src, dst) = (root.join("src"), root.join("dst"));
+        std::fs::create_dir_all(&src).unwrap();
+        std::fs::create_dir_all(&dst).unwrap();
+        std::fs::write(src.join("app_settings.json"), r#"{"theme_preference":"Dark"}"#).unwrap();
+        std::fs::write(src.join("color_favorites.json"), r#"{"colors":[]}"#).unwrap();
+        std::fs::write(dst.join("color_favorites.json"), r#"{"colors":[{"r":1,"g":2,"b":3,"a":255}]}"#).unwrap();
+        let bundle = root.join("profile.json");
+        assert_eq!(export_profile(&src, &bundle).unwrap(), 2);
+        let mut import = read_profile(&bundle).unwrap();
+        import.selected[1] = false;
+        assert_eq!(import.apply(&dst).unwrap(), vec!["Application settings"]);
+        assert!(std::fs::read_to_string(dst.join("app_settings.json")).unwrap().contains("Dark"));
+        assert!(std::fs::read_to_string(dst.join("color_favorites.json")).unwrap().contains("255"));
+        let newer = std::fs::read_to_string(&bundle).unwrap().replace("\"version\": 1", "\"version\": 99");
+        std::fs::write(&bundle, newer).unwrap();
+        assert!(read_profile(&bundle).err().unwrap().contains("newer"));
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
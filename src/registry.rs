@@ -1,5 +1,6 @@
 use eframe::egui::Color32;
 use crate::style::ColorPalette;
+use crate::modules::KeyBinding;
 
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub enum CreateModule { TextEditor, ImageEditor, JsonEditor, ImageConverter, DataConverter, ArchiveConverter, DocEditor }
@@ -11,6 +12,7 @@ pub struct ScreenDef {
     pub color: Color32,
     pub sidebar_letter: &'static str,
     pub accepted_extensions: &'static [&'static str],
+    pub key_bindings: fn() -> Vec<KeyBinding>,
     pub create: CreateModule,
 }
 
@@ -31,6 +33,7 @@ pub static SCREENS: &[ScreenDef] = &[
         color: ColorPalette::BLUE_500,
         sidebar_letter: "T",
         accepted_extensions: &["txt", "md"],
+        key_bindings: crate::modules::text_editor::key_bindings,
         create: CreateModule::TextEditor,
     },
     ScreenDef {
@@ -40,6 +43,7 @@ pub static SCREENS: &[ScreenDef] = &[
         color: ColorPalette::PURPLE_500,
         sidebar_letter: "I",
         accepted_extensions: &["jpg", "jpeg", "png", "webp", "bmp", "tiff", "tif", "gif", "ico"],
+        key_bindings: crate::modules::image_editor::key_bindings,
         create: CreateModule::ImageEditor,
     },
     ScreenDef {
@@ -49,6 +53,7 @@ pub static SCREENS: &[ScreenDef] = &[
         color: ColorPalette::AMBER_500,
         sidebar_letter: "J",
         accepted_extensions: &["json"],
+        key_bindings: crate::modules::json_editor::key_bindings,
         create: CreateModule::JsonEditor,
     },
     ScreenDef {
@@ -58,6 +63,7 @@ pub static SCREENS: &[ScreenDef] = &[
         color: ColorPalette::GREEN_500,
         sidebar_letter: "D",
         accepted_extensions: &["docx", "doc", "odt"],
+        key_bindings: crate::modules::document_editor::key_bindings,
         create: CreateModule::DocEditor,
     },
 ];
@@ -28,13 +28,39 @@ pub fn register_fonts(ctx: &egui::Context) {
         ("GoogleSans", FONT_GS_REG), ("GoogleSans-Bold", FONT_GS_BLD), ("GoogleSans-Italic", FONT_GS_ITL), ("GoogleSans-BoldItalic", FONT_GS_BLD_ITL),
         ("OpenSans", FONT_OS_REG), ("OpenSans-Bold", FONT_OS_BLD), ("OpenSans-Italic", FONT_OS_ITL), ("OpenSans-BoldItalic", FONT_OS_BLD_ITL),
     ];
+    let emoji_fallback: Vec<String> = EMOJI_FALLBACK_FONTS.iter().filter(|f| fonts.font_data.contains_key(**f)).map(|f| f.to_string()).collect();
     for (name, bytes) in entries {
         fonts.font_data.insert(name.to_string(), egui::FontData::from_static(bytes).into());
-        fonts.families.insert(egui::FontFamily::Name((*name).into()), vec![name.to_string()]);
+        fonts.families.insert(egui::FontFamily::Name((*name).into()), std::iter::once(name.to_string()).chain(emoji_fallback.iter().cloned()).collect());
     }
     ctx.set_fonts(fonts);
 }
 
+const EMOJI_FALLBACK_FONTS: &[&str] = &["NotoEmoji-Regular", "emoji-icon-font"];
+
+const SYSTEM_EMOJI_FONT_PATHS: &[&str] = &[
+    "/usr/share/fonts/truetype/noto/NotoColorEmoji.ttf",
+    "/usr/share/fonts/noto/NotoColorEmoji.ttf",
+    "/usr/share/fonts/google-noto-emoji/NotoColorEmoji.ttf",
+    "/usr/share/fonts/noto-emoji/NotoColorEmoji.ttf",
+    "/usr/share/fonts/TTF/NotoColorEmoji.ttf",
+    "C:\\Windows\\Fonts\\seguiemj.ttf",
+    "/System/Library/Fonts/Apple Color Emoji.ttc",
+];
+
+pub(crate) fn emoji_font_data() -> &'static [&'static [u8]] {
+    static DATA: std::sync::OnceLock<Vec<&'static [u8]>> = std::sync::OnceLock::new();
+    DATA.get_or_init(|| {
+        let system = SYSTEM_EMOJI_FONT_PATHS.iter().find_map(|p| std::fs::read(p).ok()).map(|b| &*Box::leak(b.into_boxed_slice()));
+        let defaults = egui::FontDefinitions::default();
+        let bundled = EMOJI_FALLBACK_FONTS.iter().filter_map(|f| defaults.font_data.get(*f)).map(|d| match &d.font {
+            std::borrow::Cow::Borrowed(b) => *b,
+            std::borrow::Cow::Owned(v) => &*Box::leak(v.clone().into_boxed_slice()),
+        });
+        system.into_iter().chain(bundled).collect()
+    })
+}
+
 pub struct ColorPalette;
 #[allow(dead_code)]
 impl ColorPalette {
@@ -169,6 +195,17 @@ pub fn apply_theme(ctx: &egui::Context, theme: ThemeMode) {
     ctx.set_style(style);
 }
 
+pub fn apply_focus_outline(ctx: &egui::Context, enabled: bool) {
+    let mut style = (*ctx.style()).clone();
+    style.visuals.widgets.active.expansion = if enabled { 2.0 } else { 1.0 };
+    if enabled {
+        let outline = if style.visuals.dark_mode { ColorPalette::AMBER_400 } else { ColorPalette::BLUE_600 };
+        style.visuals.widgets.active.bg_stroke = egui::Stroke::new(3.0, outline);
+        style.visuals.selection.stroke = egui::Stroke::new(2.5, outline);
+    }
+    ctx.set_style(style);
+}
+
 fn apply_dark_theme(style: &mut egui::Style) {
     style.visuals.dark_mode = true;
     style.visuals.panel_fill = ColorPalette::ZINC_900;
@@ -0,0 +1,85 @@
+//! Job progress on the OS taskbar: a progress bar on the Windows taskbar button and a percentage badge on the
+//! macOS dock icon. Other desktops have no common equivalent, so there the in-app task indicator is all there is.
+
+#[derive(Default)]
+pub struct TaskbarProgress { shown: Option<u32> }
+
+impl TaskbarProgress {
+    /// Mirrors `progress` (0 to 1, `None` when idle) onto the taskbar, going to the OS only when the whole percent changes.
+    pub fn update(&mut self, frame: &eframe::Frame, progress: Option<f32>) {
+        if let Some(percent) = self.step(progress) { platform::set(frame, percent); }
+    }
+
+    fn step(&mut self, progress: Option<f32>) -> Option<Option<u32>> {
+        let percent = progress.map(|p| (p.clamp(0.0, 1.0) * 100.0).round() as u32);
+        if percent == self.shown { return None; }
+        self.shown = percent;
+        Some(percent)
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use raw_window_handle::{HasWindowHandle, RawWindowHandle};
+    use windows::Win32::Foundation::HWND;
+    use windows::Win32::System::Com::{CoCreateInstance, CoInitializeEx, CLSCTX_INPROC_SERVER, COINIT_APARTMENTTHREADED};
+    use windows::Win32::UI::Shell::{ITaskbarList3, TaskbarList, TBPF_NOPROGRESS, TBPF_NORMAL};
+
+    thread_local! {
+        // SAFETY: COM is set up for the UI thread this runs on; a thread winit already initialised just gets S_FALSE.
+        static TASKBAR: Option<ITaskbarList3> = unsafe {
+            let _ = CoInitializeEx(None, COINIT_APARTMENTTHREADED);
+            CoCreateInstance(&TaskbarList, None, CLSCTX_INPROC_SERVER).ok()
+        };
+    }
+
+    pub fn set(frame: &eframe::Frame, percent: Option<u32>) {
+        let Ok(handle) = frame.window_handle() else { return };
+        let RawWindowHandle::Win32(h) = handle.as_raw() else { return };
+        let hwnd = HWND(h.hwnd.get() as *mut _);
+        TASKBAR.with(|taskbar| {
+            let Some(taskbar) = taskbar else { return };
+            // SAFETY: `hwnd` is the live main window for the duration of this frame.
+            unsafe {
+                match percent {
+                    Some(p) => { let _ = taskbar.SetProgressState(hwnd, TBPF_NORMAL); let _ = taskbar.SetProgressValue(hwnd, p as u64, 100); }
+                    None => { let _ = taskbar.SetProgressState(hwnd, TBPF_NOPROGRESS); }
+                }
+            }
+        });
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use objc2::MainThreadMarker;
+    use objc2_app_kit::NSApplication;
+    use objc2_foundation::NSString;
+
+    pub fn set(_frame: &eframe::Frame, percent: Option<u32>) {
+        let Some(mtm) = MainThreadMarker::new() else { return };
+        let label = percent.map(|p| NSString::from_str(&crate::i18n::format_percent(p as f64 / 100.0)));
+        NSApplication::sharedApplication(mtm).dockTile().setBadgeLabel(label.as_deref());
+    }
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+mod platform {
+    pub fn set(_frame: &eframe::Frame, _percent: Option<u32>) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_taskbar_is_only_touched_when_the_whole_percent_changes() {
+        let mut bar = TaskbarProgress::default();
+        assert_eq!(bar.step(None), None);
+        assert_eq!(bar.step(Some(0.421)), Some(Some(42)));
+        assert_eq!(bar.step(Some(0.419)), None);
+        assert_eq!(bar.step(Some(1.5)), Some(Some(100)));
+        assert_eq!(bar.step(None), Some(None));
+        assert_eq!(bar.step(None), None);
+    }
+}
@@ -0,0 +1,223 @@
+use eframe::egui;
+use std::sync::{Arc, Condvar, Mutex, OnceLock};
+use std::sync::atomic::{AtomicBool, AtomicU8, AtomicU32, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use crate::i18n::{tr, tr_args};
+use crate::style::{ColorPalette, ThemeMode};
+
+pub(crate) const MAX_RUNNING: usize = 2;
+const TOAST_AFTER: Duration = Duration::from_secs(2);
+const STALL_AFTER: Duration = Duration::from_secs(4);
+const ETA_SMOOTHING: f64 = 0.15;
+const ETA_MIN_PROGRESS: f32 = 0.05;
+const QUEUED: u8 = 0;
+const RUNNING: u8 = 1;
+const DONE: u8 = 2;
+
+static TASKS: Mutex<Vec<Task>> = Mutex::new(Vec::new());
+static TOASTS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+static RUNNING_SLOTS: Mutex<usize> = Mutex::new(0);
+static SLOT_FREED: Condvar = Condvar::new();
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+static REPAINT: OnceLock<egui::Context> = OnceLock::new();
+
+struct TaskState {
+    id: u64, name: String, cancellable: bool,
+    phase: AtomicU8, progress: AtomicU32, cancel: AtomicBool,
+    started: Mutex<Instant>, detail: Mutex<Option<&'static str>>, eta: Mutex<Eta>,
+}
+
+struct Eta { last: (f32, Instant), rate: Option<f64>, advanced: Instant }
+
+impl Eta {
+    fn new(now: Instant) -> Self { Self { last: (0.0, now), rate: None, advanced: now } }
+
+    fn sample(&mut self, p: f32, now: Instant) {
+        let (lp, lt) = self.last;
+        if p <= lp { return; }
+        let dt = now.duration_since(lt).as_secs_f64();
+        if dt > 0.0 {
+            let rate = (p - lp) as f64 / dt;
+            self.rate = Some(self.rate.map_or(rate, |old| old + ETA_SMOOTHING * (rate - old)));
+        }
+        self.last = (p, now);
+        self.advanced = now;
+    }
+
+    fn remaining(&self, now: Instant) -> Option<Duration> {
+        let (p, t) = self.last;
+        let rate = self.rate.filter(|r| *r > 0.0 && p >= ETA_MIN_PROGRESS)?;
+        Some(Duration::from_secs_f64(((1.0 - p) as f64 / rate - now.duration_since(t).as_secs_f64()).max(0.0)))
+    }
+
+    fn stalled(&self, now: Instant) -> bool { now.duration_since(self.advanced) >= STALL_AFTER }
+}
+
+#[derive(Clone)]
+pub struct Task(Arc<TaskState>);
+
+impl Task {
+    fn register(name: String, cancellable: bool, phase: u8) -> Self {
+        let task = Task(Arc::new(TaskState {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed), name, cancellable,
+            phase: AtomicU8::new(phase), progress: AtomicU32::new(0f32.to_bits()), cancel: AtomicBool::new(false),
+            started: Mutex::new(Instant::now()), detail: Mutex::new(None), eta: Mutex::new(Eta::new(Instant::now())),
+        }));
+        lock(&TASKS).push(task.clone());
+        repaint();
+        task
+    }
+
+    pub fn name(&self) -> &str { &self.0.name }
+    pub fn progress(&self, p: f32) {
+        let p = p.clamp(0.0, 1.0);
+        self.0.progress.store(p.to_bits(), Ordering::Relaxed);
+        lock(&self.0.eta).sample(p, Instant::now());
+    }
+    pub fn fraction(&self) -> f32 { f32::from_bits(self.0.progress.load(Ordering::Relaxed)) }
+    pub fn set_detail(&self, detail: Option<&'static str>) { *lock(&self.0.detail) = detail; }
+    pub fn detail(&self) -> Option<&'static str> { *lock(&self.0.detail) }
+    pub fn cancel(&self) { self.0.cancel.store(true, Ordering::Relaxed); SLOT_FREED.notify_all(); }
+    pub fn cancelled(&self) -> bool { self.0.cancel.load(Ordering::Relaxed) }
+    pub fn cancel_flag(&self) -> &AtomicBool { &self.0.cancel }
+    pub fn is_queued(&self) -> bool { self.0.phase.load(Ordering::Relaxed) == QUEUED }
+    pub fn elapsed(&self) -> Duration { if self.is_queued() { Duration::ZERO } else { lock(&self.0.started).elapsed() } }
+    pub fn eta(&self) -> Option<Duration> { if self.is_queued() { None } else { lock(&self.0.eta).remaining(Instant::now()) } }
+    pub fn stalled(&self) -> bool { !self.is_queued() && lock(&self.0.eta).stalled(Instant::now()) }
+    pub fn status(&self) -> String {
+        let elapsed = format_elapsed(self.elapsed());
+        if self.stalled() { tr_args("{elapsed} · still working…", &[("elapsed", &elapsed)]) }
+        else { self.eta().map_or(elapsed.clone(), |eta| tr_args("{elapsed} · about {eta} left", &[("elapsed", &elapsed), ("eta", &format_elapsed(eta))])) }
+    }
+
+    pub fn finish(&self, toast: Option<String>) {
+        if self.0.phase.swap(DONE, Ordering::Relaxed) == DONE { return; }
+        lock(&TASKS).retain(|t| t.0.id != self.0.id);
+        let toast = toast.or_else(|| (!self.cancelled() && self.elapsed() >= TOAST_AFTER).then(|| tr_args("{task} finished in {elapsed}", &[("task", &tr(&self.0.name)), ("elapsed", &format_elapsed(self.elapsed()))])));
+        if let Some(msg) = toast { lock(&TOASTS).push(msg); }
+        repaint();
+    }
+}
+
+struct Slot(Task);
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.finish(None);
+        *lock(&RUNNING_SLOTS) -= 1;
+        SLOT_FREED.notify_all();
+    }
+}
+
+fn lock<T>(m: &Mutex<T>) -> std::sync::MutexGuard<'_, T> { m.lock().unwrap_or_else(|e| e.into_inner()) }
+
+fn repaint() { if let Some(ctx) = REPAINT.get() { ctx.request_repaint(); } }
+
+pub fn init(ctx: &egui::Context) { let _ = REPAINT.set(ctx.clone()); }
+
+pub fn submit(name: impl Into<String>, cancellable: bool) -> Task { Task::register(name.into(), cancellable, RUNNING) }
+
+/// Runs `work` on its own thread once one of the running slots is free. A task cancelled while it waits still runs
+/// `work`, without taking a slot and with `cancelled()` already true, so the job can report that it stopped.
+pub fn spawn(name: impl Into<String>, cancellable: bool, work: impl FnOnce(&Task) + Send + 'static) -> Task {
+    let task = Task::register(name.into(), cancellable, QUEUED);
+    let worker = task.clone();
+    std::thread::spawn(move || {
+        let mut running = lock(&RUNNING_SLOTS);
+        while *running >= MAX_RUNNING && !worker.cancelled() { running = SLOT_FREED.wait_timeout(running, Duration::from_millis(200)).unwrap_or_else(|e| e.into_inner()).0; }
+        if worker.cancelled() {
+            drop(running);
+            work(&worker);
+            worker.finish(None);
+            return;
+        }
+        *running += 1;
+        drop(running);
+        let slot = Slot(worker);
+        *lock(&slot.0.0.started) = Instant::now();
+        *lock(&slot.0.0.eta) = Eta::new(Instant::now());
+        slot.0.0.phase.store(RUNNING, Ordering::Relaxed);
+        repaint();
+        work(&slot.0);
+    });
+    task
+}
+
+pub fn active() -> Vec<Task> { lock(&TASKS).clone() }
+
+pub fn take_toasts() -> Vec<String> { std::mem::take(&mut *lock(&TOASTS)) }
+
+pub fn format_elapsed(d: Duration) -> String {
+    let s = d.as_secs();
+    if s < 60 { format!("{}s", s) } else { format!("{}:{:02}", s / 60, s % 60) }
+}
+
+pub fn indicator(ui: &mut egui::Ui, theme: ThemeMode) {
+    let tasks = active();
+    if tasks.is_empty() { return; }
+    ui.ctx().request_repaint_after(Duration::from_millis(250));
+    let queued = tasks.iter().filter(|t| t.is_queued()).count();
+    let running = (tasks.len() - queued).to_string();
+    let label = if queued == 0 { tr_args("⟳ {running} running", &[("running", &running)]) }
+        else { tr_args("⟳ {running} running · {queued} queued", &[("running", &running), ("queued", &queued.to_string())]) };
+    let muted = if matches!(theme, ThemeMode::Dark) { ColorPalette::ZINC_400 } else { ColorPalette::ZINC_600 };
+    ui.menu_button(egui::RichText::new(label).size(12.0).color(ColorPalette::BLUE_500), |ui| {
+        ui.set_min_width(300.0);
+        for task in &tasks {
+            ui.horizontal(|ui| {
+                ui.label(egui::RichText::new(task.name()).size(13.0).strong());
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    if task.0.cancellable && !task.cancelled() && ui.small_button(tr("Cancel")).on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { task.cancel(); }
+                    let status = if task.cancelled() { tr("Cancelling...") } else if task.is_queued() { tr("Queued") } else { task.status() };
+                    ui.label(egui::RichText::new(status).size(11.0).color(muted));
+                });
+            });
+            ui.add(egui::ProgressBar::new(task.fraction()).desired_height(6.0).animate(!task.is_queued() && task.fraction() == 0.0));
+            if let Some(detail) = task.detail() { ui.label(egui::RichText::new(detail).size(11.0).color(muted)); }
+            ui.add_space(4.0);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eta_smooths_the_progress_rate_and_notices_stalls() {
+        let t0 = Instant::now();
+        let at = |s: f64| t0 + Duration::from_secs_f64(s);
+        let mut eta = Eta::new(t0);
+        eta.sample(0.01, at(0.1));
+        assert_eq!(eta.remaining(at(0.1)), None);
+        for i in 1..=5 { eta.sample(0.1 * i as f32, at(i as f64)); }
+        let left = eta.remaining(at(5.0)).unwrap().as_secs_f64();
+        assert!((4.0..7.0).contains(&left), "{left}");
+        eta.sample(0.6, at(5.2));
+        assert!(eta.remaining(at(5.2)).unwrap().as_secs_f64() < left);
+        assert!(!eta.stalled(at(8.0)) && eta.stalled(at(9.5)));
+        eta.sample(0.6, at(9.6));
+        assert!(eta.stalled(at(9.6)));
+    }
+
+    #[test]
+    fn queued_tasks_wait_for_a_slot_and_a_cancel_while_waiting_still_reaches_the_job() {
+        let gate = Arc::new(AtomicBool::new(false));
+        let blockers: Vec<Task> = (0..MAX_RUNNING).map(|i| {
+            let gate = gate.clone();
+            spawn(format!("blocker {}", i), false, move |_| while !gate.load(Ordering::Relaxed) { std::thread::sleep(Duration::from_millis(5)); })
+        }).collect();
+        while blockers.iter().any(|t| t.is_queued()) { std::thread::sleep(Duration::from_millis(5)); }
+        let (ran, saw_cancel) = (Arc::new(AtomicBool::new(false)), Arc::new(AtomicBool::new(false)));
+        let (flag, cancel_flag) = (ran.clone(), saw_cancel.clone());
+        let waiting = spawn("waiting", true, move |t| { cancel_flag.store(t.cancelled(), Ordering::Relaxed); flag.store(true, Ordering::Relaxed); });
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(waiting.is_queued() && active().iter().any(|t| t.name() == "waiting"));
+        waiting.cancel();
+        let listed = |name: &str| active().iter().any(|t| t.name() == name);
+        while listed("waiting") { std::thread::sleep(Duration::from_millis(5)); }
+        assert!(ran.load(Ordering::Relaxed) && saw_cancel.load(Ordering::Relaxed), "the job hears about the cancel before any slot frees up");
+        gate.store(true, Ordering::Relaxed);
+        while listed("blocker 0") || listed("blocker 1") { std::thread::sleep(Duration::from_millis(5)); }
+    }
+}
@@ -0,0 +1,301 @@
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use crate::style::{ColorPalette, ThemeMode};
+use crate::modules::helpers::config_file;
+
+const INDEX_FILE: &str = "index.json";
+const TEXT_EXTS: &[&str] = &["md", "markdown", "txt"];
+const IMAGE_EXTS: &[&str] = &["png", "jpg", "jpeg", "webp", "bmp"];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CanvasGuide { pub name: String, pub x: f32, pub y: f32, pub w: f32, pub h: f32 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CanvasSpec { pub width: u32, pub height: u32, #[serde(default)] pub transparent: bool, #[serde(default)] pub guides: Vec<CanvasGuide> }
+
+pub enum TemplateContent { Text { text: String, markdown: bool }, Canvas(CanvasSpec), Image(image::DynamicImage) }
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+struct TemplateMeta { name: String, #[serde(default)] description: String }
+
+pub struct Template { pub name: String, pub description: String, pub path: PathBuf }
+
+impl Template {
+    pub fn is_text(&self) -> bool { TEXT_EXTS.contains(&ext(&self.path).as_str()) }
+
+    pub fn load(&self) -> Result<TemplateContent, String> {
+        let e = ext(&self.path);
+        if TEXT_EXTS.contains(&e.as_str()) {
+            let text = std::fs::read_to_string(&self.path).map_err(|err| format!("Could not read template '{}': {}", self.name, err))?;
+            return Ok(TemplateContent::Text { text: expand_placeholders(&text), markdown: e != "txt" });
+        }
+        if e == "json" {
+            let json = std::fs::read_to_string(&self.path).map_err(|err| format!("Could not read template '{}': {}", self.name, err))?;
+            let spec: CanvasSpec = serde_json::from_str(&json).map_err(|err| format!("Template '{}' is not a valid canvas: {}", self.name, err))?;
+            if spec.width == 0 || spec.height == 0 { return Err(format!("Template '{}' has an empty canvas", self.name)); }
+            return Ok(TemplateContent::Canvas(spec));
+        }
+        image::open(&self.path).map(TemplateContent::Image).map_err(|err| format!("Could not open template '{}': {}", self.name, err))
+    }
+}
+
+fn ext(path: &Path) -> String { path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase() }
+
+fn expand_placeholders(text: &str) -> String { text.replace("{{date}}", &chrono::Local::now().format("%Y-%m-%d").to_string()) }
+
+pub fn templates_dir() -> PathBuf { config_file::config_path("templates") }
+
+fn load_index(dir: &Path) -> HashMap<String, TemplateMeta> {
+    config_file::read(&dir.join(INDEX_FILE)).ok().and_then(|b| serde_json::from_slice(&b).ok()).unwrap_or_default()
+}
+
+fn save_index(dir: &Path, index: &HashMap<String, TemplateMeta>) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(index).map_err(|e| e.to_string())?;
+    config_file::write_atomic(&dir.join(INDEX_FILE), json.as_bytes()).map_err(|e| e.to_string())
+}
+
+const BUILTIN_TEXT: &[(&str, &str, &str, &str)] = &[
+    ("meeting-notes.md", "Meeting Notes", "Agenda, attendees, decisions and action items.",
+        "# Meeting Notes - {{date}}\n\n**Attendees:** [Names]\n\n## Agenda\n\n1. [Topic]\n2. [Topic]\n\n## Notes\n\n- \n\n## Decisions\n\n- \n\n## Action Items\n\n- [ ] [Owner] - [Task] - [Due date]\n"),
+    ("readme.md", "README", "Project readme skeleton with install and usage sections.",
+        "# [Project Name]\n\n[One sentence describing what the project does.]\n\n## Installation\n\n```\n[install command]\n```\n\n## Usage\n\n```\n[example]\n```\n\n## Contributing\n\n[How to report issues and send changes.]\n\n## License\n\n[License name]\n"),
+    ("bug-report.md", "Bug Report", "Steps to reproduce, expected and actual behaviour.",
+        "# Bug: [Short summary]\n\n**Reported:** {{date}}\n**Version:** [x.y.z]\n**Platform:** [OS]\n\n## Steps to Reproduce\n\n1. \n2. \n3. \n\n## Expected Behaviour\n\n[What should happen]\n\n## Actual Behaviour\n\n[What happens instead]\n\n## Notes\n\n[Logs, screenshots, workarounds]\n"),
+];
+
+fn builtin_canvases() -> Vec<(&'static str, &'static str, &'static str, CanvasSpec)> {
+    vec![
+        ("twitter-header.json", "Twitter Header", "1500x500 transparent banner.", CanvasSpec { width: 1500, height: 500, transparent: true, guides: Vec::new() }),
+        ("app-icon.json", "App Icon", "1024x1024 transparent icon with the safe area marked.", CanvasSpec {
+            width: 1024, height: 1024, transparent: true,
+            guides: vec![CanvasGuide { name: "Safe area".into(), x: 100.0, y: 100.0, w: 824.0, h: 824.0 }],
+        }),
+    ]
+}
+
+fn seed(dir: &Path) {
+    if std::fs::create_dir_all(dir).is_err() { return; }
+    let mut index = HashMap::new();
+    for (file, name, description, body) in BUILTIN_TEXT {
+        if std::fs::write(dir.join(file), body).is_ok() { index.insert(file.to_string(), TemplateMeta { name: name.to_string(), description: description.to_string() }); }
+    }
+    for (file, name, description, spec) in builtin_canvases() {
+        let Ok(json) = serde_json::to_string_pretty(&spec) else { continue };
+        if std::fs::write(dir.join(file), json).is_ok() { index.insert(file.to_string(), TemplateMeta { name: name.to_string(), description: description.to_string() }); }
+    }
+    let _ = save_index(dir, &index);
+}
+
+pub fn list_templates() -> Vec<Template> {
+    let dir = templates_dir();
+    if !dir.exists() { seed(&dir); }
+    let index = load_index(&dir);
+    let mut out: Vec<Template> = std::fs::read_dir(&dir).into_iter().flatten().flatten().map(|e| e.path())
+        .filter(|p| p.is_file() && p.file_name().is_some_and(|n| n != INDEX_FILE))
+        .filter(|p| { let e = ext(p); TEXT_EXTS.contains(&e.as_str()) || IMAGE_EXTS.contains(&e.as_str()) || e == "json" })
+        .map(|path| {
+            let file = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+            let meta = index.get(&file).cloned().unwrap_or_else(|| TemplateMeta { name: path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or(file), description: String::new() });
+            Template { name: meta.name, description: meta.description, path }
+        }).collect();
+    out.sort_by_key(|t| t.name.to_lowercase());
+    out
+}
+
+fn slug(name: &str) -> String {
+    let s: String = name.trim().to_lowercase().chars().map(|c| if c.is_alphanumeric() { c } else { '-' }).collect();
+    let s = s.split('-').filter(|p| !p.is_empty()).collect::<Vec<_>>().join("-");
+    if s.is_empty() { "template".to_string() } else { s }
+}
+
+pub fn save_template(name: &str, description: &str, content: &TemplateContent) -> Result<PathBuf, String> {
+    let dir = templates_dir();
+    if !dir.exists() { seed(&dir); }
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let extension = match content { TemplateContent::Text { markdown: true, .. } => "md", TemplateContent::Text { .. } => "txt", TemplateContent::Canvas(_) => "json", TemplateContent::Image(_) => "png" };
+    let base = slug(name);
+    let path = (1..).map(|i| dir.join(if i == 1 { format!("{}.{}", base, extension) } else { format!("{}-{}.{}", base, i, extension) })).find(|p| !p.exists()).unwrap_or_default();
+    match content {
+        TemplateContent::Text { text, .. } => std::fs::write(&path, text).map_err(|e| e.to_string())?,
+        TemplateContent::Canvas(spec) => std::fs::write(&path, serde_json::to_string_pretty(spec).map_err(|e| e.to_string())?).map_err(|e| e.to_string())?,
+        TemplateContent::Image(img) => img.save(&path).map_err(|e| e.to_string())?,
+    }
+    let mut index = load_index(&dir);
+    let file = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    index.insert(file, TemplateMeta { name: name.trim().to_string(), description: description.trim().to_string() });
+    save_index(&dir, &index)?;
+    Ok(path)
+}
+
+enum Thumb { Text(String), Canvas(CanvasSpec), Image(egui::TextureHandle), Broken }
+
+#[derive(Clone, Copy, PartialEq)]
+enum GalleryFilter { All, Text, Image }
+
+pub enum GalleryOutcome { Open, Closed, Create(TemplateContent), Failed(String) }
+
+pub struct TemplateGallery { templates: Vec<Template>, filter: GalleryFilter, thumbs: HashMap<PathBuf, Thumb> }
+
+impl TemplateGallery {
+    pub fn new() -> Self { Self { templates: list_templates(), filter: GalleryFilter::All, thumbs: HashMap::new() } }
+
+    fn thumb<'a>(thumbs: &'a mut HashMap<PathBuf, Thumb>, ctx: &egui::Context, t: &Template) -> &'a Thumb {
+        thumbs.entry(t.path.clone()).or_insert_with(|| match t.load() {
+            Ok(TemplateContent::Text { text, .. }) => Thumb::Text(text.lines().take(8).collect::<Vec<_>>().join("\n")),
+            Ok(TemplateContent::Canvas(spec)) => Thumb::Canvas(spec),
+            Ok(TemplateContent::Image(img)) => {
+                let small = img.thumbnail(256, 256).to_rgba8();
+                let ci = egui::ColorImage::from_rgba_unmultiplied([small.width() as usize, small.height() as usize], small.as_raw());
+                Thumb::Image(ctx.load_texture(format!("template_thumb_{}", t.path.display()), ci, egui::TextureOptions::LINEAR))
+            }
+            Err(_) => Thumb::Broken,
+        })
+    }
+
+    fn paint_thumb(painter: &egui::Painter, rect: egui::Rect, thumb: &Thumb, is_dark: bool) {
+        let page = if is_dark { ColorPalette::ZINC_900 } else { egui::Color32::WHITE };
+        let muted = if is_dark { ColorPalette::ZINC_400 } else { ColorPalette::GRAY_500 };
+        let fit = |w: f32, h: f32| { let s = (rect.width() / w).min(rect.height() / h); egui::Rect::from_center_size(rect.center(), egui::vec2(w * s, h * s)) };
+        match thumb {
+            Thumb::Text(text) => {
+                painter.rect_filled(rect, 4.0, page);
+                let galley = painter.layout(text.clone(), egui::FontId::monospace(7.5), muted, rect.width() - 12.0);
+                painter.with_clip_rect(rect.shrink(6.0)).galley(rect.min + egui::vec2(6.0, 6.0), galley, muted);
+            }
+            Thumb::Canvas(spec) => {
+                let r = fit(spec.width as f32, spec.height as f32);
+                if spec.transparent {
+                    let cell = 8.0;
+                    painter.rect_filled(r, 0.0, egui::Color32::from_gray(235));
+                    let (cols, rows) = ((r.width() / cell).ceil() as i32, (r.height() / cell).ceil() as i32);
+                    for y in 0..rows { for x in 0..cols {
+                        if (x + y) & 1 == 1 { painter.rect_filled(egui::Rect::from_min_size(r.min + egui::vec2(x as f32 * cell, y as f32 * cell), egui::vec2(cell, cell)).intersect(r), 0.0, egui::Color32::from_gray(205)); }
+                    } }
+                } else {
+                    painter.rect_filled(r, 0.0, egui::Color32::WHITE);
+                }
+                let s = r.width() / spec.width as f32;
+                for g in &spec.guides {
+                    let gr = egui::Rect::from_min_size(r.min + egui::vec2(g.x * s, g.y * s), egui::vec2(g.w * s, g.h * s));
+                    painter.rect_stroke(gr, 0.0, egui::Stroke::new(1.0, ColorPalette::TEAL_400), egui::StrokeKind::Middle);
+                }
+                painter.text(r.center(), egui::Align2::CENTER_CENTER, format!("{} x {}", spec.width, spec.height), egui::FontId::proportional(11.0), ColorPalette::ZINC_600);
+            }
+            Thumb::Image(tex) => {
+                let [w, h] = tex.size();
+                painter.image(tex.id(), fit(w as f32, h as f32), egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)), egui::Color32::WHITE);
+            }
+            Thumb::Broken => { painter.text(rect.center(), egui::Align2::CENTER_CENTER, "Unreadable", egui::FontId::proportional(11.0), ColorPalette::RED_500); }
+        }
+    }
+
+    pub fn render(&mut self, ctx: &egui::Context, theme: ThemeMode) -> GalleryOutcome {
+        let is_dark = matches!(theme, ThemeMode::Dark);
+        let (bg, border, text, muted, card) = if is_dark {
+            (ColorPalette::ZINC_900, ColorPalette::ZINC_700, ColorPalette::SLATE_200, ColorPalette::ZINC_400, ColorPalette::ZINC_800)
+        } else {
+            (egui::Color32::WHITE, ColorPalette::GRAY_200, ColorPalette::GRAY_800, ColorPalette::GRAY_500, ColorPalette::GRAY_50)
+        };
+        crate::style::draw_modal_overlay(ctx, "templates_overlay", 160);
+        let mut outcome = GalleryOutcome::Open;
+        let mut open = true;
+        let mut chosen: Option<usize> = None;
+        egui::Window::new("New from Template")
+            .collapsible(false).resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+            .fixed_size(egui::vec2(640.0, 440.0))
+            .frame(egui::Frame::new().fill(bg).stroke(egui::Stroke::new(1.0, border)).corner_radius(10.0).inner_margin(20.0))
+            .open(&mut open)
+            .order(egui::Order::Tooltip)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    for (f, label) in [(GalleryFilter::All, "All"), (GalleryFilter::Text, "Text"), (GalleryFilter::Image, "Image")] {
+                        if ui.selectable_label(self.filter == f, label).on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { self.filter = f; }
+                    }
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        if ui.button("Open Templates Folder").on_hover_cursor(egui::CursorIcon::PointingHand).clicked() {
+                            let dir = templates_dir();
+                            #[cfg(target_os = "windows")]
+                            let _ = std::process::Command::new("explorer").arg(&dir).spawn();
+                            #[cfg(target_os = "macos")]
+                            let _ = std::process::Command::new("open").arg(&dir).spawn();
+                            #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+                            let _ = std::process::Command::new("xdg-open").arg(&dir).spawn();
+                        }
+                        if ui.button("Refresh").on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { self.templates = list_templates(); self.thumbs.clear(); }
+                    });
+                });
+                ui.add_space(10.0);
+                let visible: Vec<usize> = (0..self.templates.len()).filter(|&i| match self.filter {
+                    GalleryFilter::All => true, GalleryFilter::Text => self.templates[i].is_text(), GalleryFilter::Image => !self.templates[i].is_text(),
+                }).collect();
+                if visible.is_empty() {
+                    ui.label(egui::RichText::new(format!("No templates yet. Add files to {} or use File > Save as Template.", templates_dir().display())).size(12.5).color(muted));
+                    return;
+                }
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    let (card_w, thumb_h) = (190.0, 110.0);
+                    for row in visible.chunks(3) {
+                        ui.horizontal(|ui| {
+                            for &i in row {
+                                let (rect, resp) = ui.allocate_exact_size(egui::vec2(card_w, thumb_h + 62.0), egui::Sense::click());
+                                let hovered = resp.hovered();
+                                let painter = ui.painter_at(rect);
+                                painter.rect_filled(rect, 8.0, card);
+                                painter.rect_stroke(rect, 8.0, egui::Stroke::new(1.0, if hovered { ColorPalette::BLUE_400 } else { border }), egui::StrokeKind::Inside);
+                                let thumb_rect = egui::Rect::from_min_size(rect.min + egui::vec2(8.0, 8.0), egui::vec2(card_w - 16.0, thumb_h));
+                                let t = &self.templates[i];
+                                Self::paint_thumb(&painter, thumb_rect, Self::thumb(&mut self.thumbs, ui.ctx(), t), is_dark);
+                                let name_galley = painter.layout(t.name.clone(), egui::FontId::proportional(13.0), text, card_w - 16.0);
+                                painter.galley(egui::pos2(rect.min.x + 8.0, thumb_rect.max.y + 6.0), name_galley, text);
+                                let desc_galley = painter.layout(t.description.clone(), egui::FontId::proportional(11.0), muted, card_w - 16.0);
+                                painter.with_clip_rect(rect.shrink(4.0)).galley(egui::pos2(rect.min.x + 8.0, thumb_rect.max.y + 24.0), desc_galley, muted);
+                                if resp.on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { chosen = Some(i); }
+                            }
+                        });
+                        ui.add_space(8.0);
+                    }
+                });
+            });
+        if let Some(i) = chosen {
+            outcome = match self.templates[i].load() { Ok(content) => GalleryOutcome::Create(content), Err(e) => GalleryOutcome::Failed(e) };
+        }
+        if !open || (matches!(outcome, GalleryOutcome::Open) && ctx.input(|i| i.key_pressed(egui::Key::Escape))) { outcome = GalleryOutcome::Closed; }
+        outcome
+    }
+}
+
+pub struct SaveTemplateDialog { pub name: String, pub description: String }
+
+impl SaveTemplateDialog {
+    pub fn new(name: String) -> Self { Self { name, description: String::new() } }
+
+    pub fn render(&mut self, ctx: &egui::Context, theme: ThemeMode) -> Option<bool> {
+        let (bg, border, text) = if matches!(theme, ThemeMode::Dark) {
+            (ColorPalette::ZINC_900, ColorPalette::ZINC_700, ColorPalette::SLATE_200)
+        } else {
+            (egui::Color32::WHITE, ColorPalette::GRAY_200, ColorPalette::GRAY_800)
+        };
+        let mut result = None;
+        egui::Window::new("Save as Template")
+            .collapsible(false).resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+            .frame(egui::Frame::new().fill(bg).stroke(egui::Stroke::new(1.0, border)).corner_radius(10.0).inner_margin(20.0))
+            .show(ctx, |ui| {
+                ui.label(egui::RichText::new("Name").size(12.5).color(text));
+                ui.add(egui::TextEdit::singleline(&mut self.name).desired_width(300.0));
+                ui.add_space(6.0);
+                ui.label(egui::RichText::new("Description").size(12.5).color(text));
+                ui.add(egui::TextEdit::multiline(&mut self.description).desired_width(300.0).desired_rows(2));
+                ui.add_space(10.0);
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(!self.name.trim().is_empty(), egui::Button::new("Save")).on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { result = Some(true); }
+                    if ui.button("Cancel").on_hover_cursor(egui::CursorIcon::PointingHand).clicked() { result = Some(false); }
+                });
+            });
+        if ctx.input(|i| i.key_pressed(egui::Key::Escape)) { result = Some(false); }
+        result
+    }
+}
@@ -0,0 +1,71 @@
+use eframe::egui;
+use crate::style::{ColorPalette, ThemeMode};
+
+#[derive(Clone, Copy)]
+pub struct TourStep { pub anchor: &'static str, pub title: &'static str, pub body: &'static str }
+
+pub const fn step(anchor: &'static str, title: &'static str, body: &'static str) -> TourStep { TourStep { anchor, title, body } }
+
+pub const APP_TOUR: &[TourStep] = &[
+    step("", "Welcome to Universal Editor", "This short tour points out the basics. You can replay it any time from Help > Take the Tour."),
+    step("menu_bar", "Menu bar", "File, Edit and View live here, plus extra menus for whichever editor is open."),
+    step("sidebar", "Sidebar", "Open editors, converters and recent files. Hide it from the View menu or with its shortcut."),
+    step("", "Shortcuts", "Help > Keyboard Shortcuts lists every shortcut for the current editor, and its search covers all of them."),
+];
+
+pub struct Tour { pub ids: Vec<&'static str>, pub steps: Vec<TourStep>, pub index: usize }
+
+pub enum TourOutcome { Running, Finished }
+
+pub fn anchor(ctx: &egui::Context, id: &'static str, rect: egui::Rect) {
+    ctx.data_mut(|d| d.insert_temp(egui::Id::new(("tour_anchor", id)), rect));
+}
+
+fn anchor_rect(ctx: &egui::Context, id: &'static str) -> Option<egui::Rect> {
+    if id.is_empty() { return None; }
+    ctx.data(|d| d.get_temp::<egui::Rect>(egui::Id::new(("tour_anchor", id))))
+}
+
+impl Tour {
+    pub fn new(ids: Vec<&'static str>, steps: Vec<TourStep>) -> Option<Self> { if steps.is_empty() { None } else { Some(Self { ids, steps, index: 0 }) } }
+
+    pub fn render(&mut self, ctx: &egui::Context, theme: ThemeMode) -> TourOutcome {
+        let Some(step) = self.steps.get(self.index).copied() else { return TourOutcome::Finished };
+        let is_dark = matches!(theme, ThemeMode::Dark);
+        let screen = ctx.content_rect();
+        let target = anchor_rect(ctx, step.anchor);
+        let painter = ctx.layer_painter(egui::LayerId::new(egui::Order::Foreground, egui::Id::new("tour_highlight")));
+        if let Some(r) = target { painter.rect_stroke(r.expand(3.0), 6.0, egui::Stroke::new(2.0, ColorPalette::BLUE_400), egui::StrokeKind::Outside); }
+        let width = 300.0;
+        let pos = match target {
+            Some(r) if r.bottom() + 160.0 < screen.bottom() => egui::pos2(r.left().clamp(screen.left() + 8.0, screen.right() - width - 8.0), r.bottom() + 10.0),
+            Some(r) => egui::pos2(r.left().clamp(screen.left() + 8.0, screen.right() - width - 8.0), (r.top() - 170.0).max(screen.top() + 8.0)),
+            None => screen.center() - egui::vec2(width / 2.0, 80.0),
+        };
+        let (mut next, mut back, mut skip) = (false, false, false);
+        egui::Area::new(egui::Id::new("tour_bubble")).order(egui::Order::Foreground).fixed_pos(pos).show(ctx, |ui| {
+            egui::Frame::new().fill(if is_dark { ColorPalette::ZINC_800 } else { egui::Color32::WHITE }).corner_radius(8.0)
+                .stroke(egui::Stroke::new(1.0, ColorPalette::BLUE_400)).inner_margin(14.0).shadow(ui.visuals().popup_shadow)
+                .show(ui, |ui| {
+                    ui.set_width(width - 28.0);
+                    ui.label(egui::RichText::new(format!("{} / {}", self.index + 1, self.steps.len())).size(10.0).color(ColorPalette::ZINC_500));
+                    ui.label(egui::RichText::new(step.title).size(14.0).strong());
+                    ui.add_space(4.0);
+                    ui.label(egui::RichText::new(step.body).size(12.5));
+                    ui.add_space(10.0);
+                    ui.horizontal(|ui| {
+                        if ui.small_button("Skip tour").clicked() { skip = true; }
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                            let last = self.index + 1 == self.steps.len();
+                            if ui.button(if last { "Done" } else { "Next" }).clicked() { next = true; }
+                            if self.index > 0 && ui.button("Back").clicked() { back = true; }
+                        });
+                    });
+                });
+        });
+        if ctx.input(|i| i.key_pressed(egui::Key::Escape)) { skip = true; }
+        if back { self.index -= 1; }
+        if next { self.index += 1; }
+        if skip || self.index >= self.steps.len() { TourOutcome::Finished } else { TourOutcome::Running }
+    }
+}
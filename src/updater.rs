@@ -0,0 +1,58 @@
+use eframe::egui;
+use serde::Deserialize;
+use std::sync::mpsc::{sync_channel, Receiver};
+use std::time::Duration;
+
+const RELEASES_URL: &str = "https://api.github.com/repos/Evan-Pochtar/UniversalEditor/releases/latest";
+pub const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(Deserialize)]
+struct GithubRelease { tag_name: String, #[serde(default)] name: Option<String>, #[serde(default)] body: Option<String>, html_url: String }
+
+#[derive(Clone)]
+pub struct ReleaseInfo { pub version: String, pub title: String, pub notes: String, pub url: String }
+
+pub enum UpdateStatus { UpToDate, Available(ReleaseInfo) }
+
+pub struct UpdateCheck { pub manual: bool, rx: Receiver<Result<UpdateStatus, String>> }
+
+impl UpdateCheck {
+    pub fn spawn(ctx: &egui::Context, manual: bool) -> Self {
+        let (tx, rx) = sync_channel(1);
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let _ = tx.send(fetch_latest());
+            ctx.request_repaint();
+        });
+        Self { manual, rx }
+    }
+
+    pub fn poll(&self) -> Option<Result<UpdateStatus, String>> { self.rx.try_recv().ok() }
+}
+
+fn fetch_latest() -> Result<UpdateStatus, String> {
+    let agent = ureq::AgentBuilder::new().timeout(Duration::from_secs(10)).build();
+    let release: GithubRelease = agent.get(RELEASES_URL)
+        .set("User-Agent", &format!("UniversalEditor/{}", CURRENT_VERSION))
+        .set("Accept", "application/vnd.github+json")
+        .call().map_err(|e| format!("Could not reach GitHub: {}", e))?
+        .into_json().map_err(|e| format!("Unexpected response from GitHub: {}", e))?;
+    let version = release.tag_name.trim().trim_start_matches(['v', 'V']).to_string();
+    if !is_newer(&version, CURRENT_VERSION) { return Ok(UpdateStatus::UpToDate); }
+    let title = release.name.filter(|n| !n.trim().is_empty()).unwrap_or_else(|| format!("Version {}", version));
+    Ok(UpdateStatus::Available(ReleaseInfo { version, title, notes: release.body.unwrap_or_default(), url: release.html_url }))
+}
+
+fn version_parts(v: &str) -> Vec<u64> {
+    v.trim().trim_start_matches(['v', 'V']).split(['.', '-', '+']).map_while(|p| p.parse().ok()).collect()
+}
+
+pub fn is_newer(latest: &str, current: &str) -> bool {
+    let (a, b) = (version_parts(latest), version_parts(current));
+    if a.is_empty() { return false; }
+    for i in 0..a.len().max(b.len()) {
+        let (x, y) = (a.get(i).copied().unwrap_or(0), b.get(i).copied().unwrap_or(0));
+        if x != y { return x > y; }
+    }
+    false
+}